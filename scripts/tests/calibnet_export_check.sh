@@ -33,3 +33,9 @@ $FOREST_CLI_PATH snapshot export --tipset 0
 
 echo "Testing genesis snapshot validity"
 zstd --test forest_snapshot_calibnet_2022-11-01_height_0.forest.car.zst
+
+echo "Exporting pruned snapshot"
+$FOREST_CLI_PATH snapshot export --depth 900 --output pruned.forest.car.zst
+
+echo "Testing pruned snapshot validity"
+zstd --test pruned.forest.car.zst
@@ -41,15 +41,29 @@ pub enum Error {
 struct Claims {
     #[serde(rename = "Allow")]
     allow: Vec<String>,
+    /// Name of the holder of the token, identifying its requests in logs and metrics
+    #[serde(rename = "Label", default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
     // Expiration time (as UTC timestamp)
     exp: usize,
 }
 
 /// Create a new JWT Token
 pub fn create_token(perms: Vec<String>, key: &[u8], token_exp: Duration) -> JWTResult<String> {
+    create_labeled_token(perms, None, key, token_exp)
+}
+
+/// Create a new JWT Token, optionally labeled with the name of its holder
+pub fn create_labeled_token(
+    perms: Vec<String>,
+    label: Option<String>,
+    key: &[u8],
+    token_exp: Duration,
+) -> JWTResult<String> {
     let exp_time = Utc::now() + token_exp;
     let payload = Claims {
         allow: perms,
+        label,
         exp: exp_time.timestamp() as usize,
     };
     encode(&Header::default(), &payload, &EncodingKey::from_secret(key))
@@ -57,9 +71,14 @@ pub fn create_token(perms: Vec<String>, key: &[u8], token_exp: Duration) -> JWTR
 
 /// Verify JWT Token and return the allowed permissions from token
 pub fn verify_token(token: &str, key: &[u8]) -> JWTResult<Vec<String>> {
+    verify_labeled_token(token, key).map(|(allow, _)| allow)
+}
+
+/// Verify JWT Token and return the allowed permissions and the label from token
+pub fn verify_labeled_token(token: &str, key: &[u8]) -> JWTResult<(Vec<String>, Option<String>)> {
     let validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::default());
     let token = decode::<Claims>(token, &DecodingKey::from_secret(key), &validation)?;
-    Ok(token.claims.allow)
+    Ok((token.claims.allow, token.claims.label))
 }
 
 pub fn generate_priv_key() -> KeyInfo {
@@ -111,4 +130,35 @@ mod tests {
         let perms = verify_token(&token, key.private_key()).unwrap();
         assert_eq!(perms_expected, perms);
     }
+
+    #[test]
+    fn labeled_token() {
+        let key = generate_priv_key();
+
+        let token = create_labeled_token(
+            vec!["read".to_owned()],
+            Some("Nyarlathotep".to_owned()),
+            key.private_key(),
+            Duration::hours(1),
+        )
+        .unwrap();
+        assert_eq!(
+            verify_labeled_token(&token, key.private_key()).unwrap(),
+            (vec!["read".to_owned()], Some("Nyarlathotep".to_owned()))
+        );
+        // The label of a token signed with another key isn't trusted.
+        let other_key = generate_priv_key();
+        assert!(verify_labeled_token(&token, other_key.private_key()).is_err());
+
+        let token = create_token(
+            vec!["read".to_owned()],
+            key.private_key(),
+            Duration::hours(1),
+        )
+        .unwrap();
+        assert_eq!(
+            verify_labeled_token(&token, key.private_key()).unwrap(),
+            (vec!["read".to_owned()], None)
+        );
+    }
 }
@@ -13,6 +13,7 @@ use super::{
 use crate::shim::clock::ChainEpoch;
 use crate::shim::version::NetworkVersion;
 use crate::utils::net::global_http_client;
+use crate::utils::outbound::{retry, OutboundService};
 use anyhow::Context as _;
 use async_trait::async_trait;
 use bls_signatures::Serialize as _;
@@ -349,12 +350,7 @@ impl Beacon for DrandBeacon {
                         anyhow::Ok(server.join(&format!("{}/public/{round}", self.hash))?)
                     })
                     .try_collect()?;
-                Ok(
-                    backoff::future::retry(backoff::ExponentialBackoff::default(), || async {
-                        Ok(fetch_entry(urls.iter().cloned()).await?)
-                    })
-                    .await?,
-                )
+                retry(OutboundService::Drand, || fetch_entry(urls.iter().cloned())).await
             }
         }
     }
@@ -68,7 +68,7 @@ fn signing_test() {
         let sig = Signature::new_bls(bls_sig.as_bytes());
         assert_eq!(sig, signature);
 
-        let smsg = SignedMessage::new_from_parts(unsigned, sig).unwrap();
+        let smsg = SignedMessage::new_from_parts(unsigned, sig, 0).unwrap();
         let actual_cid = smsg.cid().unwrap();
 
         assert_eq!(actual_cid, expected_cid);
@@ -1,14 +1,13 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::{fmt, sync::OnceLock};
+use std::fmt;
 
 use crate::cid_collections::FrozenCidVec;
 use crate::db::{SettingsStore, SettingsStoreExt};
-use crate::networks::{calibnet, mainnet};
+use crate::networks::{calibnet, mainnet, KNOWN_BLOCKS};
 use crate::shim::clock::ChainEpoch;
 use crate::utils::cid::CidCborExt;
-use ahash::HashMap;
 use anyhow::Context as _;
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
@@ -311,17 +310,7 @@ impl Tipset {
         // slow. Let's use a list of known blocks to short-circuit the search.
         // The blocks are hash-chained together and known blocks are guaranteed
         // to have a known genesis.
-        #[derive(Serialize, Deserialize)]
-        struct KnownHeaders {
-            calibnet: HashMap<ChainEpoch, String>,
-            mainnet: HashMap<ChainEpoch, String>,
-        }
-
-        static KNOWN_HEADERS: OnceLock<KnownHeaders> = OnceLock::new();
-        let headers = KNOWN_HEADERS.get_or_init(|| {
-            serde_yaml::from_str(include_str!("../../build/known_blocks.yaml")).unwrap()
-        });
-
+        let headers = &*KNOWN_BLOCKS;
         for tipset in self.clone().chain(&store) {
             // Search for known calibnet and mainnet blocks
             for (genesis_cid, known_blocks) in [
@@ -1,36 +1,44 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
+pub mod scheduler;
 pub mod store;
 mod weight;
 use crate::blocks::Tipset;
 use crate::cid_collections::CidHashSet;
 use crate::db::car::forest;
-use crate::ipld::stream_chain;
+use crate::ipld::{stream_chain, stream_messages};
+use crate::shim::clock::ChainEpoch;
+use crate::utils::db::car_stream::CarBlock;
 use crate::utils::io::{AsyncWriterWithChecksum, Checksum};
 use crate::utils::stream::par_buffer;
 use anyhow::Context as _;
+use cid::Cid;
 use digest::Digest;
+use futures::Stream;
 use fvm_ipld_blockstore::Blockstore;
 use std::sync::Arc;
 use tokio::io::{AsyncWrite, AsyncWriteExt, BufWriter};
 
 pub use self::{store::*, weight::*};
 
+/// Exports the chain from `tipset` to genesis, with the state of the
+/// `lookup_depth` most recent epochs. The blocks of `base`, and the graphs
+/// below them, are left out.
+#[allow(clippy::too_many_arguments)]
 pub async fn export<D: Digest>(
     db: impl Blockstore + Send + Sync + 'static,
     tipset: &Tipset,
     lookup_depth: ChainEpochDelta,
     writer: impl AsyncWrite + Unpin,
     seen: CidHashSet,
+    base: Option<Arc<dyn Blockstore + Send + Sync>>,
     skip_checksum: bool,
+    include_old_messages: bool,
 ) -> anyhow::Result<Option<digest::Output<D>>, Error> {
     let db = Arc::new(db);
     let stateroot_lookup_limit = tipset.epoch() - lookup_depth;
     let roots = tipset.key().cids.clone().into_iter().collect();
 
-    // Wrap writer in optional checksum calculator
-    let mut writer = AsyncWriterWithChecksum::<D, _>::new(BufWriter::new(writer), !skip_checksum);
-
     // Stream stateroots in range stateroot_lookup_limit..=tipset.epoch(). Also
     // stream all block headers until genesis.
     let blocks = par_buffer(
@@ -43,9 +51,45 @@ pub async fn export<D: Digest>(
             tipset.clone().chain(Arc::clone(&db)),
             stateroot_lookup_limit,
         )
-        .with_seen(seen),
+        .with_seen(seen)
+        .with_base(base)
+        .with_old_messages(include_old_messages),
     );
 
+    write_forest_car::<D>(roots, blocks, writer, skip_checksum).await
+}
+
+/// Exports the block headers and messages of the tipsets in range
+/// `from..=tipset.epoch()`, without any state. Such CARs are much smaller than
+/// snapshots and suit consumers that replay the messages themselves.
+pub async fn export_messages<D: Digest>(
+    db: impl Blockstore + Send + Sync + 'static,
+    tipset: &Tipset,
+    from: ChainEpoch,
+    writer: impl AsyncWrite + Unpin,
+    skip_checksum: bool,
+) -> anyhow::Result<Option<digest::Output<D>>, Error> {
+    let db = Arc::new(db);
+    let roots = tipset.key().cids.clone().into_iter().collect();
+
+    let tipsets = tipset
+        .clone()
+        .chain(Arc::clone(&db))
+        .take_while(move |ts| ts.epoch() >= from);
+    let blocks = par_buffer(1024, stream_messages(Arc::clone(&db), tipsets));
+
+    write_forest_car::<D>(roots, blocks, writer, skip_checksum).await
+}
+
+async fn write_forest_car<D: Digest>(
+    roots: Vec<Cid>,
+    blocks: impl Stream<Item = anyhow::Result<CarBlock>>,
+    writer: impl AsyncWrite + Unpin,
+    skip_checksum: bool,
+) -> anyhow::Result<Option<digest::Output<D>>, Error> {
+    // Wrap writer in optional checksum calculator
+    let mut writer = AsyncWriterWithChecksum::<D, _>::new(BufWriter::new(writer), !skip_checksum);
+
     // Encode Ipld key-value pairs in zstd frames
     let frames = forest::Encoder::compress_stream_default(blocks);
 
@@ -59,3 +103,123 @@ pub async fn export<D: Digest>(
 
     Ok(digest)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{CachingBlockHeader, RawBlockHeader, TipsetKey, TxMeta};
+    use crate::db::{car::ForestCar, GarbageCollectable as _, MemoryDB};
+    use crate::shim::{address::Address, message::Message};
+    use crate::utils::db::CborStoreExt as _;
+    use fil_actors_shared::fvm_ipld_amt::Amtv0 as Amt;
+    use futures::TryStreamExt as _;
+    use sha2::Sha256;
+
+    /// Stores a chain of `len` tipsets of one block including one message each, whose state
+    /// roots are stored too. Returns the head and the CIDs of the messages.
+    fn chain_with_messages(db: &MemoryDB, len: i64) -> (Tipset, Vec<Cid>) {
+        let state_root = db.put_cbor_default(&"state").unwrap();
+        let mut parents = TipsetKey::default();
+        let mut messages = vec![];
+        let mut head = None;
+        for epoch in 0..len {
+            let message = db
+                .put_cbor_default(&Message {
+                    from: Address::new_id(1000),
+                    sequence: epoch as u64,
+                    ..Default::default()
+                })
+                .unwrap();
+            let header = CachingBlockHeader::new(RawBlockHeader {
+                miner_address: Address::new_id(0),
+                epoch,
+                parents: parents.clone(),
+                state_root,
+                messages: db
+                    .put_cbor_default(&TxMeta {
+                        bls_message_root: Amt::new_from_iter(db, [message]).unwrap(),
+                        secp_message_root: Amt::new_from_iter(db, std::iter::empty::<Cid>())
+                            .unwrap(),
+                    })
+                    .unwrap(),
+                ..Default::default()
+            });
+            persist_objects(db, [&header].into_iter()).unwrap();
+            parents = TipsetKey::from_iter([*header.cid()]);
+            messages.push(message);
+            head = Some(Tipset::from(header));
+        }
+        (head.unwrap(), messages)
+    }
+
+    #[tokio::test]
+    async fn stream_messages_skips_state() {
+        let db = MemoryDB::default();
+        let (head, messages) = chain_with_messages(&db, 3);
+        let state_root = *head.parent_state();
+
+        let blocks: Vec<_> = stream_messages(&db, head.clone().chain(&db))
+            .try_collect()
+            .await
+            .unwrap();
+        let cids: CidHashSet = blocks.iter().map(|block| block.cid).collect();
+        for tipset in head.clone().chain(&db) {
+            assert!(cids.contains(tipset.min_ticket_block().cid()));
+        }
+        for message in messages.iter() {
+            assert!(cids.contains(message));
+        }
+        assert!(!cids.contains(&state_root));
+
+        // Missing messages are errors rather than silently skipped.
+        db.remove_block(&messages[1]).unwrap();
+        assert!(stream_messages(&db, head.chain(&db))
+            .try_collect::<Vec<_>>()
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn stream_chain_skips_the_base() {
+        let db = MemoryDB::default();
+        let (head, messages) = chain_with_messages(&db, 3);
+        let base = MemoryDB::default();
+        base.put_keyed(&messages[0], &db.get(&messages[0]).unwrap().unwrap())
+            .unwrap();
+
+        let blocks: Vec<_> = stream_chain(&db, head.clone().chain(&db), -1)
+            .with_base(Some(Arc::new(base)))
+            .try_collect()
+            .await
+            .unwrap();
+        let cids: CidHashSet = blocks.iter().map(|block| block.cid).collect();
+        assert!(!cids.contains(&messages[0]));
+        assert!(cids.contains(&messages[1]));
+        assert!(cids.contains(head.min_ticket_block().cid()));
+    }
+
+    #[tokio::test]
+    async fn export_messages_from_epoch() {
+        let db = Arc::new(MemoryDB::default());
+        let (head, messages) = chain_with_messages(&db, 4);
+
+        let mut car = vec![];
+        let checksum = export_messages::<Sha256>(db.clone(), &head, 2, &mut car, false)
+            .await
+            .unwrap();
+        assert!(checksum.is_some());
+
+        let car = ForestCar::new(car).unwrap();
+        assert_eq!(
+            car.roots(),
+            head.key().cids.clone().into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(car.heaviest_tipset().unwrap(), head);
+        // Only the messages of epochs 2 and 3 are exported.
+        let exported: Vec<_> = messages
+            .iter()
+            .map(|message| car.has(message).unwrap())
+            .collect();
+        assert_eq!(exported, vec![false, false, true, true]);
+    }
+}
@@ -0,0 +1,172 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Scheduler for periodic work that is aligned on chain epochs.
+//!
+//! Tasks are registered with a period, in epochs, and are triggered by head
+//! changes rather than by wall-clock timers: a task is due on the first head at
+//! or past an epoch that is a multiple of its period. Null rounds thus don't
+//! cause triggers to be missed, and tests can drive the scheduler by feeding
+//! it tipsets.
+//!
+//! Triggers are delivered on a channel with room for a single pending trigger.
+//! If a task is still busy with the previous one, the trigger is coalesced and
+//! counted as skipped in the metrics.
+
+use std::sync::Arc;
+
+use crate::blocks::Tipset;
+use crate::chain::HeadChange;
+use crate::shim::clock::ChainEpoch;
+use once_cell::sync::Lazy;
+use prometheus::core::{AtomicU64, GenericCounterVec, Opts};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::{debug, warn};
+
+static EPOCH_TASK_TRIGGERS: Lazy<Box<GenericCounterVec<AtomicU64>>> = Lazy::new(|| {
+    let epoch_task_triggers = Box::new(
+        GenericCounterVec::<AtomicU64>::new(
+            Opts::new(
+                "epoch_task_triggers",
+                "Number of times epoch-aligned tasks were triggered",
+            ),
+            &["task", "outcome"],
+        )
+        .expect("Defining the epoch_task_triggers metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(epoch_task_triggers.clone())
+        .expect(
+            "Registering the epoch_task_triggers metric with the metrics registry must succeed",
+        );
+    epoch_task_triggers
+});
+
+struct EpochTask {
+    name: &'static str,
+    period: ChainEpoch,
+    /// First epoch at which the task is due again.
+    next_due: ChainEpoch,
+    trigger: flume::Sender<Arc<Tipset>>,
+}
+
+/// Triggers registered tasks every `period` epochs, on head changes.
+#[derive(Default)]
+pub struct EpochScheduler {
+    tasks: Vec<EpochTask>,
+}
+
+impl EpochScheduler {
+    /// Registers a task that is due every `period` epochs, and returns the
+    /// channel it is triggered on, with the head that triggered it.
+    pub fn register(
+        &mut self,
+        name: &'static str,
+        period: ChainEpoch,
+    ) -> flume::Receiver<Arc<Tipset>> {
+        assert!(period > 0, "the period of {name} must be positive");
+        let (trigger, triggered) = flume::bounded(1);
+        self.tasks.push(EpochTask {
+            name,
+            period,
+            next_due: 0,
+            trigger,
+        });
+        triggered
+    }
+
+    /// Triggers the tasks that are due at the epoch of `head`.
+    pub fn on_head(&mut self, head: &Arc<Tipset>) {
+        let epoch = head.epoch();
+        for task in self.tasks.iter_mut() {
+            // After a reorganization to a lower epoch, the task is due on the
+            // next aligned epoch rather than at the one it was scheduled for.
+            if task.next_due - epoch > task.period {
+                task.next_due = next_aligned_epoch(epoch - 1, task.period);
+            }
+            if epoch < task.next_due {
+                continue;
+            }
+            task.next_due = next_aligned_epoch(epoch, task.period);
+            let outcome = match task.trigger.try_send(head.clone()) {
+                Ok(()) => "triggered",
+                Err(flume::TrySendError::Full(_)) => "skipped",
+                Err(flume::TrySendError::Disconnected(_)) => "disconnected",
+            };
+            debug!("Epoch task {} {outcome} at epoch {epoch}", task.name);
+            EPOCH_TASK_TRIGGERS
+                .with_label_values(&[task.name, outcome])
+                .inc();
+        }
+    }
+
+    /// Drives the scheduler with the head changes of `head_changes`, until the
+    /// publisher is dropped.
+    pub async fn run(mut self, mut head_changes: broadcast::Receiver<HeadChange>) {
+        loop {
+            match head_changes.recv().await {
+                Ok(HeadChange::Apply(head)) => self.on_head(&head),
+                Err(RecvError::Lagged(n)) => {
+                    warn!("Epoch scheduler lagged: skipping {n} head changes")
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    }
+}
+
+/// First epoch after `epoch` that is a multiple of `period`.
+fn next_aligned_epoch(epoch: ChainEpoch, period: ChainEpoch) -> ChainEpoch {
+    (epoch.div_euclid(period) + 1) * period
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_pool::test_provider::mock_tipset;
+
+    fn triggered_epochs(
+        scheduler: &mut EpochScheduler,
+        triggered: &flume::Receiver<Arc<Tipset>>,
+        epochs: impl IntoIterator<Item = ChainEpoch>,
+    ) -> Vec<ChainEpoch> {
+        epochs
+            .into_iter()
+            .filter_map(|epoch| {
+                scheduler.on_head(&mock_tipset(0, epoch));
+                triggered.try_recv().ok().map(|head| head.epoch())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tasks_are_triggered_on_aligned_epochs() {
+        let mut scheduler = EpochScheduler::default();
+        let triggered = scheduler.register("test", 5);
+        assert_eq!(
+            triggered_epochs(&mut scheduler, &triggered, 3..=16),
+            [3, 5, 10, 15]
+        );
+        // Null rounds don't cause triggers to be missed.
+        assert_eq!(
+            triggered_epochs(&mut scheduler, &triggered, [17, 21, 24, 26]),
+            [21, 26]
+        );
+        // Nor do reorganizations delay them past the next aligned epoch.
+        assert_eq!(
+            triggered_epochs(&mut scheduler, &triggered, [12, 14, 15]),
+            [15]
+        );
+    }
+
+    #[test]
+    fn pending_triggers_are_coalesced() {
+        let mut scheduler = EpochScheduler::default();
+        let triggered = scheduler.register("test", 1);
+        for epoch in 1..=3 {
+            scheduler.on_head(&mock_tipset(0, epoch));
+        }
+        assert_eq!(triggered.try_recv().unwrap().epoch(), 1);
+        assert!(triggered.try_recv().is_err());
+    }
+}
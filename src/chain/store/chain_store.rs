@@ -9,12 +9,9 @@ use crate::interpreter::BlockMessages;
 use crate::interpreter::VMTrace;
 use crate::libp2p_bitswap::{BitswapStoreRead, BitswapStoreReadWrite};
 use crate::message::{ChainMessage, Message as MessageTrait, SignedMessage};
-use crate::networks::ChainConfig;
+use crate::networks::{ChainConfig, ConsensusKind};
 use crate::shim::clock::ChainEpoch;
-use crate::shim::{
-    address::Address, econ::TokenAmount, executor::Receipt, message::Message,
-    state_tree::StateTree, version::NetworkVersion,
-};
+use crate::shim::{executor::Receipt, message::Message, version::NetworkVersion};
 use crate::utils::db::{BlockstoreExt, CborStoreExt};
 use ahash::{HashMap, HashMapExt, HashSet};
 use cid::Cid;
@@ -27,8 +24,15 @@ use serde::{de::DeserializeOwned, Serialize};
 use tokio::sync::broadcast::{self, Sender as Publisher};
 use tracing::{debug, info, warn};
 
+#[cfg(feature = "indexer")]
+use super::event_index;
 use super::{
+    consensus_fault::ConsensusFaultDetector,
+    fee_history::FeeHistoryIndex,
+    head_journal,
     index::{ChainIndex, ResolveNullTipset},
+    message_index::{self, MessageLocation},
+    reorg::ReorgTracker,
     tipset_tracker::TipsetTracker,
     Error,
 };
@@ -68,10 +72,22 @@ pub struct ChainStore<DB> {
     /// Tracks blocks for the purpose of forming tipsets.
     tipset_tracker: TipsetTracker<DB>,
 
+    /// Detects the consensus faults of miners in the tracked blocks.
+    consensus_faults: ConsensusFaultDetector,
+
     genesis_block_header: CachingBlockHeader,
 
     /// validated blocks
     validated_blocks: Mutex<HashSet<Cid>>,
+
+    /// Fee summaries of recent tipsets, filled in as the head advances.
+    fee_history: Arc<FeeHistoryIndex>,
+
+    /// Reorgs of the heaviest tipset.
+    reorgs: ReorgTracker,
+
+    /// Consensus the heaviest tipset is selected with.
+    consensus: ConsensusKind,
 }
 
 impl<DB> BitswapStoreRead for ChainStore<DB>
@@ -111,12 +127,13 @@ where
         let (publisher, _) = broadcast::channel(SINK_CAP);
         let chain_index = Arc::new(ChainIndex::new(Arc::clone(&db)));
 
-        if !settings
-            .read_obj::<TipsetKey>(HEAD_KEY)?
-            .is_some_and(|tipset_keys| chain_index.load_tipset(&tipset_keys).is_ok())
-        {
-            let tipset_keys = TipsetKey::from_iter([*genesis_block_header.cid()]);
-            settings.write_obj(HEAD_KEY, &tipset_keys)?;
+        // Roll back to the last consistent head if the recorded one was only
+        // partially written, and to genesis if there is none.
+        let head = head_journal::recover(settings.as_ref(), &db)?
+            .filter(|tipset_keys| chain_index.load_tipset(tipset_keys).is_ok())
+            .unwrap_or_else(|| TipsetKey::from_iter([*genesis_block_header.cid()]));
+        if settings.read_obj::<TipsetKey>(HEAD_KEY)?.as_ref() != Some(&head) {
+            settings.write_obj(HEAD_KEY, &head)?;
         }
 
         let validated_blocks = Mutex::new(HashSet::default());
@@ -124,11 +141,15 @@ where
         let cs = Self {
             publisher,
             chain_index,
+            consensus: chain_config.consensus,
+            consensus_faults: ConsensusFaultDetector::new(chain_config.policy.chain_finality),
+            reorgs: ReorgTracker::new(chain_config.policy.chain_finality),
             tipset_tracker: TipsetTracker::new(Arc::clone(&db), chain_config),
             db,
             settings,
             genesis_block_header,
             validated_blocks,
+            fee_history: Arc::default(),
         };
 
         Ok(cs)
@@ -136,17 +157,56 @@ where
 
     /// Sets heaviest tipset within `ChainStore` and store its tipset keys in
     /// the settings store under the [`crate::db::setting_keys::HEAD_KEY`] key.
+    /// Consistent heads, whose blocks were all written, are journaled first so
+    /// that the node can roll back to them after a crash.
     pub fn set_heaviest_tipset(&self, ts: Arc<Tipset>) -> Result<(), Error> {
+        if head_journal::is_consistent(&self.db, ts.key()) {
+            head_journal::record(self.settings.as_ref(), ts.key())?;
+        }
+        let old_head = self.settings.read_obj::<TipsetKey>(HEAD_KEY)?;
         self.settings.write_obj(HEAD_KEY, ts.key())?;
+        if let Some(old_head) =
+            old_head.and_then(|tsk| self.chain_index.load_tipset(&tsk).ok().flatten())
+        {
+            self.reorgs
+                .track(&self.chain_index, old_head, Arc::clone(&ts));
+        }
+        if let Err(e) = self.fee_history.index_head(&self.db, &ts) {
+            warn!("failed to index fees of tipset {}: {e}", ts.epoch());
+        }
+        #[cfg(feature = "indexer")]
+        self.index_tipset(&ts);
         if self.publisher.send(HeadChange::Apply(ts)).is_err() {
             debug!("did not publish head change, no active receivers");
         }
         Ok(())
     }
 
+    /// Indexes the messages and the events executed by `ts`.
+    #[cfg(feature = "indexer")]
+    fn index_tipset(&self, ts: &Tipset) {
+        if let Err(e) = message_index::index_tipset(self.settings.as_ref(), &self.db, ts) {
+            warn!("failed to index messages of tipset {}: {e}", ts.epoch());
+        }
+        if let Err(e) = event_index::index_tipset(self.settings.as_ref(), &self.db, ts) {
+            warn!("failed to index events of tipset {}: {e}", ts.epoch());
+        }
+    }
+
     /// Adds a block header to the tipset tracker, which tracks valid headers.
     pub fn add_to_tipset_tracker(&self, header: &CachingBlockHeader) {
         self.tipset_tracker.add(header);
+        self.consensus_faults.observe(&self.db, header);
+    }
+
+    /// Returns the detector of the consensus faults in the tracked blocks.
+    pub fn consensus_faults(&self) -> &ConsensusFaultDetector {
+        &self.consensus_faults
+    }
+
+    /// Returns the tracker of the reorgs of the heaviest tipset.
+    pub fn reorgs(&self) -> &ReorgTracker {
+        &self.reorgs
     }
 
     /// Writes tipset block headers to data store and updates heaviest tipset
@@ -191,6 +251,27 @@ where
         &self.db
     }
 
+    /// Returns the fee history index.
+    pub fn fee_history(&self) -> &Arc<FeeHistoryIndex> {
+        &self.fee_history
+    }
+
+    /// Returns the location of the receipt of an executed message from the
+    /// message index, if it is indexed. Nothing is indexed without the
+    /// `indexer` feature.
+    pub fn message_location(&self, cid: &Cid) -> anyhow::Result<Option<MessageLocation>> {
+        if cfg!(feature = "indexer") {
+            message_index::lookup(self.settings.as_ref(), cid)
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns the settings store instance.
+    pub fn settings(&self) -> &Arc<dyn SettingsStore + Sync + Send> {
+        &self.settings
+    }
+
     /// Returns Tipset from key-value store from provided CIDs
     #[tracing::instrument(skip_all)]
     pub fn load_tipset(&self, tsk: &TipsetKey) -> Result<Option<Arc<Tipset>>, Error> {
@@ -210,16 +291,40 @@ where
         self.chain_index.load_required_tipset(tsk)
     }
 
+    /// Loads the tipset of `start` and its ancestors with their messages,
+    /// newest first, stopping after `count` tipsets or at genesis. The headers
+    /// and the messages are read in a single walk of the range, see
+    /// [`unique_messages_for_tipset`]. An empty key starts at the heaviest
+    /// tipset.
+    pub fn load_tipsets_range(
+        &self,
+        start: &TipsetKey,
+        count: usize,
+    ) -> Result<Vec<(Arc<Tipset>, Vec<TipsetMessage>)>, Error> {
+        let mut tsk = if start.cids.is_empty() {
+            self.heaviest_tipset().key().clone()
+        } else {
+            start.clone()
+        };
+        let mut range = Vec::with_capacity(count);
+        while range.len() < count {
+            let tipset = self.chain_index.load_required_tipset(&tsk)?;
+            let messages = unique_messages_for_tipset(&self.db, &tipset)?;
+            let is_genesis = tipset.epoch() == 0;
+            tsk = tipset.parents().clone();
+            range.push((tipset, messages));
+            if is_genesis {
+                break;
+            }
+        }
+        Ok(range)
+    }
+
     /// Determines if provided tipset is heavier than existing known heaviest
-    /// tipset
+    /// tipset, according to the consensus of the chain
     fn update_heaviest(&self, ts: Arc<Tipset>) -> Result<(), Error> {
-        // Calculate heaviest weight before matching to avoid deadlock with mutex
-        let heaviest_weight = fil_cns::weight(self.blockstore(), &self.heaviest_tipset())?;
-
-        let new_weight = fil_cns::weight(self.blockstore(), ts.as_ref())?;
-        let curr_weight = heaviest_weight;
-
-        if new_weight > curr_weight {
+        let heaviest = self.heaviest_tipset();
+        if fil_cns::is_heavier(self.consensus, self.blockstore(), &ts, &heaviest)? {
             info!("New heaviest tipset! {} (EPOCH = {})", ts.key(), ts.epoch());
             self.set_heaviest_tipset(ts)?;
         }
@@ -415,62 +520,31 @@ where
         .ok_or_else(|| Error::UndefinedKey(key.to_string()))
 }
 
-/// Given a tipset this function will return all unique messages in that tipset.
-pub fn messages_for_tipset<DB>(db: Arc<DB>, ts: &Tipset) -> Result<Vec<ChainMessage>, Error>
-where
-    DB: Blockstore,
-{
-    let mut applied: HashMap<Address, u64> = HashMap::new();
-    let mut balances: HashMap<Address, TokenAmount> = HashMap::new();
-    let state = StateTree::new_from_root(Arc::clone(&db), ts.parent_state())?;
-
-    // message to get all messages for block_header into a single iterator
-    let mut get_message_for_block_header =
-        |b: &CachingBlockHeader| -> Result<Vec<ChainMessage>, Error> {
-            let (unsigned, signed) = block_messages(&db, b)?;
-            let mut messages = Vec::with_capacity(unsigned.len() + signed.len());
-            let unsigned_box = unsigned.into_iter().map(ChainMessage::Unsigned);
-            let signed_box = signed.into_iter().map(ChainMessage::Signed);
-
-            for message in unsigned_box.chain(signed_box) {
-                let from_address = &message.from();
-                if applied.contains_key(from_address) {
-                    let actor_state = state
-                        .get_actor(from_address)?
-                        .ok_or_else(|| Error::Other("Actor state not found".to_string()))?;
-                    applied.insert(*from_address, actor_state.sequence);
-                    balances.insert(*from_address, actor_state.balance.clone().into());
-                }
-                if let Some(seq) = applied.get_mut(from_address) {
-                    if *seq != message.sequence() {
-                        continue;
-                    }
-                    *seq += 1;
-                } else {
-                    continue;
-                }
-                if let Some(bal) = balances.get_mut(from_address) {
-                    if *bal < message.required_funds() {
-                        continue;
-                    }
-                    *bal -= message.required_funds();
-                } else {
-                    continue;
-                }
-
-                messages.push(message)
-            }
-
-            Ok(messages)
-        };
+/// A message of a tipset, with the number of blocks of the tipset including it.
+#[derive(Debug, Clone)]
+pub struct TipsetMessage {
+    pub message: ChainMessage,
+    pub inclusions: usize,
+}
 
-    ts.block_headers()
-        .iter()
-        .try_fold(Vec::new(), |mut message_vec, b| {
-            let mut messages = get_message_for_block_header(b)?;
-            message_vec.append(&mut messages);
-            Ok(message_vec)
+/// Returns the messages of a tipset that are passed to the VM, as
+/// [`BlockMessages::for_tipset`], each once however many blocks include it.
+pub fn unique_messages_for_tipset(
+    db: &impl Blockstore,
+    ts: &Tipset,
+) -> Result<Vec<TipsetMessage>, Error> {
+    let mut inclusions = HashMap::new();
+    BlockMessages::for_tipset_counting(db, ts, Some(&mut inclusions))?
+        .into_iter()
+        .flat_map(|bm| bm.messages)
+        .map(|message| -> Result<_, Error> {
+            let inclusions = inclusions.get(&message.cid()?).copied().unwrap_or(1);
+            Ok(TipsetMessage {
+                message,
+                inclusions,
+            })
         })
+        .collect()
 }
 
 /// Returns messages from key-value store based on a slice of [`Cid`]s.
@@ -572,4 +646,99 @@ mod tests {
         cs.mark_block_as_validated(&cid);
         assert!(cs.is_block_validated(&cid));
     }
+
+    #[test]
+    fn load_tipsets_range_with_messages() {
+        let db = Arc::new(crate::db::MemoryDB::default());
+        let message = Message {
+            from: Address::new_id(1000),
+            ..Default::default()
+        };
+        db.put_cbor_default(&message).unwrap();
+        let tx_meta = |messages: &[&Message]| {
+            let bls_message_root =
+                Amt::new_from_iter(&db, messages.iter().map(|m| m.cid().unwrap())).unwrap();
+            let secp_message_root = Amt::new_from_iter(&db, std::iter::empty::<Cid>()).unwrap();
+            db.put_cbor_default(&TxMeta {
+                bls_message_root,
+                secp_message_root,
+            })
+            .unwrap()
+        };
+        let (empty, with_message) = (tx_meta(&[]), tx_meta(&[&message]));
+        let mut parents = TipsetKey::default();
+        let headers = (0..4)
+            .map(|epoch| {
+                let header = CachingBlockHeader::new(RawBlockHeader {
+                    miner_address: Address::new_id(0),
+                    epoch,
+                    parents: parents.clone(),
+                    messages: if epoch == 2 { with_message } else { empty },
+                    ..Default::default()
+                });
+                db.put_cbor_default(&*header).unwrap();
+                parents = TipsetKey::from_iter([*header.cid()]);
+                header
+            })
+            .collect::<Vec<_>>();
+        let cs = ChainStore::new(
+            db.clone(),
+            db,
+            Arc::new(ChainConfig::default()),
+            headers[0].clone(),
+        )
+        .unwrap();
+
+        let range = cs.load_tipsets_range(&parents, 2).unwrap();
+        let summary = range
+            .iter()
+            .map(|(ts, messages)| (ts.epoch(), messages.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(summary, vec![(3, 0), (2, 1)]);
+        assert_eq!(range[1].1[0].message.cid().unwrap(), message.cid().unwrap());
+
+        // The range stops at genesis.
+        assert_eq!(cs.load_tipsets_range(&parents, 10).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn unique_messages_count_inclusions() {
+        let db = crate::db::MemoryDB::default();
+        let message = |sequence| {
+            let message = Message {
+                from: Address::new_id(1000),
+                sequence,
+                ..Default::default()
+            };
+            db.put_cbor_default(&message).unwrap();
+            message
+        };
+        let (m0, m1) = (message(0), message(1));
+        let header = |miner, messages: &[&Message]| {
+            let bls_message_root =
+                Amt::new_from_iter(&db, messages.iter().map(|m| m.cid().unwrap())).unwrap();
+            let secp_message_root = Amt::new_from_iter(&db, std::iter::empty::<Cid>()).unwrap();
+            CachingBlockHeader::new(RawBlockHeader {
+                miner_address: Address::new_id(miner),
+                messages: db
+                    .put_cbor_default(&TxMeta {
+                        bls_message_root,
+                        secp_message_root,
+                    })
+                    .unwrap(),
+                ..Default::default()
+            })
+        };
+        let ts = Tipset::new([header(1, &[&m0, &m1]), header(2, &[&m0])]).unwrap();
+
+        let messages = unique_messages_for_tipset(&db, &ts).unwrap();
+        let summary = messages
+            .iter()
+            .map(|m| (m.message.cid().unwrap(), m.inclusions))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![(m0.cid().unwrap(), 2), (m1.cid().unwrap(), 1)]
+        );
+    }
 }
@@ -0,0 +1,353 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Detection of the consensus faults of miners in the validated blocks, see
+//! <https://spec.filecoin.io/#section-algorithms.expected_consensus.consensus-faults>.
+//! Detected faults can be reported with the `ReportConsensusFault` method of
+//! the miner actor, which expects the headers of the faulty blocks.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::blocks::{CachingBlockHeader, TipsetKey};
+use crate::lotus_json::lotus_json_with_self;
+use crate::shim::{address::Address, clock::ChainEpoch};
+use ahash::HashMap;
+use fvm_ipld_blockstore::Blockstore;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::{self, Sender as Publisher};
+use tracing::warn;
+
+/// Number of detected faults that are kept.
+const FAULTS_CAP: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConsensusFaultKind {
+    /// Two blocks mined by the same miner at the same epoch.
+    DoubleForkMining,
+    /// Two blocks mined by the same miner on the same parents, at different
+    /// epochs.
+    TimeOffsetMining,
+    /// A block mined by a miner on parents omitting the block the miner mined
+    /// at the parent epoch, as shown by a witness block of the parents.
+    ParentGrinding,
+}
+
+/// A consensus fault, with the block headers proving it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ConsensusFault {
+    pub kind: ConsensusFaultKind,
+    #[serde(with = "crate::lotus_json")]
+    pub miner: Address,
+    pub epoch: ChainEpoch,
+    #[serde(with = "crate::lotus_json")]
+    pub block_header_1: CachingBlockHeader,
+    #[serde(with = "crate::lotus_json")]
+    pub block_header_2: CachingBlockHeader,
+    #[serde(with = "crate::lotus_json")]
+    pub block_header_extra: Option<CachingBlockHeader>,
+}
+
+lotus_json_with_self!(ConsensusFault);
+
+impl ConsensusFault {
+    fn new(
+        kind: ConsensusFaultKind,
+        block_header_1: CachingBlockHeader,
+        block_header_2: CachingBlockHeader,
+        block_header_extra: Option<CachingBlockHeader>,
+    ) -> Self {
+        Self {
+            kind,
+            miner: block_header_2.miner_address,
+            epoch: block_header_2.epoch,
+            block_header_1,
+            block_header_2,
+            block_header_extra,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Observed {
+    /// Blocks by epoch and miner.
+    by_epoch: BTreeMap<ChainEpoch, HashMap<Address, CachingBlockHeader>>,
+    /// Blocks by miner and parents.
+    by_parents: HashMap<(Address, TipsetKey), CachingBlockHeader>,
+}
+
+/// Keeps the validated blocks of the last `window` epochs to detect the
+/// consensus faults of their miners.
+pub struct ConsensusFaultDetector {
+    window: ChainEpoch,
+    observed: Mutex<Observed>,
+    faults: Mutex<VecDeque<ConsensusFault>>,
+    publisher: Publisher<ConsensusFault>,
+}
+
+impl ConsensusFaultDetector {
+    pub fn new(window: ChainEpoch) -> Self {
+        let (publisher, _) = broadcast::channel(FAULTS_CAP);
+        Self {
+            window,
+            observed: Default::default(),
+            faults: Default::default(),
+            publisher,
+        }
+    }
+
+    /// Returns the detected faults, oldest first.
+    pub fn faults(&self) -> Vec<ConsensusFault> {
+        self.faults.lock().iter().cloned().collect()
+    }
+
+    /// Subscribes to the faults detected from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<ConsensusFault> {
+        self.publisher.subscribe()
+    }
+
+    /// Checks a validated block against the blocks observed before it. The
+    /// parents of the block are read from `db`.
+    pub fn observe(&self, db: &impl Blockstore, header: &CachingBlockHeader) {
+        let fault = match self.detect(db, header) {
+            Ok(fault) => fault,
+            Err(e) => {
+                warn!(
+                    "Failed to check block {} for consensus faults: {e}",
+                    header.cid()
+                );
+                None
+            }
+        };
+        if let Some(fault) = fault {
+            warn!(
+                "Detected {:?} by miner {} at epoch {}: {} {}",
+                fault.kind,
+                fault.miner,
+                fault.epoch,
+                fault.block_header_1.cid(),
+                fault.block_header_2.cid()
+            );
+            let mut faults = self.faults.lock();
+            if faults.len() == FAULTS_CAP {
+                faults.pop_front();
+            }
+            faults.push_back(fault.clone());
+            // Nobody may be reporting the faults.
+            let _ = self.publisher.send(fault);
+        }
+    }
+
+    fn detect(
+        &self,
+        db: &impl Blockstore,
+        header: &CachingBlockHeader,
+    ) -> anyhow::Result<Option<ConsensusFault>> {
+        // All the parents are at the same epoch.
+        let parent_epoch = match header.parents.cids.clone().into_iter().next() {
+            Some(parent) => CachingBlockHeader::load(db, parent)?.map(|parent| parent.epoch),
+            None => None,
+        };
+
+        let mut observed = self.observed.lock();
+        let miner = header.miner_address;
+
+        let same_epoch = observed
+            .by_epoch
+            .get(&header.epoch)
+            .and_then(|blocks| blocks.get(&miner));
+        if let Some(other) = same_epoch {
+            if other.cid() == header.cid() {
+                return Ok(None);
+            }
+            return Ok(Some(ConsensusFault::new(
+                ConsensusFaultKind::DoubleForkMining,
+                other.clone(),
+                header.clone(),
+                None,
+            )));
+        }
+
+        let mut detected = None;
+        if let Some(other) = observed.by_parents.get(&(miner, header.parents.clone())) {
+            let (earlier, later) = if other.epoch < header.epoch {
+                (other.clone(), header.clone())
+            } else {
+                (header.clone(), other.clone())
+            };
+            detected = Some(ConsensusFault::new(
+                ConsensusFaultKind::TimeOffsetMining,
+                earlier,
+                later,
+                None,
+            ));
+        }
+
+        let own_parent = parent_epoch
+            .and_then(|epoch| observed.by_epoch.get(&epoch))
+            .and_then(|blocks| blocks.get(&miner));
+        if let Some(own_parent) = own_parent.filter(|_| detected.is_none()) {
+            if !header.parents.cids.contains(*own_parent.cid()) {
+                // A block of the parents mined on the same parents as the
+                // omitted block proves the omission.
+                for cid in header.parents.cids.clone() {
+                    if let Some(witness) = CachingBlockHeader::load(db, cid)? {
+                        if witness.epoch == own_parent.epoch
+                            && witness.parents == own_parent.parents
+                        {
+                            detected = Some(ConsensusFault::new(
+                                ConsensusFaultKind::ParentGrinding,
+                                own_parent.clone(),
+                                header.clone(),
+                                Some(witness),
+                            ));
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        observed
+            .by_epoch
+            .entry(header.epoch)
+            .or_default()
+            .insert(miner, header.clone());
+        observed
+            .by_parents
+            .insert((miner, header.parents.clone()), header.clone());
+        self.prune(&mut observed, header.epoch);
+        Ok(detected)
+    }
+
+    /// Forgets the blocks more than `window` epochs older than `epoch`.
+    fn prune(&self, observed: &mut Observed, epoch: ChainEpoch) {
+        let cut_off_epoch = epoch - self.window;
+        if observed
+            .by_epoch
+            .first_key_value()
+            .is_some_and(|(oldest, _)| *oldest < cut_off_epoch)
+        {
+            observed.by_epoch = observed.by_epoch.split_off(&cut_off_epoch);
+            observed
+                .by_parents
+                .retain(|_, header| header.epoch >= cut_off_epoch);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::RawBlockHeader;
+    use crate::db::MemoryDB;
+    use crate::utils::db::CborStoreExt;
+
+    fn block(
+        db: &MemoryDB,
+        miner: u64,
+        epoch: ChainEpoch,
+        parents: &[&CachingBlockHeader],
+    ) -> CachingBlockHeader {
+        let header = CachingBlockHeader::new(RawBlockHeader {
+            miner_address: Address::new_id(miner),
+            epoch,
+            parents: TipsetKey::from_iter(parents.iter().map(|parent| *parent.cid())),
+            ..Default::default()
+        });
+        db.put_cbor_default(&*header).unwrap();
+        header
+    }
+
+    #[test]
+    fn detect_double_fork_mining() {
+        let db = MemoryDB::default();
+        let detector = ConsensusFaultDetector::new(900);
+        let genesis = block(&db, 0, 0, &[]);
+        let a = block(&db, 1000, 1, &[&genesis]);
+        let b = block(&db, 1000, 1, &[]);
+        detector.observe(&db, &a);
+        detector.observe(&db, &a);
+        assert!(detector.faults().is_empty());
+        detector.observe(&db, &b);
+        let faults = detector.faults();
+        assert_eq!(faults.len(), 1);
+        assert_eq!(faults[0].kind, ConsensusFaultKind::DoubleForkMining);
+        assert_eq!(faults[0].block_header_1, a);
+        assert_eq!(faults[0].block_header_2, b);
+    }
+
+    #[test]
+    fn detect_time_offset_mining() {
+        let db = MemoryDB::default();
+        let detector = ConsensusFaultDetector::new(900);
+        let genesis = block(&db, 0, 0, &[]);
+        let a = block(&db, 1000, 1, &[&genesis]);
+        let b = block(&db, 1000, 2, &[&genesis]);
+        detector.observe(&db, &b);
+        detector.observe(&db, &a);
+        let faults = detector.faults();
+        assert_eq!(faults.len(), 1);
+        assert_eq!(faults[0].kind, ConsensusFaultKind::TimeOffsetMining);
+        // The earlier block comes first.
+        assert_eq!(faults[0].block_header_1, a);
+        assert_eq!(faults[0].block_header_2, b);
+        assert_eq!(faults[0].epoch, 2);
+    }
+
+    #[test]
+    fn detect_parent_grinding() {
+        let db = MemoryDB::default();
+        let detector = ConsensusFaultDetector::new(900);
+        let genesis = block(&db, 0, 0, &[]);
+        let own = block(&db, 1000, 1, &[&genesis]);
+        let witness = block(&db, 1001, 1, &[&genesis]);
+        let grinding = block(&db, 1000, 2, &[&witness]);
+        let honest = block(&db, 1000, 2, &[&own, &witness]);
+        let mut subscriber = detector.subscribe();
+        detector.observe(&db, &genesis);
+        detector.observe(&db, &own);
+        detector.observe(&db, &witness);
+        detector.observe(&db, &grinding);
+        let fault = subscriber.try_recv().unwrap();
+        assert_eq!(fault.kind, ConsensusFaultKind::ParentGrinding);
+        assert_eq!(fault.block_header_1, own);
+        assert_eq!(fault.block_header_2, grinding);
+        assert_eq!(fault.block_header_extra, Some(witness));
+
+        let detector = ConsensusFaultDetector::new(900);
+        for header in [&genesis, &own, &honest] {
+            detector.observe(&db, header);
+        }
+        assert!(detector.faults().is_empty());
+    }
+
+    #[test]
+    fn parent_grinding_witness_is_at_the_omitted_epoch() {
+        let db = MemoryDB::default();
+        let detector = ConsensusFaultDetector::new(900);
+        let genesis = block(&db, 0, 0, &[]);
+        let own = block(&db, 1000, 1, &[&genesis]);
+        let other = block(&db, 1001, 1, &[]);
+        // Mined on the same parents as the omitted block, but at a later
+        // epoch.
+        let late = block(&db, 1002, 2, &[&genesis]);
+        let child = block(&db, 1000, 3, &[&other, &late]);
+        for header in [&genesis, &own, &other, &late, &child] {
+            detector.observe(&db, header);
+        }
+        assert!(detector.faults().is_empty());
+    }
+
+    #[test]
+    fn old_blocks_are_forgotten() {
+        let db = MemoryDB::default();
+        let detector = ConsensusFaultDetector::new(10);
+        let genesis = block(&db, 0, 0, &[]);
+        detector.observe(&db, &block(&db, 1000, 1, &[&genesis]));
+        detector.observe(&db, &block(&db, 1001, 20, &[&genesis]));
+        detector.observe(&db, &block(&db, 1000, 1, &[]));
+        assert!(detector.faults().is_empty());
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Persistent index of the events emitted by actors, by epoch of the tipset
+//! including the messages that emitted them.
+//!
+//! The events a receipt refers to are stored as the messages are executed.
+//! When a tipset becomes the head, the events of the messages of its parent,
+//! which it executed, are looked up from its receipts and indexed. A reorg
+//! indexes the events of the new tipsets over those of the reverted ones.
+
+use std::sync::Arc;
+
+use crate::blocks::{Tipset, TipsetKey};
+use crate::chain::index::ChainIndex;
+use crate::db::{setting_keys::EVENT_INDEX_KEY_PREFIX, SettingsStore, SettingsStoreExt};
+use crate::interpreter::BlockMessages;
+use crate::lotus_json::lotus_json_with_self;
+use crate::shim::{
+    address::Address,
+    clock::ChainEpoch,
+    executor::{load_events, Receipt},
+    state_tree::StateTree,
+};
+use ahash::{HashMap, HashMapExt as _};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use serde::{Deserialize, Serialize};
+
+/// Key-value pair of an event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct EventEntry {
+    /// Indexing flags of the entry.
+    pub flags: u64,
+    pub key: String,
+    /// IPLD codec of the value.
+    pub codec: u64,
+    #[serde(with = "crate::lotus_json")]
+    pub value: Vec<u8>,
+}
+
+/// Event emitted by an actor while executing a message.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActorEvent {
+    pub entries: Vec<EventEntry>,
+    /// Address of the actor, its delegated address if it has one.
+    #[serde(with = "crate::lotus_json")]
+    pub emitter: Address,
+    /// Whether the tipset including the message was reverted.
+    pub reverted: bool,
+    /// Epoch of the tipset including the message.
+    pub height: ChainEpoch,
+    /// Key of the tipset including the message.
+    #[serde(with = "crate::lotus_json")]
+    pub tipset_key: TipsetKey,
+    #[serde(with = "crate::lotus_json")]
+    pub msg_cid: Cid,
+}
+
+lotus_json_with_self!(EventEntry, ActorEvent);
+
+fn events_key(epoch: ChainEpoch) -> String {
+    format!("{EVENT_INDEX_KEY_PREFIX}{epoch}")
+}
+
+/// Indexes the events emitted while executing the messages of the parent of
+/// `tipset`, from the receipts of `tipset`. Returns the number of indexed
+/// events.
+pub fn index_tipset<DB: Blockstore>(
+    settings: &dyn SettingsStore,
+    db: &Arc<DB>,
+    tipset: &Tipset,
+) -> anyhow::Result<usize> {
+    if tipset.epoch() == 0 {
+        return Ok(0);
+    }
+    let receipts = Receipt::get_receipts(db, &tipset.block_headers().first().message_receipts)?;
+    let parent = ChainIndex::new(db).load_required_tipset(tipset.parents())?;
+    let key = events_key(parent.epoch());
+    // Events of a reverted tipset at the same epoch are replaced.
+    if receipts
+        .iter()
+        .all(|receipt| receipt.events_root().is_none())
+        && !settings.exists(&key)?
+    {
+        return Ok(0);
+    }
+
+    let messages = BlockMessages::for_tipset(db, &parent)?
+        .into_iter()
+        .flat_map(|bm| bm.messages);
+    let state = StateTree::new_from_root(Arc::clone(db), tipset.parent_state())?;
+    let mut emitters = HashMap::new();
+    let mut events = Vec::new();
+    for (message, receipt) in messages.zip(receipts) {
+        let Some(events_root) = receipt.events_root() else {
+            continue;
+        };
+        let msg_cid = message.cid()?;
+        for stamped in load_events(db, &events_root)? {
+            let emitter = match emitters.get(&stamped.emitter) {
+                Some(emitter) => *emitter,
+                None => {
+                    let id = Address::new_id(stamped.emitter);
+                    let emitter = state
+                        .get_actor(&id)?
+                        .and_then(|actor| actor.delegated_address)
+                        .map(Address::from)
+                        .unwrap_or(id);
+                    emitters.insert(stamped.emitter, emitter);
+                    emitter
+                }
+            };
+            events.push(ActorEvent {
+                entries: stamped
+                    .event
+                    .entries
+                    .into_iter()
+                    .map(|entry| EventEntry {
+                        flags: entry.flags.bits(),
+                        key: entry.key,
+                        codec: entry.codec,
+                        value: entry.value,
+                    })
+                    .collect(),
+                emitter,
+                reverted: false,
+                height: parent.epoch(),
+                tipset_key: parent.key().clone(),
+                msg_cid,
+            });
+        }
+    }
+    settings.write_obj(&key, &events)?;
+    Ok(events.len())
+}
+
+/// Returns the indexed events of the tipset at `epoch`. After a reorg, they
+/// might belong to a tipset that is no longer on the current chain.
+pub fn events_at(
+    settings: &dyn SettingsStore,
+    epoch: ChainEpoch,
+) -> anyhow::Result<Vec<ActorEvent>> {
+    Ok(settings
+        .read_obj::<Vec<ActorEvent>>(&events_key(epoch))?
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+
+    #[test]
+    fn events_roundtrip() {
+        let settings = MemoryDB::default();
+        assert_eq!(events_at(&settings, 42).unwrap(), vec![]);
+
+        let events = vec![ActorEvent {
+            entries: vec![EventEntry {
+                flags: 3,
+                key: "t1".into(),
+                codec: 0x55,
+                value: b"value".to_vec(),
+            }],
+            emitter: Address::new_id(1000),
+            reverted: false,
+            height: 42,
+            tipset_key: TipsetKey::from_iter([Cid::default()]),
+            msg_cid: Cid::default(),
+        }];
+        settings.write_obj(&events_key(42), &events).unwrap();
+        assert_eq!(events_at(&settings, 42).unwrap(), events);
+    }
+}
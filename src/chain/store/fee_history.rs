@@ -0,0 +1,308 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Compact per-epoch index of the fees paid on chain. Every indexed tipset is
+//! summarized by its base fee, the gas reserved by its messages, and a ladder
+//! of effective gas premium percentiles, so that fee statistics over a range
+//! of epochs don't require loading every message again.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::blocks::{Tipset, TipsetKey};
+use crate::message::{ChainMessage, Message as MessageTrait};
+use crate::shim::{
+    clock::ChainEpoch,
+    econ::{TokenAmount, BLOCK_GAS_LIMIT},
+};
+use fvm_ipld_blockstore::Blockstore;
+use num_traits::Zero as _;
+use parking_lot::{Mutex, RwLock};
+use tokio::sync::Notify;
+use tracing::warn;
+
+use super::{unique_messages_for_tipset, Error};
+
+/// Distance, in percent, between two consecutive premium percentiles of the
+/// ladder.
+pub const PERCENTILE_STEP: usize = 5;
+
+const LADDER_LEN: usize = 100 / PERCENTILE_STEP + 1;
+
+/// Number of most recent epochs kept in the index, a bit over two days.
+const MAX_INDEXED_EPOCHS: usize = 6000;
+
+/// Premium reported for tipsets without messages, as in Lotus.
+const MIN_GAS_PREMIUM: u64 = 100_000;
+
+/// Fee summary of a single tipset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EpochFees {
+    pub epoch: ChainEpoch,
+    pub key: TipsetKey,
+    /// Base fee paid by the messages of the tipset.
+    pub base_fee: TokenAmount,
+    /// Sum of the gas limits of the messages of the tipset. Receipts are only
+    /// known once the tipset is executed, so this stands in for gas used.
+    pub gas_limit: u64,
+    /// Gas-weighted effective premiums, at every [`PERCENTILE_STEP`] percent
+    /// from 0 to 100.
+    premiums: Vec<TokenAmount>,
+}
+
+impl EpochFees {
+    pub fn new(ts: &Tipset, msgs: &[ChainMessage]) -> Self {
+        let base_fee = ts.block_headers().first().parent_base_fee.clone();
+        let rewards = msgs
+            .iter()
+            .map(|msg| {
+                // Miners only get what is left of the fee cap after burning
+                // the base fee.
+                let premium = (msg.gas_fee_cap() - &base_fee).min(msg.gas_premium());
+                (premium.max(TokenAmount::zero()), msg.gas_limit())
+            })
+            .collect();
+        EpochFees {
+            epoch: ts.epoch(),
+            key: ts.key().clone(),
+            base_fee,
+            gas_limit: msgs.iter().map(|msg| msg.gas_limit()).sum(),
+            premiums: premium_ladder(rewards),
+        }
+    }
+
+    /// Ratio of the gas reserved by the messages of the tipset to the block
+    /// gas limit.
+    pub fn gas_used_ratio(&self) -> f64 {
+        self.gas_limit as f64 / BLOCK_GAS_LIMIT as f64
+    }
+
+    /// Returns the effective premium at `percentile`, rounded up to the next
+    /// step of the ladder.
+    pub fn premium_at(&self, percentile: f64) -> &TokenAmount {
+        let step = (percentile.clamp(0.0, 100.0) / PERCENTILE_STEP as f64).ceil() as usize;
+        &self.premiums[step.min(LADDER_LEN - 1)]
+    }
+}
+
+/// Computes the gas-weighted premium percentiles of `(premium, gas)` pairs,
+/// following the reward computation of Lotus' `eth_feeHistory`.
+fn premium_ladder(mut rewards: Vec<(TokenAmount, u64)>) -> Vec<TokenAmount> {
+    if rewards.is_empty() {
+        return vec![TokenAmount::from_atto(MIN_GAS_PREMIUM); LADDER_LEN];
+    }
+    rewards.sort_by(|a, b| a.0.cmp(&b.0));
+    let total_gas: u128 = rewards.iter().map(|(_, gas)| *gas as u128).sum();
+    let mut sum = 0;
+    let mut idx = 0;
+    (0..LADDER_LEN)
+        .map(|step| {
+            let threshold = total_gas * (step * PERCENTILE_STEP) as u128 / 100;
+            while sum < threshold && idx < rewards.len() - 1 {
+                sum += rewards[idx].1 as u128;
+                idx += 1;
+            }
+            rewards[idx].0.clone()
+        })
+        .collect()
+}
+
+/// Bounded index of [`EpochFees`], keyed by epoch. Entries are checked against
+/// the tipset key on lookup, so re-organizations are re-indexed transparently.
+#[derive(Default)]
+pub struct FeeHistoryIndex {
+    entries: RwLock<BTreeMap<ChainEpoch, Arc<EpochFees>>>,
+    /// Most recent tipset whose ancestors are left to index, see
+    /// [`FeeHistoryIndex::backfill_loop`].
+    backfill_from: Mutex<Option<TipsetKey>>,
+    backfill_requested: Notify,
+}
+
+impl FeeHistoryIndex {
+    /// Summarizes the fees of `ts` and adds them to the index, evicting the
+    /// oldest epochs if needed.
+    pub fn index(&self, db: impl Blockstore, ts: &Tipset) -> Result<Arc<EpochFees>, Error> {
+        let msgs = unique_messages_for_tipset(&db, ts)?
+            .into_iter()
+            .map(|m| m.message)
+            .collect::<Vec<_>>();
+        let fees = Arc::new(EpochFees::new(ts, &msgs));
+
+        let mut entries = self.entries.write();
+        entries.insert(ts.epoch(), fees.clone());
+        while entries.len() > MAX_INDEXED_EPOCHS {
+            entries.pop_first();
+        }
+        Ok(fees)
+    }
+
+    /// Indexes `head`. If its parent isn't the most recent indexed tipset,
+    /// because the head jumped several epochs ahead or was re-organized, the
+    /// skipped ancestors are left to [`FeeHistoryIndex::backfill_loop`] so
+    /// that head changes aren't held up by loading them.
+    pub fn index_head(&self, db: impl Blockstore, head: &Tipset) -> Result<(), Error> {
+        let parent_indexed = {
+            let entries = self.entries.read();
+            match entries.range(..head.epoch()).next_back() {
+                Some((_, fees)) => &fees.key == head.parents(),
+                None => true,
+            }
+        };
+        self.index(&db, head)?;
+        if !parent_indexed {
+            *self.backfill_from.lock() = Some(head.parents().clone());
+            self.backfill_requested.notify_one();
+        }
+        Ok(())
+    }
+
+    /// Indexes the ancestors of the last tipset passed to
+    /// [`FeeHistoryIndex::index_head`] down to the most recent indexed
+    /// tipset, if any are left to index.
+    pub fn backfill(&self, db: impl Blockstore) -> Result<(), Error> {
+        let Some(key) = self.backfill_from.lock().take() else {
+            return Ok(());
+        };
+        let lowest = self
+            .entries
+            .read()
+            .first_key_value()
+            .map(|(epoch, _)| *epoch);
+        let Some(lowest) = lowest else {
+            return Ok(());
+        };
+        let mut ts = Tipset::load_required(&db, &key)?;
+        for _ in 0..MAX_INDEXED_EPOCHS {
+            if ts.epoch() < lowest || self.is_indexed(&ts) {
+                break;
+            }
+            self.index(&db, &ts)?;
+            if ts.epoch() == 0 {
+                break;
+            }
+            ts = Tipset::load_required(&db, ts.parents())?;
+        }
+        Ok(())
+    }
+
+    /// Backfills the index on the blocking thread pool whenever
+    /// [`FeeHistoryIndex::index_head`] skips epochs.
+    pub async fn backfill_loop<DB>(self: Arc<Self>, db: Arc<DB>) -> anyhow::Result<()>
+    where
+        DB: Blockstore + Send + Sync + 'static,
+    {
+        loop {
+            self.backfill_requested.notified().await;
+            let index = Arc::clone(&self);
+            let db = Arc::clone(&db);
+            if let Err(e) = tokio::task::spawn_blocking(move || index.backfill(&db)).await? {
+                warn!("failed to backfill the fee history index: {e}");
+            }
+        }
+    }
+
+    fn is_indexed(&self, ts: &Tipset) -> bool {
+        self.entries
+            .read()
+            .get(&ts.epoch())
+            .is_some_and(|fees| &fees.key == ts.key())
+    }
+
+    /// Returns the indexed fees of `ts`, indexing it first if needed.
+    pub fn get_or_index(&self, db: impl Blockstore, ts: &Tipset) -> Result<Arc<EpochFees>, Error> {
+        let cached = self
+            .entries
+            .read()
+            .get(&ts.epoch())
+            .filter(|fees| &fees.key == ts.key())
+            .cloned();
+        match cached {
+            Some(fees) => Ok(fees),
+            None => self.index(db, ts),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{CachingBlockHeader, RawBlockHeader, TxMeta};
+    use crate::chain::persist_objects;
+    use crate::db::MemoryDB;
+    use crate::shim::address::Address;
+    use crate::utils::db::CborStoreExt as _;
+    use cid::Cid;
+    use fil_actors_shared::fvm_ipld_amt::Amtv0 as Amt;
+
+    /// Stores a chain of `len` tipsets of one block without messages and
+    /// returns them, from genesis to head.
+    fn chain(db: &MemoryDB, len: i64) -> Vec<Tipset> {
+        let empty = Amt::new_from_iter(db, std::iter::empty::<Cid>()).unwrap();
+        let messages = db
+            .put_cbor_default(&TxMeta {
+                bls_message_root: empty,
+                secp_message_root: empty,
+            })
+            .unwrap();
+        let mut parents = TipsetKey::default();
+        (0..len)
+            .map(|epoch| {
+                let header = CachingBlockHeader::new(RawBlockHeader {
+                    miner_address: Address::new_id(0),
+                    epoch,
+                    parents: parents.clone(),
+                    messages,
+                    ..Default::default()
+                });
+                persist_objects(db, [&header].into_iter()).unwrap();
+                parents = TipsetKey::from_iter([*header.cid()]);
+                Tipset::from(header)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn backfill_indexes_skipped_epochs() {
+        let db = MemoryDB::default();
+        let tipsets = chain(&db, 6);
+        let index = FeeHistoryIndex::default();
+
+        index.index_head(&db, &tipsets[1]).unwrap();
+        assert_eq!(
+            index.entries.read().keys().copied().collect::<Vec<_>>(),
+            [1]
+        );
+
+        index.index_head(&db, &tipsets[5]).unwrap();
+        assert_eq!(
+            index.entries.read().keys().copied().collect::<Vec<_>>(),
+            [1, 5]
+        );
+
+        index.backfill(&db).unwrap();
+        assert_eq!(
+            index.entries.read().keys().copied().collect::<Vec<_>>(),
+            [1, 2, 3, 4, 5]
+        );
+        assert!(tipsets[1..].iter().all(|ts| index.is_indexed(ts)));
+    }
+
+    #[test]
+    fn premium_ladder_is_gas_weighted() {
+        let atto = TokenAmount::from_atto;
+        let ladder = premium_ladder(vec![(atto(300), 10), (atto(100), 80), (atto(200), 10)]);
+        assert_eq!(ladder.len(), LADDER_LEN);
+        assert_eq!(ladder[0], atto(100));
+        assert_eq!(ladder[80 / PERCENTILE_STEP], atto(200));
+        assert_eq!(ladder[LADDER_LEN - 1], atto(300));
+        assert!(ladder.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn empty_tipsets_report_the_minimum_premium() {
+        let ladder = premium_ladder(vec![]);
+        assert!(ladder
+            .iter()
+            .all(|premium| *premium == TokenAmount::from_atto(MIN_GAS_PREMIUM)));
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Journal of the most recent consistent heads.
+//!
+//! The head is only journaled once the blocks it refers to were written to the
+//! blockstore, and before [`HEAD_KEY`] is updated. The database applies writes
+//! in order, so after a crash, every journaled head is backed by its headers,
+//! messages and parent state root. If the recorded head turns out to be
+//! partially written, the node rolls back to the most recent journaled head
+//! that is consistent, rather than failing to load it.
+
+use crate::blocks::{Tipset, TipsetKey};
+use crate::db::setting_keys::{HEAD_JOURNAL_KEY, HEAD_KEY};
+use crate::db::{SettingsStore, SettingsStoreExt};
+use fvm_ipld_blockstore::Blockstore;
+use tracing::warn;
+
+/// Number of heads kept in the journal.
+const HEAD_JOURNAL_LEN: usize = 16;
+
+/// Returns `true` if the headers, messages and parent state root of the tipset
+/// are all in `db`.
+pub(super) fn is_consistent(db: &impl Blockstore, tsk: &TipsetKey) -> bool {
+    match Tipset::load(db, tsk) {
+        Ok(Some(tipset)) => tipset.block_headers().iter().all(|header| {
+            db.has(&header.messages).unwrap_or(false) && db.has(&header.state_root).unwrap_or(false)
+        }),
+        _ => false,
+    }
+}
+
+/// Records `tsk` as the most recent consistent head.
+pub(super) fn record(settings: &dyn SettingsStore, tsk: &TipsetKey) -> anyhow::Result<()> {
+    let mut journal = settings
+        .read_obj::<Vec<TipsetKey>>(HEAD_JOURNAL_KEY)?
+        .unwrap_or_default();
+    if journal.first() == Some(tsk) {
+        return Ok(());
+    }
+    journal.insert(0, tsk.clone());
+    journal.truncate(HEAD_JOURNAL_LEN);
+    settings.write_obj(HEAD_JOURNAL_KEY, &journal)
+}
+
+/// Returns the head to start from: the recorded head if it is consistent,
+/// otherwise the most recent consistent head of the journal, if any.
+pub(super) fn recover(
+    settings: &dyn SettingsStore,
+    db: &impl Blockstore,
+) -> anyhow::Result<Option<TipsetKey>> {
+    let head = settings.read_obj::<TipsetKey>(HEAD_KEY)?;
+    if let Some(head) = &head {
+        if is_consistent(db, head) {
+            return Ok(Some(head.clone()));
+        }
+    }
+    let journal = settings
+        .read_obj::<Vec<TipsetKey>>(HEAD_JOURNAL_KEY)?
+        .unwrap_or_default();
+    let recovered = journal.into_iter().find(|tsk| is_consistent(db, tsk));
+    if let Some(head) = head {
+        match &recovered {
+            Some(recovered) => {
+                warn!("Head {head} is partially written, rolling back to {recovered}")
+            }
+            None => warn!("Head {head} is partially written, and no consistent head was journaled"),
+        }
+    }
+    Ok(recovered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{CachingBlockHeader, RawBlockHeader};
+    use crate::db::MemoryDB;
+    use crate::utils::db::CborStoreExt as _;
+    use cid::multihash::{Code::Blake2b256, MultihashDigest as _};
+    use cid::Cid;
+    use fvm_ipld_encoding::DAG_CBOR;
+
+    /// Stores a header at `epoch` and its messages, and its state root if
+    /// `complete`.
+    fn put_head(db: &MemoryDB, epoch: i64, complete: bool) -> TipsetKey {
+        let messages = db.put_cbor_default(&("messages", epoch)).unwrap();
+        let state_root = if complete {
+            db.put_cbor_default(&("state", epoch)).unwrap()
+        } else {
+            Cid::new_v1(DAG_CBOR, Blake2b256.digest(&epoch.to_be_bytes()))
+        };
+        let header = CachingBlockHeader::new(RawBlockHeader {
+            epoch,
+            messages,
+            state_root,
+            ..Default::default()
+        });
+        db.put_cbor_default(&header).unwrap();
+        TipsetKey::from_iter([*header.cid()])
+    }
+
+    #[test]
+    fn recover_rolls_back_to_the_last_consistent_head() {
+        let db = MemoryDB::default();
+        assert_eq!(recover(&db, &db).unwrap(), None);
+
+        let first = put_head(&db, 1, true);
+        record(&db, &first).unwrap();
+        let second = put_head(&db, 2, true);
+        record(&db, &second).unwrap();
+        db.write_obj(HEAD_KEY, &second).unwrap();
+        assert_eq!(recover(&db, &db).unwrap(), Some(second.clone()));
+
+        // The node crashed before the state of the third head was written.
+        let third = put_head(&db, 3, false);
+        assert!(!is_consistent(&db, &third));
+        db.write_obj(HEAD_KEY, &third).unwrap();
+        assert_eq!(recover(&db, &db).unwrap(), Some(second.clone()));
+
+        // Journaled heads that aren't in the database are skipped too.
+        let missing = TipsetKey::from_iter([Cid::new_v1(DAG_CBOR, Blake2b256.digest(b"missing"))]);
+        record(&db, &missing).unwrap();
+        db.write_obj(HEAD_KEY, &missing).unwrap();
+        assert_eq!(recover(&db, &db).unwrap(), Some(second));
+    }
+}
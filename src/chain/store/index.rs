@@ -1,29 +1,22 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::{num::NonZeroUsize, sync::Arc};
+use std::sync::Arc;
 
 use crate::beacon::{BeaconEntry, IGNORE_DRAND_VAR};
 use crate::blocks::{Tipset, TipsetKey};
-use crate::metrics;
 use crate::shim::clock::ChainEpoch;
 use fvm_ipld_blockstore::Blockstore;
 use itertools::Itertools;
-use lru::LruCache;
-use nonzero_ext::nonzero;
-use parking_lot::Mutex;
 
+use super::tipset_cache::TipsetCache;
 use crate::chain::Error;
 
-const DEFAULT_TIPSET_CACHE_SIZE: NonZeroUsize = nonzero!(131072_usize);
-
-type TipsetCache = Mutex<LruCache<TipsetKey, Arc<Tipset>>>;
-
 /// Keeps look-back tipsets in cache at a given interval `skip_length` and can
 /// be used to look-back at the chain to retrieve an old tipset.
 pub struct ChainIndex<DB> {
-    /// `Arc` reference tipset cache.
-    ts_cache: TipsetCache,
+    /// Tipset cache, shared with the other indices of the process by default.
+    ts_cache: Arc<TipsetCache>,
 
     /// `Blockstore` pointer needed to load tipsets from cold storage.
     pub db: DB,
@@ -39,27 +32,33 @@ pub enum ResolveNullTipset {
 }
 
 impl<DB: Blockstore> ChainIndex<DB> {
+    /// Returns an index using the [shared](TipsetCache::shared) tipset cache.
     pub fn new(db: DB) -> Self {
-        let ts_cache = Mutex::new(LruCache::new(DEFAULT_TIPSET_CACHE_SIZE));
+        Self::with_cache(db, TipsetCache::shared())
+    }
+
+    pub fn with_cache(db: DB, ts_cache: Arc<TipsetCache>) -> Self {
         Self { ts_cache, db }
     }
 
+    /// Returns an index loading tipsets from `db`, sharing the tipset cache of this index.
+    pub fn with_db<DB2: Blockstore>(&self, db: DB2) -> ChainIndex<DB2> {
+        ChainIndex {
+            ts_cache: Arc::clone(&self.ts_cache),
+            db,
+        }
+    }
+
     /// Loads a tipset from memory given the tipset keys and cache. Semantically
     /// identical to [`Tipset::load`] but the result is cached.
     pub fn load_tipset(&self, tsk: &TipsetKey) -> Result<Option<Arc<Tipset>>, Error> {
-        if let Some(ts) = self.ts_cache.lock().get(tsk) {
-            metrics::LRU_CACHE_HIT
-                .with_label_values(&[metrics::values::TIPSET])
-                .inc();
-            return Ok(Some(ts.clone()));
+        if let Some(ts) = self.ts_cache.get(tsk) {
+            return Ok(Some(ts));
         }
 
         let ts_opt = Tipset::load(&self.db, tsk)?.map(Arc::new);
         if let Some(ts) = &ts_opt {
-            self.ts_cache.lock().put(tsk.clone(), ts.clone());
-            metrics::LRU_CACHE_MISS
-                .with_label_values(&[metrics::values::TIPSET])
-                .inc();
+            self.ts_cache.insert(Arc::clone(ts));
         }
 
         Ok(ts_opt)
@@ -73,6 +72,31 @@ impl<DB: Blockstore> ChainIndex<DB> {
             .ok_or_else(|| Error::NotFound("Key for header".into()))
     }
 
+    /// Loads the tipset of `start` and its ancestors, newest first, stopping
+    /// after `count` tipsets or at genesis. Unlike [`ChainIndex::chain`],
+    /// missing tipsets are errors. Only the headers are loaded, see
+    /// [`ChainStore::load_tipsets_range`] for the messages.
+    ///
+    /// [`ChainStore::load_tipsets_range`]: crate::chain::ChainStore::load_tipsets_range
+    pub fn load_tipsets_range(
+        &self,
+        start: &TipsetKey,
+        count: usize,
+    ) -> Result<Vec<Arc<Tipset>>, Error> {
+        let mut tipsets = Vec::with_capacity(count);
+        let mut tsk = start.clone();
+        while tipsets.len() < count {
+            let tipset = self.load_required_tipset(&tsk)?;
+            let is_genesis = tipset.epoch() == 0;
+            tsk = tipset.parents().clone();
+            tipsets.push(tipset);
+            if is_genesis {
+                break;
+            }
+        }
+        Ok(tipsets)
+    }
+
     /// Find tipset at epoch `to` in the chain of ancestors starting at `from`.
     /// If the tipset is _not_ in the chain of ancestors (i.e., if the `to`
     /// epoch is higher than `from.epoch()`), an error will be returned.
@@ -295,4 +319,31 @@ mod tests {
             &epoch2b
         );
     }
+
+    #[test]
+    fn load_tipsets_range() {
+        let db = Arc::new(MemoryDB::default());
+        let gen = genesis_tipset();
+        let epoch1 = tipset_child(&gen, 1);
+        let epoch3 = tipset_child(&epoch1, 3);
+        let epoch4 = tipset_child(&epoch3, 4);
+        persist_tipset(&gen, &db);
+        persist_tipset(&epoch1, &db);
+        persist_tipset(&epoch3, &db);
+
+        let index = ChainIndex::new(db);
+        let range = index.load_tipsets_range(epoch3.key(), 2).unwrap();
+        assert_eq!(
+            range,
+            vec![Arc::new(epoch3.clone()), Arc::new(epoch1.clone())]
+        );
+
+        // The range stops at genesis.
+        let range = index.load_tipsets_range(epoch3.key(), 10).unwrap();
+        assert_eq!(range.len(), 3);
+        assert_eq!(range[2].as_ref(), &gen);
+
+        // Missing tipsets are errors.
+        assert!(index.load_tipsets_range(epoch4.key(), 2).is_err());
+    }
 }
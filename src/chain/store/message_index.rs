@@ -0,0 +1,179 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Persistent index of the messages executed on chain, by CID. Each message is
+//! mapped to the tipset whose parent receipts include its receipt, so that
+//! searching for a message doesn't require walking back the chain.
+//!
+//! Tipsets are indexed as they become the head. The messages executed before
+//! a node was bootstrapped from a snapshot are indexed with
+//! `forest-tool index backfill`.
+//!
+//! Only the messages executed in the last [`MESSAGE_INDEX_RETENTION`] epochs
+//! are kept, the CIDs of the messages indexed by each epoch are stored to
+//! prune them as the head advances. Older messages are searched for on chain.
+
+use crate::blocks::{Tipset, TipsetKey};
+use crate::chain::index::ChainIndex;
+use crate::db::{
+    setting_keys::{
+        MESSAGE_INDEX_EPOCH_KEY_PREFIX, MESSAGE_INDEX_KEY_PREFIX, MESSAGE_INDEX_TAIL_KEY,
+    },
+    SettingsStore, SettingsStoreExt,
+};
+use crate::interpreter::BlockMessages;
+use crate::shim::clock::{ChainEpoch, EPOCHS_IN_DAY};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use serde::{Deserialize, Serialize};
+
+/// Location of the receipt of a message executed on chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageLocation {
+    /// Epoch of the tipset whose parent receipts include the receipt.
+    pub epoch: ChainEpoch,
+    /// Key of that tipset.
+    pub tipset: TipsetKey,
+    /// Index of the receipt among the parent receipts of the tipset.
+    pub receipt_index: u64,
+}
+
+/// Number of epochs below the head whose messages are kept in the index.
+pub const MESSAGE_INDEX_RETENTION: ChainEpoch = EPOCHS_IN_DAY;
+
+fn message_key(cid: &Cid) -> String {
+    format!("{MESSAGE_INDEX_KEY_PREFIX}{cid}")
+}
+
+fn epoch_key(epoch: ChainEpoch) -> String {
+    format!("{MESSAGE_INDEX_EPOCH_KEY_PREFIX}{epoch}")
+}
+
+/// Indexes the messages of the parent of `tipset`, which were executed by it.
+/// Returns the number of indexed messages.
+pub fn index_tipset(
+    settings: &dyn SettingsStore,
+    db: &impl Blockstore,
+    tipset: &Tipset,
+) -> anyhow::Result<usize> {
+    if tipset.epoch() == 0 {
+        return Ok(0);
+    }
+    let parent = ChainIndex::new(db).load_required_tipset(tipset.parents())?;
+    let messages = BlockMessages::for_tipset(db, &parent)?
+        .into_iter()
+        .flat_map(|bm| bm.messages);
+    let key = epoch_key(tipset.epoch());
+    // A reorg indexes another tipset at the same epoch.
+    let mut indexed_at_epoch = settings.read_obj::<Vec<Cid>>(&key)?.unwrap_or_default();
+    let mut indexed = 0;
+    for (receipt_index, message) in messages.enumerate() {
+        let location = MessageLocation {
+            epoch: tipset.epoch(),
+            tipset: tipset.key().clone(),
+            receipt_index: receipt_index as u64,
+        };
+        let cid = message.cid()?;
+        settings.write_obj(&message_key(&cid), &location)?;
+        if !indexed_at_epoch.contains(&cid) {
+            indexed_at_epoch.push(cid);
+        }
+        indexed += 1;
+    }
+    settings.write_obj(&key, &indexed_at_epoch)?;
+    prune(settings, tipset.epoch())?;
+    Ok(indexed)
+}
+
+/// Removes the messages indexed by the epochs older than
+/// [`MESSAGE_INDEX_RETENTION`] below `epoch`.
+fn prune(settings: &dyn SettingsStore, epoch: ChainEpoch) -> anyhow::Result<()> {
+    let tail = settings
+        .read_obj::<ChainEpoch>(MESSAGE_INDEX_TAIL_KEY)?
+        .map_or(epoch, |tail| tail.min(epoch));
+    let retained = epoch - MESSAGE_INDEX_RETENTION;
+    for pruned in tail..retained {
+        let key = epoch_key(pruned);
+        let Some(cids) = settings.read_obj::<Vec<Cid>>(&key)? else {
+            continue;
+        };
+        for cid in cids {
+            // A message executed again at a later epoch after a reorg stays
+            // indexed.
+            if lookup(settings, &cid)?.is_some_and(|location| location.epoch == pruned) {
+                settings.delete(&message_key(&cid))?;
+            }
+        }
+        settings.delete(&key)?;
+    }
+    settings.write_obj(MESSAGE_INDEX_TAIL_KEY, &tail.max(retained))
+}
+
+/// Returns the location of the receipt of the message, if it is indexed. The
+/// tipset of a location might not be on the current chain after a reorg.
+pub fn lookup(settings: &dyn SettingsStore, cid: &Cid) -> anyhow::Result<Option<MessageLocation>> {
+    settings.read_obj(&message_key(cid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+    use crate::utils::cid::CidCborExt;
+
+    #[test]
+    fn lookup_indexed_location() {
+        let settings = MemoryDB::default();
+        let cid = Cid::from_cbor_blake2b256(&"message").unwrap();
+        assert_eq!(lookup(&settings, &cid).unwrap(), None);
+
+        let location = MessageLocation {
+            epoch: 42,
+            tipset: TipsetKey::from_iter([Cid::default()]),
+            receipt_index: 7,
+        };
+        settings.write_obj(&message_key(&cid), &location).unwrap();
+        assert_eq!(lookup(&settings, &cid).unwrap(), Some(location));
+    }
+
+    #[test]
+    fn old_messages_are_pruned() {
+        let settings = MemoryDB::default();
+        let index = |name: &str, epoch: ChainEpoch, indexed_at: ChainEpoch| {
+            let cid = Cid::from_cbor_blake2b256(&name).unwrap();
+            let location = MessageLocation {
+                epoch,
+                tipset: TipsetKey::from_iter([Cid::default()]),
+                receipt_index: 0,
+            };
+            settings.write_obj(&message_key(&cid), &location).unwrap();
+            let key = epoch_key(indexed_at);
+            let mut cids = settings
+                .read_obj::<Vec<Cid>>(&key)
+                .unwrap()
+                .unwrap_or_default();
+            cids.push(cid);
+            settings.write_obj(&key, &cids).unwrap();
+            cid
+        };
+        let old = index("old", 1, 1);
+        // Executed again at a later epoch after a reorg.
+        let reorged = index("reorged", 3, 1);
+        let recent = index("recent", 2, 2);
+
+        prune(&settings, 1).unwrap();
+        assert!(lookup(&settings, &old).unwrap().is_some());
+
+        prune(&settings, 2 + MESSAGE_INDEX_RETENTION).unwrap();
+        assert_eq!(lookup(&settings, &old).unwrap(), None);
+        assert!(lookup(&settings, &reorged).unwrap().is_some());
+        assert!(lookup(&settings, &recent).unwrap().is_some());
+        assert!(!settings.exists(&epoch_key(1)).unwrap());
+        assert_eq!(
+            settings
+                .read_obj::<ChainEpoch>(MESSAGE_INDEX_TAIL_KEY)
+                .unwrap(),
+            Some(2)
+        );
+    }
+}
@@ -0,0 +1,68 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use once_cell::sync::Lazy;
+use prometheus::{
+    core::{AtomicU64, GenericCounter, GenericGauge, Opts},
+    Histogram, HistogramOpts,
+};
+
+pub static REORG_TOTAL: Lazy<Box<GenericCounter<AtomicU64>>> = Lazy::new(|| {
+    let reorg_total = Box::new(
+        GenericCounter::<AtomicU64>::new(
+            "reorg_total",
+            "Total number of head changes that orphaned blocks of the previous head",
+        )
+        .expect("Defining the reorg_total metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(reorg_total.clone())
+        .expect("Registering the reorg_total metric with the metrics registry must succeed");
+    reorg_total
+});
+pub static REORG_DEPTH: Lazy<Box<Histogram>> = Lazy::new(|| {
+    let reorg_depth = Box::new(
+        Histogram::with_opts(HistogramOpts {
+            common_opts: Opts::new(
+                "reorg_depth",
+                "Number of epochs between the previous head and the fork point of reorgs",
+            ),
+            buckets: vec![1.0, 2.0, 3.0, 5.0, 10.0, 20.0, 50.0, 100.0, 900.0],
+        })
+        .expect("Defining the reorg_depth metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(reorg_depth.clone())
+        .expect("Registering the reorg_depth metric with the metrics registry must succeed");
+    reorg_depth
+});
+pub static ORPHANED_BLOCK_TOTAL: Lazy<Box<GenericCounter<AtomicU64>>> = Lazy::new(|| {
+    let orphaned_block_total = Box::new(
+        GenericCounter::<AtomicU64>::new(
+            "orphaned_block_total",
+            "Total number of blocks orphaned by reorgs",
+        )
+        .expect("Defining the orphaned_block_total metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(orphaned_block_total.clone())
+        .expect(
+            "Registering the orphaned_block_total metric with the metrics registry must succeed",
+        );
+    orphaned_block_total
+});
+pub static TIPSET_CACHE_SIZE: Lazy<Box<GenericGauge<AtomicU64>>> = Lazy::new(|| {
+    let tipset_cache_size = Box::new(
+        GenericGauge::<AtomicU64>::new(
+            "tipset_cache_size_bytes",
+            "Size of the encoded headers of the cached tipsets",
+        )
+        .expect("Defining the tipset_cache_size_bytes metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(tipset_cache_size.clone())
+        .expect(
+            "Registering the tipset_cache_size_bytes metric with the metrics registry must succeed",
+        );
+    tipset_cache_size
+});
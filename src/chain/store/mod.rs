@@ -3,8 +3,16 @@
 
 pub mod base_fee;
 mod chain_store;
+pub mod consensus_fault;
 mod errors;
+pub mod event_index;
+pub mod fee_history;
+mod head_journal;
 pub mod index;
+pub mod message_index;
+mod metrics;
+pub mod reorg;
+pub mod tipset_cache;
 mod tipset_tracker;
 
 pub use self::{base_fee::*, chain_store::*, errors::*};
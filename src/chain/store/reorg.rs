@@ -0,0 +1,244 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Tracking of the reorgs of the chain, the head changes that orphan blocks of
+//! the previous head. A head lagging behind the network because of reorgs
+//! shows up here, while a head lagging because of the network does not.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::blocks::{Tipset, TipsetKey};
+use crate::lotus_json::lotus_json_with_self;
+use crate::shim::clock::ChainEpoch;
+use ahash::HashSet;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::{index::ChainIndex, metrics, Error};
+
+/// Number of reorgs that are kept.
+const REORGS_CAP: usize = 256;
+
+/// A head change that orphaned blocks of the previous head.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Reorg {
+    /// Unix timestamp of the head change, in seconds.
+    pub timestamp: i64,
+    #[serde(with = "crate::lotus_json")]
+    pub old_head: TipsetKey,
+    pub old_epoch: ChainEpoch,
+    #[serde(with = "crate::lotus_json")]
+    pub new_head: TipsetKey,
+    pub new_epoch: ChainEpoch,
+    /// Epoch of the last tipset shared by the old and the new chains.
+    pub fork_epoch: ChainEpoch,
+    /// Number of epochs rolled back, from the old head to the fork point.
+    pub depth: ChainEpoch,
+    /// Blocks of the old chain that aren't in the new chain.
+    #[serde(with = "crate::lotus_json")]
+    pub orphaned_blocks: Vec<Cid>,
+}
+
+lotus_json_with_self!(Reorg);
+
+/// Keeps the most recent reorgs and updates the reorg metrics.
+pub struct ReorgTracker {
+    /// Maximum number of epochs that are walked back to find the fork point.
+    max_depth: ChainEpoch,
+    reorgs: Mutex<VecDeque<Reorg>>,
+}
+
+impl ReorgTracker {
+    pub fn new(max_depth: ChainEpoch) -> Self {
+        Self {
+            max_depth,
+            reorgs: Default::default(),
+        }
+    }
+
+    /// Returns the tracked reorgs, oldest first.
+    pub fn reorgs(&self) -> Vec<Reorg> {
+        self.reorgs.lock().iter().cloned().collect()
+    }
+
+    /// Records the change of the head from `old` to `new` if it is a reorg.
+    pub fn track(
+        &self,
+        chain_index: &ChainIndex<impl Blockstore>,
+        old: Arc<Tipset>,
+        new: Arc<Tipset>,
+    ) {
+        let reorg = match find_reorg(chain_index, old, new, self.max_depth) {
+            Ok(Some(reorg)) => reorg,
+            Ok(None) => return,
+            Err(e) => {
+                warn!("Failed to find the fork point of the new head: {e}");
+                return;
+            }
+        };
+        info!(
+            "Reorg from {} (EPOCH = {}) to {} (EPOCH = {}), {} epochs deep, orphaning {} blocks",
+            reorg.old_head,
+            reorg.old_epoch,
+            reorg.new_head,
+            reorg.new_epoch,
+            reorg.depth,
+            reorg.orphaned_blocks.len()
+        );
+        metrics::REORG_TOTAL.inc();
+        metrics::REORG_DEPTH.observe(reorg.depth as f64);
+        metrics::ORPHANED_BLOCK_TOTAL.inc_by(reorg.orphaned_blocks.len() as u64);
+        let mut reorgs = self.reorgs.lock();
+        if reorgs.len() == REORGS_CAP {
+            reorgs.pop_front();
+        }
+        reorgs.push_back(reorg);
+    }
+}
+
+/// The tipsets to revert and to apply to move the head from one tipset to
+/// another.
+pub struct ReorgOps {
+    /// Last tipset shared by both chains.
+    pub fork_point: Arc<Tipset>,
+    /// Tipsets of the chain of the previous head, newest first.
+    pub revert: Vec<Arc<Tipset>>,
+    /// Tipsets of the chain of the new head, oldest first.
+    pub apply: Vec<Arc<Tipset>>,
+}
+
+/// Walks back the chains of `from` and `to` to their fork point, at most
+/// `max_depth` epochs below `from` if given.
+pub fn reorg_ops(
+    chain_index: &ChainIndex<impl Blockstore>,
+    from: Arc<Tipset>,
+    to: Arc<Tipset>,
+    max_depth: Option<ChainEpoch>,
+) -> Result<ReorgOps, Error> {
+    let (mut from_chain, mut to_chain) = (Arc::clone(&from), to);
+    let (mut revert, mut apply) = (vec![], vec![]);
+    while from_chain.key() != to_chain.key() {
+        if let Some(max_depth) = max_depth {
+            if from.epoch() - from_chain.epoch() > max_depth {
+                return Err(Error::Other(format!(
+                    "no fork point within {max_depth} epochs of {}",
+                    from.key()
+                )));
+            }
+        }
+        let epoch = from_chain.epoch().max(to_chain.epoch());
+        if from_chain.epoch() == epoch {
+            let parent = chain_index.load_required_tipset(from_chain.parents())?;
+            revert.push(std::mem::replace(&mut from_chain, parent));
+        }
+        if to_chain.epoch() == epoch {
+            let parent = chain_index.load_required_tipset(to_chain.parents())?;
+            apply.push(std::mem::replace(&mut to_chain, parent));
+        }
+    }
+    apply.reverse();
+    Ok(ReorgOps {
+        fork_point: from_chain,
+        revert,
+        apply,
+    })
+}
+
+/// Returns `None` if no block of the `old` chain is orphaned by `new`, e.g.,
+/// when `new` extends it.
+fn find_reorg(
+    chain_index: &ChainIndex<impl Blockstore>,
+    old: Arc<Tipset>,
+    new: Arc<Tipset>,
+    max_depth: ChainEpoch,
+) -> Result<Option<Reorg>, Error> {
+    if new.parents() == old.key() || new.key() == old.key() {
+        return Ok(None);
+    }
+    let ops = reorg_ops(
+        chain_index,
+        Arc::clone(&old),
+        Arc::clone(&new),
+        Some(max_depth),
+    )?;
+    let new_blocks: HashSet<_> = ops.apply.iter().flat_map(|tipset| tipset.cids()).collect();
+    let orphaned_blocks: Vec<_> = ops
+        .revert
+        .iter()
+        .flat_map(|tipset| tipset.cids())
+        .filter(|cid| !new_blocks.contains(cid))
+        .collect();
+    if orphaned_blocks.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Reorg {
+        timestamp: chrono::Utc::now().timestamp(),
+        old_head: old.key().clone(),
+        old_epoch: old.epoch(),
+        new_head: new.key().clone(),
+        new_epoch: new.epoch(),
+        fork_epoch: ops.fork_point.epoch(),
+        depth: old.epoch() - ops.fork_point.epoch(),
+        orphaned_blocks,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::CachingBlockHeader;
+    use crate::db::MemoryDB;
+    use crate::message_pool::test_provider::mock_header;
+    use crate::utils::db::CborStoreExt;
+
+    fn tipset(db: &MemoryDB, miner: u64, epoch: ChainEpoch, parent: &Tipset) -> Arc<Tipset> {
+        let header = mock_header(miner, epoch, parent.key().clone());
+        db.put_cbor_default(&*header).unwrap();
+        Arc::new(Tipset::from(header))
+    }
+
+    #[test]
+    fn track_reorgs() {
+        let db = Arc::new(MemoryDB::default());
+        let chain_index = ChainIndex::new(Arc::clone(&db));
+        let genesis = CachingBlockHeader::default();
+        db.put_cbor_default(&*genesis).unwrap();
+        let genesis = Arc::new(Tipset::from(genesis));
+        let a1 = tipset(&db, 1000, 1, &genesis);
+        let a2 = tipset(&db, 1000, 2, &a1);
+        let b2 = tipset(&db, 1001, 2, &a1);
+        let b3 = tipset(&db, 1001, 3, &b2);
+
+        let tracker = ReorgTracker::new(900);
+        tracker.track(&chain_index, Arc::clone(&genesis), Arc::clone(&a1));
+        tracker.track(&chain_index, Arc::clone(&a1), Arc::clone(&a2));
+        assert!(tracker.reorgs().is_empty());
+
+        tracker.track(&chain_index, Arc::clone(&a2), Arc::clone(&b3));
+        let reorgs = tracker.reorgs();
+        assert_eq!(reorgs.len(), 1);
+        assert_eq!(reorgs[0].fork_epoch, 1);
+        assert_eq!(reorgs[0].depth, 1);
+        assert_eq!(reorgs[0].new_head, *b3.key());
+        assert_eq!(reorgs[0].orphaned_blocks, a2.cids());
+
+        // Skipping tipsets of the same chain is no reorg.
+        tracker.track(&chain_index, Arc::clone(&genesis), Arc::clone(&b3));
+        assert_eq!(tracker.reorgs().len(), 1);
+
+        let ops = reorg_ops(&chain_index, Arc::clone(&b3), Arc::clone(&a2), None).unwrap();
+        assert_eq!(ops.fork_point, a1);
+        assert_eq!(ops.revert, vec![Arc::clone(&b3), b2]);
+        assert_eq!(ops.apply, vec![Arc::clone(&a2)]);
+
+        // The fork point is too deep.
+        let tracker = ReorgTracker::new(0);
+        tracker.track(&chain_index, Arc::clone(&a2), Arc::clone(&b3));
+        assert!(tracker.reorgs().is_empty());
+    }
+}
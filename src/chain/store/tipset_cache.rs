@@ -0,0 +1,168 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! A cache of tipsets bounded by their size in bytes rather than by their
+//! number, as tipsets range from one to a dozen headers.
+//!
+//! Tipsets are identified by the CIDs of their headers, so a cached tipset is
+//! valid whatever the database it was loaded from. All the chain indices of the
+//! process share the [same cache](TipsetCache::shared) by default, and thus
+//! don't deserialize the same headers once each.
+
+use std::sync::Arc;
+
+use crate::blocks::{Tipset, TipsetKey};
+use crate::metrics;
+use lru::LruCache;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use tracing::warn;
+
+use super::metrics::TIPSET_CACHE_SIZE;
+
+/// Default capacity of the [shared](TipsetCache::shared) cache.
+const DEFAULT_TIPSET_CACHE_SIZE_BYTES: usize = 256 << 20;
+
+/// Capacity of the [shared](TipsetCache::shared) cache, set with the
+/// `FOREST_TIPSET_CACHE_SIZE_BYTES` environment variable.
+fn shared_tipset_cache_size() -> usize {
+    match std::env::var("FOREST_TIPSET_CACHE_SIZE_BYTES") {
+        Ok(var) => var.parse().unwrap_or_else(|_| {
+            warn!(
+                "Invalid FOREST_TIPSET_CACHE_SIZE_BYTES {var}, using {DEFAULT_TIPSET_CACHE_SIZE_BYTES}"
+            );
+            DEFAULT_TIPSET_CACHE_SIZE_BYTES
+        }),
+        _ => DEFAULT_TIPSET_CACHE_SIZE_BYTES,
+    }
+}
+
+/// Least recently used tipsets are evicted first, until the cached tipsets fit
+/// in the capacity.
+pub struct TipsetCache {
+    capacity: usize,
+    inner: Mutex<Inner>,
+}
+
+struct Inner {
+    size: usize,
+    entries: LruCache<TipsetKey, (Arc<Tipset>, usize)>,
+}
+
+impl TipsetCache {
+    pub fn new(capacity_bytes: usize) -> Self {
+        Self {
+            capacity: capacity_bytes,
+            inner: Mutex::new(Inner {
+                size: 0,
+                entries: LruCache::unbounded(),
+            }),
+        }
+    }
+
+    /// The cache shared by the chain indices of the process.
+    pub fn shared() -> Arc<Self> {
+        static SHARED: Lazy<Arc<TipsetCache>> =
+            Lazy::new(|| Arc::new(TipsetCache::new(shared_tipset_cache_size())));
+        Arc::clone(&SHARED)
+    }
+
+    pub fn get(&self, tsk: &TipsetKey) -> Option<Arc<Tipset>> {
+        let tipset = self
+            .inner
+            .lock()
+            .entries
+            .get(tsk)
+            .map(|(tipset, _)| Arc::clone(tipset));
+        let counter = match tipset {
+            Some(_) => &metrics::LRU_CACHE_HIT,
+            None => &metrics::LRU_CACHE_MISS,
+        };
+        counter.with_label_values(&[metrics::values::TIPSET]).inc();
+        tipset
+    }
+
+    /// Caches `tipset`, unless it alone exceeds the capacity.
+    pub fn insert(&self, tipset: Arc<Tipset>) {
+        let weight = weight(&tipset);
+        if weight > self.capacity {
+            return;
+        }
+        let mut inner = self.inner.lock();
+        inner.size += weight;
+        if let Some((_, replaced)) = inner.entries.put(tipset.key().clone(), (tipset, weight)) {
+            inner.size -= replaced;
+        }
+        while inner.size > self.capacity {
+            let Some((_, (_, evicted))) = inner.entries.pop_lru() else {
+                break;
+            };
+            inner.size -= evicted;
+        }
+        TIPSET_CACHE_SIZE.set(inner.size as u64);
+    }
+
+    /// Size of the cached tipsets, in bytes.
+    pub fn size(&self) -> usize {
+        self.inner.lock().size
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Size of the encoded headers of `tipset`, a proxy for its size in memory.
+fn weight(tipset: &Tipset) -> usize {
+    tipset
+        .block_headers()
+        .iter()
+        .map(|header| {
+            fvm_ipld_encoding::to_vec(&**header)
+                .map(|bytes| bytes.len())
+                .unwrap_or_default()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_pool::test_provider::mock_tipset;
+
+    #[test]
+    fn evict_by_weight() {
+        let (a, b, c) = (
+            mock_tipset(1000, 0),
+            mock_tipset(1001, 0),
+            mock_tipset(1002, 0),
+        );
+        let cache = TipsetCache::new(weight(&a) * 2);
+
+        cache.insert(Arc::clone(&a));
+        cache.insert(Arc::clone(&b));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.size(), weight(&a) + weight(&b));
+
+        // Reading `a` makes `b` the least recently used.
+        assert_eq!(cache.get(a.key()), Some(Arc::clone(&a)));
+        cache.insert(Arc::clone(&c));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(b.key()).is_none());
+        assert!(cache.get(a.key()).is_some());
+        assert!(cache.get(c.key()).is_some());
+
+        // Inserting a cached tipset again doesn't count it twice.
+        cache.insert(Arc::clone(&c));
+        assert_eq!(cache.size(), weight(&a) + weight(&c));
+
+        // A tipset larger than the cache isn't cached.
+        let cache = TipsetCache::new(weight(&a) - 1);
+        cache.insert(Arc::clone(&a));
+        assert!(cache.is_empty());
+    }
+}
@@ -17,4 +17,17 @@ pub trait Scale {
     fn weight<DB>(db: &Arc<DB>, ts: &Tipset) -> Result<Weight, anyhow::Error>
     where
         DB: Blockstore;
+
+    /// Returns whether `candidate` should replace `head` as the heaviest
+    /// tipset. By default, heavier tipsets are selected.
+    fn is_heavier<DB>(
+        db: &Arc<DB>,
+        candidate: &Tipset,
+        head: &Tipset,
+    ) -> Result<bool, anyhow::Error>
+    where
+        DB: Blockstore,
+    {
+        Ok(Self::weight(db, candidate)? > Self::weight(db, head)?)
+    }
 }
@@ -2,18 +2,35 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use std::num::NonZeroUsize;
+use std::sync::Arc;
 
+use crate::db::{setting_keys::BAD_BLOCK_KEY_PREFIX, SettingsStore, SettingsStoreExt};
 use cid::Cid;
 use lru::LruCache;
 use nonzero_ext::nonzero;
 use parking_lot::Mutex;
+use tracing::warn;
 
 /// Thread-safe cache for tracking bad blocks.
 /// This cache is checked before validating a block, to ensure no duplicate
 /// work.
-#[derive(Debug)]
+///
+/// When backed by a settings store, the bad blocks and the reasons they were
+/// rejected for are persisted, one entry per block, so that they aren't synced
+/// again after a restart. The entries of the blocks evicted from the cache or
+/// unmarked are deleted.
 pub struct BadBlockCache {
     cache: Mutex<LruCache<Cid, String>>,
+    store: Option<Arc<dyn SettingsStore + Sync + Send>>,
+}
+
+impl std::fmt::Debug for BadBlockCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BadBlockCache")
+            .field("cache", &self.cache)
+            .field("persistent", &self.store.is_some())
+            .finish()
+    }
 }
 
 impl Default for BadBlockCache {
@@ -22,16 +39,84 @@ impl Default for BadBlockCache {
     }
 }
 
+fn bad_block_key(c: &Cid) -> String {
+    format!("{BAD_BLOCK_KEY_PREFIX}{c}")
+}
+
 impl BadBlockCache {
     pub fn new(cap: NonZeroUsize) -> Self {
         Self {
             cache: Mutex::new(LruCache::new(cap)),
+            store: None,
+        }
+    }
+
+    /// Creates a cache of the default capacity backed by `store`, loaded with
+    /// the bad blocks persisted in it.
+    pub fn with_store(store: Arc<dyn SettingsStore + Sync + Send>) -> anyhow::Result<Self> {
+        let mut cache = Self::default();
+        for key in store.setting_keys()? {
+            let Some(cid) = key.strip_prefix(BAD_BLOCK_KEY_PREFIX) else {
+                continue;
+            };
+            if let Some(reason) = store.read_obj::<String>(&key)? {
+                if let Some((evicted, _)) = cache.cache.get_mut().push(cid.parse()?, reason) {
+                    store.delete(&bad_block_key(&evicted))?;
+                }
+            }
+        }
+        cache.store = Some(store);
+        Ok(cache)
+    }
+
+    fn persist(&self, c: &Cid, reason: &String) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.write_obj(&bad_block_key(c), reason) {
+                warn!("Persisting bad block {c} failed: {e}");
+            }
+        }
+    }
+
+    fn unpersist(&self, c: &Cid) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.delete(&bad_block_key(c)) {
+                warn!("Deleting persisted bad block {c} failed: {e}");
+            }
         }
     }
 
     /// Puts a bad block `Cid` in the cache with a given reason.
     pub fn put(&self, c: Cid, reason: String) -> Option<String> {
-        self.cache.lock().put(c, reason)
+        let mut cache = self.cache.lock();
+        self.persist(&c, &reason);
+        match cache.push(c, reason) {
+            Some((replaced, previous)) if replaced == c => Some(previous),
+            Some((evicted, _)) => {
+                self.unpersist(&evicted);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Removes a block `Cid` from the cache, returning the reason it was
+    /// marked bad for, if it was.
+    pub fn remove(&self, c: &Cid) -> Option<String> {
+        let mut cache = self.cache.lock();
+        let reason = cache.pop(c);
+        if reason.is_some() {
+            self.unpersist(c);
+        }
+        reason
+    }
+
+    /// Removes all the blocks from the cache.
+    pub fn clear(&self) {
+        let mut cache = self.cache.lock();
+        for (c, _) in cache.iter() {
+            self.unpersist(c);
+        }
+        cache.clear();
     }
 
     /// Returns `Some` with the reason if the block CID is in bad block cache.
@@ -46,3 +131,53 @@ impl BadBlockCache {
         self.cache.lock().peek(c).cloned()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+    use crate::utils::cid::CidCborExt;
+
+    #[test]
+    fn bad_blocks_are_persisted() {
+        let store = Arc::new(MemoryDB::default());
+        let bad = Cid::from_cbor_blake2b256(&"bad").unwrap();
+        let unmarked = Cid::from_cbor_blake2b256(&"unmarked").unwrap();
+
+        let cache = BadBlockCache::with_store(store.clone()).unwrap();
+        cache.put(bad, "invalid signature".into());
+        cache.put(unmarked, "invalid state root".into());
+        assert_eq!(cache.remove(&unmarked), Some("invalid state root".into()));
+
+        let cache = BadBlockCache::with_store(store.clone()).unwrap();
+        assert_eq!(cache.peek(&bad), Some("invalid signature".into()));
+        assert_eq!(cache.peek(&unmarked), None);
+
+        cache.clear();
+        let cache = BadBlockCache::with_store(store.clone()).unwrap();
+        assert_eq!(cache.peek(&bad), None);
+        assert!(store.setting_keys().unwrap().is_empty());
+    }
+
+    #[test]
+    fn evicted_bad_blocks_are_deleted() {
+        let store = Arc::new(MemoryDB::default());
+        let mut cache = BadBlockCache::new(nonzero!(1usize));
+        cache.store = Some(store.clone());
+        let evicted = Cid::from_cbor_blake2b256(&"evicted").unwrap();
+        let bad = Cid::from_cbor_blake2b256(&"bad").unwrap();
+
+        cache.put(evicted, "invalid signature".into());
+        assert_eq!(cache.put(bad, "invalid state root".into()), None);
+        assert_eq!(store.setting_keys().unwrap(), vec![bad_block_key(&bad)]);
+        // Marking a block again keeps its entry.
+        assert_eq!(
+            cache.put(bad, "invalid ticket".into()),
+            Some("invalid state root".into())
+        );
+        assert_eq!(
+            store.read_obj::<String>(&bad_block_key(&bad)).unwrap(),
+            Some("invalid ticket".into())
+        );
+    }
+}
@@ -2,10 +2,11 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use std::{
+    collections::HashSet,
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
-    time::SystemTime,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use crate::blocks::{Block, CreateTipsetError, FullTipset, GossipBlock, Tipset, TipsetKey};
@@ -15,6 +16,7 @@ use crate::libp2p::{
 };
 use crate::message::SignedMessage;
 use crate::message_pool::{MessagePool, Provider};
+use crate::networks::{ConsensusKind, HeightInfo};
 use crate::shim::{clock::SECONDS_IN_DAY, message::Message};
 use crate::state_manager::StateManager;
 use cid::Cid;
@@ -24,18 +26,20 @@ use futures::{
     try_join, StreamExt,
 };
 use fvm_ipld_blockstore::Blockstore;
-use parking_lot::RwLock;
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::{debug, error, info, trace, warn};
 
 use crate::chain_sync::{
     bad_block_cache::BadBlockCache,
+    checkpoints::Checkpoint,
     metrics,
     network_context::SyncNetworkContext,
-    sync_state::SyncState,
+    sync_state::SyncWorkers,
     tipset_syncer::{
-        TipsetProcessor, TipsetProcessorError, TipsetRangeSyncer, TipsetRangeSyncerError,
+        early_block_delay, TipsetProcessor, TipsetProcessorError, TipsetRangeSyncer,
+        TipsetRangeSyncerError,
     },
     validation::{TipsetValidationError, TipsetValidator},
 };
@@ -45,8 +49,10 @@ use crate::chain_sync::{
 const DEFAULT_REQUEST_WINDOW: usize = 8;
 const DEFAULT_TIPSET_SAMPLE_SIZE: usize = 5;
 const DEFAULT_RECENT_STATE_ROOTS: i64 = 2000;
-
-pub(in crate::chain_sync) type WorkerState = Arc<RwLock<SyncState>>;
+const DEFAULT_VERIFICATION_THREADS: usize = 0;
+const DEFAULT_SYNC_WORKERS: usize = 0;
+// Maximum number of early gossip blocks held back for revalidation at a time
+const MAX_DELAYED_BLOCKS: usize = 64;
 
 type ChainMuxerFuture<T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + Send>>;
 
@@ -74,6 +80,53 @@ pub enum ChainMuxerError {
     NetworkFollowingFailure(String),
 }
 
+/// Sends gossip blocks that arrived slightly ahead of their timestamp back to
+/// the chain muxer once their timestamp is reached. The pending blocks are
+/// deduplicated by CID and bounded by [`MAX_DELAYED_BLOCKS`].
+#[derive(Clone)]
+struct DelayedBlockSender {
+    sender: flume::Sender<NetworkEvent>,
+    pending: Arc<Mutex<HashSet<Cid>>>,
+}
+
+impl DelayedBlockSender {
+    fn new(sender: flume::Sender<NetworkEvent>) -> Self {
+        Self {
+            sender,
+            pending: Default::default(),
+        }
+    }
+
+    /// Marks the block as pending. Returns `false` if it is already pending
+    /// or if too many blocks are pending.
+    fn reserve(&self, cid: Cid) -> bool {
+        let mut pending = self.pending.lock();
+        pending.len() < MAX_DELAYED_BLOCKS && pending.insert(cid)
+    }
+
+    /// Sends the block back after `delay`. Returns `false` if the block is
+    /// dropped instead, see [`DelayedBlockSender::reserve`].
+    fn delay(&self, source: PeerId, block: GossipBlock, delay: Duration) -> bool {
+        let cid = *block.header.cid();
+        if !self.reserve(cid) {
+            return false;
+        }
+        let this = self.clone();
+        tokio::task::spawn(async move {
+            tokio::time::sleep(delay).await;
+            this.pending.lock().remove(&cid);
+            let event = NetworkEvent::PubsubMessage {
+                source,
+                message: PubsubMessage::Block(block),
+            };
+            if let Err(why) = this.sender.send_async(event).await {
+                debug!("Requeuing delayed block failed: {why}");
+            }
+        });
+        true
+    }
+}
+
 /// Structure that defines syncing configuration options
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 #[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
@@ -88,6 +141,30 @@ pub struct SyncConfig {
     /// head is
     #[cfg_attr(test, arbitrary(gen(|g| u32::arbitrary(g) as _)))]
     pub tipset_sample_size: usize,
+    /// Number of threads verifying message signatures, or `0` for one thread
+    /// per CPU
+    #[cfg_attr(test, arbitrary(gen(|g| u32::arbitrary(g) as _)))]
+    #[serde(default)]
+    pub verification_threads: usize,
+    /// Number of tipsets validated concurrently while syncing a range of
+    /// tipsets, or `0` for one per CPU. The following tipsets are executed
+    /// meanwhile.
+    #[cfg_attr(test, arbitrary(gen(|g| u32::arbitrary(g) as _)))]
+    #[serde(default)]
+    pub workers: usize,
+    /// Known-good tipsets whose ancestors are synced without verifying their
+    /// signatures and election proofs. Replaces the checkpoints compiled in
+    /// for the network if set.
+    #[serde(default)]
+    pub checkpoints: Option<Vec<Checkpoint>>,
+    /// Consensus the chain is synced with. Alternatives to Expected Consensus
+    /// are only available on devnets.
+    #[serde(default)]
+    pub consensus: ConsensusKind,
+    /// Network upgrades rescheduled from those compiled in for the network,
+    /// optionally with their actor bundle. Only available on devnets.
+    #[serde(default)]
+    pub upgrades: Vec<HeightInfo>,
 }
 
 impl Default for SyncConfig {
@@ -96,6 +173,21 @@ impl Default for SyncConfig {
             request_window: DEFAULT_REQUEST_WINDOW,
             recent_state_roots: DEFAULT_RECENT_STATE_ROOTS,
             tipset_sample_size: DEFAULT_TIPSET_SAMPLE_SIZE,
+            verification_threads: DEFAULT_VERIFICATION_THREADS,
+            workers: DEFAULT_SYNC_WORKERS,
+            checkpoints: None,
+            consensus: ConsensusKind::Ec,
+            upgrades: vec![],
+        }
+    }
+}
+
+impl SyncConfig {
+    /// Number of tipsets validated concurrently, see [`SyncConfig::workers`].
+    pub fn sync_workers(&self) -> usize {
+        match self.workers {
+            0 => std::thread::available_parallelism().map_or(1, usize::from),
+            workers => workers,
         }
     }
 }
@@ -130,8 +222,8 @@ pub struct ChainMuxer<DB, M> {
     /// State of the `ChainSyncer` `Future` implementation
     state: ChainMuxerState,
 
-    /// Syncing state of chain sync workers.
-    worker_state: WorkerState,
+    /// Syncing states of the chain sync workers.
+    workers: SyncWorkers,
 
     /// manages retrieving and updates state objects
     state_manager: Arc<StateManager<DB>>,
@@ -150,6 +242,13 @@ pub struct ChainMuxer<DB, M> {
     /// Incoming network events to be handled by synchronizer
     net_handler: flume::Receiver<NetworkEvent>,
 
+    /// Gossip blocks that arrived slightly ahead of their timestamp, sent back
+    /// once their timestamp is reached so they can be revalidated
+    delayed_blocks_sender: DelayedBlockSender,
+
+    /// Receiver side of `delayed_blocks_sender`, polled alongside `net_handler`
+    delayed_blocks: flume::Receiver<NetworkEvent>,
+
     /// Message pool
     mpool: Arc<MessagePool<M>>,
 
@@ -178,18 +277,23 @@ where
         genesis: Arc<Tipset>,
         tipset_sender: flume::Sender<Arc<Tipset>>,
         tipset_receiver: flume::Receiver<Arc<Tipset>>,
+        bad_blocks: Arc<BadBlockCache>,
         stateless_mode: bool,
     ) -> Result<Self, ChainMuxerError> {
         let network =
             SyncNetworkContext::new(network_send, peer_manager, state_manager.blockstore_owned());
+        let (delayed_blocks_sender, delayed_blocks) = flume::unbounded();
+        let delayed_blocks_sender = DelayedBlockSender::new(delayed_blocks_sender);
 
         Ok(Self {
             state: ChainMuxerState::Idle,
-            worker_state: Default::default(),
+            workers: Default::default(),
             network,
             genesis,
-            bad_blocks: Arc::new(BadBlockCache::default()),
+            bad_blocks,
             net_handler: network_rx,
+            delayed_blocks_sender,
+            delayed_blocks,
             mpool,
             tipset_sender,
             tipset_receiver,
@@ -198,15 +302,9 @@ where
         })
     }
 
-    /// Returns a clone of the bad blocks cache to be used outside of chain
-    /// sync.
-    pub fn bad_blocks_cloned(&self) -> Arc<BadBlockCache> {
-        self.bad_blocks.clone()
-    }
-
-    /// Returns a cloned `Arc` of the sync worker state.
-    pub fn sync_state_cloned(&self) -> WorkerState {
-        self.worker_state.clone()
+    /// Returns a clone of the syncing states of the workers.
+    pub fn sync_workers_cloned(&self) -> SyncWorkers {
+        self.workers.clone()
     }
 
     async fn get_full_tipset(
@@ -341,8 +439,8 @@ where
         Ok(FullTipset::from(block))
     }
 
-    fn handle_pubsub_message(mem_pool: Arc<MessagePool<M>>, message: SignedMessage) {
-        if let Err(why) = mem_pool.add(message) {
+    async fn handle_pubsub_message(mem_pool: Arc<MessagePool<M>>, message: SignedMessage) {
+        if let Err(why) = mem_pool.add(message).await {
             debug!(
                 "GossipSub message could not be added to the mem pool: {}",
                 why
@@ -350,6 +448,18 @@ where
         }
     }
 
+    /// Waits for the next event from the p2p event stream, or for an early
+    /// block whose revalidation delay has elapsed.
+    async fn next_network_event(
+        p2p_messages: &flume::Receiver<NetworkEvent>,
+        delayed_blocks: &flume::Receiver<NetworkEvent>,
+    ) -> Result<NetworkEvent, flume::RecvError> {
+        tokio::select! {
+            event = p2p_messages.recv_async() => event,
+            Ok(event) = delayed_blocks.recv_async() => Ok(event),
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     async fn process_gossipsub_event(
         event: NetworkEvent,
@@ -360,6 +470,7 @@ where
         genesis: Arc<Tipset>,
         message_processing_strategy: PubsubMessageProcessingStrategy,
         block_delay: u64,
+        delayed_blocks: DelayedBlockSender,
     ) -> Result<Option<(FullTipset, PeerId)>, ChainMuxerError> {
         let (tipset, source) = match event {
             NetworkEvent::HelloRequestInbound { source, request } => {
@@ -439,6 +550,24 @@ where
                     metrics::LIBP2P_MESSAGE_TOTAL
                         .with_label_values(&[metrics::values::PUBSUB_BLOCK])
                         .inc();
+                    // Hold back blocks that are slightly ahead of our clock rather than
+                    // rejecting them, and revalidate them once their timestamp is reached
+                    let time_now = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .expect("Retrieved system time before UNIX epoch")
+                        .as_secs();
+                    if let Some(delay) = early_block_delay(b.header.timestamp, time_now) {
+                        let epoch = b.header.epoch;
+                        if delayed_blocks.delay(source, b, delay) {
+                            debug!("Delaying block at epoch {epoch} from {source} by {delay:?}");
+                            metrics::DELAYED_BLOCK_TOTAL.inc();
+                        } else {
+                            debug!(
+                                "Dropping early block at epoch {epoch} from {source}, already pending or too many pending blocks"
+                            );
+                        }
+                        return Ok(None);
+                    }
                     // Assemble full tipset from block
                     let tipset =
                         Self::gossipsub_block_to_full_tipset(b, source, network.clone()).await?;
@@ -449,7 +578,7 @@ where
                         .with_label_values(&[metrics::values::PUBSUB_MESSAGE])
                         .inc();
                     if let PubsubMessageProcessingStrategy::Process = message_processing_strategy {
-                        Self::handle_pubsub_message(mem_pool, m);
+                        Self::handle_pubsub_message(mem_pool, m).await;
                     }
                     return Ok(None);
                 }
@@ -523,6 +652,8 @@ where
 
     fn stateless_node(&self) -> ChainMuxerFuture<(), ChainMuxerError> {
         let p2p_messages = self.net_handler.clone();
+        let delayed_blocks = self.delayed_blocks.clone();
+        let delayed_blocks_sender = self.delayed_blocks_sender.clone();
         let chain_store = self.state_manager.chain_store().clone();
         let network = self.network.clone();
         let genesis = self.genesis.clone();
@@ -532,7 +663,7 @@ where
 
         let future = async move {
             loop {
-                let event = match p2p_messages.recv_async().await {
+                let event = match Self::next_network_event(&p2p_messages, &delayed_blocks).await {
                     Ok(event) => event,
                     Err(why) => {
                         debug!("Receiving event from p2p event stream failed: {why}");
@@ -549,6 +680,7 @@ where
                     genesis.clone(),
                     PubsubMessageProcessingStrategy::DoNotProcess,
                     block_delay,
+                    delayed_blocks_sender.clone(),
                 )
                 .await
                 {
@@ -565,6 +697,8 @@ where
 
     fn evaluate_network_head(&self) -> ChainMuxerFuture<NetworkHeadEvaluation, ChainMuxerError> {
         let p2p_messages = self.net_handler.clone();
+        let delayed_blocks = self.delayed_blocks.clone();
+        let delayed_blocks_sender = self.delayed_blocks_sender.clone();
         let chain_store = self.state_manager.chain_store().clone();
         let network = self.network.clone();
         let genesis = self.genesis.clone();
@@ -576,7 +710,7 @@ where
         let evaluator = async move {
             let mut tipsets = vec![];
             loop {
-                let event = match p2p_messages.recv_async().await {
+                let event = match Self::next_network_event(&p2p_messages, &delayed_blocks).await {
                     Ok(event) => event,
                     Err(why) => {
                         debug!("Receiving event from p2p event stream failed: {}", why);
@@ -593,6 +727,7 @@ where
                     genesis.clone(),
                     PubsubMessageProcessingStrategy::Process,
                     block_delay,
+                    delayed_blocks_sender.clone(),
                 )
                 .await
                 {
@@ -651,12 +786,12 @@ where
         let trs_bad_block_cache = self.bad_blocks.clone();
         let trs_chain_store = self.state_manager.chain_store().clone();
         let trs_network = self.network.clone();
-        let trs_tracker = self.worker_state.clone();
+        let trs_workers = self.workers.clone();
         let trs_genesis = self.genesis.clone();
         let tipset_range_syncer: ChainMuxerFuture<(), ChainMuxerError> = Box::pin(async move {
             let network_head_epoch = network_head.epoch();
             let tipset_range_syncer = match TipsetRangeSyncer::new(
-                trs_tracker,
+                trs_workers,
                 Arc::new(network_head.into_tipset()),
                 local_head,
                 trs_state_manager,
@@ -683,6 +818,8 @@ where
 
         // The stream processor _must_ only error if the stream ends
         let p2p_messages = self.net_handler.clone();
+        let delayed_blocks = self.delayed_blocks.clone();
+        let delayed_blocks_sender = self.delayed_blocks_sender.clone();
         let chain_store = self.state_manager.chain_store().clone();
         let network = self.network.clone();
         let genesis = self.genesis.clone();
@@ -691,7 +828,7 @@ where
         let block_delay = self.state_manager.chain_config().block_delay_secs as u64;
         let stream_processor: ChainMuxerFuture<(), ChainMuxerError> = Box::pin(async move {
             loop {
-                let event = match p2p_messages.recv_async().await {
+                let event = match Self::next_network_event(&p2p_messages, &delayed_blocks).await {
                     Ok(event) => event,
                     Err(why) => {
                         debug!("Receiving event from p2p event stream failed: {}", why);
@@ -708,6 +845,7 @@ where
                     genesis.clone(),
                     PubsubMessageProcessingStrategy::DoNotProcess,
                     block_delay,
+                    delayed_blocks_sender.clone(),
                 )
                 .await
                 {
@@ -748,7 +886,7 @@ where
         let tp_chain_store = self.state_manager.chain_store().clone();
         let tp_bad_block_cache = self.bad_blocks.clone();
         let tp_tipset_receiver = self.tipset_receiver.clone();
-        let tp_tracker = self.worker_state.clone();
+        let tp_workers = self.workers.clone();
         let tp_genesis = self.genesis.clone();
         enum UnexpectedReturnKind {
             TipsetProcessor,
@@ -756,7 +894,7 @@ where
         let tipset_processor: ChainMuxerFuture<UnexpectedReturnKind, ChainMuxerError> =
             Box::pin(async move {
                 TipsetProcessor::new(
-                    tp_tracker,
+                    tp_workers,
                     Box::pin(tp_tipset_receiver.into_stream()),
                     tp_state_manager,
                     tp_network,
@@ -773,6 +911,8 @@ where
         // The stream processor _must_ only error if the p2p event stream ends or if the
         // tipset channel is unexpectedly closed
         let p2p_messages = self.net_handler.clone();
+        let delayed_blocks = self.delayed_blocks.clone();
+        let delayed_blocks_sender = self.delayed_blocks_sender.clone();
         let chain_store = self.state_manager.chain_store().clone();
         let network = self.network.clone();
         let genesis = self.genesis.clone();
@@ -793,7 +933,8 @@ where
                     };
                 }
                 loop {
-                    let event = match p2p_messages.recv_async().await {
+                    let event = match Self::next_network_event(&p2p_messages, &delayed_blocks).await
+                    {
                         Ok(event) => event,
                         Err(why) => {
                             debug!("Receiving event from p2p event stream failed: {}", why);
@@ -810,6 +951,7 @@ where
                         genesis.clone(),
                         PubsubMessageProcessingStrategy::Process,
                         block_delay,
+                        delayed_blocks_sender.clone(),
                     )
                     .await
                     {
@@ -970,8 +1112,8 @@ where
                         self.state = ChainMuxerState::Idle;
                     }
                     Poll::Pending => {
-                        let tp_tracker = self.worker_state.clone();
-                        tp_tracker
+                        self.workers
+                            .main()
                             .write()
                             .set_stage(crate::chain_sync::SyncStage::Complete);
 
@@ -982,3 +1124,26 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::cid::CidCborExt;
+
+    #[test]
+    fn delayed_blocks_are_deduplicated_and_bounded() {
+        let (sender, _receiver) = flume::unbounded();
+        let delayed = DelayedBlockSender::new(sender);
+        let cids = (0..=MAX_DELAYED_BLOCKS as u64)
+            .map(|i| Cid::from_cbor_blake2b256(&i).unwrap())
+            .collect::<Vec<_>>();
+
+        assert!(delayed.reserve(cids[0]));
+        assert!(!delayed.reserve(cids[0]));
+        for cid in &cids[1..MAX_DELAYED_BLOCKS] {
+            assert!(delayed.reserve(*cid));
+        }
+        assert!(!delayed.reserve(cids[MAX_DELAYED_BLOCKS]));
+        assert_eq!(delayed.pending.lock().len(), MAX_DELAYED_BLOCKS);
+    }
+}
@@ -0,0 +1,234 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Checkpoints are known-good blocks of a network. The tipsets of a synced
+//! range which are ancestors of a checkpoint are accepted without verifying
+//! the signatures and the election proofs of their blocks, which are the most
+//! expensive parts of their validation. So is the tipset of the checkpoint
+//! itself, when the checkpoint has all its blocks.
+//!
+//! The checkpoints come, in order of precedence, from:
+//! * the checkpoint set with `forest-cli sync set-checkpoint`,
+//! * the `checkpoints` of the `[sync]` configuration section, if set,
+//! * the known blocks of the network otherwise, see `build/known_blocks.yaml`.
+
+use std::str::FromStr;
+
+use crate::blocks::{Tipset, TipsetKey};
+use crate::chain_sync::SyncConfig;
+use crate::db::{setting_keys::SYNC_CHECKPOINT_KEY, SettingsStore, SettingsStoreExt};
+use crate::lotus_json::lotus_json_with_self;
+use crate::networks::{NetworkChain, KNOWN_BLOCKS};
+use crate::shim::clock::ChainEpoch;
+use cid::Cid;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+
+/// Known-good blocks of a tipset of a network.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+pub struct Checkpoint {
+    pub epoch: ChainEpoch,
+    /// CIDs of some or all the blocks of the tipset, in the order of its key.
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub cids: Vec<Cid>,
+}
+
+lotus_json_with_self!(Checkpoint);
+
+impl Checkpoint {
+    pub fn key(&self) -> TipsetKey {
+        TipsetKey::from_iter(self.cids.iter().copied())
+    }
+
+    /// Returns `true` if `tipset` is at the epoch of the checkpoint and has its blocks.
+    fn is_in(&self, tipset: &Tipset) -> bool {
+        let cids = tipset.cids();
+        tipset.epoch() == self.epoch
+            && !self.cids.is_empty()
+            && self.cids.iter().all(|cid| cids.contains(cid))
+    }
+}
+
+impl From<&Tipset> for Checkpoint {
+    fn from(tipset: &Tipset) -> Self {
+        Self {
+            epoch: tipset.epoch(),
+            cids: tipset.cids(),
+        }
+    }
+}
+
+/// Returns the checkpoints compiled in for `network`, its known blocks.
+pub fn network_checkpoints(network: &NetworkChain) -> Vec<Checkpoint> {
+    let known_blocks = match network {
+        NetworkChain::Mainnet => &KNOWN_BLOCKS.mainnet,
+        NetworkChain::Calibnet => &KNOWN_BLOCKS.calibnet,
+        NetworkChain::Butterflynet | NetworkChain::Devnet(_) => return vec![],
+    };
+    known_blocks
+        .iter()
+        .map(|(epoch, cid)| Checkpoint {
+            epoch: *epoch,
+            cids: vec![Cid::from_str(cid).expect("known blocks must be valid CIDs")],
+        })
+        .collect()
+}
+
+/// Returns the checkpoint set at runtime, if any.
+pub fn load_checkpoint(settings: &dyn SettingsStore) -> anyhow::Result<Option<Checkpoint>> {
+    Ok(settings
+        .read_obj::<Option<Checkpoint>>(SYNC_CHECKPOINT_KEY)?
+        .flatten())
+}
+
+/// Sets the checkpoint which takes precedence over the configured ones.
+pub fn save_checkpoint(
+    settings: &dyn SettingsStore,
+    checkpoint: &Checkpoint,
+) -> anyhow::Result<()> {
+    settings.write_obj(SYNC_CHECKPOINT_KEY, &Some(checkpoint))
+}
+
+/// Returns the checkpoints to sync with, see the [module documentation](self).
+pub(in crate::chain_sync) fn checkpoints(
+    sync_config: &SyncConfig,
+    network: &NetworkChain,
+    settings: &dyn SettingsStore,
+) -> anyhow::Result<Vec<Checkpoint>> {
+    if let Some(checkpoint) = load_checkpoint(settings)? {
+        return Ok(vec![checkpoint]);
+    }
+    Ok(sync_config
+        .checkpoints
+        .clone()
+        .unwrap_or_else(|| network_checkpoints(network)))
+}
+
+/// Returns the epoch up to which the tipsets of `tipsets`, a chain of tipsets,
+/// are trusted: the epoch of the highest checkpoint included in `tipsets`, or
+/// the epoch below when the tipset of the checkpoint has other blocks, whose
+/// parents are the same but which aren't vouched for.
+///
+/// Fails if the chain crosses the epoch of a checkpoint without including it,
+/// as it then is a fork of the checkpointed chain.
+pub(in crate::chain_sync) fn trusted_epoch(
+    tipsets: &[std::sync::Arc<Tipset>],
+    checkpoints: &[Checkpoint],
+) -> Result<Option<ChainEpoch>, Checkpoint> {
+    let (Some(highest), Some(lowest)) = (tipsets.first(), tipsets.last()) else {
+        return Ok(None);
+    };
+    let mut trusted_epoch = None;
+    for checkpoint in checkpoints {
+        if checkpoint.epoch < lowest.epoch() || checkpoint.epoch > highest.epoch() {
+            continue;
+        }
+        let Some(tipset) = tipsets.iter().find(|tipset| checkpoint.is_in(tipset)) else {
+            return Err(checkpoint.clone());
+        };
+        let epoch = if *tipset.key() == checkpoint.key() {
+            checkpoint.epoch
+        } else {
+            checkpoint.epoch - 1
+        };
+        trusted_epoch = trusted_epoch.max(Some(epoch));
+    }
+    Ok(trusted_epoch)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::blocks::{CachingBlockHeader, RawBlockHeader};
+    use crate::shim::address::Address;
+
+    fn chain(len: ChainEpoch, miner: u64) -> Vec<Arc<Tipset>> {
+        let mut parents = TipsetKey::default();
+        let mut tipsets = Vec::new();
+        for epoch in 0..len {
+            let header = CachingBlockHeader::new(RawBlockHeader {
+                miner_address: Address::new_id(miner),
+                epoch,
+                parents: parents.clone(),
+                ..Default::default()
+            });
+            let tipset = Arc::new(Tipset::from(header));
+            parents = tipset.key().clone();
+            tipsets.push(tipset);
+        }
+        // Head first, as synced.
+        tipsets.reverse();
+        tipsets
+    }
+
+    #[test]
+    fn trusted_epoch_of_range() {
+        let tipsets = chain(10, 0);
+        let fork = chain(10, 1);
+        let checkpoint = |epoch: ChainEpoch, chain: &[Arc<Tipset>]| {
+            Checkpoint::from(chain.iter().find(|t| t.epoch() == epoch).unwrap().as_ref())
+        };
+
+        assert_eq!(trusted_epoch(&tipsets, &[]), Ok(None));
+        assert_eq!(
+            trusted_epoch(
+                &tipsets,
+                &[checkpoint(3, &tipsets), checkpoint(6, &tipsets)]
+            ),
+            Ok(Some(6))
+        );
+        // Checkpoints out of the range don't matter.
+        let out_of_range = Checkpoint {
+            epoch: 42,
+            cids: vec![Cid::default()],
+        };
+        assert_eq!(trusted_epoch(&tipsets, &[out_of_range]), Ok(None));
+        // A range forking from a checkpointed chain is rejected.
+        let forked = checkpoint(4, &fork);
+        assert_eq!(trusted_epoch(&tipsets, &[forked.clone()]), Err(forked));
+    }
+
+    #[test]
+    fn partial_checkpoints_trust_the_ancestors() {
+        let tipsets = chain(10, 0);
+        let blocks = (0..2)
+            .map(|miner| {
+                CachingBlockHeader::new(RawBlockHeader {
+                    miner_address: Address::new_id(miner),
+                    epoch: 10,
+                    parents: tipsets[0].key().clone(),
+                    ..Default::default()
+                })
+            })
+            .collect::<Vec<_>>();
+        let head = Arc::new(Tipset::new(blocks.clone()).unwrap());
+        let range = std::iter::once(head.clone())
+            .chain(tipsets)
+            .collect::<Vec<_>>();
+
+        let partial = Checkpoint {
+            epoch: 10,
+            cids: vec![*blocks[0].cid()],
+        };
+        assert_eq!(trusted_epoch(&range, &[partial]), Ok(Some(9)));
+        let full = Checkpoint::from(head.as_ref());
+        assert_eq!(trusted_epoch(&range, &[full]), Ok(Some(10)));
+    }
+
+    #[test]
+    fn networks_have_checkpoints() {
+        assert!(!network_checkpoints(&NetworkChain::Mainnet).is_empty());
+        assert!(!network_checkpoints(&NetworkChain::Calibnet).is_empty());
+        assert!(network_checkpoints(&NetworkChain::Butterflynet).is_empty());
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn checkpoint_toml_roundtrip(checkpoint: Checkpoint) {
+        let serialized = toml::to_string(&checkpoint).unwrap();
+        assert_eq!(checkpoint, toml::from_str(&serialized).unwrap());
+    }
+}
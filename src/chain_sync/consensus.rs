@@ -28,6 +28,10 @@ use tokio::task::JoinSet;
 ///
 /// Common rules for message ordering will be followed, and can be validated
 /// outside by the host system during chain synchronization.
+///
+/// The weight of tipsets and the selection of the heaviest one are given by
+/// the [`Scale`] of the consensus. The implementation the node uses is selected
+/// by the [`crate::networks::ConsensusKind`] of its chain.
 #[async_trait]
 pub trait Consensus: Scale + Debug + Send + Sync + Unpin + 'static {
     type Error: Debug + Display + Send + Sync;
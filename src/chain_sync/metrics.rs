@@ -28,6 +28,24 @@ pub static TIPSET_PROCESSING_TIME: Lazy<Box<Histogram>> = Lazy::new(|| {
         );
     tipset_processing_time
 });
+pub static TIPSET_EXECUTION_TIME: Lazy<Box<Histogram>> = Lazy::new(|| {
+    let tipset_execution_time = Box::new(
+        Histogram::with_opts(HistogramOpts {
+            common_opts: Opts::new(
+                "tipset_execution_time",
+                "Duration of the execution of Tipsets ahead of their validation during sync",
+            ),
+            buckets: vec![],
+        })
+        .expect("Defining the tipset_execution_time metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(tipset_execution_time.clone())
+        .expect(
+            "Registering the tipset_execution_time metric with the metrics registry must succeed",
+        );
+    tipset_execution_time
+});
 pub static BLOCK_VALIDATION_TIME: Lazy<Box<Histogram>> = Lazy::new(|| {
     let block_validation_time = Box::new(
         Histogram::with_opts(HistogramOpts {
@@ -100,6 +118,21 @@ pub static INVALID_TIPSET_TOTAL: Lazy<Box<GenericCounter<AtomicU64>>> = Lazy::ne
         );
     invalid_tipset_total
 });
+pub static DELAYED_BLOCK_TOTAL: Lazy<Box<GenericCounter<AtomicU64>>> = Lazy::new(|| {
+    let delayed_block_total = Box::new(
+        GenericCounter::<AtomicU64>::new(
+            "delayed_block_total",
+            "Total number of early blocks received over gossipsub and held back for revalidation",
+        )
+        .expect("Defining the delayed_block_total metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(delayed_block_total.clone())
+        .expect(
+            "Registering the delayed_block_total metric with the metrics registry must succeed",
+        );
+    delayed_block_total
+});
 pub static TIPSET_RANGE_SYNC_FAILURE_TOTAL: Lazy<Box<GenericCounter<AtomicU64>>> = Lazy::new(
     || {
         let tipset_range_sync_failure_total = Box::new(
@@ -258,6 +291,7 @@ mod tests {
     #[test]
     fn metrics_defined_and_registered() {
         test_counter!(TIPSET_PROCESSING_TIME);
+        test_counter!(TIPSET_EXECUTION_TIME);
         test_counter_vec!(LIBP2P_MESSAGE_TOTAL);
         test_counter!(INVALID_TIPSET_TOTAL);
         test_counter!(TIPSET_RANGE_SYNC_FAILURE_TOTAL);
@@ -3,6 +3,7 @@
 
 mod bad_block_cache;
 mod chain_muxer;
+pub mod checkpoints;
 pub mod consensus;
 mod metrics;
 mod network_context;
@@ -13,6 +14,8 @@ mod validation;
 pub use self::{
     bad_block_cache::BadBlockCache,
     chain_muxer::{ChainMuxer, SyncConfig},
+    checkpoints::Checkpoint,
     consensus::collect_errs,
-    sync_state::{SyncStage, SyncState},
+    sync_state::{SyncStage, SyncState, SyncWorkers},
+    validation::TipsetValidator,
 };
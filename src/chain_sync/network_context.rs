@@ -13,8 +13,8 @@ use std::{
 use crate::blocks::{FullTipset, Tipset, TipsetKey};
 use crate::libp2p::{
     chain_exchange::{
-        ChainExchangeRequest, ChainExchangeResponse, CompactedMessages, TipsetBundle, HEADERS,
-        MESSAGES,
+        ChainExchangeRequest, ChainExchangeResponse, ChainExchangeResponseStatus,
+        CompactedMessages, TipsetBundle, HEADERS, MESSAGES,
     },
     hello::{HelloRequest, HelloResponse},
     rpc::RequestResponseError,
@@ -237,14 +237,18 @@ where
         let lookup_failures = Arc::new(AtomicU64::new(0));
         let chain_exchange_result = match peer_id {
             // Specific peer is given to send request, send specifically to that peer.
-            Some(id) => Self::chain_exchange_request(
-                self.peer_manager.clone(),
-                self.network_send.clone(),
+            Some(id) => validate_chain_exchange_response(
+                &self.peer_manager,
                 id,
-                request,
-            )
-            .await?
-            .into_result()?,
+                Self::chain_exchange_request(
+                    self.peer_manager.clone(),
+                    self.network_send.clone(),
+                    id,
+                    request,
+                )
+                .await?,
+                request_len,
+            )?,
             None => {
                 // No specific peer set, send requests to a shuffled set of top peers until
                 // a request succeeds.
@@ -259,7 +263,7 @@ where
                     let lookup_failures = lookup_failures.clone();
                     batch.add(async move {
                         match Self::chain_exchange_request(
-                            peer_manager,
+                            peer_manager.clone(),
                             network_send,
                             peer_id,
                             request,
@@ -267,7 +271,12 @@ where
                         .await
                         {
                             Ok(chain_exchange_result) => {
-                                match chain_exchange_result.into_result::<T>() {
+                                match validate_chain_exchange_response::<T>(
+                                    &peer_manager,
+                                    peer_id,
+                                    chain_exchange_result,
+                                    request_len,
+                                ) {
                                     Ok(r) => Ok(r),
                                     Err(e) => {
                                         lookup_failures.fetch_add(1, Ordering::Relaxed);
@@ -353,6 +362,9 @@ where
             Ok(Ok(Ok(bs_res))) => {
                 // Successful response
                 peer_manager.log_success(peer_id, res_duration);
+                if let Ok(bytes) = fvm_ipld_encoding::to_vec(&bs_res) {
+                    peer_manager.log_served_bytes(peer_id, bytes.len() as u64);
+                }
                 debug!("Succeeded: ChainExchange Request to {peer_id}");
                 Ok(bs_res)
             }
@@ -414,6 +426,42 @@ where
     }
 }
 
+/// Converts the tipset bundles of a chain exchange response. Responses with
+/// malformed bundles, or with more or fewer tipsets than they should have, are
+/// logged as invalid to the peer manager, which quarantines repeat offenders.
+fn validate_chain_exchange_response<T>(
+    peer_manager: &PeerManager,
+    peer_id: PeerId,
+    response: ChainExchangeResponse,
+    request_len: u64,
+) -> Result<Vec<T>, String>
+where
+    T: TryFrom<TipsetBundle, Error = String>,
+{
+    let status = response.status;
+    let result = match response.into_result::<T>() {
+        // Even partial responses must include the requested head.
+        Ok(chain) if chain.is_empty() => Err("Truncated response: no tipsets".to_string()),
+        Ok(chain) if chain.len() as u64 > request_len => Err(format!(
+            "Invalid response: {} tipsets for a request of {request_len}",
+            chain.len()
+        )),
+        Ok(chain) => return Ok(chain),
+        // Conversion errors in a successful response come from malformed bundles.
+        Err(e)
+            if matches!(
+                status,
+                ChainExchangeResponseStatus::Success | ChainExchangeResponseStatus::PartialResponse
+            ) =>
+        {
+            Err(e)
+        }
+        Err(e) => return Err(e),
+    };
+    peer_manager.log_invalid_response(peer_id);
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
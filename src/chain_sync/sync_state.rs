@@ -1,6 +1,7 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
 
 use crate::blocks::Tipset;
@@ -8,6 +9,12 @@ use crate::shim::clock::ChainEpoch;
 #[cfg(test)]
 use chrono::TimeZone;
 use chrono::{DateTime, Duration, Utc};
+use nonempty::NonEmpty;
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tokio::sync::broadcast;
+
+/// Number of stage transitions buffered for slow subscribers.
+const STAGE_TRANSITIONS_CAPACITY: usize = 64;
 
 /// Current state of the `ChainSyncer` using the `ChainExchange` protocol.
 #[derive(PartialEq, Eq, Debug, Clone, Copy, strum::Display, strum::EnumString)]
@@ -22,6 +29,9 @@ pub enum SyncStage {
     /// Persisting headers on chain from heaviest to genesis.
     #[strum(to_string = "persisting headers")]
     PersistHeaders,
+    /// Fetching the messages of the tipsets from the network.
+    #[strum(to_string = "fetching messages")]
+    FetchingMessages,
     /// Syncing messages and performing state transitions.
     #[strum(to_string = "message sync")]
     Messages,
@@ -40,11 +50,22 @@ impl Default for SyncStage {
     }
 }
 
+impl SyncStage {
+    /// Returns `true` while a worker is syncing.
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self,
+            Self::Headers | Self::PersistHeaders | Self::FetchingMessages | Self::Messages
+        )
+    }
+}
+
 /// State of the node's syncing process.
 /// This state is different from the general state of the `ChainSync` process.
 #[derive(Clone, Debug, Default, PartialEq)]
 #[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
 pub struct SyncState {
+    worker_id: u64,
     base: Option<Arc<Tipset>>,
     target: Option<Arc<Tipset>>,
 
@@ -71,6 +92,7 @@ impl SyncState {
     /// start time.
     pub fn init(&mut self, base: Arc<Tipset>, target: Arc<Tipset>) {
         *self = Self {
+            worker_id: self.worker_id,
             target: Some(target),
             base: Some(base),
             start: Some(Utc::now()),
@@ -78,6 +100,11 @@ impl SyncState {
         }
     }
 
+    /// Returns the identifier of the worker syncing
+    pub fn worker_id(&self) -> u64 {
+        self.worker_id
+    }
+
     /// Get the current [`SyncStage`] of the `Syncer`
     pub fn stage(&self) -> SyncStage {
         self.stage
@@ -131,6 +158,139 @@ impl SyncState {
     }
 }
 
+/// Syncing state of a chain sync worker. Its stage transitions are broadcast to
+/// the subscribers of the [`SyncWorkers`] it belongs to.
+#[derive(Clone)]
+pub struct WorkerState {
+    state: Arc<RwLock<SyncState>>,
+    transitions: broadcast::Sender<SyncState>,
+}
+
+impl WorkerState {
+    fn new(worker_id: u64, transitions: broadcast::Sender<SyncState>) -> Self {
+        Self {
+            state: Arc::new(RwLock::new(SyncState {
+                worker_id,
+                ..Default::default()
+            })),
+            transitions,
+        }
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, SyncState> {
+        self.state.read()
+    }
+
+    pub fn write(&self) -> WorkerStateGuard<'_> {
+        let state = self.state.write();
+        WorkerStateGuard {
+            stage: state.stage,
+            state,
+            transitions: &self.transitions,
+        }
+    }
+}
+
+impl Default for WorkerState {
+    /// Worker state of its own, whose transitions aren't observed.
+    fn default() -> Self {
+        Self::new(0, broadcast::channel(1).0)
+    }
+}
+
+/// Write access to a [`WorkerState`], broadcasting its new state on drop if
+/// its stage changed.
+pub struct WorkerStateGuard<'a> {
+    state: RwLockWriteGuard<'a, SyncState>,
+    stage: SyncStage,
+    transitions: &'a broadcast::Sender<SyncState>,
+}
+
+impl Deref for WorkerStateGuard<'_> {
+    type Target = SyncState;
+
+    fn deref(&self) -> &Self::Target {
+        &self.state
+    }
+}
+
+impl DerefMut for WorkerStateGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.state
+    }
+}
+
+impl Drop for WorkerStateGuard<'_> {
+    fn drop(&mut self) {
+        if self.state.stage != self.stage {
+            // There may be no subscriber.
+            let _ = self.transitions.send(self.state.clone());
+        }
+    }
+}
+
+/// Syncing states of the chain sync workers. Worker `0` syncs the ranges of
+/// tipsets towards the network head, and the other workers sync the tipsets
+/// that are added to the range being synced.
+#[derive(Clone)]
+pub struct SyncWorkers {
+    workers: Arc<RwLock<Vec<WorkerState>>>,
+    transitions: broadcast::Sender<SyncState>,
+}
+
+impl Default for SyncWorkers {
+    fn default() -> Self {
+        let transitions = broadcast::channel(STAGE_TRANSITIONS_CAPACITY).0;
+        Self {
+            workers: Arc::new(RwLock::new(vec![WorkerState::new(0, transitions.clone())])),
+            transitions,
+        }
+    }
+}
+
+impl SyncWorkers {
+    /// Returns the state of the worker syncing tipset ranges.
+    pub fn main(&self) -> WorkerState {
+        self.workers.read()[0].clone()
+    }
+
+    /// Returns the state of an idle worker, or of a new one if all of them are
+    /// syncing, initialized to sync from `base` to `target`.
+    pub fn acquire(&self, base: Arc<Tipset>, target: Arc<Tipset>) -> WorkerState {
+        let mut workers = self.workers.write();
+        let worker = match workers
+            .iter()
+            .skip(1)
+            .find(|worker| !worker.read().stage().is_active())
+        {
+            Some(worker) => worker.clone(),
+            None => {
+                let worker = WorkerState::new(workers.len() as u64, self.transitions.clone());
+                workers.push(worker.clone());
+                worker
+            }
+        };
+        worker.write().init(base, target);
+        worker
+    }
+
+    /// Returns the states of all the workers, by worker identifier.
+    pub fn states(&self) -> NonEmpty<SyncState> {
+        let states = self
+            .workers
+            .read()
+            .iter()
+            .map(|worker| worker.read().clone())
+            .collect::<Vec<_>>();
+        NonEmpty::from_vec(states).expect("there is always a main worker")
+    }
+
+    /// Subscribes to the states of the workers, as they change stage.
+    pub fn subscribe(&self) -> broadcast::Receiver<SyncState> {
+        self.transitions.subscribe()
+    }
+}
+
 mod lotus_json {
     use super::SyncState;
     use crate::{blocks::Tipset, chain_sync::SyncStage, lotus_json::*};
@@ -143,12 +303,15 @@ mod lotus_json {
     #[derive(Serialize, Deserialize)]
     #[serde(rename_all = "PascalCase")]
     pub struct SyncStateLotusJson {
+        #[serde(rename = "WorkerID", default)]
+        worker_id: LotusJson<u64>,
         #[serde(skip_serializing_if = "LotusJson::is_none", default)]
         base: LotusJson<Option<Tipset>>,
         #[serde(skip_serializing_if = "LotusJson::is_none", default)]
         target: LotusJson<Option<Tipset>>,
 
         stage: LotusJson<SyncStage>,
+        #[serde(rename = "Height", alias = "Epoch")]
         epoch: LotusJson<i64>,
 
         #[serde(skip_serializing_if = "LotusJson::is_none", default)]
@@ -164,9 +327,10 @@ mod lotus_json {
         fn snapshots() -> Vec<(serde_json::Value, Self)> {
             vec![(
                 json!({
-                    "Epoch": 0,
+                    "WorkerID": 0,
+                    "Height": 0,
                     "Message": "",
-                    "Stage": "header sync",
+                    "Stage": 1,
                 }),
                 Self::default(),
             )]
@@ -174,6 +338,7 @@ mod lotus_json {
 
         fn into_lotus_json(self) -> Self::LotusJson {
             let Self {
+                worker_id,
                 base,
                 target,
                 stage,
@@ -183,6 +348,7 @@ mod lotus_json {
                 message,
             } = self;
             Self::LotusJson {
+                worker_id: worker_id.into(),
                 base: base.as_deref().cloned().into(),
                 target: target.as_deref().cloned().into(),
                 stage: stage.into(),
@@ -195,6 +361,7 @@ mod lotus_json {
 
         fn from_lotus_json(lotus_json: Self::LotusJson) -> Self {
             let Self::LotusJson {
+                worker_id,
                 base,
                 target,
                 stage,
@@ -204,6 +371,7 @@ mod lotus_json {
                 message,
             } = lotus_json;
             Self {
+                worker_id: worker_id.into_inner(),
                 base: base.into_inner().map(Arc::new),
                 target: target.into_inner().map(Arc::new),
                 stage: stage.into_inner(),
@@ -227,3 +395,44 @@ mod lotus_json {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_pool::test_provider::mock_tipset;
+
+    #[test]
+    fn workers_are_reused_once_idle() {
+        let workers = SyncWorkers::default();
+        let first = workers.acquire(mock_tipset(0, 0), mock_tipset(0, 1));
+        let second = workers.acquire(mock_tipset(0, 0), mock_tipset(0, 1));
+        assert_eq!(first.read().worker_id(), 1);
+        assert_eq!(second.read().worker_id(), 2);
+
+        first.write().set_stage(SyncStage::Complete);
+        let third = workers.acquire(mock_tipset(0, 1), mock_tipset(0, 2));
+        assert_eq!(third.read().worker_id(), 1);
+        assert_eq!(third.read().stage(), SyncStage::Headers);
+
+        let ids = workers
+            .states()
+            .iter()
+            .map(SyncState::worker_id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [0, 1, 2]);
+    }
+
+    #[test]
+    fn stage_transitions_are_broadcast() {
+        let workers = SyncWorkers::default();
+        let mut transitions = workers.subscribe();
+
+        workers.main().write().set_epoch(42);
+        assert!(transitions.try_recv().is_err());
+
+        workers.main().write().set_stage(SyncStage::Messages);
+        let state = transitions.try_recv().unwrap();
+        assert_eq!(state.stage(), SyncStage::Messages);
+        assert_eq!(state.epoch(), 42);
+    }
+}
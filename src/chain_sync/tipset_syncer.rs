@@ -8,17 +8,25 @@ use std::{
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use crate::chain::{persist_objects, ChainStore, Error as ChainStoreError};
 use crate::libp2p::chain_exchange::TipsetBundle;
 use crate::message::{valid_for_block_inclusion, Message as MessageTrait};
-use crate::networks::Height;
+use crate::networks::{ConsensusKind, Height};
 use crate::shim::clock::ALLOWABLE_CLOCK_DRIFT;
 use crate::shim::{
-    address::Address, clock::ChainEpoch, crypto::verify_bls_aggregate, econ::BLOCK_GAS_LIMIT,
-    gas::price_list_by_network_version, message::Message, state_tree::StateTree,
+    address::Address,
+    clock::ChainEpoch,
+    crypto::{
+        verification_pool::{self, Verification},
+        verify_bls_aggregate,
+    },
+    econ::BLOCK_GAS_LIMIT,
+    gas::price_list_by_network_version,
+    message::Message,
+    state_tree::StateTree,
 };
 use crate::state_manager::{is_valid_for_sending, Error as StateManagerError, StateManager};
 use crate::utils::io::WithProgressRaw;
@@ -39,8 +47,13 @@ use tokio::task::JoinSet;
 use tracing::{debug, error, info, trace, warn};
 
 use crate::chain_sync::{
-    bad_block_cache::BadBlockCache, consensus::collect_errs, metrics,
-    network_context::SyncNetworkContext, sync_state::SyncStage, validation::TipsetValidator,
+    bad_block_cache::BadBlockCache,
+    checkpoints,
+    consensus::{collect_errs, Consensus as _},
+    metrics,
+    network_context::SyncNetworkContext,
+    sync_state::{SyncStage, SyncWorkers, WorkerState},
+    validation::TipsetValidator,
 };
 
 const MAX_TIPSETS_TO_REQUEST: u64 = 100;
@@ -105,6 +118,8 @@ pub enum TipsetRangeSyncerError {
     TipsetParentNotFound(ChainStoreError),
     #[error("Consensus error: {0}")]
     ConsensusError(FilecoinConsensusError),
+    #[error("Chain conflicts with the checkpoint at epoch {0}: {1}")]
+    CheckpointConflict(ChainEpoch, TipsetKey),
 }
 
 impl<T> From<flume::SendError<T>> for TipsetRangeSyncerError {
@@ -232,7 +247,7 @@ impl TipsetGroup {
 /// range which will be synced into the Chain Store.
 pub(in crate::chain_sync) struct TipsetProcessor<DB> {
     state: TipsetProcessorState<DB>,
-    tracker: crate::chain_sync::chain_muxer::WorkerState,
+    workers: SyncWorkers,
     /// Tipsets pushed into this stream _must_ be validated beforehand by the
     /// `TipsetValidator`
     tipsets: Pin<Box<dyn futures::Stream<Item = Arc<Tipset>> + Send>>,
@@ -249,7 +264,7 @@ where
 {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
-        tracker: crate::chain_sync::chain_muxer::WorkerState,
+        workers: SyncWorkers,
         tipsets: Pin<Box<dyn futures::Stream<Item = Arc<Tipset>> + Send>>,
         state_manager: Arc<StateManager<DB>>,
         network: SyncNetworkContext<DB>,
@@ -259,7 +274,7 @@ where
     ) -> Self {
         Self {
             state: TipsetProcessorState::Idle,
-            tracker,
+            workers,
             tipsets,
             state_manager,
             network,
@@ -274,7 +289,7 @@ where
         let chain_store = self.chain_store.clone();
         let network = self.network.clone();
         let bad_block_cache = self.bad_block_cache.clone();
-        let tracker = self.tracker.clone();
+        let workers = self.workers.clone();
         let genesis = self.genesis.clone();
 
         // Define the low end of the range
@@ -286,7 +301,7 @@ where
         }
 
         let mut tipset_range_syncer = TipsetRangeSyncer::new(
-            tracker,
+            workers,
             proposed_head,
             current_head,
             state_manager,
@@ -587,6 +602,7 @@ pub(in crate::chain_sync) struct TipsetRangeSyncer<DB> {
     chain_store: Arc<ChainStore<DB>>,
     bad_block_cache: Arc<BadBlockCache>,
     genesis: Arc<Tipset>,
+    workers: SyncWorkers,
 }
 
 impl<DB> TipsetRangeSyncer<DB>
@@ -595,7 +611,7 @@ where
 {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
-        tracker: crate::chain_sync::chain_muxer::WorkerState,
+        workers: SyncWorkers,
         proposed_head: Arc<Tipset>,
         current_head: Arc<Tipset>,
         state_manager: Arc<StateManager<DB>>,
@@ -615,7 +631,7 @@ where
         tipset_tasks.spawn(sync_tipset_range(
             proposed_head.clone(),
             current_head.clone(),
-            tracker,
+            workers.main(),
             // Casting from i64 -> u64 is safe because we ensured that
             // the value is greater than 0
             tipset_range_length as u64,
@@ -637,6 +653,7 @@ where
             chain_store,
             bad_block_cache,
             genesis,
+            workers,
         })
     }
 
@@ -666,7 +683,11 @@ where
         // Keep track of tipsets included
         self.tipsets_included.insert(new_key);
 
+        let tracker = self
+            .workers
+            .acquire(self.chain_store.heaviest_tipset(), additional_head.clone());
         self.tipset_tasks.spawn(sync_tipset(
+            tracker,
             additional_head,
             self.state_manager.clone(),
             self.chain_store.clone(),
@@ -718,7 +739,7 @@ where
 async fn sync_tipset_range<DB: Blockstore + Sync + Send + 'static>(
     proposed_head: Arc<Tipset>,
     current_head: Arc<Tipset>,
-    tracker: crate::chain_sync::chain_muxer::WorkerState,
+    tracker: WorkerState,
     tipset_range_length: u64,
     state_manager: Arc<StateManager<DB>>,
     chain_store: Arc<ChainStore<DB>>,
@@ -749,7 +770,7 @@ async fn sync_tipset_range<DB: Blockstore + Sync + Send + 'static>(
     };
 
     // Persist the blocks from the synced Tipsets into the store
-    tracker.write().set_stage(SyncStage::Headers);
+    tracker.write().set_stage(SyncStage::PersistHeaders);
     let headers: Vec<&CachingBlockHeader> = parent_tipsets
         .iter()
         .flat_map(|t| t.block_headers())
@@ -760,7 +781,7 @@ async fn sync_tipset_range<DB: Blockstore + Sync + Send + 'static>(
     };
 
     //  Sync and validate messages from the tipsets
-    tracker.write().set_stage(SyncStage::Messages);
+    tracker.write().set_stage(SyncStage::FetchingMessages);
     if let Err(why) = sync_messages_check_state(
         tracker.clone(),
         state_manager,
@@ -801,8 +822,9 @@ async fn sync_tipset_range<DB: Blockstore + Sync + Send + 'static>(
 /// Download headers between the proposed head and the current one available
 /// locally. If they turn out to be on different forks, download more headers up
 /// to a certain limit to try to find a common ancestor.
+#[tracing::instrument(skip_all, fields(from = current_head.epoch(), to = proposed_head.epoch()))]
 async fn sync_headers_in_reverse<DB: Blockstore + Sync + Send + 'static>(
-    tracker: crate::chain_sync::chain_muxer::WorkerState,
+    tracker: WorkerState,
     tipset_range_length: u64,
     proposed_head: Arc<Tipset>,
     current_head: &Tipset,
@@ -919,6 +941,7 @@ async fn sync_headers_in_reverse<DB: Blockstore + Sync + Send + 'static>(
 
 #[allow(clippy::too_many_arguments)]
 async fn sync_tipset<DB: Blockstore + Sync + Send + 'static>(
+    tracker: WorkerState,
     proposed_head: Arc<Tipset>,
     state_manager: Arc<StateManager<DB>>,
     chain_store: Arc<ChainStore<DB>>,
@@ -927,15 +950,19 @@ async fn sync_tipset<DB: Blockstore + Sync + Send + 'static>(
     genesis: Arc<Tipset>,
 ) -> Result<(), TipsetRangeSyncerError> {
     // Persist the blocks from the proposed tipsets into the store
-    persist_objects(
+    tracker.write().set_stage(SyncStage::PersistHeaders);
+    if let Err(why) = persist_objects(
         chain_store.blockstore(),
         proposed_head.block_headers().iter(),
-    )?;
+    ) {
+        tracker.write().error(why.to_string());
+        return Err(why.into());
+    }
 
     // Sync and validate messages from the tipsets
+    tracker.write().set_stage(SyncStage::FetchingMessages);
     if let Err(e) = sync_messages_check_state(
-        // Include a dummy WorkerState
-        crate::chain_sync::chain_muxer::WorkerState::default(),
+        tracker.clone(),
         state_manager,
         network,
         chain_store.clone(),
@@ -947,6 +974,7 @@ async fn sync_tipset<DB: Blockstore + Sync + Send + 'static>(
     .await
     {
         warn!("Sync messages check state failed for single tipset");
+        tracker.write().error(e.to_string());
         return Err(e);
     }
 
@@ -960,8 +988,10 @@ async fn sync_tipset<DB: Blockstore + Sync + Send + 'static>(
             proposed_head.key(),
             why
         );
+        tracker.write().error(why.to_string());
         return Err(why.into());
     };
+    tracker.write().set_stage(SyncStage::Complete);
     Ok(())
 }
 
@@ -1027,9 +1057,20 @@ async fn fetch_batch<DB: Blockstore>(
 /// Going forward along the tipsets, try to load the messages in them from the
 /// `BlockStore`, or download them from the network, then validate the full
 /// tipset on each epoch.
+///
+/// This runs as a pipeline of stages connected by bounded channels: the
+/// messages of the next batches are fetched while the tipsets are executed in
+/// order, and the fetched tipsets are validated by up to
+/// [`SyncConfig::sync_workers`](crate::chain_sync::SyncConfig::sync_workers)
+/// concurrent workers. Validating a tipset needs the state of its parent only,
+/// so it overlaps with the execution of the tipset itself.
+///
+/// The tipsets up to the highest [checkpoint](checkpoints) of the range are
+/// validated without their block signatures and election proofs.
 #[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip_all, fields(tipsets = tipsets.len()))]
 async fn sync_messages_check_state<DB: Blockstore + Send + Sync + 'static>(
-    tracker: crate::chain_sync::chain_muxer::WorkerState,
+    tracker: WorkerState,
     state_manager: Arc<StateManager<DB>>,
     network: SyncNetworkContext<DB>,
     chainstore: Arc<ChainStore<DB>>,
@@ -1039,44 +1080,94 @@ async fn sync_messages_check_state<DB: Blockstore + Send + Sync + 'static>(
     invalid_block_strategy: InvalidBlockStrategy,
 ) -> Result<(), TipsetRangeSyncerError> {
     let request_window = state_manager.sync_config().request_window;
+    let sync_workers = state_manager.sync_config().sync_workers();
     let db = chainstore.blockstore();
+    // The state of the head of the range isn't needed to validate the range.
+    let head_key = tipsets.first().map(|tipset| tipset.key().clone());
+    let checkpoints = checkpoints::checkpoints(
+        state_manager.sync_config(),
+        &state_manager.chain_config().network,
+        chainstore.settings().as_ref(),
+    )
+    .map_err(|e| TipsetRangeSyncerError::Calculation(format!("Loading checkpoints failed: {e}")))?;
+    let trusted_epoch =
+        checkpoints::trusted_epoch(&tipsets, &checkpoints).map_err(|checkpoint| {
+            TipsetRangeSyncerError::CheckpointConflict(checkpoint.epoch, checkpoint.key())
+        })?;
 
-    // Stream through the tipsets from lowest epoch to highest epoch
-    stream::iter(tipsets.into_iter().rev())
-        // Chunk tipsets in batches (default batch size is 8)
-        .chunks(request_window)
-        // Request batches from the p2p network
-        .map(|batch| fetch_batch(batch, &network, db))
-        // run 64 batches concurrently
-        .buffered(64)
-        // validate each full tipset in each batch
-        .try_for_each(|batch| async {
+    let (fetched_tx, fetched_rx) = flume::bounded(sync_workers);
+
+    let execute = async {
+        let fetched_tx = fetched_tx;
+        // Stream through the tipsets from lowest epoch to highest epoch
+        let mut batches = stream::iter(tipsets.into_iter().rev())
+            // Chunk tipsets in batches (default batch size is 8)
+            .chunks(request_window)
+            // Request batches from the p2p network
+            .map(|batch| fetch_batch(batch, &network, db))
+            // run 64 batches concurrently
+            .buffered(64);
+        // After a failed execution, the following tipsets aren't executed
+        // ahead of their validation, which reports the failure.
+        let mut execute_ahead = true;
+        while let Some(batch) = batches.try_next().await? {
+            tracker.write().set_stage(SyncStage::Messages);
             for full_tipset in batch {
-                let current_epoch = full_tipset.epoch();
-                let timer = metrics::TIPSET_PROCESSING_TIME.start_timer();
-                validate_tipset(
-                    state_manager.clone(),
-                    &chainstore,
-                    bad_block_cache,
-                    full_tipset.clone(),
-                    genesis,
-                    invalid_block_strategy,
-                )
-                .await?;
-                drop(timer);
-                chainstore.set_heaviest_tipset(Arc::new(full_tipset.into_tipset()))?;
-                tracker.write().set_epoch(current_epoch);
-                metrics::LAST_VALIDATED_TIPSET_EPOCH.set(current_epoch as u64);
+                let tipset = Arc::new(Tipset::from(full_tipset.clone()));
+                fetched_tx
+                    .send_async((full_tipset, Arc::clone(&tipset)))
+                    .await?;
+                if execute_ahead && Some(tipset.key()) != head_key.as_ref() {
+                    let _timer = metrics::TIPSET_EXECUTION_TIME.start_timer();
+                    if let Err(e) = state_manager.tipset_state(&tipset).await {
+                        warn!("Executing tipset at epoch {} failed: {e}", tipset.epoch());
+                        execute_ahead = false;
+                    }
+                }
             }
-            Ok(())
+        }
+        Ok::<_, TipsetRangeSyncerError>(())
+    };
+
+    let validate = fetched_rx
+        .into_stream()
+        // validate the tipsets concurrently
+        .map(|(full_tipset, tipset): (FullTipset, Arc<Tipset>)| async {
+            let timer = metrics::TIPSET_PROCESSING_TIME.start_timer();
+            let trusted = trusted_epoch.is_some_and(|epoch| tipset.epoch() <= epoch);
+            validate_tipset(
+                state_manager.clone(),
+                &chainstore,
+                bad_block_cache,
+                full_tipset,
+                genesis,
+                invalid_block_strategy,
+                trusted,
+            )
+            .await?;
+            drop(timer);
+            Ok::<_, TipsetRangeSyncerError>(tipset)
         })
-        .await
+        .buffered(sync_workers)
+        // and move the head forward in order
+        .try_for_each(|tipset| async {
+            let current_epoch = tipset.epoch();
+            chainstore.set_heaviest_tipset(tipset)?;
+            tracker.write().set_epoch(current_epoch);
+            metrics::LAST_VALIDATED_TIPSET_EPOCH.set(current_epoch as u64);
+            Ok(())
+        });
+
+    tokio::try_join!(execute, validate)?;
+    Ok(())
 }
 
 /// Validates full blocks in the tipset in parallel (since the messages are not
 /// executed), adding the successful ones to the tipset tracker, and the failed
 /// ones to the bad block cache, depending on strategy. Any bad block fails
-/// validation.
+/// validation. The block signatures and election proofs of `trusted` tipsets
+/// aren't verified.
+#[tracing::instrument(skip_all, fields(epoch = full_tipset.epoch(), trusted))]
 async fn validate_tipset<DB: Blockstore + Send + Sync + 'static>(
     state_manager: Arc<StateManager<DB>>,
     chainstore: &ChainStore<DB>,
@@ -1084,6 +1175,7 @@ async fn validate_tipset<DB: Blockstore + Send + Sync + 'static>(
     full_tipset: FullTipset,
     genesis: &Tipset,
     invalid_block_strategy: InvalidBlockStrategy,
+    trusted: bool,
 ) -> Result<(), TipsetRangeSyncerError> {
     if full_tipset.key().eq(genesis.key()) {
         trace!("Skipping genesis tipset validation");
@@ -1103,7 +1195,8 @@ async fn validate_tipset<DB: Blockstore + Send + Sync + 'static>(
     debug!("Tipset keys: {:?}", full_tipset_key.cids);
 
     for b in blocks {
-        let validation_fn = tokio::task::spawn(validate_block(state_manager.clone(), Arc::new(b)));
+        let validation_fn =
+            tokio::task::spawn(validate_block(state_manager.clone(), Arc::new(b), trusted));
         validations.push(validation_fn);
     }
 
@@ -1156,11 +1249,15 @@ async fn validate_tipset<DB: Blockstore + Send + Sync + 'static>(
 /// * Checking that the messages in the block correspond to the agreed upon
 ///   total ordering
 /// * That the block is a deterministic derivative of the underlying consensus
+///
+/// The block signature and the consensus specific validation are skipped for
+/// `trusted` blocks, which are ancestors of a checkpoint.
 async fn validate_block<DB: Blockstore + Sync + Send + 'static>(
     state_manager: Arc<StateManager<DB>>,
     block: Arc<Block>,
+    trusted: bool,
 ) -> Result<Arc<Block>, (Cid, TipsetRangeSyncerError)> {
-    let consensus = FilecoinConsensus::new(state_manager.beacon_schedule());
+    let consensus = state_manager.chain_config().consensus;
     trace!(
         "Validating block: epoch = {}, weight = {}, key = {}",
         block.header().epoch,
@@ -1254,9 +1351,10 @@ async fn validate_block<DB: Blockstore + Sync + Send + 'static>(
         let _timer = metrics::BLOCK_VALIDATION_TASKS_TIME
             .with_label_values(&[metrics::values::PARENT_WEIGHT_CAL])
             .start_timer();
-        let calc_weight = fil_cns::weight(&v_block_store, &v_base_tipset).map_err(|e| {
-            TipsetRangeSyncerError::Calculation(format!("Error calculating weight: {e}"))
-        })?;
+        let calc_weight =
+            fil_cns::weight(consensus, &v_block_store, &v_base_tipset).map_err(|e| {
+                TipsetRangeSyncerError::Calculation(format!("Error calculating weight: {e}"))
+            })?;
         if weight != calc_weight {
             return Err(TipsetRangeSyncerError::Validation(format!(
                 "Parent weight doesn't match: {weight} (header), {calc_weight} (computed)"
@@ -1294,32 +1392,40 @@ async fn validate_block<DB: Blockstore + Sync + Send + 'static>(
         Ok(())
     }));
 
-    // Block signature check
-    let v_block = block.clone();
-    validations.push(tokio::task::spawn_blocking(move || {
-        let _timer = metrics::BLOCK_VALIDATION_TASKS_TIME
-            .with_label_values(&[metrics::values::BLOCK_SIGNATURE_CHECK])
-            .start_timer();
-        v_block.header().verify_signature_against(&work_addr)?;
-        Ok(())
-    }));
-
-    let v_block = block.clone();
-    validations.push(tokio::task::spawn(async move {
-        consensus
-            .validate_block(state_manager, v_block)
-            .map_err(|errs| {
-                // NOTE: Concatenating errors here means the wrapper type of error
-                // never surfaces, yet we always pay the cost of the generic argument.
-                // But there's no reason `validate_block` couldn't return a list of all
-                // errors instead of a single one that has all the error messages,
-                // removing the caller's ability to distinguish between them.
-                let errs = errs.map(TipsetRangeSyncerError::ConsensusError);
-
-                TipsetRangeSyncerError::concat(errs)
-            })
-            .await
-    }));
+    // The signatures and election proofs of the ancestors of a checkpoint are
+    // trusted.
+    if !trusted {
+        // Block signature check
+        let v_block = block.clone();
+        validations.push(tokio::task::spawn_blocking(move || {
+            let _timer = metrics::BLOCK_VALIDATION_TASKS_TIME
+                .with_label_values(&[metrics::values::BLOCK_SIGNATURE_CHECK])
+                .start_timer();
+            v_block.header().verify_signature_against(&work_addr)?;
+            Ok(())
+        }));
+
+        // Instant consensus has no rules of its own to validate blocks with.
+        if consensus == ConsensusKind::Ec {
+            let consensus = FilecoinConsensus::new(state_manager.beacon_schedule());
+            let v_block = block.clone();
+            validations.push(tokio::task::spawn(async move {
+                consensus
+                    .validate_block(state_manager, v_block)
+                    .map_err(|errs| {
+                        // NOTE: Concatenating errors here means the wrapper type of error
+                        // never surfaces, yet we always pay the cost of the generic argument.
+                        // But there's no reason `validate_block` couldn't return a list of all
+                        // errors instead of a single one that has all the error messages,
+                        // removing the caller's ability to distinguish between them.
+                        let errs = errs.map(TipsetRangeSyncerError::ConsensusError);
+
+                        TipsetRangeSyncerError::concat(errs)
+                    })
+                    .await
+            }));
+        }
+    }
 
     // Collect the errors from the async validations
     if let Err(errs) = collect_errs(validations).await {
@@ -1350,6 +1456,10 @@ async fn check_block_messages<DB: Blockstore + Send + Sync + 'static>(
         .chain_config()
         .network_version(block.header.epoch);
 
+    // Signatures are verified on the verification pool, once all of them are
+    // collected.
+    let mut signature_checks: Vec<Verification<'static, TipsetRangeSyncerError>> = Vec::new();
+
     if let Some(sig) = &block.header().bls_aggregate {
         // Do the initial loop here
         // check block message and signatures in them
@@ -1370,16 +1480,21 @@ async fn check_block_messages<DB: Blockstore + Send + Sync + 'static>(
             );
         }
 
-        if !verify_bls_aggregate(
-            &cids.iter().map(|x| x.as_slice()).collect_vec(),
-            &pub_keys,
-            sig,
-        ) {
-            return Err(TipsetRangeSyncerError::BlsAggregateSignatureInvalid(
-                format!("{sig:?}"),
-                format!("{cids:?}"),
-            ));
-        }
+        let sig = sig.clone();
+        signature_checks.push(Box::new(move || {
+            if verify_bls_aggregate(
+                &cids.iter().map(|x| x.as_slice()).collect_vec(),
+                &pub_keys,
+                &sig,
+            ) {
+                Ok(())
+            } else {
+                Err(TipsetRangeSyncerError::BlsAggregateSignatureInvalid(
+                    format!("{sig:?}"),
+                    format!("{cids:?}"),
+                ))
+            }
+        }));
     } else {
         return Err(TipsetRangeSyncerError::BlockWithoutBlsAggregate);
     }
@@ -1415,7 +1530,11 @@ async fn check_block_messages<DB: Blockstore + Send + Sync + 'static>(
                     .chain_config()
                     .network_version(block.header.epoch);
                 if !is_valid_for_sending(network_version, &actor) {
-                    anyhow::bail!("not valid for sending!");
+                    anyhow::bail!(
+                        "sender {} of actor {} not valid for sending",
+                        msg.from,
+                        state_manager.actor_registry().name(&actor.code)
+                    );
                 }
                 actor.sequence
             }
@@ -1466,12 +1585,21 @@ async fn check_block_messages<DB: Blockstore + Send + Sync + 'static>(
             .resolve_to_key_addr(&msg.from(), &base_tipset)
             .await
             .map_err(|e| TipsetRangeSyncerError::ResolvingAddressFromMessage(e.to_string()))?;
-        // SecP256K1 Signature validation
-        msg.signature
-            .verify(&msg.message().cid().unwrap().to_bytes(), &key_addr)
+        // SecP256K1 and delegated signature validation
+        msg.signature()
+            .check_network_version(network_version)
             .map_err(TipsetRangeSyncerError::MessageSignatureInvalid)?;
+        let msg = msg.clone();
+        let eth_chain_id = state_manager.chain_config().eth_chain_id as u64;
+        signature_checks.push(Box::new(move || {
+            msg.verify_with_key(&key_addr, eth_chain_id)
+                .map_err(TipsetRangeSyncerError::MessageSignatureInvalid)
+        }));
     }
 
+    // Check BLS and SECP signatures in parallel
+    verification_pool::submit_all(signature_checks).await?;
+
     // Validate message root from header matches message root
     let msg_root = TipsetValidator::compute_msg_root(
         state_manager.blockstore(),
@@ -1522,6 +1650,20 @@ fn block_timestamp_checks(header: &CachingBlockHeader) -> Result<(), TipsetRange
     Ok(())
 }
 
+/// Number of seconds beyond [`ALLOWABLE_CLOCK_DRIFT`] for which a block from
+/// the future is held back and revalidated later instead of being rejected.
+const FUTURE_BLOCK_GRACE_SECS: u64 = 6;
+
+/// Returns how long a block with the given timestamp should be held back
+/// before validation, if it arrived too early to pass the clock drift check
+/// but is still within the grace bound. Blocks further in the future are left
+/// to [`block_timestamp_checks`].
+pub(in crate::chain_sync) fn early_block_delay(timestamp: u64, time_now: u64) -> Option<Duration> {
+    let limit = time_now + ALLOWABLE_CLOCK_DRIFT;
+    (timestamp > limit && timestamp <= limit + FUTURE_BLOCK_GRACE_SECS)
+        .then(|| Duration::from_secs(timestamp - limit))
+}
+
 /// Check if any CID in `tipset` is a known bad block.
 /// If so, add all their descendants to the bad block cache and return an error.
 fn validate_tipset_against_cache(
@@ -1597,4 +1739,24 @@ mod test {
         assert_eq!(ts, ts3);
         assert_eq!(ts.weight(), &BigInt::from(10));
     }
+
+    #[test]
+    fn test_early_block_delay() {
+        let now = 1_000;
+        let limit = now + ALLOWABLE_CLOCK_DRIFT;
+        assert_eq!(early_block_delay(now, now), None);
+        assert_eq!(early_block_delay(limit, now), None);
+        assert_eq!(
+            early_block_delay(limit + 1, now),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            early_block_delay(limit + FUTURE_BLOCK_GRACE_SECS, now),
+            Some(Duration::from_secs(FUTURE_BLOCK_GRACE_SECS))
+        );
+        assert_eq!(
+            early_block_delay(limit + FUTURE_BLOCK_GRACE_SECS + 1, now),
+            None
+        );
+    }
 }
@@ -26,7 +26,11 @@ where
         .build()
         .unwrap()
         .block_on(async {
-            logger::setup_logger(&crate::cli_shared::cli::CliOpts::default());
+            logger::setup_logger(
+                &crate::cli_shared::cli::CliOpts::default(),
+                &Default::default(),
+                &Default::default(),
+            );
             if let Ok(name) = api.state_network_name().await {
                 if get_actual_chain_name(&name) != "mainnet" {
                     CurrentNetwork::set_global(Network::Testnet);
@@ -40,10 +44,12 @@ where
                 Subcommand::Sync(cmd) => cmd.run(api).await,
                 Subcommand::Mpool(cmd) => cmd.run(api).await,
                 Subcommand::State(cmd) => cmd.run(api).await,
-                Subcommand::Config(cmd) => cmd.run(&mut std::io::stdout()),
+                Subcommand::Config(cmd) => cmd.run(api, &mut std::io::stdout()).await,
                 Subcommand::Send(cmd) => cmd.run(api).await,
                 Subcommand::Info(cmd) => cmd.run(api).await,
                 Subcommand::Snapshot(cmd) => cmd.run(api).await,
+                Subcommand::Db(cmd) => cmd.run(api).await,
+                Subcommand::Log(cmd) => cmd.run(api).await,
                 Subcommand::Attach(cmd) => cmd.run(api),
                 Subcommand::Shutdown(cmd) => cmd.run(api).await,
             }
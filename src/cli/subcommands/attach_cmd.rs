@@ -309,9 +309,10 @@ impl AttachCommand {
                 "node_status" => |()| ApiInfo::node_status_req(),
 
                 // Sync API
-                "sync_check_bad" => ApiInfo::sync_check_bad_req,
-                "sync_mark_bad"  => ApiInfo::sync_mark_bad_req,
-                "sync_status"    => |()| ApiInfo::sync_status_req(),
+                "sync_check_bad"        => ApiInfo::sync_check_bad_req,
+                "sync_consensus_faults" => |()| ApiInfo::sync_consensus_faults_req(),
+                "sync_mark_bad"         => ApiInfo::sync_mark_bad_req,
+                "sync_status"           => |()| ApiInfo::sync_status_req(),
 
                 // Wallet API
                 // TODO(elmattic): https://github.com/ChainSafe/forest/issues/3575
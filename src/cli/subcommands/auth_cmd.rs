@@ -19,6 +19,9 @@ pub enum AuthCommands {
         /// Token is revoked after this duration
         #[arg(long, default_value_t = humantime::Duration::from_str("2 months").expect("infallible"))]
         expire_in: humantime::Duration,
+        /// Name of the holder of the token, identifying its requests in the node logs and metrics
+        #[arg(long)]
+        label: Option<String>,
     },
     /// Get RPC API Information
     ApiInfo {
@@ -28,6 +31,9 @@ pub enum AuthCommands {
         /// Token is revoked after this duration
         #[arg(long, default_value_t = humantime::Duration::from_str("2 months").expect("infallible"))]
         expire_in: humantime::Duration,
+        /// Name of the holder of the token, identifying its requests in the node logs and metrics
+        #[arg(long)]
+        label: Option<String>,
     },
 }
 
@@ -47,17 +53,25 @@ fn process_perms(perm: String) -> Result<Vec<String>, JsonRpcError> {
 impl AuthCommands {
     pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
         match self {
-            Self::CreateToken { perm, expire_in } => {
+            Self::CreateToken {
+                perm,
+                expire_in,
+                label,
+            } => {
                 let perm: String = perm.parse()?;
                 let perms = process_perms(perm)?;
                 let token_exp = Duration::from_std(expire_in.into())?;
-                print_rpc_res_bytes(api.auth_new(perms, token_exp).await?)
+                print_rpc_res_bytes(api.auth_new(perms, label, token_exp).await?)
             }
-            Self::ApiInfo { perm, expire_in } => {
+            Self::ApiInfo {
+                perm,
+                expire_in,
+                label,
+            } => {
                 let perm: String = perm.parse()?;
                 let perms = process_perms(perm)?;
                 let token_exp = Duration::from_std(expire_in.into())?;
-                let token = api.auth_new(perms, token_exp).await?;
+                let token = api.auth_new(perms, label, token_exp).await?;
                 let new_api = ApiInfo {
                     token: Some(String::from_utf8(token)?),
                     ..api
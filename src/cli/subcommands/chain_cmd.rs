@@ -1,14 +1,22 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::path::PathBuf;
+
 use crate::blocks::{Tipset, TipsetKey};
 use crate::lotus_json::{HasLotusJson, LotusJson};
 use crate::message::ChainMessage;
+use crate::rpc_api::chain_api::ChainExportRangeParams;
+use crate::rpc_api::eth_api::{BlockNumberOrHash, FeeHistory, Predefined};
 use crate::rpc_client::{ApiInfo, JsonRpcError};
-use anyhow::bail;
+use crate::shim::clock::ChainEpoch;
+use anyhow::{bail, Context as _};
 use cid::Cid;
 use clap::Subcommand;
+use num_traits::{ToPrimitive as _, Zero as _};
+use tempfile::NamedTempFile;
 
+use super::snapshot_cmd::save_checksum;
 use super::{print_pretty_json, print_rpc_res_cids};
 
 #[derive(Debug, Subcommand)]
@@ -53,6 +61,38 @@ pub enum ChainCommands {
         #[arg(short, long, aliases = ["yes", "no-confirm"], short_alias = 'y')]
         force: bool,
     },
+
+    /// Export the block headers and messages of an epoch range to a CAR file,
+    /// without any state
+    ExportRange {
+        /// First epoch of the range
+        #[arg(long)]
+        from: ChainEpoch,
+        /// Last epoch of the range, default is the chain head
+        #[arg(long)]
+        to: Option<ChainEpoch>,
+        /// Output file, or a directory to write
+        /// `forest_messages_{chain}_{from}_{to}.car.zst` to.
+        #[arg(short, long, default_value = ".", verbatim_doc_comment)]
+        output_path: PathBuf,
+        /// Skip creating the checksum file.
+        #[arg(long)]
+        skip_checksum: bool,
+        /// Don't write the archive.
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Lists the most recent reorgs of the chain head, with the blocks they
+    /// orphaned
+    Reorgs,
+
+    /// Charts the base fee and median gas premium of recent tipsets
+    GasHistory {
+        /// Number of tipsets to chart
+        #[arg(short, long, default_value_t = 30)]
+        tipsets: u64,
+    },
 }
 
 impl ChainCommands {
@@ -96,6 +136,71 @@ impl ChainCommands {
                     .await?;
                 Ok(())
             }
+            Self::ExportRange {
+                from,
+                to,
+                output_path,
+                skip_checksum,
+                dry_run,
+            } => {
+                let chain_head = api.chain_head().await?;
+                let to = to.unwrap_or(chain_head.epoch());
+
+                let output_path = match output_path.is_dir() {
+                    true => {
+                        let raw_network_name = api.state_network_name().await?;
+                        let chain_name = crate::daemon::get_actual_chain_name(&raw_network_name);
+                        output_path
+                            .join(format!("forest_messages_{chain_name}_{from}_{to}.car.zst"))
+                    }
+                    false => output_path,
+                };
+
+                let output_dir = output_path.parent().context("invalid output path")?;
+                let temp_path = NamedTempFile::new_in(output_dir)?.into_temp_path();
+
+                let params = ChainExportRangeParams {
+                    from,
+                    to,
+                    output_path: temp_path.to_path_buf(),
+                    tipset_keys: chain_head.key().clone(),
+                    skip_checksum,
+                    dry_run,
+                };
+                if let Some(hash) = api.chain_export_range(params).await? {
+                    save_checksum(&output_path, hash).await?;
+                }
+                temp_path.persist(output_path)?;
+
+                println!("Export completed.");
+                Ok(())
+            }
+            Self::Reorgs => {
+                for reorg in api.chain_get_reorgs().await? {
+                    let time = chrono::NaiveDateTime::from_timestamp_opt(reorg.timestamp, 0)
+                        .map(|time| time.to_string())
+                        .unwrap_or_default();
+                    println!(
+                        "{time}: head at epoch {} replaced by epoch {}, forked at epoch {} ({} epochs deep)",
+                        reorg.old_epoch, reorg.new_epoch, reorg.fork_epoch, reorg.depth
+                    );
+                    for cid in reorg.orphaned_blocks {
+                        println!("  orphaned {cid}");
+                    }
+                }
+                Ok(())
+            }
+            Self::GasHistory { tipsets } => {
+                let history = api
+                    .call(ApiInfo::eth_fee_history_req(
+                        tipsets,
+                        BlockNumberOrHash::from_predefined(Predefined::Latest),
+                        Some(vec![50.0]),
+                    ))
+                    .await?;
+                print_gas_history(&history);
+                Ok(())
+            }
         }
     }
 }
@@ -117,6 +222,46 @@ async fn tipset_by_epoch_or_offset(
         .await
 }
 
+/// Width of the base fee bars of `gas-history`, in characters.
+const GAS_HISTORY_CHART_WIDTH: u64 = 40;
+
+fn print_gas_history(history: &FeeHistory) {
+    println!(
+        "Fees of the last {} tipsets, since epoch {} (attoFIL)",
+        history.gas_used_ratio.len(),
+        history.oldest_block.0
+    );
+    println!("{:>20} {:>16} {:>6}", "base fee", "median premium", "gas");
+    let max_base_fee = history
+        .base_fee_per_gas
+        .iter()
+        .map(|fee| &fee.0)
+        .max()
+        .cloned()
+        .unwrap_or_default();
+    let rewards = history.reward.as_deref().unwrap_or_default();
+    for (i, gas_used_ratio) in history.gas_used_ratio.iter().enumerate() {
+        let base_fee = &history.base_fee_per_gas[i].0;
+        let premium = rewards
+            .get(i)
+            .and_then(|reward| reward.first())
+            .map(|premium| premium.0.to_string())
+            .unwrap_or_default();
+        let bar_len = if max_base_fee.is_zero() {
+            0
+        } else {
+            (base_fee * GAS_HISTORY_CHART_WIDTH / &max_base_fee)
+                .to_usize()
+                .unwrap_or_default()
+        };
+        println!(
+            "{base_fee:>20} {premium:>16} {:>5.0}% {}",
+            gas_used_ratio * 100.0,
+            "█".repeat(bar_len)
+        );
+    }
+}
+
 const SET_HEAD_CONFIRMATION_MESSAGE: &str =
     "Manually setting head is an unsafe operation that could brick the node! Continue?";
 
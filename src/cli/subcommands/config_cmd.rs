@@ -7,15 +7,19 @@ use anyhow::Context as _;
 use clap::Subcommand;
 
 use crate::cli::subcommands::Config;
+use crate::rpc_client::ApiInfo;
 
 #[derive(Debug, Subcommand)]
 pub enum ConfigCommands {
     /// Dump default configuration to standard output
     Dump,
+    /// Reload the configuration file of the node, applying the changes that
+    /// are safe at runtime. Sending `SIGHUP` to the node has the same effect.
+    Reload,
 }
 
 impl ConfigCommands {
-    pub fn run<W: Write + Unpin>(self, sink: &mut W) -> anyhow::Result<()> {
+    pub async fn run<W: Write + Unpin>(self, api: ApiInfo, sink: &mut W) -> anyhow::Result<()> {
         match self {
             Self::Dump => writeln!(
                 sink,
@@ -24,6 +28,16 @@ impl ConfigCommands {
                     .context("Could not convert configuration to TOML format")?
             )
             .context("Failed to write the configuration"),
+            Self::Reload => {
+                let report = api.config_reload().await?;
+                for field in report.applied {
+                    writeln!(sink, "applied: {field}")?;
+                }
+                for field in report.requires_restart {
+                    writeln!(sink, "requires a restart: {field}")?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -37,7 +51,10 @@ mod tests {
         let expected_config = Config::default();
         let mut sink = std::io::BufWriter::new(Vec::new());
 
-        ConfigCommands::Dump.run(&mut sink).unwrap();
+        ConfigCommands::Dump
+            .run(ApiInfo::from_env().unwrap(), &mut sink)
+            .await
+            .unwrap();
 
         let actual_config: Config = toml::from_str(std::str::from_utf8(sink.buffer()).unwrap())
             .expect("Invalid configuration!");
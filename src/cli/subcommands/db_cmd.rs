@@ -0,0 +1,36 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::rpc_client::ApiInfo;
+use clap::Subcommand;
+use human_repr::HumanCount as _;
+
+#[derive(Debug, Subcommand)]
+pub enum DbCommands {
+    /// Run the garbage collector of the node database now. Blocks are swept
+    /// once the chain has advanced enough since they were marked, the progress
+    /// and outcome of the sweep are reported by the `gc_*` metrics.
+    Gc,
+}
+
+impl DbCommands {
+    pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
+        match self {
+            Self::Gc => {
+                println!("Collecting garbage...");
+                let report = api.database_garbage_collect().await?;
+                match report.sweep_epoch {
+                    Some(epoch) => println!(
+                        "Sweep scheduled at epoch {epoch}, see the gc_* metrics for its outcome"
+                    ),
+                    None => println!(
+                        "Removed {} blocks, reclaimed {}",
+                        report.removed_blocks,
+                        report.reclaimed_bytes.human_count_bytes()
+                    ),
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -57,8 +57,17 @@ module.exports = {
     return sendMessage(from, to, amount.toString());
   },
   showSyncStatus: function () {
-    let stage = syncStatus().ActiveSyncs[0].Stage;
-    let height = syncStatus().ActiveSyncs[0].Epoch;
+    const stages = [
+      "idle worker",
+      "header sync",
+      "persisting headers",
+      "message sync",
+      "complete",
+      "error",
+      "fetching messages",
+    ];
+    let stage = stages[syncStatus().ActiveSyncs[0].Stage];
+    let height = syncStatus().ActiveSyncs[0].Height;
     let result = `sync status:
 Stage:  ${stage}
 Height: ${height}
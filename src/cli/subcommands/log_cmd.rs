@@ -0,0 +1,42 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::rpc_client::ApiInfo;
+use clap::Subcommand;
+
+#[derive(Debug, Subcommand)]
+pub enum LogCommands {
+    /// List the targets whose log level is set
+    List,
+    /// Set the level of the logs printed by the node, until it is restarted
+    SetLevel {
+        /// Level of the logs, e.g. `debug`
+        level: String,
+        /// Targets of the logs, e.g. `forest_filecoin::chain_sync`. Defaults
+        /// to all the listed targets.
+        #[arg(long)]
+        target: Vec<String>,
+    },
+}
+
+impl LogCommands {
+    pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
+        match self {
+            Self::List => {
+                for target in api.log_list().await? {
+                    println!("{target}");
+                }
+            }
+            Self::SetLevel { level, target } => {
+                let targets = match target.is_empty() {
+                    true => api.log_list().await?,
+                    false => target,
+                };
+                for target in targets {
+                    api.log_set_level(target, level.clone()).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
@@ -10,7 +10,9 @@ mod attach_cmd;
 mod auth_cmd;
 mod chain_cmd;
 mod config_cmd;
+mod db_cmd;
 mod info_cmd;
+mod log_cmd;
 mod mpool_cmd;
 mod net_cmd;
 pub(crate) mod send_cmd;
@@ -31,9 +33,9 @@ use tracing::error;
 
 pub(super) use self::{
     attach_cmd::AttachCommand, auth_cmd::AuthCommands, chain_cmd::ChainCommands,
-    config_cmd::ConfigCommands, mpool_cmd::MpoolCommands, net_cmd::NetCommands,
-    send_cmd::SendCommand, shutdown_cmd::ShutdownCommand, snapshot_cmd::SnapshotCommands,
-    state_cmd::StateCommands, sync_cmd::SyncCommands,
+    config_cmd::ConfigCommands, db_cmd::DbCommands, log_cmd::LogCommands, mpool_cmd::MpoolCommands,
+    net_cmd::NetCommands, send_cmd::SendCommand, shutdown_cmd::ShutdownCommand,
+    snapshot_cmd::SnapshotCommands, state_cmd::StateCommands, sync_cmd::SyncCommands,
 };
 use crate::cli::subcommands::info_cmd::InfoCommand;
 
@@ -84,6 +86,14 @@ pub enum Subcommand {
     #[command(subcommand)]
     Snapshot(SnapshotCommands),
 
+    /// Manage the node database
+    #[command(subcommand)]
+    Db(DbCommands),
+
+    /// Manage the logs of the node
+    #[command(subcommand)]
+    Log(LogCommands),
+
     /// Send funds between accounts
     Send(SendCommand),
 
@@ -1,12 +1,12 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use crate::libp2p::{Multiaddr, Protocol};
+use crate::libp2p::{BlockList, Multiaddr, Protocol};
 use crate::rpc_api::data_types::AddrInfo;
 use crate::rpc_client::ApiInfo;
 use ahash::HashSet;
 use cid::multibase;
-use clap::Subcommand;
+use clap::{Subcommand, ValueEnum};
 use itertools::Itertools;
 
 use crate::cli::subcommands::cli_error_and_die;
@@ -19,6 +19,15 @@ pub enum NetCommands {
     Info,
     /// Lists `libp2p` swarm peers
     Peers,
+    /// Shows the addresses and the chain exchange score of a peer
+    PeerInfo {
+        /// Peer ID
+        id: String,
+    },
+    /// Lists the `Gossipsub` scores of peers, lowest first
+    Scores,
+    /// Starts a peer discovery round right away
+    Discover,
     /// Connects to a peer by its peer ID and multi-addresses
     Connect {
         /// Multi-address (with `/p2p/` protocol)
@@ -29,6 +38,68 @@ pub enum NetCommands {
         /// Peer ID to disconnect from
         id: String,
     },
+    /// Protects peers from being banned by the node
+    Protect {
+        /// Peer IDs to protect
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// Removes the protection of peers
+    Unprotect {
+        /// Peer IDs to unprotect
+        #[arg(required = true)]
+        ids: Vec<String>,
+    },
+    /// Lists protected peers
+    ListProtected,
+    /// Manages the peers, IP addresses and subnets refused connections
+    #[command(subcommand)]
+    Block(BlockCommands),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BlockCommands {
+    /// Blocks peers, IP addresses or subnets, closing their connections
+    Add {
+        /// Kind of the blocked values
+        #[arg(value_enum)]
+        kind: BlockKind,
+        /// Peer IDs, IP addresses or subnets (e.g. `10.0.0.0/8`)
+        #[arg(required = true)]
+        values: Vec<String>,
+    },
+    /// Unblocks peers, IP addresses or subnets
+    Remove {
+        /// Kind of the unblocked values
+        #[arg(value_enum)]
+        kind: BlockKind,
+        /// Peer IDs, IP addresses or subnets (e.g. `10.0.0.0/8`)
+        #[arg(required = true)]
+        values: Vec<String>,
+    },
+    /// Lists blocked peers, IP addresses and subnets
+    List,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum BlockKind {
+    Peer,
+    Ip,
+    Subnet,
+}
+
+impl BlockKind {
+    fn block_list(self, values: &[String]) -> anyhow::Result<BlockList> {
+        let mut block_list = BlockList::default();
+        for value in values {
+            match self {
+                Self::Peer => block_list.peers.push(value.parse()?),
+                Self::Ip => block_list.ip_addrs.push(value.parse()?),
+                Self::Subnet => block_list.ip_subnets.push(value.parse()?),
+            }
+        }
+        Ok(block_list)
+    }
 }
 
 impl NetCommands {
@@ -53,6 +124,10 @@ impl NetCommands {
                 println!("num pending incoming: {}", info.num_pending_incoming);
                 println!("num pending outgoing: {}", info.num_pending_outgoing);
                 println!("num established: {}", info.num_established);
+                println!("reachability: {}", info.reachability);
+                if let Some(public_addr) = info.public_addr {
+                    println!("public address: {public_addr}");
+                }
                 Ok(())
             }
             Self::Peers => {
@@ -74,12 +149,58 @@ impl NetCommands {
                         if addresses.is_empty() {
                             return None;
                         }
-                        Some(format!("{}, [{}]", info.id, addresses.join(", ")))
+                        let quarantine = match info.quarantine {
+                            Some(quarantine) if quarantine.remaining_secs > 0 => {
+                                format!(" (quarantined for {}s)", quarantine.remaining_secs)
+                            }
+                            _ => String::new(),
+                        };
+                        Some(format!(
+                            "{}, [{}]{quarantine}",
+                            info.id,
+                            addresses.join(", ")
+                        ))
                     })
                     .collect();
                 println!("{}", output.join("\n"));
                 Ok(())
             }
+            Self::PeerInfo { id } => {
+                let info = api.net_peer_info(id).await?;
+                println!("id: {}", info.id);
+                println!(
+                    "addresses: [{}]",
+                    info.addrs.iter().map(ToString::to_string).join(", ")
+                );
+                match info.score {
+                    Some(score) => {
+                        println!("successes: {}", score.successes);
+                        println!("failures: {}", score.failures);
+                        println!("average latency: {}ms", score.average_latency_ms);
+                        println!("served bytes: {}", score.served_bytes);
+                        println!("cost: {:.4}", score.cost);
+                    }
+                    None => println!("not tracked by the peer manager"),
+                }
+                if let Some(quarantine) = info.quarantine {
+                    println!("penalty: {:.2}", quarantine.penalty);
+                    println!("quarantined for: {}s", quarantine.remaining_secs);
+                }
+                Ok(())
+            }
+            Self::Scores => {
+                let mut scores = api.net_pubsub_scores().await?;
+                scores.sort_by(|a, b| a.score.score.total_cmp(&b.score.score));
+                for score in scores {
+                    println!("{}, {:.2}", score.id, score.score.score);
+                }
+                Ok(())
+            }
+            Self::Discover => {
+                api.net_discover().await?;
+                println!("discovery round started");
+                Ok(())
+            }
             Self::Connect { address } => {
                 let addr: Multiaddr = address
                     .parse()
@@ -105,6 +226,7 @@ impl NetCommands {
                 let addr_info = AddrInfo {
                     id: id.clone(),
                     addrs,
+                    quarantine: None,
                 };
 
                 api.net_connect(addr_info).await?;
@@ -116,6 +238,55 @@ impl NetCommands {
                 println!("disconnect {id}: success");
                 Ok(())
             }
+            Self::Protect { ids } => {
+                api.net_protect_add(ids.clone()).await?;
+                println!("protect {}: success", ids.join(", "));
+                Ok(())
+            }
+            Self::Unprotect { ids } => {
+                api.net_protect_remove(ids.clone()).await?;
+                println!("unprotect {}: success", ids.join(", "));
+                Ok(())
+            }
+            Self::ListProtected => {
+                let ids = api.net_protect_list().await?;
+                println!("{}", ids.join("\n"));
+                Ok(())
+            }
+            Self::Block(BlockCommands::Add { kind, values }) => {
+                api.net_block_add(kind.block_list(&values)?).await?;
+                println!("block {}: success", values.join(", "));
+                Ok(())
+            }
+            Self::Block(BlockCommands::Remove { kind, values }) => {
+                api.net_block_remove(kind.block_list(&values)?).await?;
+                println!("unblock {}: success", values.join(", "));
+                Ok(())
+            }
+            Self::Block(BlockCommands::List) => {
+                let block_list = api.net_block_list().await?;
+                println!(
+                    "peers: [{}]",
+                    block_list.peers.iter().map(ToString::to_string).join(", ")
+                );
+                println!(
+                    "IP addresses: [{}]",
+                    block_list
+                        .ip_addrs
+                        .iter()
+                        .map(ToString::to_string)
+                        .join(", ")
+                );
+                println!(
+                    "IP subnets: [{}]",
+                    block_list
+                        .ip_subnets
+                        .iter()
+                        .map(ToString::to_string)
+                        .join(", ")
+                );
+                Ok(())
+            }
         }
     }
 }
@@ -10,16 +10,28 @@ pub struct ShutdownCommand {
     /// Assume "yes" as answer to shutdown prompt
     #[arg(long)]
     force: bool,
+    /// Start the node again once it is shut down, e.g. to apply configuration changes that
+    /// require a restart
+    #[arg(long)]
+    restart: bool,
 }
 
 impl ShutdownCommand {
     pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
-        println!("Shutting down Forest node");
+        if self.restart {
+            println!("Restarting Forest node");
+        } else {
+            println!("Shutting down Forest node");
+        }
         if !self.force && !prompt_confirm() {
             println!("Aborted.");
             return Ok(());
         }
-        api.shutdown().await?;
+        if self.restart {
+            api.restart().await?;
+        } else {
+            api.shutdown().await?;
+        }
         Ok(())
     }
 }
@@ -6,11 +6,13 @@ use crate::blocks::TipsetKey;
 use crate::chain_sync::SyncConfig;
 use crate::cli_shared::snapshot::{self, TrustedVendor};
 use crate::rpc_api::chain_api::ChainExportParams;
-use crate::rpc_client::ApiInfo;
+use crate::rpc_client::{ApiInfo, RpcRequest};
 use anyhow::Context as _;
 use chrono::NaiveDateTime;
 use clap::Subcommand;
+use futures::StreamExt as _;
 use human_repr::HumanCount;
+use sha2::{Digest as _, Sha256};
 use std::path::{Path, PathBuf};
 use tempfile::NamedTempFile;
 use tokio::io::AsyncWriteExt;
@@ -19,8 +21,15 @@ use tokio::io::AsyncWriteExt;
 pub enum SnapshotCommands {
     /// Export a snapshot of the chain to `<output_path>`
     Export {
-        /// `./forest_snapshot_{chain}_{year}-{month}-{day}_height_{epoch}.car.zst`.
-        #[arg(short, long, default_value = ".", verbatim_doc_comment)]
+        /// Snapshot output filename or directory. Defaults to
+        /// `./forest_snapshot_{chain}_{year}-{month}-{day}_height_{epoch}.forest.car.zst`.
+        #[arg(
+            short,
+            long,
+            visible_alias = "output",
+            default_value = ".",
+            verbatim_doc_comment
+        )]
         output_path: PathBuf,
         /// Skip creating the checksum file.
         #[arg(long)]
@@ -31,10 +40,38 @@ pub enum SnapshotCommands {
         /// Tipset to start the export from, default is the chain head
         #[arg(short, long)]
         tipset: Option<i64>,
-        /// How many state-roots to include. Lower limit is 900 for `calibnet` and `mainnet`.
+        /// How many state-roots to include. Block headers are always included back
+        /// to genesis. Lower limit is 900 for `calibnet` and `mainnet`.
         #[arg(short, long)]
         depth: Option<crate::chain::ChainEpochDelta>,
+        /// Base snapshot to export a diff against. Blocks present in the base
+        /// snapshot, and everything they link to, are left out.
+        #[arg(long)]
+        diff: Option<PathBuf>,
+        /// Also export the messages of the tipsets older than the state-roots,
+        /// and the message receipts of every tipset.
+        #[arg(long)]
+        include_old_messages: bool,
+        /// Download the snapshot from the node as it is exported, rather than
+        /// having the node write it. Use this when `<output_path>` isn't on the
+        /// filesystem of the node.
+        #[arg(long, conflicts_with_all = ["dry_run", "diff"])]
+        stream: bool,
+    },
+    /// Load a CAR file into the blockstore of the running node, e.g. an
+    /// archival snapshot for historical queries. The file isn't copied, and
+    /// isn't loaded again after a restart
+    AddCar {
+        /// Path of the CAR file
+        path: PathBuf,
     },
+    /// Unload a CAR file from the blockstore of the running node
+    RemoveCar {
+        /// Path of the CAR file
+        path: PathBuf,
+    },
+    /// List the CAR files loaded into the blockstore of the running node
+    ListCars,
 }
 
 impl SnapshotCommands {
@@ -46,6 +83,9 @@ impl SnapshotCommands {
                 dry_run,
                 tipset,
                 depth,
+                diff,
+                include_old_messages,
+                stream,
             } => {
                 let chain_head = api.chain_head().await?;
 
@@ -74,16 +114,28 @@ impl SnapshotCommands {
                     false => output_path.clone(),
                 };
 
+                // The base snapshot is read by the daemon, whose working
+                // directory may differ.
+                let diff = diff
+                    .map(|base| {
+                        std::fs::canonicalize(&base)
+                            .with_context(|| format!("invalid base snapshot {}", base.display()))
+                    })
+                    .transpose()?;
+
                 let output_dir = output_path.parent().context("invalid output path")?;
                 let temp_path = NamedTempFile::new_in(output_dir)?.into_temp_path();
 
+                let recent_roots = depth.unwrap_or(SyncConfig::default().recent_state_roots);
                 let params = ChainExportParams {
                     epoch,
-                    recent_roots: depth.unwrap_or(SyncConfig::default().recent_state_roots),
+                    recent_roots,
                     output_path: temp_path.to_path_buf(),
                     tipset_keys: chain_head.key().clone(),
                     skip_checksum,
                     dry_run,
+                    diff,
+                    include_old_messages,
                 };
 
                 let handle = tokio::spawn({
@@ -113,7 +165,16 @@ impl SnapshotCommands {
                     }
                 });
 
-                let hash_result = api.chain_export(params).await?;
+                let hash_result = if stream {
+                    let request = ApiInfo::chain_export_stream_req(
+                        recent_roots,
+                        !include_old_messages,
+                        tipset.key().clone(),
+                    );
+                    download_snapshot(&api, request, &temp_path, skip_checksum).await?
+                } else {
+                    api.chain_export(params).await?
+                };
 
                 handle.abort();
                 let _ = handle.await;
@@ -126,13 +187,59 @@ impl SnapshotCommands {
                 println!("Export completed.");
                 Ok(())
             }
+            Self::AddCar { path } => {
+                let path = std::fs::canonicalize(&path)
+                    .with_context(|| format!("invalid CAR file {}", path.display()))?;
+                let heaviest_tipset = api.chain_add_car(path.clone()).await?;
+                println!(
+                    "Loaded {}, heaviest epoch: {}",
+                    path.display(),
+                    heaviest_tipset.epoch()
+                );
+                Ok(())
+            }
+            Self::RemoveCar { path } => {
+                let path = std::fs::canonicalize(&path).unwrap_or(path);
+                api.chain_remove_car(path.clone()).await?;
+                println!("Unloaded {}", path.display());
+                Ok(())
+            }
+            Self::ListCars => {
+                for path in api.chain_list_cars().await? {
+                    println!("{}", path.display());
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Writes the snapshot streamed by the node in response to `request` to `path`.
+/// Returns the hex-encoded SHA-256 checksum of the snapshot, unless
+/// `skip_checksum`.
+async fn download_snapshot(
+    api: &ApiInfo,
+    request: RpcRequest<Vec<u8>>,
+    path: &Path,
+    skip_checksum: bool,
+) -> anyhow::Result<Option<String>> {
+    let mut stream = std::pin::pin!(api.call_stream(request).await?);
+    let mut file = tokio::fs::File::create(path).await?;
+    let mut hasher = (!skip_checksum).then(Sha256::new);
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        if let Some(hasher) = &mut hasher {
+            hasher.update(&chunk);
         }
+        file.write_all(&chunk).await?;
     }
+    file.flush().await?;
+    Ok(hasher.map(|hasher| hex::encode(hasher.finalize())))
 }
 
 /// Prints hex-encoded representation of SHA-256 checksum and saves it to a file
 /// with the same name but with a `.sha256sum` extension.
-async fn save_checksum(source: &Path, encoded_hash: String) -> anyhow::Result<()> {
+pub(super) async fn save_checksum(source: &Path, encoded_hash: String) -> anyhow::Result<()> {
     let checksum_file_content = format!(
         "{encoded_hash} {}\n",
         source
@@ -4,10 +4,14 @@
 use std::path::PathBuf;
 
 use crate::rpc_client::ApiInfo;
+use crate::shim::address::{Address, StrictAddress};
 use crate::shim::clock::ChainEpoch;
 use crate::shim::econ::TokenAmount;
+use anyhow::Context as _;
 use cid::Cid;
 use clap::Subcommand;
+use fil_actors_shared::fvm_ipld_bitfield::{iter::Ranges, BitField};
+use itertools::Itertools as _;
 use serde_tuple::{self, Deserialize_tuple, Serialize_tuple};
 
 #[derive(Serialize_tuple, Deserialize_tuple, Clone, Debug)]
@@ -29,6 +33,18 @@ pub enum StateCommands {
         #[arg(short, long)]
         save_to_file: Option<PathBuf>,
     },
+    /// Print the on-chain info of sectors of a miner, one JSON object per line
+    SectorInfo {
+        /// Miner address
+        miner: StrictAddress,
+        /// Sector numbers, as a comma-separated list of numbers and inclusive
+        /// ranges, e.g. `1,5-10`
+        #[arg(value_parser = parse_sector_numbers)]
+        sectors: BitField,
+        /// Number of sectors queried per request
+        #[arg(long, default_value_t = 1000)]
+        batch_size: usize,
+    },
 }
 
 impl StateCommands {
@@ -37,7 +53,63 @@ impl StateCommands {
             Self::Fetch { root, save_to_file } => {
                 println!("{}", api.state_fetch_root(root, save_to_file).await?);
             }
+            Self::SectorInfo {
+                miner,
+                sectors,
+                batch_size,
+            } => {
+                anyhow::ensure!(batch_size > 0, "batch size must be positive");
+                // All batches are queried at the same tipset, so that the
+                // output is consistent even if the head changes meanwhile.
+                let tsk = api.chain_head().await?.key().clone();
+                for batch in &sectors.iter().chunks(batch_size) {
+                    let batch = BitField::try_from_bits(batch)?;
+                    for info in api
+                        .state_miner_sectors(Address::from(miner), Some(batch), tsk.clone())
+                        .await?
+                    {
+                        println!("{}", serde_json::to_string(&info)?);
+                    }
+                }
+            }
         }
         Ok(())
     }
 }
+
+/// Parses a comma-separated list of sector numbers and inclusive ranges of
+/// sector numbers, e.g. `1,5-10`.
+fn parse_sector_numbers(s: &str) -> anyhow::Result<BitField> {
+    let ranges = s
+        .split(',')
+        .map(|part| {
+            let part = part.trim();
+            let (start, end) = match part.split_once('-') {
+                Some((start, end)) => (start.trim().parse()?, end.trim().parse()?),
+                None => {
+                    let number = part.parse()?;
+                    (number, number)
+                }
+            };
+            anyhow::ensure!(start <= end, "invalid sector range {part}");
+            let end = u64::checked_add(end, 1).context("sector number out of range")?;
+            Ok(BitField::from_ranges(Ranges::new([start..end])))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(BitField::union(&ranges))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sector_numbers_test() {
+        let sectors = parse_sector_numbers("7, 1,3-5,4-6").unwrap();
+        assert_eq!(sectors.iter().collect::<Vec<_>>(), [1, 3, 4, 5, 6, 7]);
+
+        assert!(parse_sector_numbers("").is_err());
+        assert!(parse_sector_numbers("5-3").is_err());
+        assert!(parse_sector_numbers("1,a").is_err());
+    }
+}
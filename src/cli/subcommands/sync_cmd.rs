@@ -6,8 +6,9 @@ use std::{
     time::Duration,
 };
 
-use crate::chain_sync::SyncStage;
+use crate::chain_sync::{Checkpoint, SyncStage};
 use crate::rpc_client::*;
+use crate::shim::clock::ChainEpoch;
 use cid::Cid;
 use clap::Subcommand;
 use ticker::Ticker;
@@ -30,12 +31,34 @@ pub enum SyncCommands {
         /// The block CID to check
         cid: String,
     },
+    /// List the consensus faults of miners detected in the validated blocks
+    ConsensusFaults,
     /// Mark a given block as bad
     MarkBad {
         /// The block CID to mark as a bad block
         #[arg(short)]
         cid: String,
     },
+    /// Unmark a given block, or all the blocks, as bad, so that they can be
+    /// synced again
+    UnmarkBad {
+        /// The block CID to unmark as a bad block
+        #[arg(short, required_unless_present = "all", conflicts_with = "all")]
+        cid: Option<String>,
+        /// Unmark all the bad blocks
+        #[arg(long)]
+        all: bool,
+    },
+    /// Set a known-good tipset, whose ancestors are synced without verifying
+    /// their signatures and election proofs, in place of the configured ones
+    SetCheckpoint {
+        /// Epoch of the tipset
+        #[arg(long)]
+        epoch: ChainEpoch,
+        /// CIDs of the blocks of the tipset
+        #[arg(required = true)]
+        cids: Vec<Cid>,
+    },
 }
 
 impl SyncCommands {
@@ -93,37 +116,40 @@ impl SyncCommands {
             Self::Status => {
                 let response = api.sync_status().await?;
 
-                let state = response.active_syncs.first();
-                let base = state.base();
-                let elapsed_time = state.get_elapsed_time();
-                let target = state.target();
+                println!("sync status:");
+                for state in response.active_syncs.iter() {
+                    let base = state.base();
+                    let elapsed_time = state.get_elapsed_time();
+                    let target = state.target();
+
+                    let (target_cids, target_height) = if let Some(tipset) = target {
+                        let cid_vec = tipset.cids().iter().map(|cid| cid.to_string()).collect();
+                        (format_vec_pretty(cid_vec), tipset.epoch())
+                    } else {
+                        ("[]".to_string(), 0)
+                    };
 
-                let (target_cids, target_height) = if let Some(tipset) = target {
-                    let cid_vec = tipset.cids().iter().map(|cid| cid.to_string()).collect();
-                    (format_vec_pretty(cid_vec), tipset.epoch())
-                } else {
-                    ("[]".to_string(), 0)
-                };
+                    let (base_cids, base_height) = if let Some(tipset) = base {
+                        let cid_vec = tipset.cids().iter().map(|cid| cid.to_string()).collect();
+                        (format_vec_pretty(cid_vec), tipset.epoch())
+                    } else {
+                        ("[]".to_string(), 0)
+                    };
 
-                let (base_cids, base_height) = if let Some(tipset) = base {
-                    let cid_vec = tipset.cids().iter().map(|cid| cid.to_string()).collect();
-                    (format_vec_pretty(cid_vec), tipset.epoch())
-                } else {
-                    ("[]".to_string(), 0)
-                };
+                    let height_diff = base_height - target_height;
 
-                let height_diff = base_height - target_height;
+                    println!("worker {}:", state.worker_id());
+                    println!("\tBase:\t{base_cids}");
+                    println!("\tTarget:\t{target_cids} ({target_height})");
+                    println!("\tHeight diff:\t{}", height_diff.abs());
+                    println!("\tStage:\t{}", state.stage());
+                    println!("\tHeight:\t{}", state.epoch());
 
-                println!("sync status:");
-                println!("Base:\t{base_cids}");
-                println!("Target:\t{target_cids} ({target_height})");
-                println!("Height diff:\t{}", height_diff.abs());
-                println!("Stage:\t{}", state.stage());
-                println!("Height:\t{}", state.epoch());
-
-                if let Some(duration) = elapsed_time {
-                    println!("Elapsed time:\t{}s", duration.num_seconds());
+                    if let Some(duration) = elapsed_time {
+                        println!("\tElapsed time:\t{}s", duration.num_seconds());
+                    }
                 }
+                println!("Messages applied:\t{}", response.vm_applied);
                 Ok(())
             }
             Self::CheckBad { cid } => {
@@ -133,7 +159,24 @@ impl SyncCommands {
                 if response.is_empty() {
                     println!("Block \"{cid}\" is not marked as a bad block");
                 } else {
-                    println!("response");
+                    println!("Block \"{cid}\" is marked as a bad block: {response}");
+                }
+                Ok(())
+            }
+            Self::ConsensusFaults => {
+                for fault in api.sync_consensus_faults().await? {
+                    let extra = fault
+                        .block_header_extra
+                        .map(|header| format!(" (witness {})", header.cid()))
+                        .unwrap_or_default();
+                    println!(
+                        "{:?} by {} at epoch {}: {} {}{extra}",
+                        fault.kind,
+                        fault.miner,
+                        fault.epoch,
+                        fault.block_header_1.cid(),
+                        fault.block_header_2.cid()
+                    );
                 }
                 Ok(())
             }
@@ -143,6 +186,19 @@ impl SyncCommands {
                 println!("OK");
                 Ok(())
             }
+            Self::UnmarkBad { cid, all } => {
+                match cid {
+                    Some(cid) if !all => api.sync_unmark_bad(cid.parse()?).await?,
+                    _ => api.sync_unmark_all_bad().await?,
+                }
+                println!("OK");
+                Ok(())
+            }
+            Self::SetCheckpoint { epoch, cids } => {
+                api.sync_set_checkpoint(Checkpoint { epoch, cids }).await?;
+                println!("OK");
+                Ok(())
+            }
         }
     }
 }
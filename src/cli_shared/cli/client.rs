@@ -7,11 +7,13 @@ use std::{
     str::FromStr,
 };
 
+use crate::networks::ActorBundleSource;
 use crate::rpc_client::DEFAULT_PORT;
 use chrono::Duration;
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DurationSeconds};
+use url::Url;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(transparent)]
@@ -51,6 +53,16 @@ pub struct Client {
     pub snapshot_height: Option<i64>,
     pub snapshot_head: Option<i64>,
     pub snapshot_path: Option<PathBuf>,
+    /// Stable URLs of the latest snapshot of the chain, e.g.
+    /// `https://forest-archive.chainsafe.dev/latest/calibnet/`, among which the highest snapshot
+    /// is downloaded when the node needs one. Defaults to the Forest archive.
+    #[cfg_attr(test, arbitrary(gen(
+        |_| vec!["https://forest-archive.chainsafe.dev/latest/calibnet/".parse().unwrap()]
+    )))]
+    pub snapshot_providers: Vec<Url>,
+    /// Base64-encoded Ed25519 public keys of the trusted snapshot publishers. When set, imported
+    /// snapshots must come with a checksum file signed by one of the keys.
+    pub snapshot_publisher_keys: Vec<String>,
     /// Skips loading import CAR file and assumes it's already been loaded.
     /// Will use the CIDs in the header of the file to index the chain.
     pub skip_load: bool,
@@ -60,7 +72,13 @@ pub struct Client {
     /// When importing CAR files, maintain a read-ahead buffer measured in
     /// number of chunks.
     pub buffer_size: BufferSize,
+    /// When importing CAR files, number of threads verifying and compressing
+    /// blocks. `0` uses one thread per CPU.
+    pub import_threads: usize,
     pub encrypt_keystore: bool,
+    /// Read and store the keystore passphrase in the OS keyring. Requires the
+    /// `os-keyring` feature.
+    pub use_os_keyring: bool,
     /// Metrics bind, e.g. 127.0.0.1:6116
     pub metrics_address: SocketAddr,
     /// RPC bind, e.g. 127.0.0.1:1234
@@ -71,8 +89,15 @@ pub struct Client {
         |g| Duration::milliseconds(i64::arbitrary(g))
     )))]
     pub token_exp: Duration,
+    /// Directory of the files exported on RPC requests, e.g., with `forest-cli
+    /// chain export-range`. Exports to paths outside of it are refused. Unset,
+    /// exports are written to any path the node can write to.
+    pub export_dir: Option<PathBuf>,
     /// Load actors from the bundle file (possibly generating it if it doesn't exist)
     pub load_actors: bool,
+    /// Actor bundles to fetch in addition to those compiled in, verified and
+    /// kept by the garbage collector.
+    pub actor_bundles: Vec<ActorBundleSource>,
 }
 
 impl Default for Client {
@@ -85,6 +110,8 @@ impl Default for Client {
             enable_metrics_endpoint: true,
             rpc_token: None,
             snapshot_path: None,
+            snapshot_providers: vec![],
+            snapshot_publisher_keys: vec![],
             snapshot: false,
             consume_snapshot: false,
             snapshot_height: None,
@@ -92,11 +119,15 @@ impl Default for Client {
             skip_load: false,
             chunk_size: ChunkSize::default(),
             buffer_size: BufferSize::default(),
+            import_threads: 0,
             encrypt_keystore: true,
+            use_os_keyring: false,
             metrics_address: FromStr::from_str("0.0.0.0:6116").unwrap(),
             rpc_address: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), DEFAULT_PORT),
             token_exp: Duration::seconds(5184000), // 60 Days = 5184000 Seconds
+            export_dir: None,
             load_actors: true,
+            actor_bundles: vec![],
         }
     }
 }
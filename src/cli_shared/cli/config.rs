@@ -1,8 +1,13 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use crate::db::db_engine::DbConfig;
+use crate::cli_shared::logger::{LogConfig, TelemetryConfig};
+use crate::db::db_engine::{BackendConfig, BackendKind, DbConfig};
+use crate::db::{BlockCacheConfig, GcConfig, HealthCheckConfig};
+use crate::health::HealthcheckConfig;
 use crate::libp2p::Libp2pConfig;
+use crate::message_pool::FeeConfig;
+use crate::metrics::MetricsConfig;
 use crate::{chain_sync::SyncConfig, networks::NetworkChain};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
@@ -36,21 +41,67 @@ impl Default for DaemonConfig {
     }
 }
 
+/// Structure that defines where the state of epochs missing from the database
+/// is retrieved from, for nodes that serve deep history without storing it
+#[derive(Deserialize, Serialize, PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(default)]
+pub struct ArchivalConfig {
+    /// CAR files, e.g. archival snapshots, whose blocks are available to the
+    /// node without being imported
+    pub car_files: Vec<PathBuf>,
+    /// Fetch the blocks that are in neither the database nor `car_files` from
+    /// bitswap peers, and keep them in the database
+    pub fetch_missing: bool,
+    /// How long to wait for a block to be fetched, in seconds
+    pub fetch_timeout_secs: u64,
+}
+
+impl Default for ArchivalConfig {
+    fn default() -> Self {
+        Self {
+            car_files: vec![],
+            fetch_missing: false,
+            fetch_timeout_secs: 10,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, PartialEq, Default, Debug, Clone)]
 #[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
 #[serde(default)]
 pub struct Config {
     pub chain: NetworkChain,
     pub client: Client,
+    pub db: BackendConfig,
     pub parity_db: crate::db::parity_db_config::ParityDbConfig,
+    pub rocks_db: crate::db::rocks_db_config::RocksDbConfig,
     pub network: Libp2pConfig,
     pub sync: SyncConfig,
     pub daemon: DaemonConfig,
+    pub archival: ArchivalConfig,
+    pub garbage_collection: GcConfig,
+    pub block_cache: BlockCacheConfig,
+    pub db_health_check: HealthCheckConfig,
+    pub log: LogConfig,
+    pub telemetry: TelemetryConfig,
+    pub healthcheck: HealthcheckConfig,
+    pub metrics: MetricsConfig,
+    pub fee: FeeConfig,
 }
 
 impl Config {
-    pub fn db_config(&self) -> &DbConfig {
-        &self.parity_db
+    /// Returns the configuration of the selected database backend.
+    pub fn db_config(&self) -> DbConfig {
+        self.db_config_for(self.db.backend)
+    }
+
+    /// Returns the configuration of the given database backend.
+    pub fn db_config_for(&self, backend: BackendKind) -> DbConfig {
+        match backend {
+            BackendKind::ParityDb => DbConfig::ParityDb(self.parity_db.clone()),
+            BackendKind::RocksDb => DbConfig::RocksDb(self.rocks_db.clone()),
+        }
     }
 }
 
@@ -11,6 +11,7 @@ use std::{
 
 use crate::cli_shared::read_config;
 use crate::networks::NetworkChain;
+use crate::shim::address::Address;
 use crate::utils::io::read_file_to_string;
 use crate::utils::misc::LoggingColor;
 use ahash::HashSet;
@@ -36,7 +37,7 @@ OPTIONS:
 ";
 
 /// CLI options
-#[derive(Default, Debug, Parser)]
+#[derive(Default, Debug, Clone, Parser)]
 pub struct CliOpts {
     /// A TOML file containing relevant configurations
     #[arg(short, long)]
@@ -66,6 +67,9 @@ pub struct CliOpts {
     /// Allow MDNS (default: false)
     #[arg(long)]
     pub mdns: Option<bool>,
+    /// Dial the bootstrap peers (default: true)
+    #[arg(long)]
+    pub bootstrap: Option<bool>,
     /// Validate snapshot at given EPOCH, use a negative value -N to validate
     /// the last N EPOCH(s) starting at HEAD.
     #[arg(long)]
@@ -75,15 +79,24 @@ pub struct CliOpts {
     /// is unspecified.
     #[arg(long)]
     pub head: Option<u64>,
-    /// Import a snapshot from a local CAR file or URL
+    /// Import a snapshot from a local CAR file or URL. Snapshots at URLs are
+    /// imported as they are downloaded, without a local copy
     #[arg(long)]
     pub import_snapshot: Option<String>,
     /// Import a snapshot from a local CAR file and delete it, or from a URL
     #[arg(long)]
     pub consume_snapshot: Option<String>,
+    /// Number of threads verifying and compressing blocks when importing a
+    /// snapshot (default is one per CPU)
+    #[arg(long)]
+    pub import_threads: Option<usize>,
     /// Halt with exit code 0 after successfully importing a snapshot
     #[arg(long)]
     pub halt_after_import: bool,
+    /// Import the snapshot even if it can't be verified against its published
+    /// checksum and signature
+    #[arg(long)]
+    pub force: bool,
     /// Import a chain from a local CAR file or URL
     #[arg(long)]
     pub import_chain: Option<String>,
@@ -94,6 +107,10 @@ pub struct CliOpts {
     /// Number of tipsets requested over one chain exchange (default is 8)
     #[arg(long)]
     pub req_window: Option<usize>,
+    /// Number of tipsets validated concurrently during sync, while the
+    /// following ones are executed (default is one per CPU)
+    #[arg(long)]
+    pub sync_workers: Option<usize>,
     /// Number of tipsets to include in the sample that determines what the
     /// network head is (default is 5)
     #[arg(long)]
@@ -138,7 +155,8 @@ pub struct CliOpts {
     /// Track peak physical memory usage and print on exit
     #[arg(long)]
     pub track_peak_rss: bool,
-    /// Disable the automatic database garbage collection.
+    /// Disable the automatic database garbage collection, overriding the
+    /// `garbage_collection` section of the configuration.
     #[arg(long)]
     pub no_gc: bool,
     /// In stateless mode, forest connects to the P2P network but does not sync to HEAD.
@@ -150,6 +168,15 @@ pub struct CliOpts {
     /// Skip loading actors from the actors bundle.
     #[arg(long)]
     pub skip_load_actors: bool,
+    /// Mine blocks with the given miner actor, whose worker key is in the
+    /// wallet of the node. Only available on devnets.
+    #[arg(long)]
+    pub mine: Option<Address>,
+    /// Submit a `ReportConsensusFault` message from the given wallet address
+    /// for every consensus fault detected in the validated blocks. Requires
+    /// the RPC server to be enabled.
+    #[arg(long)]
+    pub report_consensus_faults: Option<Address>,
 }
 
 impl CliOpts {
@@ -207,9 +234,13 @@ impl CliOpts {
         if let Some(skip_load) = self.skip_load {
             cfg.client.skip_load = skip_load;
         }
+        if let Some(import_threads) = self.import_threads {
+            cfg.client.import_threads = import_threads;
+        }
 
         cfg.network.kademlia = self.kademlia.unwrap_or(cfg.network.kademlia);
         cfg.network.mdns = self.mdns.unwrap_or(cfg.network.mdns);
+        cfg.network.bootstrap = self.bootstrap.unwrap_or(cfg.network.bootstrap);
         if let Some(target_peer_count) = self.target_peer_count {
             cfg.network.target_peer_count = target_peer_count;
         }
@@ -219,6 +250,9 @@ impl CliOpts {
         if let Some(req_window) = self.req_window {
             cfg.sync.request_window = req_window;
         }
+        if let Some(sync_workers) = self.sync_workers {
+            cfg.sync.workers = sync_workers;
+        }
         if let Some(tipset_sample_size) = self.tipset_sample_size {
             cfg.sync.tipset_sample_size = tipset_sample_size.into();
         }
@@ -227,6 +261,9 @@ impl CliOpts {
         }
 
         cfg.client.load_actors = !self.skip_load_actors;
+        if self.no_gc {
+            cfg.garbage_collection.enabled = false;
+        }
 
         Ok((cfg, path))
     }
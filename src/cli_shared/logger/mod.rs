@@ -1,13 +1,139 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+mod telemetry;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr as _;
+
+use anyhow::Context as _;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
 use tracing_chrome::{ChromeLayerBuilder, FlushGuard};
-use tracing_subscriber::{filter::LevelFilter, prelude::*, EnvFilter};
+use tracing_subscriber::{filter::LevelFilter, prelude::*, reload, EnvFilter};
 
 use crate::cli_shared::cli::CliOpts;
 use crate::utils::misc::LoggingColor;
 
-pub fn setup_logger(opts: &CliOpts) -> (Option<tracing_loki::BackgroundTask>, Option<FlushGuard>) {
+pub use telemetry::{shutdown as shutdown_telemetry, TelemetryConfig};
+
+/// Filter of the logs printed to the standard output, which can be changed once the logger is
+/// set up. Both configuration reloads and runtime log levels go through it, so that neither
+/// overrides the other.
+struct ReloadableFilter {
+    /// Replaces the filter of the logging layer.
+    reload: Box<dyn Fn(EnvFilter) -> anyhow::Result<()> + Send + Sync>,
+    /// Directives of the configured filter.
+    directives: String,
+    /// Levels set at runtime, by target.
+    levels: BTreeMap<String, LevelFilter>,
+}
+
+impl ReloadableFilter {
+    fn apply(&self) -> anyhow::Result<()> {
+        let directives = std::iter::once(self.directives.clone())
+            .chain(
+                self.levels
+                    .iter()
+                    .map(|(target, level)| format!("{target}={level}")),
+            )
+            .filter(|directive| !directive.is_empty())
+            .collect::<Vec<_>>();
+        (self.reload)(EnvFilter::try_new(directives.join(","))?)
+    }
+}
+
+static RELOADABLE_FILTER: OnceCell<Mutex<ReloadableFilter>> = OnceCell::new();
+
+fn reloadable_filter() -> anyhow::Result<&'static Mutex<ReloadableFilter>> {
+    RELOADABLE_FILTER
+        .get()
+        .context("the logger doesn't support reloading its filter")
+}
+
+/// Configuration of the logs printed to the standard output.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+pub struct LogConfig {
+    /// Directives filtering the logs, in the format of `RUST_LOG`. The `RUST_LOG` environment
+    /// variable takes precedence.
+    pub filter: Option<String>,
+}
+
+impl LogConfig {
+    /// Returns the configured filter, or the default one.
+    fn env_filter(&self) -> anyhow::Result<EnvFilter> {
+        match &self.filter {
+            Some(directives) => EnvFilter::try_new(directives)
+                .with_context(|| format!("invalid log filter `{directives}`")),
+            None => Ok(default_env_filter()),
+        }
+    }
+}
+
+/// Replaces the filter of the logs printed to the standard output by the filter of `config`,
+/// unless the `RUST_LOG` environment variable is set. The levels set with [`set_log_level`] are
+/// kept.
+pub fn reload_filter(config: &LogConfig) -> anyhow::Result<()> {
+    let directives = get_env_filter(config.env_filter()?).to_string();
+    let mut filter = reloadable_filter()?.lock();
+    filter.directives = directives;
+    filter.apply()
+}
+
+/// Sets the level of the logs of `target`, e.g. `forest_filecoin::chain_sync`, printed to the
+/// standard output.
+pub fn set_log_level(target: &str, level: &str) -> anyhow::Result<()> {
+    validate_target(target)?;
+    let level = LevelFilter::from_str(level).with_context(|| format!("invalid level `{level}`"))?;
+    let mut filter = reloadable_filter()?.lock();
+    filter.levels.insert(target.to_owned(), level);
+    filter.apply()
+}
+
+/// Returns the targets whose level is set by the filter of the logs printed to the standard
+/// output.
+pub fn log_targets() -> anyhow::Result<Vec<String>> {
+    let filter = reloadable_filter()?.lock();
+    Ok(directive_targets(&filter.directives)
+        .chain(filter.levels.keys().cloned())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect())
+}
+
+/// Returns an error unless `target` is a module path, e.g. `forest_filecoin::chain_sync`, so that
+/// it can't add other directives to the filter.
+fn validate_target(target: &str) -> anyhow::Result<()> {
+    let is_module_path = target.split("::").all(|segment| {
+        !segment.is_empty()
+            && segment
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    });
+    anyhow::ensure!(
+        is_module_path,
+        "invalid target `{target}`, expected a module path"
+    );
+    Ok(())
+}
+
+/// Returns the targets of `RUST_LOG`-like directives, ignoring the directives without a target.
+fn directive_targets(directives: &str) -> impl Iterator<Item = String> + '_ {
+    directives.split(',').filter_map(|directive| {
+        let (target, _level) = directive.split_once('=')?;
+        let target = target.split('[').next().unwrap_or_default().trim();
+        (!target.is_empty()).then(|| target.to_owned())
+    })
+}
+
+pub fn setup_logger(
+    opts: &CliOpts,
+    log_config: &LogConfig,
+    telemetry_config: &TelemetryConfig,
+) -> (Option<tracing_loki::BackgroundTask>, Option<FlushGuard>) {
     let mut loki_task = None;
     let tracing_tokio_console = if opts.tokio_console {
         Some(
@@ -64,17 +190,54 @@ pub fn setup_logger(opts: &CliOpts) -> (Option<tracing_loki::BackgroundTask>, Op
             None => (None, None),
         };
 
+    // The spans are exported from a background thread, which wouldn't survive detaching.
+    let mut telemetry_error = None;
+    let telemetry_layer = match (telemetry_config.enabled, opts.detach) {
+        (true, true) => {
+            telemetry_error = Some("not supported with --detach".to_string());
+            None
+        }
+        (true, false) => match telemetry::layer(telemetry_config) {
+            Ok(layer) => Some(layer),
+            Err(e) => {
+                telemetry_error = Some(format!("{e:#}"));
+                None
+            }
+        },
+        (false, _) => None,
+    };
+
+    let (log_filter, log_filter_error) = match log_config.env_filter() {
+        Ok(filter) => (filter, None),
+        Err(e) => (default_env_filter(), Some(e)),
+    };
+    let stdout_filter = get_env_filter(log_filter);
+    let directives = stdout_filter.to_string();
+    let (stdout_filter, reload_handle) = reload::Layer::new(stdout_filter);
+
     tracing_subscriber::registry()
         .with(tracing_tokio_console)
         .with(tracing_loki)
         .with(tracing_rolling_file)
         .with(chrome_layer)
+        .with(telemetry_layer)
         .with(
             tracing_subscriber::fmt::Layer::new()
                 .with_ansi(opts.color.coloring_enabled())
-                .with_filter(get_env_filter(default_env_filter())),
+                .with_filter(stdout_filter),
         )
         .init();
+    let _ = RELOADABLE_FILTER.set(Mutex::new(ReloadableFilter {
+        reload: Box::new(move |filter| Ok(reload_handle.reload(filter)?)),
+        directives,
+        levels: BTreeMap::new(),
+    }));
+    if let Some(e) = log_filter_error {
+        tracing::warn!("Using the default log filter: {e:#}");
+    }
+    if let Some(e) = telemetry_error {
+        tracing::warn!("OpenTelemetry export disabled: {e}");
+    }
     (loki_task, flush_guard)
 }
 
@@ -139,3 +302,27 @@ fn default_tool_filter() -> EnvFilter {
 fn test_default_env_filter() {
     let _did_not_panic = default_env_filter();
 }
+
+#[test]
+fn test_directive_targets() {
+    let targets =
+        directive_targets("info,axum=warn,rpc[method]=error, libp2p_kad = off").collect::<Vec<_>>();
+    assert_eq!(targets, ["axum", "rpc", "libp2p_kad"]);
+}
+
+#[test]
+fn test_validate_target() {
+    for target in ["rpc", "forest_filecoin::chain_sync", "libp2p_kad"] {
+        assert!(validate_target(target).is_ok(), "{target}");
+    }
+    for target in [
+        "",
+        "forest_filecoin::",
+        "rpc=trace",
+        "rpc,libp2p_kad",
+        "rpc[method]",
+        "chain sync",
+    ] {
+        assert!(validate_target(target).is_err(), "{target}");
+    }
+}
@@ -0,0 +1,69 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Export of the tracing spans to an OpenTelemetry collector, over OTLP/HTTP.
+
+use anyhow::Context as _;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig as _;
+use opentelemetry_sdk::{runtime::TokioCurrentThread, trace, Resource};
+use serde::{Deserialize, Serialize};
+use tracing::Subscriber;
+use tracing_subscriber::{registry::LookupSpan, EnvFilter, Layer};
+
+/// Configuration of the OpenTelemetry trace export.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+pub struct TelemetryConfig {
+    /// Export the spans to the collector.
+    pub enabled: bool,
+    /// OTLP/HTTP endpoint of the collector.
+    pub endpoint: String,
+    /// Name of the service the spans are attributed to.
+    pub service_name: String,
+    /// Directives selecting the exported spans, in the format of `RUST_LOG`.
+    pub filter: String,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: "http://localhost:4318".into(),
+            service_name: "forest".into(),
+            filter: "forest_filecoin=info".into(),
+        }
+    }
+}
+
+/// Returns a layer exporting the spans selected by the configured filter. The spans are exported
+/// in batches, from a dedicated thread.
+pub fn layer<S>(config: &TelemetryConfig) -> anyhow::Result<impl Layer<S>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .http()
+                .with_endpoint(&config.endpoint),
+        )
+        .with_trace_config(trace::config().with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            config.service_name.clone(),
+        )])))
+        .install_batch(TokioCurrentThread)
+        .context("failed to set up the OpenTelemetry exporter")?;
+    let filter = EnvFilter::try_new(&config.filter)
+        .with_context(|| format!("invalid telemetry filter {}", config.filter))?;
+    Ok(tracing_opentelemetry::layer()
+        .with_tracer(tracer)
+        .with_filter(filter))
+}
+
+/// Exports the spans not exported yet, before the node exits.
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
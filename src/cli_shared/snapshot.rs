@@ -8,12 +8,17 @@ use std::{
 };
 
 use crate::{
+    libp2p::ed25519,
     networks::NetworkChain,
-    utils::{retry, RetryArgs},
+    utils::{
+        net::{global_http_client, http_get},
+        outbound::{retry, OutboundService},
+    },
 };
 use anyhow::{bail, Context as _};
+use base64::{prelude::BASE64_STANDARD, Engine as _};
 use chrono::NaiveDate;
-use tracing::event;
+use tracing::{debug, event, warn};
 use url::Url;
 
 use crate::cli_shared::snapshot::parse::ParsedFilename;
@@ -81,21 +86,31 @@ pub async fn download_file_with_retry(
     directory: &Path,
     filename: &str,
 ) -> anyhow::Result<PathBuf> {
-    Ok(retry(
-        RetryArgs {
-            timeout: None,
-            ..Default::default()
-        },
-        || download_http(url, directory, filename),
-    )
-    .await?)
+    retry(OutboundService::Snapshot, || {
+        download_http(url, directory, filename)
+    })
+    .await
 }
 
 /// Returns
 /// - The size of the snapshot from this vendor on this chain
 /// - The filename of the snapshot
 pub async fn peek(vendor: TrustedVendor, chain: &NetworkChain) -> anyhow::Result<(u64, String)> {
-    let stable_url = stable_url(vendor, chain)?;
+    let RemoteSnapshot { size, filename, .. } = peek_url(stable_url(vendor, chain)?).await?;
+    Ok((size, filename))
+}
+
+/// Snapshot served at the stable URL of a provider.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteSnapshot {
+    /// URL of the snapshot file, once the redirections of the stable URL are followed.
+    pub url: Url,
+    pub filename: String,
+    pub height: i64,
+    pub size: u64,
+}
+
+async fn peek_url(stable_url: Url) -> anyhow::Result<RemoteSnapshot> {
     // issue an actual GET, so the content length will be of the body
     // (we never actually fetch the body)
     // if we issue a HEAD, the content-length will be zero for our stable URLs
@@ -105,16 +120,175 @@ pub async fn peek(vendor: TrustedVendor, chain: &NetworkChain) -> anyhow::Result
         .await?
         .error_for_status()
         .context("server returned an error response")?;
-    let cd_path = response
+    let filename = response
         .headers()
         .get(reqwest::header::CONTENT_DISPOSITION)
-        .and_then(parse_content_disposition);
-    Ok((
-        response
+        .and_then(parse_content_disposition)
+        .context("no content-disposition filepath")?;
+    let (_date, height, _forest_format) = ParsedFilename::parse_str(&filename)
+        .context("unexpected path format")?
+        .date_and_height_and_forest();
+    Ok(RemoteSnapshot {
+        url: response.url().clone(),
+        size: response
             .content_length()
             .context("no content-length header")?,
-        cd_path.context("no content-disposition filepath")?,
-    ))
+        filename,
+        height,
+    })
+}
+
+/// Returns the highest snapshot served by the `providers`, stable URLs of the latest snapshot of
+/// `chain`, or by the default vendor if there is none. Unreachable providers are skipped.
+pub async fn latest(providers: &[Url], chain: &NetworkChain) -> anyhow::Result<RemoteSnapshot> {
+    let providers = match providers {
+        [] => vec![stable_url(TrustedVendor::default(), chain)?],
+        providers => providers.to_vec(),
+    };
+    let mut latest: Option<RemoteSnapshot> = None;
+    for provider in providers {
+        match peek_url(provider.clone()).await {
+            Ok(snapshot) => {
+                debug!(%provider, "latest snapshot at height {}", snapshot.height);
+                if latest
+                    .as_ref()
+                    .map_or(true, |latest| snapshot.height > latest.height)
+                {
+                    latest = Some(snapshot);
+                }
+            }
+            Err(e) => warn!(%provider, "failed to look up the latest snapshot: {e:#}"),
+        }
+    }
+    latest.context("no snapshot provider could be reached")
+}
+
+/// Returns the URL of the checksum file of the snapshot at `url`, following the naming of
+/// `forest-cli snapshot export`.
+pub fn checksum_url(url: &Url) -> anyhow::Result<Url> {
+    let filename = url
+        .path_segments()
+        .and_then(Iterator::last)
+        .filter(|filename| !filename.is_empty())
+        .context("no snapshot filename in URL")?;
+    Ok(url.join(
+        &Path::new(filename)
+            .with_extension("sha256sum")
+            .to_string_lossy(),
+    )?)
+}
+
+/// Verifies the snapshot at `location`, a local path or a URL, against the checksum file
+/// published next to it. With `publisher_keys`, the checksum file must come with a detached
+/// signature, with the `.sig` extension, by one of the keys. Returns the checksum the snapshot has
+/// to match, if there is one.
+pub async fn verify(
+    location: &str,
+    publisher_keys: &[ed25519::PublicKey],
+    require_checksum: bool,
+) -> anyhow::Result<Option<[u8; 32]>> {
+    let checksum_file = match Url::parse(location) {
+        Ok(url) => {
+            // Stable URLs redirect to the snapshot file, which the checksum file is next to.
+            let url = http_get(&url).await?.url().clone();
+            SiblingFile::Url(checksum_url(&url)?)
+        }
+        Err(_) => SiblingFile::Path(Path::new(location).with_extension("sha256sum")),
+    };
+    let Some(checksum) = checksum_file.read().await? else {
+        if require_checksum || !publisher_keys.is_empty() {
+            bail!("no checksum published at {checksum_file}");
+        }
+        warn!("No checksum published at {checksum_file}, the snapshot isn't verified");
+        return Ok(None);
+    };
+    if !publisher_keys.is_empty() {
+        let signature_file = checksum_file.with_suffix(".sig")?;
+        let signature = signature_file
+            .read()
+            .await?
+            .with_context(|| format!("no signature published at {signature_file}"))?;
+        let signature = BASE64_STANDARD
+            .decode(signature.trim())
+            .context("invalid signature encoding")?;
+        if !publisher_keys
+            .iter()
+            .any(|key| key.verify(checksum.as_bytes(), &signature))
+        {
+            bail!("the snapshot checksum isn't signed by a trusted publisher");
+        }
+        debug!("Snapshot checksum signature is valid");
+    }
+    parse_checksum(&checksum).map(Some)
+}
+
+/// Parses a base64-encoded Ed25519 public key of a snapshot publisher.
+pub fn parse_publisher_key(key: &str) -> anyhow::Result<ed25519::PublicKey> {
+    let bytes = BASE64_STANDARD
+        .decode(key)
+        .with_context(|| format!("invalid publisher key encoding: {key}"))?;
+    ed25519::PublicKey::try_from_bytes(&bytes)
+        .with_context(|| format!("invalid publisher key: {key}"))
+}
+
+/// File published next to a snapshot, e.g. its checksum.
+enum SiblingFile {
+    Url(Url),
+    Path(PathBuf),
+}
+
+impl SiblingFile {
+    fn with_suffix(&self, suffix: &str) -> anyhow::Result<Self> {
+        Ok(match self {
+            SiblingFile::Url(url) => SiblingFile::Url(Url::parse(&format!("{url}{suffix}"))?),
+            SiblingFile::Path(path) => {
+                let mut path = path.clone().into_os_string();
+                path.push(suffix);
+                SiblingFile::Path(path.into())
+            }
+        })
+    }
+
+    /// Returns the content of the file, or [`None`] if it doesn't exist.
+    async fn read(&self) -> anyhow::Result<Option<String>> {
+        match self {
+            SiblingFile::Url(url) => {
+                retry(OutboundService::Snapshot, || async {
+                    let response = global_http_client().get(url.clone()).send().await?;
+                    if response.status() == reqwest::StatusCode::NOT_FOUND {
+                        return Ok(None);
+                    }
+                    Ok(Some(response.error_for_status()?.text().await?))
+                })
+                .await
+            }
+            SiblingFile::Path(path) => match tokio::fs::read_to_string(path).await {
+                Ok(content) => Ok(Some(content)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e.into()),
+            },
+        }
+    }
+}
+
+impl Display for SiblingFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SiblingFile::Url(url) => url.fmt(f),
+            SiblingFile::Path(path) => path.display().fmt(f),
+        }
+    }
+}
+
+/// Parses the first checksum of the content of a file in the `sha256sum` format.
+pub fn parse_checksum(content: &str) -> anyhow::Result<[u8; 32]> {
+    let checksum = content
+        .split_whitespace()
+        .next()
+        .context("empty checksum file")?;
+    let mut digest = [0; 32];
+    hex::decode_to_slice(checksum, &mut digest).context("invalid SHA-256 checksum")?;
+    Ok(digest)
 }
 
 // Extract file paths from content-disposition values:
@@ -440,9 +614,89 @@ mod parse {
 
 #[cfg(test)]
 mod tests {
-    use super::parse_content_disposition;
+    use super::*;
     use reqwest::header::HeaderValue;
 
+    /// Writes a snapshot with its checksum file, signed by `publisher` if any, and returns the
+    /// path of the snapshot.
+    fn publish(dir: &Path, publisher: Option<&ed25519::Keypair>) -> String {
+        let snapshot = dir.join("snapshot.car.zst");
+        std::fs::write(&snapshot, b"snapshot").unwrap();
+        let checksum =
+            "3386191dc5c285074c3827452f4e3b685e3253f5b9ca7c4c2bb3f44d1263aef1 snapshot.car.zst\n";
+        std::fs::write(dir.join("snapshot.car.sha256sum"), checksum).unwrap();
+        if let Some(publisher) = publisher {
+            std::fs::write(
+                dir.join("snapshot.car.sha256sum.sig"),
+                BASE64_STANDARD.encode(publisher.sign(checksum.as_bytes())),
+            )
+            .unwrap();
+        }
+        snapshot.display().to_string()
+    }
+
+    #[tokio::test]
+    async fn verify_signed_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let publisher = ed25519::Keypair::generate();
+        let snapshot = publish(dir.path(), Some(&publisher));
+        let key =
+            parse_publisher_key(&BASE64_STANDARD.encode(publisher.public().to_bytes())).unwrap();
+
+        let checksum = verify(&snapshot, &[key], true).await.unwrap().unwrap();
+        assert_eq!(
+            hex::encode(checksum),
+            "3386191dc5c285074c3827452f4e3b685e3253f5b9ca7c4c2bb3f44d1263aef1"
+        );
+
+        let other = ed25519::Keypair::generate().public();
+        verify(&snapshot, &[other], false).await.unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn verify_unsigned_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = publish(dir.path(), None);
+        assert!(verify(&snapshot, &[], false).await.unwrap().is_some());
+
+        let key = ed25519::Keypair::generate().public();
+        verify(&snapshot, &[key], false).await.unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn verify_without_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = dir.path().join("snapshot.car.zst").display().to_string();
+        assert!(verify(&snapshot, &[], false).await.unwrap().is_none());
+        verify(&snapshot, &[], true).await.unwrap_err();
+    }
+
+    #[test]
+    fn checksum_next_to_snapshot() {
+        let url = Url::parse(
+            "https://forest-archive.chainsafe.dev/calibnet/forest_snapshot_calibnet_2023-09-14_height_911888.forest.car.zst",
+        )
+        .unwrap();
+        assert_eq!(
+            checksum_url(&url).unwrap().as_str(),
+            "https://forest-archive.chainsafe.dev/calibnet/forest_snapshot_calibnet_2023-09-14_height_911888.forest.car.sha256sum"
+        );
+        assert!(
+            checksum_url(&Url::parse("https://forest-archive.chainsafe.dev/").unwrap()).is_err()
+        );
+    }
+
+    #[test]
+    fn checksum_in_sha256sum_format() {
+        let checksum = "3386191dc5c285074c3827452f4e3b685e3253f5b9ca7c4c2bb3f44d1263aef1";
+        assert_eq!(
+            hex::encode(parse_checksum(&format!("{checksum} snapshot.car.zst\n")).unwrap()),
+            checksum
+        );
+        assert!(parse_checksum("").is_err());
+        assert!(parse_checksum("not-hex snapshot.car.zst").is_err());
+    }
+
     #[test]
     fn content_disposition_forest() {
         assert_eq!(
@@ -0,0 +1,103 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Archival mode: the state of epochs that are missing from the database is
+//! retrieved when it is queried, rather than stored up-front.
+//!
+//! Blocks are looked up in the archival CAR files first, and then fetched
+//! from the bitswap peers of the node, which stores them in the database so
+//! that later queries are served locally. Waiting for a peer blocks the
+//! querying thread, so the worker threads of the runtime hand their tasks over
+//! to other threads while they wait, and nothing is fetched from a
+//! single-threaded runtime, whose only thread would stall.
+
+use std::num::NonZeroUsize;
+use std::time::{Duration, Instant};
+
+use crate::db::car::BlockFetcher;
+use crate::libp2p::NetworkMessage;
+use cid::Cid;
+use lru::LruCache;
+use nonzero_ext::nonzero;
+use parking_lot::Mutex;
+use tokio::runtime::{Handle, RuntimeFlavor};
+use tracing::debug;
+
+/// Number of blocks that failed to be fetched that are remembered.
+const FAILURES_CACHE_SIZE: NonZeroUsize = nonzero!(4096usize);
+/// Blocks that failed to be fetched aren't requested again for this long, so
+/// that queries don't keep waiting on blocks that no peer has.
+const FAILURE_RETRY_DELAY: Duration = Duration::from_secs(600);
+
+/// Fetches blocks from the bitswap peers of the node.
+pub struct BitswapFetcher {
+    network_send: flume::Sender<NetworkMessage>,
+    timeout: Duration,
+    failures: Mutex<LruCache<Cid, Instant>>,
+}
+
+impl BitswapFetcher {
+    pub fn new(network_send: flume::Sender<NetworkMessage>, timeout: Duration) -> Self {
+        BitswapFetcher {
+            network_send,
+            timeout,
+            failures: Mutex::new(LruCache::new(FAILURES_CACHE_SIZE)),
+        }
+    }
+}
+
+impl BitswapFetcher {
+    /// Sends the bitswap request built by `message` for `cid`, and waits for
+    /// its outcome.
+    fn request(
+        &self,
+        cid: &Cid,
+        message: impl FnOnce(flume::Sender<bool>) -> NetworkMessage,
+    ) -> bool {
+        if let Some(failed_at) = self.failures.lock().get(cid) {
+            if failed_at.elapsed() < FAILURE_RETRY_DELAY {
+                return false;
+            }
+        }
+
+        let request = || {
+            let (tx, rx) = flume::bounded(1);
+            self.network_send.send(message(tx)).is_ok()
+                && rx.recv_timeout(self.timeout).unwrap_or_default()
+        };
+        let fetched = match Handle::try_current().map(|handle| handle.runtime_flavor()) {
+            // Worker threads hand their tasks over to other threads first,
+            // other threads, e.g. of `spawn_blocking`, just block.
+            Err(_) | Ok(RuntimeFlavor::MultiThread) => tokio::task::block_in_place(request),
+            Ok(_) => {
+                debug!("Not fetching {cid} over bitswap from a single-threaded runtime");
+                return false;
+            }
+        };
+        if fetched {
+            debug!("Fetched {cid} over bitswap");
+            self.failures.lock().pop(cid);
+        } else {
+            debug!("Failed to fetch {cid} over bitswap");
+            self.failures.lock().put(*cid, Instant::now());
+        }
+        fetched
+    }
+}
+
+impl BlockFetcher for BitswapFetcher {
+    fn fetch(&self, cid: &Cid) -> bool {
+        self.request(cid, |response_channel| NetworkMessage::BitswapRequest {
+            cid: *cid,
+            response_channel,
+            epoch: None,
+        })
+    }
+
+    fn refetch(&self, cid: &Cid) -> bool {
+        self.request(cid, |response_channel| NetworkMessage::BitswapRefetch {
+            cid: *cid,
+            response_channel,
+        })
+    }
+}
@@ -2,15 +2,22 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use crate::{
-    networks::{ActorBundleInfo, NetworkChain, ACTOR_BUNDLES},
-    utils::{db::car_util::load_car, net::http_get},
+    networks::{ActorBundleInfo, ActorBundleSource, NetworkChain, ACTOR_BUNDLES},
+    shim::machine::BuiltinActorManifest,
+    utils::{
+        db::car_stream::CarStream,
+        net::http_get,
+        outbound::{retry, OutboundService},
+    },
 };
-use anyhow::ensure;
+use anyhow::{ensure, Context as _};
+use cid::Cid;
 use futures::{stream::FuturesUnordered, TryStreamExt};
 use fvm_ipld_blockstore::Blockstore;
+use reqwest::Url;
 use std::io::Cursor;
 use std::mem::discriminant;
-use tracing::warn;
+use tracing::{info, warn};
 
 /// Tries to load the missing actor bundles to the blockstore. If the bundle is
 /// not present, it will be downloaded.
@@ -29,23 +36,11 @@ pub async fn load_actor_bundles(
             })
             .map(
                 |ActorBundleInfo {
-                     manifest: root,
+                     manifest,
                      url,
                      alt_url,
-                     network: _,
-                 }| async move {
-                    let response = if let Ok(response) = http_get(url).await {
-                        response
-                    } else {
-                        warn!("failed to download bundle from primary URL, trying alternative URL");
-                        http_get(alt_url).await?
-                    };
-                    let bytes = response.bytes().await?;
-                    let header = load_car(db, Cursor::new(bytes)).await?;
-                    ensure!(header.roots.len() == 1);
-                    ensure!(&header.roots[0] == root);
-                    Ok(())
-                },
+                     ..
+                 }| fetch_actor_bundle(db, manifest, &[url, alt_url]),
             ),
     )
     .try_collect::<Vec<_>>()
@@ -53,3 +48,64 @@ pub async fn load_actor_bundles(
 
     Ok(())
 }
+
+/// Tries to load the missing actor bundles of the configuration to the
+/// blockstore, downloading them from their URL.
+pub async fn load_actor_bundle_sources(
+    db: &impl Blockstore,
+    sources: &[ActorBundleSource],
+) -> anyhow::Result<()> {
+    FuturesUnordered::from_iter(
+        sources
+            .iter()
+            .filter(|source| !db.has(&source.manifest).unwrap_or(false))
+            .map(|ActorBundleSource { manifest, url }| fetch_actor_bundle(db, manifest, &[url])),
+    )
+    .try_collect::<Vec<_>>()
+    .await?;
+
+    Ok(())
+}
+
+/// Downloads the bundle from the first URL that works and stores its blocks,
+/// once their CIDs and the manifest have been verified.
+async fn fetch_actor_bundle(
+    db: &impl Blockstore,
+    manifest: &Cid,
+    urls: &[&Url],
+) -> anyhow::Result<()> {
+    let bytes = retry(OutboundService::ActorBundle, || async {
+        let mut urls = urls.iter();
+        let response = loop {
+            let url = urls.next().context("no actor bundle URL")?;
+            match http_get(url).await {
+                Ok(response) => break response,
+                Err(e) if urls.len() > 0 => {
+                    warn!("failed to download bundle from {url}, trying alternative URL: {e}")
+                }
+                Err(e) => return Err(e),
+            }
+        };
+        anyhow::Ok(response.bytes().await?)
+    })
+    .await?;
+
+    let mut stream = CarStream::new(Cursor::new(bytes)).await?;
+    ensure!(
+        stream.header.roots == [*manifest],
+        "actor bundle roots {:?} don't match the manifest {manifest}",
+        stream.header.roots
+    );
+    // Verify all the blocks before storing any, so that a corrupted download
+    // doesn't leave a partial bundle behind.
+    let mut blocks = vec![];
+    while let Some(block) = stream.try_next().await? {
+        ensure!(block.valid(), "invalid block {} in actor bundle", block.cid);
+        blocks.push(block);
+    }
+    db.put_many_keyed(blocks.into_iter().map(|block| (block.cid, block.data)))?;
+    BuiltinActorManifest::load_manifest(db, manifest)
+        .with_context(|| format!("invalid actor bundle {manifest}"))?;
+    info!("Loaded actor bundle {manifest}");
+    Ok(())
+}
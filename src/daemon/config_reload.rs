@@ -0,0 +1,213 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Reloading of the configuration of a running node, on `SIGHUP` or on request. The changes that
+//! are safe at runtime are applied, and the other ones are reported as requiring a restart.
+//!
+//! The metrics endpoint binds its listener once at startup, so enabling, disabling or moving it
+//! requires a restart, as do the other listeners.
+
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+use crate::cli_shared::cli::{CliOpts, Config};
+use crate::cli_shared::logger;
+use crate::health::HealthcheckConfig;
+use crate::libp2p::{NetRPCMethods, NetworkMessage};
+use crate::lotus_json::lotus_json_with_self;
+use crate::message_pool::FeeConfig;
+use anyhow::Context as _;
+use futures::channel::oneshot;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{info, warn};
+
+/// Outcome of a configuration reload.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ConfigReloadReport {
+    /// Changed fields, applied to the running node.
+    pub applied: Vec<String>,
+    /// Changed fields that only take effect once the node is restarted.
+    pub requires_restart: Vec<String>,
+}
+
+lotus_json_with_self!(ConfigReloadReport);
+
+/// Request for a configuration reload. The outcome of the reload is sent back on the channel.
+pub type ConfigReloadRequest = flume::Sender<anyhow::Result<ConfigReloadReport>>;
+
+/// Re-reads the configuration the node was started with, and applies its changes.
+pub struct ConfigReloader {
+    opts: CliOpts,
+    /// Configuration the node runs with.
+    current: Config,
+    network_send: flume::Sender<NetworkMessage>,
+    healthcheck: Arc<RwLock<HealthcheckConfig>>,
+    fee: Arc<RwLock<FeeConfig>>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        opts: CliOpts,
+        network_send: flume::Sender<NetworkMessage>,
+        healthcheck: Arc<RwLock<HealthcheckConfig>>,
+        fee: Arc<RwLock<FeeConfig>>,
+    ) -> anyhow::Result<Self> {
+        let (current, _) = opts.to_config()?;
+        Ok(Self {
+            opts,
+            current,
+            network_send,
+            healthcheck,
+            fee,
+        })
+    }
+
+    /// Reloads the configuration on `SIGHUP` and on `requests`, until both are closed.
+    pub async fn reload_loop(
+        mut self,
+        requests: flume::Receiver<ConfigReloadRequest>,
+    ) -> anyhow::Result<()> {
+        let mut hangup = signal(SignalKind::hangup())?;
+        loop {
+            tokio::select! {
+                Some(()) = hangup.recv() => {
+                    info!("Received SIGHUP, reloading the configuration");
+                    match self.reload().await {
+                        Ok(report) => log_report(&report),
+                        Err(e) => warn!("Failed to reload the configuration: {e:#}"),
+                    }
+                }
+                Ok(request) = requests.recv_async() => {
+                    let result = self.reload().await;
+                    if let Ok(report) = &result {
+                        log_report(report);
+                    }
+                    // The requester may have given up waiting.
+                    let _ = request.send(result);
+                }
+                else => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Re-reads the configuration and applies the changes that are safe at runtime. Changes that
+    /// require a restart are reported again by the next reloads.
+    pub async fn reload(&mut self) -> anyhow::Result<ConfigReloadReport> {
+        let (new, _) = self
+            .opts
+            .to_config()
+            .context("failed to read the configuration")?;
+        let mut report = ConfigReloadReport::default();
+        for field in changed_fields(&self.current, &new)? {
+            match field.as_str() {
+                "log.filter" => {
+                    logger::reload_filter(&new.log)?;
+                    self.current.log = new.log.clone();
+                }
+                "network.target_peer_count" => {
+                    let target_peer_count = new.network.target_peer_count;
+                    let (tx, rx) = oneshot::channel();
+                    self.network_send
+                        .send_async(NetworkMessage::JSONRPCRequest {
+                            method: NetRPCMethods::SetTargetPeerCount(tx, target_peer_count.into()),
+                        })
+                        .await?;
+                    rx.await?;
+                    self.current.network.target_peer_count = target_peer_count;
+                }
+                "healthcheck.min_peers" => {
+                    self.healthcheck.write().min_peers = new.healthcheck.min_peers;
+                    self.current.healthcheck.min_peers = new.healthcheck.min_peers;
+                }
+                "healthcheck.max_epoch_lag" => {
+                    self.healthcheck.write().max_epoch_lag = new.healthcheck.max_epoch_lag;
+                    self.current.healthcheck.max_epoch_lag = new.healthcheck.max_epoch_lag;
+                }
+                "fee.max_fee" => {
+                    self.fee.write().max_fee = new.fee.max_fee.clone();
+                    self.current.fee.max_fee = new.fee.max_fee.clone();
+                }
+                _ => {
+                    report.requires_restart.push(field);
+                    continue;
+                }
+            }
+            report.applied.push(field);
+        }
+        Ok(report)
+    }
+}
+
+fn log_report(report: &ConfigReloadReport) {
+    info!(
+        "Configuration reloaded, applied: {:?}, requiring a restart: {:?}",
+        report.applied, report.requires_restart
+    );
+}
+
+/// Returns the dotted paths of the fields that differ between two configurations.
+fn changed_fields(old: &Config, new: &Config) -> anyhow::Result<Vec<String>> {
+    let mut changed = vec![];
+    diff(
+        "",
+        &toml::Value::try_from(old)?,
+        &toml::Value::try_from(new)?,
+        &mut changed,
+    );
+    Ok(changed)
+}
+
+fn diff(path: &str, old: &toml::Value, new: &toml::Value, changed: &mut Vec<String>) {
+    match (old, new) {
+        (toml::Value::Table(old), toml::Value::Table(new)) => {
+            let keys = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+            for key in keys {
+                let path = match path {
+                    "" => key.clone(),
+                    _ => format!("{path}.{key}"),
+                };
+                match (old.get(key), new.get(key)) {
+                    (Some(old), Some(new)) => diff(&path, old, new, changed),
+                    _ => changed.push(path),
+                }
+            }
+        }
+        (old, new) => {
+            if old != new {
+                changed.push(path.to_owned());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unchanged_config_has_no_changed_fields() {
+        let config = Config::default();
+        assert!(changed_fields(&config, &config).unwrap().is_empty());
+    }
+
+    #[test]
+    fn changed_fields_are_dotted_paths() {
+        let old = Config::default();
+        let mut new = old.clone();
+        new.network.target_peer_count += 1;
+        new.log.filter = Some("debug".into());
+        new.client.enable_rpc = !new.client.enable_rpc;
+        assert_eq!(
+            changed_fields(&old, &new).unwrap(),
+            [
+                "client.enable_rpc",
+                "log.filter",
+                "network.target_peer_count"
+            ]
+        );
+    }
+}
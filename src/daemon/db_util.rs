@@ -2,21 +2,22 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use crate::blocks::Tipset;
-use crate::cli_shared::snapshot;
-use crate::db::car::forest::FOREST_CAR_FILE_EXTENSION;
+use crate::db::car::forest::{
+    DEFAULT_FOREST_CAR_COMPRESSION_LEVEL, DEFAULT_FOREST_CAR_FRAME_SIZE, FOREST_CAR_FILE_EXTENSION,
+};
 use crate::db::car::{ForestCar, ManyCar};
 use crate::utils::db::car_stream::CarStream;
-use crate::utils::io::EitherMmapOrRandomAccessFile;
+use crate::utils::io::{AsyncReaderWithChecksum, EitherMmapOrRandomAccessFile};
 use anyhow::Context as _;
 use futures::TryStreamExt;
-use std::ffi::OsStr;
+use sha2::{Digest as _, Sha256};
 use std::fs;
 use std::io;
 use std::{
     path::{Path, PathBuf},
     time,
 };
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncBufRead, AsyncWriteExt};
 use tracing::{debug, info};
 use url::Url;
 use walkdir::WalkDir;
@@ -41,7 +42,7 @@ pub fn load_all_forest_cars<T>(store: &ManyCar<T>, forest_car_db_dir: &Path) ->
     {
         let car = ForestCar::try_from(file.as_path())
             .with_context(|| format!("Error loading car DB at {}", file.display()))?;
-        store.read_only(car.into());
+        store.read_only_file(file.clone(), car.into());
         debug!("Loaded car DB at {}", file.display());
     }
 
@@ -49,39 +50,69 @@ pub fn load_all_forest_cars<T>(store: &ManyCar<T>, forest_car_db_dir: &Path) ->
 }
 
 /// This function validates and stores the CAR binary from `from_path`(either local path or URL) into the `{DB_ROOT}/car_db/`
-/// (automatically trans-code into `.forest.car.zst` format when needed), and returns its final file path and the heaviest tipset.
+/// (automatically trans-code into `.forest.car.zst` format when needed, on `import_threads` threads, or one per CPU if `0`),
+/// and returns its final file path and the heaviest tipset.
+/// Snapshots at URLs are downloaded, decompressed and trans-coded in a single pass, resuming the download on failures,
+/// so that they are never stored on disk as is.
+/// With `expected_sha256`, the import fails if the SHA-256 checksum of the snapshot doesn't match.
 pub async fn import_chain_as_forest_car(
     from_path: &Path,
     forest_car_db_dir: &Path,
     consume_snapshot_file: bool,
+    import_threads: usize,
+    expected_sha256: Option<[u8; 32]>,
 ) -> anyhow::Result<(PathBuf, Tipset)> {
     info!("Importing chain from snapshot at: {}", from_path.display());
 
     let stopwatch = time::Instant::now();
 
-    let downloaded_car_temp_path =
-        tempfile::NamedTempFile::new_in(forest_car_db_dir)?.into_temp_path();
-    if let Ok(url) = Url::parse(&from_path.display().to_string()) {
-        download_to(&url, &downloaded_car_temp_path).await?;
-    } else {
-        move_or_copy_file(from_path, &downloaded_car_temp_path, consume_snapshot_file)?;
-    }
-
     let forest_car_db_path = forest_car_db_dir.join(format!(
         "{}{FOREST_CAR_FILE_EXTENSION}",
         chrono::Utc::now().timestamp_millis()
     ));
 
-    if ForestCar::is_valid(&EitherMmapOrRandomAccessFile::open(
-        &downloaded_car_temp_path,
-    )?) {
-        downloaded_car_temp_path.persist(&forest_car_db_path)?;
-    } else {
-        // Use another temp file to make sure all final `.forest.car.zst` files are complete and valid.
+    if let Ok(url) = Url::parse(&from_path.display().to_string()) {
         let forest_car_db_temp_path =
             tempfile::NamedTempFile::new_in(forest_car_db_dir)?.into_temp_path();
-        transcode_into_forest_car(&downloaded_car_temp_path, &forest_car_db_temp_path).await?;
+        let mut reader = tokio::io::BufReader::new(AsyncReaderWithChecksum::<Sha256, _>::new(
+            crate::utils::net::reader(url.as_str()).await?,
+        ));
+        transcode_into_forest_car(&mut reader, &forest_car_db_temp_path, import_threads)
+            .await
+            .with_context(|| format!("failed to import the snapshot at {url}"))?;
+        if let Some(expected) = expected_sha256 {
+            // The CAR stream may end before the file does, e.g. on trailing zstd skippable frames.
+            tokio::io::copy(&mut reader, &mut tokio::io::sink()).await?;
+            ensure_checksum(&reader.into_inner().finalize(), &expected)?;
+        }
         forest_car_db_temp_path.persist(&forest_car_db_path)?;
+    } else {
+        if let Some(expected) = expected_sha256 {
+            let mut reader =
+                AsyncReaderWithChecksum::<Sha256, _>::new(tokio::fs::File::open(from_path).await?);
+            tokio::io::copy(&mut reader, &mut tokio::io::sink()).await?;
+            ensure_checksum(&reader.finalize(), &expected)?;
+        }
+        let downloaded_car_temp_path =
+            tempfile::NamedTempFile::new_in(forest_car_db_dir)?.into_temp_path();
+        move_or_copy_file(from_path, &downloaded_car_temp_path, consume_snapshot_file)?;
+
+        if ForestCar::is_valid(&EitherMmapOrRandomAccessFile::open(
+            &downloaded_car_temp_path,
+        )?) {
+            downloaded_car_temp_path.persist(&forest_car_db_path)?;
+        } else {
+            // Use another temp file to make sure all final `.forest.car.zst` files are complete and valid.
+            let forest_car_db_temp_path =
+                tempfile::NamedTempFile::new_in(forest_car_db_dir)?.into_temp_path();
+            transcode_into_forest_car(
+                tokio::io::BufReader::new(tokio::fs::File::open(&downloaded_car_temp_path).await?),
+                &forest_car_db_temp_path,
+                import_threads,
+            )
+            .await?;
+            forest_car_db_temp_path.persist(&forest_car_db_path)?;
+        }
     }
 
     let ts = ForestCar::try_from(forest_car_db_path.as_path())?.heaviest_tipset()?;
@@ -94,22 +125,14 @@ pub async fn import_chain_as_forest_car(
     Ok((forest_car_db_path, ts))
 }
 
-async fn download_to(url: &Url, destination: &Path) -> anyhow::Result<()> {
-    snapshot::download_file_with_retry(
-        url,
-        destination.parent().with_context(|| {
-            format!(
-                "Error getting the parent directory of {}",
-                destination.display()
-            )
-        })?,
-        destination
-            .file_name()
-            .and_then(OsStr::to_str)
-            .with_context(|| format!("Error getting the file name of {}", destination.display()))?,
-    )
-    .await?;
-
+fn ensure_checksum(actual: &[u8], expected: &[u8; 32]) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        actual == expected,
+        "snapshot checksum mismatch, expected {} but got {}",
+        hex::encode(expected),
+        hex::encode(actual)
+    );
+    debug!("Snapshot checksum is valid");
     Ok(())
 }
 
@@ -125,15 +148,25 @@ fn move_or_copy_file(from: &Path, to: &Path, consume: bool) -> io::Result<()> {
     }
 }
 
-async fn transcode_into_forest_car(from: &Path, to: &Path) -> anyhow::Result<()> {
-    let car_stream = CarStream::new(tokio::io::BufReader::new(
-        tokio::fs::File::open(from).await?,
-    ))
-    .await?;
+// Blocks are decoded from `from`, then verified and compressed into frames in
+// parallel, and finally written to `to` in order.
+async fn transcode_into_forest_car(
+    from: impl AsyncBufRead + Unpin,
+    to: &Path,
+    import_threads: usize,
+) -> anyhow::Result<()> {
+    let import_threads = match import_threads {
+        0 => std::thread::available_parallelism().map_or(1, usize::from),
+        n => n,
+    };
+    let car_stream = CarStream::new(from).await?;
     let roots = car_stream.header.roots.clone();
 
     let mut writer = tokio::io::BufWriter::new(tokio::fs::File::create(to).await?);
-    let frames = crate::db::car::forest::Encoder::compress_stream_default(
+    let frames = crate::db::car::forest::Encoder::compress_stream_parallel(
+        DEFAULT_FOREST_CAR_FRAME_SIZE,
+        DEFAULT_FOREST_CAR_COMPRESSION_LEVEL,
+        import_threads,
         car_stream.map_err(anyhow::Error::from),
     );
     crate::db::car::forest::Encoder::write(&mut writer, roots, frames).await?;
@@ -177,10 +210,37 @@ mod test {
             .unwrap_err();
     }
 
+    #[tokio::test]
+    async fn import_snapshot_from_url_valid() {
+        let snapshot = std::fs::read("test-snapshots/chain4.car.zst").unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/chain4.car.zst", listener.local_addr().unwrap());
+        let app = axum::Router::new().route(
+            "/chain4.car.zst",
+            axum::routing::get(|| async move { snapshot }),
+        );
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        import_snapshot_from_file(&url).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn import_snapshot_with_checksum() {
+        let snapshot = "test-snapshots/chain4.car.zst";
+        let checksum: [u8; 32] = Sha256::digest(std::fs::read(snapshot).unwrap()).into();
+        import_snapshot(snapshot, Some(checksum)).await.unwrap();
+        import_snapshot(snapshot, Some([0; 32])).await.unwrap_err();
+    }
+
     async fn import_snapshot_from_file(file_path: &str) -> anyhow::Result<()> {
+        import_snapshot(file_path, None).await
+    }
+
+    async fn import_snapshot(file_path: &str, checksum: Option<[u8; 32]>) -> anyhow::Result<()> {
         let temp = tempfile::Builder::new().tempdir()?;
         let (path, ts) =
-            import_chain_as_forest_car(Path::new(file_path), temp.path(), false).await?;
+            import_chain_as_forest_car(Path::new(file_path), temp.path(), false, 2, checksum)
+                .await?;
         assert!(path.is_file());
         assert!(ts.epoch() > 0);
         Ok(())
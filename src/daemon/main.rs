@@ -6,7 +6,7 @@ use crate::cli_shared::{
     cli::{check_for_unknown_keys, cli_error_and_die, ConfigPath, DaemonConfig},
     logger,
 };
-use crate::daemon::ipc_shmem_conf;
+use crate::daemon::{ipc_shmem_conf, ShutdownKind};
 use crate::utils::version::FOREST_VERSION_STRING;
 use anyhow::Context as _;
 use clap::Parser;
@@ -16,6 +16,7 @@ use raw_sync_2::{
     Timeout,
 };
 use std::ffi::OsString;
+use std::os::unix::process::CommandExt as _;
 use std::{fs::File, process, time::Duration};
 use tracing::info;
 
@@ -65,6 +66,15 @@ fn build_daemon<'a>(config: &DaemonConfig) -> anyhow::Result<Daemon<'a>> {
     Ok(daemon)
 }
 
+/// Replaces the process with a new Forest process, started with the same command line.
+fn restart() -> anyhow::Result<()> {
+    info!("Restarting Forest");
+    let err = process::Command::new(std::env::current_exe()?)
+        .args(std::env::args_os().skip(1))
+        .exec();
+    Err(err).context("failed to restart Forest")
+}
+
 /// CLI structure generated when interacting with Forest binary
 #[derive(Parser)]
 #[command(name = env!("CARGO_PKG_NAME"), author = env!("CARGO_PKG_AUTHORS"), version = FOREST_VERSION_STRING.as_str(), about = env!("CARGO_PKG_DESCRIPTION"))]
@@ -87,7 +97,7 @@ where
     // Run forest as a daemon if no other subcommands are used. Otherwise, run the
     // subcommand.
 
-    let (loki_task, _chrome_flush_guard) = logger::setup_logger(&opts);
+    let (loki_task, _chrome_flush_guard) = logger::setup_logger(&opts, &cfg.log, &cfg.telemetry);
 
     if let Some(path) = &path {
         match path {
@@ -131,10 +141,14 @@ where
                 rt.spawn(loki_task);
             }
             let ret = rt.block_on(super::start_interruptable(opts, cfg));
+            logger::shutdown_telemetry();
             info!("Shutting down tokio...");
             rt.shutdown_timeout(Duration::from_secs_f32(0.5));
             info!("Forest finish shutdown");
-            ret
+            match ret? {
+                ShutdownKind::Stop => Ok(()),
+                ShutdownKind::Restart => restart(),
+            }
         }
     }
 }
@@ -1,32 +1,39 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+mod archival;
 pub mod bundle;
+pub mod config_reload;
 mod db_util;
 pub mod main;
 
 use crate::auth::{create_token, generate_priv_key, ADMIN, JWT_IDENTIFIER};
 use crate::blocks::Tipset;
 use crate::chain::ChainStore;
-use crate::chain_sync::ChainMuxer;
+use crate::chain_sync::{consensus::Proposer as _, BadBlockCache, ChainMuxer};
 use crate::cli_shared::snapshot;
 use crate::cli_shared::{
     chain_path,
     cli::{CliOpts, Config},
 };
 
+use crate::daemon::archival::BitswapFetcher;
+use crate::daemon::config_reload::ConfigReloader;
 use crate::daemon::db_util::{import_chain_as_forest_car, load_all_forest_cars};
 use crate::db::car::ManyCar;
 use crate::db::db_engine::{db_root, open_db};
-use crate::db::MarkAndSweep;
+use crate::db::{BlockCache, HealthCheck, MarkAndSweep, Quarantine};
+use crate::fil_cns::FilecoinProposer;
 use crate::genesis::{get_network_name_from_genesis, read_genesis_header};
 use crate::key_management::{
-    KeyStore, KeyStoreConfig, ENCRYPTED_KEYSTORE_NAME, FOREST_KEYSTORE_PHRASE_ENV,
+    os_keyring, KeyStore, KeyStoreConfig, ENCRYPTED_KEYSTORE_NAME, FOREST_KEYSTORE_PHRASE_ENV,
 };
 use crate::libp2p::{Libp2pConfig, Libp2pService, PeerManager};
 use crate::message_pool::{MessagePool, MpoolConfig, MpoolRpcProvider};
-use crate::networks::{ChainConfig, NetworkChain};
-use crate::rpc::start_rpc;
+use crate::networks::{ChainConfig, ConsensusKind, NetworkChain};
+#[cfg(feature = "rpc")]
+use crate::rpc::{report_consensus_faults, start_rpc};
+#[cfg(feature = "rpc")]
 use crate::rpc_api::data_types::RPCState;
 use crate::shim::address::{CurrentNetwork, Network};
 use crate::shim::clock::ChainEpoch;
@@ -37,19 +44,21 @@ use crate::utils::{
     version::FOREST_VERSION_STRING,
 };
 use anyhow::{bail, Context as _};
-use bundle::load_actor_bundles;
+use bundle::{load_actor_bundle_sources, load_actor_bundles};
+use cid::Cid;
 use dialoguer::console::Term;
 use dialoguer::theme::ColorfulTheme;
 use futures::{select, Future, FutureExt};
+use fvm_ipld_blockstore::Blockstore as _;
 use once_cell::sync::Lazy;
 use raw_sync_2::events::{Event, EventInit as _, EventState};
 use shared_memory::ShmemConf;
 use std::path::Path;
+use std::str::FromStr as _;
 use std::time::Duration;
-use std::{cell::RefCell, cmp, path::PathBuf, sync::Arc};
+use std::{cell::RefCell, path::PathBuf, sync::Arc};
 use tempfile::{Builder, TempPath};
 use tokio::{
-    net::TcpListener,
     signal::{
         ctrl_c,
         unix::{signal, SignalKind},
@@ -109,40 +118,67 @@ fn maybe_increase_fd_limit() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// How the node is shut down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownKind {
+    /// Stop the node.
+    Stop,
+    /// Start the node again, with the same command line, once it is stopped.
+    Restart,
+}
+
 // Start the daemon and abort if we're interrupted by ctrl-c, SIGTERM, or `forest-cli shutdown`.
-pub async fn start_interruptable(opts: CliOpts, config: Config) -> anyhow::Result<()> {
+pub async fn start_interruptable(opts: CliOpts, config: Config) -> anyhow::Result<ShutdownKind> {
     let mut terminate = signal(SignalKind::terminate())?;
     let (shutdown_send, mut shutdown_recv) = mpsc::channel(1);
 
     let result = tokio::select! {
-        ret = start(opts, config, shutdown_send) => ret,
+        ret = start(opts, config, shutdown_send) => ret.map(|()| ShutdownKind::Stop),
         _ = ctrl_c() => {
             info!("Keyboard interrupt.");
-            Ok(())
+            Ok(ShutdownKind::Stop)
         },
         _ = terminate.recv() => {
             info!("Received SIGTERM.");
-            Ok(())
+            Ok(ShutdownKind::Stop)
         },
-        _ = shutdown_recv.recv() => {
-            info!("Client requested a shutdown.");
-            Ok(())
+        kind = shutdown_recv.recv() => {
+            let kind = kind.unwrap_or(ShutdownKind::Stop);
+            match kind {
+                ShutdownKind::Stop => info!("Client requested a shutdown."),
+                ShutdownKind::Restart => info!("Client requested a restart."),
+            }
+            Ok(kind)
         },
     };
     crate::utils::io::terminal_cleanup();
     result
 }
 
-// Garbage collection interval, currently set at 10 hours.
-const GC_INTERVAL: Duration = Duration::from_secs(60 * 60 * 10);
-
 /// Starts daemon process
 pub(super) async fn start(
     opts: CliOpts,
     config: Config,
-    shutdown_send: mpsc::Sender<()>,
+    shutdown_send: mpsc::Sender<ShutdownKind>,
 ) -> anyhow::Result<()> {
-    let chain_config = Arc::new(ChainConfig::from_chain(&config.chain));
+    anyhow::ensure!(
+        config.sync.consensus == ConsensusKind::Ec
+            || matches!(config.chain, NetworkChain::Devnet(_)),
+        "only devnets can be synced with {:?} consensus",
+        config.sync.consensus
+    );
+    anyhow::ensure!(
+        config.sync.upgrades.is_empty() || matches!(config.chain, NetworkChain::Devnet(_)),
+        "only devnets can reschedule their upgrades"
+    );
+    let chain_config = Arc::new(
+        ChainConfig {
+            consensus: config.sync.consensus,
+            ..ChainConfig::from_chain(&config.chain)
+        }
+        .with_upgrades(&config.sync.upgrades)
+        .context("invalid upgrade schedule")?,
+    );
     if chain_config.is_testnet() {
         CurrentNetwork::set_global(Network::Testnet);
     }
@@ -152,6 +188,7 @@ pub(super) async fn start(
         FOREST_VERSION_STRING.as_str()
     );
     maybe_increase_fd_limit()?;
+    crate::utils::outbound::init(config.network.outbound.clone())?;
 
     let start_time = chrono::Utc::now();
     let path: PathBuf = config.client.data_dir.join("libp2p");
@@ -177,14 +214,33 @@ pub(super) async fn start(
     }
 
     let db_root_dir = db_root(&chain_data_path)?;
-    let db_writer = Arc::new(open_db(db_root_dir.clone(), config.db_config().clone())?);
+    let db_backend = Arc::new(open_db(db_root_dir.clone(), config.db_config())?);
+    let quarantine = Arc::new(Quarantine::load(db_backend.clone())?);
+    let db_writer = Arc::new(
+        BlockCache::new(db_backend.clone(), &config.block_cache)
+            .with_quarantine(quarantine.clone()),
+    );
     let db = Arc::new(ManyCar::new(db_writer.clone()));
     let forest_car_db_dir = db_root_dir.join("car_db");
     load_all_forest_cars(&db, &forest_car_db_dir)?;
+    db.read_only_files(config.archival.car_files.iter().cloned())
+        .context("failed to open the archival CAR files")?;
 
     if config.client.load_actors {
         load_actor_bundles(&db, &config.chain).await?;
     }
+    load_actor_bundle_sources(&db, &config.client.actor_bundles).await?;
+    for bundle in config
+        .sync
+        .upgrades
+        .iter()
+        .filter_map(|upgrade| upgrade.bundle)
+    {
+        anyhow::ensure!(
+            db.has(&bundle)?,
+            "actor bundle {bundle} of the upgrade schedule is missing from the database"
+        );
+    }
 
     let mut services = JoinSet::new();
 
@@ -196,9 +252,15 @@ pub(super) async fn start(
         });
     }
 
+    #[cfg(not(feature = "metrics-server"))]
+    if config.client.enable_metrics_endpoint {
+        warn!("Forest was built without the `metrics-server` feature, the metrics endpoint is disabled");
+    }
+
+    #[cfg(feature = "metrics-server")]
     if config.client.enable_metrics_endpoint {
         // Start Prometheus server port
-        let prometheus_listener = TcpListener::bind(config.client.metrics_address)
+        let prometheus_listener = tokio::net::TcpListener::bind(config.client.metrics_address)
             .await
             .context(format!(
                 "could not bind to {}",
@@ -210,19 +272,27 @@ pub(super) async fn start(
         );
         let db_directory = crate::db::db_engine::db_root(&chain_path(&config))?;
         let db = db.writer().clone();
+        let metrics_config = config.metrics.clone();
         services.spawn(async {
-            crate::metrics::init_prometheus(prometheus_listener, db_directory, db)
+            crate::metrics::init_prometheus(prometheus_listener, db_directory, db, metrics_config)
                 .await
                 .context("Failed to initiate prometheus server")
         });
     }
 
+    crate::shim::crypto::verification_pool::init(config.sync.verification_threads)?;
+
     // Read Genesis file
     // * When snapshot command implemented, this genesis does not need to be
     //   initialized
     let genesis_header = read_genesis_header(
         config.client.genesis_file.as_ref(),
         chain_config.genesis_bytes(&db).await?.as_deref(),
+        chain_config
+            .genesis_cid
+            .as_deref()
+            .map(Cid::from_str)
+            .transpose()?,
         &db,
     )
     .await?;
@@ -234,25 +304,49 @@ pub(super) async fn start(
         chain_config.clone(),
         genesis_header.clone(),
     )?);
+    crate::state_migration::progress::init_checkpoints(chain_store.settings().clone())?;
+    services.spawn(Arc::clone(chain_store.fee_history()).backfill_loop(Arc::clone(&db)));
 
-    if !opts.no_gc {
+    let (gc_requests, gc_requests_rx) = flume::bounded(1);
+    {
         let mut db_garbage_collector = {
             let chain_store = chain_store.clone();
-            let depth = cmp::max(
-                chain_config.policy.chain_finality * 2,
+            let depth = config.garbage_collection.depth(
+                chain_config.policy.chain_finality,
                 config.sync.recent_state_roots,
             );
 
             let get_heaviest_tipset = Box::new(move || chain_store.heaviest_tipset());
 
+            // Bundles of upcoming upgrades aren't reachable from the chain yet.
+            let pinned = chain_config
+                .height_infos
+                .iter()
+                .filter_map(|info| info.bundle)
+                .chain(
+                    config
+                        .client
+                        .actor_bundles
+                        .iter()
+                        .map(|source| source.manifest),
+                )
+                .collect::<Vec<_>>();
+
             MarkAndSweep::new(
-                db_writer,
+                db_writer.clone(),
                 get_heaviest_tipset,
                 depth,
                 Duration::from_secs(chain_config.block_delay_secs as u64),
             )
+            .with_pinned(pinned)
+            .with_pinned_fn(crate::state_migration::premigration::pinned_roots)
         };
-        services.spawn(async move { db_garbage_collector.gc_loop(GC_INTERVAL).await });
+        // Runs can still be requested when periodic collection is disabled.
+        let interval = config
+            .garbage_collection
+            .enabled
+            .then(|| config.garbage_collection.interval());
+        services.spawn(async move { db_garbage_collector.gc_loop(interval, gc_requests_rx).await });
     }
 
     let publisher = chain_store.publisher();
@@ -265,6 +359,7 @@ pub(super) async fn start(
     )?;
 
     let state_manager = Arc::new(sm);
+    services.spawn(Arc::clone(&state_manager).expire_actor_subscriptions_loop());
 
     let network_name = get_network_name_from_genesis(&genesis_header, &state_manager)?;
 
@@ -310,36 +405,157 @@ pub(super) async fn start(
     let network_rx = p2p_service.network_receiver();
     let network_send = p2p_service.network_sender();
 
+    if config.archival.fetch_missing {
+        info!("Fetching missing blocks from bitswap peers");
+        db.set_fetcher(Arc::new(BitswapFetcher::new(
+            network_send.clone(),
+            Duration::from_secs(config.archival.fetch_timeout_secs),
+        )));
+    }
+
+    if config.db_health_check.enabled {
+        let health_check = {
+            let chain_store = chain_store.clone();
+            // Cached copies of the blocks would hide the corruption of the stored ones.
+            HealthCheck::new(
+                db_backend,
+                Box::new(move || chain_store.heaviest_tipset()),
+                config.sync.recent_state_roots,
+                config.db_health_check.sample_size,
+                quarantine,
+            )
+        };
+        let health_check = if config.db_health_check.refetch {
+            health_check.with_fetcher(Arc::new(BitswapFetcher::new(
+                network_send.clone(),
+                Duration::from_secs(config.archival.fetch_timeout_secs),
+            )))
+        } else {
+            health_check
+        };
+        let interval = config.db_health_check.interval();
+        services.spawn(Arc::new(health_check).health_check_loop(interval));
+    }
+
+    if !opts.stateless {
+        services.spawn(crate::state_migration::premigration::pre_migration_loop(
+            Arc::clone(&state_manager),
+        ));
+    }
+
     // Initialize mpool
     let provider = MpoolRpcProvider::new(publisher.clone(), Arc::clone(&state_manager));
     let mpool = MessagePool::new(
         provider,
-        network_name.clone(),
+        config.network.gossip_network_name(&network_name).to_owned(),
         network_send.clone(),
         MpoolConfig::load_config(db.writer().as_ref())?,
         state_manager.chain_config().clone(),
         &mut services,
-    )?;
+    )
+    .await?;
 
     let mpool = Arc::new(mpool);
 
     // Initialize ChainMuxer
+    let bad_blocks = Arc::new(BadBlockCache::with_store(chain_store.settings().clone())?);
     let chain_muxer = ChainMuxer::new(
         Arc::clone(&state_manager),
-        peer_manager,
+        peer_manager.clone(),
         mpool.clone(),
         network_send.clone(),
         network_rx,
         Arc::new(Tipset::from(genesis_header)),
         tipset_sink,
         tipset_stream,
+        Arc::clone(&bad_blocks),
         opts.stateless,
     )?;
-    let bad_blocks = chain_muxer.bad_blocks_cloned();
-    let sync_state = chain_muxer.sync_state_cloned();
+    let sync_workers = chain_muxer.sync_workers_cloned();
     services.spawn(async { Err(anyhow::anyhow!("{}", chain_muxer.await)) });
 
+    let healthcheck_config = Arc::new(parking_lot::RwLock::new(config.healthcheck.clone()));
+    if config.healthcheck.enabled {
+        let healthcheck_listener = tokio::net::TcpListener::bind(config.healthcheck.address)
+            .await
+            .context(format!(
+                "could not bind to healthcheck address {}",
+                config.healthcheck.address
+            ))?;
+        info!(
+            "Healthcheck endpoints started at {}",
+            config.healthcheck.address
+        );
+        let forest_state = {
+            let chain_store = chain_store.clone();
+            crate::health::ForestState {
+                config: healthcheck_config.clone(),
+                genesis_timestamp: chain_store.genesis_block_header().timestamp,
+                block_delay_secs: chain_config.block_delay_secs,
+                settings: chain_store.settings().clone(),
+                heaviest_tipset: Box::new(move || chain_store.heaviest_tipset()),
+                sync_workers: sync_workers.clone(),
+                peer_manager,
+            }
+        };
+        services.spawn(async move {
+            crate::health::init_healthcheck_server(forest_state, healthcheck_listener)
+                .await
+                .context("Failed to initiate healthcheck server")
+        });
+    }
+
+    let (config_reload_requests, config_reload_requests_rx) = flume::bounded(1);
+    let fee_config = Arc::new(parking_lot::RwLock::new(config.fee.clone()));
+    let config_reloader = ConfigReloader::new(
+        opts.clone(),
+        network_send.clone(),
+        healthcheck_config,
+        fee_config.clone(),
+    )?;
+    services.spawn(config_reloader.reload_loop(config_reload_requests_rx));
+
+    if let Some(miner) = opts.mine {
+        if !matches!(config.chain, NetworkChain::Devnet(_)) {
+            bail!("Mining is only available on devnets");
+        }
+        if config.sync.consensus != ConsensusKind::Instant {
+            bail!("Mining requires the instant consensus");
+        }
+        FilecoinProposer::new(
+            miner,
+            Arc::clone(&keystore),
+            state_manager.beacon_schedule(),
+            network_send.clone(),
+            config.network.gossip_network_name(&network_name).to_owned(),
+        )
+        .spawn(Arc::clone(&state_manager), mpool.clone(), &mut services)
+        .await?;
+    }
+
+    if opts.report_consensus_faults.is_some()
+        && !(cfg!(feature = "rpc") && config.client.enable_rpc)
+    {
+        bail!("Reporting consensus faults requires the RPC server to be enabled");
+    }
+
+    #[cfg(not(feature = "rpc"))]
+    {
+        if config.client.enable_rpc {
+            warn!("Forest was built without the `rpc` feature, the RPC server is disabled");
+        }
+        // Without the RPC server, nothing requests garbage collections, configuration reloads or
+        // shutdowns.
+        let _ = (
+            &gc_requests,
+            &config_reload_requests,
+            &start_time,
+            &shutdown_send,
+        );
+    }
+
     // Start services
+    #[cfg(feature = "rpc")]
     if config.client.enable_rpc {
         let keystore_rpc = Arc::clone(&keystore);
         let rpc_listen = tokio::net::TcpListener::bind(config.client.rpc_address)
@@ -349,29 +565,38 @@ pub(super) async fn start(
                 config.client.rpc_address
             ))?;
 
-        let rpc_state_manager = Arc::clone(&state_manager);
-        let rpc_chain_store = Arc::clone(&chain_store);
+        let beacon = Arc::new(
+            state_manager
+                .chain_config()
+                .get_beacon_schedule(chain_store.genesis_block_header().timestamp),
+        );
+        let rpc_state = Arc::new(RPCState {
+            state_manager: Arc::clone(&state_manager),
+            keystore: keystore_rpc,
+            mpool,
+            bad_blocks,
+            sync_workers,
+            network_send,
+            gc_requests,
+            config_reload_requests,
+            fee_config,
+            export_dir: config.client.export_dir.clone(),
+            network_name,
+            start_time,
+            beacon,
+            chain_store: Arc::clone(&chain_store),
+            car_files: Arc::clone(&db),
+        });
+
+        if let Some(reporter) = opts.report_consensus_faults {
+            info!("Reporting consensus faults from {reporter}");
+            services.spawn(report_consensus_faults(Arc::clone(&rpc_state), reporter));
+        }
 
         services.spawn(async move {
             info!("JSON-RPC endpoint started at {}", config.client.rpc_address);
-            let beacon = Arc::new(
-                rpc_state_manager
-                    .chain_config()
-                    .get_beacon_schedule(chain_store.genesis_block_header().timestamp),
-            );
             start_rpc(
-                Arc::new(RPCState {
-                    state_manager: Arc::clone(&rpc_state_manager),
-                    keystore: keystore_rpc,
-                    mpool,
-                    bad_blocks,
-                    sync_state,
-                    network_send,
-                    network_name,
-                    start_time,
-                    beacon,
-                    chain_store: rpc_chain_store,
-                }),
+                rpc_state,
                 rpc_listen,
                 FOREST_VERSION_STRING.as_str(),
                 shutdown_send,
@@ -395,8 +620,9 @@ pub(super) async fn start(
 
     // Sets the latest snapshot if needed for downloading later
     let mut config = config;
+    let mut from_provider = false;
     if config.client.snapshot_path.is_none() && !opts.stateless {
-        set_snapshot_path_if_needed(
+        from_provider = set_snapshot_path_if_needed(
             &mut config,
             &chain_config,
             epoch,
@@ -409,10 +635,20 @@ pub(super) async fn start(
     // Import chain if needed
     if !opts.skip_load.unwrap_or_default() {
         if let Some(path) = &config.client.snapshot_path {
+            // Snapshots picked from the providers must have a published checksum.
+            let snapshot_checksum = verify_snapshot(
+                path,
+                &config.client.snapshot_publisher_keys,
+                from_provider,
+                opts.force,
+            )
+            .await?;
             let (car_db_path, ts) = import_chain_as_forest_car(
                 path,
                 &forest_car_db_dir,
                 config.client.consume_snapshot,
+                config.client.import_threads,
+                snapshot_checksum,
             )
             .await?;
             db.read_only_files(std::iter::once(car_db_path.clone()))?;
@@ -459,7 +695,8 @@ pub(super) async fn start(
 }
 
 /// If our current chain is below a supported height, we need a snapshot to bring it up
-/// to a supported height. If we've not been given a snapshot by the user, get one.
+/// to a supported height. If we've not been given a snapshot by the user, get the latest one of
+/// the snapshot providers, and return `true`.
 ///
 /// An [`Err`] should be considered fatal.
 async fn set_snapshot_path_if_needed(
@@ -468,7 +705,7 @@ async fn set_snapshot_path_if_needed(
     epoch: ChainEpoch,
     auto_download_snapshot: bool,
     download_directory: &Path,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<bool> {
     if !download_directory.is_dir() {
         anyhow::bail!(
             "`download_directory` does not exist: {}",
@@ -476,7 +713,6 @@ async fn set_snapshot_path_if_needed(
         );
     }
 
-    let vendor = snapshot::TrustedVendor::default();
     let chain = &config.chain;
 
     // What height is our chain at right now, and what network version does that correspond to?
@@ -488,18 +724,17 @@ async fn set_snapshot_path_if_needed(
     let require_a_snapshot = network_version_is_small;
     let have_a_snapshot = config.client.snapshot_path.is_some();
 
-    match (require_a_snapshot, have_a_snapshot, auto_download_snapshot) {
-        (false, _, _) => {}   // noop - don't need a snapshot
-        (true, true, _) => {} // noop - we need a snapshot, and we have one
-        (true, false, true) => {
-            let url = crate::cli_shared::snapshot::stable_url(vendor, chain)?;
-            config.client.snapshot_path = Some(url.to_string().into());
-            config.client.snapshot = true;
-        }
+    let latest = match (require_a_snapshot, have_a_snapshot, auto_download_snapshot) {
+        (false, _, _) => None,   // noop - don't need a snapshot
+        (true, true, _) => None, // noop - we need a snapshot, and we have one
+        (true, false, true) => Some(
+            snapshot::latest(&config.client.snapshot_providers, chain)
+                .await
+                .context("couldn't find a snapshot to download")?,
+        ),
         (true, false, false) => {
             // we need a snapshot, don't have one, and don't have permission to download one, so ask the user
-            let url = crate::cli_shared::snapshot::stable_url(vendor, chain)?;
-            let (num_bytes, _path) = crate::cli_shared::snapshot::peek(vendor, chain)
+            let latest = snapshot::latest(&config.client.snapshot_providers, chain)
                 .await
                 .context("couldn't get snapshot size")?;
             // dialoguer will double-print long lines, so manually print the first clause ourselves,
@@ -507,7 +742,7 @@ async fn set_snapshot_path_if_needed(
             println!("Forest requires a snapshot to sync with the network, but automatic fetching is disabled.");
             let message = format!(
                 "Fetch a {} snapshot to the current directory? (denying will exit the program). ",
-                indicatif::HumanBytes(num_bytes)
+                indicatif::HumanBytes(latest.size)
             );
             let have_permission = asyncify(|| {
                 dialoguer::Confirm::with_theme(&ColorfulTheme::default())
@@ -521,12 +756,45 @@ async fn set_snapshot_path_if_needed(
             if !have_permission {
                 bail!("Forest requires a snapshot to sync with the network, but automatic fetching is disabled.")
             }
-            config.client.snapshot_path = Some(url.to_string().into());
-            config.client.snapshot = true;
+            Some(latest)
         }
     };
 
-    Ok(())
+    let Some(latest) = latest else {
+        return Ok(false);
+    };
+    info!(
+        "Using snapshot {} at height {}",
+        latest.filename, latest.height
+    );
+    config.client.snapshot_path = Some(latest.url.to_string().into());
+    config.client.snapshot = true;
+    Ok(true)
+}
+
+/// Verifies the snapshot at `path` against its published checksum and signature, and returns the
+/// checksum the imported snapshot has to match. With `force`, the snapshot isn't verified.
+async fn verify_snapshot(
+    path: &Path,
+    publisher_keys: &[String],
+    require_checksum: bool,
+    force: bool,
+) -> anyhow::Result<Option<[u8; 32]>> {
+    if force {
+        warn!("Importing the snapshot without verifying it");
+        return Ok(None);
+    }
+    let publisher_keys = publisher_keys
+        .iter()
+        .map(|key| snapshot::parse_publisher_key(key))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    snapshot::verify(
+        &path.display().to_string(),
+        &publisher_keys,
+        require_checksum,
+    )
+    .await
+    .context("couldn't verify the snapshot, use --force to import it anyway")
 }
 
 /// Generates, prints and optionally writes to a file the administrator JWT
@@ -575,6 +843,7 @@ pub fn get_actual_chain_name(internal_network_name: &str) -> &str {
 /// This may:
 /// - create a [`KeyStore`]
 /// - load a [`KeyStore`]
+/// - read the password from the OS keyring
 /// - ask a user for password input
 async fn load_or_create_keystore(config: &Config) -> anyhow::Result<KeyStore> {
     use std::env::VarError;
@@ -585,7 +854,7 @@ async fn load_or_create_keystore(config: &Config) -> anyhow::Result<KeyStore> {
         .client
         .data_dir
         .join(ENCRYPTED_KEYSTORE_NAME)
-        .is_dir();
+        .is_file();
 
     match (require_encryption, passphrase_from_env) {
         // don't need encryption, we can implicitly create a keystore
@@ -622,18 +891,41 @@ async fn load_or_create_keystore(config: &Config) -> anyhow::Result<KeyStore> {
 
             let data_dir = config.client.data_dir.clone();
 
-            match keystore_already_exists {
+            if config.client.use_os_keyring && keystore_already_exists {
+                match os_keyring::load_passphrase() {
+                    Ok(Some(passphrase)) => {
+                        match KeyStore::new(KeyStoreConfig::Encrypted(data_dir.clone(), passphrase))
+                        {
+                            Ok(keystore) => return Ok(keystore),
+                            Err(e) => warn!("Couldn't unlock keystore with the OS keyring: {e}"),
+                        }
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!("Couldn't read passphrase from the OS keyring: {e}"),
+                }
+            }
+
+            let (keystore, password) = match keystore_already_exists {
                 true => asyncify(move || input_password_to_load_encrypted_keystore(data_dir))
                     .await
-                    .context("Couldn't load keystore"),
+                    .context("Couldn't load keystore")?,
                 false => {
                     let password =
                         asyncify(|| create_password("Create a password for Forest's keystore"))
                             .await?;
-                    KeyStore::new(KeyStoreConfig::Encrypted(data_dir, password))
-                        .context("Couldn't create keystore")
+                    let keystore =
+                        KeyStore::new(KeyStoreConfig::Encrypted(data_dir, password.clone()))
+                            .context("Couldn't create keystore")?;
+                    (keystore, password)
+                }
+            };
+
+            if config.client.use_os_keyring {
+                if let Err(e) = os_keyring::store_passphrase(&password) {
+                    warn!("Couldn't save passphrase to the OS keyring: {e}");
                 }
             }
+            Ok(keystore)
         }
     }
 }
@@ -650,9 +942,12 @@ where
 }
 
 /// Prompts for password, looping until the [`KeyStore`] is successfully loaded.
+/// Returns the keystore along with the accepted password.
 ///
 /// This code makes blocking syscalls.
-fn input_password_to_load_encrypted_keystore(data_dir: PathBuf) -> dialoguer::Result<KeyStore> {
+fn input_password_to_load_encrypted_keystore(
+    data_dir: PathBuf,
+) -> dialoguer::Result<(KeyStore, String)> {
     let keystore = RefCell::new(None);
     let term = Term::stderr();
 
@@ -667,7 +962,7 @@ fn input_password_to_load_encrypted_keystore(data_dir: PathBuf) -> dialoguer::Re
         .into());
     }
 
-    dialoguer::Password::new()
+    let password = dialoguer::Password::new()
         .with_prompt("Enter the password for Forest's keystore")
         .allow_empty_password(true) // let validator do validation
         .validate_with(|input: &String| {
@@ -679,9 +974,12 @@ fn input_password_to_load_encrypted_keystore(data_dir: PathBuf) -> dialoguer::Re
         })
         .interact_on(&term)?;
 
-    Ok(keystore
-        .into_inner()
-        .expect("validation succeeded, so keystore must be emplaced"))
+    Ok((
+        keystore
+            .into_inner()
+            .expect("validation succeeded, so keystore must be emplaced"),
+        password,
+    ))
 }
 
 /// Loops until the user provides two matching passwords.
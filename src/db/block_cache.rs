@@ -0,0 +1,559 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! In-memory cache of the blocks of the database, in two tiers:
+//!
+//! - a small _hot_ tier, evicting the least frequently used blocks, holding the small blocks that
+//!   are read repeatedly, e.g. the nodes of the state-trees.
+//! - a larger _recent_ tier, evicting the least recently used blocks, holding the blocks recently
+//!   read or written.
+//!
+//! Blocks enter the recent tier, and are promoted to the hot tier when they are read again. Both
+//! tiers are bounded by the total size of their blocks, and don't admit blocks too large for them.
+//!
+//! Reads of the blocks quarantined by the [health check](crate::db::HealthCheck) fail, until a
+//! block matching their CID is written.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::db::health::{is_valid_block, Quarantine};
+use crate::db::{
+    truncated_hash, ColumnStatistics, DBStatistics, GarbageCollectable, SettingsStore,
+};
+use crate::libp2p_bitswap::{BitswapStoreRead, BitswapStoreReadWrite};
+use crate::metrics;
+use ahash::{HashMap, HashSet};
+use anyhow::bail;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use lru::LruCache;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+/// Largest block admitted in the hot tier.
+const HOT_MAX_BLOCK_SIZE: usize = 16 << 10;
+/// Largest block admitted in the recent tier.
+const RECENT_MAX_BLOCK_SIZE: usize = 1 << 20;
+/// Number of blocks evicted at once after a sweep, so that readers aren't blocked for long.
+const SWEEP_EVICTION_BATCH_SIZE: usize = 1024;
+
+/// Block cache settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(default)]
+pub struct BlockCacheConfig {
+    /// Capacity of the hot tier, in bytes. 0 disables the tier.
+    pub hot_capacity_bytes: usize,
+    /// Capacity of the recent tier, in bytes. 0 disables the tier.
+    pub recent_capacity_bytes: usize,
+}
+
+impl Default for BlockCacheConfig {
+    fn default() -> Self {
+        Self {
+            // 64 MiB
+            hot_capacity_bytes: 64 << 20,
+            // 256 MiB
+            recent_capacity_bytes: 256 << 20,
+        }
+    }
+}
+
+/// Least frequently used blocks are evicted first, the oldest first among equally used blocks.
+struct HotTier {
+    capacity: usize,
+    size: usize,
+    tick: u64,
+    entries: HashMap<Cid, HotEntry>,
+    /// Keys of the entries, by `(hits, tick)`.
+    eviction_order: BTreeMap<(u64, u64), Cid>,
+}
+
+struct HotEntry {
+    block: Vec<u8>,
+    hits: u64,
+    tick: u64,
+}
+
+impl HotTier {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            size: 0,
+            tick: 0,
+            entries: HashMap::default(),
+            eviction_order: BTreeMap::new(),
+        }
+    }
+
+    fn admits(&self, block: &[u8]) -> bool {
+        block.len() <= HOT_MAX_BLOCK_SIZE.min(self.capacity)
+    }
+
+    fn get(&mut self, k: &Cid) -> Option<Vec<u8>> {
+        let entry = self.entries.get_mut(k)?;
+        self.eviction_order.remove(&(entry.hits, entry.tick));
+        self.tick += 1;
+        entry.hits += 1;
+        entry.tick = self.tick;
+        self.eviction_order.insert((entry.hits, entry.tick), *k);
+        Some(entry.block.clone())
+    }
+
+    fn insert(&mut self, k: Cid, block: Vec<u8>, hits: u64) {
+        if !self.admits(&block) || self.entries.contains_key(&k) {
+            return;
+        }
+        while self.size + block.len() > self.capacity {
+            let Some((_, evicted)) = self.eviction_order.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&evicted) {
+                self.size -= entry.block.len();
+            }
+        }
+        self.tick += 1;
+        self.size += block.len();
+        self.eviction_order.insert((hits, self.tick), k);
+        self.entries.insert(
+            k,
+            HotEntry {
+                block,
+                hits,
+                tick: self.tick,
+            },
+        );
+    }
+
+    /// Replaces the content of the block `k`, if cached.
+    fn replace(&mut self, k: &Cid, block: &[u8]) {
+        if let Some(entry) = self.entries.get_mut(k) {
+            if entry.block != block {
+                self.size = self.size - entry.block.len() + block.len();
+                entry.block = block.to_vec();
+            }
+        }
+    }
+
+    fn remove(&mut self, k: &Cid) -> Option<Vec<u8>> {
+        let entry = self.entries.remove(k)?;
+        self.eviction_order.remove(&(entry.hits, entry.tick));
+        self.size -= entry.block.len();
+        Some(entry.block)
+    }
+
+    fn contains(&self, k: &Cid) -> bool {
+        self.entries.contains_key(k)
+    }
+}
+
+/// Least recently used blocks are evicted first.
+struct RecentTier {
+    capacity: usize,
+    size: usize,
+    entries: LruCache<Cid, Vec<u8>>,
+}
+
+impl RecentTier {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            size: 0,
+            entries: LruCache::unbounded(),
+        }
+    }
+
+    fn admits(&self, block: &[u8]) -> bool {
+        block.len() <= RECENT_MAX_BLOCK_SIZE.min(self.capacity)
+    }
+
+    fn remove(&mut self, k: &Cid) -> Option<Vec<u8>> {
+        let block = self.entries.pop(k)?;
+        self.size -= block.len();
+        Some(block)
+    }
+
+    fn get(&mut self, k: &Cid) -> Option<Vec<u8>> {
+        self.entries.get(k).cloned()
+    }
+
+    fn insert(&mut self, k: Cid, block: Vec<u8>) {
+        if !self.admits(&block) {
+            return;
+        }
+        self.size += block.len();
+        if let Some(replaced) = self.entries.put(k, block) {
+            self.size -= replaced.len();
+        }
+        while self.size > self.capacity {
+            let Some((_, evicted)) = self.entries.pop_lru() else {
+                break;
+            };
+            self.size -= evicted.len();
+        }
+    }
+
+    fn contains(&self, k: &Cid) -> bool {
+        self.entries.contains(k)
+    }
+}
+
+struct Tiers {
+    hot: HotTier,
+    recent: RecentTier,
+}
+
+impl Tiers {
+    fn get(&mut self, k: &Cid) -> Option<Vec<u8>> {
+        if let Some(block) = self.hot.get(k) {
+            record_hit(metrics::values::BLOCK_CACHE_HOT);
+            return Some(block);
+        }
+        record_miss(metrics::values::BLOCK_CACHE_HOT);
+
+        let block = self.recent.get(k);
+        match &block {
+            Some(block) => {
+                record_hit(metrics::values::BLOCK_CACHE_RECENT);
+                // Read at least twice, promote it.
+                if self.hot.admits(block) {
+                    self.recent.remove(k);
+                    self.hot.insert(*k, block.clone(), 2);
+                }
+            }
+            None => record_miss(metrics::values::BLOCK_CACHE_RECENT),
+        }
+        block
+    }
+
+    fn insert(&mut self, k: Cid, block: &[u8]) {
+        if self.hot.contains(&k) {
+            // Blocks are only written again with another content to repair them.
+            self.hot.replace(&k, block);
+        } else if self.recent.admits(block) {
+            self.recent.insert(k, block.to_vec());
+        }
+    }
+
+    fn contains(&self, k: &Cid) -> bool {
+        self.hot.contains(k) || self.recent.contains(k)
+    }
+
+    fn remove(&mut self, k: &Cid) {
+        self.hot.remove(k);
+        self.recent.remove(k);
+    }
+
+    fn keys(&self) -> Vec<Cid> {
+        self.hot
+            .entries
+            .keys()
+            .chain(self.recent.entries.iter().map(|(k, _)| k))
+            .copied()
+            .collect()
+    }
+}
+
+fn record_hit(kind: &str) {
+    metrics::LRU_CACHE_HIT.with_label_values(&[kind]).inc();
+}
+
+fn record_miss(kind: &str) {
+    metrics::LRU_CACHE_MISS.with_label_values(&[kind]).inc();
+}
+
+/// Database with a [tiered cache](self) of its blocks.
+pub struct BlockCache<DB> {
+    db: DB,
+    tiers: Mutex<Tiers>,
+    quarantine: Option<Arc<Quarantine>>,
+}
+
+impl<DB> BlockCache<DB> {
+    pub fn new(db: DB, config: &BlockCacheConfig) -> Self {
+        Self {
+            db,
+            tiers: Mutex::new(Tiers {
+                hot: HotTier::new(config.hot_capacity_bytes),
+                recent: RecentTier::new(config.recent_capacity_bytes),
+            }),
+            quarantine: None,
+        }
+    }
+
+    /// Fails the reads of the blocks in `quarantine`.
+    pub fn with_quarantine(mut self, quarantine: Arc<Quarantine>) -> Self {
+        self.quarantine = Some(quarantine);
+        self
+    }
+
+    fn is_quarantined(&self, k: &Cid) -> bool {
+        self.quarantine
+            .as_ref()
+            .is_some_and(|quarantine| quarantine.contains(k))
+    }
+
+    /// Releases the block `k` from the quarantine if `block` matches its CID.
+    fn release(&self, k: &Cid, block: &[u8]) {
+        if let Some(quarantine) = &self.quarantine {
+            if quarantine.contains(k) && is_valid_block(k, block) {
+                quarantine.release(k);
+            }
+        }
+    }
+}
+
+impl<DB: Blockstore> Blockstore for BlockCache<DB> {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        if self.is_quarantined(k) {
+            bail!("block {k} is quarantined, its content doesn't match its CID");
+        }
+        if let Some(block) = self.tiers.lock().get(k) {
+            return Ok(Some(block));
+        }
+        // Don't hold the lock while reading from the database.
+        let block = self.db.get(k)?;
+        if let Some(block) = &block {
+            self.tiers.lock().insert(*k, block);
+        }
+        Ok(block)
+    }
+
+    fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        Ok(self.tiers.lock().contains(k) || self.db.has(k)?)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.db.put_keyed(k, block)?;
+        self.tiers.lock().insert(*k, block);
+        self.release(k, block);
+        Ok(())
+    }
+
+    fn put_many_keyed<D, I>(&self, blocks: I) -> anyhow::Result<()>
+    where
+        Self: Sized,
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = (Cid, D)>,
+    {
+        let blocks: Vec<_> = blocks.into_iter().collect();
+        self.db
+            .put_many_keyed(blocks.iter().map(|(k, block)| (*k, block)))?;
+        let mut tiers = self.tiers.lock();
+        for (k, block) in &blocks {
+            tiers.insert(*k, block.as_ref());
+        }
+        drop(tiers);
+        for (k, block) in &blocks {
+            self.release(k, block.as_ref());
+        }
+        Ok(())
+    }
+}
+
+impl<DB: Blockstore> BitswapStoreRead for BlockCache<DB> {
+    fn contains(&self, cid: &Cid) -> anyhow::Result<bool> {
+        Blockstore::has(self, cid)
+    }
+
+    fn get(&self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        Blockstore::get(self, cid)
+    }
+}
+
+impl<DB: Blockstore> BitswapStoreReadWrite for BlockCache<DB> {
+    /// `fvm_ipld_encoding::DAG_CBOR(0x71)` is covered by
+    /// [`libipld::DefaultParams`] under feature `dag-cbor`
+    type Params = libipld::DefaultParams;
+
+    fn insert(&self, block: &libipld::Block<Self::Params>) -> anyhow::Result<()> {
+        self.put_keyed(block.cid(), block.data())
+    }
+}
+
+impl<DB: SettingsStore> SettingsStore for BlockCache<DB> {
+    fn read_bin(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        self.db.read_bin(key)
+    }
+
+    fn write_bin(&self, key: &str, value: &[u8]) -> anyhow::Result<()> {
+        self.db.write_bin(key, value)
+    }
+
+    fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        self.db.exists(key)
+    }
+
+    fn setting_keys(&self) -> anyhow::Result<Vec<String>> {
+        self.db.setting_keys()
+    }
+
+    fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.db.delete(key)
+    }
+}
+
+impl<DB: DBStatistics> DBStatistics for BlockCache<DB> {
+    fn get_statistics(&self) -> Option<String> {
+        self.db.get_statistics()
+    }
+
+    fn get_column_statistics(&self) -> Vec<ColumnStatistics> {
+        self.db.get_column_statistics()
+    }
+}
+
+impl<DB: GarbageCollectable> GarbageCollectable for BlockCache<DB> {
+    fn get_keys(&self) -> anyhow::Result<HashSet<u32>> {
+        self.db.get_keys()
+    }
+
+    fn remove_keys(&self, keys: HashSet<u32>) -> anyhow::Result<u64> {
+        let swept = keys.clone();
+        let removed = self.db.remove_keys(keys)?;
+        // The removed blocks must not be served from the cache. The cache is only locked for short
+        // periods, not for the whole sweep.
+        let cached = self.tiers.lock().keys();
+        let evicted: Vec<Cid> = cached
+            .into_iter()
+            .filter(|cid| swept.contains(&truncated_hash(cid.hash())))
+            .collect();
+        for batch in evicted.chunks(SWEEP_EVICTION_BATCH_SIZE) {
+            let mut tiers = self.tiers.lock();
+            for cid in batch {
+                tiers.remove(cid);
+            }
+        }
+        Ok(removed)
+    }
+
+    fn remove_block(&self, cid: &Cid) -> anyhow::Result<()> {
+        self.tiers.lock().remove(cid);
+        self.db.remove_block(cid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+    use cid::multihash::{Code, MultihashDigest as _};
+    use fvm_ipld_encoding::IPLD_RAW;
+
+    fn block(data: &[u8]) -> (Cid, Vec<u8>) {
+        (
+            Cid::new_v1(IPLD_RAW, Code::Blake2b256.digest(data)),
+            data.to_vec(),
+        )
+    }
+
+    #[test]
+    fn blocks_read_again_are_promoted() {
+        let cache = BlockCache::new(MemoryDB::default(), &BlockCacheConfig::default());
+        let (cid, data) = block(b"Cthulhu");
+        cache.db.put_keyed(&cid, &data).unwrap();
+
+        assert_eq!(cache.get(&cid).unwrap(), Some(data.clone()));
+        assert!(cache.tiers.lock().recent.contains(&cid));
+        assert_eq!(cache.get(&cid).unwrap(), Some(data.clone()));
+        let tiers = cache.tiers.lock();
+        assert!(tiers.hot.contains(&cid));
+        assert!(!tiers.recent.contains(&cid));
+    }
+
+    #[test]
+    fn tiers_are_bounded_by_size() {
+        let mut recent = RecentTier::new(10);
+        let (cid_a, a) = block(b"Dagon");
+        let (cid_b, b) = block(b"Hydra");
+        let (cid_c, c) = block(b"Nyarlathotep");
+        recent.insert(cid_a, a);
+        recent.insert(cid_b, b);
+        assert_eq!(recent.size, 10);
+        // Too large to be admitted.
+        recent.insert(cid_c, c.clone());
+        assert!(!recent.contains(&cid_c));
+        // The least recently used block is evicted.
+        recent.get(&cid_a);
+        let (cid_d, d) = block(b"Yig");
+        recent.insert(cid_d, d);
+        assert!(recent.contains(&cid_a) && !recent.contains(&cid_b) && recent.contains(&cid_d));
+        assert_eq!(recent.size, 8);
+
+        let mut hot = HotTier::new(10);
+        hot.insert(cid_a, b"Dagon".to_vec(), 2);
+        hot.insert(cid_b, b"Hydra".to_vec(), 2);
+        // The least frequently used block is evicted.
+        hot.get(&cid_a);
+        hot.insert(cid_d, b"Yig".to_vec(), 2);
+        assert!(hot.contains(&cid_a) && !hot.contains(&cid_b) && hot.contains(&cid_d));
+        assert_eq!(hot.size, 8);
+        hot.insert(cid_c, c, 2);
+        assert!(!hot.contains(&cid_c));
+    }
+
+    #[test]
+    fn disabled_tiers_cache_nothing() {
+        let cache = BlockCache::new(
+            MemoryDB::default(),
+            &BlockCacheConfig {
+                hot_capacity_bytes: 0,
+                recent_capacity_bytes: 0,
+            },
+        );
+        let (cid, data) = block(b"Shoggoth");
+        cache.put_keyed(&cid, &data).unwrap();
+        assert_eq!(cache.get(&cid).unwrap(), Some(data));
+        assert!(!cache.tiers.lock().contains(&cid));
+    }
+
+    #[test]
+    fn repaired_blocks_replace_cached_copies() {
+        let cache = BlockCache::new(MemoryDB::default(), &BlockCacheConfig::default());
+        let (cid, data) = block(b"Ithaqua");
+        cache.put_keyed(&cid, b"Corrupted").unwrap();
+        // Promote the corrupted copy to the hot tier.
+        cache.get(&cid).unwrap();
+        cache.get(&cid).unwrap();
+
+        cache.put_keyed(&cid, &data).unwrap();
+        assert_eq!(cache.get(&cid).unwrap(), Some(data));
+    }
+
+    #[test]
+    fn quarantined_blocks_are_unreadable_until_repaired() {
+        let quarantine = Arc::new(Quarantine::load(Arc::new(MemoryDB::default())).unwrap());
+        let cache = BlockCache::new(MemoryDB::default(), &BlockCacheConfig::default())
+            .with_quarantine(quarantine.clone());
+        let (cid, data) = block(b"Tsathoggua");
+        cache.put_keyed(&cid, b"Corrupted").unwrap();
+        quarantine.insert(cid, 0);
+        assert!(cache.get(&cid).is_err());
+
+        // Writing the corrupted content again doesn't release the block.
+        cache.put_keyed(&cid, b"Corrupted").unwrap();
+        assert!(cache.get(&cid).is_err());
+
+        cache.put_keyed(&cid, &data).unwrap();
+        assert!(!quarantine.contains(&cid));
+        assert_eq!(cache.get(&cid).unwrap(), Some(data));
+    }
+
+    #[test]
+    fn swept_blocks_are_evicted() {
+        let cache = BlockCache::new(MemoryDB::default(), &BlockCacheConfig::default());
+        let (swept, swept_data) = block(b"Yog-Sothoth");
+        let (kept, kept_data) = block(b"Hastur");
+        cache.put_keyed(&swept, &swept_data).unwrap();
+        cache.put_keyed(&kept, &kept_data).unwrap();
+        // Promote one of the blocks to the hot tier.
+        cache.get(&swept).unwrap();
+
+        let keys = HashSet::from_iter([truncated_hash(swept.hash())]);
+        assert_eq!(cache.remove_keys(keys).unwrap(), swept_data.len() as u64);
+        assert!(!cache.tiers.lock().contains(&swept));
+        assert_eq!(cache.get(&swept).unwrap(), None);
+        assert_eq!(cache.get(&kept).unwrap(), Some(kept_data));
+    }
+}
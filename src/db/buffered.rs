@@ -0,0 +1,156 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use ahash::{HashMap, HashMapExt};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use prometheus::{core::Opts, Histogram, HistogramOpts};
+use tracing::warn;
+
+/// Default number of blocks written at once by [`BufferedBlockstore`].
+const DEFAULT_WRITE_BATCH_SIZE: usize = 100_000;
+
+static WRITE_BATCH_TIME: Lazy<Box<Histogram>> = Lazy::new(|| {
+    let write_batch_time = Box::new(
+        Histogram::with_opts(HistogramOpts {
+            common_opts: Opts::new(
+                "write_batch_time",
+                "Duration of the writes of batches of blocks to the database",
+            ),
+            buckets: vec![],
+        })
+        .expect("Defining the write_batch_time metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(write_batch_time.clone())
+        .expect("Registering the write_batch_time metric with the metrics registry must succeed");
+    write_batch_time
+});
+
+/// Number of blocks written at once by the state computation, set with the
+/// `FOREST_STATE_WRITE_BATCH_SIZE` environment variable.
+pub fn state_write_batch_size() -> usize {
+    static BATCH_SIZE: Lazy<usize> =
+        Lazy::new(|| match std::env::var("FOREST_STATE_WRITE_BATCH_SIZE") {
+            Ok(var) => var.parse().unwrap_or_else(|_| {
+                warn!(
+                    "Invalid FOREST_STATE_WRITE_BATCH_SIZE {var}, using {DEFAULT_WRITE_BATCH_SIZE}"
+                );
+                DEFAULT_WRITE_BATCH_SIZE
+            }),
+            _ => DEFAULT_WRITE_BATCH_SIZE,
+        });
+    *BATCH_SIZE
+}
+
+/// Blockstore keeping the written blocks in memory, and writing them to the underlying store in
+/// batches of `batch_size` blocks, rather than one at a time. The buffered blocks must be written
+/// with [`BufferedBlockstore::flush`] before the store is dropped.
+pub struct BufferedBlockstore<BS> {
+    inner: BS,
+    batch_size: usize,
+    buffer: RwLock<HashMap<Cid, Vec<u8>>>,
+}
+
+impl<BS: Blockstore> BufferedBlockstore<BS> {
+    pub fn new(inner: BS, batch_size: usize) -> Self {
+        Self {
+            inner,
+            batch_size,
+            buffer: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Writes the buffered blocks to the underlying store, in a single batch.
+    #[tracing::instrument(skip_all)]
+    pub fn flush(&self) -> anyhow::Result<()> {
+        // Keep the buffer locked while writing so that no reader misses the blocks in flight.
+        let mut buffer = self.buffer.write();
+        self.write_batch(&mut buffer)
+    }
+
+    fn write_batch(&self, buffer: &mut HashMap<Cid, Vec<u8>>) -> anyhow::Result<()> {
+        if buffer.is_empty() {
+            return Ok(());
+        }
+        let _timer = WRITE_BATCH_TIME.start_timer();
+        self.inner.put_many_keyed(buffer.drain())
+    }
+}
+
+impl<BS: Blockstore> Blockstore for BufferedBlockstore<BS> {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        if let Some(block) = self.buffer.read().get(k) {
+            return Ok(Some(block.clone()));
+        }
+        self.inner.get(k)
+    }
+
+    fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        Ok(self.buffer.read().contains_key(k) || self.inner.has(k)?)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.put_many_keyed([(*k, block)])
+    }
+
+    fn put_many_keyed<D, I>(&self, blocks: I) -> anyhow::Result<()>
+    where
+        Self: Sized,
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = (Cid, D)>,
+    {
+        let mut buffer = self.buffer.write();
+        buffer.extend(
+            blocks
+                .into_iter()
+                .map(|(k, block)| (k, block.as_ref().to_vec())),
+        );
+        if buffer.len() >= self.batch_size {
+            self.write_batch(&mut buffer)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+    use cid::multihash::{Code, MultihashDigest as _};
+    use fvm_ipld_encoding::IPLD_RAW;
+
+    fn block(data: &[u8]) -> (Cid, Vec<u8>) {
+        (
+            Cid::new_v1(IPLD_RAW, Code::Blake2b256.digest(data)),
+            data.to_vec(),
+        )
+    }
+
+    #[test]
+    fn writes_in_batches() {
+        let store = BufferedBlockstore::new(MemoryDB::default(), 2);
+        let (cid_a, a) = block(b"Cthulhu");
+        let (cid_b, b) = block(b"Dagon");
+        let (cid_c, c) = block(b"Hydra");
+
+        store.put_keyed(&cid_a, &a).unwrap();
+        // Buffered blocks are readable before being written.
+        assert_eq!(store.get(&cid_a).unwrap(), Some(a.clone()));
+        assert!(store.has(&cid_a).unwrap());
+        assert!(!store.inner.has(&cid_a).unwrap());
+
+        // Reaching the batch size writes the buffered blocks.
+        store.put_keyed(&cid_b, &b).unwrap();
+        assert_eq!(store.inner.get(&cid_a).unwrap(), Some(a));
+        assert_eq!(store.inner.get(&cid_b).unwrap(), Some(b));
+
+        store.put_keyed(&cid_c, &c).unwrap();
+        assert!(!store.inner.has(&cid_c).unwrap());
+        store.flush().unwrap();
+        assert_eq!(store.inner.get(&cid_c).unwrap(), Some(c));
+        assert!(store.buffer.read().is_empty());
+    }
+}
@@ -361,6 +361,57 @@ impl Encoder {
             }
         })
     }
+
+    /// Like [`Encoder::compress_stream`], but compresses frames on up to
+    /// `num_threads` blocking threads, verifying that each block matches its
+    /// CID along the way. As frames are compressed independently, they are cut
+    /// once `zstd_frame_size_tripwire` bytes of _uncompressed_ blocks were
+    /// collected. Frames are emitted in the order of the blocks.
+    pub fn compress_stream_parallel(
+        zstd_frame_size_tripwire: usize,
+        zstd_compression_level: u16,
+        num_threads: usize,
+        stream: impl TryStream<Ok = CarBlock, Error = anyhow::Error>,
+    ) -> impl TryStream<Ok = (Vec<Cid>, Bytes), Error = anyhow::Error> {
+        let frames = futures::stream::try_unfold(
+            Box::pin(stream.into_stream()),
+            move |mut stream| async move {
+                let mut batch = vec![];
+                let mut batch_len = 0;
+                while batch_len <= zstd_frame_size_tripwire {
+                    match stream.try_next().await? {
+                        Some(block) => {
+                            batch_len += block.data.len();
+                            batch.push(block);
+                        }
+                        None => break,
+                    }
+                }
+                anyhow::Ok((!batch.is_empty()).then_some((batch, stream)))
+            },
+        )
+        .map_ok(move |batch| async move {
+            tokio::task::spawn_blocking(move || compress_frame(zstd_compression_level, batch))
+                .await?
+        })
+        .try_buffered(num_threads.max(1));
+        Box::pin(frames)
+    }
+}
+
+/// Compresses `blocks` into a single zstd frame.
+fn compress_frame(
+    zstd_compression_level: u16,
+    blocks: Vec<CarBlock>,
+) -> anyhow::Result<(Vec<Cid>, Bytes)> {
+    let mut encoder = new_encoder(zstd_compression_level)?;
+    let mut cids = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        anyhow::ensure!(block.valid(), "Block {} doesn't match its hash", block.cid);
+        block.write(&mut encoder)?;
+        cids.push(block.cid);
+    }
+    Ok((cids, encoder.finish()?.into_inner().freeze()))
 }
 
 fn invalid_data(inner: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
@@ -479,6 +530,52 @@ mod tests {
         }
     }
 
+    #[quickcheck]
+    fn forest_car_create_parallel(
+        head: CarBlock,
+        mut tail: Vec<CarBlock>,
+        roots: Vec<Cid>,
+        frame_size: u16,
+        num_threads: u8,
+    ) {
+        tail.push(head);
+        let encoded = tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let frame_stream = Encoder::compress_stream_parallel(
+                frame_size.into(),
+                3,
+                num_threads.into(),
+                futures::stream::iter(tail.clone().into_iter().map(Ok)),
+            );
+            let mut encoded = vec![];
+            Encoder::write(&mut encoded, roots.clone(), frame_stream)
+                .await
+                .unwrap();
+            encoded
+        });
+        let forest_car = ForestCar::new(encoded).unwrap();
+        assert_eq!(forest_car.roots(), roots);
+        for block in tail {
+            assert_eq!(forest_car.get(&block.cid).unwrap(), Some(block.data));
+        }
+    }
+
+    #[tokio::test]
+    async fn compress_stream_parallel_rejects_invalid_blocks() {
+        use cid::multihash::{Code::Blake2b256, MultihashDigest};
+
+        let mut block = CarBlock {
+            cid: Cid::new_v1(fvm_ipld_encoding::DAG_CBOR, Blake2b256.digest(b"data")),
+            data: b"data".to_vec(),
+        };
+        assert!(block.valid());
+        block.data = b"corrupted".to_vec();
+        let frames: anyhow::Result<Vec<_>> =
+            Encoder::compress_stream_parallel(1024, 3, 2, futures::stream::iter([Ok(block)]))
+                .try_collect()
+                .await;
+        assert!(frames.is_err());
+    }
+
     #[quickcheck]
     fn forest_car_open_invalid(junk: Vec<u8>) {
         // The chance of thinking random data is a valid ForestCar should be practically zero.
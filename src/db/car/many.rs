@@ -7,6 +7,13 @@
 //! requests are only forwarded to the writable store.
 //!
 //! A single z-frame cache is shared between all read-only stores.
+//!
+//! Read-only stores backed by CAR files can be added and removed while the
+//! store is in use, see [`ReadOnlyCarFiles`].
+//!
+//! Optionally, blocks that are in none of the stores are fetched into the
+//! writable store by a [`BlockFetcher`], e.g. from the bitswap peers of the
+//! node in archival mode. Only get requests are forwarded to the fetcher.
 
 use super::{AnyCar, ZstdFrameCache};
 use crate::db::{MemoryDB, SettingsStore};
@@ -17,20 +24,54 @@ use anyhow::Context as _;
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
 use parking_lot::{Mutex, RwLock};
-use std::{io, path::PathBuf, sync::Arc};
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
 
 pub struct ManyCar<WriterT = MemoryDB> {
     shared_cache: Arc<Mutex<ZstdFrameCache>>,
-    read_only: RwLock<Vec<AnyCar<Box<dyn super::RandomAccessFileReader>>>>,
+    /// Keys of the read-only stores in the shared cache. Keys aren't reused, so
+    /// that stores never hit the cached frames of removed ones.
+    next_cache_key: AtomicU64,
+    read_only: RwLock<Vec<ReadOnlyCar>>,
     writer: WriterT,
+    fetcher: RwLock<Option<Arc<dyn BlockFetcher>>>,
+}
+
+/// Source of the blocks that are in none of the stores of a [`ManyCar`].
+pub trait BlockFetcher: Send + Sync {
+    /// Fetches the block `cid` into the writable store, and returns `false` if
+    /// it couldn't be fetched.
+    fn fetch(&self, cid: &Cid) -> bool;
+
+    /// Fetches the block `cid` again, even if the writable store has it, and
+    /// returns `false` if it couldn't be fetched. The copy of the writable
+    /// store is only replaced by a fetched block matching `cid`. Fetchers that
+    /// can't replace blocks always return `false`.
+    fn refetch(&self, _cid: &Cid) -> bool {
+        false
+    }
+}
+
+struct ReadOnlyCar {
+    /// Path of the CAR file, if the store was opened from one.
+    path: Option<PathBuf>,
+    car: AnyCar<Box<dyn super::RandomAccessFileReader>>,
 }
 
 impl<WriterT> ManyCar<WriterT> {
     pub fn new(writer: WriterT) -> Self {
         ManyCar {
             shared_cache: Arc::new(Mutex::new(ZstdFrameCache::default())),
+            next_cache_key: AtomicU64::new(0),
             read_only: RwLock::new(Vec::new()),
             writer,
+            fetcher: RwLock::new(None),
         }
     }
 
@@ -55,13 +96,29 @@ impl<WriterT> ManyCar<WriterT> {
     }
 
     pub fn read_only<ReaderT: super::RandomAccessFileReader>(&self, any_car: AnyCar<ReaderT>) {
-        let mut read_only = self.read_only.write();
-        let key = read_only.len() as u64;
-        read_only.push(
-            any_car
-                .with_cache(self.shared_cache.clone(), key)
-                .into_dyn(),
-        );
+        self.push_read_only(None, any_car)
+    }
+
+    /// Adds `any_car`, opened from the CAR file at `path`, so that it can be
+    /// removed later on with [`ReadOnlyCarFiles::remove_read_only_file`].
+    pub fn read_only_file<ReaderT: super::RandomAccessFileReader>(
+        &self,
+        path: PathBuf,
+        any_car: AnyCar<ReaderT>,
+    ) {
+        self.push_read_only(Some(canonical_path(path)), any_car)
+    }
+
+    fn push_read_only<ReaderT: super::RandomAccessFileReader>(
+        &self,
+        path: Option<PathBuf>,
+        any_car: AnyCar<ReaderT>,
+    ) {
+        let key = self.next_cache_key.fetch_add(1, Ordering::Relaxed);
+        let car = any_car
+            .with_cache(self.shared_cache.clone(), key)
+            .into_dyn();
+        self.read_only.write().push(ReadOnlyCar { path, car });
     }
 
     pub fn with_read_only_files(self, files: impl Iterator<Item = PathBuf>) -> io::Result<Self> {
@@ -71,18 +128,24 @@ impl<WriterT> ManyCar<WriterT> {
 
     pub fn read_only_files(&self, files: impl Iterator<Item = PathBuf>) -> io::Result<()> {
         for file in files {
-            self.read_only(AnyCar::new(EitherMmapOrRandomAccessFile::open(file)?)?);
+            let car = AnyCar::new(EitherMmapOrRandomAccessFile::open(&file)?)?;
+            self.read_only_file(file, car);
         }
 
         Ok(())
     }
 
+    /// Fetches the blocks that are in none of the stores with `fetcher`.
+    pub fn set_fetcher(&self, fetcher: Arc<dyn BlockFetcher>) {
+        *self.fetcher.write() = Some(fetcher);
+    }
+
     pub fn heaviest_tipset(&self) -> anyhow::Result<Tipset> {
         let tipsets = self
             .read_only
             .read()
             .iter()
-            .map(AnyCar::heaviest_tipset)
+            .map(|read_only| read_only.car.heaviest_tipset())
             .collect::<anyhow::Result<Vec<_>>>()?;
         tipsets
             .into_iter()
@@ -91,6 +154,61 @@ impl<WriterT> ManyCar<WriterT> {
     }
 }
 
+/// Paths are compared in their canonical form, when the file still exists.
+fn canonical_path(path: PathBuf) -> PathBuf {
+    std::fs::canonicalize(&path).unwrap_or(path)
+}
+
+/// Management of the read-only CAR files of a store while it is in use, e.g.
+/// to add archival snapshots for deep-history queries without a restart.
+pub trait ReadOnlyCarFiles: Send + Sync {
+    /// Opens the CAR file at `path` and adds it to the read-only stores.
+    /// Returns the heaviest tipset of the file.
+    fn add_read_only_file(&self, path: &Path) -> anyhow::Result<Tipset>;
+
+    /// Removes the read-only store of the CAR file at `path`. Returns `false`
+    /// if there is none.
+    fn remove_read_only_file(&self, path: &Path) -> bool;
+
+    /// Paths of the CAR files of the read-only stores, in lookup order.
+    fn read_only_file_paths(&self) -> Vec<PathBuf>;
+}
+
+impl<WriterT: Send + Sync> ReadOnlyCarFiles for ManyCar<WriterT> {
+    fn add_read_only_file(&self, path: &Path) -> anyhow::Result<Tipset> {
+        let path = canonical_path(path.to_path_buf());
+        anyhow::ensure!(
+            !self.read_only_file_paths().contains(&path),
+            "{} is already loaded",
+            path.display()
+        );
+        let car = AnyCar::new(
+            EitherMmapOrRandomAccessFile::open(&path)
+                .with_context(|| format!("failed to open {}", path.display()))?,
+        )
+        .with_context(|| format!("{} is not a valid CAR file", path.display()))?;
+        let heaviest_tipset = car.heaviest_tipset()?;
+        self.push_read_only(Some(path), car);
+        Ok(heaviest_tipset)
+    }
+
+    fn remove_read_only_file(&self, path: &Path) -> bool {
+        let path = canonical_path(path.to_path_buf());
+        let mut read_only = self.read_only.write();
+        let len = read_only.len();
+        read_only.retain(|read_only| read_only.path.as_ref() != Some(&path));
+        read_only.len() != len
+    }
+
+    fn read_only_file_paths(&self) -> Vec<PathBuf> {
+        self.read_only
+            .read()
+            .iter()
+            .filter_map(|read_only| read_only.path.clone())
+            .collect()
+    }
+}
+
 impl<ReaderT: super::RandomAccessFileReader> From<AnyCar<ReaderT>> for ManyCar<MemoryDB> {
     fn from(any_car: AnyCar<ReaderT>) -> Self {
         ManyCar::default().with_read_only(any_car)
@@ -104,18 +222,36 @@ impl TryFrom<Vec<PathBuf>> for ManyCar<MemoryDB> {
     }
 }
 
-impl<WriterT: Blockstore> Blockstore for ManyCar<WriterT> {
-    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+impl<WriterT: Blockstore> ManyCar<WriterT> {
+    /// Looks `k` up in the stores, without fetching it.
+    fn get_local(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
         // Theoretically it should be easily parallelizable with `rayon`.
         // In practice, there is a massive performance loss when providing
         // more than a single reader.
         for reader in self.read_only.read().iter() {
-            if let Some(val) = reader.get(k)? {
+            if let Some(val) = reader.car.get(k)? {
                 return Ok(Some(val));
             }
         }
         self.writer.get(k)
     }
+}
+
+impl<WriterT: Blockstore> Blockstore for ManyCar<WriterT> {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        if let Some(val) = self.get_local(k)? {
+            return Ok(Some(val));
+        }
+        let fetcher = self.fetcher.read().clone();
+        match fetcher {
+            Some(fetcher) if fetcher.fetch(k) => self.writer.get(k),
+            _ => Ok(None),
+        }
+    }
+
+    fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        Ok(self.get_local(k)?.is_some())
+    }
 
     fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
         self.writer.put_keyed(k, block)
@@ -128,7 +264,8 @@ impl<WriterT: BitswapStoreRead + Blockstore> BitswapStoreRead for ManyCar<Writer
     }
 
     fn get(&self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
-        Blockstore::get(self, cid)
+        // Blocks requested by peers aren't fetched from other peers.
+        self.get_local(cid)
     }
 }
 
@@ -156,6 +293,10 @@ impl<WriterT: SettingsStore> SettingsStore for ManyCar<WriterT> {
     fn setting_keys(&self) -> anyhow::Result<Vec<String>> {
         SettingsStore::setting_keys(self.writer())
     }
+
+    fn delete(&self, key: &str) -> anyhow::Result<()> {
+        SettingsStore::delete(self.writer(), key)
+    }
 }
 
 #[cfg(test)]
@@ -163,6 +304,7 @@ mod tests {
     use super::super::AnyCar;
     use super::*;
     use crate::networks::{calibnet, mainnet};
+    use crate::utils::db::CborStoreExt as _;
 
     #[test]
     fn many_car_empty() {
@@ -184,6 +326,55 @@ mod tests {
         );
     }
 
+    #[test]
+    fn many_car_add_and_remove_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("genesis.car");
+        std::fs::write(&path, calibnet::DEFAULT_GENESIS).unwrap();
+
+        let many = ManyCar::new(MemoryDB::default());
+        let heaviest = many.add_read_only_file(&path).unwrap();
+        assert_eq!(many.heaviest_tipset().unwrap(), heaviest);
+        assert!(many.has(heaviest.min_ticket_block().cid()).unwrap());
+        assert!(many.add_read_only_file(&path).is_err());
+        assert_eq!(many.read_only_file_paths(), [path.canonicalize().unwrap()]);
+
+        assert!(many.remove_read_only_file(&path));
+        assert!(!many.remove_read_only_file(&path));
+        assert!(!many.has(heaviest.min_ticket_block().cid()).unwrap());
+        assert!(many.heaviest_tipset().is_err());
+    }
+
+    #[test]
+    fn many_car_fetches_missing_blocks() {
+        struct Fetcher(MemoryDB, Arc<MemoryDB>);
+        impl BlockFetcher for Fetcher {
+            fn fetch(&self, cid: &Cid) -> bool {
+                match Blockstore::get(&self.0, cid).unwrap() {
+                    Some(block) => {
+                        self.1.put_keyed(cid, &block).unwrap();
+                        true
+                    }
+                    None => false,
+                }
+            }
+        }
+
+        let remote = MemoryDB::default();
+        let cid = remote.put_cbor_default(&"remote").unwrap();
+        let missing = MemoryDB::default().put_cbor_default(&"missing").unwrap();
+        let writer = Arc::new(MemoryDB::default());
+        let many = ManyCar::new(writer.clone());
+        many.set_fetcher(Arc::new(Fetcher(remote, writer.clone())));
+
+        assert!(!many.has(&cid).unwrap());
+        assert!(BitswapStoreRead::get(&many, &cid).unwrap().is_none());
+        assert!(Blockstore::get(&many, &cid).unwrap().is_some());
+        // Fetched blocks are kept in the writable store.
+        assert!(writer.has(&cid).unwrap());
+        assert!(Blockstore::get(&many, &missing).unwrap().is_none());
+    }
+
     #[test]
     fn many_car_calibnet_heaviest() {
         let many = ManyCar::from(AnyCar::try_from(calibnet::DEFAULT_GENESIS).unwrap());
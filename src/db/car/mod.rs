@@ -7,7 +7,7 @@ pub mod plain;
 
 pub use any::AnyCar;
 pub use forest::ForestCar;
-pub use many::ManyCar;
+pub use many::{BlockFetcher, ManyCar, ReadOnlyCarFiles};
 pub use plain::PlainCar;
 
 use ahash::HashMap;
@@ -0,0 +1,323 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Persistent storage of the node. The backend is selected with the `backend` setting of the
+//! `[db]` section of the configuration:
+//!
+//! - `paritydb` (default): stored in the database directory.
+//! - `rocksdb`: stored in the `rocksdb` sub-directory of the database directory. Requires the
+//!   `rocksdb` feature.
+//!
+//! Databases can be converted from a backend to another with `forest-tool db migrate-backend`.
+
+use std::path::{Path, PathBuf};
+
+use super::db_mode::choose_db;
+use super::parity_db::ParityDb;
+use super::parity_db_config::ParityDbConfig;
+#[cfg(feature = "rocksdb")]
+use super::rocks_db::RocksDb;
+use super::rocks_db_config::RocksDbConfig;
+use super::{ColumnStatistics, DBStatistics, GarbageCollectable, SettingsStore};
+use crate::libp2p_bitswap::{BitswapStoreRead, BitswapStoreReadWrite};
+use ahash::HashSet;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumIter, IntoEnumIterator};
+
+/// Sub-directory of the database directory holding a `RocksDb` database.
+const ROCKS_DB_DIR: &str = "rocksdb";
+/// File created by `ParityDb` in its directory.
+const PARITY_DB_METADATA_FILE: &str = "metadata";
+/// Number of blocks written at once when copying a database.
+const COPY_BATCH_SIZE: usize = 10_000;
+
+/// Interface of the persistent storage backends.
+pub trait DbBackend:
+    Blockstore
+    + SettingsStore
+    + BitswapStoreReadWrite<Params = libipld::DefaultParams>
+    + DBStatistics
+    + GarbageCollectable
+{
+    /// Calls `f` with every IPLD block of the database, and its CID.
+    fn for_each_block(
+        &self,
+        f: &mut dyn FnMut(Cid, Vec<u8>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()>;
+}
+
+/// Available storage backends.
+#[derive(
+    Copy,
+    Clone,
+    Debug,
+    Default,
+    Display,
+    EnumIter,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    clap::ValueEnum,
+)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+#[value(rename_all = "lowercase")]
+pub enum BackendKind {
+    #[default]
+    ParityDb,
+    RocksDb,
+}
+
+impl BackendKind {
+    /// Returns `true` if `db_root` holds a database of this kind.
+    pub fn exists_in(&self, db_root: &Path) -> bool {
+        match self {
+            Self::ParityDb => db_root.join(PARITY_DB_METADATA_FILE).is_file(),
+            Self::RocksDb => db_root.join(ROCKS_DB_DIR).is_dir(),
+        }
+    }
+}
+
+/// `[db]` section of the configuration.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(default)]
+pub struct BackendConfig {
+    pub backend: BackendKind,
+}
+
+/// Configuration of the selected backend.
+#[derive(Clone, Debug)]
+pub enum DbConfig {
+    ParityDb(ParityDbConfig),
+    RocksDb(RocksDbConfig),
+}
+
+impl DbConfig {
+    pub fn kind(&self) -> BackendKind {
+        match self {
+            Self::ParityDb(_) => BackendKind::ParityDb,
+            Self::RocksDb(_) => BackendKind::RocksDb,
+        }
+    }
+}
+
+/// The database of the node, in one of the available backends.
+pub enum Db {
+    ParityDb(ParityDb),
+    #[cfg(feature = "rocksdb")]
+    RocksDb(RocksDb),
+}
+
+impl Db {
+    fn backend(&self) -> &dyn DbBackend {
+        match self {
+            Self::ParityDb(db) => db,
+            #[cfg(feature = "rocksdb")]
+            Self::RocksDb(db) => db,
+        }
+    }
+}
+
+/// Returns the path to the database directory to be used by the daemon.
+pub fn db_root(chain_data_root: &Path) -> anyhow::Result<PathBuf> {
+    choose_db(chain_data_root)
+}
+
+/// Opens the database in `db_root` with the configured backend, or creates it.
+pub fn open_db(db_root: PathBuf, config: DbConfig) -> anyhow::Result<Db> {
+    let kind = config.kind();
+    // Don't silently start from an empty database after the backend is changed.
+    if !kind.exists_in(&db_root) {
+        if let Some(other) = BackendKind::iter().find(|other| other.exists_in(&db_root)) {
+            anyhow::bail!(
+                "{} holds a {other} database. Set `backend = \"{other}\"` in the `[db]` section of \
+                 the configuration, or convert it with `forest-tool db migrate-backend --to {kind}`",
+                db_root.display()
+            );
+        }
+    }
+
+    match config {
+        DbConfig::ParityDb(config) => Ok(Db::ParityDb(ParityDb::open(db_root, &config)?)),
+        #[cfg(feature = "rocksdb")]
+        DbConfig::RocksDb(config) => Ok(Db::RocksDb(RocksDb::open(
+            db_root.join(ROCKS_DB_DIR),
+            &config,
+        )?)),
+        #[cfg(not(feature = "rocksdb"))]
+        DbConfig::RocksDb(_) => {
+            anyhow::bail!("Forest was built without the `rocksdb` feature")
+        }
+    }
+}
+
+/// Copies all the blocks and settings of `from` into `to`. Returns the number of copied blocks.
+pub fn copy_db(
+    from: &(impl DbBackend + ?Sized),
+    to: &(impl Blockstore + SettingsStore),
+) -> anyhow::Result<u64> {
+    let mut copied = 0;
+    let mut batch = Vec::with_capacity(COPY_BATCH_SIZE);
+    from.for_each_block(&mut |cid, block| {
+        batch.push((cid, block));
+        if batch.len() == COPY_BATCH_SIZE {
+            copied += batch.len() as u64;
+            to.put_many_keyed(batch.drain(..))?;
+        }
+        Ok(())
+    })?;
+    copied += batch.len() as u64;
+    to.put_many_keyed(batch)?;
+
+    for key in from.setting_keys()? {
+        if let Some(value) = from.read_bin(&key)? {
+            to.write_bin(&key, &value)?;
+        }
+    }
+    Ok(copied)
+}
+
+impl SettingsStore for Db {
+    fn read_bin(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        self.backend().read_bin(key)
+    }
+
+    fn write_bin(&self, key: &str, value: &[u8]) -> anyhow::Result<()> {
+        self.backend().write_bin(key, value)
+    }
+
+    fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        self.backend().exists(key)
+    }
+
+    fn setting_keys(&self) -> anyhow::Result<Vec<String>> {
+        self.backend().setting_keys()
+    }
+
+    fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.backend().delete(key)
+    }
+}
+
+impl Blockstore for Db {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        Blockstore::get(self.backend(), k)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.backend().put_keyed(k, block)
+    }
+
+    fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        self.backend().has(k)
+    }
+
+    fn put_many_keyed<D, I>(&self, blocks: I) -> anyhow::Result<()>
+    where
+        Self: Sized,
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = (Cid, D)>,
+    {
+        match self {
+            Self::ParityDb(db) => db.put_many_keyed(blocks),
+            #[cfg(feature = "rocksdb")]
+            Self::RocksDb(db) => db.put_many_keyed(blocks),
+        }
+    }
+}
+
+impl BitswapStoreRead for Db {
+    fn contains(&self, cid: &Cid) -> anyhow::Result<bool> {
+        self.backend().contains(cid)
+    }
+
+    fn get(&self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        BitswapStoreRead::get(self.backend(), cid)
+    }
+}
+
+impl BitswapStoreReadWrite for Db {
+    /// `fvm_ipld_encoding::DAG_CBOR(0x71)` is covered by
+    /// [`libipld::DefaultParams`] under feature `dag-cbor`
+    type Params = libipld::DefaultParams;
+
+    fn insert(&self, block: &libipld::Block<Self::Params>) -> anyhow::Result<()> {
+        self.backend().insert(block)
+    }
+}
+
+impl DBStatistics for Db {
+    fn get_statistics(&self) -> Option<String> {
+        self.backend().get_statistics()
+    }
+
+    fn get_column_statistics(&self) -> Vec<ColumnStatistics> {
+        self.backend().get_column_statistics()
+    }
+}
+
+impl GarbageCollectable for Db {
+    fn get_keys(&self) -> anyhow::Result<HashSet<u32>> {
+        self.backend().get_keys()
+    }
+
+    fn remove_keys(&self, keys: HashSet<u32>) -> anyhow::Result<u64> {
+        self.backend().remove_keys(keys)
+    }
+
+    fn remove_block(&self, cid: &Cid) -> anyhow::Result<()> {
+        self.backend().remove_block(cid)
+    }
+}
+
+impl DbBackend for Db {
+    fn for_each_block(
+        &self,
+        f: &mut dyn FnMut(Cid, Vec<u8>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        self.backend().for_each_block(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::db_utils::parity::TempParityDB;
+    use cid::multihash::{Code, MultihashDigest as _};
+    use fvm_ipld_encoding::{DAG_CBOR, IPLD_RAW};
+
+    #[test]
+    fn copy_db_copies_blocks_and_settings() {
+        let from = TempParityDB::new();
+        let to = TempParityDB::new();
+        let blocks = [
+            (DAG_CBOR, Code::Blake2b256, b"Cthulhu".to_vec()),
+            (DAG_CBOR, Code::Sha2_256, b"Dagon".to_vec()),
+            (IPLD_RAW, Code::Blake2b256, b"Hydra".to_vec()),
+        ]
+        .map(|(codec, code, data)| (Cid::new_v1(codec, code.digest(&data)), data));
+        for (cid, data) in &blocks {
+            from.put_keyed(cid, data).unwrap();
+        }
+        from.write_bin("head", b"R'lyeh").unwrap();
+
+        assert_eq!(copy_db(&*from, &*to).unwrap(), blocks.len() as u64);
+        for (cid, data) in &blocks {
+            assert_eq!(Blockstore::get(&*to, cid).unwrap().as_ref(), Some(data));
+        }
+        assert_eq!(to.read_bin("head").unwrap().unwrap(), b"R'lyeh");
+    }
+
+    #[test]
+    fn open_db_refuses_other_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        drop(open_db(dir.path().into(), DbConfig::ParityDb(Default::default())).unwrap());
+        assert!(BackendKind::ParityDb.exists_in(dir.path()));
+        assert!(open_db(dir.path().into(), DbConfig::RocksDb(Default::default())).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use once_cell::sync::Lazy;
+use prometheus::core::{AtomicU64, GenericGauge};
+
+pub static GC_LAST_RUN: Lazy<Box<GenericGauge<AtomicU64>>> = Lazy::new(|| {
+    let gc_last_run = Box::new(
+        GenericGauge::<AtomicU64>::new(
+            "gc_last_run",
+            "Unix timestamp, in seconds, of the end of the last garbage collection",
+        )
+        .expect("Defining the gc_last_run metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(gc_last_run.clone())
+        .expect("Registering the gc_last_run metric with the metrics registry must succeed");
+    gc_last_run
+});
+
+pub static GC_RECLAIMED_BYTES: Lazy<Box<GenericGauge<AtomicU64>>> = Lazy::new(|| {
+    let gc_reclaimed_bytes = Box::new(
+        GenericGauge::<AtomicU64>::new(
+            "gc_reclaimed_bytes",
+            "Size of the values removed from the database by the last garbage collection",
+        )
+        .expect("Defining the gc_reclaimed_bytes metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(gc_reclaimed_bytes.clone())
+        .expect("Registering the gc_reclaimed_bytes metric with the metrics registry must succeed");
+    gc_reclaimed_bytes
+});
+
+pub static GC_REMOVED_BLOCKS: Lazy<Box<GenericGauge<AtomicU64>>> = Lazy::new(|| {
+    let gc_removed_blocks = Box::new(
+        GenericGauge::<AtomicU64>::new(
+            "gc_removed_blocks",
+            "Number of blocks marked for removal by the last garbage collection",
+        )
+        .expect("Defining the gc_removed_blocks metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(gc_removed_blocks.clone())
+        .expect("Registering the gc_removed_blocks metric with the metrics registry must succeed");
+    gc_removed_blocks
+});
+
+pub static GC_MARKED_BLOCKS: Lazy<Box<GenericGauge<AtomicU64>>> = Lazy::new(|| {
+    let gc_marked_blocks = Box::new(
+        GenericGauge::<AtomicU64>::new(
+            "gc_marked_blocks",
+            "Number of blocks currently marked for removal by the ongoing garbage collection",
+        )
+        .expect("Defining the gc_marked_blocks metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(gc_marked_blocks.clone())
+        .expect("Registering the gc_marked_blocks metric with the metrics registry must succeed");
+    gc_marked_blocks
+});
+
+pub static GC_SWEEP_EPOCH: Lazy<Box<GenericGauge<AtomicU64>>> = Lazy::new(|| {
+    let gc_sweep_epoch = Box::new(
+        GenericGauge::<AtomicU64>::new(
+            "gc_sweep_epoch",
+            "Epoch at which the ongoing garbage collection sweeps the marked blocks, 0 if none",
+        )
+        .expect("Defining the gc_sweep_epoch metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(gc_sweep_epoch.clone())
+        .expect("Registering the gc_sweep_epoch metric with the metrics registry must succeed");
+    gc_sweep_epoch
+});
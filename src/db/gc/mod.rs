@@ -64,26 +64,113 @@
 //! 3. Then, the `sweep` step happens.
 //! 4. Finally, the algorithm waits for a configured amount of time to initiate the next run.
 //!
+//! The interval between runs and the number of state-trees to keep are set in the
+//! `[garbage_collection]` section of the configuration, see [`GcConfig`]. The end time of the last
+//! run and the amount of data it removed are exported as metrics.
+//!
+//! Actor bundles, e.g. those of upcoming network upgrades, aren't reachable from the chain and are
+//! kept by pinning their manifests, see [`MarkAndSweep::with_pinned`]. Data computed ahead of its
+//! use, e.g. by the pre-migrations of upcoming network upgrades, is pinned likewise, with roots
+//! read at every run, see [`MarkAndSweep::with_pinned_fn`].
+//!
+//! A run can also be requested at any time, e.g. with `Filecoin.DatabaseGarbageCollect`. Such runs
+//! skip the wait between runs, and continue the ongoing run if any, but still wait for the chain
+//! to advance between the `mark` and `filter` steps, so that blocks written while syncing or on
+//! forks aren't swept before they become reachable. Requests return right after the `mark` step
+//! with the epoch of the sweep, which the GC loop then performs and reports in metrics.
+//!
 //! ## Performance
 //! The time complexity of mark and sweep steps is `O(n)`. The filter step is currently utilizing a
 //! depth-first search algorithm, with `O(V+E)` complexity, where V is the number of vertices and E
 //! is the number of edges.
 
+mod metrics;
+
 use crate::blocks::Tipset;
 use crate::chain::ChainEpochDelta;
 
+use crate::cid_collections::CidHashSet;
 use crate::db::{truncated_hash, GarbageCollectable};
-use crate::ipld::unordered_stream_graph;
+use crate::ipld::{recurse_links_hash, unordered_stream_graph};
+use crate::lotus_json::lotus_json_with_self;
 use crate::shim::clock::ChainEpoch;
 use ahash::{HashSet, HashSetExt};
+use anyhow::Context as _;
+use cid::Cid;
 use futures::StreamExt;
 use fvm_ipld_blockstore::Blockstore;
+use human_repr::HumanCount as _;
+use serde::{Deserialize, Serialize};
 use std::mem;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::time;
 use tracing::info;
 
+/// Garbage collection settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(default)]
+pub struct GcConfig {
+    /// Collect garbage periodically.
+    pub enabled: bool,
+    /// Time to wait between two runs, in seconds.
+    pub interval_secs: u64,
+    /// Number of `chain finality` periods of state-trees to keep, at least 2. Block headers are
+    /// always kept, and so are the state-trees required to sync.
+    pub finality_periods: u32,
+}
+
+impl Default for GcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            // 10 hours
+            interval_secs: 60 * 60 * 10,
+            finality_periods: 2,
+        }
+    }
+}
+
+impl GcConfig {
+    /// Time to wait between two runs.
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+
+    /// Number of state-roots to keep, given the `chain finality` and the number of state-roots
+    /// required to sync.
+    pub fn depth(
+        &self,
+        chain_finality: ChainEpochDelta,
+        recent_state_roots: ChainEpochDelta,
+    ) -> ChainEpochDelta {
+        std::cmp::max(
+            chain_finality * ChainEpochDelta::from(self.finality_periods.max(2)),
+            recent_state_roots,
+        )
+    }
+}
+
+/// Outcome of a garbage collection request.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GcReport {
+    /// Number of blocks removed from the database.
+    pub removed_blocks: u64,
+    /// Size of the removed blocks, in bytes.
+    pub reclaimed_bytes: u64,
+    /// Epoch at which the marked blocks are swept, if the chain hasn't advanced enough since the
+    /// `mark` step yet. The outcome of the sweep is then only reported in metrics.
+    pub sweep_epoch: Option<ChainEpoch>,
+}
+
+lotus_json_with_self!(GcReport);
+
+/// Request for an immediate garbage collection run. The outcome of the run is sent back on the
+/// channel.
+pub type GcRequest = flume::Sender<anyhow::Result<GcReport>>;
+
 /// [`MarkAndSweep`] is a simple garbage collector implementation that traverses all the database
 /// keys writing them to a [`HashSet`], then filters out those that need to be kept and schedules
 /// the rest for removal.
@@ -97,6 +184,8 @@ pub struct MarkAndSweep<DB> {
     epoch_marked: ChainEpoch,
     depth: ChainEpochDelta,
     block_time: Duration,
+    pinned: Vec<Cid>,
+    pinned_fns: Vec<Box<dyn Fn() -> Vec<Cid> + Send>>,
 }
 
 impl<DB: Blockstore + GarbageCollectable + Sync + Send + 'static> MarkAndSweep<DB> {
@@ -121,11 +210,27 @@ impl<DB: Blockstore + GarbageCollectable + Sync + Send + 'static> MarkAndSweep<D
             marked: HashSet::new(),
             epoch_marked: 0,
             block_time,
+            pinned: vec![],
+            pinned_fns: vec![],
         }
     }
+
+    /// Keeps the graphs of the given roots, when present in the database, on top of the chain.
+    pub fn with_pinned(mut self, pinned: impl IntoIterator<Item = Cid>) -> Self {
+        self.pinned.extend(pinned);
+        self
+    }
+
+    /// Keeps the graphs of the roots returned by `pinned` when filtering, when present in the
+    /// database, on top of the chain.
+    pub fn with_pinned_fn(mut self, pinned: impl Fn() -> Vec<Cid> + Send + 'static) -> Self {
+        self.pinned_fns.push(Box::new(pinned));
+        self
+    }
     // Populate the initial set with all the available database keys.
     fn populate(&mut self) -> anyhow::Result<()> {
         self.marked = self.db.get_keys()?;
+        metrics::GC_MARKED_BLOCKS.set(self.marked.len() as u64);
         Ok(())
     }
 
@@ -144,29 +249,138 @@ impl<DB: Blockstore + GarbageCollectable + Sync + Send + 'static> MarkAndSweep<D
             self.marked.remove(&truncated_hash(block.cid.hash()));
         }
 
+        let mut pinned = CidHashSet::default();
+        let roots = self
+            .pinned
+            .iter()
+            .copied()
+            .chain(self.pinned_fns.iter().flat_map(|pinned| pinned()))
+            .collect::<Vec<_>>();
+        for root in roots {
+            if self.db.has(&root)? {
+                let db = &self.db;
+                recurse_links_hash(
+                    &mut pinned,
+                    root,
+                    &mut |cid| async move {
+                        db.get(&cid)?
+                            .with_context(|| format!("pinned block {cid} is missing"))
+                    },
+                    &|_| (),
+                )
+                .await?;
+            }
+        }
+        for cid in pinned {
+            self.marked.remove(&truncated_hash(cid.hash()));
+        }
+        metrics::GC_MARKED_BLOCKS.set(self.marked.len() as u64);
+
         anyhow::Ok(())
     }
 
     // Remove marked keys from the database.
-    fn sweep(&mut self) -> anyhow::Result<()> {
+    fn sweep(&mut self) -> anyhow::Result<GcReport> {
         let marked = mem::take(&mut self.marked);
-        self.db.remove_keys(marked)
+        let removed_blocks = marked.len() as u64;
+        let reclaimed_bytes = self.db.remove_keys(marked)?;
+        info!(
+            "GC removed {removed_blocks} blocks, {}",
+            reclaimed_bytes.human_count_bytes()
+        );
+        metrics::GC_LAST_RUN.set(chrono::Utc::now().timestamp().max(0) as u64);
+        metrics::GC_RECLAIMED_BYTES.set(reclaimed_bytes);
+        metrics::GC_REMOVED_BLOCKS.set(removed_blocks);
+        metrics::GC_MARKED_BLOCKS.set(0);
+        metrics::GC_SWEEP_EPOCH.set(0);
+        Ok(GcReport {
+            removed_blocks,
+            reclaimed_bytes,
+            sweep_epoch: None,
+        })
     }
 
     /// Starts the Garbage Collection loop.
     ///
     /// # Arguments
     ///
-    /// * `interval` - GC Interval to avoid constantly consuming node's resources.
+    /// * `interval` - GC Interval to avoid constantly consuming node's resources. Runs only happen
+    /// on request if `None`.
+    /// * `requests` - Requests for immediate runs, which interrupt the ongoing run, if any.
     ///
     /// NOTE: This currently does not take into account the fact that we might be starting the node
     /// using CAR-backed storage with a snapshot, for implementation simplicity.
-    pub async fn gc_loop(&mut self, interval: Duration) -> anyhow::Result<()> {
+    pub async fn gc_loop(
+        &mut self,
+        interval: Option<Duration>,
+        requests: flume::Receiver<GcRequest>,
+    ) -> anyhow::Result<()> {
         loop {
-            self.gc_workflow(interval).await?
+            // Sweeps scheduled by requests are carried out even if runs only happen on request.
+            let interval = match interval {
+                None if !self.marked.is_empty() => Some(Duration::ZERO),
+                interval => interval,
+            };
+            let request = tokio::select! {
+                result = async {
+                    match interval {
+                        Some(interval) => self.gc_workflow(interval).await,
+                        None => std::future::pending().await,
+                    }
+                } => {
+                    result?;
+                    None
+                }
+                Ok(request) = requests.recv_async() => Some(request),
+            };
+            if let Some(request) = request {
+                // The requester may have given up waiting.
+                let _ = request.send(self.gc_now().await);
+            }
         }
     }
 
+    /// Runs the GC steps without waiting between runs, keeping the state-trees of the last
+    /// `depth` epochs of the heaviest tipset. The ongoing run, if any, is continued. Otherwise,
+    /// keys are marked right away. Marked keys are only filtered and swept once the chain has
+    /// advanced `depth` epochs, until then the epoch of the sweep is returned and the sweep is
+    /// left to [`MarkAndSweep::gc_loop`].
+    pub async fn gc_now(&mut self) -> anyhow::Result<GcReport> {
+        let tipset = (self.get_heaviest_tipset)();
+        anyhow::ensure!(
+            self.depth <= tipset.epoch(),
+            "the chain is shorter than the {} epochs to keep",
+            self.depth
+        );
+
+        if self.marked.is_empty() {
+            info!("populate keys for GC");
+            self.populate()?;
+            self.epoch_marked = tipset.epoch();
+        }
+        let sweep_epoch = self.epoch_marked + self.depth;
+        if tipset.epoch() < sweep_epoch {
+            info!("GC sweep scheduled at epoch {sweep_epoch}");
+            metrics::GC_SWEEP_EPOCH.set(sweep_epoch as u64);
+            return Ok(GcReport {
+                sweep_epoch: Some(sweep_epoch),
+                ..Default::default()
+            });
+        }
+
+        info!("filter keys for GC");
+        if let Err(e) = self.filter(tipset, self.depth).await {
+            // Start the next run from scratch rather than sweeping reachable keys.
+            self.marked.clear();
+            metrics::GC_MARKED_BLOCKS.set(0);
+            metrics::GC_SWEEP_EPOCH.set(0);
+            return Err(e);
+        }
+
+        info!("GC sweep");
+        self.sweep()
+    }
+
     // This function yields to the main GC loop if the conditions are not met for execution of the
     // next step.
     async fn gc_workflow(&mut self, interval: Duration) -> anyhow::Result<()> {
@@ -188,6 +402,7 @@ impl<DB: Blockstore + GarbageCollectable + Sync + Send + 'static> MarkAndSweep<D
             info!("populate keys for GC");
             self.populate()?;
             self.epoch_marked = current_epoch;
+            metrics::GC_SWEEP_EPOCH.set((current_epoch + depth) as u64);
         }
 
         let epochs_since_marked = current_epoch - self.epoch_marked;
@@ -220,7 +435,11 @@ mod test {
 
     use core::time::Duration;
 
-    use crate::shim::clock::ChainEpoch;
+    use crate::shim::{clock::ChainEpoch, crypto::IPLD_RAW};
+    use cid::{
+        multihash::{Code, MultihashDigest as _},
+        Cid,
+    };
     use fvm_ipld_blockstore::Blockstore;
     use std::sync::Arc;
 
@@ -434,4 +653,133 @@ mod test {
             current_epoch + 1 + depth * 2
         );
     }
+
+    #[quickcheck_async::tokio]
+    async fn gc_now_collects_unreachable_data(depth: u8, current_epoch: u8, unreachable_nodes: u8) {
+        // Enforce depth above zero.
+        if depth < 1 {
+            return;
+        }
+
+        let depth = depth as ChainEpochDelta;
+        let current_epoch = current_epoch as ChainEpochDelta;
+        let unreachable_nodes = unreachable_nodes as u64;
+
+        let tester = GCTester::new();
+        let mut gc = MarkAndSweep::new(
+            tester.db.clone(),
+            tester.get_heaviest_tipset_fn(),
+            depth,
+            ZERO_DURATION,
+        );
+
+        // Not enough epochs yet.
+        assert!(gc.gc_now().await.is_err());
+
+        tester.run_epochs(current_epoch);
+        tester.run_epochs(depth);
+        tester.insert_unreachable(unreachable_nodes as i64);
+        // Mark.
+        gc.gc_workflow(ZERO_DURATION).await.unwrap();
+        tester.run_epochs(depth);
+
+        // The ongoing run is old enough to be swept right away.
+        let report = gc.gc_now().await.unwrap();
+        assert_eq!(report.removed_blocks, unreachable_nodes);
+        assert!(gc.marked.is_empty());
+        assert_eq!(
+            tester.db.get_keys().unwrap().len() as i64,
+            // `Current epoch + genesis block + twice the depth.`
+            current_epoch + 1 + depth * 2
+        );
+    }
+
+    #[tokio::test]
+    async fn gc_now_schedules_the_sweep() {
+        let depth = 3;
+        let tester = GCTester::new();
+        let mut gc = MarkAndSweep::new(
+            tester.db.clone(),
+            tester.get_heaviest_tipset_fn(),
+            depth,
+            ZERO_DURATION,
+        );
+        tester.run_epochs(depth);
+        tester.insert_unreachable(2);
+
+        // Mark.
+        let report = gc.gc_now().await.unwrap();
+        assert_eq!(report.sweep_epoch, Some(2 * depth));
+        assert_eq!(report.removed_blocks, 0);
+
+        // Written after the mark, e.g. while syncing.
+        let young = tester.db.put_cbor_default(&"young").unwrap();
+        tester.run_epochs(depth - 1);
+        assert_eq!(gc.gc_now().await.unwrap().sweep_epoch, Some(2 * depth));
+        assert_eq!(tester.db.get_keys().unwrap().len() as i64, 2 * depth + 3);
+
+        tester.run_epochs(1);
+        let report = gc.gc_now().await.unwrap();
+        assert_eq!(report.sweep_epoch, None);
+        assert_eq!(report.removed_blocks, 2);
+        assert!(tester.db.has(&young).unwrap());
+    }
+
+    #[tokio::test]
+    async fn pinned_data_kept() {
+        let tester = GCTester::new();
+        // A bundle-like graph: a raw code block referenced by a CBOR manifest.
+        let code = b"\0asm".to_vec();
+        let code_cid = Cid::new_v1(IPLD_RAW, Code::Blake2b256.digest(&code));
+        tester.db.put_keyed(&code_cid, &code).unwrap();
+        let manifest = tester.db.put_cbor_default(&vec![code_cid]).unwrap();
+        let unpinned = tester.db.put_cbor_default(&"young").unwrap();
+
+        let mut gc = MarkAndSweep::new(
+            tester.db.clone(),
+            tester.get_heaviest_tipset_fn(),
+            1,
+            ZERO_DURATION,
+        )
+        .with_pinned([manifest]);
+        tester.run_epochs(2);
+        // Mark.
+        gc.gc_workflow(ZERO_DURATION).await.unwrap();
+        tester.run_epochs(1);
+
+        let report = gc.gc_now().await.unwrap();
+        assert_eq!(report.removed_blocks, 1);
+        assert!(tester.db.has(&manifest).unwrap());
+        assert!(tester.db.has(&code_cid).unwrap());
+        assert!(!tester.db.has(&unpinned).unwrap());
+    }
+
+    #[tokio::test]
+    async fn dynamically_pinned_data_kept() {
+        let tester = GCTester::new();
+        let roots = Arc::new(parking_lot::Mutex::new(vec![]));
+        let mut gc = MarkAndSweep::new(
+            tester.db.clone(),
+            tester.get_heaviest_tipset_fn(),
+            1,
+            ZERO_DURATION,
+        )
+        .with_pinned_fn({
+            let roots = roots.clone();
+            move || roots.lock().clone()
+        });
+        let pinned = tester.db.put_cbor_default(&"pinned").unwrap();
+        let unpinned = tester.db.put_cbor_default(&"unpinned").unwrap();
+        tester.run_epochs(2);
+        // Mark.
+        gc.gc_workflow(ZERO_DURATION).await.unwrap();
+        // Pinned after the mark step, before the filter step.
+        roots.lock().push(pinned);
+        tester.run_epochs(1);
+
+        let report = gc.gc_now().await.unwrap();
+        assert_eq!(report.removed_blocks, 1);
+        assert!(tester.db.has(&pinned).unwrap());
+        assert!(!tester.db.has(&unpinned).unwrap());
+    }
 }
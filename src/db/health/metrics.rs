@@ -0,0 +1,69 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use once_cell::sync::Lazy;
+use prometheus::core::{AtomicU64, GenericCounter, GenericGauge};
+
+pub static HEALTH_CHECKED_BLOCKS: Lazy<Box<GenericCounter<AtomicU64>>> = Lazy::new(|| {
+    let health_checked_blocks = Box::new(
+        GenericCounter::<AtomicU64>::new(
+            "db_health_checked_blocks",
+            "Number of database blocks whose content was checked against their CID",
+        )
+        .expect("Defining the db_health_checked_blocks metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(health_checked_blocks.clone())
+        .expect(
+            "Registering the db_health_checked_blocks metric with the metrics registry must succeed",
+        );
+    health_checked_blocks
+});
+
+pub static HEALTH_CORRUPTED_BLOCKS: Lazy<Box<GenericCounter<AtomicU64>>> = Lazy::new(|| {
+    let health_corrupted_blocks = Box::new(
+        GenericCounter::<AtomicU64>::new(
+            "db_health_corrupted_blocks",
+            "Number of database blocks found not to match their CID",
+        )
+        .expect("Defining the db_health_corrupted_blocks metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(health_corrupted_blocks.clone())
+        .expect(
+            "Registering the db_health_corrupted_blocks metric with the metrics registry must succeed",
+        );
+    health_corrupted_blocks
+});
+
+pub static HEALTH_QUARANTINED_BLOCKS: Lazy<Box<GenericGauge<AtomicU64>>> = Lazy::new(|| {
+    let health_quarantined_blocks = Box::new(
+        GenericGauge::<AtomicU64>::new(
+            "db_health_quarantined_blocks",
+            "Number of corrupted database blocks that haven't been repaired",
+        )
+        .expect("Defining the db_health_quarantined_blocks metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(health_quarantined_blocks.clone())
+        .expect(
+            "Registering the db_health_quarantined_blocks metric with the metrics registry must succeed",
+        );
+    health_quarantined_blocks
+});
+
+pub static HEALTH_LAST_CHECK: Lazy<Box<GenericGauge<AtomicU64>>> = Lazy::new(|| {
+    let health_last_check = Box::new(
+        GenericGauge::<AtomicU64>::new(
+            "db_health_last_check",
+            "Unix timestamp, in seconds, of the end of the last database health check",
+        )
+        .expect("Defining the db_health_last_check metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(health_last_check.clone())
+        .expect(
+            "Registering the db_health_last_check metric with the metrics registry must succeed",
+        );
+    health_last_check
+});
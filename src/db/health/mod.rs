@@ -0,0 +1,505 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Continuous health checks of the database.
+//!
+//! Silent corruption of the database, e.g. bit-rot of the disk, otherwise only surfaces when a
+//! corrupted block is used, typically as a confusing state computation failure in the middle of a
+//! sync. The health check periodically samples blocks of the database, and verifies that their
+//! content matches the hash of their CID.
+//!
+//! ## Sampling
+//! Each run picks a random tipset among the `depth` most recent ones, and walks the graph of
+//! blocks reachable from it at random, until `sample_size` blocks are checked. Recent state-trees
+//! are sampled this way, rather than the whole database, since those are the blocks that the node
+//! is about to use. Blocks of the read-only CAR files aren't checked.
+//!
+//! ## Quarantine
+//! Corrupted blocks are reported in the logs and in the `db_health_corrupted_blocks` metric,
+//! along with the epoch of the tipset they were reached from, and kept in [`Quarantine`] until
+//! they are repaired. Reads of quarantined blocks through the
+//! [`BlockCache`](crate::db::BlockCache) fail, so that the corrupted content isn't used, nor
+//! served to peers. The quarantine is persisted in the settings store, and survives restarts.
+//!
+//! A quarantined block is released once a copy matching its CID is written. When re-fetching is
+//! enabled, a corrupted block is fetched again from the bitswap peers of the node. Blocks that
+//! can't be fetched stay in quarantine, and fetching them is retried on the next runs.
+
+mod metrics;
+
+use crate::blocks::Tipset;
+use crate::chain::ChainEpochDelta;
+use crate::db::car::BlockFetcher;
+use crate::db::{setting_keys::DB_HEALTH_QUARANTINE_KEY_PREFIX, SettingsStore, SettingsStoreExt};
+use crate::shim::clock::ChainEpoch;
+use crate::utils::encoding::extract_cids;
+use ahash::{HashMap, HashSet, HashSetExt};
+use cid::multihash::{Code, MultihashDigest as _};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::DAG_CBOR;
+use parking_lot::RwLock;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time;
+use tracing::{error, info, warn};
+
+/// Maximum number of blocks waiting to be visited by a run, per sampled block.
+const FRONTIER_PER_SAMPLE: usize = 4;
+
+/// Database health check settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(default)]
+pub struct HealthCheckConfig {
+    /// Check the database periodically.
+    pub enabled: bool,
+    /// Time to wait between two runs, in seconds.
+    pub interval_secs: u64,
+    /// Number of blocks checked by each run.
+    pub sample_size: usize,
+    /// Replace the corrupted blocks with blocks fetched from the bitswap peers of the node.
+    pub refetch: bool,
+}
+
+impl Default for HealthCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            // 10 minutes
+            interval_secs: 60 * 10,
+            sample_size: 1000,
+            refetch: false,
+        }
+    }
+}
+
+impl HealthCheckConfig {
+    /// Time to wait between two runs.
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_secs)
+    }
+}
+
+/// Outcome of a health check run.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HealthReport {
+    /// Number of blocks checked.
+    pub checked_blocks: usize,
+    /// Blocks whose content doesn't match their CID.
+    pub corrupted_blocks: Vec<Cid>,
+}
+
+/// Returns `false` if `block` isn't the content of `cid`. Blocks hashed with a function that
+/// isn't supported are assumed to be valid.
+pub fn is_valid_block(cid: &Cid, block: &[u8]) -> bool {
+    match Code::try_from(cid.hash().code()) {
+        Ok(code) => code.digest(block) == *cid.hash(),
+        Err(_) => true,
+    }
+}
+
+fn quarantine_key(cid: &Cid) -> String {
+    format!("{DB_HEALTH_QUARANTINE_KEY_PREFIX}{cid}")
+}
+
+/// Corrupted blocks, and the epoch of the tipset they were reached from, persisted in a settings
+/// store, one entry per block.
+pub struct Quarantine {
+    blocks: RwLock<HashMap<Cid, ChainEpoch>>,
+    /// Whether `blocks` is empty, so that reads don't lock it in the common case.
+    is_empty: AtomicBool,
+    store: Arc<dyn SettingsStore + Sync + Send>,
+}
+
+impl Quarantine {
+    /// Creates a quarantine backed by `store`, loaded with the blocks persisted in it.
+    pub fn load(store: Arc<dyn SettingsStore + Sync + Send>) -> anyhow::Result<Self> {
+        let mut blocks = HashMap::default();
+        for key in store.setting_keys()? {
+            let Some(cid) = key.strip_prefix(DB_HEALTH_QUARANTINE_KEY_PREFIX) else {
+                continue;
+            };
+            if let Some(epoch) = store.read_obj::<ChainEpoch>(&key)? {
+                blocks.insert(cid.parse()?, epoch);
+            }
+        }
+        if !blocks.is_empty() {
+            warn!(
+                "{} corrupted blocks are quarantined, reading them will fail until they are repaired",
+                blocks.len()
+            );
+        }
+        metrics::HEALTH_QUARANTINED_BLOCKS.set(blocks.len() as u64);
+        Ok(Self {
+            is_empty: AtomicBool::new(blocks.is_empty()),
+            blocks: RwLock::new(blocks),
+            store,
+        })
+    }
+
+    /// Returns `true` if the block `cid` is quarantined.
+    pub fn contains(&self, cid: &Cid) -> bool {
+        !self.is_empty.load(Ordering::Acquire) && self.blocks.read().contains_key(cid)
+    }
+
+    /// Returns the quarantined blocks.
+    pub fn cids(&self) -> Vec<Cid> {
+        self.blocks.read().keys().copied().collect()
+    }
+
+    /// Quarantines the block `cid`, reached from the tipset at `epoch`.
+    pub fn insert(&self, cid: Cid, epoch: ChainEpoch) {
+        if let Err(e) = self.store.write_obj(&quarantine_key(&cid), &epoch) {
+            warn!("Persisting quarantined block {cid} failed: {e}");
+        }
+        let mut blocks = self.blocks.write();
+        blocks.insert(cid, epoch);
+        self.is_empty.store(false, Ordering::Release);
+        metrics::HEALTH_QUARANTINED_BLOCKS.set(blocks.len() as u64);
+    }
+
+    /// Releases the block `cid` from the quarantine, once repaired.
+    pub fn release(&self, cid: &Cid) {
+        let mut blocks = self.blocks.write();
+        if blocks.remove(cid).is_none() {
+            return;
+        }
+        self.is_empty.store(blocks.is_empty(), Ordering::Release);
+        metrics::HEALTH_QUARANTINED_BLOCKS.set(blocks.len() as u64);
+        drop(blocks);
+        if let Err(e) = self.store.delete(&quarantine_key(cid)) {
+            warn!("Deleting quarantined block {cid} failed: {e}");
+        }
+    }
+}
+
+/// [`HealthCheck`] samples the blocks of recent tipsets, and quarantines those that are
+/// corrupted.
+pub struct HealthCheck<DB> {
+    db: Arc<DB>,
+    get_heaviest_tipset: Box<dyn Fn() -> Arc<Tipset> + Send + Sync>,
+    depth: ChainEpochDelta,
+    sample_size: usize,
+    fetcher: Option<Arc<dyn BlockFetcher>>,
+    quarantine: Arc<Quarantine>,
+}
+
+impl<DB: Blockstore + Send + Sync + 'static> HealthCheck<DB> {
+    /// Creates a new health check.
+    ///
+    /// # Arguments
+    ///
+    /// * `db` - A reference to the database instance, which must not be cached, so that the
+    ///   blocks are checked as stored.
+    /// * `get_heaviest_tipset` - A function that facilitates heaviest tipset retrieval.
+    /// * `depth` - The number of recent tipsets the sampled tipset is picked from.
+    /// * `sample_size` - The number of blocks checked by each run.
+    /// * `quarantine` - The quarantine of the corrupted blocks, shared with the block cache of the
+    ///   node.
+    pub fn new(
+        db: Arc<DB>,
+        get_heaviest_tipset: Box<dyn Fn() -> Arc<Tipset> + Send + Sync>,
+        depth: ChainEpochDelta,
+        sample_size: usize,
+        quarantine: Arc<Quarantine>,
+    ) -> Self {
+        Self {
+            db,
+            get_heaviest_tipset,
+            depth,
+            sample_size,
+            fetcher: None,
+            quarantine,
+        }
+    }
+
+    /// Replaces the corrupted blocks with blocks fetched by `fetcher`.
+    pub fn with_fetcher(mut self, fetcher: Arc<dyn BlockFetcher>) -> Self {
+        self.fetcher = Some(fetcher);
+        self
+    }
+
+    /// Checks the blocks of a random recent tipset.
+    pub fn check(&self) -> anyhow::Result<HealthReport> {
+        self.repair_quarantined();
+
+        let mut rng = rand::thread_rng();
+        let heaviest = (self.get_heaviest_tipset)();
+        let lookback = rng.gen_range(0..=self.depth.clamp(0, heaviest.epoch())) as usize;
+        let tipset = (*heaviest)
+            .clone()
+            .chain(self.db.as_ref())
+            .take(lookback + 1)
+            .last()
+            .unwrap_or_else(|| (*heaviest).clone());
+        let report = self.check_from(tipset.cids(), tipset.epoch(), &mut rng)?;
+
+        metrics::HEALTH_LAST_CHECK.set(chrono::Utc::now().timestamp().max(0) as u64);
+        Ok(report)
+    }
+
+    /// Checks up to `sample_size` blocks reachable from `roots`, reached from the tipset at
+    /// `epoch`.
+    fn check_from(
+        &self,
+        roots: Vec<Cid>,
+        epoch: ChainEpoch,
+        rng: &mut impl Rng,
+    ) -> anyhow::Result<HealthReport> {
+        let mut report = HealthReport::default();
+        let mut seen = HashSet::new();
+        let mut frontier = roots;
+        let max_frontier = self.sample_size.saturating_mul(FRONTIER_PER_SAMPLE).max(1);
+
+        while report.checked_blocks < self.sample_size && !frontier.is_empty() {
+            let cid = frontier.swap_remove(rng.gen_range(0..frontier.len()));
+            if !seen.insert(cid) {
+                continue;
+            }
+            // Blocks may be missing, e.g. removed by the garbage collector or in a CAR file.
+            let Some(block) = self.db.get(&cid)? else {
+                continue;
+            };
+            report.checked_blocks += 1;
+            metrics::HEALTH_CHECKED_BLOCKS.inc();
+
+            if !is_valid_block(&cid, &block) {
+                self.quarantine(cid, epoch);
+                report.corrupted_blocks.push(cid);
+                continue;
+            }
+            if cid.codec() == DAG_CBOR {
+                if let Ok(links) = extract_cids(&block) {
+                    frontier.extend(links.into_iter().filter(|link| !seen.contains(link)));
+                }
+            }
+            // Drop random blocks rather than the latest ones, so that the walk stays random.
+            while frontier.len() > max_frontier {
+                frontier.swap_remove(rng.gen_range(0..frontier.len()));
+            }
+        }
+        Ok(report)
+    }
+
+    fn quarantine(&self, cid: Cid, epoch: ChainEpoch) {
+        error!(
+            "Database corruption detected: the content of block {cid}, reached from the tipset at epoch {epoch}, doesn't match its CID"
+        );
+        metrics::HEALTH_CORRUPTED_BLOCKS.inc();
+        self.quarantine.insert(cid, epoch);
+
+        if self.fetcher.is_some() {
+            self.repair(&cid);
+        }
+    }
+
+    /// Retries fetching the quarantined blocks.
+    fn repair_quarantined(&self) {
+        if self.fetcher.is_none() {
+            return;
+        }
+        for cid in self.quarantine.cids() {
+            self.repair(&cid);
+        }
+    }
+
+    /// Replaces the block `cid` with a block fetched from the network, and returns `true` if the
+    /// block is repaired. The corrupted block stays in quarantine until a valid block is fetched.
+    fn repair(&self, cid: &Cid) -> bool {
+        let Some(fetcher) = &self.fetcher else {
+            return false;
+        };
+        let repaired = fetcher.refetch(cid)
+            && matches!(self.db.get(cid), Ok(Some(block)) if is_valid_block(cid, &block));
+        if repaired {
+            info!("Repaired corrupted block {cid}");
+            self.quarantine.release(cid);
+        } else {
+            warn!("Failed to fetch corrupted block {cid}, it stays in quarantine");
+        }
+        repaired
+    }
+
+    /// Starts the health check loop, with `interval` between two runs.
+    pub async fn health_check_loop(self: Arc<Self>, interval: Duration) -> anyhow::Result<()> {
+        loop {
+            time::sleep(interval).await;
+            let health_check = self.clone();
+            match tokio::task::spawn_blocking(move || health_check.check()).await? {
+                Ok(report) if report.corrupted_blocks.is_empty() => {
+                    info!(
+                        "Database health check passed, {} blocks checked",
+                        report.checked_blocks
+                    );
+                }
+                Ok(report) => {
+                    error!(
+                        "Database health check found {} corrupted blocks out of {} checked",
+                        report.corrupted_blocks.len(),
+                        report.checked_blocks
+                    );
+                }
+                Err(e) => warn!("Database health check failed: {e}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{CachingBlockHeader, RawBlockHeader};
+    use crate::db::MemoryDB;
+    use fvm_ipld_encoding::{to_vec, IPLD_RAW};
+
+    fn raw_block(data: &[u8]) -> (Cid, Vec<u8>) {
+        (
+            Cid::new_v1(IPLD_RAW, Code::Blake2b256.digest(data)),
+            data.to_vec(),
+        )
+    }
+
+    fn cbor_block(links: &[Cid]) -> (Cid, Vec<u8>) {
+        let data = to_vec(&links).unwrap();
+        (Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(&data)), data)
+    }
+
+    fn health_check(db: Arc<MemoryDB>) -> HealthCheck<MemoryDB> {
+        let genesis = Arc::new(Tipset::from(CachingBlockHeader::new(
+            RawBlockHeader::default(),
+        )));
+        let quarantine = Arc::new(Quarantine::load(Arc::new(MemoryDB::default())).unwrap());
+        HealthCheck::new(db, Box::new(move || genesis.clone()), 0, 100, quarantine)
+    }
+
+    /// Puts the DAG `root -> [a, b]` in `db`, with `b` corrupted.
+    fn corrupted_dag(db: &MemoryDB) -> (Cid, Cid, Vec<u8>) {
+        let (a, a_data) = raw_block(b"Cthulhu");
+        let (b, b_data) = raw_block(b"Dagon");
+        let (root, root_data) = cbor_block(&[a, b]);
+        db.put_keyed(&a, &a_data).unwrap();
+        db.put_keyed(&b, b"Hydra").unwrap();
+        db.put_keyed(&root, &root_data).unwrap();
+        (root, b, b_data)
+    }
+
+    #[test]
+    fn validates_blocks() {
+        let (cid, data) = raw_block(b"Cthulhu");
+        assert!(is_valid_block(&cid, &data));
+        assert!(!is_valid_block(&cid, b"Dagon"));
+    }
+
+    #[test]
+    fn quarantines_corrupted_blocks() {
+        let db = Arc::new(MemoryDB::default());
+        let (root, corrupted, _) = corrupted_dag(&db);
+        let health_check = health_check(db);
+
+        let report = health_check
+            .check_from(vec![root], 42, &mut rand::thread_rng())
+            .unwrap();
+        assert_eq!(report.checked_blocks, 3);
+        assert_eq!(report.corrupted_blocks, vec![corrupted]);
+        assert_eq!(
+            health_check.quarantine.blocks.read().get(&corrupted),
+            Some(&42)
+        );
+    }
+
+    struct FakeFetcher {
+        db: Arc<MemoryDB>,
+        blocks: HashMap<Cid, Vec<u8>>,
+    }
+
+    impl BlockFetcher for FakeFetcher {
+        fn fetch(&self, cid: &Cid) -> bool {
+            !self.db.has(cid).unwrap() && self.refetch(cid)
+        }
+
+        fn refetch(&self, cid: &Cid) -> bool {
+            match self.blocks.get(cid) {
+                Some(block) => self.db.put_keyed(cid, block).is_ok(),
+                None => false,
+            }
+        }
+    }
+
+    #[test]
+    fn repairs_corrupted_blocks() {
+        let db = Arc::new(MemoryDB::default());
+        let (root, corrupted, data) = corrupted_dag(&db);
+        let fetcher = FakeFetcher {
+            db: db.clone(),
+            blocks: [(corrupted, data.clone())].into_iter().collect(),
+        };
+        let health_check = health_check(db.clone()).with_fetcher(Arc::new(fetcher));
+
+        let report = health_check
+            .check_from(vec![root], 0, &mut rand::thread_rng())
+            .unwrap();
+        assert_eq!(report.corrupted_blocks, vec![corrupted]);
+        assert_eq!(db.get(&corrupted).unwrap(), Some(data));
+        assert!(!health_check.quarantine.contains(&corrupted));
+    }
+
+    #[test]
+    fn keeps_corrupted_blocks_until_fetched() {
+        let db = Arc::new(MemoryDB::default());
+        let (root, corrupted, _) = corrupted_dag(&db);
+        let fetcher = FakeFetcher {
+            db: db.clone(),
+            blocks: HashMap::default(),
+        };
+        let health_check = health_check(db.clone()).with_fetcher(Arc::new(fetcher));
+
+        let report = health_check
+            .check_from(vec![root], 0, &mut rand::thread_rng())
+            .unwrap();
+        assert_eq!(report.corrupted_blocks, vec![corrupted]);
+        assert_eq!(db.get(&corrupted).unwrap(), Some(b"Hydra".to_vec()));
+        assert!(health_check.quarantine.contains(&corrupted));
+    }
+
+    #[test]
+    fn quarantine_is_persisted() {
+        let store = Arc::new(MemoryDB::default());
+        let (cid, _) = raw_block(b"Cthulhu");
+        let quarantine = Quarantine::load(store.clone()).unwrap();
+        assert!(!quarantine.contains(&cid));
+        quarantine.insert(cid, 42);
+        assert!(quarantine.contains(&cid));
+
+        let reloaded = Quarantine::load(store.clone()).unwrap();
+        assert_eq!(reloaded.blocks.read().get(&cid), Some(&42));
+        reloaded.release(&cid);
+        assert!(!reloaded.contains(&cid));
+        assert!(!Quarantine::load(store).unwrap().contains(&cid));
+    }
+
+    #[test]
+    fn samples_at_most_sample_size_blocks() {
+        let db = Arc::new(MemoryDB::default());
+        let leaves = (0..10u8)
+            .map(|i| {
+                let (cid, data) = raw_block(&[i]);
+                db.put_keyed(&cid, &data).unwrap();
+                cid
+            })
+            .collect::<Vec<_>>();
+        let (root, root_data) = cbor_block(&leaves);
+        db.put_keyed(&root, &root_data).unwrap();
+        let mut health_check = health_check(db);
+        health_check.sample_size = 5;
+
+        let report = health_check
+            .check_from(vec![root], 0, &mut rand::thread_rng())
+            .unwrap();
+        assert_eq!(report.checked_blocks, 5);
+        assert!(report.corrupted_blocks.is_empty());
+    }
+}
@@ -27,15 +27,24 @@ impl GarbageCollectable for MemoryDB {
         Ok(set)
     }
 
-    fn remove_keys(&self, keys: HashSet<u32>) -> anyhow::Result<()> {
+    fn remove_keys(&self, keys: HashSet<u32>) -> anyhow::Result<u64> {
         let mut db = self.blockchain_db.write();
-        db.retain(|key, _| {
+        let mut removed_bytes = 0;
+        db.retain(|key, value| {
             let cid = Cid::try_from(key.as_slice());
             match cid {
-                Ok(cid) => !keys.contains(&truncated_hash(cid.hash())),
+                Ok(cid) if keys.contains(&truncated_hash(cid.hash())) => {
+                    removed_bytes += value.len() as u64;
+                    false
+                }
                 _ => true,
             }
         });
+        Ok(removed_bytes)
+    }
+
+    fn remove_block(&self, cid: &Cid) -> anyhow::Result<()> {
+        self.blockchain_db.write().remove(&cid.to_bytes());
         Ok(())
     }
 }
@@ -59,6 +68,11 @@ impl SettingsStore for MemoryDB {
     fn setting_keys(&self) -> anyhow::Result<Vec<String>> {
         Ok(self.settings_db.read().keys().cloned().collect_vec())
     }
+
+    fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.settings_db.write().remove(key);
+        Ok(())
+    }
 }
 
 impl Blockstore for MemoryDB {
@@ -5,9 +5,9 @@
 //! We are getting rid of rolling db in favor of mark-and-sweep GC. Therefore the two databases
 //! previously representing node state have to be merged into a new one and removed.
 
-use crate::db::db_engine::Db;
 use crate::db::migration::migration_map::temporary_db_name;
 use crate::db::migration::v0_16_0::paritydb_0_15_1::{DbColumn, ParityDb};
+use crate::db::parity_db::ParityDb as Db;
 use anyhow::Context;
 use cid::multihash::Code::Blake2b256;
 use cid::multihash::MultihashDigest;
@@ -1,20 +1,31 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+mod block_cache;
+mod buffered;
 pub mod car;
+pub mod db_engine;
 mod memory;
 pub mod parity_db;
 pub mod parity_db_config;
+#[cfg(feature = "rocksdb")]
+pub mod rocks_db;
+pub mod rocks_db_config;
 
 mod gc;
-pub use gc::MarkAndSweep;
+mod health;
+pub use block_cache::{BlockCache, BlockCacheConfig};
+pub use buffered::{state_write_batch_size, BufferedBlockstore};
+pub use db_engine::DbBackend;
+pub use gc::{GcConfig, GcReport, GcRequest, MarkAndSweep};
+pub use health::{HealthCheck, HealthCheckConfig, Quarantine};
 pub use memory::MemoryDB;
 mod db_mode;
 pub mod migration;
 
 use ahash::HashSet;
 use anyhow::Context as _;
-use cid::multihash;
+use cid::{multihash, Cid};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use std::sync::Arc;
@@ -22,8 +33,43 @@ use std::sync::Arc;
 pub mod setting_keys {
     /// Key used to store the heaviest tipset in the settings store. This is expected to be a [`crate::blocks::TipsetKey`]s
     pub const HEAD_KEY: &str = "head";
+    /// Key used to store the journal of the most recent consistent heads. This is expected to be a list of [`crate::blocks::TipsetKey`]s
+    pub const HEAD_JOURNAL_KEY: &str = "/head/journal";
     /// Key used to store the memory pool configuration in the settings store.
     pub const MPOOL_CONFIG_KEY: &str = "/mpool/config";
+    /// Prefix of the keys used to store the locally published messages of the memory pool, by CID,
+    /// in the settings store.
+    pub const MPOOL_LOCAL_MESSAGE_KEY_PREFIX: &str = "/mpool/local/";
+    /// Key used to store the checkpoint of the running state migration in the settings store.
+    pub const STATE_MIGRATION_CHECKPOINT_KEY: &str = "/state_migration/checkpoint";
+    /// Key used to store the sync checkpoint set through the RPC API in the settings store.
+    pub const SYNC_CHECKPOINT_KEY: &str = "/sync/checkpoint";
+    /// Prefix of the keys used to store the bad blocks, by CID, and the reasons they were marked
+    /// bad for in the settings store.
+    pub const BAD_BLOCK_KEY_PREFIX: &str = "/sync/bad_block/";
+    /// Prefix of the keys used to store the quarantined corrupted blocks, by CID, and the epochs
+    /// of the tipsets they were reached from in the settings store.
+    pub const DB_HEALTH_QUARANTINE_KEY_PREFIX: &str = "/db_health/quarantine/";
+    /// Prefix of the keys used to store the locations of the receipts of the messages executed on
+    /// chain, by message CID, in the settings store.
+    pub const MESSAGE_INDEX_KEY_PREFIX: &str = "/index/message/";
+    /// Prefix of the keys used to store the CIDs of the messages indexed by each epoch, to prune
+    /// them, in the settings store.
+    pub const MESSAGE_INDEX_EPOCH_KEY_PREFIX: &str = "/index/message_epoch/";
+    /// Key used to store the lowest epoch whose messages may still be indexed in the settings
+    /// store.
+    pub const MESSAGE_INDEX_TAIL_KEY: &str = "/index/message_tail";
+    /// Prefix of the keys used to store the events emitted by actors, by epoch of the tipset
+    /// including the messages that emitted them, in the settings store.
+    pub const EVENT_INDEX_KEY_PREFIX: &str = "/index/events/";
+    /// Key used to store the addresses of the best scored peers in the settings store, to dial
+    /// them first on restart.
+    pub const KNOWN_PEERS_KEY: &str = "/net/known_peers";
+    /// Key used to store the peers protected with `Filecoin.NetProtectAdd` in the settings store.
+    pub const NET_PROTECTED_PEERS_KEY: &str = "/net/protected_peers";
+    /// Key used to store the block list managed with `Filecoin.NetBlockAdd` in the settings
+    /// store.
+    pub const NET_BLOCK_LIST_KEY: &str = "/net/block_list";
 }
 
 /// Interface used to store and retrieve settings from the database.
@@ -42,6 +88,9 @@ pub trait SettingsStore {
 
     /// Returns all setting keys.
     fn setting_keys(&self) -> anyhow::Result<Vec<String>>;
+
+    /// Removes the field `key` from the Settings store, if present.
+    fn delete(&self, key: &str) -> anyhow::Result<()>;
 }
 
 impl<T: SettingsStore> SettingsStore for Arc<T> {
@@ -60,6 +109,10 @@ impl<T: SettingsStore> SettingsStore for Arc<T> {
     fn setting_keys(&self) -> anyhow::Result<Vec<String>> {
         SettingsStore::setting_keys(self.as_ref())
     }
+
+    fn delete(&self, key: &str) -> anyhow::Result<()> {
+        SettingsStore::delete(self.as_ref(), key)
+    }
 }
 
 /// Extension trait for the [`SettingsStore`] trait. It is implemented for all types that implement
@@ -92,17 +145,41 @@ impl<T: ?Sized + SettingsStore> SettingsStoreExt for T {
     }
 }
 
+/// Statistics of a column (column family) of the database. The statistics that a backend doesn't
+/// track are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ColumnStatistics {
+    pub name: String,
+    /// Size of the column on disk, in bytes.
+    pub size_bytes: Option<u64>,
+    /// Number of files holding the column.
+    pub file_count: Option<u64>,
+    /// Estimated number of bytes that compaction needs to rewrite.
+    pub compaction_pending_bytes: Option<u64>,
+    /// Memory used by the block cache of the column, in bytes.
+    pub cache_usage_bytes: Option<u64>,
+}
+
 /// Traits for collecting DB stats
 pub trait DBStatistics {
     fn get_statistics(&self) -> Option<String> {
         None
     }
+
+    /// Statistics of each column of the database.
+    fn get_column_statistics(&self) -> Vec<ColumnStatistics> {
+        vec![]
+    }
 }
 
 impl<DB: DBStatistics> DBStatistics for std::sync::Arc<DB> {
     fn get_statistics(&self) -> Option<String> {
         self.as_ref().get_statistics()
     }
+
+    fn get_column_statistics(&self) -> Vec<ColumnStatistics> {
+        self.as_ref().get_column_statistics()
+    }
 }
 
 /// A trait to facilitate mark-and-sweep garbage collection.
@@ -121,38 +198,41 @@ pub trait GarbageCollectable {
     /// # Arguments
     ///
     /// * `keys` - A set of keys to be removed from the database.
-    fn remove_keys(&self, keys: HashSet<u32>) -> anyhow::Result<()>;
-}
+    ///
+    /// Returns the number of bytes of the removed values.
+    fn remove_keys(&self, keys: HashSet<u32>) -> anyhow::Result<u64>;
 
-/// A function that converts a [`multihash::MultihashGeneric`] digest into a `u32` representation.
-/// We don't care about collisions here as main use-case is garbage collection.
-pub(crate) fn truncated_hash<const S: usize>(hash: &multihash::MultihashGeneric<S>) -> u32 {
-    let digest = hash.digest();
-    u32::from_le_bytes(digest[0..4].try_into().expect("shouldn't fail"))
+    /// Removes the block `cid`, and only that block, e.g. to replace a corrupted copy.
+    fn remove_block(&self, cid: &Cid) -> anyhow::Result<()>;
 }
 
-pub mod db_engine {
-    use std::path::{Path, PathBuf};
-
-    use super::db_mode::choose_db;
-
-    pub type Db = crate::db::parity_db::ParityDb;
-    pub type DbConfig = crate::db::parity_db_config::ParityDbConfig;
+impl<DB: GarbageCollectable> GarbageCollectable for std::sync::Arc<DB> {
+    fn get_keys(&self) -> anyhow::Result<HashSet<u32>> {
+        self.as_ref().get_keys()
+    }
 
-    /// Returns the path to the database directory to be used by the daemon.
-    pub fn db_root(chain_data_root: &Path) -> anyhow::Result<PathBuf> {
-        choose_db(chain_data_root)
+    fn remove_keys(&self, keys: HashSet<u32>) -> anyhow::Result<u64> {
+        self.as_ref().remove_keys(keys)
     }
 
-    pub fn open_db(path: PathBuf, config: DbConfig) -> anyhow::Result<Db> {
-        Db::open(path, &config).map_err(Into::into)
+    fn remove_block(&self, cid: &Cid) -> anyhow::Result<()> {
+        self.as_ref().remove_block(cid)
     }
 }
 
+/// A function that converts a [`multihash::MultihashGeneric`] digest into a `u32` representation.
+/// We don't care about collisions here as main use-case is garbage collection.
+pub(crate) fn truncated_hash<const S: usize>(hash: &multihash::MultihashGeneric<S>) -> u32 {
+    let digest = hash.digest();
+    u32::from_le_bytes(digest[0..4].try_into().expect("shouldn't fail"))
+}
+
 #[cfg(test)]
 mod tests {
     pub mod db_utils;
     mod mem_test;
     mod parity_test;
+    #[cfg(feature = "rocksdb")]
+    mod rocks_test;
     pub mod subtests;
 }
@@ -7,7 +7,8 @@ use std::path::PathBuf;
 use super::SettingsStore;
 
 use crate::db::{
-    parity_db_config::ParityDbConfig, truncated_hash, DBStatistics, GarbageCollectable,
+    parity_db_config::ParityDbConfig, truncated_hash, ColumnStatistics, DBStatistics, DbBackend,
+    GarbageCollectable,
 };
 use crate::libp2p_bitswap::{BitswapStoreRead, BitswapStoreReadWrite};
 
@@ -164,6 +165,11 @@ impl SettingsStore for ParityDb {
         }
         Ok(keys)
     }
+
+    fn delete(&self, key: &str) -> anyhow::Result<()> {
+        let tx = [(DbColumn::Settings as u8, key.as_bytes(), None)];
+        self.db.commit(tx).context("error deleting setting")
+    }
 }
 
 impl Blockstore for ParityDb {
@@ -263,6 +269,26 @@ impl DBStatistics for ParityDb {
             }
         }
     }
+
+    fn get_column_statistics(&self) -> Vec<ColumnStatistics> {
+        // `ParityDb` only tracks the statistics when enabled, and has no notion of files,
+        // compaction or block cache.
+        if !self.statistics_enabled {
+            return vec![];
+        }
+        let stats = self.db.stats();
+        DbColumn::iter()
+            .map(|column| ColumnStatistics {
+                name: column.to_string(),
+                size_bytes: stats
+                    .columns
+                    .get(column as usize)
+                    .and_then(Option::as_ref)
+                    .map(|column| column.total_bytes),
+                ..Default::default()
+            })
+            .collect()
+    }
 }
 
 type Op = (u8, Operation<Vec<u8>, Vec<u8>>);
@@ -309,15 +335,17 @@ impl GarbageCollectable for ParityDb {
         Ok(set)
     }
 
-    fn remove_keys(&self, keys: HashSet<u32>) -> anyhow::Result<()> {
+    fn remove_keys(&self, keys: HashSet<u32>) -> anyhow::Result<u64> {
+        let mut removed_bytes = 0;
         let mut iter = self.db.iter(DbColumn::GraphFull as u8)?;
-        while let Some((key, _)) = iter.next()? {
+        while let Some((key, value)) = iter.next()? {
             let cid = Cid::try_from(key)?;
 
             if keys.contains(&truncated_hash(cid.hash())) {
                 self.db
                     .commit_changes([Self::dereference_operation(&cid)])
-                    .context("error remove")?
+                    .context("error remove")?;
+                removed_bytes += value.len() as u64;
             }
         }
 
@@ -338,10 +366,39 @@ impl GarbageCollectable for ParityDb {
                         result = res;
                         return false;
                     }
+                    removed_bytes += val.value.len() as u64;
                 }
                 true
             })?;
 
+        result.map(|()| removed_bytes)
+    }
+
+    fn remove_block(&self, cid: &Cid) -> anyhow::Result<()> {
+        self.db
+            .commit_changes([Self::dereference_operation(cid)])
+            .context("error remove")
+    }
+}
+
+impl DbBackend for ParityDb {
+    fn for_each_block(
+        &self,
+        f: &mut dyn FnMut(Cid, Vec<u8>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let mut iter = self.db.iter(DbColumn::GraphFull as u8)?;
+        while let Some((key, value)) = iter.next()? {
+            f(Cid::try_from(key)?, value)?;
+        }
+
+        // Keys of this column are the hashes of the values.
+        let mut result = Ok(());
+        self.db
+            .iter_column_while(DbColumn::GraphDagCborBlake2b256 as u8, |val| {
+                let cid = Cid::new_v1(DAG_CBOR, Blake2b256.digest(&val.value));
+                result = f(cid, val.value);
+                result.is_ok()
+            })?;
         result
     }
 }
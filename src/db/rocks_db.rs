@@ -0,0 +1,245 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use ahash::{HashSet, HashSetExt};
+use std::path::Path;
+
+use super::SettingsStore;
+
+use crate::db::{
+    rocks_db_config::RocksDbConfig, truncated_hash, ColumnStatistics, DBStatistics, DbBackend,
+    GarbageCollectable,
+};
+use crate::libp2p_bitswap::{BitswapStoreRead, BitswapStoreReadWrite};
+
+use anyhow::Context as _;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use rocksdb::{properties, ColumnFamily, DBCompressionType, IteratorMode, Options, WriteBatch, DB};
+use tracing::warn;
+
+/// Column family for storing IPLD data, keyed by CID.
+const GRAPH_COLUMN: &str = "graph";
+/// Column family for storing Forest-specific settings.
+const SETTINGS_COLUMN: &str = "settings";
+/// Number of levels of the LSM tree, the `RocksDB` default.
+const NUM_LEVELS: usize = 7;
+
+pub struct RocksDb {
+    db: DB,
+    options: Options,
+    statistics_enabled: bool,
+}
+
+impl RocksDb {
+    fn to_options(config: &RocksDbConfig) -> Options {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+        options.increase_parallelism(num_cpus::get() as i32);
+        options.set_max_open_files(config.max_open_files);
+        options.set_write_buffer_size(config.write_buffer_size);
+        options.set_compression_type(DBCompressionType::Lz4);
+        if config.enable_statistics {
+            options.enable_statistics();
+        }
+        options
+    }
+
+    pub fn open(path: impl AsRef<Path>, config: &RocksDbConfig) -> anyhow::Result<Self> {
+        let options = Self::to_options(config);
+        Ok(Self {
+            db: DB::open_cf(&options, path, [GRAPH_COLUMN, SETTINGS_COLUMN])?,
+            options,
+            statistics_enabled: config.enable_statistics,
+        })
+    }
+
+    fn column(&self, name: &str) -> anyhow::Result<&ColumnFamily> {
+        self.db
+            .cf_handle(name)
+            .with_context(|| format!("missing column family {name}"))
+    }
+
+    fn read_from_column(&self, key: &[u8], column: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(self
+            .db
+            .get_pinned_cf(self.column(column)?, key)
+            .with_context(|| format!("error from column {column}"))?
+            .map(|value| value.to_vec()))
+    }
+
+    fn write_to_column(&self, key: &[u8], value: &[u8], column: &str) -> anyhow::Result<()> {
+        self.db
+            .put_cf(self.column(column)?, key, value)
+            .with_context(|| format!("error writing to column {column}"))
+    }
+}
+
+impl SettingsStore for RocksDb {
+    fn read_bin(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        self.read_from_column(key.as_bytes(), SETTINGS_COLUMN)
+    }
+
+    fn write_bin(&self, key: &str, value: &[u8]) -> anyhow::Result<()> {
+        self.write_to_column(key.as_bytes(), value, SETTINGS_COLUMN)
+    }
+
+    fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        Ok(self
+            .read_from_column(key.as_bytes(), SETTINGS_COLUMN)?
+            .is_some())
+    }
+
+    fn setting_keys(&self) -> anyhow::Result<Vec<String>> {
+        self.db
+            .iterator_cf(self.column(SETTINGS_COLUMN)?, IteratorMode::Start)
+            .map(|entry| Ok(String::from_utf8(entry?.0.into_vec())?))
+            .collect()
+    }
+
+    fn delete(&self, key: &str) -> anyhow::Result<()> {
+        self.db
+            .delete_cf(self.column(SETTINGS_COLUMN)?, key.as_bytes())
+            .context("error deleting setting")
+    }
+}
+
+impl Blockstore for RocksDb {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        self.read_from_column(&k.to_bytes(), GRAPH_COLUMN)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.write_to_column(&k.to_bytes(), block, GRAPH_COLUMN)
+    }
+
+    fn put_many_keyed<D, I>(&self, blocks: I) -> anyhow::Result<()>
+    where
+        Self: Sized,
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = (Cid, D)>,
+    {
+        let column = self.column(GRAPH_COLUMN)?;
+        let mut batch = WriteBatch::default();
+        for (k, v) in blocks {
+            batch.put_cf(column, k.to_bytes(), v);
+        }
+        self.db.write(batch).context("error bulk writing")
+    }
+}
+
+impl BitswapStoreRead for RocksDb {
+    fn contains(&self, cid: &Cid) -> anyhow::Result<bool> {
+        Ok(self
+            .db
+            .get_pinned_cf(self.column(GRAPH_COLUMN)?, cid.to_bytes())
+            .context("error checking if key exists")?
+            .is_some())
+    }
+
+    fn get(&self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        Blockstore::get(self, cid)
+    }
+}
+
+impl BitswapStoreReadWrite for RocksDb {
+    /// `fvm_ipld_encoding::DAG_CBOR(0x71)` is covered by
+    /// [`libipld::DefaultParams`] under feature `dag-cbor`
+    type Params = libipld::DefaultParams;
+
+    fn insert(&self, block: &libipld::Block<Self::Params>) -> anyhow::Result<()> {
+        self.put_keyed(block.cid(), block.data())
+    }
+}
+
+impl DBStatistics for RocksDb {
+    fn get_statistics(&self) -> Option<String> {
+        if !self.statistics_enabled {
+            return None;
+        }
+        self.options.get_statistics()
+    }
+
+    fn get_column_statistics(&self) -> Vec<ColumnStatistics> {
+        [GRAPH_COLUMN, SETTINGS_COLUMN]
+            .into_iter()
+            .filter_map(|name| {
+                let column = self.column(name).ok()?;
+                let property = |property: &str| {
+                    self.db
+                        .property_int_value_cf(column, property)
+                        .unwrap_or_else(|e| {
+                            warn!("Unable to read {property} of column {name}: {e}");
+                            None
+                        })
+                };
+                let file_count = (0..NUM_LEVELS)
+                    .map(|level| property(&format!("rocksdb.num-files-at-level{level}")))
+                    .sum();
+                Some(ColumnStatistics {
+                    name: name.to_owned(),
+                    size_bytes: property(properties::TOTAL_SST_FILES_SIZE),
+                    file_count,
+                    compaction_pending_bytes: property(
+                        properties::ESTIMATE_PENDING_COMPACTION_BYTES,
+                    ),
+                    cache_usage_bytes: property(properties::BLOCK_CACHE_USAGE),
+                })
+            })
+            .collect()
+    }
+}
+
+impl GarbageCollectable for RocksDb {
+    fn get_keys(&self) -> anyhow::Result<HashSet<u32>> {
+        let mut set = HashSet::new();
+        for entry in self
+            .db
+            .iterator_cf(self.column(GRAPH_COLUMN)?, IteratorMode::Start)
+        {
+            let (key, _) = entry?;
+            let cid = Cid::try_from(&key[..])?;
+            set.insert(truncated_hash(cid.hash()));
+        }
+        Ok(set)
+    }
+
+    fn remove_keys(&self, keys: HashSet<u32>) -> anyhow::Result<u64> {
+        let column = self.column(GRAPH_COLUMN)?;
+        let mut removed_bytes = 0;
+        let mut batch = WriteBatch::default();
+        for entry in self.db.iterator_cf(column, IteratorMode::Start) {
+            let (key, value) = entry?;
+            let cid = Cid::try_from(&key[..])?;
+            if keys.contains(&truncated_hash(cid.hash())) {
+                batch.delete_cf(column, key);
+                removed_bytes += value.len() as u64;
+            }
+        }
+        self.db.write(batch).context("error remove")?;
+        Ok(removed_bytes)
+    }
+
+    fn remove_block(&self, cid: &Cid) -> anyhow::Result<()> {
+        self.db
+            .delete_cf(self.column(GRAPH_COLUMN)?, cid.to_bytes())
+            .context("error remove")
+    }
+}
+
+impl DbBackend for RocksDb {
+    fn for_each_block(
+        &self,
+        f: &mut dyn FnMut(Cid, Vec<u8>) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        for entry in self
+            .db
+            .iterator_cf(self.column(GRAPH_COLUMN)?, IteratorMode::Start)
+        {
+            let (key, value) = entry?;
+            f(Cid::try_from(&key[..])?, value.into_vec())?;
+        }
+        Ok(())
+    }
+}
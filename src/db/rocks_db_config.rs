@@ -0,0 +1,26 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use serde::{Deserialize, Serialize};
+
+/// `RocksDb` configuration exposed in Forest.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(default)]
+pub struct RocksDbConfig {
+    pub enable_statistics: bool,
+    /// Maximum number of open files, or `-1` for no limit.
+    pub max_open_files: i32,
+    /// Size of the memory tables, in bytes.
+    pub write_buffer_size: usize,
+}
+
+impl Default for RocksDbConfig {
+    fn default() -> Self {
+        Self {
+            enable_statistics: false,
+            max_open_files: -1,
+            write_buffer_size: 256 * 1024 * 1024,
+        }
+    }
+}
@@ -2,3 +2,5 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 pub(in crate::db) mod parity;
+#[cfg(feature = "rocksdb")]
+pub(in crate::db) mod rocks;
@@ -0,0 +1,43 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::ops::Deref;
+
+use crate::db::{rocks_db::RocksDb, rocks_db_config::RocksDbConfig};
+
+/// Temporary, self-cleaning RocksDB
+pub struct TempRocksDB {
+    db: Option<RocksDb>,
+    _dir: tempfile::TempDir, // kept for cleaning up during Drop
+}
+
+impl TempRocksDB {
+    /// Creates a new DB in a temporary path that gets wiped out when the
+    /// variable gets out of scope.
+    pub fn new() -> TempRocksDB {
+        let dir = tempfile::Builder::new()
+            .tempdir()
+            .expect("Failed to create temporary path for db.");
+        let path = dir.path().join("rocksdb");
+        let config = RocksDbConfig::default();
+
+        TempRocksDB {
+            db: Some(RocksDb::open(path, &config).unwrap()),
+            _dir: dir,
+        }
+    }
+}
+
+impl Deref for TempRocksDB {
+    type Target = RocksDb;
+
+    fn deref(&self) -> &Self::Target {
+        self.db.as_ref().unwrap()
+    }
+}
+
+impl AsRef<RocksDb> for TempRocksDB {
+    fn as_ref(&self) -> &RocksDb {
+        self.db.as_ref().unwrap()
+    }
+}
@@ -23,6 +23,12 @@ fn mem_db_exists() {
     subtests::exists(&db);
 }
 
+#[test]
+fn mem_db_delete() {
+    let db = MemoryDB::default();
+    subtests::delete(&db);
+}
+
 #[test]
 fn mem_db_does_not_exist() {
     let db = MemoryDB::default();
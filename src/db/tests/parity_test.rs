@@ -21,6 +21,12 @@ fn db_exists() {
     subtests::exists(&*db);
 }
 
+#[test]
+fn db_delete() {
+    let db = TempParityDB::new();
+    subtests::delete(&*db);
+}
+
 #[test]
 fn db_does_not_exist() {
     let db = TempParityDB::new();
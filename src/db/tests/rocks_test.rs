@@ -0,0 +1,76 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{db_utils::rocks::TempRocksDB, subtests};
+use crate::db::{DbBackend, GarbageCollectable};
+use cid::multihash::{Code::Blake2b256, MultihashDigest};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::{DAG_CBOR, IPLD_RAW};
+
+#[test]
+fn db_write() {
+    let db = TempRocksDB::new();
+    subtests::write_bin(&*db);
+}
+
+#[test]
+fn db_read() {
+    let db = TempRocksDB::new();
+    subtests::read_bin(&*db);
+}
+
+#[test]
+fn db_exists() {
+    let db = TempRocksDB::new();
+    subtests::exists(&*db);
+}
+
+#[test]
+fn db_delete() {
+    let db = TempRocksDB::new();
+    subtests::delete(&*db);
+}
+
+#[test]
+fn db_does_not_exist() {
+    let db = TempRocksDB::new();
+    subtests::does_not_exist(&*db);
+}
+
+#[test]
+fn db_write_read_obj() {
+    let db = TempRocksDB::new();
+    subtests::write_read_obj(&*db);
+}
+
+#[test]
+fn db_blocks_garbage_collectable() {
+    let db = TempRocksDB::new();
+    let data = [b"Cthulhu".to_vec(), b"Dagon".to_vec()];
+    let cids = [
+        Cid::new_v1(DAG_CBOR, Blake2b256.digest(&data[0])),
+        Cid::new_v1(IPLD_RAW, Blake2b256.digest(&data[1])),
+    ];
+    for (cid, data) in cids.iter().zip(&data) {
+        db.put_keyed(cid, data).unwrap();
+        assert_eq!(&Blockstore::get(&*db, cid).unwrap().unwrap(), data);
+    }
+
+    let mut blocks = vec![];
+    db.for_each_block(&mut |cid, _| {
+        blocks.push(cid);
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(blocks.len(), cids.len());
+
+    let keys = db.get_keys().unwrap();
+    assert_eq!(keys.len(), cids.len());
+    let removed_bytes = db.remove_keys(keys).unwrap();
+    assert_eq!(
+        removed_bytes,
+        data.iter().map(|d| d.len() as u64).sum::<u64>()
+    );
+    assert!(db.get_keys().unwrap().is_empty());
+}
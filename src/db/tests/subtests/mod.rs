@@ -54,6 +54,19 @@ where
     assert!(res);
 }
 
+pub fn delete<DB>(db: &DB)
+where
+    DB: SettingsStore,
+{
+    let key = "0";
+    let value = [1];
+    db.write_bin(key, &value).unwrap();
+    db.delete(key).unwrap();
+    assert!(!db.exists(key).unwrap());
+    // Deleting a missing key is a no-op.
+    db.delete(key).unwrap();
+}
+
 pub fn does_not_exist<DB>(db: &DB)
 where
     DB: SettingsStore,
@@ -0,0 +1,608 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Ethereum transactions equivalent to messages sent from delegated (`f410`)
+//! addresses. Such messages are not signed over their CID but over the RLP
+//! encoding of the equivalent unsigned transaction, which has to be rebuilt
+//! to verify or produce their signatures.
+
+use crate::message::SignedMessage;
+use crate::shim::{
+    address::{Address, Payload},
+    crypto::{recover_delegated_address, verify_delegated_sig, Signature, SignatureType},
+    econ::TokenAmount,
+    message::{Message, MethodNum},
+};
+use anyhow::{bail, ensure, Context as _};
+use fvm_ipld_encoding::{BytesDe, BytesSer, RawBytes};
+use num_bigint::{BigInt, Sign};
+use num_traits::Zero as _;
+use rlp::{Rlp, RlpStream};
+
+pub type EthChainId = u64;
+
+/// An Ethereum address, i.e. the last 20 bytes of the Keccak-256 hash of a
+/// public key.
+pub type EthAddress = [u8; ETH_ADDRESS_LEN];
+
+pub const ETH_ADDRESS_LEN: usize = 20;
+
+/// Namespace of `f410` addresses, i.e. the actor ID of the Ethereum Address
+/// Manager.
+pub const EAM_NAMESPACE: u64 = 10;
+
+/// Method number of `InvokeContract` on EVM actors.
+pub const EVM_INVOKE_CONTRACT_METHOD: MethodNum = 3844450837;
+
+/// Method number of `CreateExternal` on the Ethereum Address Manager actor.
+pub const EAM_CREATE_EXTERNAL_METHOD: MethodNum = 4;
+
+/// Marker byte prepended to the signature of legacy (EIP-155) transactions,
+/// telling them apart from 65-byte EIP-1559 signatures.
+pub const LEGACY_TX_SIGNATURE_PREFIX: u8 = 0x01;
+
+/// Type byte prepended to the RLP encoding of EIP-1559 transactions.
+const EIP_1559_TX_TYPE: u8 = 0x02;
+
+/// Length of a recoverable secp256k1 signature: `r || s || v`.
+const RECOVERABLE_SIGNATURE_LEN: usize = 65;
+
+/// Returns the Ethereum address of `addr`: the subaddress of `f410` addresses,
+/// or the masked ID address (`0xff`, zeros, then the big-endian actor ID) of
+/// ID addresses.
+pub fn eth_address_from_filecoin_address(addr: &Address) -> anyhow::Result<EthAddress> {
+    match addr.payload() {
+        Payload::ID(id) => {
+            let mut eth_addr = [0; ETH_ADDRESS_LEN];
+            eth_addr[0] = 0xff;
+            eth_addr[12..].copy_from_slice(&id.to_be_bytes());
+            Ok(eth_addr)
+        }
+        Payload::Delegated(delegated) if delegated.namespace() == EAM_NAMESPACE => delegated
+            .subaddress()
+            .try_into()
+            .with_context(|| format!("invalid Ethereum address length in {addr}")),
+        _ => bail!("cannot convert {addr} to an Ethereum address"),
+    }
+}
+
+/// Returns the Filecoin address of an Ethereum address: the ID address of
+/// masked ID addresses, the `f410` address otherwise.
+pub fn filecoin_address_from_eth_address(eth_addr: &EthAddress) -> anyhow::Result<Address> {
+    match eth_addr.split_at(12) {
+        ([0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], id) => Ok(Address::new_id(u64::from_be_bytes(
+            id.try_into().expect("masked ID addresses end with 8 bytes"),
+        ))),
+        _ => Ok(Address::new_delegated(EAM_NAMESPACE, eth_addr)?),
+    }
+}
+
+/// Fields shared by every transaction type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EthTxArgs {
+    pub nonce: u64,
+    /// `None` for contract creations.
+    pub to: Option<EthAddress>,
+    pub value: BigInt,
+    pub gas_limit: u64,
+    pub input: Vec<u8>,
+}
+
+impl EthTxArgs {
+    /// Builds the transaction fields of a message sent from an `f410`
+    /// address, which must either invoke an EVM contract or create one
+    /// through the Ethereum Address Manager.
+    pub fn from_message(msg: &Message) -> anyhow::Result<Self> {
+        match msg.from.payload() {
+            Payload::Delegated(delegated) if delegated.namespace() == EAM_NAMESPACE => {}
+            _ => bail!("sender {} is not an Ethereum address", msg.from),
+        }
+        let to = if msg.to == Address::ETHEREUM_ACCOUNT_MANAGER_ACTOR
+            && msg.method_num == EAM_CREATE_EXTERNAL_METHOD
+        {
+            None
+        } else if msg.method_num == EVM_INVOKE_CONTRACT_METHOD {
+            Some(eth_address_from_filecoin_address(&msg.to)?)
+        } else {
+            bail!(
+                "invalid method {} for a message sent from an Ethereum address",
+                msg.method_num
+            )
+        };
+        let input = if msg.params.bytes().is_empty() {
+            vec![]
+        } else {
+            let BytesDe(input) = fvm_ipld_encoding::from_slice(msg.params.bytes())
+                .context("failed to decode message params as bytes")?;
+            input
+        };
+        Ok(Self {
+            nonce: msg.sequence,
+            to,
+            value: msg.value.atto().clone(),
+            gas_limit: msg.gas_limit,
+            input,
+        })
+    }
+}
+
+/// An unsigned Ethereum transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EthTx {
+    /// Dynamic fee transaction, see <https://eips.ethereum.org/EIPS/eip-1559>.
+    Eip1559 {
+        chain_id: EthChainId,
+        args: EthTxArgs,
+        max_fee_per_gas: BigInt,
+        max_priority_fee_per_gas: BigInt,
+    },
+    /// Legacy transaction with replay protection, see
+    /// <https://eips.ethereum.org/EIPS/eip-155>.
+    Eip155 {
+        chain_id: EthChainId,
+        args: EthTxArgs,
+        gas_price: BigInt,
+    },
+}
+
+impl EthTx {
+    /// Builds the EIP-1559 transaction equivalent to `msg`. This is the
+    /// transaction new messages from delegated keys are signed as.
+    pub fn new_eip1559(chain_id: EthChainId, msg: &Message) -> anyhow::Result<Self> {
+        Ok(Self::Eip1559 {
+            chain_id,
+            args: EthTxArgs::from_message(msg)?,
+            max_fee_per_gas: msg.gas_fee_cap.atto().clone(),
+            max_priority_fee_per_gas: msg.gas_premium.atto().clone(),
+        })
+    }
+
+    /// Builds the EIP-155 transaction equivalent to `msg`. Legacy
+    /// transactions have a single gas price, so the gas fee cap and premium
+    /// must be equal.
+    pub fn new_eip155(chain_id: EthChainId, msg: &Message) -> anyhow::Result<Self> {
+        ensure!(
+            msg.gas_fee_cap == msg.gas_premium,
+            "legacy transactions must have equal gas fee cap and gas premium"
+        );
+        Ok(Self::Eip155 {
+            chain_id,
+            args: EthTxArgs::from_message(msg)?,
+            gas_price: msg.gas_fee_cap.atto().clone(),
+        })
+    }
+
+    /// Rebuilds the transaction a delegated message was signed as. Its type
+    /// is told by the signature: EIP-155 ones start with
+    /// [`LEGACY_TX_SIGNATURE_PREFIX`], EIP-1559 ones are plain 65-byte
+    /// signatures.
+    pub fn from_signed_message(
+        chain_id: EthChainId,
+        msg: &Message,
+        signature: &Signature,
+    ) -> anyhow::Result<Self> {
+        ensure!(
+            signature.signature_type() == SignatureType::Delegated,
+            "expected a delegated signature, got {}",
+            signature.signature_type()
+        );
+        let bytes = signature.bytes();
+        if bytes.len() > RECOVERABLE_SIGNATURE_LEN && bytes[0] == LEGACY_TX_SIGNATURE_PREFIX {
+            Self::new_eip155(chain_id, msg)
+        } else {
+            Self::new_eip1559(chain_id, msg)
+        }
+    }
+
+    /// Decodes a signed EIP-1559 transaction, as submitted through
+    /// `eth_sendRawTransaction`: `0x02 || rlp([chain_id, nonce,
+    /// max_priority_fee_per_gas, max_fee_per_gas, gas_limit, to, value, input,
+    /// access_list, v, r, s])`.
+    pub fn decode_signed_eip1559(raw: &[u8]) -> anyhow::Result<(Self, Signature)> {
+        let payload = raw
+            .strip_prefix(&[EIP_1559_TX_TYPE])
+            .context("only EIP-1559 transactions are supported")?;
+        let rlp = Rlp::new(payload);
+        ensure!(
+            rlp.item_count()? == 12,
+            "invalid EIP-1559 transaction, expected 12 fields, got {}",
+            rlp.item_count()?
+        );
+        ensure!(
+            rlp.at(8)?.item_count()? == 0,
+            "access lists are not supported"
+        );
+        let to: Vec<u8> = rlp.val_at(5)?;
+        let to = if to.is_empty() {
+            None
+        } else {
+            Some(
+                EthAddress::try_from(to.as_slice())
+                    .with_context(|| format!("invalid recipient address length {}", to.len()))?,
+            )
+        };
+        let tx = Self::Eip1559 {
+            chain_id: rlp.val_at(0)?,
+            args: EthTxArgs {
+                nonce: rlp.val_at(1)?,
+                to,
+                value: bigint_at(&rlp, 6)?,
+                gas_limit: rlp.val_at(4)?,
+                input: rlp.val_at(7)?,
+            },
+            max_priority_fee_per_gas: bigint_at(&rlp, 2)?,
+            max_fee_per_gas: bigint_at(&rlp, 3)?,
+        };
+
+        let v: u8 = rlp.val_at(9)?;
+        ensure!(v <= 1, "invalid EIP-1559 signature recovery id {v}");
+        let mut signature = Vec::with_capacity(RECOVERABLE_SIGNATURE_LEN);
+        signature.extend_from_slice(&left_pad_32(&rlp.val_at::<Vec<u8>>(10)?)?);
+        signature.extend_from_slice(&left_pad_32(&rlp.val_at::<Vec<u8>>(11)?)?);
+        signature.push(v);
+        Ok((tx, Signature::new(SignatureType::Delegated, signature)))
+    }
+
+    pub fn chain_id(&self) -> EthChainId {
+        match self {
+            Self::Eip1559 { chain_id, .. } | Self::Eip155 { chain_id, .. } => *chain_id,
+        }
+    }
+
+    pub fn args(&self) -> &EthTxArgs {
+        match self {
+            Self::Eip1559 { args, .. } | Self::Eip155 { args, .. } => args,
+        }
+    }
+
+    /// Converts the transaction into the equivalent message sent by `from`:
+    /// an `InvokeContract` call, or a `CreateExternal` call to the Ethereum
+    /// Address Manager for contract creations.
+    pub fn to_message(&self, from: Address) -> anyhow::Result<Message> {
+        let args = self.args();
+        let (to, method_num, params) = match &args.to {
+            Some(to) => (
+                filecoin_address_from_eth_address(to)?,
+                EVM_INVOKE_CONTRACT_METHOD,
+                if args.input.is_empty() {
+                    RawBytes::default()
+                } else {
+                    RawBytes::serialize(BytesSer(&args.input))?
+                },
+            ),
+            None => (
+                Address::ETHEREUM_ACCOUNT_MANAGER_ACTOR,
+                EAM_CREATE_EXTERNAL_METHOD,
+                RawBytes::serialize(BytesSer(&args.input))?,
+            ),
+        };
+        let (gas_fee_cap, gas_premium) = match self {
+            Self::Eip1559 {
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+                ..
+            } => (max_fee_per_gas, max_priority_fee_per_gas),
+            Self::Eip155 { gas_price, .. } => (gas_price, gas_price),
+        };
+        Ok(Message {
+            version: 0,
+            from,
+            to,
+            sequence: args.nonce,
+            value: TokenAmount::from_atto(args.value.clone()),
+            method_num,
+            params,
+            gas_limit: args.gas_limit,
+            gas_fee_cap: TokenAmount::from_atto(gas_fee_cap.clone()),
+            gas_premium: TokenAmount::from_atto(gas_premium.clone()),
+        })
+    }
+
+    /// Converts the transaction into a message signed with `signature`, sent
+    /// by the `f410` address of the key that produced it.
+    pub fn into_signed_message(self, signature: Signature) -> anyhow::Result<SignedMessage> {
+        let from = self.recover_sender(&signature)?;
+        Ok(SignedMessage::new_unchecked(
+            self.to_message(from)?,
+            signature,
+        ))
+    }
+
+    /// Appends the fields common to the signed and unsigned encodings.
+    fn append_fields(&self, stream: &mut RlpStream) {
+        match self {
+            Self::Eip1559 {
+                chain_id,
+                args,
+                max_fee_per_gas,
+                max_priority_fee_per_gas,
+            } => {
+                stream.append(chain_id);
+                stream.append(&args.nonce);
+                stream.append(&format_bigint(max_priority_fee_per_gas));
+                stream.append(&format_bigint(max_fee_per_gas));
+                stream.append(&args.gas_limit);
+                append_to(stream, &args.to);
+                stream.append(&format_bigint(&args.value));
+                stream.append(&args.input);
+                // Empty access list
+                stream.begin_list(0);
+            }
+            Self::Eip155 {
+                args, gas_price, ..
+            } => {
+                stream.append(&args.nonce);
+                stream.append(&format_bigint(gas_price));
+                stream.append(&args.gas_limit);
+                append_to(stream, &args.to);
+                stream.append(&format_bigint(&args.value));
+                stream.append(&args.input);
+            }
+        }
+    }
+
+    /// Returns the RLP encoding of the unsigned transaction, which is what
+    /// gets signed.
+    pub fn rlp_unsigned_message(&self) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(9);
+        self.append_fields(&mut stream);
+        match self {
+            Self::Eip1559 { .. } => [&[EIP_1559_TX_TYPE], stream.as_raw()].concat(),
+            Self::Eip155 { chain_id, .. } => {
+                stream.append(chain_id);
+                stream.append(&0u64);
+                stream.append(&0u64);
+                stream.out().to_vec()
+            }
+        }
+    }
+
+    /// Returns the RLP encoding of the transaction signed with `signature`,
+    /// whose Keccak-256 hash is the transaction hash.
+    pub fn rlp_signed_message(&self, signature: &Signature) -> anyhow::Result<Vec<u8>> {
+        let signature = self.recoverable_signature(signature.bytes())?;
+        let (r, s) = (&signature[..32], &signature[32..64]);
+        let recovery_id = u64::from(signature[64]);
+        match self {
+            Self::Eip1559 { .. } => {
+                let mut stream = RlpStream::new_list(12);
+                self.append_fields(&mut stream);
+                stream.append(&recovery_id);
+                stream.append(&trim_leading_zeros(r));
+                stream.append(&trim_leading_zeros(s));
+                Ok([&[EIP_1559_TX_TYPE], stream.as_raw()].concat())
+            }
+            Self::Eip155 { chain_id, .. } => {
+                let mut stream = RlpStream::new_list(9);
+                self.append_fields(&mut stream);
+                stream.append(&(chain_id * 2 + 35 + recovery_id));
+                stream.append(&trim_leading_zeros(r));
+                stream.append(&trim_leading_zeros(s));
+                Ok(stream.out().to_vec())
+            }
+        }
+    }
+
+    /// Converts a signature of this transaction to the 65-byte `r || s || v`
+    /// form, with `v` being the recovery identifier. EIP-155 signatures carry
+    /// `v = chain_id * 2 + 35 + recovery_id` instead.
+    fn recoverable_signature(&self, signature: &[u8]) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Self::Eip1559 { .. } => {
+                ensure!(
+                    signature.len() == RECOVERABLE_SIGNATURE_LEN,
+                    "invalid EIP-1559 signature length {}",
+                    signature.len()
+                );
+                Ok(signature.to_vec())
+            }
+            Self::Eip155 { chain_id, .. } => {
+                let signature = signature
+                    .strip_prefix(&[LEGACY_TX_SIGNATURE_PREFIX])
+                    .context("missing legacy transaction signature prefix")?;
+                ensure!(
+                    signature.len() > 64 && signature.len() <= 64 + 8,
+                    "invalid EIP-155 signature length {}",
+                    signature.len()
+                );
+                let (rs, v) = signature.split_at(64);
+                let mut v_bytes = [0; 8];
+                v_bytes[8 - v.len()..].copy_from_slice(v);
+                let v = u64::from_be_bytes(v_bytes);
+                let recovery_id = chain_id
+                    .checked_mul(2)
+                    .and_then(|id| id.checked_add(35))
+                    .and_then(|offset| v.checked_sub(offset))
+                    .filter(|id| *id <= 1)
+                    .with_context(|| {
+                        format!("invalid EIP-155 signature value {v} for chain {chain_id}")
+                    })?;
+                Ok([rs, &[recovery_id as u8]].concat())
+            }
+        }
+    }
+
+    /// Verifies that `signature` of this transaction was produced by the key
+    /// behind the `f410` address `from`.
+    pub fn verify_signature(&self, signature: &Signature, from: &Address) -> anyhow::Result<()> {
+        let signature = self.recoverable_signature(signature.bytes())?;
+        verify_delegated_sig(&signature, &self.rlp_unsigned_message(), from)
+            .map_err(anyhow::Error::msg)
+    }
+
+    /// Returns the `f410` address of the key that produced `signature` of
+    /// this transaction.
+    pub fn recover_sender(&self, signature: &Signature) -> anyhow::Result<Address> {
+        let signature = self.recoverable_signature(signature.bytes())?;
+        recover_delegated_address(&signature, &self.rlp_unsigned_message())
+            .map_err(anyhow::Error::msg)
+    }
+}
+
+/// RLP encodes integers as big-endian bytes without leading zeros.
+fn format_bigint(n: &BigInt) -> Vec<u8> {
+    if n.is_zero() {
+        vec![]
+    } else {
+        n.to_bytes_be().1
+    }
+}
+
+fn trim_leading_zeros(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+fn left_pad_32(bytes: &[u8]) -> anyhow::Result<[u8; 32]> {
+    ensure!(
+        bytes.len() <= 32,
+        "invalid signature value length {}",
+        bytes.len()
+    );
+    let mut padded = [0; 32];
+    padded[32 - bytes.len()..].copy_from_slice(bytes);
+    Ok(padded)
+}
+
+fn bigint_at(rlp: &Rlp, index: usize) -> anyhow::Result<BigInt> {
+    Ok(BigInt::from_bytes_be(
+        Sign::Plus,
+        &rlp.val_at::<Vec<u8>>(index)?,
+    ))
+}
+
+fn append_to(stream: &mut RlpStream, to: &Option<EthAddress>) {
+    match to {
+        Some(to) => stream.append(&to.to_vec()),
+        None => stream.append_empty_data(),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::key_management::{new_address, sign, to_public};
+
+    const CHAIN_ID: EthChainId = 314159;
+
+    fn delegated_key() -> (Vec<u8>, Address) {
+        let mut private_key = [0; 32];
+        private_key[31] = 1;
+        let public_key = to_public(SignatureType::Delegated, &private_key).unwrap();
+        let addr = new_address(SignatureType::Delegated, &public_key).unwrap();
+        (private_key.to_vec(), addr)
+    }
+
+    fn invoke_message(from: Address) -> Message {
+        Message {
+            from,
+            to: Address::new_id(1234),
+            sequence: 7,
+            value: TokenAmount::from_atto(42),
+            method_num: EVM_INVOKE_CONTRACT_METHOD,
+            params: RawBytes::serialize(BytesSer(&[0xde, 0xad])).unwrap(),
+            gas_limit: 1_000_000,
+            gas_fee_cap: TokenAmount::from_atto(200),
+            gas_premium: TokenAmount::from_atto(200),
+            ..Message::default()
+        }
+    }
+
+    #[test]
+    fn eth_address_conversions() {
+        let eth_addr = eth_address_from_filecoin_address(&Address::new_id(1234)).unwrap();
+        assert_eq!(
+            hex::encode(eth_addr),
+            "ff000000000000000000000000000000000004d2"
+        );
+
+        let (_, addr) = delegated_key();
+        let eth_addr = eth_address_from_filecoin_address(&addr).unwrap();
+        assert_eq!(
+            hex::encode(eth_addr),
+            "7e5f4552091a69125d5dfcb7b8c2659029395bdf"
+        );
+        assert_eq!(filecoin_address_from_eth_address(&eth_addr).unwrap(), addr);
+    }
+
+    #[test]
+    fn verify_eip1559_message() {
+        let (private_key, from) = delegated_key();
+        let msg = invoke_message(from);
+        let tx = EthTx::new_eip1559(CHAIN_ID, &msg).unwrap();
+        assert_eq!(tx.rlp_unsigned_message()[0], EIP_1559_TX_TYPE);
+
+        let sig = sign(
+            SignatureType::Delegated,
+            &private_key,
+            &tx.rlp_unsigned_message(),
+        )
+        .unwrap();
+        let smsg = SignedMessage::new_unchecked(msg, sig);
+        smsg.verify(CHAIN_ID).unwrap();
+        // Replaying the message on another chain must fail
+        assert!(smsg.verify(CHAIN_ID + 1).is_err());
+    }
+
+    #[test]
+    fn verify_eip155_message() {
+        let (private_key, from) = delegated_key();
+        let msg = invoke_message(from);
+        let tx = EthTx::new_eip155(CHAIN_ID, &msg).unwrap();
+
+        let sig = sign(
+            SignatureType::Delegated,
+            &private_key,
+            &tx.rlp_unsigned_message(),
+        )
+        .unwrap();
+        let (rs, recovery_id) = sig.bytes().split_at(64);
+        let v = CHAIN_ID * 2 + 35 + recovery_id[0] as u64;
+        let legacy_sig = Signature::new(
+            SignatureType::Delegated,
+            [&[LEGACY_TX_SIGNATURE_PREFIX], rs, &v.to_be_bytes()[5..]].concat(),
+        );
+        let smsg = SignedMessage::new_unchecked(msg, legacy_sig);
+        smsg.verify(CHAIN_ID).unwrap();
+        assert!(smsg.verify(CHAIN_ID + 1).is_err());
+    }
+
+    #[test]
+    fn decode_signed_eip1559_transaction() {
+        let (private_key, from) = delegated_key();
+        let msg = invoke_message(from);
+        let tx = EthTx::new_eip1559(CHAIN_ID, &msg).unwrap();
+        let sig = sign(
+            SignatureType::Delegated,
+            &private_key,
+            &tx.rlp_unsigned_message(),
+        )
+        .unwrap();
+        let raw = tx.rlp_signed_message(&sig).unwrap();
+
+        let (decoded, decoded_sig) = EthTx::decode_signed_eip1559(&raw).unwrap();
+        assert_eq!(decoded.chain_id(), CHAIN_ID);
+        assert_eq!(decoded_sig, sig);
+        assert_eq!(decoded.recover_sender(&decoded_sig).unwrap(), from);
+
+        let smsg = decoded.into_signed_message(decoded_sig).unwrap();
+        assert_eq!(smsg.message(), &msg);
+        smsg.verify(CHAIN_ID).unwrap();
+
+        assert!(EthTx::decode_signed_eip1559(&raw[1..]).is_err());
+    }
+
+    #[test]
+    fn reject_delegated_signature_from_other_key() {
+        let (_, from) = delegated_key();
+        let mut other_key = [0; 32];
+        other_key[31] = 2;
+        let data = b"forest";
+        let sig = sign(SignatureType::Delegated, &other_key, data).unwrap();
+        assert!(sig.verify(data, &from).is_err());
+
+        let (private_key, _) = delegated_key();
+        let sig = sign(SignatureType::Delegated, &private_key, data).unwrap();
+        sig.verify(data, &from).unwrap();
+    }
+}
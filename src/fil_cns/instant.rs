@@ -0,0 +1,108 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::{convert::Infallible, sync::Arc};
+
+use crate::blocks::{Block, Tipset};
+use crate::chain::{Scale, Weight};
+use crate::chain_sync::consensus::Consensus;
+use crate::state_manager::StateManager;
+use async_trait::async_trait;
+use fvm_ipld_blockstore::Blockstore;
+use nonempty::NonEmpty;
+
+use super::FilecoinConsensus;
+
+/// Testing consensus with instant finality, for devnets.
+///
+/// Blocks are only subject to the validations common to all consensus types,
+/// so they don't need to win an election, and the heaviest tipset only ever
+/// advances to its descendants: a head is never reverted. Tipsets are weighed
+/// as in Expected Consensus, since the block headers of the network carry
+/// those weights.
+#[derive(Debug, Default)]
+pub struct InstantConsensus;
+
+impl Scale for InstantConsensus {
+    fn weight<DB>(db: &Arc<DB>, ts: &Tipset) -> Result<Weight, anyhow::Error>
+    where
+        DB: Blockstore,
+    {
+        FilecoinConsensus::weight(db, ts)
+    }
+
+    fn is_heavier<DB>(
+        db: &Arc<DB>,
+        candidate: &Tipset,
+        head: &Tipset,
+    ) -> Result<bool, anyhow::Error>
+    where
+        DB: Blockstore,
+    {
+        if candidate.epoch() <= head.epoch() {
+            return Ok(false);
+        }
+        let ancestor = candidate
+            .clone()
+            .chain(db)
+            .find(|tipset| tipset.epoch() <= head.epoch());
+        Ok(ancestor.is_some_and(|tipset| tipset.key() == head.key()))
+    }
+}
+
+#[async_trait]
+impl Consensus for InstantConsensus {
+    type Error = Infallible;
+
+    async fn validate_block<DB>(
+        &self,
+        _state_manager: Arc<StateManager<DB>>,
+        _block: Arc<Block>,
+    ) -> Result<(), NonEmpty<Infallible>>
+    where
+        DB: Blockstore + Sync + Send + 'static,
+    {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{CachingBlockHeader, RawBlockHeader};
+    use crate::db::MemoryDB;
+    use crate::shim::clock::ChainEpoch;
+    use crate::utils::db::CborStoreExt;
+
+    fn tipset_child(db: &MemoryDB, parent: &Tipset, epoch: ChainEpoch, timestamp: u64) -> Tipset {
+        let tipset = Tipset::from(CachingBlockHeader::new(RawBlockHeader {
+            parents: parent.key().clone(),
+            epoch,
+            timestamp,
+            ..Default::default()
+        }));
+        db.put_cbor_default(tipset.block_headers().first()).unwrap();
+        tipset
+    }
+
+    #[test]
+    fn heads_are_never_reverted() {
+        let db = Arc::new(MemoryDB::default());
+        let genesis = Tipset::from(CachingBlockHeader::default());
+        db.put_cbor_default(genesis.block_headers().first())
+            .unwrap();
+        let epoch1 = tipset_child(&db, &genesis, 1, 1);
+        let epoch3 = tipset_child(&db, &epoch1, 3, 2);
+        let fork2 = tipset_child(&db, &genesis, 2, 3);
+        let fork4 = tipset_child(&db, &fork2, 4, 4);
+
+        let is_heavier = |candidate: &Tipset, head: &Tipset| {
+            InstantConsensus::is_heavier(&db, candidate, head).unwrap()
+        };
+        assert!(is_heavier(&epoch1, &genesis));
+        assert!(is_heavier(&epoch3, &epoch1));
+        assert!(!is_heavier(&epoch1, &epoch3));
+        assert!(!is_heavier(&fork4, &epoch3));
+        assert!(!is_heavier(&fork4, &epoch1));
+    }
+}
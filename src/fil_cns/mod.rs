@@ -4,18 +4,26 @@ use std::{fmt::Debug, sync::Arc};
 
 use crate::beacon::BeaconSchedule;
 use crate::blocks::{Block, Tipset};
-use crate::chain::{Error as ChainStoreError, Weight};
+use crate::chain::{Error as ChainStoreError, Scale, Weight};
+use crate::chain_sync::consensus::Consensus;
+use crate::networks::ConsensusKind;
 use crate::state_manager::{Error as StateManagerError, StateManager};
 use anyhow::anyhow;
+use async_trait::async_trait;
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::Error as ForestEncodingError;
 use nonempty::NonEmpty;
 use thiserror::Error;
 
+mod instant;
 mod metrics;
+mod proposer;
 mod validation;
 mod weight;
 
+pub use instant::InstantConsensus;
+pub use proposer::{create_block, FilecoinProposer};
+
 #[derive(Debug, Error)]
 pub enum FilecoinConsensusError {
     #[error("Block must have an election proof included in tipset")]
@@ -69,12 +77,29 @@ impl FilecoinConsensus {
     pub fn new(beacon: Arc<BeaconSchedule>) -> Self {
         Self { beacon }
     }
+}
 
-    pub async fn validate_block<DB: Blockstore + Sync + Send + 'static>(
+impl Scale for FilecoinConsensus {
+    fn weight<DB>(db: &Arc<DB>, ts: &Tipset) -> Result<Weight, anyhow::Error>
+    where
+        DB: Blockstore,
+    {
+        weight::weight(db, ts).map_err(|s| anyhow!(s))
+    }
+}
+
+#[async_trait]
+impl Consensus for FilecoinConsensus {
+    type Error = FilecoinConsensusError;
+
+    async fn validate_block<DB>(
         &self,
         state_manager: Arc<StateManager<DB>>,
         block: Arc<Block>,
-    ) -> Result<(), NonEmpty<FilecoinConsensusError>> {
+    ) -> Result<(), NonEmpty<FilecoinConsensusError>>
+    where
+        DB: Blockstore + Sync + Send + 'static,
+    {
         validation::validate_block::<_>(state_manager, self.beacon.clone(), block).await
     }
 }
@@ -87,9 +112,30 @@ impl Debug for FilecoinConsensus {
     }
 }
 
-pub fn weight<DB>(db: &DB, ts: &Tipset) -> Result<Weight, anyhow::Error>
+/// Returns the weight of the tipset under the given consensus.
+pub fn weight<DB>(consensus: ConsensusKind, db: &DB, ts: &Tipset) -> Result<Weight, anyhow::Error>
 where
     DB: Blockstore,
 {
-    weight::weight(&Arc::new(db), ts).map_err(|s| anyhow!(s))
+    match consensus {
+        ConsensusKind::Ec => FilecoinConsensus::weight(&Arc::new(db), ts),
+        ConsensusKind::Instant => InstantConsensus::weight(&Arc::new(db), ts),
+    }
+}
+
+/// Returns whether `candidate` should replace `head` as the heaviest tipset
+/// under the given consensus.
+pub fn is_heavier<DB>(
+    consensus: ConsensusKind,
+    db: &DB,
+    candidate: &Tipset,
+    head: &Tipset,
+) -> Result<bool, anyhow::Error>
+where
+    DB: Blockstore,
+{
+    match consensus {
+        ConsensusKind::Ec => FilecoinConsensus::is_heavier(&Arc::new(db), candidate, head),
+        ConsensusKind::Instant => InstantConsensus::is_heavier(&Arc::new(db), candidate, head),
+    }
 }
@@ -0,0 +1,561 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::beacon::{BeaconEntry, BeaconSchedule};
+use crate::blocks::{
+    Block, CachingBlockHeader, ElectionProof, GossipBlock, RawBlockHeader, Ticket, Tipset, VRFProof,
+};
+use crate::chain::{compute_base_fee, ChainStore};
+use crate::chain_sync::consensus::{MessagePoolApi, Proposer};
+use crate::chain_sync::TipsetValidator;
+use crate::key_management::{find_key, sign, Key, KeyStore};
+use crate::libp2p::{pubsub_block_topic, NetworkMessage, Topic};
+use crate::networks::Height;
+use crate::rpc_api::data_types::BlockTemplate;
+use crate::shim::{
+    address::Address,
+    clock::ChainEpoch,
+    crypto::{Signature, SignatureType, TICKET_RANDOMNESS_LOOKBACK},
+    econ::TokenAmount,
+};
+use crate::state_manager::{chain_rand::draw_randomness, StateManager};
+use crate::utils::cid::CidCborExt as _;
+use anyhow::Context as _;
+use async_trait::async_trait;
+use bls_signatures::Serialize as _;
+use cid::Cid;
+use fil_actors_shared::v10::runtime::DomainSeparationTag;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::to_vec;
+use num::BigInt;
+use tokio::{sync::RwLock, task::JoinSet};
+use tracing::{debug, info, warn};
+
+/// Compressed BLS signature of the point at infinity, the aggregate of no
+/// signatures.
+const EMPTY_BLS_AGGREGATE: [u8; 96] = {
+    let mut bytes = [0; 96];
+    bytes[0] = 0xc0;
+    bytes
+};
+
+/// Mines blocks with a miner actor of a local devnet.
+///
+/// Blocks are mined on the heaviest tipset, in the rounds the miner wins an
+/// election against the power table of the lookback tipset. They carry no
+/// winning PoSt proof, so they are only valid on devnets synced with the
+/// instant consensus.
+pub struct FilecoinProposer {
+    miner: Address,
+    keystore: Arc<RwLock<KeyStore>>,
+    beacon: Arc<BeaconSchedule>,
+    network_send: flume::Sender<NetworkMessage>,
+    /// Network name of the `Gossipsub` topics.
+    network_name: String,
+}
+
+impl FilecoinProposer {
+    pub fn new(
+        miner: Address,
+        keystore: Arc<RwLock<KeyStore>>,
+        beacon: Arc<BeaconSchedule>,
+        network_send: flume::Sender<NetworkMessage>,
+        network_name: String,
+    ) -> Self {
+        Self {
+            miner,
+            keystore,
+            beacon,
+            network_send,
+            network_name,
+        }
+    }
+
+    async fn run<DB, MP>(
+        self,
+        state_manager: Arc<StateManager<DB>>,
+        mpool: Arc<MP>,
+    ) -> anyhow::Result<()>
+    where
+        DB: Blockstore + Sync + Send + 'static,
+        MP: MessagePoolApi + Sync + Send + 'static,
+    {
+        let block_delay = state_manager.chain_config().block_delay_secs as u64;
+        let mut last_round = 0;
+        loop {
+            let base = state_manager.chain_store().heaviest_tipset();
+            // Rounds that elapsed without a block are null rounds.
+            let elapsed = now().saturating_sub(base.min_timestamp()) / block_delay;
+            let round = (base.epoch() + 1)
+                .max(base.epoch() + elapsed as ChainEpoch)
+                .max(last_round + 1);
+            let timestamp = base.min_timestamp() + block_delay * (round - base.epoch()) as u64;
+            tokio::time::sleep(Duration::from_secs(timestamp.saturating_sub(now()))).await;
+            last_round = round;
+
+            // Mine on the blocks received while waiting, if any.
+            if state_manager.chain_store().heaviest_tipset().key() != base.key() {
+                continue;
+            }
+            match self
+                .mine(&state_manager, mpool.as_ref(), base, round, timestamp)
+                .await
+            {
+                Ok(Some(block)) => {
+                    info!("Mined block {} at epoch {round}", block.header().cid());
+                    self.submit(state_manager.chain_store(), block).await?;
+                }
+                Ok(None) => debug!("Miner {} wasn't elected at epoch {round}", self.miner),
+                Err(e) => warn!("Failed to mine a block at epoch {round}: {e}"),
+            }
+        }
+    }
+
+    /// Returns the block mined on `base` at `round`, if the miner is elected.
+    async fn mine<DB, MP>(
+        &self,
+        state_manager: &Arc<StateManager<DB>>,
+        mpool: &MP,
+        base: Arc<Tipset>,
+        round: ChainEpoch,
+        timestamp: u64,
+    ) -> anyhow::Result<Option<Block>>
+    where
+        DB: Blockstore + Sync + Send + 'static,
+        MP: MessagePoolApi,
+    {
+        let chain_store = state_manager.chain_store();
+        let chain_config = state_manager.chain_config();
+        let (lookback_tipset, lookback_state) = ChainStore::get_lookback_tipset_for_round(
+            chain_store.chain_index.clone(),
+            chain_config.clone(),
+            base.clone(),
+            round,
+        )?;
+        if !state_manager.eligible_to_mine(&self.miner, &base, &lookback_tipset)? {
+            return Ok(None);
+        }
+        let worker = state_manager.get_miner_work_addr(lookback_state, &self.miner)?;
+        let key = find_key(&worker, &*self.keystore.read().await)
+            .with_context(|| format!("worker key {worker} isn't in the wallet"))?;
+
+        let prev_beacon = chain_store.chain_index.latest_beacon_entry(&base)?;
+        let beacon_entries = self
+            .beacon
+            .beacon_entries_for_block(
+                state_manager.get_network_version(base.epoch()),
+                round,
+                base.epoch(),
+                &prev_beacon,
+            )
+            .await?;
+        let beacon = beacon_entries.last().unwrap_or(&prev_beacon);
+        let (miner_power, total_power) = state_manager
+            .get_power(&lookback_state, Some(&self.miner))?
+            .context("miner has no power")?;
+        let Some(election_proof) = self.elect(
+            &key,
+            beacon,
+            round,
+            &miner_power.quality_adj_power,
+            &total_power.quality_adj_power,
+        )?
+        else {
+            return Ok(None);
+        };
+        let ticket = self.ticket(
+            &key,
+            beacon,
+            &base,
+            round,
+            chain_config.epoch(Height::Smoke),
+        )?;
+
+        let template = BlockTemplate {
+            miner: self.miner,
+            parents: base.key().clone(),
+            ticket: Some(ticket),
+            eproof: Some(election_proof),
+            beacon_values: beacon_entries,
+            messages: mpool
+                .select_signed(state_manager, &base)?
+                .into_iter()
+                .map(|message| message.into_owned())
+                .collect(),
+            epoch: round,
+            timestamp,
+            winning_post_proof: vec![],
+        };
+        Ok(Some(
+            create_block(state_manager, &self.keystore, template).await?,
+        ))
+    }
+
+    /// Returns the election proof of the miner at `round`, if it wins the
+    /// election with `miner_power` out of `total_power`.
+    fn elect(
+        &self,
+        key: &Key,
+        beacon: &BeaconEntry,
+        round: ChainEpoch,
+        miner_power: &BigInt,
+        total_power: &BigInt,
+    ) -> anyhow::Result<Option<ElectionProof>> {
+        let election_rand = draw_randomness(
+            beacon.signature(),
+            DomainSeparationTag::ElectionProofProduction as i64,
+            round,
+            &to_vec(&self.miner)?,
+        )?;
+        let mut election_proof = ElectionProof {
+            win_count: 0,
+            vrfproof: vrf(key, &election_rand)?,
+        };
+        election_proof.win_count = election_proof.compute_win_count(miner_power, total_power);
+        Ok((election_proof.win_count >= 1).then_some(election_proof))
+    }
+
+    /// Returns the ticket of the block mined on `base` at `round`.
+    fn ticket(
+        &self,
+        key: &Key,
+        beacon: &BeaconEntry,
+        base: &Tipset,
+        round: ChainEpoch,
+        smoke_height: ChainEpoch,
+    ) -> anyhow::Result<Ticket> {
+        let mut ticket_buf = to_vec(&self.miner)?;
+        if round > smoke_height {
+            let min_ticket = base.min_ticket().context("base tipset has no ticket")?;
+            ticket_buf.extend_from_slice(min_ticket.vrfproof.as_bytes());
+        }
+        let ticket_rand = draw_randomness(
+            beacon.signature(),
+            DomainSeparationTag::TicketProduction as i64,
+            round - TICKET_RANDOMNESS_LOOKBACK,
+            &ticket_buf,
+        )?;
+        Ok(Ticket::new(vrf(key, &ticket_rand)?))
+    }
+
+    /// Adds the block to the chain, and publishes it.
+    async fn submit<DB: Blockstore>(
+        &self,
+        chain_store: &ChainStore<DB>,
+        block: Block,
+    ) -> anyhow::Result<()> {
+        block.persist(chain_store.blockstore())?;
+        chain_store.put_tipset(&Tipset::from(block.header().clone()))?;
+
+        let gossip_block = GossipBlock {
+            header: block.header().clone(),
+            bls_messages: block
+                .bls_msgs()
+                .iter()
+                .map(Cid::from_cbor_blake2b256)
+                .collect::<Result<_, _>>()?,
+            secpk_messages: block
+                .secp_msgs()
+                .iter()
+                .map(Cid::from_cbor_blake2b256)
+                .collect::<Result<_, _>>()?,
+        };
+        self.network_send
+            .send_async(NetworkMessage::PubsubMessage {
+                topic: Topic::new(pubsub_block_topic(&self.network_name)),
+                message: to_vec(&gossip_block)?,
+            })
+            .await
+            .context("network receiver dropped")
+    }
+}
+
+#[async_trait]
+impl Proposer for FilecoinProposer {
+    async fn spawn<DB, MP>(
+        self,
+        state_manager: Arc<StateManager<DB>>,
+        mpool: Arc<MP>,
+        services: &mut JoinSet<anyhow::Result<()>>,
+    ) -> anyhow::Result<()>
+    where
+        DB: Blockstore + Sync + Send + 'static,
+        MP: MessagePoolApi + Sync + Send + 'static,
+    {
+        info!("Mining blocks with miner {}", self.miner);
+        services.spawn(self.run(state_manager, mpool));
+        Ok(())
+    }
+}
+
+/// Assembles the block of `template` on its parents, and signs it with the key of the miner's
+/// worker.
+pub async fn create_block<DB>(
+    state_manager: &Arc<StateManager<DB>>,
+    keystore: &RwLock<KeyStore>,
+    template: BlockTemplate,
+) -> anyhow::Result<Block>
+where
+    DB: Blockstore + Sync + Send + 'static,
+{
+    let chain_store = state_manager.chain_store();
+    let chain_config = state_manager.chain_config();
+    let base = chain_store.load_required_tipset(&template.parents)?;
+    let (_, lookback_state) = ChainStore::get_lookback_tipset_for_round(
+        chain_store.chain_index.clone(),
+        chain_config.clone(),
+        base.clone(),
+        template.epoch,
+    )?;
+    let worker = state_manager.get_miner_work_addr(lookback_state, &template.miner)?;
+    let key = find_key(&worker, &*keystore.read().await)
+        .with_context(|| format!("worker key {worker} isn't in the wallet"))?;
+
+    let db = state_manager.blockstore();
+    let (state_root, message_receipts) = state_manager.tipset_state(&base).await?;
+    let parent = ParentValues {
+        weight: super::weight(chain_config.consensus, db, &base)?,
+        state_root,
+        message_receipts,
+        base_fee: compute_base_fee(db, &base, chain_config.epoch(Height::Smoke))?,
+    };
+    assemble_block(db, template, parent, &key)
+}
+
+/// Values of a block header computed from its parent tipset.
+struct ParentValues {
+    weight: BigInt,
+    state_root: Cid,
+    message_receipts: Cid,
+    base_fee: TokenAmount,
+}
+
+/// Assembles the block of `template` with the values computed from its parents, and signs it
+/// with `key`.
+fn assemble_block(
+    db: &impl Blockstore,
+    template: BlockTemplate,
+    parent: ParentValues,
+    key: &Key,
+) -> anyhow::Result<Block> {
+    // Messages
+    let (bls_messages, secp_messages): (Vec<_>, Vec<_>) = template
+        .messages
+        .into_iter()
+        .partition(|message| message.is_bls());
+    let bls_signatures = bls_messages
+        .iter()
+        .map(|message| bls_signatures::Signature::try_from(message.signature()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let bls_aggregate = if bls_signatures.is_empty() {
+        Signature::new_bls(EMPTY_BLS_AGGREGATE.to_vec())
+    } else {
+        Signature::new_bls(bls_signatures::aggregate(&bls_signatures)?.as_bytes())
+    };
+    let bls_messages: Vec<_> = bls_messages
+        .into_iter()
+        .map(|message| message.message)
+        .collect();
+
+    let mut header = RawBlockHeader {
+        miner_address: template.miner,
+        ticket: template.ticket,
+        election_proof: template.eproof,
+        beacon_entries: template.beacon_values,
+        winning_post_proof: template.winning_post_proof,
+        parents: template.parents,
+        weight: parent.weight,
+        epoch: template.epoch,
+        state_root: parent.state_root,
+        message_receipts: parent.message_receipts,
+        messages: TipsetValidator::compute_msg_root(db, &bls_messages, &secp_messages)?,
+        bls_aggregate: Some(bls_aggregate),
+        timestamp: template.timestamp,
+        signature: None,
+        fork_signal: 0,
+        parent_base_fee: parent.base_fee,
+    };
+    header.signature = Some(sign(
+        *key.key_info.key_type(),
+        key.key_info.private_key(),
+        &header.signing_bytes(),
+    )?);
+
+    Ok(Block {
+        header: CachingBlockHeader::new(header),
+        bls_messages,
+        secp_messages,
+    })
+}
+
+/// Returns the VRF proof of the randomness: the BLS signature of the worker.
+fn vrf(key: &Key, randomness: &[u8]) -> anyhow::Result<VRFProof> {
+    anyhow::ensure!(
+        *key.key_info.key_type() == SignatureType::Bls,
+        "worker key {} isn't a BLS key",
+        key.address
+    );
+    let signature = sign(SignatureType::Bls, key.key_info.private_key(), randomness)?;
+    Ok(VRFProof::new(signature.bytes().to_vec()))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Retrieved system time before UNIX epoch")
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::TipsetKey;
+    use crate::db::MemoryDB;
+    use crate::key_management::{generate_key, KeyStoreConfig};
+    use crate::utils::db::CborStoreExt as _;
+    use num_traits::Zero as _;
+
+    fn proposer(miner: Address) -> FilecoinProposer {
+        FilecoinProposer::new(
+            miner,
+            Arc::new(RwLock::new(KeyStore::new(KeyStoreConfig::Memory).unwrap())),
+            Arc::new(BeaconSchedule(vec![])),
+            flume::unbounded().0,
+            "devnet".into(),
+        )
+    }
+
+    #[test]
+    fn elected_with_power() {
+        let proposer = proposer(Address::new_id(1000));
+        let key = generate_key(SignatureType::Bls).unwrap();
+        let beacon = BeaconEntry::new(1, vec![7; 96]);
+        let total_power = BigInt::from(1u64 << 40);
+
+        // The expected number of wins per round is 5 with all the power.
+        let proofs = (1..=5)
+            .filter_map(|round| {
+                proposer
+                    .elect(&key, &beacon, round, &total_power, &total_power)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert!(!proofs.is_empty());
+        for proof in proofs {
+            assert!(proof.win_count >= 1);
+            assert_eq!(
+                proof.compute_win_count(&total_power, &total_power),
+                proof.win_count
+            );
+        }
+
+        for round in 1..=5 {
+            assert_eq!(
+                proposer
+                    .elect(&key, &beacon, round, &BigInt::zero(), &total_power)
+                    .unwrap(),
+                None
+            );
+        }
+
+        // The VRF proofs are BLS signatures.
+        let key = generate_key(SignatureType::Secp256k1).unwrap();
+        assert!(proposer
+            .elect(&key, &beacon, 1, &total_power, &total_power)
+            .is_err());
+    }
+
+    #[test]
+    fn tickets_include_the_min_ticket_after_smoke() {
+        let proposer = proposer(Address::new_id(1000));
+        let key = generate_key(SignatureType::Bls).unwrap();
+        let beacon = BeaconEntry::new(1, vec![7; 96]);
+        let smoke_height = 10;
+        let without_ticket = Tipset::from(CachingBlockHeader::default());
+        let with_ticket = Tipset::from(CachingBlockHeader::new(RawBlockHeader {
+            ticket: Some(Ticket::new(VRFProof::new(vec![1; 96]))),
+            ..Default::default()
+        }));
+
+        assert!(proposer
+            .ticket(&key, &beacon, &without_ticket, smoke_height, smoke_height)
+            .is_ok());
+        assert!(proposer
+            .ticket(
+                &key,
+                &beacon,
+                &without_ticket,
+                smoke_height + 1,
+                smoke_height
+            )
+            .is_err());
+        assert_ne!(
+            proposer
+                .ticket(&key, &beacon, &with_ticket, smoke_height + 1, smoke_height)
+                .unwrap(),
+            proposer
+                .ticket(&key, &beacon, &with_ticket, smoke_height, smoke_height)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn assembled_block_header() {
+        let db = MemoryDB::default();
+        let key = generate_key(SignatureType::Bls).unwrap();
+        let miner = Address::new_id(1000);
+        let parents = TipsetKey::from_iter([db.put_cbor_default(&"parent").unwrap()]);
+        let ticket = Ticket::new(VRFProof::new(vec![1; 96]));
+        let election_proof = ElectionProof {
+            win_count: 2,
+            vrfproof: VRFProof::new(vec![2; 96]),
+        };
+        let beacon_entries = vec![BeaconEntry::new(3, vec![3; 96])];
+        let template = BlockTemplate {
+            miner,
+            parents: parents.clone(),
+            ticket: Some(ticket.clone()),
+            eproof: Some(election_proof.clone()),
+            beacon_values: beacon_entries.clone(),
+            messages: vec![],
+            epoch: 42,
+            timestamp: 7777,
+            winning_post_proof: vec![],
+        };
+        let state_root = db.put_cbor_default(&"state").unwrap();
+        let message_receipts = db.put_cbor_default(&"receipts").unwrap();
+        let parent = ParentValues {
+            weight: BigInt::from(5),
+            state_root,
+            message_receipts,
+            base_fee: TokenAmount::from_atto(100),
+        };
+
+        let block = assemble_block(&db, template, parent, &key).unwrap();
+        let header = block.header();
+        assert_eq!(header.miner_address, miner);
+        assert_eq!(header.parents, parents);
+        assert_eq!(header.ticket, Some(ticket));
+        assert_eq!(header.election_proof, Some(election_proof));
+        assert_eq!(header.beacon_entries, beacon_entries);
+        assert_eq!(header.epoch, 42);
+        assert_eq!(header.timestamp, 7777);
+        assert_eq!(header.weight, BigInt::from(5));
+        assert_eq!(header.state_root, state_root);
+        assert_eq!(header.message_receipts, message_receipts);
+        assert_eq!(header.parent_base_fee, TokenAmount::from_atto(100));
+        assert_eq!(
+            header.messages,
+            TipsetValidator::compute_msg_root(&db, &[], &[]).unwrap()
+        );
+        assert_eq!(
+            header.bls_aggregate,
+            Some(Signature::new_bls(EMPTY_BLS_AGGREGATE.to_vec()))
+        );
+        assert!(block.bls_msgs().is_empty() && block.secp_msgs().is_empty());
+        header.verify_signature_against(&key.address).unwrap();
+    }
+}
@@ -1,12 +1,18 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use crate::blocks::Tipset;
+use crate::blocks::{Tipset, TipsetKey};
+use crate::metrics;
 use crate::shim::{address::Address, state_tree::StateTree};
 use fil_actor_interface::power;
 use fvm_ipld_blockstore::Blockstore;
+use lru::LruCache;
+use nonzero_ext::nonzero;
 use num::{BigInt, Integer};
 use num_traits::Zero;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::num::NonZeroUsize;
 use std::sync::Arc;
 
 // constants for Weight calculation
@@ -18,9 +24,36 @@ const W_RATIO_DEN: u64 = 2;
 /// Blocks epoch allowed
 const BLOCKS_PER_EPOCH: u64 = 5;
 
+/// Number of tipset weights kept in cache. Fork choice compares the same few
+/// tipsets over and over while syncing, so recent weights are enough.
+const WEIGHT_CACHE_SIZE: NonZeroUsize = nonzero!(1024usize);
+
+/// The weight of a tipset only depends on the tipset, so weights are cached by
+/// tipset key.
+static WEIGHT_CACHE: Lazy<Mutex<LruCache<TipsetKey, BigInt>>> =
+    Lazy::new(|| Mutex::new(LruCache::new(WEIGHT_CACHE_SIZE)));
+
 /// Returns the weight of provided [Tipset]. This function will load power actor
-/// state and calculate the total weight of the [Tipset].
+/// state and calculate the total weight of the [Tipset], unless it is cached.
 pub(in crate::fil_cns) fn weight<DB>(db: &Arc<DB>, ts: &Tipset) -> Result<BigInt, String>
+where
+    DB: Blockstore,
+{
+    if let Some(weight) = WEIGHT_CACHE.lock().get(ts.key()) {
+        metrics::LRU_CACHE_HIT
+            .with_label_values(&[metrics::values::TIPSET_WEIGHT])
+            .inc();
+        return Ok(weight.clone());
+    }
+    metrics::LRU_CACHE_MISS
+        .with_label_values(&[metrics::values::TIPSET_WEIGHT])
+        .inc();
+    let weight = compute_weight(db, ts)?;
+    WEIGHT_CACHE.lock().put(ts.key().clone(), weight.clone());
+    Ok(weight)
+}
+
+fn compute_weight<DB>(db: &Arc<DB>, ts: &Tipset) -> Result<BigInt, String>
 where
     DB: Blockstore,
 {
@@ -63,3 +96,25 @@ where
     out += &e_weight;
     Ok(out)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{CachingBlockHeader, RawBlockHeader};
+    use crate::db::MemoryDB;
+
+    #[test]
+    fn weights_are_cached() {
+        let db = Arc::new(MemoryDB::default());
+        let ts = Tipset::from(CachingBlockHeader::new(RawBlockHeader {
+            timestamp: 1_000_003,
+            ..Default::default()
+        }));
+        // The state of the tipset is missing.
+        assert!(weight(&db, &ts).is_err());
+        assert!(WEIGHT_CACHE.lock().get(ts.key()).is_none());
+
+        WEIGHT_CACHE.lock().put(ts.key().clone(), BigInt::from(42));
+        assert_eq!(weight(&db, &ts), Ok(BigInt::from(42)));
+    }
+}
@@ -0,0 +1,437 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::io;
+use std::sync::Arc;
+
+use crate::beacon::BeaconEntry;
+use crate::blocks::{CachingBlockHeader, RawBlockHeader, Ticket, Tipset, VRFProof};
+use crate::chain_sync::TipsetValidator;
+use crate::ipld::stream_chain;
+use crate::shim::{
+    address::{Address, Protocol},
+    econ::TokenAmount,
+    machine::{BuiltinActor, BuiltinActorManifest},
+    sector::{RegisteredPoStProofV3, StoragePower},
+    state_tree::{ActorID, ActorState, StateTree, StateTreeVersion},
+};
+use crate::utils::db::car_stream::{CarBlock, CarWriter};
+use crate::utils::db::CborStoreExt as _;
+use anyhow::{ensure, Context as _};
+use cid::Cid;
+use fil_actors_shared::fvm_ipld_amt::Amt;
+use fil_actors_shared::v11::{
+    builtin::HAMT_BIT_WIDTH, make_empty_map, make_map_with_root, runtime::Policy, Map,
+};
+use futures::{stream, StreamExt as _, TryStreamExt as _};
+use fvm_ipld_blockstore::Blockstore;
+use itertools::Itertools as _;
+use num_traits::Zero as _;
+use tokio::io::AsyncWrite;
+
+/// Base fee of the genesis block, in `attoFIL`.
+const INITIAL_BASE_FEE: u64 = 100_000_000;
+
+/// Builds the genesis block and initial state tree of a custom network, made of
+/// the `v11` builtin actors of network version 20.
+///
+/// ```ignore
+/// let (genesis, miners) = GenesisBuilder::new("localnet")
+///     .with_timestamp(timestamp)
+///     .with_account(owner, TokenAmount::from_whole(1_000))
+///     .with_miner(owner, StoragePower::from(1 << 40))
+///     .build(&db, &manifest)?;
+/// write_car(&db, &genesis, file).await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct GenesisBuilder {
+    network_name: String,
+    timestamp: u64,
+    accounts: Vec<(Address, TokenAmount)>,
+    miners: Vec<(Address, StoragePower)>,
+}
+
+impl GenesisBuilder {
+    pub fn new(network_name: impl Into<String>) -> Self {
+        Self {
+            network_name: network_name.into(),
+            timestamp: 0,
+            accounts: vec![],
+            miners: vec![],
+        }
+    }
+
+    /// Sets the timestamp of the genesis block, in seconds since the UNIX
+    /// epoch.
+    pub fn with_timestamp(mut self, timestamp: u64) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Adds an account with the given key address and balance. The first
+    /// account is the root key of the verified registry.
+    pub fn with_account(mut self, address: Address, balance: TokenAmount) -> Self {
+        self.accounts.push((address, balance));
+        self
+    }
+
+    /// Adds a miner owned by the account of the given key address, which is
+    /// also its worker. Its power is claimed without pre-sealed sectors, so it
+    /// can only mine on networks synced with the instant consensus.
+    pub fn with_miner(mut self, owner: Address, power: StoragePower) -> Self {
+        self.miners.push((owner, power));
+        self
+    }
+
+    /// Writes the genesis state and block to the blockstore. Returns the
+    /// genesis block, and the addresses of the miners.
+    pub fn build<DB: Blockstore>(
+        &self,
+        db: &Arc<DB>,
+        manifest: &BuiltinActorManifest,
+    ) -> anyhow::Result<(CachingBlockHeader, Vec<Address>)> {
+        // Miner owners without an account get an empty one.
+        let mut accounts = self.accounts.clone();
+        for (owner, _) in &self.miners {
+            if !accounts.iter().any(|(address, _)| address == owner) {
+                accounts.push((*owner, TokenAmount::zero()));
+            }
+        }
+        ensure!(
+            accounts.iter().map(|(address, _)| address).all_unique(),
+            "duplicate genesis account"
+        );
+        ensure!(
+            accounts.iter().all(|(address, _)| matches!(
+                address.protocol(),
+                Protocol::BLS | Protocol::Secp256k1
+            )),
+            "genesis accounts must have key addresses"
+        );
+
+        let mut tree = StateTree::new(Arc::clone(db), StateTreeVersion::V5)?;
+        let mut set_actor = |address: &Address, actor, state, balance| {
+            tree.set_actor(
+                address,
+                ActorState::new(manifest.get(actor)?, state, balance, 0, None),
+            )
+        };
+
+        let system_state = fil_actor_system_state::v11::State {
+            builtin_actors: manifest.source_cid(),
+        };
+        set_actor(
+            &Address::SYSTEM_ACTOR,
+            BuiltinActor::System,
+            db.put_cbor_default(&system_state)?,
+            TokenAmount::zero(),
+        )?;
+
+        // Accounts and miners get their IDs from the init actor.
+        let mut init_state = fil_actor_init_state::v11::State::new(db, self.network_name.clone())?;
+        let mut address_map: Map<_, ActorID> =
+            make_map_with_root(&init_state.address_map, db).map_err(|e| anyhow::anyhow!("{e}"))?;
+        let mut new_id = |address: Option<&Address>| {
+            let id = init_state.next_id;
+            init_state.next_id += 1;
+            if let Some(address) = address {
+                address_map.set(address.to_bytes().into(), id)?;
+            }
+            anyhow::Ok(id)
+        };
+        let account_ids = accounts
+            .iter()
+            .map(|(address, _)| new_id(Some(address)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let eth_zero_address =
+            Address::new_delegated(Address::ETHEREUM_ACCOUNT_MANAGER_ACTOR.id()?, &[0; 20])?;
+        let eth_zero_id = new_id(Some(&eth_zero_address))?;
+        let miner_ids = self
+            .miners
+            .iter()
+            .map(|_| new_id(None))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        init_state.address_map = address_map.flush()?;
+        set_actor(
+            &Address::INIT_ACTOR,
+            BuiltinActor::Init,
+            db.put_cbor_default(&init_state)?,
+            TokenAmount::zero(),
+        )?;
+
+        let total_power: StoragePower = self.miners.iter().map(|(_, power)| power).sum();
+        let reward_state = fil_actor_reward_state::v11::State::new(total_power.clone());
+        set_actor(
+            &Address::REWARD_ACTOR,
+            BuiltinActor::Reward,
+            db.put_cbor_default(&reward_state)?,
+            TokenAmount::from_whole(1_100_000_000),
+        )?;
+
+        let cron_state = fil_actor_cron_state::v11::State {
+            entries: vec![
+                fil_actor_cron_state::v11::Entry {
+                    receiver: Address::POWER_ACTOR.into(),
+                    method_num: fil_actor_interface::power::Method::OnEpochTickEnd as u64,
+                },
+                fil_actor_cron_state::v11::Entry {
+                    receiver: Address::MARKET_ACTOR.into(),
+                    method_num: fil_actor_interface::market::Method::CronTick as u64,
+                },
+            ],
+        };
+        set_actor(
+            &Address::CRON_ACTOR,
+            BuiltinActor::Cron,
+            db.put_cbor_default(&cron_state)?,
+            TokenAmount::zero(),
+        )?;
+
+        // Genesis miners, with power claims but no sectors.
+        let window_post_proof_type = RegisteredPoStProofV3::StackedDRGWindow2KiBV1P1;
+        let mut power_state = fil_actor_power_state::v11::State::new(db)?;
+        let mut claims = make_empty_map(db, HAMT_BIT_WIDTH);
+        let mut miners = Vec::with_capacity(miner_ids.len());
+        for ((owner, power), miner_id) in self.miners.iter().zip(miner_ids) {
+            let owner_id = accounts
+                .iter()
+                .zip(&account_ids)
+                .find_map(|((address, _), id)| (address == owner).then_some(*id))
+                .context("miner owner has no account")?;
+            let info = fil_actor_miner_state::v11::MinerInfo::new(
+                owner_id,
+                owner_id,
+                vec![],
+                vec![],
+                vec![],
+                window_post_proof_type,
+            )
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+            let miner_state = fil_actor_miner_state::v11::State::new(
+                &Policy::mainnet(),
+                db,
+                db.put_cbor_default(&info)?,
+                0,
+                0,
+            )
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+            let miner = Address::new_id(miner_id);
+            set_actor(
+                &miner,
+                BuiltinActor::Miner,
+                db.put_cbor_default(&miner_state)?,
+                TokenAmount::zero(),
+            )?;
+            claims.set(
+                miner.to_bytes().into(),
+                fil_actor_power_state::v11::Claim {
+                    window_post_proof_type,
+                    raw_byte_power: power.clone(),
+                    quality_adj_power: power.clone(),
+                },
+            )?;
+            miners.push(miner);
+        }
+        power_state.claims = claims.flush()?;
+        power_state.miner_count = miners.len() as i64;
+        power_state.miner_above_min_power_count = miners.len() as i64;
+        power_state.total_raw_byte_power = total_power.clone();
+        power_state.total_bytes_committed = total_power.clone();
+        power_state.total_quality_adj_power = total_power.clone();
+        power_state.total_qa_bytes_committed = total_power.clone();
+        power_state.this_epoch_raw_byte_power = total_power.clone();
+        power_state.this_epoch_quality_adj_power = total_power;
+        set_actor(
+            &Address::POWER_ACTOR,
+            BuiltinActor::Power,
+            db.put_cbor_default(&power_state)?,
+            TokenAmount::zero(),
+        )?;
+
+        let market_state = fil_actor_market_state::v11::State::new(db)?;
+        set_actor(
+            &Address::MARKET_ACTOR,
+            BuiltinActor::Market,
+            db.put_cbor_default(&market_state)?,
+            TokenAmount::zero(),
+        )?;
+
+        let verifreg_root = account_ids
+            .first()
+            .map_or(Address::SYSTEM_ACTOR, |id| Address::new_id(*id));
+        let verifreg_state = fil_actor_verifreg_state::v11::State::new(db, verifreg_root.into())
+            .map_err(|e| anyhow::anyhow!("{e}"))?;
+        set_actor(
+            &Address::VERIFIED_REGISTRY_ACTOR,
+            BuiltinActor::VerifiedRegistry,
+            db.put_cbor_default(&verifreg_state)?,
+            TokenAmount::zero(),
+        )?;
+
+        let datacap_state = fil_actor_datacap_state::v11::State {
+            governor: Address::VERIFIED_REGISTRY_ACTOR.into(),
+            token: fil_actors_shared::frc46_token::TokenState::new_with_bit_width(
+                db,
+                HAMT_BIT_WIDTH,
+            )?,
+        };
+        set_actor(
+            &Address::DATACAP_TOKEN_ACTOR,
+            BuiltinActor::DataCap,
+            db.put_cbor_default(&datacap_state)?,
+            TokenAmount::zero(),
+        )?;
+
+        tree.set_actor(
+            &Address::ETHEREUM_ACCOUNT_MANAGER_ACTOR,
+            ActorState::new_empty(manifest.get(BuiltinActor::EAM)?, None),
+        )?;
+        tree.set_actor(
+            &Address::new_id(eth_zero_id),
+            ActorState::new(
+                manifest.get(BuiltinActor::EthAccount)?,
+                fil_actors_shared::v10::runtime::EMPTY_ARR_CID,
+                TokenAmount::zero(),
+                0,
+                Some(eth_zero_address),
+            ),
+        )?;
+
+        let burnt_funds = (Address::BURNT_FUNDS_ACTOR, TokenAmount::zero());
+        let account_ids = account_ids.into_iter().map(Address::new_id);
+        for (id, (address, balance)) in std::iter::once(Address::BURNT_FUNDS_ACTOR)
+            .chain(account_ids)
+            .zip(std::iter::once(burnt_funds).chain(accounts))
+        {
+            let account_state = fil_actor_account_state::v11::State {
+                address: address.into(),
+            };
+            tree.set_actor(
+                &id,
+                ActorState::new(
+                    manifest.get(BuiltinActor::Account)?,
+                    db.put_cbor_default(&account_state)?,
+                    balance,
+                    0,
+                    None,
+                ),
+            )?;
+        }
+
+        let state_root = tree.flush()?;
+        let header = CachingBlockHeader::new(RawBlockHeader {
+            miner_address: Address::SYSTEM_ACTOR,
+            ticket: Some(Ticket::new(VRFProof::new(
+                b"vrf proof0000000vrf proof0000000".to_vec(),
+            ))),
+            beacon_entries: vec![BeaconEntry::new(0, vec![0; 32])],
+            state_root,
+            message_receipts: Amt::<Cid, _>::new(db).flush()?,
+            messages: TipsetValidator::compute_msg_root(db, &[], &[])?,
+            timestamp: self.timestamp,
+            parent_base_fee: TokenAmount::from_atto(INITIAL_BASE_FEE),
+            ..Default::default()
+        });
+        db.put_cbor_default(&header)?;
+        Ok((header, miners))
+    }
+}
+
+/// Writes the genesis block and state to a `CARv1` archive, which can be loaded
+/// with [`super::read_genesis_header`].
+pub async fn write_car<DB: Blockstore>(
+    db: &Arc<DB>,
+    genesis: &CachingBlockHeader,
+    writer: impl AsyncWrite + Unpin,
+) -> anyhow::Result<()> {
+    let tipset = Tipset::from(genesis.clone());
+    let blocks: Vec<CarBlock> = stream_chain(db, tipset.chain(db), 0).try_collect().await?;
+    stream::iter(blocks)
+        .map(io::Result::Ok)
+        .forward(CarWriter::new_carv1(vec![*genesis.cid()], writer)?)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+    use crate::genesis::read_genesis_header;
+    use cid::multihash::MultihashDigest as _;
+    use fvm_ipld_encoding::{CborStore as _, IPLD_RAW};
+
+    fn make_test_manifest<BS: Blockstore>(store: &BS) -> BuiltinActorManifest {
+        let manifest_data = [
+            "account",
+            "cron",
+            "init",
+            "storagemarket",
+            "storageminer",
+            "multisig",
+            "paymentchannel",
+            "storagepower",
+            "reward",
+            "system",
+            "verifiedregistry",
+            "datacap",
+            "eam",
+            "ethaccount",
+        ]
+        .map(|name| {
+            let hash = cid::multihash::Code::Identity.digest(format!("fil/11/{name}").as_bytes());
+            (name, Cid::new_v1(IPLD_RAW, hash))
+        });
+        let manifest_cid = store
+            .put_cbor_default(&(1, store.put_cbor_default(&manifest_data).unwrap()))
+            .unwrap();
+        BuiltinActorManifest::load_manifest(store, &manifest_cid).unwrap()
+    }
+
+    #[tokio::test]
+    async fn genesis_round_trip() {
+        let db = Arc::new(MemoryDB::default());
+        let manifest = make_test_manifest(&db);
+        let owner = Address::new_bls(&[1; 48]).unwrap();
+        let (header, miners) = GenesisBuilder::new("localnet")
+            .with_timestamp(1_700_000_000)
+            .with_account(owner, TokenAmount::from_whole(1_000))
+            .with_miner(owner, StoragePower::from(1 << 40))
+            .with_miner(owner, StoragePower::from(1 << 40))
+            .build(&db, &manifest)
+            .unwrap();
+        assert_eq!(miners, [Address::new_id(102), Address::new_id(103)]);
+
+        let mut car = vec![];
+        write_car(&db, &header, &mut car).await.unwrap();
+        let loaded_db = Arc::new(MemoryDB::default());
+        let loaded =
+            read_genesis_header(None, Some(car.as_slice()), Some(*header.cid()), &loaded_db)
+                .await
+                .unwrap();
+        assert_eq!(loaded.cid(), header.cid());
+
+        let tree = StateTree::new_from_root(loaded_db.clone(), &loaded.state_root).unwrap();
+        let init_actor = tree.get_actor(&Address::INIT_ACTOR).unwrap().unwrap();
+        let init_state: fil_actor_init_state::v11::State =
+            loaded_db.get_cbor(&init_actor.state).unwrap().unwrap();
+        assert_eq!(init_state.network_name, "localnet");
+        let owner = tree.get_actor(&Address::new_id(100)).unwrap().unwrap();
+        assert_eq!(
+            TokenAmount::from(&owner.balance),
+            TokenAmount::from_whole(1_000)
+        );
+        for miner in miners {
+            assert!(tree.get_actor(&miner).unwrap().is_some());
+        }
+    }
+
+    #[test]
+    fn accounts_need_key_addresses() {
+        let db = Arc::new(MemoryDB::default());
+        let manifest = make_test_manifest(&db);
+        let builder = GenesisBuilder::new("localnet")
+            .with_account(Address::new_id(1000), TokenAmount::from_whole(1));
+        assert!(builder.build(&db, &manifest).is_err());
+    }
+}
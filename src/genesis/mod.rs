@@ -2,21 +2,45 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use crate::blocks::CachingBlockHeader;
+use crate::shim::{address::Address, clock::ChainEpoch};
 use crate::state_manager::StateManager;
 use crate::utils::db::car_util::load_car;
 use anyhow::Context as _;
+use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
+use thiserror::Error;
 use tokio::{fs::File, io::AsyncBufRead, io::BufReader};
 use tracing::{debug, info};
 
+pub mod builder;
+
 #[cfg(test)]
 pub const EXPORT_SR_40: &[u8] = std::include_bytes!("export40.car");
 
+/// Reasons for a genesis CAR to be rejected.
+#[derive(Debug, Error)]
+pub enum GenesisError {
+    #[error("Genesis CAR must have a single root, found {0}")]
+    RootCount(usize),
+    #[error("Genesis block {0} is missing from the genesis CAR")]
+    MissingBlock(Cid),
+    #[error("Genesis block must be at epoch 0, found {0}")]
+    Epoch(ChainEpoch),
+    #[error("Genesis block has no timestamp")]
+    Timestamp,
+    #[error("Genesis block must be mined by the system actor, found {0}")]
+    Miner(Address),
+    #[error("Genesis block {actual} doesn't match the genesis {expected} of the network")]
+    Mismatch { expected: Cid, actual: Cid },
+}
+
 /// Uses an optional file path or the default genesis to parse the genesis and
-/// determine if chain store has existing data for the given genesis.
+/// determine if chain store has existing data for the given genesis. The
+/// genesis block must be `expected`, when the network genesis is known.
 pub async fn read_genesis_header<DB>(
     genesis_fp: Option<&String>,
     genesis_bytes: Option<&[u8]>,
+    expected: Option<Cid>,
     db: &DB,
 ) -> Result<CachingBlockHeader, anyhow::Error>
 where
@@ -34,6 +58,7 @@ where
             process_car(genesis_bytes, db).await?
         }
     };
+    validate_genesis_header(&genesis, expected)?;
 
     info!("Initialized genesis: {}", genesis.cid());
     Ok(genesis)
@@ -59,14 +84,91 @@ where
     BS: Blockstore,
 {
     // Load genesis state into the database and get the Cid
-    let header = load_car(db, reader).await?;
-    if header.roots.len() != 1 {
-        panic!("Invalid Genesis. Genesis Tipset must have only 1 Block.");
-    }
+    let header = load_car(db, reader).await.context("Invalid genesis CAR")?;
+    let [root] = header.roots[..] else {
+        return Err(GenesisError::RootCount(header.roots.len()).into());
+    };
 
-    let genesis_block = CachingBlockHeader::load(db, header.roots[0])?.ok_or_else(|| {
-        anyhow::anyhow!("Could not find genesis block despite being loaded using a genesis file")
-    })?;
+    let genesis_block =
+        CachingBlockHeader::load(db, root)?.ok_or(GenesisError::MissingBlock(root))?;
 
     Ok(genesis_block)
 }
+
+fn validate_genesis_header(
+    header: &CachingBlockHeader,
+    expected: Option<Cid>,
+) -> Result<(), GenesisError> {
+    if header.epoch != 0 {
+        return Err(GenesisError::Epoch(header.epoch));
+    }
+    if header.timestamp == 0 {
+        return Err(GenesisError::Timestamp);
+    }
+    if header.miner_address != Address::SYSTEM_ACTOR {
+        return Err(GenesisError::Miner(header.miner_address));
+    }
+    match expected {
+        Some(expected) if expected != *header.cid() => Err(GenesisError::Mismatch {
+            expected,
+            actual: *header.cid(),
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+
+    #[tokio::test]
+    async fn malformed_genesis_is_an_error() {
+        let db = MemoryDB::default();
+        assert!(
+            read_genesis_header(None, Some(b"not a car".as_slice()), None, &db)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn genesis_header_validation() {
+        use crate::blocks::RawBlockHeader;
+
+        let genesis = CachingBlockHeader::new(RawBlockHeader {
+            timestamp: 1,
+            ..Default::default()
+        });
+        assert!(validate_genesis_header(&genesis, None).is_ok());
+        assert!(validate_genesis_header(&genesis, Some(*genesis.cid())).is_ok());
+        assert!(matches!(
+            validate_genesis_header(&genesis, Some(Cid::default())),
+            Err(GenesisError::Mismatch { .. })
+        ));
+
+        let invalid = |raw: RawBlockHeader| {
+            validate_genesis_header(&CachingBlockHeader::new(raw), None).unwrap_err()
+        };
+        assert!(matches!(
+            invalid(RawBlockHeader {
+                epoch: 1,
+                timestamp: 1,
+                ..Default::default()
+            }),
+            GenesisError::Epoch(1)
+        ));
+        assert!(matches!(
+            invalid(RawBlockHeader::default()),
+            GenesisError::Timestamp
+        ));
+        assert!(matches!(
+            invalid(RawBlockHeader {
+                timestamp: 1,
+                miner_address: Address::new_id(1000),
+                ..Default::default()
+            }),
+            GenesisError::Miner(_)
+        ));
+    }
+}
@@ -0,0 +1,242 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Kubernetes-style health endpoints of the daemon, served on a dedicated port:
+//! - `/livez` fails when the node is stuck: syncing errored or all peers are gone,
+//! - `/readyz` fails until the node is synced, has enough peers and can write to its database,
+//! - `/healthz` runs all the checks.
+//!
+//! Checks that pass are reported with `[+]` and failing checks with `[!]`. The report is only
+//! returned with `?verbose`, otherwise the body is `ok` or the failing checks.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::blocks::Tipset;
+use crate::chain_sync::{SyncStage, SyncWorkers};
+use crate::db::SettingsStore;
+use crate::libp2p::PeerManager;
+use crate::shim::clock::ChainEpoch;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+
+/// Settings key written by the database writability check.
+const HEALTHCHECK_KEY: &str = "/healthcheck";
+
+/// Configuration of the health endpoints.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+pub struct HealthcheckConfig {
+    /// Serve the health endpoints.
+    pub enabled: bool,
+    /// Address of the health endpoints, e.g. `0.0.0.0:2346`.
+    pub address: SocketAddr,
+    /// Number of peers the node needs to be ready.
+    pub min_peers: usize,
+    /// Number of epochs the head can be behind the current epoch, for the node to be considered
+    /// synced.
+    pub max_epoch_lag: ChainEpoch,
+}
+
+impl Default for HealthcheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            address: SocketAddr::from(([0, 0, 0, 0], 2346)),
+            min_peers: 1,
+            max_epoch_lag: 5,
+        }
+    }
+}
+
+/// State of the node inspected by the health checks.
+pub struct ForestState {
+    /// Configuration of the health endpoints, whose thresholds can be reloaded at runtime.
+    pub config: Arc<RwLock<HealthcheckConfig>>,
+    pub genesis_timestamp: u64,
+    pub block_delay_secs: u32,
+    pub heaviest_tipset: Box<dyn Fn() -> Arc<Tipset> + Send + Sync>,
+    pub settings: Arc<dyn SettingsStore + Send + Sync>,
+    pub sync_workers: SyncWorkers,
+    pub peer_manager: Arc<PeerManager>,
+}
+
+/// Serves the health endpoints until an error occurs.
+pub async fn init_healthcheck_server(
+    state: ForestState,
+    listener: TcpListener,
+) -> anyhow::Result<()> {
+    let app = Router::new()
+        .route("/livez", get(livez))
+        .route("/readyz", get(readyz))
+        .route("/healthz", get(healthz))
+        .with_state(Arc::new(state));
+    Ok(axum::serve(listener, app.into_make_service()).await?)
+}
+
+#[derive(Deserialize)]
+struct Verbosity {
+    verbose: Option<String>,
+}
+
+/// Outcome of the checks of an endpoint.
+struct Report {
+    lines: Vec<String>,
+    healthy: bool,
+}
+
+impl Report {
+    fn new() -> Self {
+        Self {
+            lines: vec![],
+            healthy: true,
+        }
+    }
+
+    fn check(&mut self, name: &str, outcome: Result<(), String>) -> &mut Self {
+        match outcome {
+            Ok(()) => self.lines.push(format!("[+] {name} ok")),
+            Err(reason) => {
+                self.lines.push(format!("[!] {name} failed: {reason}"));
+                self.healthy = false;
+            }
+        }
+        self
+    }
+
+    fn into_response(self, verbose: bool) -> impl IntoResponse {
+        let status = if self.healthy {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+        let body = match (verbose, self.healthy) {
+            (true, _) => self.lines.join("\n"),
+            (false, true) => "ok".to_owned(),
+            (false, false) => self
+                .lines
+                .into_iter()
+                .filter(|line| line.starts_with("[!]"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        };
+        (status, body)
+    }
+}
+
+#[allow(clippy::unused_async)]
+async fn livez(
+    State(state): State<Arc<ForestState>>,
+    Query(verbosity): Query<Verbosity>,
+) -> impl IntoResponse {
+    let mut report = Report::new();
+    report
+        .check("sync", check_sync_not_errored(&state))
+        .check("peers", check_peers(&state, 1));
+    report.into_response(verbosity.verbose.is_some())
+}
+
+#[allow(clippy::unused_async)]
+async fn readyz(
+    State(state): State<Arc<ForestState>>,
+    Query(verbosity): Query<Verbosity>,
+) -> impl IntoResponse {
+    let mut report = Report::new();
+    report
+        .check("epoch", check_epoch_up_to_date(&state))
+        .check("peers", check_peers(&state, state.config.read().min_peers))
+        .check("db", check_db_writable(&state));
+    report.into_response(verbosity.verbose.is_some())
+}
+
+#[allow(clippy::unused_async)]
+async fn healthz(
+    State(state): State<Arc<ForestState>>,
+    Query(verbosity): Query<Verbosity>,
+) -> impl IntoResponse {
+    let mut report = Report::new();
+    report
+        .check("sync", check_sync_not_errored(&state))
+        .check("epoch", check_epoch_up_to_date(&state))
+        .check("peers", check_peers(&state, state.config.read().min_peers))
+        .check("db", check_db_writable(&state));
+    report.into_response(verbosity.verbose.is_some())
+}
+
+fn check_sync_not_errored(state: &ForestState) -> Result<(), String> {
+    match state.sync_workers.main().read().stage() {
+        SyncStage::Error => Err("syncing errored".into()),
+        _ => Ok(()),
+    }
+}
+
+fn check_epoch_up_to_date(state: &ForestState) -> Result<(), String> {
+    let now = chrono::Utc::now().timestamp() as u64;
+    let expected = expected_epoch(now, state.genesis_timestamp, state.block_delay_secs);
+    let head = (state.heaviest_tipset)().epoch();
+    is_synced(head, expected, state.config.read().max_epoch_lag)
+        .then_some(())
+        .ok_or_else(|| format!("head at epoch {head}, expected epoch {expected}"))
+}
+
+fn check_peers(state: &ForestState, min_peers: usize) -> Result<(), String> {
+    let peers = state.peer_manager.peer_count();
+    (peers >= min_peers)
+        .then_some(())
+        .ok_or_else(|| format!("{peers} peers, expected at least {min_peers}"))
+}
+
+fn check_db_writable(state: &ForestState) -> Result<(), String> {
+    let now = chrono::Utc::now().timestamp().to_be_bytes();
+    state
+        .settings
+        .write_bin(HEALTHCHECK_KEY, &now)
+        .map_err(|e| e.to_string())
+}
+
+/// Returns the epoch the network is at, at the `now` Unix timestamp.
+fn expected_epoch(now: u64, genesis_timestamp: u64, block_delay_secs: u32) -> ChainEpoch {
+    (now.saturating_sub(genesis_timestamp) / u64::from(block_delay_secs.max(1))) as ChainEpoch
+}
+
+fn is_synced(head: ChainEpoch, expected: ChainEpoch, max_epoch_lag: ChainEpoch) -> bool {
+    head >= expected - max_epoch_lag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expected_epoch_counts_elapsed_block_delays() {
+        assert_eq!(expected_epoch(1_000, 1_000, 30), 0);
+        assert_eq!(expected_epoch(1_089, 1_000, 30), 2);
+        assert_eq!(expected_epoch(1_090, 1_000, 30), 3);
+        // A clock behind genesis doesn't underflow.
+        assert_eq!(expected_epoch(0, 1_000, 30), 0);
+    }
+
+    #[test]
+    fn synced_within_max_epoch_lag() {
+        assert!(is_synced(100, 100, 5));
+        assert!(is_synced(95, 100, 5));
+        assert!(!is_synced(94, 100, 5));
+    }
+
+    #[test]
+    fn report_fails_if_any_check_fails() {
+        let mut report = Report::new();
+        report.check("a", Ok(())).check("b", Err("broken".into()));
+        assert!(!report.healthy);
+        assert_eq!(report.lines, ["[+] a ok", "[!] b failed: broken"]);
+    }
+}
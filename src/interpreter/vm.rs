@@ -1,12 +1,13 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
 use crate::blocks::Tipset;
-use crate::chain::block_messages;
 use crate::chain::index::ChainIndex;
 use crate::chain::store::Error;
+use crate::chain::{block_messages_from_cids, read_msg_cids};
 use crate::interpreter::{
     fvm2::ForestExternsV2, fvm3::ForestExterns as ForestExternsV3,
     fvm4::ForestExterns as ForestExternsV4,
@@ -17,7 +18,7 @@ use crate::networks::{ChainConfig, NetworkChain};
 use crate::shim::{
     address::Address,
     econ::TokenAmount,
-    executor::{ApplyRet, Receipt},
+    executor::{ApplyRet, Receipt, StampedEvent},
     externs::{Rand, RandWrapper},
     machine::MultiEngine,
     message::{Message, Message_v3},
@@ -75,6 +76,15 @@ type ForestExecutorV4<DB> = DefaultExecutor_v4<ForestKernelV4<DB>>;
 
 pub type ApplyResult = anyhow::Result<(ApplyRet, Duration)>;
 
+/// Number of explicit messages applied since the node started.
+static APPLIED_MESSAGES: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the number of explicit messages applied since the node started, as
+/// reported by `Filecoin.SyncState`.
+pub fn applied_messages() -> u64 {
+    APPLIED_MESSAGES.load(Ordering::Relaxed)
+}
+
 /// Comes from <https://github.com/filecoin-project/lotus/blob/v1.23.2/chain/vm/fvm.go#L473>
 pub const IMPLICIT_MESSAGE_GAS_LIMIT: i64 = i64::MAX / 2;
 
@@ -90,6 +100,16 @@ pub struct BlockMessages {
 impl BlockMessages {
     /// Retrieves block messages to be passed through the VM and removes duplicate messages which appear in multiple blocks.
     pub fn for_tipset(db: impl Blockstore, ts: &Tipset) -> Result<Vec<BlockMessages>, Error> {
+        Self::for_tipset_counting(db, ts, None)
+    }
+
+    /// Same as [`BlockMessages::for_tipset`], also counting in `inclusions` the number of blocks
+    /// including each message, by CID.
+    pub fn for_tipset_counting(
+        db: impl Blockstore,
+        ts: &Tipset,
+        mut inclusions: Option<&mut HashMap<Cid, usize>>,
+    ) -> Result<Vec<BlockMessages>, Error> {
         let mut applied = HashMap::new();
         let mut select_msg = |m: ChainMessage| -> Option<ChainMessage> {
             // The first match for a sender is guaranteed to have correct nonce
@@ -107,7 +127,13 @@ impl BlockMessages {
         ts.block_headers()
             .iter()
             .map(|b| {
-                let (usm, sm) = block_messages(&db, b)?;
+                let (bls_cids, secp_cids) = read_msg_cids(&db, &b.messages)?;
+                if let Some(inclusions) = inclusions.as_deref_mut() {
+                    for cid in bls_cids.iter().chain(&secp_cids) {
+                        *inclusions.entry(*cid).or_default() += 1;
+                    }
+                }
+                let (usm, sm) = block_messages_from_cids(&db, &bls_cids, &secp_cids)?;
 
                 let mut messages = Vec::with_capacity(usm.len() + sm.len());
                 messages.extend(
@@ -344,7 +370,8 @@ where
     }
 
     /// Apply block messages from a Tipset.
-    /// Returns the receipts from the transactions.
+    /// Returns the receipts from the transactions, along with the events
+    /// emitted by each of them.
     pub fn apply_block_messages(
         &mut self,
         messages: &[BlockMessages],
@@ -352,8 +379,9 @@ where
         // note: we take &MessageCallbackCtx rather than MessageCallbackCtx<'_>
         //       because I'm not smart enough to make the second one work
         mut callback: Option<impl FnMut(&MessageCallbackCtx) -> anyhow::Result<()>>,
-    ) -> Result<Vec<Receipt>, anyhow::Error> {
+    ) -> Result<(Vec<Receipt>, Vec<Vec<StampedEvent>>), anyhow::Error> {
         let mut receipts = Vec::new();
+        let mut events = Vec::new();
         let mut processed = HashSet::<Cid>::default();
 
         for block in messages.iter() {
@@ -383,6 +411,7 @@ where
                 penalty += ret.penalty();
                 let msg_receipt = ret.msg_receipt();
                 receipts.push(msg_receipt.clone());
+                events.push(ret.events());
 
                 // Add processed Cid to set of processed messages
                 processed.insert(cid);
@@ -429,7 +458,7 @@ where
             tracing::error!("End of epoch cron failed to run: {}", e);
         }
 
-        Ok(receipts)
+        Ok((receipts, events))
     }
 
     /// Applies single message through VM and returns result from execution.
@@ -461,6 +490,7 @@ where
 
         // Basic validity check
         msg.message().check()?;
+        APPLIED_MESSAGES.fetch_add(1, Ordering::Relaxed);
 
         let unsigned = msg.message().clone();
         let raw_length = to_vec(msg).expect("encoding error").len();
@@ -188,16 +188,40 @@ pin_project! {
         db: DB,
         dfs: VecDeque<Task>, // Depth-first work queue.
         seen: CidHashSet,
+        base: Option<Arc<dyn Blockstore + Send + Sync>>,
         stateroot_limit: ChainEpoch,
         fail_on_dead_links: bool,
+        include_state_roots: bool,
+        include_old_messages: bool,
     }
 }
 
+/// Returns `true` if `cid` is in `base`.
+fn in_base(base: &Option<Arc<dyn Blockstore + Send + Sync>>, cid: &Cid) -> anyhow::Result<bool> {
+    base.as_ref().map_or(Ok(false), |base| base.has(cid))
+}
+
 impl<DB, T> ChainStream<DB, T> {
     pub fn with_seen(self, seen: CidHashSet) -> Self {
         ChainStream { seen, ..self }
     }
 
+    /// Skips the blocks of `base`, and the graphs below them, as if they had
+    /// been seen already. Blocks are looked up in `base` as they are reached,
+    /// so that a large base, e.g. an indexed snapshot, isn't loaded in memory.
+    pub fn with_base(self, base: Option<Arc<dyn Blockstore + Send + Sync>>) -> Self {
+        ChainStream { base, ..self }
+    }
+
+    /// Also walk the messages of the tipsets before the `stateroot_limit`
+    /// epoch, and the message receipts of every tipset.
+    pub fn with_old_messages(self, include_old_messages: bool) -> Self {
+        ChainStream {
+            include_old_messages,
+            ..self
+        }
+    }
+
     #[allow(dead_code)]
     pub fn into_seen(self) -> CidHashSet {
         self.seen
@@ -225,8 +249,11 @@ pub fn stream_chain<DB: Blockstore, T: Iterator<Item = Tipset> + Unpin>(
         db,
         dfs: VecDeque::new(),
         seen: CidHashSet::default(),
+        base: None,
         stateroot_limit,
         fail_on_dead_links: true,
+        include_state_roots: true,
+        include_old_messages: false,
     }
 }
 
@@ -242,8 +269,30 @@ pub fn stream_graph<DB: Blockstore, T: Iterator<Item = Tipset> + Unpin>(
         db,
         dfs: VecDeque::new(),
         seen: CidHashSet::default(),
+        base: None,
         stateroot_limit,
         fail_on_dead_links: false,
+        include_state_roots: true,
+        include_old_messages: false,
+    }
+}
+
+/// Stream block headers and messages of every tipset yielded by `tipset_iter`, without walking
+/// any state roots. Any dead links are reported as errors.
+pub fn stream_messages<DB: Blockstore, T: Iterator<Item = Tipset> + Unpin>(
+    db: DB,
+    tipset_iter: T,
+) -> ChainStream<DB, T> {
+    ChainStream {
+        tipset_iter,
+        db,
+        dfs: VecDeque::new(),
+        seen: CidHashSet::default(),
+        base: None,
+        stateroot_limit: ChainEpoch::MIN,
+        fail_on_dead_links: true,
+        include_state_roots: false,
+        include_old_messages: false,
     }
 }
 
@@ -262,6 +311,8 @@ impl<DB: Blockstore, T: Iterator<Item = Tipset> + Unpin> Stream for ChainStream<
         };
 
         let stateroot_limit = *this.stateroot_limit;
+        let include_state_roots = *this.include_state_roots;
+        let include_old_messages = *this.include_old_messages;
         loop {
             while let Some(task) = this.dfs.front_mut() {
                 match task {
@@ -281,7 +332,10 @@ impl<DB: Blockstore, T: Iterator<Item = Tipset> + Unpin> Stream for ChainStream<
                             // 2. IPLD_RAW: WASM blocks, for example. Need to be loaded, but not traversed.
                             // 3. _: ignore all other links
                             // Don't revisit what's already been visited.
-                            if should_save_block_to_snapshot(cid) && this.seen.insert(cid) {
+                            if should_save_block_to_snapshot(cid)
+                                && this.seen.insert(cid)
+                                && !in_base(this.base, &cid)?
+                            {
                                 if let Some(data) = this.db.get(&cid)? {
                                     if cid.codec() == fvm_ipld_encoding::DAG_CBOR {
                                         let new_values = extract_cids(&data)?;
@@ -310,7 +364,7 @@ impl<DB: Blockstore, T: Iterator<Item = Tipset> + Unpin> Stream for ChainStream<
             // yield the block without walking the graph it represents.
             if let Some(tipset) = this.tipset_iter.next() {
                 for block in tipset.into_block_headers().into_iter() {
-                    if this.seen.insert(*block.cid()) {
+                    if this.seen.insert(*block.cid()) && !in_base(this.base, block.cid())? {
                         // Make sure we always yield a block otherwise.
                         this.dfs.push_back(Emit(*block.cid()));
 
@@ -322,17 +376,26 @@ impl<DB: Blockstore, T: Iterator<Item = Tipset> + Unpin> Stream for ChainStream<
                         }
 
                         // Process block messages.
-                        if block.epoch > stateroot_limit {
+                        if block.epoch > stateroot_limit || include_old_messages {
                             this.dfs.push_back(Iterate(
                                 DfsIter::from(block.messages)
                                     .filter_map(ipld_to_cid)
                                     .collect(),
                             ));
                         }
+                        if include_old_messages {
+                            this.dfs.push_back(Iterate(
+                                DfsIter::from(block.message_receipts)
+                                    .filter_map(ipld_to_cid)
+                                    .collect(),
+                            ));
+                        }
 
                         // Visit the block if it's within required depth. And a special case for `0`
                         // epoch to match Lotus' implementation.
-                        if block.epoch == 0 || block.epoch > stateroot_limit {
+                        if include_state_roots
+                            && (block.epoch == 0 || block.epoch > stateroot_limit)
+                        {
                             // NOTE: In the original `walk_snapshot` implementation we walk the dag
                             // immediately. Which is what we do here as well, but using a queue.
                             this.dfs.push_back(Iterate(
@@ -9,6 +9,7 @@ use std::{
 };
 
 use crate::{shim::crypto::SignatureType, utils::encoding::from_slice_with_fallback};
+use aes_gcm::{Aes256Gcm, Nonce};
 use ahash::{HashMap, HashMapExt};
 use argon2::{
     password_hash::SaltString, Argon2, ParamsBuilder, PasswordHasher, RECOMMENDED_SALT_LEN,
@@ -21,11 +22,15 @@ use crypto_secretbox::{
 use rand::{rngs::OsRng, RngCore};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tracing::{error, warn};
+use tracing::{error, info, warn};
 
 use super::errors::Error;
 
 const NONCE_SIZE: usize = SecretBox::<Box<dyn std::any::Any>>::NONCE_SIZE;
+const AES_GCM_NONCE_SIZE: usize = 12;
+
+/// Prefix of keystores encrypted with `AES-256-GCM`.
+const KEYSTORE_MAGIC: &[u8; 4] = b"FKS2";
 
 pub const KEYSTORE_NAME: &str = "keystore.json";
 pub const ENCRYPTED_KEYSTORE_NAME: &str = "keystore";
@@ -93,7 +98,7 @@ struct PersistentKeyStore {
 
 /// Encrypted `KeyStore`
 /// `Argon2id` hash key derivation
-/// `XSalsa20Poly1305` authenticated encryption
+/// `AES-256-GCM` authenticated encryption (`XSalsa20Poly1305` for old keystores)
 /// CBOR encoding
 #[derive(Clone, PartialEq, Debug, Eq)]
 struct EncryptedKeyStore {
@@ -172,100 +177,135 @@ impl KeyStore {
                 }
             }
             KeyStoreConfig::Encrypted(location, passphrase) => {
-                if !location.exists() {
-                    create_dir(location.clone())?;
-                }
+                let mut keystore = Self::open_encrypted(&location, &passphrase)?;
+                keystore.import_plaintext(&location)?;
+                Ok(keystore)
+            }
+        }
+    }
 
-                let file_path = location.join(Path::new(ENCRYPTED_KEYSTORE_NAME));
+    fn open_encrypted(location: &Path, passphrase: &str) -> Result<Self, Error> {
+        if !location.exists() {
+            create_dir(location)?;
+        }
 
-                if !file_path.exists() {
-                    File::create(file_path.clone())?;
-                }
+        let file_path = location.join(Path::new(ENCRYPTED_KEYSTORE_NAME));
 
-                match File::open(&file_path) {
-                    Ok(file) => {
-                        let mut reader = BufReader::new(file);
-                        let mut buf = vec![];
-                        let read_bytes = reader.read_to_end(&mut buf)?;
-
-                        if read_bytes == 0 {
-                            // New encrypted keystore if file exists but is zero bytes (i.e., touch)
-                            warn!(
-                                "Keystore does not exist, initializing new keystore at {:?}",
-                                file_path
-                            );
+        let mut buf = vec![];
+        match File::open(&file_path) {
+            Ok(file) => {
+                BufReader::new(file).read_to_end(&mut buf)?;
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
 
-                            let (salt, encryption_key) =
-                                EncryptedKeyStore::derive_key(&passphrase, None).map_err(
-                                    |error| {
-                                        error!("Failed to create key from passphrase");
-                                        Error::Other(error.to_string())
-                                    },
-                                )?;
-                            Ok(Self {
-                                key_info: HashMap::new(),
-                                persistence: Some(PersistentKeyStore { file_path }),
-                                encryption: Some(EncryptedKeyStore {
-                                    salt,
-                                    encryption_key,
-                                }),
-                            })
-                        } else {
-                            // Existing encrypted keystore
-                            // Split off data from prepended salt
-                            let data = buf.split_off(RECOMMENDED_SALT_LEN);
-                            let mut prev_salt = [0; RECOMMENDED_SALT_LEN];
-                            prev_salt.copy_from_slice(&buf);
-                            let (salt, encryption_key) =
-                                EncryptedKeyStore::derive_key(&passphrase, Some(prev_salt))
-                                    .map_err(|error| {
-                                        error!("Failed to create key from passphrase");
-                                        Error::Other(error.to_string())
-                                    })?;
-
-                            let decrypted_data = EncryptedKeyStore::decrypt(&encryption_key, &data)
-                                .map_err(|error| Error::Other(error.to_string()))?;
-
-                            let key_info = from_slice_with_fallback(&decrypted_data)
-                                .map_err(|e| {
-                                    error!("Failed to deserialize keyfile, initializing new");
-                                    e
-                                })
-                                .unwrap_or_default();
+        if buf.is_empty() {
+            warn!(
+                "Keystore does not exist, initializing new keystore at {:?}",
+                file_path
+            );
+
+            let (salt, encryption_key) =
+                EncryptedKeyStore::derive_key(passphrase, None).map_err(|error| {
+                    error!("Failed to create key from passphrase");
+                    Error::Other(error.to_string())
+                })?;
+            let keystore = Self {
+                key_info: HashMap::new(),
+                persistence: Some(PersistentKeyStore { file_path }),
+                encryption: Some(EncryptedKeyStore {
+                    salt,
+                    encryption_key,
+                }),
+            };
+            keystore
+                .flush()
+                .map_err(|error| Error::Other(error.to_string()))?;
+            return Ok(keystore);
+        }
 
-                            Ok(Self {
-                                key_info,
-                                persistence: Some(PersistentKeyStore { file_path }),
-                                encryption: Some(EncryptedKeyStore {
-                                    salt,
-                                    encryption_key,
-                                }),
-                            })
-                        }
-                    }
-                    Err(_) => {
-                        warn!("Encrypted keystore does not exist, initializing new keystore");
+        // Keystores written before the switch to `AES-256-GCM` have no magic
+        // prefix and are encrypted with `XSalsa20Poly1305`.
+        let (legacy, buf) = match buf.strip_prefix(KEYSTORE_MAGIC) {
+            Some(rest) => (false, rest),
+            None => (true, buf.as_slice()),
+        };
+        if buf.len() < RECOMMENDED_SALT_LEN {
+            return Err(Error::Other("Keystore file is truncated".to_string()));
+        }
+        let (prev_salt, data) = buf.split_at(RECOMMENDED_SALT_LEN);
+        let mut salt = [0; RECOMMENDED_SALT_LEN];
+        salt.copy_from_slice(prev_salt);
+        let (salt, encryption_key) = EncryptedKeyStore::derive_key(passphrase, Some(salt))
+            .map_err(|error| {
+                error!("Failed to create key from passphrase");
+                Error::Other(error.to_string())
+            })?;
+
+        let decrypted_data = if legacy {
+            EncryptedKeyStore::decrypt_legacy(&encryption_key, data)
+        } else {
+            EncryptedKeyStore::decrypt(&encryption_key, data)
+        }
+        .map_err(|error| Error::Other(error.to_string()))?;
+
+        let key_info = from_slice_with_fallback(&decrypted_data)
+            .map_err(|e| {
+                error!("Failed to deserialize keyfile, initializing new");
+                e
+            })
+            .unwrap_or_default();
+
+        let keystore = Self {
+            key_info,
+            persistence: Some(PersistentKeyStore { file_path }),
+            encryption: Some(EncryptedKeyStore {
+                salt,
+                encryption_key,
+            }),
+        };
+        if legacy {
+            info!("Re-encrypting keystore with AES-256-GCM");
+            keystore
+                .flush()
+                .map_err(|error| Error::Other(error.to_string()))?;
+        }
+        Ok(keystore)
+    }
 
-                        let (salt, encryption_key) =
-                            EncryptedKeyStore::derive_key(&passphrase, None).map_err(|error| {
-                                error!("Failed to create key from passphrase");
-                                Error::Other(error.to_string())
-                            })?;
+    /// Moves the keys of a cleartext keystore found in `location` into this
+    /// keystore and deletes the cleartext file. Keys already present are kept.
+    fn import_plaintext(&mut self, location: &Path) -> Result<(), Error> {
+        let plaintext_path = location.join(KEYSTORE_NAME);
+        if !plaintext_path.is_file() {
+            return Ok(());
+        }
 
-                        Ok(Self {
-                            key_info: HashMap::new(),
-                            persistence: Some(PersistentKeyStore { file_path }),
-                            encryption: Some(EncryptedKeyStore {
-                                salt,
-                                encryption_key,
-                            }),
-                        })
-                    }
-                }
+        let plaintext = Self::new(KeyStoreConfig::Persistent(location.to_path_buf()))?;
+        let mut imported = 0;
+        for (key, key_info) in plaintext.key_info {
+            if !self.key_info.contains_key(&key) {
+                self.key_info.insert(key, key_info);
+                imported += 1;
             }
         }
+        self.flush()
+            .map_err(|error| Error::Other(error.to_string()))?;
+        remove_securely(&plaintext_path)?;
+        // Left by earlier versions, which backed the keystore up on flushes.
+        let backup_path = with_suffix(&plaintext_path, "bak");
+        if backup_path.is_file() {
+            remove_securely(&backup_path)?;
+        }
+        info!(
+            "Migrated {imported} keys from cleartext keystore at {:?}",
+            plaintext_path
+        );
+        Ok(())
     }
 
+    /// Writes the keys to the keystore file. The file is replaced atomically.
     pub fn flush(&self) -> anyhow::Result<()> {
         match &self.persistence {
             Some(persistent_keystore) => {
@@ -274,49 +314,44 @@ impl KeyStore {
                     .parent()
                     .ok_or_else(|| Error::Other("Invalid Path".to_string()))?;
                 fs::create_dir_all(dir)?;
-                let file = File::create(&persistent_keystore.file_path)?;
 
-                // Restrict permissions on files containing private keys
-                #[cfg(unix)]
-                crate::utils::io::set_user_perm(&file)?;
-
-                let mut writer = BufWriter::new(file);
-
-                match &self.encryption {
-                    Some(encrypted_keystore) => {
-                        // Flush For EncryptedKeyStore
-                        let data = serde_ipld_dagcbor::to_vec(&self.key_info).map_err(|e| {
-                            Error::Other(format!("failed to serialize and write key info: {e}"))
-                        })?;
-
-                        let encrypted_data =
-                            EncryptedKeyStore::encrypt(&encrypted_keystore.encryption_key, &data)?;
-                        let mut salt_vec = encrypted_keystore.salt.to_vec();
-                        salt_vec.extend(encrypted_data);
-                        writer.write_all(&salt_vec)?;
+                write_atomically(&persistent_keystore.file_path, |writer| {
+                    match &self.encryption {
+                        Some(encrypted_keystore) => {
+                            // Flush For EncryptedKeyStore
+                            let data = serde_ipld_dagcbor::to_vec(&self.key_info).map_err(|e| {
+                                Error::Other(format!("failed to serialize and write key info: {e}"))
+                            })?;
 
-                        Ok(())
-                    }
-                    None => {
-                        let mut key_info: HashMap<String, PersistentKeyInfo> = HashMap::new();
-                        for (key, value) in self.key_info.iter() {
-                            key_info.insert(
-                                key.to_string(),
-                                PersistentKeyInfo {
-                                    private_key: BASE64_STANDARD.encode(value.private_key.clone()),
-                                    key_type: value.key_type,
-                                },
-                            );
+                            let encrypted_data = EncryptedKeyStore::encrypt(
+                                &encrypted_keystore.encryption_key,
+                                &data,
+                            )?;
+                            writer.write_all(KEYSTORE_MAGIC)?;
+                            writer.write_all(&encrypted_keystore.salt)?;
+                            writer.write_all(&encrypted_data)?;
                         }
+                        None => {
+                            let mut key_info: HashMap<String, PersistentKeyInfo> = HashMap::new();
+                            for (key, value) in self.key_info.iter() {
+                                key_info.insert(
+                                    key.to_string(),
+                                    PersistentKeyInfo {
+                                        private_key: BASE64_STANDARD
+                                            .encode(value.private_key.clone()),
+                                        key_type: value.key_type,
+                                    },
+                                );
+                            }
 
-                        // Flush for PersistentKeyStore
-                        serde_json::to_writer_pretty(writer, &key_info).map_err(|e| {
-                            Error::Other(format!("failed to serialize and write key info: {e}"))
-                        })?;
-
-                        Ok(())
+                            // Flush for PersistentKeyStore
+                            serde_json::to_writer_pretty(writer, &key_info).map_err(|e| {
+                                Error::Other(format!("failed to serialize and write key info: {e}"))
+                            })?;
+                        }
                     }
-                }
+                    Ok(())
+                })
             }
             None => {
                 // NoOp for MemKeyStore
@@ -404,18 +439,34 @@ impl EncryptedKeyStore {
         }
     }
 
+    /// Encrypts `msg` with `AES-256-GCM`, returning the nonce followed by the
+    /// ciphertext.
     fn encrypt(encryption_key: &[u8], msg: &[u8]) -> anyhow::Result<Vec<u8>> {
-        let mut nonce = [0; NONCE_SIZE];
+        let mut nonce = [0; AES_GCM_NONCE_SIZE];
         OsRng.fill_bytes(&mut nonce);
-        let nonce = GenericArray::from_slice(&nonce);
-        let key = GenericArray::from_slice(encryption_key);
-        let cipher = XSalsa20Poly1305::new(key);
-        let mut ciphertext = cipher.encrypt(nonce, msg).map_err(map_err_to_anyhow)?;
-        ciphertext.extend(nonce.iter());
-        Ok(ciphertext)
+        let cipher = Aes256Gcm::new_from_slice(encryption_key).map_err(map_err_to_anyhow)?;
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), msg)
+            .map_err(map_err_to_anyhow)?;
+        let mut out = nonce.to_vec();
+        out.extend(ciphertext);
+        Ok(out)
     }
 
     fn decrypt(encryption_key: &[u8], msg: &[u8]) -> anyhow::Result<Vec<u8>> {
+        anyhow::ensure!(msg.len() >= AES_GCM_NONCE_SIZE, "ciphertext is too short");
+        let (nonce, ciphertext) = msg.split_at(AES_GCM_NONCE_SIZE);
+        let cipher = Aes256Gcm::new_from_slice(encryption_key).map_err(map_err_to_anyhow)?;
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(map_err_to_anyhow)?;
+        Ok(plaintext)
+    }
+
+    /// Decrypts a keystore written with `XSalsa20Poly1305`, where the nonce
+    /// follows the ciphertext.
+    fn decrypt_legacy(encryption_key: &[u8], msg: &[u8]) -> anyhow::Result<Vec<u8>> {
+        anyhow::ensure!(msg.len() >= NONCE_SIZE, "ciphertext is too short");
         let cyphertext_len = msg.len() - NONCE_SIZE;
         let ciphertext = &msg[..cyphertext_len];
         let nonce = GenericArray::from_slice(&msg[cyphertext_len..]);
@@ -428,6 +479,54 @@ impl EncryptedKeyStore {
     }
 }
 
+/// Returns `path` with `suffix` appended to its file name.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_owned();
+    file_name.push(".");
+    file_name.push(suffix);
+    path.with_file_name(file_name)
+}
+
+/// Replaces the file at `path` with the data written by `write`, such that a
+/// crash leaves either the previous or the new version of the file. The data
+/// is written and synced to a temporary file first, which is then renamed over
+/// `path`, so that no copy of removed keys is left behind.
+fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let tmp_path = with_suffix(path, "tmp");
+    let file = File::create(&tmp_path)?;
+
+    // Restrict permissions on files containing private keys
+    #[cfg(unix)]
+    crate::utils::io::set_user_perm(&file)?;
+
+    let mut writer = BufWriter::new(file);
+    write(&mut writer)?;
+    let file = writer.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+
+    fs::rename(&tmp_path, path)?;
+    // Persist the rename.
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Overwrites the file at `path` with zeros before removing it, so that the
+/// cleartext keys it contained don't linger in the freed blocks.
+fn remove_securely(path: &Path) -> std::io::Result<()> {
+    let len = fs::metadata(path)?.len();
+    let mut file = fs::OpenOptions::new().write(true).open(path)?;
+    std::io::copy(&mut std::io::repeat(0).take(len), &mut file)?;
+    file.sync_all()?;
+    drop(file);
+    fs::remove_file(path)
+}
+
 fn map_err_to_anyhow<T: Display>(e: T) -> anyhow::Error {
     anyhow::Error::msg(e.to_string())
 }
@@ -479,8 +578,68 @@ mod test {
     fn test_read_old_encrypted_keystore() {
         let dir: PathBuf = "src/key_management/tests/keystore_encrypted_old".into();
         assert!(dir.exists());
-        let ks = KeyStore::new(KeyStoreConfig::Encrypted(dir, PASSPHRASE.to_string())).unwrap();
+        // Loading migrates the keystore in place, so work on a copy.
+        let keystore_location = tempfile::tempdir().unwrap().into_path();
+        fs::copy(
+            dir.join(ENCRYPTED_KEYSTORE_NAME),
+            keystore_location.join(ENCRYPTED_KEYSTORE_NAME),
+        )
+        .unwrap();
+        let ks = KeyStore::new(KeyStoreConfig::Encrypted(
+            keystore_location.clone(),
+            PASSPHRASE.to_string(),
+        ))
+        .unwrap();
         assert!(ks.persistence.is_some());
+
+        let migrated = fs::read(keystore_location.join(ENCRYPTED_KEYSTORE_NAME)).unwrap();
+        assert!(migrated.starts_with(KEYSTORE_MAGIC));
+        let ks_read = KeyStore::new(KeyStoreConfig::Encrypted(
+            keystore_location,
+            PASSPHRASE.to_string(),
+        ))
+        .unwrap();
+        assert_eq!(ks, ks_read);
+    }
+
+    #[test]
+    fn test_migrate_plaintext_keystore() {
+        let keystore_location = tempfile::tempdir().unwrap().into_path();
+        let mut ks = KeyStore::new(KeyStoreConfig::Persistent(keystore_location.clone())).unwrap();
+        let key = wallet::generate_key(SignatureType::Secp256k1).unwrap();
+        let addr = format!("wallet-{}", key.address);
+        ks.put(&addr, key.key_info.clone()).unwrap();
+
+        let ks_encrypted = KeyStore::new(KeyStoreConfig::Encrypted(
+            keystore_location.clone(),
+            PASSPHRASE.to_string(),
+        ))
+        .unwrap();
+        assert_eq!(ks_encrypted.get(&addr).unwrap(), key.key_info);
+        assert!(!keystore_location.join(KEYSTORE_NAME).exists());
+        assert!(!keystore_location
+            .join(format!("{KEYSTORE_NAME}.bak"))
+            .exists());
+    }
+
+    #[test]
+    fn test_flush_leaves_no_backup() {
+        let keystore_location = tempfile::tempdir().unwrap().into_path();
+        let mut ks = KeyStore::new(KeyStoreConfig::Persistent(keystore_location.clone())).unwrap();
+        let key = wallet::generate_key(SignatureType::Secp256k1).unwrap();
+        let addr = format!("wallet-{}", key.address);
+        ks.put(&addr, key.key_info.clone()).unwrap();
+
+        ks.remove(&addr).unwrap();
+        assert!(!keystore_location
+            .join(format!("{KEYSTORE_NAME}.bak"))
+            .exists());
+        assert!(!keystore_location
+            .join(format!("{KEYSTORE_NAME}.tmp"))
+            .exists());
+
+        let ks_read = KeyStore::new(KeyStoreConfig::Persistent(keystore_location)).unwrap();
+        assert!(ks_read.get(&addr).is_err());
     }
 
     #[test]
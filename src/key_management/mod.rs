@@ -3,6 +3,7 @@
 
 mod errors;
 mod keystore;
+pub mod os_keyring;
 mod wallet;
 mod wallet_helpers;
 
@@ -0,0 +1,37 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Keeps the keystore passphrase in the operating system's credential store
+//! (Keychain, Secret Service, Windows Credential Manager) so that an encrypted
+//! keystore can be unlocked without prompting.
+
+#[cfg(feature = "os-keyring")]
+const SERVICE: &str = "forest";
+#[cfg(feature = "os-keyring")]
+const USER: &str = "keystore";
+
+/// Returns the stored passphrase, or `None` if there is none.
+#[cfg(feature = "os-keyring")]
+pub fn load_passphrase() -> anyhow::Result<Option<String>> {
+    match keyring::Entry::new(SERVICE, USER)?.get_password() {
+        Ok(passphrase) => Ok(Some(passphrase)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(feature = "os-keyring")]
+pub fn store_passphrase(passphrase: &str) -> anyhow::Result<()> {
+    keyring::Entry::new(SERVICE, USER)?.set_password(passphrase)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "os-keyring"))]
+pub fn load_passphrase() -> anyhow::Result<Option<String>> {
+    anyhow::bail!("Forest was built without the `os-keyring` feature")
+}
+
+#[cfg(not(feature = "os-keyring"))]
+pub fn store_passphrase(_passphrase: &str) -> anyhow::Result<()> {
+    anyhow::bail!("Forest was built without the `os-keyring` feature")
+}
@@ -472,4 +472,28 @@ mod tests {
         let invalid_addr = wallet.generate_addr(SignatureType::Bls).unwrap();
         assert!(sig.verify(&msg, &invalid_addr).is_err())
     }
+
+    #[test]
+    fn delegated_key_test() {
+        // The well-known Ethereum account for private key `1`.
+        let mut priv_key = vec![0u8; 32];
+        priv_key[31] = 1;
+        let key = Key::try_from(KeyInfo::new(SignatureType::Delegated, priv_key)).unwrap();
+        let eth_addr = hex::decode("7e5f4552091a69125d5dfcb7b8c2659029395bdf").unwrap();
+        assert_eq!(
+            key.address,
+            Address::new_delegated(
+                Address::ETHEREUM_ACCOUNT_MANAGER_ACTOR.id().unwrap(),
+                &eth_addr
+            )
+            .unwrap()
+        );
+
+        let addr = key.address;
+        let key_store = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let mut wallet = Wallet::new_from_keys(key_store, vec![key.clone()]);
+        let sig = wallet.sign(&addr, &[0u8; 64]).unwrap();
+        assert_eq!(sig.signature_type(), SignatureType::Delegated);
+        assert_eq!(sig.bytes().len(), 65);
+    }
 }
@@ -36,13 +36,16 @@ mod blocks;
 mod chain;
 mod chain_sync;
 mod cid_collections;
+#[cfg(feature = "tools")]
 mod cli;
 mod cli_shared;
 mod daemon;
 mod db;
 mod documentation;
+mod eth;
 mod fil_cns;
 mod genesis;
+mod health;
 mod interpreter;
 mod ipld;
 mod key_management;
@@ -53,6 +56,7 @@ mod message;
 mod message_pool;
 mod metrics;
 mod networks;
+#[cfg(feature = "rpc")]
 mod rpc;
 mod rpc_api;
 mod rpc_client;
@@ -62,8 +66,10 @@ mod state_migration;
 mod statediff;
 #[cfg(test)]
 mod test_utils;
+#[cfg(feature = "tools")]
 mod tool;
 mod utils;
+#[cfg(feature = "tools")]
 mod wallet;
 
 /// These items are semver-exempt, and exist for forest author use only
@@ -100,13 +106,16 @@ pub mod benchmark_private {
 
 // These should be made private in https://github.com/ChainSafe/forest/issues/3013
 pub use auth::{verify_token, JWT_IDENTIFIER};
+#[cfg(feature = "tools")]
 pub use cli::main::main as forest_main;
 pub use cli_shared::cli::{Client, Config};
 pub use daemon::main::main as forestd_main;
 pub use key_management::{
     KeyStore, KeyStoreConfig, ENCRYPTED_KEYSTORE_NAME, FOREST_KEYSTORE_PHRASE_ENV, KEYSTORE_NAME,
 };
+#[cfg(feature = "tools")]
 pub use tool::main::main as forest_tool_main;
+#[cfg(feature = "tools")]
 pub use wallet::main::main as forest_wallet_main;
 
 #[cfg(test)]
@@ -5,7 +5,9 @@ use crate::libp2p_bitswap::BitswapBehaviour;
 use crate::utils::{encoding::blake2b_256, version::FOREST_VERSION_STRING};
 use ahash::{HashMap, HashSet};
 use libp2p::{
-    allow_block_list, connection_limits,
+    allow_block_list,
+    autonat::NatStatus,
+    connection_limits,
     gossipsub::{
         self, IdentTopic as Topic, MessageAuthenticity, MessageId, PublishError, SubscriptionError,
         ValidationMode,
@@ -13,9 +15,9 @@ use libp2p::{
     identity::{Keypair, PeerId},
     kad::QueryId,
     metrics::{Metrics, Recorder},
-    ping,
-    swarm::NetworkBehaviour,
-    Multiaddr,
+    ping, relay,
+    swarm::{behaviour::toggle::Toggle, NetworkBehaviour},
+    upnp, Multiaddr,
 };
 use tracing::info;
 
@@ -23,6 +25,7 @@ use crate::libp2p::{
     chain_exchange::ChainExchangeBehaviour,
     config::Libp2pConfig,
     discovery::{DiscoveryBehaviour, DiscoveryConfig},
+    gater::ConnectionGater,
     gossip_params::{build_peer_score_params, build_peer_score_threshold},
     hello::HelloBehaviour,
 };
@@ -33,14 +36,17 @@ use super::discovery::{DerivedDiscoveryBehaviourEvent, DiscoveryEvent};
 /// for a Filecoin node.
 #[derive(NetworkBehaviour)]
 pub(in crate::libp2p) struct ForestBehaviour {
-    gossipsub: gossipsub::Behaviour,
+    pub(super) gossipsub: gossipsub::Behaviour,
     discovery: DiscoveryBehaviour,
     ping: ping::Behaviour,
     connection_limits: connection_limits::Behaviour,
     pub(super) blocked_peers: allow_block_list::Behaviour<allow_block_list::BlockedPeers>,
+    pub(super) gater: ConnectionGater,
     pub(super) hello: HelloBehaviour,
     pub(super) chain_exchange: ChainExchangeBehaviour,
     pub(super) bitswap: BitswapBehaviour,
+    upnp: Toggle<upnp::tokio::Behaviour>,
+    relay_client: relay::client::Behaviour,
 }
 
 impl Recorder<ForestBehaviourEvent> for Metrics {
@@ -63,10 +69,12 @@ impl ForestBehaviour {
         local_key: &Keypair,
         config: &Libp2pConfig,
         network_name: &str,
+        relay_client: relay::client::Behaviour,
     ) -> anyhow::Result<Self> {
         let mut gs_config_builder = gossipsub::ConfigBuilder::default();
         gs_config_builder.max_transmit_size(1 << 20);
         gs_config_builder.validation_mode(ValidationMode::Strict);
+        gs_config_builder.validate_messages();
         gs_config_builder.message_id_fn(|msg: &gossipsub::Message| {
             let s = blake2b_256(&msg.data);
             MessageId::from(s)
@@ -81,7 +89,7 @@ impl ForestBehaviour {
 
         gossipsub
             .with_peer_score(
-                build_peer_score_params(network_name),
+                build_peer_score_params(config.gossip_network_name(network_name)),
                 build_peer_score_threshold(),
             )
             .unwrap();
@@ -94,13 +102,18 @@ impl ForestBehaviour {
                 "/chain/ipfs/bitswap",
             ],
             Default::default(),
-        );
+        )
+        .with_server_config(config.bitswap_server.clone());
         crate::libp2p_bitswap::register_metrics(&mut crate::metrics::DEFAULT_REGISTRY.write());
 
         let discovery = DiscoveryConfig::new(local_key.public(), network_name)
             .with_mdns(config.mdns)
             .with_kademlia(config.kademlia)
-            .with_user_defined(config.bootstrap_peers.clone())?
+            .with_user_defined(if config.bootstrap {
+                config.bootstrap_peers.clone()
+            } else {
+                vec![]
+            })?
             .target_peer_count(config.target_peer_count as u64)
             .finish()?;
 
@@ -137,9 +150,13 @@ impl ForestBehaviour {
             ping: Default::default(),
             connection_limits,
             blocked_peers: Default::default(),
+            gater: Default::default(),
             bitswap,
             hello: HelloBehaviour::default(),
-            chain_exchange: ChainExchangeBehaviour::default(),
+            chain_exchange: ChainExchangeBehaviour::default()
+                .with_max_request_len(config.chain_exchange_max_request_len),
+            upnp: config.upnp.then(upnp::tokio::Behaviour::default).into(),
+            relay_client,
         })
     }
 
@@ -162,6 +179,16 @@ impl ForestBehaviour {
         self.gossipsub.subscribe(topic)
     }
 
+    /// Starts a discovery round right away.
+    pub fn discover_now(&mut self) {
+        self.discovery.discover_now()
+    }
+
+    /// Sets the number of connected peers to pause discovery on.
+    pub fn set_target_peer_count(&mut self, target_peer_count: u64) {
+        self.discovery.set_target_peer_count(target_peer_count)
+    }
+
     /// Returns a set of peer ids
     pub fn peers(&self) -> &HashSet<PeerId> {
         self.discovery.peers()
@@ -171,4 +198,26 @@ impl ForestBehaviour {
     pub fn peer_addresses(&mut self) -> &HashMap<PeerId, HashSet<Multiaddr>> {
         self.discovery.peer_addresses()
     }
+
+    /// Returns the reachability of the node, as probed by `AutoNAT`.
+    pub fn nat_status(&self) -> NatStatus {
+        self.discovery.nat_status()
+    }
+
+    /// Exports the number of peers in the `Gossipsub` mesh of each subscribed topic.
+    pub fn update_gossip_mesh_metrics(&self) {
+        for topic in self.gossipsub.topics() {
+            crate::libp2p::metrics::GOSSIP_MESH_PEERS
+                .with_label_values(&[topic.as_str()])
+                .set(self.gossipsub.mesh_peers(topic).count() as u64);
+        }
+    }
+
+    /// Returns the `Gossipsub` scores of the known peers.
+    pub fn gossip_scores(&self) -> Vec<(PeerId, f64)> {
+        self.gossipsub
+            .all_peers()
+            .filter_map(|(peer, _)| Some((*peer, self.gossipsub.peer_score(peer)?)))
+            .collect()
+    }
 }
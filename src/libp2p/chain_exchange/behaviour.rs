@@ -22,9 +22,21 @@ pub struct ChainExchangeBehaviour {
         OutboundRequestId,
         flume::Sender<Result<ChainExchangeResponse, RequestResponseError>>,
     >,
+    max_request_len: u64,
 }
 
 impl ChainExchangeBehaviour {
+    /// Sets the maximum number of tipsets served for a single request.
+    pub fn with_max_request_len(mut self, max_request_len: u64) -> Self {
+        self.max_request_len = max_request_len;
+        self
+    }
+
+    /// Maximum number of tipsets served for a single request.
+    pub fn max_request_len(&self) -> u64 {
+        self.max_request_len
+    }
+
     pub fn send_request(
         &mut self,
         peer: &PeerId,
@@ -89,6 +101,7 @@ impl Default for ChainExchangeBehaviour {
                 Default::default(),
             ),
             response_channels: Default::default(),
+            max_request_len: DEFAULT_MAX_REQUEST_LEN,
         }
     }
 }
@@ -66,6 +66,22 @@ pub enum ChainExchangeResponseStatus {
     Other(#[cfg_attr(test, arbitrary(gen(|_| 1)))] i32),
 }
 
+impl ChainExchangeResponseStatus {
+    /// Name of the status, e.g. for metric labels.
+    pub fn as_str(&self) -> &'static str {
+        use ChainExchangeResponseStatus::*;
+        match self {
+            Success => "success",
+            PartialResponse => "partial_response",
+            BlockNotFound => "block_not_found",
+            GoAway => "go_away",
+            InternalError => "internal_error",
+            BadRequest => "bad_request",
+            Other(_) => "other",
+        }
+    }
+}
+
 impl Serialize for ChainExchangeResponseStatus {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -13,10 +13,16 @@ use super::{
     TipsetBundle,
 };
 
-/// Builds chain exchange response out of chain data.
+/// Default maximum number of tipsets served for a single request, matching
+/// Lotus.
+pub const DEFAULT_MAX_REQUEST_LEN: u64 = 800;
+
+/// Builds chain exchange response out of chain data. Requests for more than
+/// `max_request_len` tipsets are rejected.
 pub fn make_chain_exchange_response<DB>(
     cs: &ChainStore<DB>,
     request: &ChainExchangeRequest,
+    max_request_len: u64,
 ) -> ChainExchangeResponse
 where
     DB: Blockstore + Send + Sync + 'static,
@@ -28,6 +34,16 @@ where
             message: format!("Invalid options {}", request.options),
         };
     }
+    if request.request_len == 0 || request.request_len > max_request_len {
+        return ChainExchangeResponse {
+            chain: Default::default(),
+            status: ChainExchangeResponseStatus::BadRequest,
+            message: format!(
+                "Invalid request length {}, must be between 1 and {max_request_len}",
+                request.request_len
+            ),
+        };
+    }
 
     let inner = move || {
         let root = match cs.load_tipset(&TipsetKey::from_iter(request.start.clone()))? {
@@ -179,6 +195,7 @@ mod tests {
                 request_len: 2,
                 options: HEADERS | MESSAGES,
             },
+            DEFAULT_MAX_REQUEST_LEN,
         );
 
         // The response will be loaded with tipsets 39 and 38.
@@ -230,4 +247,29 @@ mod tests {
         assert_eq!(ts_38_msgs.secp_msg_includes[1].len(), 1);
         assert_eq!(ts_38_msgs.bls_msg_includes[1].len(), 11);
     }
+
+    #[tokio::test]
+    async fn request_len_is_bounded() {
+        let (cids, db) = populate_db().await;
+        let gen_block = CachingBlockHeader::new(RawBlockHeader {
+            miner_address: Address::new_id(0),
+            ..Default::default()
+        });
+        let cs =
+            ChainStore::new(db.clone(), db, Arc::new(ChainConfig::default()), gen_block).unwrap();
+        let request = |request_len| ChainExchangeRequest {
+            start: cids.clone(),
+            request_len,
+            options: HEADERS,
+        };
+
+        for request_len in [0, 3] {
+            let response = make_chain_exchange_response(&cs, &request(request_len), 2);
+            assert_eq!(response.status, ChainExchangeResponseStatus::BadRequest);
+            assert!(response.chain.is_empty());
+        }
+        let response = make_chain_exchange_response(&cs, &request(2), 2);
+        assert_eq!(response.status, ChainExchangeResponseStatus::Success);
+        assert_eq!(response.chain.len(), 2);
+    }
 }
@@ -1,6 +1,9 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::libp2p::chain_exchange::DEFAULT_MAX_REQUEST_LEN;
+use crate::libp2p_bitswap::BitswapServerConfig;
+use crate::utils::outbound::OutboundConfig;
 use libp2p::Multiaddr;
 use serde::{Deserialize, Serialize};
 #[cfg(test)]
@@ -27,12 +30,41 @@ pub struct Libp2pConfig {
         |g| vec![Ipv4Addr::arbitrary(g).into()]
     )))]
     pub bootstrap_peers: Vec<Multiaddr>,
+    /// Dial the bootstrap peers. Without them, peers are only discovered with MDNS, or from the
+    /// peers known from previous runs.
+    pub bootstrap: bool,
     /// MDNS discovery enabled.
     pub mdns: bool,
     /// Kademlia discovery enabled.
     pub kademlia: bool,
     /// Target peer count.
     pub target_peer_count: u32,
+    /// Network name used in the `Gossipsub` topics, overriding the name of the network from the
+    /// genesis.
+    pub gossip_network_name: Option<String>,
+    /// Retry policies of the outbound HTTP requests.
+    pub outbound: OutboundConfig,
+    /// Serving of the blocks of the local store to peers over `bitswap`.
+    pub bitswap_server: BitswapServerConfig,
+    /// Maximum number of tipsets served for a single `ChainExchange` request.
+    pub chain_exchange_max_request_len: u64,
+    /// Map the listening ports on the gateway of the local network with `UPnP`, to be reachable
+    /// behind a NAT.
+    pub upnp: bool,
+    /// Circuit relay v2 addresses (with the `/p2p/` protocol) to reserve a slot on and listen
+    /// through, to be reachable behind a NAT that can't be traversed otherwise.
+    #[cfg_attr(test, arbitrary(gen(
+        |g| vec![Ipv4Addr::arbitrary(g).into()]
+    )))]
+    pub relay_addresses: Vec<Multiaddr>,
+}
+
+impl Libp2pConfig {
+    /// Returns the network name used in the `Gossipsub` topics, given the name of the network
+    /// from the genesis.
+    pub fn gossip_network_name<'a>(&'a self, network_name: &'a str) -> &'a str {
+        self.gossip_network_name.as_deref().unwrap_or(network_name)
+    }
 }
 
 impl Default for Libp2pConfig {
@@ -40,9 +72,32 @@ impl Default for Libp2pConfig {
         Self {
             listening_multiaddrs: vec!["/ip4/0.0.0.0/tcp/0".parse().expect("Infallible")],
             bootstrap_peers: vec![],
+            bootstrap: true,
             mdns: false,
             kademlia: true,
             target_peer_count: 75,
+            gossip_network_name: None,
+            outbound: OutboundConfig::default(),
+            bitswap_server: BitswapServerConfig::default(),
+            chain_exchange_max_request_len: DEFAULT_MAX_REQUEST_LEN,
+            upnp: false,
+            relay_addresses: vec![],
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gossip_network_name_defaults_to_genesis_network_name() {
+        let mut config = Libp2pConfig::default();
+        assert_eq!(
+            config.gossip_network_name("calibrationnet"),
+            "calibrationnet"
+        );
+        config.gossip_network_name = Some("localnet".into());
+        assert_eq!(config.gossip_network_name("calibrationnet"), "localnet");
+    }
+}
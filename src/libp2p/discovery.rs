@@ -230,6 +230,11 @@ impl DiscoveryBehaviour {
         &self.peer_addresses
     }
 
+    /// Returns the reachability of the node, as probed by `AutoNAT`.
+    pub fn nat_status(&self) -> autonat::NatStatus {
+        self.discovery.autonat.nat_status()
+    }
+
     /// Bootstrap Kademlia network
     pub fn bootstrap(&mut self) -> Result<kad::QueryId, String> {
         if let Some(active_kad) = self.discovery.kademlia.as_mut() {
@@ -247,6 +252,24 @@ impl DiscoveryBehaviour {
             Err("Kademlia is not activated".to_string())
         }
     }
+
+    /// Starts a discovery round right away: bootstraps Kademlia (or dials the seed peers when
+    /// it's disabled), queries random peers, and resets the delay between the next queries.
+    pub fn discover_now(&mut self) {
+        if let Err(e) = self.bootstrap() {
+            debug!("Kademlia bootstrap skipped: {e}");
+        }
+        if let Some(kademlia) = self.discovery.kademlia.as_mut() {
+            kademlia.get_closest_peers(PeerId::random());
+        }
+        self.duration_to_next_kad = Duration::from_secs(1);
+        self.next_kad_random_query = tokio::time::interval(self.duration_to_next_kad);
+    }
+
+    /// Sets the number of connected peers to pause discovery on.
+    pub fn set_target_peer_count(&mut self, target_peer_count: u64) {
+        self.target_peer_count = target_peer_count;
+    }
 }
 
 impl NetworkBehaviour for DiscoveryBehaviour {
@@ -0,0 +1,347 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Connection gater enforcing the peer protections and blocks set by the node
+//! operator, e.g. with `Filecoin.NetBlockAdd`.
+//!
+//! Blocked peers, IP addresses and subnets can't connect to the node, nor be
+//! dialed, and their existing connections are closed. Protected peers are
+//! never banned by the node for misbehaving, which allows pinning peering with
+//! trusted infrastructure. Blocks take precedence over protections. Both are
+//! persisted in the settings store.
+
+use std::{
+    collections::VecDeque,
+    convert::Infallible,
+    net::IpAddr,
+    task::{Context, Poll, Waker},
+};
+
+use ahash::{HashMap, HashSet};
+use ipnet::IpNet;
+use libp2p::{
+    core::Endpoint,
+    multiaddr::Protocol,
+    swarm::{
+        dummy, CloseConnection, ConnectionDenied, ConnectionId, FromSwarm, NetworkBehaviour,
+        THandler, THandlerInEvent, THandlerOutEvent, ToSwarm,
+    },
+    Multiaddr, PeerId,
+};
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+
+use crate::db::{
+    setting_keys::{NET_BLOCK_LIST_KEY, NET_PROTECTED_PEERS_KEY},
+    SettingsStore, SettingsStoreExt,
+};
+use crate::lotus_json::lotus_json_with_self;
+
+/// Peers, IP addresses and subnets that are refused connections, matching the
+/// `NetBlockList` of Lotus.
+#[serde_as]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BlockList {
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default)]
+    pub peers: Vec<PeerId>,
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default, rename = "IPAddrs")]
+    pub ip_addrs: Vec<IpAddr>,
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default, rename = "IPSubnets")]
+    pub ip_subnets: Vec<IpNet>,
+}
+
+lotus_json_with_self!(BlockList);
+
+#[derive(Debug, thiserror::Error)]
+#[error("connection refused by the block list")]
+pub struct Blocked;
+
+#[derive(Default)]
+pub(in crate::libp2p) struct ConnectionGater {
+    protected: HashSet<PeerId>,
+    blocked_peers: HashSet<PeerId>,
+    blocked_ips: HashSet<IpAddr>,
+    blocked_subnets: HashSet<IpNet>,
+    /// Established connections, with the IP address of the remote end.
+    connections: HashMap<ConnectionId, (PeerId, Option<IpAddr>)>,
+    /// Connections to close after a block.
+    to_close: VecDeque<(PeerId, ConnectionId)>,
+    waker: Option<Waker>,
+}
+
+impl ConnectionGater {
+    /// Loads the protections and blocks persisted in the settings store.
+    pub fn load(settings: &(impl SettingsStore + ?Sized)) -> anyhow::Result<Self> {
+        let mut gater = Self::default();
+        if let Some(protected) = settings.read_obj::<ProtectedPeers>(NET_PROTECTED_PEERS_KEY)? {
+            gater.protect(protected.0);
+        }
+        if let Some(block_list) = settings.read_obj::<BlockList>(NET_BLOCK_LIST_KEY)? {
+            gater.block(block_list);
+        }
+        Ok(gater)
+    }
+
+    /// Persists the protections and blocks in the settings store.
+    pub fn save(&self, settings: &(impl SettingsStore + ?Sized)) -> anyhow::Result<()> {
+        settings.write_obj(NET_PROTECTED_PEERS_KEY, &ProtectedPeers(self.protected()))?;
+        settings.write_obj(NET_BLOCK_LIST_KEY, &self.block_list())
+    }
+
+    pub fn protect(&mut self, peers: impl IntoIterator<Item = PeerId>) {
+        self.protected.extend(peers);
+    }
+
+    pub fn unprotect(&mut self, peers: &[PeerId]) {
+        for peer in peers {
+            self.protected.remove(peer);
+        }
+    }
+
+    pub fn is_protected(&self, peer: &PeerId) -> bool {
+        self.protected.contains(peer)
+    }
+
+    pub fn protected(&self) -> Vec<PeerId> {
+        let mut protected: Vec<_> = self.protected.iter().copied().collect();
+        protected.sort();
+        protected
+    }
+
+    /// Adds to the block list, closing the connections it matches.
+    pub fn block(&mut self, block_list: BlockList) {
+        self.blocked_peers.extend(block_list.peers);
+        self.blocked_ips.extend(block_list.ip_addrs);
+        self.blocked_subnets.extend(block_list.ip_subnets);
+
+        let to_close: Vec<_> = self
+            .connections
+            .iter()
+            .filter(|(_, (peer, ip))| self.is_blocked(Some(peer), *ip))
+            .map(|(id, (peer, _))| (*peer, *id))
+            .collect();
+        if !to_close.is_empty() {
+            self.to_close.extend(to_close);
+            if let Some(waker) = self.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    pub fn unblock(&mut self, block_list: &BlockList) {
+        for peer in &block_list.peers {
+            self.blocked_peers.remove(peer);
+        }
+        for ip in &block_list.ip_addrs {
+            self.blocked_ips.remove(ip);
+        }
+        for subnet in &block_list.ip_subnets {
+            self.blocked_subnets.remove(subnet);
+        }
+    }
+
+    pub fn block_list(&self) -> BlockList {
+        let mut block_list = BlockList {
+            peers: self.blocked_peers.iter().copied().collect(),
+            ip_addrs: self.blocked_ips.iter().copied().collect(),
+            ip_subnets: self.blocked_subnets.iter().copied().collect(),
+        };
+        block_list.peers.sort();
+        block_list.ip_addrs.sort();
+        block_list.ip_subnets.sort();
+        block_list
+    }
+
+    fn is_blocked(&self, peer: Option<&PeerId>, ip: Option<IpAddr>) -> bool {
+        peer.is_some_and(|peer| self.blocked_peers.contains(peer))
+            || ip.is_some_and(|ip| {
+                self.blocked_ips.contains(&ip)
+                    || self
+                        .blocked_subnets
+                        .iter()
+                        .any(|subnet| subnet.contains(&ip))
+            })
+    }
+
+    fn enforce(&self, peer: Option<&PeerId>, addr: &Multiaddr) -> Result<(), ConnectionDenied> {
+        if self.is_blocked(peer, ip_addr(addr)) {
+            Err(ConnectionDenied::new(Blocked))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Protected peers, as persisted in the settings store.
+#[serde_as]
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+struct ProtectedPeers(#[serde_as(as = "Vec<DisplayFromStr>")] Vec<PeerId>);
+
+fn ip_addr(addr: &Multiaddr) -> Option<IpAddr> {
+    addr.iter().find_map(|protocol| match protocol {
+        Protocol::Ip4(ip) => Some(IpAddr::V4(ip)),
+        Protocol::Ip6(ip) => Some(IpAddr::V6(ip)),
+        _ => None,
+    })
+}
+
+impl NetworkBehaviour for ConnectionGater {
+    type ConnectionHandler = dummy::ConnectionHandler;
+    type ToSwarm = Infallible;
+
+    fn handle_pending_inbound_connection(
+        &mut self,
+        _connection_id: ConnectionId,
+        _local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<(), ConnectionDenied> {
+        self.enforce(None, remote_addr)
+    }
+
+    fn handle_established_inbound_connection(
+        &mut self,
+        _connection_id: ConnectionId,
+        peer: PeerId,
+        _local_addr: &Multiaddr,
+        remote_addr: &Multiaddr,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.enforce(Some(&peer), remote_addr)?;
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn handle_pending_outbound_connection(
+        &mut self,
+        _connection_id: ConnectionId,
+        maybe_peer: Option<PeerId>,
+        _addresses: &[Multiaddr],
+        _effective_role: Endpoint,
+    ) -> Result<Vec<Multiaddr>, ConnectionDenied> {
+        if self.is_blocked(maybe_peer.as_ref(), None) {
+            return Err(ConnectionDenied::new(Blocked));
+        }
+        Ok(vec![])
+    }
+
+    fn handle_established_outbound_connection(
+        &mut self,
+        _connection_id: ConnectionId,
+        peer: PeerId,
+        addr: &Multiaddr,
+        _role_override: Endpoint,
+    ) -> Result<THandler<Self>, ConnectionDenied> {
+        self.enforce(Some(&peer), addr)?;
+        Ok(dummy::ConnectionHandler)
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm) {
+        match event {
+            FromSwarm::ConnectionEstablished(e) => {
+                self.connections.insert(
+                    e.connection_id,
+                    (e.peer_id, ip_addr(e.endpoint.get_remote_address())),
+                );
+            }
+            FromSwarm::ConnectionClosed(e) => {
+                self.connections.remove(&e.connection_id);
+            }
+            _ => {}
+        }
+    }
+
+    fn on_connection_handler_event(
+        &mut self,
+        _peer_id: PeerId,
+        _connection_id: ConnectionId,
+        event: THandlerOutEvent<Self>,
+    ) {
+        match event {}
+    }
+
+    fn poll(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<ToSwarm<Self::ToSwarm, THandlerInEvent<Self>>> {
+        if let Some((peer_id, connection_id)) = self.to_close.pop_front() {
+            return Poll::Ready(ToSwarm::CloseConnection {
+                peer_id,
+                connection: CloseConnection::One(connection_id),
+            });
+        }
+        self.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+
+    #[test]
+    fn block_list_matches_peers_ips_and_subnets() {
+        let mut gater = ConnectionGater::default();
+        let peer = PeerId::random();
+        gater.block(BlockList {
+            peers: vec![peer],
+            ip_addrs: vec!["1.2.3.4".parse().unwrap()],
+            ip_subnets: vec!["10.0.0.0/8".parse().unwrap()],
+        });
+
+        let addr = |s: &str| s.parse::<Multiaddr>().unwrap();
+        assert!(gater
+            .enforce(Some(&peer), &addr("/ip4/8.8.8.8/tcp/1"))
+            .is_err());
+        assert!(gater.enforce(None, &addr("/ip4/1.2.3.4/tcp/1")).is_err());
+        assert!(gater.enforce(None, &addr("/ip4/10.1.2.3/tcp/1")).is_err());
+        assert!(gater
+            .enforce(Some(&PeerId::random()), &addr("/ip4/8.8.8.8/tcp/1"))
+            .is_ok());
+        assert!(gater
+            .enforce(None, &addr("/dns4/example.com/tcp/1"))
+            .is_ok());
+
+        gater.unblock(&BlockList {
+            ip_subnets: vec!["10.0.0.0/8".parse().unwrap()],
+            ..Default::default()
+        });
+        assert!(gater.enforce(None, &addr("/ip4/10.1.2.3/tcp/1")).is_ok());
+    }
+
+    #[test]
+    fn gater_round_trip() {
+        let db = MemoryDB::default();
+        let mut gater = ConnectionGater::default();
+        let peer = PeerId::random();
+        gater.protect([peer]);
+        gater.block(BlockList {
+            ip_subnets: vec!["fd00::/8".parse().unwrap()],
+            ..Default::default()
+        });
+        gater.save(&db).unwrap();
+
+        let loaded = ConnectionGater::load(&db).unwrap();
+        assert!(loaded.is_protected(&peer));
+        assert_eq!(loaded.block_list(), gater.block_list());
+    }
+
+    #[test]
+    fn block_list_is_lotus_compatible() {
+        let block_list: BlockList = serde_json::from_str(
+            r#"{"Peers":[],"IPAddrs":["1.2.3.4"],"IPSubnets":["10.0.0.0/8"]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            block_list.ip_addrs,
+            vec!["1.2.3.4".parse::<IpAddr>().unwrap()]
+        );
+        assert_eq!(
+            serde_json::to_value(&block_list).unwrap(),
+            serde_json::json!({"Peers": [], "IPAddrs": ["1.2.3.4"], "IPSubnets": ["10.0.0.0/8"]})
+        );
+    }
+}
@@ -7,13 +7,11 @@ use libp2p::gossipsub::{
     score_parameter_decay, IdentTopic, PeerScoreParams, PeerScoreThresholds, TopicScoreParams,
 };
 
-use crate::libp2p::{PUBSUB_BLOCK_STR, PUBSUB_MSG_STR};
+use crate::libp2p::{pubsub_block_topic, pubsub_msg_topic};
 
 // All these parameters are copied from what Lotus has set for their Topic
-// scores. They are currently unused because enabling them causes GossipSub
-// blocks to come delayed usually by 1 second compared to when we have these
-// parameters disabled. Leaving these here so that we can enable and fix these
-// parameters when they are needed.
+// scores. Invalid message deliveries are penalized from the validation results
+// reported by the service for every gossip message.
 
 fn build_msg_topic_config() -> TopicScoreParams {
     TopicScoreParams {
@@ -78,15 +76,15 @@ fn build_block_topic_config() -> TopicScoreParams {
     }
 }
 
-pub(in crate::libp2p) fn build_peer_score_params(network_name: &str) -> PeerScoreParams {
+pub(in crate::libp2p) fn build_peer_score_params(gossip_network_name: &str) -> PeerScoreParams {
     #[allow(clippy::disallowed_types)]
     let mut psp_topics = std::collections::HashMap::new();
 
     // msg topic
-    let msg_topic = IdentTopic::new(format!("{PUBSUB_MSG_STR}/{network_name}"));
+    let msg_topic = IdentTopic::new(pubsub_msg_topic(gossip_network_name));
     psp_topics.insert(msg_topic.hash(), build_msg_topic_config());
     // block topic
-    let block_topic = IdentTopic::new(format!("{PUBSUB_BLOCK_STR}/{network_name}"));
+    let block_topic = IdentTopic::new(pubsub_block_topic(gossip_network_name));
     psp_topics.insert(block_topic.hash(), build_block_topic_config());
 
     PeerScoreParams {
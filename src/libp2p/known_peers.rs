@@ -0,0 +1,64 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Peers that served the node well, persisted in the settings store so that
+//! they can be dialed first on restart, rather than rediscovering peers from
+//! the bootstrap nodes only.
+
+use std::time::Duration;
+
+use libp2p::{multiaddr::Protocol, Multiaddr, PeerId};
+
+use crate::db::{setting_keys::KNOWN_PEERS_KEY, SettingsStore, SettingsStoreExt};
+
+/// Time between two saves of the known peers.
+pub(in crate::libp2p) const KNOWN_PEERS_SAVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Maximum number of peers saved, the best scored ones.
+const MAX_KNOWN_PEERS: usize = 50;
+
+/// Returns the addresses of the peers known from previous runs, with their
+/// `/p2p` suffix.
+pub(in crate::libp2p) fn load_known_peers(
+    settings: &(impl SettingsStore + ?Sized),
+) -> anyhow::Result<Vec<Multiaddr>> {
+    Ok(settings
+        .read_obj::<Vec<Multiaddr>>(KNOWN_PEERS_KEY)?
+        .unwrap_or_default())
+}
+
+/// Saves the addresses of the given peers, in order of preference.
+pub(in crate::libp2p) fn save_known_peers<'a>(
+    settings: &(impl SettingsStore + ?Sized),
+    peers: impl IntoIterator<Item = (PeerId, impl IntoIterator<Item = &'a Multiaddr>)>,
+) -> anyhow::Result<()> {
+    let addrs: Vec<_> = peers
+        .into_iter()
+        .take(MAX_KNOWN_PEERS)
+        .flat_map(|(peer, addrs)| {
+            addrs
+                .into_iter()
+                .map(move |addr| addr.clone().with(Protocol::P2p(peer)))
+        })
+        .collect();
+    settings.write_obj(KNOWN_PEERS_KEY, &addrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+
+    #[test]
+    fn known_peers_round_trip() {
+        let db = MemoryDB::default();
+        assert!(load_known_peers(&db).unwrap().is_empty());
+
+        let peer = PeerId::random();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/1234".parse().unwrap();
+        save_known_peers(&db, [(peer, [&addr])]).unwrap();
+        assert_eq!(
+            load_known_peers(&db).unwrap(),
+            vec![addr.with(Protocol::P2p(peer))]
+        );
+    }
+}
@@ -1,8 +1,12 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use ahash::HashMap;
+use libp2p::{swarm::DialError, PeerId};
 use once_cell::sync::Lazy;
-use prometheus::core::{AtomicU64, GenericCounter, GenericGauge};
+use prometheus::core::{
+    AtomicU64, GenericCounter, GenericCounterVec, GenericGauge, GenericGaugeVec, Opts,
+};
 
 pub static PEER_FAILURE_TOTAL: Lazy<Box<GenericCounter<AtomicU64>>> = Lazy::new(|| {
     let peer_failure_total = Box::new(
@@ -40,3 +44,193 @@ pub static BAD_PEERS: Lazy<Box<GenericGauge<AtomicU64>>> = Lazy::new(|| {
         .expect("Registering the bad_peers metric with the metrics registry must succeed");
     bad_peers
 });
+pub static CHAIN_EXCHANGE_SERVED_REQUESTS: Lazy<Box<GenericCounterVec<AtomicU64>>> = Lazy::new(
+    || {
+        let served_requests = Box::new(
+            GenericCounterVec::<AtomicU64>::new(
+                Opts::new(
+                    "chain_exchange_served_requests",
+                    "Number of ChainExchange requests served to peers, by response status",
+                ),
+                &["status"],
+            )
+            .expect("Defining the chain_exchange_served_requests metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(served_requests.clone())
+            .expect(
+                "Registering the chain_exchange_served_requests metric with the metrics registry must succeed",
+            );
+        served_requests
+    },
+);
+pub static CHAIN_EXCHANGE_SERVED_TIPSETS: Lazy<Box<GenericCounter<AtomicU64>>> = Lazy::new(|| {
+    let served_tipsets = Box::new(
+        GenericCounter::<AtomicU64>::new(
+            "chain_exchange_served_tipsets",
+            "Number of tipsets served to peers over ChainExchange",
+        )
+        .expect("Defining the chain_exchange_served_tipsets metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(served_tipsets.clone())
+        .expect(
+            "Registering the chain_exchange_served_tipsets metric with the metrics registry must succeed",
+        );
+    served_tipsets
+});
+pub static GOSSIP_VALIDATION_RESULTS: Lazy<Box<GenericCounterVec<AtomicU64>>> = Lazy::new(|| {
+    let validation_results = Box::new(
+        GenericCounterVec::<AtomicU64>::new(
+            Opts::new(
+                "gossip_validation_results",
+                "Number of gossip messages validated, by topic, result and reason",
+            ),
+            &["topic", "result", "reason"],
+        )
+        .expect("Defining the gossip_validation_results metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(validation_results.clone())
+        .expect(
+            "Registering the gossip_validation_results metric with the metrics registry must succeed",
+        );
+    validation_results
+});
+pub static PEERS_CONNECTED: Lazy<Box<GenericGaugeVec<AtomicU64>>> = Lazy::new(|| {
+    let peers_connected = Box::new(
+        GenericGaugeVec::<AtomicU64>::new(
+            Opts::new(
+                "peers_connected",
+                "Number of connected peers, by direction of their connections",
+            ),
+            &["direction"],
+        )
+        .expect("Defining the peers_connected metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(peers_connected.clone())
+        .expect("Registering the peers_connected metric with the metrics registry must succeed");
+    peers_connected
+});
+pub static DIAL_FAILURES: Lazy<Box<GenericCounterVec<AtomicU64>>> = Lazy::new(|| {
+    let dial_failures = Box::new(
+        GenericCounterVec::<AtomicU64>::new(
+            Opts::new("dial_failures", "Number of failed dials, by error class"),
+            &["error"],
+        )
+        .expect("Defining the dial_failures metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(dial_failures.clone())
+        .expect("Registering the dial_failures metric with the metrics registry must succeed");
+    dial_failures
+});
+pub static GOSSIP_MESH_PEERS: Lazy<Box<GenericGaugeVec<AtomicU64>>> = Lazy::new(|| {
+    let mesh_peers = Box::new(
+        GenericGaugeVec::<AtomicU64>::new(
+            Opts::new(
+                "gossip_mesh_peers",
+                "Number of peers in the Gossipsub mesh, by topic",
+            ),
+            &["topic"],
+        )
+        .expect("Defining the gossip_mesh_peers metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(mesh_peers.clone())
+        .expect("Registering the gossip_mesh_peers metric with the metrics registry must succeed");
+    mesh_peers
+});
+
+pub fn dial_error_class(error: &DialError) -> &'static str {
+    match error {
+        DialError::LocalPeerId { .. } => "local_peer_id",
+        DialError::NoAddresses => "no_addresses",
+        DialError::DialPeerConditionFalse(_) => "peer_condition",
+        DialError::Aborted => "aborted",
+        DialError::WrongPeerId { .. } => "wrong_peer_id",
+        DialError::Denied { .. } => "denied",
+        DialError::Transport(_) => "transport",
+    }
+}
+
+/// Counts the inbound and outbound connections of each peer, to export the number of peers
+/// connected in each direction. Peers with connections in both directions count in both.
+#[derive(Default)]
+pub struct ConnectedPeers {
+    connections: HashMap<PeerId, (u32, u32)>,
+}
+
+impl ConnectedPeers {
+    pub fn on_established(&mut self, peer: PeerId, inbound: bool) {
+        let (n_inbound, n_outbound) = self.connections.entry(peer).or_default();
+        if inbound {
+            *n_inbound += 1;
+        } else {
+            *n_outbound += 1;
+        }
+        self.update_metrics();
+    }
+
+    pub fn on_closed(&mut self, peer: PeerId, inbound: bool) {
+        if let Some((n_inbound, n_outbound)) = self.connections.get_mut(&peer) {
+            if inbound {
+                *n_inbound = n_inbound.saturating_sub(1);
+            } else {
+                *n_outbound = n_outbound.saturating_sub(1);
+            }
+            if *n_inbound == 0 && *n_outbound == 0 {
+                self.connections.remove(&peer);
+            }
+        }
+        self.update_metrics();
+    }
+
+    /// Returns the numbers of peers with inbound and outbound connections.
+    pub fn count(&self) -> (usize, usize) {
+        self.connections
+            .values()
+            .fold((0, 0), |(inbound, outbound), (n_inbound, n_outbound)| {
+                (
+                    inbound + usize::from(*n_inbound > 0),
+                    outbound + usize::from(*n_outbound > 0),
+                )
+            })
+    }
+
+    fn update_metrics(&self) {
+        let (inbound, outbound) = self.count();
+        PEERS_CONNECTED
+            .with_label_values(&["inbound"])
+            .set(inbound as u64);
+        PEERS_CONNECTED
+            .with_label_values(&["outbound"])
+            .set(outbound as u64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connected_peers_by_direction() {
+        let mut peers = ConnectedPeers::default();
+        let (a, b) = (PeerId::random(), PeerId::random());
+        peers.on_established(a, true);
+        peers.on_established(a, false);
+        peers.on_established(b, false);
+        peers.on_established(b, false);
+        assert_eq!(peers.count(), (1, 2));
+
+        peers.on_closed(a, true);
+        peers.on_closed(b, false);
+        assert_eq!(peers.count(), (0, 2));
+
+        peers.on_closed(a, false);
+        peers.on_closed(b, false);
+        assert_eq!(peers.count(), (0, 0));
+        assert!(peers.connections.is_empty());
+    }
+}
@@ -5,9 +5,11 @@ mod behaviour;
 pub mod chain_exchange;
 mod config;
 mod discovery;
+mod gater;
 mod gossip_params;
 pub mod hello;
 pub mod keypair;
+mod known_peers;
 mod metrics;
 mod peer_manager;
 pub mod rpc;
@@ -21,7 +23,7 @@ pub use libp2p::{
 };
 
 pub(in crate::libp2p) use self::behaviour::*;
-pub use self::{config::*, peer_manager::*, service::*};
+pub use self::{config::*, gater::BlockList, peer_manager::*, service::*};
 #[cfg(test)]
 mod tests {
     mod decode_test;
@@ -27,6 +27,22 @@ const LOCAL_INV_ALPHA: u32 = 5;
 /// Global duration multiplier, affects duration delta change.
 const GLOBAL_INV_ALPHA: u32 = 20;
 
+/// Time for the penalty of a peer serving invalid chain exchange responses to
+/// halve.
+const QUARANTINE_PENALTY_HALF_LIFE: Duration = Duration::from_secs(10 * 60);
+/// Penalty from which a peer is quarantined.
+const QUARANTINE_PENALTY_THRESHOLD: f64 = 3.0;
+/// Quarantine duration at the threshold, doubled for every further invalid
+/// response.
+const QUARANTINE_BASE_DURATION: Duration = Duration::from_secs(60);
+const MAX_QUARANTINE_DURATION: Duration = Duration::from_secs(60 * 60);
+/// Penalty below which a peer that isn't quarantined is forgotten.
+const MIN_TRACKED_PENALTY: f64 = 0.1;
+
+/// Weight of the data served by a peer in its cost, which decreases with the
+/// logarithm of the served MiBs.
+const SERVED_BYTES_WEIGHT: f64 = 0.1;
+
 #[derive(Debug, Default)]
 /// Contains info about the peer's head [Tipset], as well as the request stats.
 struct PeerInfo {
@@ -38,6 +54,8 @@ struct PeerInfo {
     failures: u32,
     /// Average response time for the peer.
     average_time: Duration,
+    /// Size of the chain exchange responses served by the peer.
+    served_bytes: u64,
 }
 
 impl PeerInfo {
@@ -47,8 +65,100 @@ impl PeerInfo {
             successes: 0,
             failures: 0,
             average_time: Default::default(),
+            served_bytes: 0,
+        }
+    }
+
+    /// Cost of sending requests to the peer, based on the failure rate and
+    /// latency of its requests and the data it served. The lower the better.
+    fn cost(&self, global_average_time: Duration) -> f64 {
+        let cost = if (self.successes + self.failures) > 0 {
+            // Calculate cost based on fail rate and latency
+            let fail_rate = f64::from(self.failures) / f64::from(self.successes);
+            self.average_time.as_secs_f64() + fail_rate * global_average_time.as_secs_f64()
+        } else {
+            // There have been no failures or successes
+            global_average_time.as_secs_f64() * NEW_PEER_MUL
+        };
+        let served_mib = self.served_bytes as f64 / (1024.0 * 1024.0);
+        cost / (1.0 + SERVED_BYTES_WEIGHT * served_mib.ln_1p())
+    }
+}
+
+/// Request statistics and score of a peer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerScore {
+    /// Number of successful chain exchange requests.
+    pub successes: u32,
+    /// Number of failed chain exchange requests.
+    pub failures: u32,
+    /// Average response time of the peer.
+    pub average_time: Duration,
+    /// Size of the chain exchange responses served by the peer.
+    pub served_bytes: u64,
+    /// Cost of sending requests to the peer, the peers with the lowest costs
+    /// are preferred.
+    pub cost: f64,
+}
+
+/// Tracks the invalid chain exchange responses served by a peer. The penalty
+/// decays exponentially, so that misbehaving peers are quarantined for a while
+/// rather than banned for good.
+#[derive(Debug, Clone, Copy)]
+struct Quarantine {
+    /// Penalty as of `updated`.
+    penalty: f64,
+    updated: Instant,
+    /// End of the current, or last, quarantine.
+    until: Option<Instant>,
+}
+
+impl Quarantine {
+    fn new(now: Instant) -> Self {
+        Self {
+            penalty: 0.0,
+            updated: now,
+            until: None,
         }
     }
+
+    fn penalty_at(&self, now: Instant) -> f64 {
+        let half_lives = now.saturating_duration_since(self.updated).as_secs_f64()
+            / QUARANTINE_PENALTY_HALF_LIFE.as_secs_f64();
+        self.penalty * 0.5_f64.powf(half_lives)
+    }
+
+    fn remaining_at(&self, now: Instant) -> Option<Duration> {
+        self.until
+            .map(|until| until.saturating_duration_since(now))
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Records an invalid response, and returns `true` if the peer gets
+    /// quarantined.
+    fn record_invalid_response(&mut self, now: Instant) -> bool {
+        self.penalty = self.penalty_at(now) + 1.0;
+        self.updated = now;
+        if self.penalty < QUARANTINE_PENALTY_THRESHOLD {
+            return false;
+        }
+        let doublings = (self.penalty - QUARANTINE_PENALTY_THRESHOLD).floor() as i32;
+        let duration = QUARANTINE_BASE_DURATION
+            .mul_f64(2_f64.powi(doublings.min(16)))
+            .min(MAX_QUARANTINE_DURATION);
+        self.until = Some(now + duration);
+        true
+    }
+}
+
+/// Chain exchange quarantine state of a peer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerQuarantine {
+    /// Invalid responses served by the peer, decayed over time.
+    pub penalty: f64,
+    /// Time left before the peer is sent chain exchange requests again, if it
+    /// is quarantined.
+    pub remaining: Option<Duration>,
 }
 
 /// Peer tracking sets, these are handled together to avoid race conditions or
@@ -60,6 +170,9 @@ struct PeerSets {
     /// Set of peers to ignore for being incompatible/ failing to accept
     /// connections.
     bad_peers: HashSet<PeerId>,
+    /// Peers that served invalid chain exchange responses. Entries outlive
+    /// disconnections, so that reconnecting doesn't lift a quarantine.
+    quarantines: HashMap<PeerId, Quarantine>,
 }
 
 /// Thread safe peer manager which handles peer management for the
@@ -120,24 +233,22 @@ impl PeerManager {
     }
 
     /// Sort peers based on a score function with the success rate and latency
-    /// of requests.
+    /// of requests. Quarantined peers are left out.
     pub(in crate::libp2p) fn sorted_peers(&self) -> Vec<PeerId> {
         let peer_lk = self.peers.read();
         let average_time = self.avg_global_time.read();
+        let now = Instant::now();
         let mut peers: Vec<_> = peer_lk
             .full_peers
             .iter()
-            .map(|(p, info)| {
-                let cost = if (info.successes + info.failures) > 0 {
-                    // Calculate cost based on fail rate and latency
-                    let fail_rate = f64::from(info.failures) / f64::from(info.successes);
-                    info.average_time.as_secs_f64() + fail_rate * average_time.as_secs_f64()
-                } else {
-                    // There have been no failures or successes
-                    average_time.as_secs_f64() * NEW_PEER_MUL
-                };
-                (p, cost)
+            .filter(|(p, _)| {
+                peer_lk
+                    .quarantines
+                    .get(*p)
+                    .and_then(|quarantine| quarantine.remaining_at(now))
+                    .is_none()
             })
+            .map(|(p, info)| (p, info.cost(*average_time)))
             .collect();
 
         // Unstable sort because hashmap iter order doesn't need to be preserved.
@@ -160,6 +271,11 @@ impl PeerManager {
         peers
     }
 
+    /// Returns the number of full peers.
+    pub fn peer_count(&self) -> usize {
+        self.peers.read().full_peers.len()
+    }
+
     /// Logs a global request success. This just updates the average for the
     /// peer manager.
     pub fn log_global_success(&self, dur: Duration) {
@@ -194,6 +310,27 @@ impl PeerManager {
         log_time(peer_stats, dur);
     }
 
+    /// Logs the size of a chain exchange response served by the given peer.
+    pub fn log_served_bytes(&self, peer: PeerId, bytes: u64) {
+        let mut peers = self.peers.write();
+        if let Some(info) = peers.full_peers.get_mut(&peer) {
+            info.served_bytes = info.served_bytes.saturating_add(bytes);
+        }
+    }
+
+    /// Returns the request statistics and score of a peer, if it is tracked.
+    pub fn peer_score(&self, peer_id: &PeerId) -> Option<PeerScore> {
+        let peers = self.peers.read();
+        let average_time = self.avg_global_time.read();
+        peers.full_peers.get(peer_id).map(|info| PeerScore {
+            successes: info.successes,
+            failures: info.failures,
+            average_time: info.average_time,
+            served_bytes: info.served_bytes,
+            cost: info.cost(*average_time),
+        })
+    }
+
     /// Logs a failure for the given peer, and updates the average request
     /// duration.
     pub fn log_failure(&self, peer: PeerId, dur: Duration) {
@@ -210,6 +347,43 @@ impl PeerManager {
         }
     }
 
+    /// Logs an invalid or truncated chain exchange response from the given
+    /// peer. Peers doing so repeatedly are quarantined, for exponentially
+    /// longer periods.
+    pub fn log_invalid_response(&self, peer: PeerId) {
+        debug!("logging invalid chain exchange response from {:?}", peer);
+        let mut peers = self.peers.write();
+        let now = Instant::now();
+        peers.quarantines.retain(|_, quarantine| {
+            quarantine.remaining_at(now).is_some()
+                || quarantine.penalty_at(now) >= MIN_TRACKED_PENALTY
+        });
+        let quarantine = peers
+            .quarantines
+            .entry(peer)
+            .or_insert_with(|| Quarantine::new(now));
+        if quarantine.record_invalid_response(now) {
+            warn!(
+                "quarantining peer {peer} for {}s after repeated invalid chain exchange responses",
+                quarantine.remaining_at(now).unwrap_or_default().as_secs()
+            );
+        }
+    }
+
+    /// Returns the chain exchange quarantine state of a peer, if it served
+    /// invalid responses recently.
+    pub fn peer_quarantine(&self, peer_id: &PeerId) -> Option<PeerQuarantine> {
+        let peers = self.peers.read();
+        let now = Instant::now();
+        peers
+            .quarantines
+            .get(peer_id)
+            .map(|quarantine| PeerQuarantine {
+                penalty: quarantine.penalty_at(now),
+                remaining: quarantine.remaining_at(now),
+            })
+    }
+
     /// Removes a peer from the set and returns true if the value was present
     /// previously
     pub fn mark_peer_bad(&self, peer_id: PeerId) -> bool {
@@ -323,3 +497,62 @@ pub enum PeerOperation {
     Ban(PeerId, String),
     Unban(PeerId),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quarantine_grows_exponentially_and_decays() {
+        let start = Instant::now();
+        let mut quarantine = Quarantine::new(start);
+        assert!(!quarantine.record_invalid_response(start));
+        assert!(!quarantine.record_invalid_response(start));
+        assert!(quarantine.record_invalid_response(start));
+        assert_eq!(
+            quarantine.remaining_at(start),
+            Some(QUARANTINE_BASE_DURATION)
+        );
+        assert!(quarantine.record_invalid_response(start));
+        assert_eq!(
+            quarantine.remaining_at(start),
+            Some(QUARANTINE_BASE_DURATION * 2)
+        );
+
+        // After two half-lives, the penalty is back under the threshold.
+        let later = start + QUARANTINE_PENALTY_HALF_LIFE * 2;
+        assert_eq!(quarantine.remaining_at(later), None);
+        assert!((quarantine.penalty_at(later) - 1.0).abs() < 1e-9);
+        assert!(!quarantine.record_invalid_response(later));
+    }
+
+    #[test]
+    fn quarantined_peers_are_not_sorted() {
+        let peer_manager = PeerManager::default();
+        let peer = PeerId::random();
+        peer_manager.log_success(peer, Duration::from_millis(10));
+        for _ in 0..QUARANTINE_PENALTY_THRESHOLD as usize {
+            peer_manager.log_invalid_response(peer);
+        }
+        assert!(peer_manager.sorted_peers().is_empty());
+        let quarantine = peer_manager.peer_quarantine(&peer).unwrap();
+        assert!(quarantine.remaining.is_some());
+    }
+
+    #[test]
+    fn peers_serving_more_data_are_preferred() {
+        let peer_manager = PeerManager::default();
+        let (peer, other) = (PeerId::random(), PeerId::random());
+        for peer in [peer, other] {
+            peer_manager.log_success(peer, Duration::from_millis(100));
+        }
+        peer_manager.log_served_bytes(other, 64 << 20);
+        assert_eq!(peer_manager.sorted_peers(), vec![other, peer]);
+
+        let score = peer_manager.peer_score(&other).unwrap();
+        assert_eq!(score.successes, 1);
+        assert_eq!(score.served_bytes, 64 << 20);
+        assert!(score.cost < peer_manager.peer_score(&peer).unwrap().cost);
+        assert!(peer_manager.peer_score(&PeerId::random()).is_none());
+    }
+}
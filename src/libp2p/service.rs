@@ -6,12 +6,17 @@ use std::{
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
+use crate::db::SettingsStore;
 use crate::libp2p_bitswap::{
     request_manager::{BitswapRequestManager, ValidatePeerCallback},
     BitswapStoreRead, BitswapStoreReadWrite,
 };
 use crate::message::SignedMessage;
-use crate::{blocks::GossipBlock, rpc_api::net_api::NetInfoResult};
+use crate::shim::econ::BLOCK_GAS_LIMIT;
+use crate::{
+    blocks::{GossipBlock, BLOCK_MESSAGE_LIMIT},
+    rpc_api::net_api::NetInfoResult,
+};
 use crate::{chain::ChainStore, utils::encoding::from_slice_with_fallback};
 use ahash::{HashMap, HashSet};
 use anyhow::Context as _;
@@ -24,14 +29,15 @@ use libp2p::connection_limits::Exceeded;
 pub use libp2p::gossipsub::{IdentTopic, Topic};
 use libp2p::swarm::DialError;
 use libp2p::{
+    autonat::{self, NatStatus},
     core::{self, muxing::StreamMuxerBox, transport::Boxed, Multiaddr},
-    gossipsub,
+    gossipsub::{self, MessageAcceptance},
     identity::Keypair,
-    metrics::{Metrics, Recorder},
+    metrics::{BandwidthTransport, Metrics, Recorder},
     multiaddr::Protocol,
-    noise, ping, request_response,
+    noise, ping, relay, request_response,
     swarm::{self, SwarmEvent},
-    yamux, PeerId, Swarm, Transport,
+    upnp, yamux, PeerId, Swarm, Transport,
 };
 use tokio_stream::wrappers::IntervalStream;
 use tracing::{debug, error, info, trace, warn};
@@ -42,10 +48,13 @@ use super::{
 };
 use crate::libp2p::{
     chain_exchange::ChainExchangeBehaviour,
-    discovery::DiscoveryEvent,
+    discovery::{DerivedDiscoveryBehaviourEvent, DiscoveryEvent},
+    gater::{BlockList, ConnectionGater},
     hello::{HelloBehaviour, HelloRequest, HelloResponse},
+    known_peers::{load_known_peers, save_known_peers, KNOWN_PEERS_SAVE_INTERVAL},
+    metrics::{dial_error_class, ConnectedPeers},
     rpc::RequestResponseError,
-    PeerManager, PeerOperation,
+    PeerManager, PeerOperation, PeerQuarantine, PeerScore,
 };
 
 pub(in crate::libp2p) mod metrics {
@@ -85,7 +94,15 @@ pub const PUBSUB_BLOCK_STR: &str = "/fil/blocks";
 /// `Gossipsub` Filecoin messages topic identifier.
 pub const PUBSUB_MSG_STR: &str = "/fil/msgs";
 
-const PUBSUB_TOPICS: [&str; 2] = [PUBSUB_BLOCK_STR, PUBSUB_MSG_STR];
+/// Returns the `Gossipsub` Filecoin blocks topic of the network.
+pub fn pubsub_block_topic(gossip_network_name: &str) -> String {
+    format!("{PUBSUB_BLOCK_STR}/{gossip_network_name}")
+}
+
+/// Returns the `Gossipsub` Filecoin messages topic of the network.
+pub fn pubsub_msg_topic(gossip_network_name: &str) -> String {
+    format!("{PUBSUB_MSG_STR}/{gossip_network_name}")
+}
 
 pub const BITSWAP_TIMEOUT: Duration = Duration::from_secs(30);
 
@@ -161,6 +178,12 @@ pub enum NetworkMessage {
         response_channel: flume::Sender<bool>,
         epoch: Option<i64>,
     },
+    /// Fetches a block over bitswap even if the database has it, replacing the
+    /// copy of the database once a block matching the CID is received.
+    BitswapRefetch {
+        cid: Cid,
+        response_channel: flume::Sender<bool>,
+    },
     JSONRPCRequest {
         method: NetRPCMethods,
     },
@@ -170,10 +193,27 @@ pub enum NetworkMessage {
 #[derive(Debug)]
 pub enum NetRPCMethods {
     AddrsListen(OneShotSender<(PeerId, HashSet<Multiaddr>)>),
-    Peers(OneShotSender<HashMap<PeerId, HashSet<Multiaddr>>>),
+    Peers(OneShotSender<HashMap<PeerId, (HashSet<Multiaddr>, Option<PeerQuarantine>)>>),
+    PeerInfo(
+        OneShotSender<(
+            HashSet<Multiaddr>,
+            Option<PeerScore>,
+            Option<PeerQuarantine>,
+        )>,
+        PeerId,
+    ),
     Info(OneShotSender<NetInfoResult>),
     Connect(OneShotSender<bool>, PeerId, HashSet<Multiaddr>),
     Disconnect(OneShotSender<()>, PeerId),
+    ProtectAdd(OneShotSender<()>, Vec<PeerId>),
+    ProtectRemove(OneShotSender<()>, Vec<PeerId>),
+    ProtectList(OneShotSender<Vec<PeerId>>),
+    BlockAdd(OneShotSender<()>, BlockList),
+    BlockRemove(OneShotSender<()>, BlockList),
+    BlockList(OneShotSender<BlockList>),
+    PubsubScores(OneShotSender<Vec<(PeerId, f64)>>),
+    Discover(OneShotSender<()>),
+    SetTargetPeerCount(OneShotSender<()>, u64),
 }
 
 /// The `Libp2pService` listens to events from the libp2p swarm.
@@ -185,7 +225,7 @@ pub struct Libp2pService<DB> {
     network_sender_in: Sender<NetworkMessage>,
     network_receiver_out: flume::Receiver<NetworkEvent>,
     network_sender_out: Sender<NetworkEvent>,
-    network_name: String,
+    gossip_network_name: String,
     genesis_cid: Cid,
 }
 
@@ -203,12 +243,13 @@ where
     ) -> anyhow::Result<Self> {
         let peer_id = PeerId::from(net_keypair.public());
 
-        let transport =
-            build_transport(net_keypair.clone()).expect("Failed to build libp2p transport");
+        let (relay_transport, relay_client) = relay::client::new(peer_id);
+        let transport = build_transport(net_keypair.clone(), relay_transport)
+            .expect("Failed to build libp2p transport");
 
         let mut swarm = Swarm::new(
             transport,
-            ForestBehaviour::new(&net_keypair, &config, network_name)?,
+            ForestBehaviour::new(&net_keypair, &config, network_name, relay_client)?,
             peer_id,
             swarm::Config::with_tokio_executor()
                 .with_notify_handler_buffer_size(std::num::NonZeroUsize::new(20).expect("Not zero"))
@@ -216,10 +257,20 @@ where
                 .with_idle_connection_timeout(Duration::from_secs(60 * 10)),
         );
 
+        // Restore the protections and blocks set by the operator in previous runs.
+        match ConnectionGater::load(cs.settings().as_ref()) {
+            Ok(gater) => swarm.behaviour_mut().gater = gater,
+            Err(e) => warn!("Failed to load the connection gater state: {e}"),
+        }
+
         // Subscribe to gossipsub topics with the network name suffix
-        for topic in PUBSUB_TOPICS.iter() {
-            let t = Topic::new(format!("{topic}/{network_name}"));
-            swarm.behaviour_mut().subscribe(&t).unwrap();
+        let gossip_network_name = config.gossip_network_name(network_name);
+        for topic in [
+            pubsub_block_topic(gossip_network_name),
+            pubsub_msg_topic(gossip_network_name),
+        ] {
+            info!("Subscribing to gossipsub topic {topic}");
+            swarm.behaviour_mut().subscribe(&Topic::new(topic)).unwrap();
         }
 
         let (network_sender_in, network_receiver_in) = flume::unbounded();
@@ -253,6 +304,27 @@ where
             anyhow::bail!("p2p peer failed to listen on any network endpoints");
         }
 
+        // Reserve a slot on the configured relays, to be reachable through them behind a NAT.
+        for addr in &config.relay_addresses {
+            let circuit_addr = addr.clone().with(Protocol::P2pCircuit);
+            if let Err(err) = swarm.listen_on(circuit_addr.clone()) {
+                error!("Fail to listen on {circuit_addr}: {err}");
+            }
+        }
+
+        // Dial the peers that served well in previous runs first.
+        match load_known_peers(cs.settings().as_ref()) {
+            Ok(known_peers) => {
+                info!("Dialing {} known peer addresses", known_peers.len());
+                for addr in known_peers {
+                    if let Err(e) = swarm.dial(addr.clone()) {
+                        debug!("Failed to dial known peer {addr}: {e}");
+                    }
+                }
+            }
+            Err(e) => warn!("Failed to load known peers: {e}"),
+        }
+
         Ok(Libp2pService {
             swarm,
             cs,
@@ -261,7 +333,7 @@ where
             network_sender_in,
             network_receiver_out,
             network_sender_out,
-            network_name: network_name.into(),
+            gossip_network_name: gossip_network_name.into(),
             genesis_cid,
         })
     }
@@ -281,8 +353,10 @@ where
         let mut network_stream = self.network_receiver_in.stream().fuse();
         let mut interval =
             IntervalStream::new(tokio::time::interval(Duration::from_secs(15))).fuse();
-        let pubsub_block_str = format!("{}/{}", PUBSUB_BLOCK_STR, self.network_name);
-        let pubsub_msg_str = format!("{}/{}", PUBSUB_MSG_STR, self.network_name);
+        let mut known_peers_interval =
+            IntervalStream::new(tokio::time::interval(KNOWN_PEERS_SAVE_INTERVAL)).fuse();
+        let pubsub_block_str = pubsub_block_topic(&self.gossip_network_name);
+        let pubsub_msg_str = pubsub_msg_topic(&self.gossip_network_name);
 
         let (cx_response_tx, cx_response_rx) = flume::unbounded();
 
@@ -291,6 +365,7 @@ where
             bitswap_request_manager.outbound_request_stream().fuse();
         let mut peer_ops_rx_stream = self.peer_manager.peer_ops_rx().stream().fuse();
         let metrics = Metrics::new(&mut crate::metrics::DEFAULT_REGISTRY.write());
+        let mut connected_peers = ConnectedPeers::default();
         loop {
             select! {
                 swarm_event = swarm_stream.next() => match swarm_event {
@@ -309,6 +384,17 @@ where
                             &pubsub_block_str,
                             &pubsub_msg_str,).await;
                     },
+                    Some(SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. }) => {
+                        connected_peers.on_established(peer_id, endpoint.is_listener());
+                    },
+                    Some(SwarmEvent::ConnectionClosed { peer_id, endpoint, .. }) => {
+                        connected_peers.on_closed(peer_id, endpoint.is_listener());
+                    },
+                    Some(SwarmEvent::OutgoingConnectionError { error, .. }) => {
+                        crate::libp2p::metrics::DIAL_FAILURES
+                            .with_label_values(&[dial_error_class(&error)])
+                            .inc();
+                    },
                     None => { break; },
                     _ => { },
                 },
@@ -321,13 +407,26 @@ where
                             bitswap_request_manager.clone(),
                             message,
                             &self.network_sender_out,
-                            &self.peer_manager).await;
+                            &self.peer_manager,
+                            self.cs.settings().as_ref()).await;
                     }
                     None => { break; }
                 },
                 interval_event = interval.next() => if interval_event.is_some() {
                     // Print peer count on an interval.
                     debug!("Peers connected: {}", swarm_stream.get_mut().behaviour_mut().peers().len());
+                    swarm_stream.get_mut().behaviour().update_gossip_mesh_metrics();
+                },
+                known_peers_event = known_peers_interval.next() => if known_peers_event.is_some() {
+                    let peer_addresses = swarm_stream.get_mut().behaviour_mut().peer_addresses();
+                    let peers = self
+                        .peer_manager
+                        .sorted_peers()
+                        .into_iter()
+                        .filter_map(|peer| peer_addresses.get(&peer).map(|addrs| (peer, addrs)));
+                    if let Err(e) = save_known_peers(self.cs.settings().as_ref(), peers) {
+                        warn!("Failed to save known peers: {e}");
+                    }
                 },
                 cs_pair_opt = cx_response_rx_stream.next() => {
                     if let Some((_request_id, channel, cx_response)) = cs_pair_opt {
@@ -368,6 +467,10 @@ fn handle_peer_ops(swarm: &mut Swarm<ForestBehaviour>, peer_ops: PeerOperation)
     use PeerOperation::*;
     match peer_ops {
         Ban(peer_id, reason) => {
+            if swarm.behaviour().gater.is_protected(&peer_id) {
+                debug!("Not banning protected peer {peer_id}, reason: {reason}");
+                return;
+            }
             warn!("Banning {peer_id}, reason: {reason}");
             swarm.behaviour_mut().blocked_peers.block_peer(peer_id);
         }
@@ -385,6 +488,7 @@ async fn handle_network_message(
     message: NetworkMessage,
     network_sender_out: &Sender<NetworkEvent>,
     peer_manager: &Arc<PeerManager>,
+    settings: &(dyn SettingsStore + Sync + Send),
 ) {
     match message {
         NetworkMessage::PubsubMessage { topic, message } => {
@@ -449,6 +553,12 @@ async fn handle_network_message(
                 peer_validator,
             );
         }
+        NetworkMessage::BitswapRefetch {
+            cid,
+            response_channel,
+        } => {
+            bitswap_request_manager.refetch_block(store, cid, BITSWAP_TIMEOUT, response_channel);
+        }
         NetworkMessage::JSONRPCRequest { method } => {
             match method {
                 NetRPCMethods::AddrsListen(response_channel) => {
@@ -460,13 +570,48 @@ async fn handle_network_message(
                     }
                 }
                 NetRPCMethods::Peers(response_channel) => {
-                    let peer_addresses = swarm.behaviour_mut().peer_addresses();
-                    if response_channel.send(peer_addresses.clone()).is_err() {
+                    let peers = swarm
+                        .behaviour_mut()
+                        .peer_addresses()
+                        .iter()
+                        .map(|(peer_id, addrs)| {
+                            (
+                                *peer_id,
+                                (addrs.clone(), peer_manager.peer_quarantine(peer_id)),
+                            )
+                        })
+                        .collect();
+                    if response_channel.send(peers).is_err() {
                         warn!("Failed to get Libp2p peers");
                     }
                 }
+                NetRPCMethods::PeerInfo(response_channel, peer_id) => {
+                    let addrs = swarm
+                        .behaviour_mut()
+                        .peer_addresses()
+                        .get(&peer_id)
+                        .cloned()
+                        .unwrap_or_default();
+                    let info = (
+                        addrs,
+                        peer_manager.peer_score(&peer_id),
+                        peer_manager.peer_quarantine(&peer_id),
+                    );
+                    if response_channel.send(info).is_err() {
+                        warn!("Failed to get Libp2p peer info");
+                    }
+                }
                 NetRPCMethods::Info(response_channel) => {
-                    if response_channel.send(swarm.network_info().into()).is_err() {
+                    let mut info = NetInfoResult::from(swarm.network_info());
+                    match swarm.behaviour().nat_status() {
+                        NatStatus::Public(addr) => {
+                            info.reachability = "Public".into();
+                            info.public_addr = Some(addr);
+                        }
+                        NatStatus::Private => info.reachability = "Private".into(),
+                        NatStatus::Unknown => info.reachability = "Unknown".into(),
+                    }
+                    if response_channel.send(info).is_err() {
                         warn!("Failed to get Libp2p peers");
                     }
                 }
@@ -510,11 +655,90 @@ async fn handle_network_message(
                         warn!("Failed to disconnect from a peer");
                     }
                 }
+                NetRPCMethods::ProtectAdd(response_channel, peers) => {
+                    let gater = &mut swarm.behaviour_mut().gater;
+                    gater.protect(peers);
+                    save_gater(gater, settings);
+                    if response_channel.send(()).is_err() {
+                        warn!("Failed to protect peers");
+                    }
+                }
+                NetRPCMethods::ProtectRemove(response_channel, peers) => {
+                    let gater = &mut swarm.behaviour_mut().gater;
+                    gater.unprotect(&peers);
+                    save_gater(gater, settings);
+                    if response_channel.send(()).is_err() {
+                        warn!("Failed to unprotect peers");
+                    }
+                }
+                NetRPCMethods::ProtectList(response_channel) => {
+                    if response_channel
+                        .send(swarm.behaviour().gater.protected())
+                        .is_err()
+                    {
+                        warn!("Failed to get protected peers");
+                    }
+                }
+                NetRPCMethods::BlockAdd(response_channel, block_list) => {
+                    for peer_id in &block_list.peers {
+                        let _ = Swarm::disconnect_peer_id(swarm, *peer_id);
+                    }
+                    let gater = &mut swarm.behaviour_mut().gater;
+                    gater.block(block_list);
+                    save_gater(gater, settings);
+                    if response_channel.send(()).is_err() {
+                        warn!("Failed to update the block list");
+                    }
+                }
+                NetRPCMethods::BlockRemove(response_channel, block_list) => {
+                    let gater = &mut swarm.behaviour_mut().gater;
+                    gater.unblock(&block_list);
+                    save_gater(gater, settings);
+                    if response_channel.send(()).is_err() {
+                        warn!("Failed to update the block list");
+                    }
+                }
+                NetRPCMethods::Discover(response_channel) => {
+                    swarm.behaviour_mut().discover_now();
+                    if response_channel.send(()).is_err() {
+                        warn!("Failed to start a discovery round");
+                    }
+                }
+                NetRPCMethods::SetTargetPeerCount(response_channel, target_peer_count) => {
+                    swarm
+                        .behaviour_mut()
+                        .set_target_peer_count(target_peer_count);
+                    if response_channel.send(()).is_err() {
+                        warn!("Failed to set the target peer count");
+                    }
+                }
+                NetRPCMethods::PubsubScores(response_channel) => {
+                    if response_channel
+                        .send(swarm.behaviour().gossip_scores())
+                        .is_err()
+                    {
+                        warn!("Failed to get Gossipsub scores");
+                    }
+                }
+                NetRPCMethods::BlockList(response_channel) => {
+                    if response_channel
+                        .send(swarm.behaviour().gater.block_list())
+                        .is_err()
+                    {
+                        warn!("Failed to get the block list");
+                    }
+                }
             }
         }
     }
 }
 
+fn save_gater(gater: &ConnectionGater, settings: &(dyn SettingsStore + Sync + Send)) {
+    if let Err(e) = gater.save(settings) {
+        warn!("Failed to save the connection gater state: {e}");
+    }
+}
+
 async fn handle_discovery_event(
     discovery_out: DiscoveryEvent,
     network_sender_out: &Sender<NetworkEvent>,
@@ -528,11 +752,72 @@ async fn handle_discovery_event(
             debug!("Peer disconnected, {:?}", peer_id);
             emit_event(network_sender_out, NetworkEvent::PeerDisconnected(peer_id)).await;
         }
-        DiscoveryEvent::Discovery(_) => {}
+        DiscoveryEvent::Discovery(e) => {
+            if let DerivedDiscoveryBehaviourEvent::Autonat(autonat::Event::StatusChanged {
+                old,
+                new,
+            }) = *e
+            {
+                info!("NAT status changed from {old:?} to {new:?}");
+            }
+        }
+    }
+}
+
+/// Reasons for a gossip message to fail validation.
+#[derive(Debug, thiserror::Error)]
+enum GossipValidationError {
+    #[error("could not be deserialized: {0}")]
+    Decode(String),
+    #[error("includes {0} messages, above the limit of {BLOCK_MESSAGE_LIMIT}")]
+    TooManyMessages(usize),
+    #[error("has a gas limit of {0}, above the block gas limit")]
+    GasLimit(u64),
+    #[error("was received on an unknown topic")]
+    UnknownTopic,
+}
+
+impl GossipValidationError {
+    fn reason(&self) -> &'static str {
+        match self {
+            Self::Decode(_) => "decode",
+            Self::TooManyMessages(_) => "too_many_messages",
+            Self::GasLimit(_) => "gas_limit",
+            Self::UnknownTopic => "unknown_topic",
+        }
+    }
+
+    /// Invalid messages are rejected, penalizing the peers that forwarded them. Messages on
+    /// topics the node doesn't know about are ignored.
+    fn acceptance(&self) -> MessageAcceptance {
+        match self {
+            Self::UnknownTopic => MessageAcceptance::Ignore,
+            _ => MessageAcceptance::Reject,
+        }
+    }
+}
+
+fn validate_gossip_block(data: &[u8]) -> Result<GossipBlock, GossipValidationError> {
+    let block = from_slice_with_fallback::<GossipBlock>(data)
+        .map_err(|e| GossipValidationError::Decode(e.to_string()))?;
+    let message_count = block.bls_messages.len() + block.secpk_messages.len();
+    if message_count > BLOCK_MESSAGE_LIMIT {
+        return Err(GossipValidationError::TooManyMessages(message_count));
+    }
+    Ok(block)
+}
+
+fn validate_gossip_message(data: &[u8]) -> Result<SignedMessage, GossipValidationError> {
+    let message = from_slice_with_fallback::<SignedMessage>(data)
+        .map_err(|e| GossipValidationError::Decode(e.to_string()))?;
+    if message.message().gas_limit > BLOCK_GAS_LIMIT {
+        return Err(GossipValidationError::GasLimit(message.message().gas_limit));
     }
+    Ok(message)
 }
 
 async fn handle_gossip_event(
+    gossipsub: &mut gossipsub::Behaviour,
     e: gossipsub::Event,
     network_sender_out: &Sender<NetworkEvent>,
     pubsub_block_str: &str,
@@ -541,46 +826,51 @@ async fn handle_gossip_event(
     if let gossipsub::Event::Message {
         propagation_source: source,
         message,
-        message_id: _,
+        message_id,
     } = e
     {
         let topic = message.topic.as_str();
-        let message = message.data;
         trace!("Got a Gossip Message from {:?}", source);
-        if topic == pubsub_block_str {
-            match from_slice_with_fallback::<GossipBlock>(&message) {
-                Ok(b) => {
-                    emit_event(
-                        network_sender_out,
-                        NetworkEvent::PubsubMessage {
-                            source,
-                            message: PubsubMessage::Block(b),
-                        },
-                    )
-                    .await;
-                }
-                Err(e) => {
-                    warn!("Gossip Block from peer {source:?} could not be deserialized: {e}",);
-                }
-            }
+        let (topic_label, validated) = if topic == pubsub_block_str {
+            (
+                "blocks",
+                validate_gossip_block(&message.data).map(PubsubMessage::Block),
+            )
         } else if topic == pubsub_msg_str {
-            match from_slice_with_fallback::<SignedMessage>(&message) {
-                Ok(m) => {
-                    emit_event(
-                        network_sender_out,
-                        NetworkEvent::PubsubMessage {
-                            source,
-                            message: PubsubMessage::Message(m),
-                        },
-                    )
-                    .await;
-                }
-                Err(e) => {
-                    warn!("Gossip Message from peer {source:?} could not be deserialized: {e}");
-                }
-            }
+            (
+                "msgs",
+                validate_gossip_message(&message.data).map(PubsubMessage::Message),
+            )
         } else {
-            warn!("Getting gossip messages from unknown topic: {topic}");
+            ("unknown", Err(GossipValidationError::UnknownTopic))
+        };
+
+        let (acceptance, result, reason) = match &validated {
+            Ok(_) => (MessageAcceptance::Accept, "accept", "valid"),
+            Err(e) => {
+                warn!("Gossip message from peer {source} on topic {topic} {e}");
+                let acceptance = e.acceptance();
+                let result = match acceptance {
+                    MessageAcceptance::Ignore => "ignore",
+                    _ => "reject",
+                };
+                (acceptance, result, e.reason())
+            }
+        };
+        crate::libp2p::metrics::GOSSIP_VALIDATION_RESULTS
+            .with_label_values(&[topic_label, result, reason])
+            .inc();
+        if let Err(e) = gossipsub.report_message_validation_result(&message_id, &source, acceptance)
+        {
+            debug!("Failed to report the validation of gossip message {message_id}: {e}");
+        }
+
+        if let Ok(message) = validated {
+            emit_event(
+                network_sender_out,
+                NetworkEvent::PubsubMessage { source, message },
+            )
+            .await;
         }
     }
 }
@@ -733,12 +1023,15 @@ async fn handle_chain_exchange_event<DB>(
                 .await;
 
                 let db = db.clone();
+                let max_request_len = chain_exchange.max_request_len();
                 tokio::task::spawn(async move {
-                    if let Err(e) = cx_response_tx.send((
-                        request_id,
-                        channel,
-                        make_chain_exchange_response(&db, &request),
-                    )) {
+                    let response = make_chain_exchange_response(&db, &request, max_request_len);
+                    crate::libp2p::metrics::CHAIN_EXCHANGE_SERVED_REQUESTS
+                        .with_label_values(&[response.status.as_str()])
+                        .inc();
+                    crate::libp2p::metrics::CHAIN_EXCHANGE_SERVED_TIPSETS
+                        .inc_by(response.chain.len() as u64);
+                    if let Err(e) = cx_response_tx.send((request_id, channel, response)) {
                         debug!("Failed to send ChainExchangeResponse: {e:?}");
                     }
                 });
@@ -808,7 +1101,14 @@ async fn handle_forest_behaviour_event<DB>(
             handle_discovery_event(discovery_out, network_sender_out).await
         }
         ForestBehaviourEvent::Gossipsub(e) => {
-            handle_gossip_event(e, network_sender_out, pubsub_block_str, pubsub_msg_str).await
+            handle_gossip_event(
+                &mut swarm.behaviour_mut().gossipsub,
+                e,
+                network_sender_out,
+                pubsub_block_str,
+                pubsub_msg_str,
+            )
+            .await
         }
         ForestBehaviourEvent::Hello(rr_event) => {
             handle_hello_event(
@@ -832,6 +1132,16 @@ async fn handle_forest_behaviour_event<DB>(
         ForestBehaviourEvent::Ping(ping_event) => handle_ping_event(ping_event, peer_manager).await,
         ForestBehaviourEvent::ConnectionLimits(_) => {}
         ForestBehaviourEvent::BlockedPeers(_) => {}
+        ForestBehaviourEvent::Gater(_) => {}
+        ForestBehaviourEvent::Upnp(upnp_event) => match upnp_event {
+            upnp::Event::NewExternalAddr(addr) => info!("UPnP mapped external address {addr}"),
+            upnp::Event::ExpiredExternalAddr(addr) => {
+                debug!("UPnP external address {addr} expired")
+            }
+            upnp::Event::GatewayNotFound => info!("UPnP gateway not found"),
+            upnp::Event::NonRoutableGateway => warn!("UPnP gateway is not exposed to the internet"),
+        },
+        ForestBehaviourEvent::RelayClient(relay_event) => debug!("Relay client: {relay_event:?}"),
         ForestBehaviourEvent::ChainExchange(ce_event) => {
             handle_chain_exchange_event(
                 &mut swarm.behaviour_mut().chain_exchange,
@@ -857,17 +1167,27 @@ async fn emit_event(sender: &Sender<NetworkEvent>, event: NetworkEvent) {
 ///
 /// As a reference `lotus` uses the default `go-libp2p` transport builder which
 /// has all above protocols enabled.
-pub fn build_transport(local_key: Keypair) -> anyhow::Result<Boxed<(PeerId, StreamMuxerBox)>> {
+pub fn build_transport(
+    local_key: Keypair,
+    relay_transport: relay::client::Transport,
+) -> anyhow::Result<Boxed<(PeerId, StreamMuxerBox)>> {
     let build_tcp = || libp2p::tcp::tokio::Transport::new(libp2p::tcp::Config::new().nodelay(true));
     let build_dns_tcp = || libp2p::dns::tokio::Transport::system(build_tcp());
-    let transport = build_dns_tcp()?;
+    let transport = relay_transport.or_transport(build_dns_tcp()?);
 
     let auth_config = noise::Config::new(&local_key).context("Noise key generation failed")?;
 
-    Ok(transport
+    let transport = transport
         .upgrade(core::upgrade::Version::V1)
         .authenticate(auth_config)
         .multiplex(yamux::Config::default())
-        .timeout(Duration::from_secs(20))
-        .boxed())
+        .timeout(Duration::from_secs(20));
+
+    // Account for the bytes sent and received, by transport protocols.
+    let mut registry = crate::metrics::DEFAULT_REGISTRY.write();
+    Ok(
+        BandwidthTransport::new(transport, registry.sub_registry_with_prefix("libp2p"))
+            .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
+            .boxed(),
+    )
 }
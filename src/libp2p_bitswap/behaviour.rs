@@ -19,6 +19,7 @@ pub type BitswapBehaviourEvent = request_response::Event<Vec<BitswapMessage>, ()
 pub struct BitswapBehaviour {
     inner: request_response::Behaviour<BitswapRequestResponseCodec>,
     request_manager: Arc<BitswapRequestManager>,
+    pub(in crate::libp2p_bitswap) server: BitswapServer,
 }
 
 impl BitswapBehaviour {
@@ -33,9 +34,16 @@ impl BitswapBehaviour {
         BitswapBehaviour {
             inner: request_response::Behaviour::new(protocols, cfg),
             request_manager: Default::default(),
+            server: Default::default(),
         }
     }
 
+    /// Sets the policy for answering the requests of peers
+    pub fn with_server_config(mut self, config: BitswapServerConfig) -> Self {
+        self.server = BitswapServer::new(config);
+        self
+    }
+
     /// Gets mutable borrow of the inner [`request_response::Behaviour`]
     pub fn inner_mut(&mut self) -> &mut request_response::Behaviour<BitswapRequestResponseCodec> {
         &mut self.inner
@@ -184,6 +192,9 @@ impl NetworkBehaviour for BitswapBehaviour {
             }
             FromSwarm::ConnectionClosed(e) => {
                 self.request_manager.on_peer_disconnected(&e.peer_id);
+                if e.remaining_established == 0 {
+                    self.server.on_peer_disconnected(&e.peer_id);
+                }
             }
             _ => {}
         };
@@ -33,7 +33,11 @@ pub fn handle_event_impl<S: BitswapStoreRead>(
                 for message in request {
                     match message {
                         BitswapMessage::Request(request) => {
+                            if !bitswap.server.accept(&peer, &request) {
+                                continue;
+                            }
                             if let Some(response) = handle_inbound_request(store, &request) {
+                                bitswap.server.on_response(&peer, &response);
                                 bitswap.send_response(&peer, (request.cid, response));
                             }
                         }
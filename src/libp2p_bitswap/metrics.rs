@@ -1,6 +1,7 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use libp2p::PeerId;
 use once_cell::sync::Lazy;
 use parking_lot::MappedRwLockReadGuard;
 use prometheus_client::{
@@ -20,6 +21,22 @@ impl TypeLabel {
     }
 }
 
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct PeerLabel {
+    peer: String,
+}
+
+impl PeerLabel {
+    fn new(peer: &PeerId) -> Self {
+        Self {
+            peer: peer.to_string(),
+        }
+    }
+}
+
+static SERVER_BLOCKS: Lazy<Family<PeerLabel, Counter>> = Lazy::new(Default::default);
+static SERVER_BYTES: Lazy<Family<PeerLabel, Counter>> = Lazy::new(Default::default);
+static SERVER_THROTTLED: Lazy<Family<PeerLabel, Counter>> = Lazy::new(Default::default);
 static MESSAGE_COUNTER: Lazy<Family<TypeLabel, Counter>> = Lazy::new(Default::default);
 static CONTAINER_CAPACITIES: Lazy<Family<TypeLabel, Gauge>> = Lazy::new(Default::default);
 pub(in crate::libp2p_bitswap) static GET_BLOCK_TIME: Lazy<Histogram> = Lazy::new(|| {
@@ -48,6 +65,46 @@ pub fn register_metrics(registry: &mut Registry) {
         "Duration of get_block",
         GET_BLOCK_TIME.clone(),
     );
+    registry.register(
+        "bitswap_server_blocks",
+        "Number of blocks served to each connected peer",
+        SERVER_BLOCKS.clone(),
+    );
+    registry.register(
+        "bitswap_server_bytes",
+        "Size of the blocks served to each connected peer",
+        SERVER_BYTES.clone(),
+    );
+    registry.register(
+        "bitswap_server_throttled_requests",
+        "Number of requests of each connected peer dropped by the rate limit",
+        SERVER_THROTTLED.clone(),
+    );
+}
+
+pub(in crate::libp2p_bitswap) fn server_blocks<'a>(
+    peer: &PeerId,
+) -> MappedRwLockReadGuard<'a, Counter> {
+    SERVER_BLOCKS.get_or_create(&PeerLabel::new(peer))
+}
+
+pub(in crate::libp2p_bitswap) fn server_bytes<'a>(
+    peer: &PeerId,
+) -> MappedRwLockReadGuard<'a, Counter> {
+    SERVER_BYTES.get_or_create(&PeerLabel::new(peer))
+}
+
+pub(in crate::libp2p_bitswap) fn server_throttled_requests<'a>(
+    peer: &PeerId,
+) -> MappedRwLockReadGuard<'a, Counter> {
+    SERVER_THROTTLED.get_or_create(&PeerLabel::new(peer))
+}
+
+pub(in crate::libp2p_bitswap) fn remove_server_peer(peer: &PeerId) {
+    let label = PeerLabel::new(peer);
+    SERVER_BLOCKS.remove(&label);
+    SERVER_BYTES.remove(&label);
+    SERVER_THROTTLED.remove(&label);
 }
 
 pub(in crate::libp2p_bitswap) fn inbound_stream_count<'a>() -> MappedRwLockReadGuard<'a, Counter> {
@@ -8,6 +8,7 @@
 //!
 //! - Compatible with [`go-bitswap`](https://github.com/ipfs/go-bitswap)
 //! - Optional request manager
+//! - Rate limited server with per-peer accounting
 //! - Prometheus metrics
 //!
 //! ## Usage
@@ -42,6 +43,9 @@ pub use metrics::register_metrics;
 
 pub mod request_manager;
 
+mod server;
+pub use server::*;
+
 mod store;
 pub use store::*;
 
@@ -159,6 +159,30 @@ impl BitswapRequestManager {
         });
     }
 
+    /// Gets a block even if the given block store already has it, e.g. to
+    /// replace a corrupted copy, and respond to the channel. The copy is only
+    /// replaced once a block matching `cid` is received. Note: this method is
+    /// a non-blocking, it is intended to return immediately.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn refetch_block(
+        self: Arc<Self>,
+        store: Arc<impl BitswapStoreReadWrite>,
+        cid: Cid,
+        timeout: Duration,
+        responder: flume::Sender<bool>,
+    ) {
+        let deadline = Instant::now().checked_add(timeout).expect("Infallible");
+        task::spawn(async move {
+            let success =
+                task::spawn_blocking(move || self.get_block_sync(store, cid, deadline, None))
+                    .await
+                    .unwrap_or_default();
+            if let Err(e) = responder.send_async(success).await {
+                warn!("{e}");
+            }
+        });
+    }
+
     fn get_block_sync(
         &self,
         store: Arc<impl BitswapStoreReadWrite>,
@@ -0,0 +1,149 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Policy of the `bitswap` server, answering the `WANT`s of peers for the
+//! blocks present in the local store.
+
+use std::time::Instant;
+
+use ahash::HashMap;
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+
+use crate::libp2p_bitswap::*;
+
+/// Settings of the `bitswap` server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+pub struct BitswapServerConfig {
+    /// Answer the `WANT`s of peers. Requests are ignored otherwise.
+    pub enabled: bool,
+    /// Maximum number of `WANT`s answered per second for each peer, `0` for no
+    /// limit. Peers may burst up to one second worth of requests.
+    pub max_requests_per_sec: u32,
+}
+
+impl Default for BitswapServerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_requests_per_sec: 200,
+        }
+    }
+}
+
+/// Token bucket of a peer, refilled continuously.
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Rate limiting and per-peer accounting of the `bitswap` server.
+#[derive(Debug, Default)]
+pub struct BitswapServer {
+    config: BitswapServerConfig,
+    buckets: HashMap<PeerId, Bucket>,
+}
+
+impl BitswapServer {
+    pub fn new(config: BitswapServerConfig) -> Self {
+        Self {
+            config,
+            buckets: HashMap::default(),
+        }
+    }
+
+    /// Whether the request of the peer is to be answered. Cancellations are
+    /// always accepted as they cost nothing to serve.
+    pub(in crate::libp2p_bitswap) fn accept(
+        &mut self,
+        peer: &PeerId,
+        request: &BitswapRequest,
+    ) -> bool {
+        if !self.config.enabled {
+            return false;
+        }
+        if request.cancel || self.take(peer, Instant::now()) {
+            true
+        } else {
+            metrics::server_throttled_requests(peer).inc();
+            false
+        }
+    }
+
+    fn take(&mut self, peer: &PeerId, now: Instant) -> bool {
+        let rate = self.config.max_requests_per_sec as f64;
+        if rate == 0.0 {
+            return true;
+        }
+        let bucket = self.buckets.entry(*peer).or_insert(Bucket {
+            tokens: rate,
+            updated: now,
+        });
+        bucket.tokens = (bucket.tokens
+            + now.saturating_duration_since(bucket.updated).as_secs_f64() * rate)
+            .min(rate);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Accounts for a response sent to the peer.
+    pub(in crate::libp2p_bitswap) fn on_response(&self, peer: &PeerId, response: &BitswapResponse) {
+        if let BitswapResponse::Block(data) = response {
+            metrics::server_blocks(peer).inc();
+            metrics::server_bytes(peer).inc_by(data.len() as u64);
+        }
+    }
+
+    /// Forgets the state and the metrics of the peer, keeping their number
+    /// bounded.
+    pub(in crate::libp2p_bitswap) fn on_peer_disconnected(&mut self, peer: &PeerId) {
+        self.buckets.remove(peer);
+        metrics::remove_server_peer(peer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn requests_are_rate_limited() {
+        let mut server = BitswapServer::new(BitswapServerConfig {
+            enabled: true,
+            max_requests_per_sec: 2,
+        });
+        let (peer, other) = (PeerId::random(), PeerId::random());
+        let now = Instant::now();
+        assert!(server.take(&peer, now));
+        assert!(server.take(&peer, now));
+        assert!(!server.take(&peer, now));
+        // Peers have their own budget.
+        assert!(server.take(&other, now));
+        // The budget is refilled over time, without exceeding the burst.
+        assert!(server.take(&peer, now + Duration::from_millis(500)));
+        assert!(!server.take(&peer, now + Duration::from_millis(500)));
+        let later = now + Duration::from_secs(60);
+        assert!(server.take(&peer, later));
+        assert!(server.take(&peer, later));
+        assert!(!server.take(&peer, later));
+    }
+
+    #[test]
+    fn disabled_server_ignores_requests() {
+        let mut server = BitswapServer::new(BitswapServerConfig {
+            enabled: false,
+            ..Default::default()
+        });
+        let request = BitswapRequest::new_block(Cid::default());
+        assert!(!server.accept(&PeerId::random(), &request));
+    }
+}
@@ -0,0 +1,40 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::*;
+use ahash::HashMap;
+
+impl<T> HasLotusJson for HashMap<String, T>
+where
+    T: HasLotusJson,
+{
+    type LotusJson = HashMap<String, T::LotusJson>;
+
+    // Snapshots of the entries are those of `T`, see the test below for
+    // `HashMap<String, Cid>`.
+    fn snapshots() -> Vec<(serde_json::Value, Self)> {
+        vec![(json!({}), HashMap::default())]
+    }
+
+    fn into_lotus_json(self) -> Self::LotusJson {
+        self.into_iter()
+            .map(|(k, v)| (k, v.into_lotus_json()))
+            .collect()
+    }
+
+    fn from_lotus_json(lotus_json: Self::LotusJson) -> Self {
+        lotus_json
+            .into_iter()
+            .map(|(k, v)| (k, T::from_lotus_json(v)))
+            .collect()
+    }
+}
+
+#[test]
+fn snapshots() {
+    assert_all_snapshots::<HashMap<String, ::cid::Cid>>();
+    assert_one_snapshot(
+        json!({"account": {"/": "baeaaaaa"}}),
+        HashMap::from_iter([("account".to_string(), ::cid::Cid::default())]),
+    );
+}
@@ -202,6 +202,7 @@ decl_and_test!(
 );
 
 mod cid; // can't make snapshots of generic type
+mod hash_map;
 mod nonempty;
 mod opt; // can't make snapshots of generic type
 mod raw_bytes; // fvm_ipld_encoding::RawBytes: !quickcheck::Arbitrary
@@ -439,6 +440,7 @@ lotus_json_with_self!(
     u32,
     u64,
     i64,
+    f64,
     String,
     chrono::DateTime<chrono::Utc>,
     serde_json::Value,
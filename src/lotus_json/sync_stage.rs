@@ -4,18 +4,73 @@ use super::*;
 
 use crate::chain_sync::SyncStage;
 
+// Lotus serializes sync stages (`api.SyncStateStage`) as integers, whereas
+// previous versions of Forest serialized them as strings.
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)] // try an int, then a string
+pub enum SyncStageLotusJson {
+    Integer(#[serde(with = "code")] SyncStage),
+    String(Stringify<SyncStage>),
+}
+
+mod code {
+    use super::*;
+    use serde::{de::Error as _, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(stage: &SyncStage, serializer: S) -> Result<S::Ok, S::Error> {
+        let code: u8 = match stage {
+            SyncStage::Idle => 0,
+            SyncStage::Headers => 1,
+            SyncStage::PersistHeaders => 2,
+            SyncStage::Messages => 3,
+            SyncStage::Complete => 4,
+            SyncStage::Error => 5,
+            SyncStage::FetchingMessages => 6,
+        };
+        serializer.serialize_u8(code)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SyncStage, D::Error> {
+        match u8::deserialize(deserializer)? {
+            0 => Ok(SyncStage::Idle),
+            1 => Ok(SyncStage::Headers),
+            2 => Ok(SyncStage::PersistHeaders),
+            3 => Ok(SyncStage::Messages),
+            4 => Ok(SyncStage::Complete),
+            5 => Ok(SyncStage::Error),
+            6 => Ok(SyncStage::FetchingMessages),
+            code => Err(D::Error::custom(format!("unknown sync stage: {code}"))),
+        }
+    }
+}
+
 impl HasLotusJson for SyncStage {
-    type LotusJson = Stringify<SyncStage>;
+    type LotusJson = SyncStageLotusJson;
 
     fn snapshots() -> Vec<(serde_json::Value, Self)> {
-        vec![(json!("idle worker"), Self::Idle)]
+        vec![(json!(0), Self::Idle), (json!(6), Self::FetchingMessages)]
     }
 
     fn into_lotus_json(self) -> Self::LotusJson {
-        self.into()
+        SyncStageLotusJson::Integer(self)
     }
 
-    fn from_lotus_json(Stringify(sync_stage): Self::LotusJson) -> Self {
-        sync_stage
+    fn from_lotus_json(lotus_json: Self::LotusJson) -> Self {
+        match lotus_json {
+            SyncStageLotusJson::Integer(inner) | SyncStageLotusJson::String(Stringify(inner)) => {
+                inner
+            }
+        }
     }
 }
+
+#[test]
+fn deserialize_string() {
+    pretty_assertions::assert_eq!(
+        SyncStage::Messages,
+        serde_json::from_value::<LotusJson<SyncStage>>(json!("message sync"))
+            .unwrap()
+            .into_inner()
+    );
+}
@@ -9,7 +9,7 @@ use crate::shim::{address::Address, econ::TokenAmount, message::Message as ShimM
 use crate::shim::{gas::Gas, version::NetworkVersion};
 pub use chain_message::ChainMessage;
 use fvm_ipld_encoding::RawBytes;
-pub use signed_message::SignedMessage;
+pub use signed_message::{signing_bytes, SignedMessage};
 
 /// Message interface to interact with Signed and unsigned messages in a generic
 /// context.
@@ -1,10 +1,11 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::eth::EthChainId;
 use crate::shim::message::MethodNum;
 use crate::shim::{
     address::Address,
-    crypto::{Signature, SignatureType},
+    crypto::{signature_scheme, Signature, SignatureType},
     econ::TokenAmount,
     message::Message,
 };
@@ -24,11 +25,14 @@ pub struct SignedMessage {
 impl SignedMessage {
     /// Generate a new signed message from fields.
     /// The signature will be verified.
-    pub fn new_from_parts(message: Message, signature: Signature) -> anyhow::Result<SignedMessage> {
-        signature
-            .verify(&message.cid()?.to_bytes(), &message.from())
-            .map_err(anyhow::Error::msg)?;
-        Ok(SignedMessage { message, signature })
+    pub fn new_from_parts(
+        message: Message,
+        signature: Signature,
+        eth_chain_id: EthChainId,
+    ) -> anyhow::Result<SignedMessage> {
+        let smsg = SignedMessage { message, signature };
+        smsg.verify(eth_chain_id).map_err(anyhow::Error::msg)?;
+        Ok(smsg)
     }
 
     /// Generate a new signed message from fields.
@@ -68,9 +72,24 @@ impl SignedMessage {
     }
 
     /// Verifies that the from address of the message generated the signature.
-    pub fn verify(&self) -> Result<(), String> {
-        self.signature
-            .verify(&self.message.cid().unwrap().to_bytes(), &self.from())
+    pub fn verify(&self, eth_chain_id: EthChainId) -> Result<(), String> {
+        self.verify_with_key(&self.from(), eth_chain_id)
+    }
+
+    /// Verifies that `key_addr`, the key address of the sender, generated the
+    /// signature. Delegated signatures are checked against the Ethereum
+    /// transaction equivalent to the message.
+    pub fn verify_with_key(
+        &self,
+        key_addr: &Address,
+        eth_chain_id: EthChainId,
+    ) -> Result<(), String> {
+        signature_scheme(self.signature.signature_type()).verify_message(
+            &self.signature,
+            &self.message,
+            key_addr,
+            eth_chain_id,
+        )
     }
 
     // Important note: `msg.cid()` is different from
@@ -86,6 +105,17 @@ impl SignedMessage {
     }
 }
 
+/// Returns the bytes a key of type `sig_type` signs for `message`: the RLP
+/// encoded EIP-1559 transaction equivalent to the message for delegated keys,
+/// the message CID for every other key type.
+pub fn signing_bytes(
+    message: &Message,
+    sig_type: SignatureType,
+    eth_chain_id: EthChainId,
+) -> anyhow::Result<Vec<u8>> {
+    signature_scheme(sig_type).message_signing_bytes(message, eth_chain_id)
+}
+
 impl MessageTrait for SignedMessage {
     fn from(&self) -> Address {
         self.message.from()
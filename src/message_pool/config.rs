@@ -5,7 +5,7 @@ use std::time::Duration;
 
 use crate::{
     db::{setting_keys::MPOOL_CONFIG_KEY, SettingsStore},
-    shim::address::Address,
+    shim::{address::Address, econ::TokenAmount},
     utils::encoding::from_slice_with_fallback,
 };
 use serde::{Deserialize, Serialize};
@@ -15,6 +15,8 @@ const SIZE_LIMIT_HIGH: i64 = 30000;
 const PRUNE_COOLDOWN: Duration = Duration::from_secs(60); // 1 minute
 const REPLACE_BY_FEE_RATIO: f64 = 1.25;
 const GAS_LIMIT_OVERESTIMATION: f64 = 1.25;
+/// Default maximum fee of a message, 0.07 FIL as in Lotus.
+const DEFAULT_MAX_FEE_NANO: u64 = 70_000_000;
 
 /// Configuration available for the [`crate::message_pool::MessagePool`].
 ///
@@ -41,9 +43,9 @@ impl Default for MpoolConfig {
         }
     }
 }
-#[cfg(test)]
 impl MpoolConfig {
     /// Saves message pool `config` to the database, to easily reload.
+    #[cfg(test)]
     pub fn save_config<DB: SettingsStore>(&self, store: &DB) -> Result<(), anyhow::Error> {
         store.write_bin(MPOOL_CONFIG_KEY, &fvm_ipld_encoding::to_vec(&self)?)
     }
@@ -69,3 +71,22 @@ impl MpoolConfig {
         }
     }
 }
+
+/// Caps of the fees of the messages whose gas is estimated by the node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(default)]
+pub struct FeeConfig {
+    /// Maximum fee of a message, i.e., its gas fee cap times its gas limit, when the sender
+    /// doesn't specify one.
+    #[serde(with = "crate::shim::econ::fil_string")]
+    pub max_fee: TokenAmount,
+}
+
+impl Default for FeeConfig {
+    fn default() -> Self {
+        Self {
+            max_fee: TokenAmount::from_nano(DEFAULT_MAX_FEE_NANO),
+        }
+    }
+}
@@ -2,7 +2,10 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use once_cell::sync::Lazy;
-use prometheus::core::{AtomicU64, GenericGauge};
+use prometheus::{
+    core::{AtomicU64, GenericGauge},
+    Gauge,
+};
 
 pub static MPOOL_MESSAGE_TOTAL: Lazy<Box<GenericGauge<AtomicU64>>> = Lazy::new(|| {
     let mpool_message_total = Box::new(
@@ -19,3 +22,33 @@ pub static MPOOL_MESSAGE_TOTAL: Lazy<Box<GenericGauge<AtomicU64>>> = Lazy::new(|
         );
     mpool_message_total
 });
+
+pub static MPOOL_SELECTED_MESSAGES: Lazy<Box<GenericGauge<AtomicU64>>> = Lazy::new(|| {
+    let mpool_selected_messages = Box::new(
+        GenericGauge::<AtomicU64>::new(
+            "mpool_selected_messages",
+            "Number of messages picked by the last message selection for a block",
+        )
+        .expect("Defining the mpool_selected_messages metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(mpool_selected_messages.clone())
+        .expect(
+            "Registering the mpool_selected_messages metric with the metrics registry must succeed",
+        );
+    mpool_selected_messages
+});
+
+pub static MPOOL_SELECTED_TIP: Lazy<Box<Gauge>> = Lazy::new(|| {
+    let mpool_selected_tip = Box::new(
+        Gauge::new(
+            "mpool_selected_tip",
+            "Total tip, in attoFIL, of the messages picked by the last message selection for a block, i.e. the sum of their gas premiums times their gas limits",
+        )
+        .expect("Defining the mpool_selected_tip metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(mpool_selected_tip.clone())
+        .expect("Registering the mpool_selected_tip metric with the metrics registry must succeed");
+    mpool_selected_tip
+});
@@ -13,7 +13,7 @@ pub(in crate::message_pool) mod utils;
 use std::{borrow::BorrowMut, cmp::Ordering, sync::Arc};
 
 use crate::blocks::Tipset;
-use crate::libp2p::{NetworkMessage, Topic, PUBSUB_MSG_STR};
+use crate::libp2p::{pubsub_msg_topic, NetworkMessage, Topic};
 use crate::message::{Message as MessageTrait, SignedMessage};
 use crate::networks::ChainConfig;
 use crate::shim::{address::Address, crypto::Signature};
@@ -92,7 +92,7 @@ where
         let mb = to_vec(m)?;
         network_sender
             .send_async(NetworkMessage::PubsubMessage {
-                topic: Topic::new(format!("{PUBSUB_MSG_STR}/{network_name}")),
+                topic: Topic::new(pubsub_msg_topic(network_name)),
                 message: mb,
             })
             .await
@@ -219,7 +219,6 @@ pub async fn head_change<T>(
 where
     T: Provider + 'static,
 {
-    let mut repub = false;
     let mut rmsgs: HashMap<Address, HashMap<u64, SignedMessage>> = HashMap::new();
     for ts in revert {
         let pts = api.load_tipset(ts.parents())?;
@@ -240,6 +239,10 @@ where
         }
     }
 
+    // Messages from reverted tipsets are no longer on chain and have to be
+    // broadcast again.
+    let mut repub = !rmsgs.is_empty();
+
     for ts in apply {
         for b in ts.block_headers() {
             let (msgs, smsgs) = api.messages_for_block(b)?;
@@ -264,12 +267,6 @@ where
         }
         *cur_tipset.lock() = Arc::new(ts);
     }
-    if repub {
-        repub_trigger
-            .send_async(())
-            .await
-            .map_err(|e| Error::Other(format!("Republish receiver dropped: {e}")))?;
-    }
     for (_, hm) in rmsgs {
         for (_, msg) in hm {
             let sequence = get_state_sequence(api, &msg.from(), &cur_tipset.lock().clone())?;
@@ -278,6 +275,14 @@ where
             }
         }
     }
+    // Republish once the reverted messages are back in the pool, so that they
+    // are part of the next republish cycle.
+    if repub {
+        repub_trigger
+            .send_async(())
+            .await
+            .map_err(|e| Error::Other(format!("Republish receiver dropped: {e}")))?;
+    }
     Ok(())
 }
 
@@ -360,6 +365,7 @@ pub mod tests {
             Arc::default(),
             &mut services,
         )
+        .await
         .unwrap();
         let mut smsg_vec = Vec::new();
         for i in 0..(mpool.api.max_actor_pending_messages() + 1) {
@@ -369,10 +375,10 @@ pub mod tests {
 
         let (last, body) = smsg_vec.split_last().unwrap();
         for smsg in body {
-            mpool.add(smsg.clone()).unwrap();
+            mpool.add(smsg.clone()).await.unwrap();
         }
         assert_eq!(
-            mpool.add(last.clone()),
+            mpool.add(last.clone()).await,
             Err(Error::TooManyPendingMessages(sender.to_string(), true))
         );
     }
@@ -447,6 +453,7 @@ pub mod tests {
             Arc::default(),
             &mut services,
         )
+        .await
         .unwrap();
         let mut smsg_vec = Vec::new();
         for i in 0..2 {
@@ -456,9 +463,9 @@ pub mod tests {
 
         mpool.api.inner.lock().set_state_sequence(&sender, 0);
         assert_eq!(mpool.get_sequence(&sender).unwrap(), 0);
-        mpool.add(smsg_vec[0].clone()).unwrap();
+        mpool.add(smsg_vec[0].clone()).await.unwrap();
         assert_eq!(mpool.get_sequence(&sender).unwrap(), 1);
-        mpool.add(smsg_vec[1].clone()).unwrap();
+        mpool.add(smsg_vec[1].clone()).await.unwrap();
         assert_eq!(mpool.get_sequence(&sender).unwrap(), 2);
 
         let a = mock_block(1, 1);
@@ -515,6 +522,7 @@ pub mod tests {
             Arc::default(),
             &mut services,
         )
+        .await
         .unwrap();
 
         {
@@ -525,10 +533,10 @@ pub mod tests {
             drop(api_temp);
         }
 
-        mpool.add(smsg_vec[0].clone()).unwrap();
-        mpool.add(smsg_vec[1].clone()).unwrap();
-        mpool.add(smsg_vec[2].clone()).unwrap();
-        mpool.add(smsg_vec[3].clone()).unwrap();
+        mpool.add(smsg_vec[0].clone()).await.unwrap();
+        mpool.add(smsg_vec[1].clone()).await.unwrap();
+        mpool.add(smsg_vec[2].clone()).await.unwrap();
+        mpool.add(smsg_vec[3].clone()).await.unwrap();
 
         mpool.api.set_state_sequence(&sender, 0);
 
@@ -615,6 +623,7 @@ pub mod tests {
             Arc::default(),
             &mut services,
         )
+        .await
         .unwrap();
 
         let mut smsg_vec = Vec::new();
@@ -644,6 +653,62 @@ pub mod tests {
         assert_eq!(cur_ts.as_ref(), &tipset);
     }
 
+    #[tokio::test]
+    async fn test_local_messages_persisted() {
+        let keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let mut wallet = Wallet::new(keystore);
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let target = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+
+        let tma = TestApi::default();
+        tma.set_state_sequence(&sender, 0);
+        let (tx, _rx) = flume::bounded(50);
+        let mut services = JoinSet::new();
+        let mpool = MessagePool::new(
+            tma,
+            "mptest".to_string(),
+            tx,
+            Default::default(),
+            Arc::default(),
+            &mut services,
+        )
+        .await
+        .unwrap();
+
+        let mut smsg_vec = Vec::new();
+        for i in 0..3 {
+            let msg = create_smsg(&target, &sender, wallet.borrow_mut(), i, 1000000, 1);
+            mpool.push(msg.clone()).await.unwrap();
+            smsg_vec.push(msg);
+        }
+        let persisted = mpool.api.local_messages();
+        assert_eq!(persisted.len(), 3);
+
+        // Simulate a restart, with the first message having landed on chain.
+        let tma = TestApi::default();
+        tma.set_state_sequence(&sender, 1);
+        for msg in &persisted {
+            tma.save_local_message(msg).unwrap();
+        }
+        let (tx, _rx) = flume::bounded(50);
+        let mpool = MessagePool::new(
+            tma,
+            "mptest".to_string(),
+            tx,
+            Default::default(),
+            Arc::default(),
+            &mut services,
+        )
+        .await
+        .unwrap();
+
+        let mut pending = mpool.pending_for(&sender).unwrap();
+        pending.sort_by_key(|m| m.sequence());
+        assert_eq!(pending, smsg_vec[1..]);
+        assert_eq!(mpool.api.local_messages().len(), 2);
+        assert_eq!(mpool.get_sequence(&sender).unwrap(), 3);
+    }
+
     #[tokio::test]
     async fn test_msg_chains() {
         let keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
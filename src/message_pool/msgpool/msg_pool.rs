@@ -6,18 +6,29 @@
 // inclusion in the chain. Messages are added either directly for locally
 // published messages or through pubsub propagation.
 
-use std::{num::NonZeroUsize, sync::Arc, time::Duration};
+use std::{
+    convert::Infallible,
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 use crate::blocks::{CachingBlockHeader, Tipset};
-use crate::chain::{HeadChange, MINIMUM_BASE_FEE};
+use crate::chain::{scheduler::EpochScheduler, HeadChange, MINIMUM_BASE_FEE};
 #[cfg(test)]
 use crate::db::SettingsStore;
-use crate::libp2p::{NetworkMessage, Topic, PUBSUB_MSG_STR};
+use crate::libp2p::{pubsub_msg_topic, NetworkMessage, Topic};
 use crate::message::{valid_for_block_inclusion, ChainMessage, Message, SignedMessage};
 use crate::networks::{ChainConfig, NEWEST_NETWORK_VERSION};
 use crate::shim::{
     address::Address,
-    crypto::{Signature, SignatureType},
+    clock::ChainEpoch,
+    crypto::{
+        verification_pool::{self, Verification},
+        Signature, SignatureType,
+    },
     econ::TokenAmount,
     gas::{price_list_by_network_version, Gas},
 };
@@ -30,8 +41,9 @@ use fvm_ipld_encoding::to_vec;
 use lru::LruCache;
 use nonzero_ext::nonzero;
 use num::BigInt;
+use num_traits::ToPrimitive as _;
 use parking_lot::{Mutex, RwLock as SyncRwLock};
-use tokio::{sync::broadcast::error::RecvError, task::JoinSet, time::interval};
+use tokio::{sync::broadcast::error::RecvError, task::JoinSet};
 use tracing::warn;
 
 use crate::message_pool::{
@@ -50,6 +62,9 @@ use crate::message_pool::{
 const BLS_SIG_CACHE_SIZE: NonZeroUsize = nonzero!(40000usize);
 const SIG_VAL_CACHE_SIZE: NonZeroUsize = nonzero!(32000usize);
 
+/// Number of epochs between two republishings of the pending local messages.
+const REPUBLISH_PERIOD: ChainEpoch = 10;
+
 pub const MAX_ACTOR_PENDING_MESSAGES: u64 = 1000;
 pub const MAX_UNTRUSTED_ACTOR_PENDING_MESSAGES: u64 = 10;
 
@@ -178,6 +193,7 @@ pub struct MessagePool<T> {
     /// The minimum gas price needed for executing the transaction based on
     /// number of included blocks
     pub min_gas_price: BigInt,
+    /// Network name used in the `Gossipsub` messages topic
     pub network_name: String,
     /// Sender half to send messages to other components
     pub network_sender: flume::Sender<NetworkMessage>,
@@ -201,16 +217,21 @@ impl<T> MessagePool<T>
 where
     T: Provider,
 {
-    /// Add a signed message to the pool and its address.
+    /// Add a signed message to the pool and its address. Local messages are
+    /// persisted so that they can be republished after a restart.
     fn add_local(&self, m: SignedMessage) -> Result<(), Error> {
         self.local_addrs.write().push(m.from());
-        self.local_msgs.write().insert(m);
+        let inserted = self.local_msgs.write().insert(m.clone());
+        if inserted {
+            self.api.save_local_message(&m)?;
+        }
         Ok(())
     }
 
     /// Push a signed message to the `MessagePool`. Additionally performs basic
     /// checks on the validity of a message.
     pub async fn push(&self, msg: SignedMessage) -> Result<Cid, Error> {
+        self.verify_msg_sig(&msg).await?;
         self.check_message(&msg)?;
         let cid = msg.cid().map_err(|err| Error::Other(err.to_string()))?;
         let cur_ts = self.cur_tipset.lock().clone();
@@ -220,7 +241,7 @@ where
         if publish {
             self.network_sender
                 .send_async(NetworkMessage::PubsubMessage {
-                    topic: Topic::new(format!("{}/{}", PUBSUB_MSG_STR, self.network_name)),
+                    topic: Topic::new(pubsub_msg_topic(&self.network_name)),
                     message: msg_ser,
                 })
                 .await
@@ -240,12 +261,19 @@ where
         if msg.gas_fee_cap().atto() < &MINIMUM_BASE_FEE.into() {
             return Err(Error::GasFeeCapTooLow);
         }
-        self.verify_msg_sig(msg)
+        Ok(())
     }
 
     /// This is a helper to push that will help to make sure that the message
     /// fits the parameters to be pushed to the `MessagePool`.
-    pub fn add(&self, msg: SignedMessage) -> Result<(), Error> {
+    pub async fn add(&self, msg: SignedMessage) -> Result<(), Error> {
+        self.verify_msg_sig(&msg).await?;
+        self.add_verified(msg)
+    }
+
+    /// Same as [`MessagePool::add`], for messages whose signature is already
+    /// verified.
+    fn add_verified(&self, msg: SignedMessage) -> Result<(), Error> {
         self.check_message(&msg)?;
 
         let tip = self.cur_tipset.lock().clone();
@@ -255,22 +283,62 @@ where
     }
 
     /// Verify the message signature. first check if it has already been
-    /// verified and put into cache. If it has not, then manually verify it
-    /// then put it into cache for future use.
-    fn verify_msg_sig(&self, msg: &SignedMessage) -> Result<(), Error> {
+    /// verified and put into cache. If it has not, then verify it on the
+    /// verification pool, without blocking the runtime, then put it into cache
+    /// for future use.
+    async fn verify_msg_sig(&self, msg: &SignedMessage) -> Result<(), Error> {
         let cid = msg.cid()?;
 
         if let Some(()) = self.sig_val_cache.lock().get(&cid) {
             return Ok(());
         }
 
-        msg.verify().map_err(Error::Other)?;
+        let eth_chain_id = self.chain_config.eth_chain_id as u64;
+        let msg = msg.clone();
+        let verification: Verification<'static, String> =
+            Box::new(move || msg.verify(eth_chain_id));
+        verification_pool::submit_all(vec![verification])
+            .await
+            .map_err(Error::Other)?;
 
         self.sig_val_cache.lock().put(cid, ());
 
         Ok(())
     }
 
+    /// Verifies the signatures of `msgs` in parallel on the verification pool
+    /// and caches the valid ones, so that adding the messages afterwards
+    /// doesn't verify them one at a time. Returns whether each signature is
+    /// valid.
+    async fn verify_msg_sigs(&self, msgs: &[SignedMessage]) -> Vec<bool> {
+        let eth_chain_id = self.chain_config.eth_chain_id as u64;
+        let valid = Arc::new(
+            msgs.iter()
+                .map(|_| AtomicBool::new(false))
+                .collect::<Vec<_>>(),
+        );
+        let verifications = msgs
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(i, msg)| -> Verification<'static, Infallible> {
+                let sig_val_cache = Arc::clone(&self.sig_val_cache);
+                let valid = Arc::clone(&valid);
+                Box::new(move || {
+                    if let Ok(cid) = msg.cid() {
+                        if sig_val_cache.lock().contains(&cid) || msg.verify(eth_chain_id).is_ok() {
+                            sig_val_cache.lock().put(cid, ());
+                            valid[i].store(true, Ordering::Relaxed);
+                        }
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        let _ = verification_pool::submit_all(verifications).await;
+        valid.iter().map(|v| v.load(Ordering::Relaxed)).collect()
+    }
+
     /// Verify the `state_sequence` and balance for the sender of the message
     /// given then call `add_locked` to finish adding the `signed_message`
     /// to pending.
@@ -290,6 +358,9 @@ where
                 "Sender actor is not a valid top-level sender".to_owned(),
             ));
         }
+        msg.signature()
+            .check_network_version(nv)
+            .map_err(Error::Other)?;
 
         let publish = verify_msg_before_add(&msg, cur_ts, local, &self.chain_config)?;
 
@@ -408,18 +479,32 @@ where
         Ok(msg_vec)
     }
 
-    /// Loads local messages to the message pool to be applied.
-    pub fn load_local(&mut self) -> Result<(), Error> {
+    /// Loads local messages, including the ones persisted by a previous run, to
+    /// the message pool to be applied.
+    pub async fn load_local(&mut self) -> Result<(), Error> {
+        // Verify the persisted messages before locking the local messages.
+        let loaded = self.api.load_local_messages()?;
+        let valid = self.verify_msg_sigs(&loaded).await;
         let mut local_msgs = self.local_msgs.write();
-        for k in local_msgs.iter().cloned().collect::<Vec<SignedMessage>>() {
-            self.add(k.clone()).unwrap_or_else(|err| {
+        for (k, valid) in loaded.into_iter().zip(valid) {
+            local_msgs.insert(k.clone());
+            let from = k.from();
+            let result = if valid {
+                self.add_verified(k.clone())
+            } else {
+                Err(Error::Other("invalid signature".into()))
+            };
+            if let Err(err) = result {
                 if err == Error::SequenceTooLow {
                     warn!("error adding message: {:?}", err);
                     local_msgs.remove(&k);
+                    self.api.remove_local_message(&k.cid()?)?;
                 }
-            })
+            }
+            if local_msgs.contains(&k) && !self.local_addrs.read().contains(&from) {
+                self.local_addrs.write().push(from);
+            }
         }
-
         Ok(())
     }
 
@@ -458,7 +543,17 @@ where
                 .collect()
         };
 
-        select_messages_for_block(self.api.as_ref(), self.chain_config.as_ref(), base, pending)
+        let msgs = select_messages_for_block(
+            self.api.as_ref(),
+            self.chain_config.as_ref(),
+            base,
+            pending,
+        )?;
+
+        metrics::MPOOL_SELECTED_MESSAGES.set(msgs.len() as u64);
+        metrics::MPOOL_SELECTED_TIP.set(total_tip(&msgs).atto().to_f64().unwrap_or(f64::MAX));
+
+        Ok(msgs)
     }
 }
 
@@ -467,7 +562,7 @@ where
     T: Provider + Send + Sync + 'static,
 {
     /// Creates a new `MessagePool` instance.
-    pub fn new(
+    pub async fn new(
         api: T,
         network_name: String,
         network_sender: flume::Sender<NetworkMessage>,
@@ -485,7 +580,6 @@ where
         let sig_val_cache = Arc::new(Mutex::new(LruCache::new(SIG_VAL_CACHE_SIZE)));
         let local_msgs = Arc::new(SyncRwLock::new(HashSet::new()));
         let republished = Arc::new(SyncRwLock::new(HashSet::new()));
-        let block_delay = chain_config.block_delay_secs;
 
         let (repub_trigger, repub_trigger_rx) = flume::bounded::<()>(4);
         let mut mp = MessagePool {
@@ -505,7 +599,7 @@ where
             chain_config: Arc::clone(&chain_config),
         };
 
-        mp.load_local()?;
+        mp.load_local().await?;
 
         let mut subscriber = mp.api.subscribe_head_changes();
 
@@ -557,18 +651,32 @@ where
         let cur_tipset = mp.cur_tipset.clone();
         let republished = mp.republished.clone();
         let local_addrs = mp.local_addrs.clone();
+        let local_msgs = mp.local_msgs.clone();
         let network_sender = Arc::new(mp.network_sender.clone());
         let network_name = mp.network_name.clone();
-        let republish_interval = (10 * block_delay + chain_config.propagation_delay_secs) as u64;
+        let mut scheduler = EpochScheduler::default();
+        let mut republish_due = scheduler
+            .register("mpool_republish", REPUBLISH_PERIOD)
+            .into_stream();
+        services.spawn({
+            let head_changes = mp.api.subscribe_head_changes();
+            async move {
+                scheduler.run(head_changes).await;
+                Ok(())
+            }
+        });
         // Reacts to republishing requests
         services.spawn(async move {
             let mut repub_trigger_rx = repub_trigger_rx.stream();
-            let mut interval = interval(Duration::from_secs(republish_interval));
             loop {
                 tokio::select! {
-                    _ = interval.tick() => (),
+                    Some(_) = republish_due.next() => (),
                     _ = repub_trigger_rx.next() => (),
                 }
+                let ts = cur_tipset.lock().clone();
+                if let Err(e) = prune_local_messages(api.as_ref(), local_msgs.as_ref(), &ts) {
+                    warn!("Failed to prune local messages: {}", e.to_string());
+                }
                 if let Err(e) = republish_pending_messages(
                     api.as_ref(),
                     network_sender.as_ref(),
@@ -635,6 +743,49 @@ where
     Ok(())
 }
 
+/// Returns the total tip offered by `msgs` to the miner including them, i.e.
+/// the sum of their gas premiums times their gas limits.
+pub(in crate::message_pool) fn total_tip(msgs: &[SignedMessage]) -> TokenAmount {
+    msgs.iter().map(|m| m.gas_premium() * m.gas_limit()).sum()
+}
+
+/// Drop the locally published messages that can no longer be included in the
+/// chain, i.e. the ones with a sequence below the sender's state sequence at
+/// the given tipset. Such messages either landed on chain or were replaced.
+pub(in crate::message_pool) fn prune_local_messages<T>(
+    api: &T,
+    local_msgs: &SyncRwLock<HashSet<SignedMessage>>,
+    cur_ts: &Tipset,
+) -> Result<(), Error>
+where
+    T: Provider,
+{
+    let mut local_msgs = local_msgs.write();
+    let mut state_sequences = HashMap::new();
+    let mut pruned = Vec::new();
+    local_msgs.retain(|m| {
+        let from = m.from();
+        let sequence = match state_sequences.get(&from) {
+            Some(sequence) => *sequence,
+            None => match api.get_actor_after(&from, cur_ts) {
+                Ok(actor) => *state_sequences.entry(from).or_insert(actor.sequence),
+                // Keep the message around if the sender cannot be resolved yet.
+                Err(_) => return true,
+            },
+        };
+        let keep = m.sequence() >= sequence;
+        if !keep {
+            pruned.push(m.cid());
+        }
+        keep
+    });
+    drop(local_msgs);
+    for cid in pruned {
+        api.remove_local_message(&cid?)?;
+    }
+    Ok(())
+}
+
 fn verify_msg_before_add(
     m: &SignedMessage,
     cur_ts: &Tipset,
@@ -5,6 +5,7 @@ use std::sync::Arc;
 
 use crate::blocks::{CachingBlockHeader, Tipset, TipsetKey};
 use crate::chain::HeadChange;
+use crate::db::{setting_keys::MPOOL_LOCAL_MESSAGE_KEY_PREFIX, SettingsStore};
 use crate::message::{ChainMessage, SignedMessage};
 use crate::message_pool::msg_pool::{
     MAX_ACTOR_PENDING_MESSAGES, MAX_UNTRUSTED_ACTOR_PENDING_MESSAGES,
@@ -18,6 +19,7 @@ use crate::shim::{
 };
 use crate::state_manager::StateManager;
 use crate::utils::db::CborStoreExt;
+use crate::utils::encoding::from_slice_with_fallback;
 use async_trait::async_trait;
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
@@ -52,6 +54,14 @@ pub trait Provider {
     fn load_tipset(&self, tsk: &TipsetKey) -> Result<Arc<Tipset>, Error>;
     /// Computes the base fee
     fn chain_compute_base_fee(&self, ts: &Tipset) -> Result<TokenAmount, Error>;
+    /// Return the locally published messages that were persisted by
+    /// [`Provider::save_local_message`]
+    fn load_local_messages(&self) -> Result<Vec<SignedMessage>, Error>;
+    /// Persist a locally published message so that it survives a restart
+    fn save_local_message(&self, msg: &SignedMessage) -> Result<(), Error>;
+    /// Remove a locally published message persisted by
+    /// [`Provider::save_local_message`]
+    fn remove_local_message(&self, cid: &Cid) -> Result<(), Error>;
     // Get max number of messages per actor in the pool
     fn max_actor_pending_messages(&self) -> u64 {
         MAX_ACTOR_PENDING_MESSAGES
@@ -78,6 +88,10 @@ where
     }
 }
 
+fn local_message_key(cid: &Cid) -> String {
+    format!("{MPOOL_LOCAL_MESSAGE_KEY_PREFIX}{cid}")
+}
+
 #[async_trait]
 impl<DB> Provider for MpoolRpcProvider<DB>
 where
@@ -131,4 +145,34 @@ where
             .map_err(|err| err.into())
             .map(Into::into)
     }
+
+    fn load_local_messages(&self) -> Result<Vec<SignedMessage>, Error> {
+        let settings = self.sm.chain_store().settings();
+        let mut msgs = Vec::new();
+        for key in settings.setting_keys()? {
+            if !key.starts_with(MPOOL_LOCAL_MESSAGE_KEY_PREFIX) {
+                continue;
+            }
+            if let Some(bytes) = settings.read_bin(&key)? {
+                msgs.push(from_slice_with_fallback(&bytes)?);
+            }
+        }
+        Ok(msgs)
+    }
+
+    fn save_local_message(&self, msg: &SignedMessage) -> Result<(), Error> {
+        self.sm.chain_store().settings().write_bin(
+            &local_message_key(&msg.cid()?),
+            &fvm_ipld_encoding::to_vec(msg)?,
+        )?;
+        Ok(())
+    }
+
+    fn remove_local_message(&self, cid: &Cid) -> Result<(), Error> {
+        self.sm
+            .chain_store()
+            .settings()
+            .delete(&local_message_key(cid))?;
+        Ok(())
+    }
 }
@@ -697,6 +697,7 @@ mod test_selection {
     use super::*;
     use crate::message_pool::{
         head_change,
+        msg_pool::total_tip,
         msgpool::{
             test_provider::{mock_block, TestApi},
             tests::{create_fake_smsg, create_smsg},
@@ -705,7 +706,7 @@ mod test_selection {
 
     const TEST_GAS_LIMIT: i64 = 6955002;
 
-    fn make_test_mpool(joinset: &mut JoinSet<anyhow::Result<()>>) -> MessagePool<TestApi> {
+    async fn make_test_mpool(joinset: &mut JoinSet<anyhow::Result<()>>) -> MessagePool<TestApi> {
         let tma = TestApi::default();
         let (tx, _rx) = flume::bounded(50);
         MessagePool::new(
@@ -716,13 +717,14 @@ mod test_selection {
             Arc::default(),
             joinset,
         )
+        .await
         .unwrap()
     }
 
     #[tokio::test]
     async fn basic_message_selection() {
         let mut joinset = JoinSet::new();
-        let mpool = make_test_mpool(&mut joinset);
+        let mpool = make_test_mpool(&mut joinset).await;
 
         let ks1 = KeyStore::new(KeyStoreConfig::Memory).unwrap();
         let mut w1 = Wallet::new(ks1);
@@ -763,11 +765,11 @@ mod test_selection {
         // order his messages first
         for i in 0..10 {
             let m = create_smsg(&a2, &a1, &mut w1, i, TEST_GAS_LIMIT, 2 * i + 1);
-            mpool.add(m).unwrap();
+            mpool.add(m).await.unwrap();
         }
         for i in 0..10 {
             let m = create_smsg(&a1, &a2, &mut w2, i, TEST_GAS_LIMIT, i + 1);
-            mpool.add(m).unwrap();
+            mpool.add(m).await.unwrap();
         }
 
         let msgs = mpool.select_messages(&ts, 1.0).unwrap();
@@ -841,9 +843,11 @@ mod test_selection {
                     TEST_GAS_LIMIT,
                     2 * i + 200,
                 ))
+                .await
                 .unwrap();
             mpool
                 .add(create_smsg(&a1, &a2, &mut w2, i, TEST_GAS_LIMIT, i + 1))
+                .await
                 .unwrap();
         }
         // select messages in the last tipset; this should include the missed messages
@@ -882,10 +886,72 @@ mod test_selection {
         }
     }
 
+    #[tokio::test]
+    async fn selection_follows_nonces_and_sums_tips() {
+        let mut joinset = JoinSet::new();
+        let mpool = make_test_mpool(&mut joinset).await;
+
+        let mut w1 = Wallet::new(KeyStore::new(KeyStoreConfig::Memory).unwrap());
+        let a1 = w1.generate_addr(SignatureType::Secp256k1).unwrap();
+        let mut w2 = Wallet::new(KeyStore::new(KeyStoreConfig::Memory).unwrap());
+        let a2 = w2.generate_addr(SignatureType::Secp256k1).unwrap();
+
+        let b1 = mock_block(1, 1);
+        let ts = Tipset::from(&b1);
+        let api = mpool.api.clone();
+        head_change(
+            api.as_ref(),
+            mpool.bls_sig_cache.as_ref(),
+            Arc::new(mpool.repub_trigger.clone()),
+            mpool.republished.as_ref(),
+            mpool.pending.as_ref(),
+            mpool.cur_tipset.as_ref(),
+            Vec::new(),
+            vec![Tipset::from(b1)],
+        )
+        .await
+        .unwrap();
+        api.set_state_balance_raw(&a1, TokenAmount::from_whole(1));
+        api.set_state_balance_raw(&a2, TokenAmount::from_whole(1));
+
+        // The later messages of `a1` pay more, but can't be selected before the earlier ones.
+        for (i, premium) in [1, 50, 100].into_iter().enumerate() {
+            let m = create_smsg(&a2, &a1, &mut w1, i as u64, TEST_GAS_LIMIT, premium);
+            mpool.add(m).await.unwrap();
+        }
+        let m = create_smsg(&a1, &a2, &mut w2, 0, TEST_GAS_LIMIT, 10);
+        mpool.add(m).await.unwrap();
+
+        for tq in [1.0, 0.5] {
+            let msgs = mpool.select_messages(&ts, tq).unwrap();
+            assert_eq!(msgs.len(), 4);
+            let nonces: Vec<_> = msgs
+                .iter()
+                .filter(|m| m.from() == a1)
+                .map(|m| m.sequence())
+                .collect();
+            assert_eq!(nonces, vec![0, 1, 2]);
+            assert_eq!(
+                total_tip(&msgs),
+                TokenAmount::from_atto(161 * TEST_GAS_LIMIT)
+            );
+        }
+
+        // So does the selection for the blocks of the node, used by `MpoolSelect`.
+        let msgs = mpool.select_messages_for_block(&ts).unwrap();
+        assert_eq!(msgs.len(), 4);
+        let nonces: Vec<_> = msgs
+            .iter()
+            .filter(|m| m.from() == a1)
+            .map(|m| m.sequence())
+            .collect();
+        assert_eq!(nonces, vec![0, 1, 2]);
+    }
+
     #[tokio::test]
     async fn message_selection_trimming() {
         let mut joinset = JoinSet::new();
-        let mpool = make_test_mpool(&mut joinset);
+        let mpool = make_test_mpool(&mut joinset).await;
 
         let ks1 = KeyStore::new(KeyStoreConfig::Memory).unwrap();
         let mut w1 = Wallet::new(ks1);
@@ -932,7 +998,7 @@ mod test_selection {
                 TEST_GAS_LIMIT,
                 (1 + i % 3 + bias) as u64,
             );
-            mpool.add(m).unwrap();
+            mpool.add(m).await.unwrap();
             let m = create_fake_smsg(
                 &mpool,
                 &a1,
@@ -941,7 +1007,7 @@ mod test_selection {
                 TEST_GAS_LIMIT,
                 (1 + i % 3 + bias) as u64,
             );
-            mpool.add(m).unwrap();
+            mpool.add(m).await.unwrap();
         }
 
         let msgs = mpool.select_messages(&ts, 1.0).unwrap();
@@ -960,7 +1026,7 @@ mod test_selection {
         let db = MemoryDB::default();
 
         let mut joinset = JoinSet::new();
-        let mut mpool = make_test_mpool(&mut joinset);
+        let mut mpool = make_test_mpool(&mut joinset).await;
 
         let ks1 = KeyStore::new(KeyStoreConfig::Memory).unwrap();
         let mut w1 = Wallet::new(ks1);
@@ -1013,7 +1079,7 @@ mod test_selection {
                 TEST_GAS_LIMIT,
                 (1 + i % 3 + bias) as u64,
             );
-            mpool.add(m).unwrap();
+            mpool.add(m).await.unwrap();
             let m = create_smsg(
                 &a1,
                 &a2,
@@ -1022,7 +1088,7 @@ mod test_selection {
                 TEST_GAS_LIMIT,
                 (1 + i % 3 + bias) as u64,
             );
-            mpool.add(m).unwrap();
+            mpool.add(m).await.unwrap();
         }
 
         let msgs = mpool.select_messages(&ts, 1.0).unwrap();
@@ -1057,7 +1123,7 @@ mod test_selection {
         // the chain dependent merging algorithm should pick messages from the actor
         // from the start
         let mut joinset = JoinSet::new();
-        let mpool = make_test_mpool(&mut joinset);
+        let mpool = make_test_mpool(&mut joinset).await;
 
         // create two actors
         let mut w1 = Wallet::new(KeyStore::new(KeyStoreConfig::Memory).unwrap());
@@ -1108,7 +1174,7 @@ mod test_selection {
                 TEST_GAS_LIMIT,
                 (1 + i % 3 + bias) as u64,
             );
-            mpool.add(m).unwrap();
+            mpool.add(m).await.unwrap();
         }
 
         let msgs = mpool.select_messages(&ts, 0.25).unwrap();
@@ -1136,7 +1202,7 @@ mod test_selection {
         // actor paying (much) higher gas premium than the second.
         // We select with a low ticket quality; the chain depenent merging algorithm
         // should pick messages from the second actor from the start
-        let mpool = make_test_mpool(&mut joinset);
+        let mpool = make_test_mpool(&mut joinset).await;
 
         // create two actors
         let mut w1 = Wallet::new(KeyStore::new(KeyStoreConfig::Memory).unwrap());
@@ -1183,7 +1249,7 @@ mod test_selection {
                 TEST_GAS_LIMIT,
                 (200000 + i % 3 + bias) as u64,
             );
-            mpool.add(m).unwrap();
+            mpool.add(m).await.unwrap();
             let m = create_fake_smsg(
                 &mpool,
                 &a1,
@@ -1192,7 +1258,7 @@ mod test_selection {
                 TEST_GAS_LIMIT,
                 (190000 + i % 3 + bias) as u64,
             );
-            mpool.add(m).unwrap();
+            mpool.add(m).await.unwrap();
         }
 
         let msgs = mpool.select_messages(&ts, 0.1).unwrap();
@@ -1246,7 +1312,7 @@ mod test_selection {
         // actors. We select with a low ticket quality; the chain depenent
         // merging algorithm should pick messages from the median actor from the
         // start
-        let mpool = make_test_mpool(&mut joinset);
+        let mpool = make_test_mpool(&mut joinset).await;
 
         let n_actors = 10;
 
@@ -1303,7 +1369,7 @@ mod test_selection {
                     TEST_GAS_LIMIT,
                     premium as u64,
                 );
-                mpool.add(m).unwrap();
+                mpool.add(m).await.unwrap();
             }
         }
 
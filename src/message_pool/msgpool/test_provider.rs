@@ -11,7 +11,10 @@ use crate::blocks::{CachingBlockHeader, ElectionProof, Ticket, Tipset, TipsetKey
 use crate::chain::HeadChange;
 use crate::cid_collections::CidHashMap;
 use crate::message::{ChainMessage, Message as MessageTrait, SignedMessage};
-use crate::shim::{address::Address, econ::TokenAmount, message::Message, state_tree::ActorState};
+use crate::shim::{
+    address::Address, clock::ChainEpoch, econ::TokenAmount, message::Message,
+    state_tree::ActorState,
+};
 use ahash::HashMap;
 use async_trait::async_trait;
 use cid::Cid;
@@ -36,6 +39,7 @@ pub struct TestApiInner {
     balances: HashMap<Address, TokenAmount>,
     tipsets: Vec<Tipset>,
     max_actor_pending_messages: u64,
+    local_msgs: Vec<SignedMessage>,
 }
 
 impl Default for TestApi {
@@ -88,6 +92,11 @@ impl TestApi {
     pub fn next_block(&self) -> CachingBlockHeader {
         self.inner.lock().next_block()
     }
+
+    /// Returns the locally published messages persisted by the message pool
+    pub fn local_messages(&self) -> Vec<SignedMessage> {
+        self.inner.lock().local_msgs.clone()
+    }
 }
 
 impl TestApiInner {
@@ -215,6 +224,26 @@ impl Provider for TestApi {
         Ok(TokenAmount::from_atto(100))
     }
 
+    fn load_local_messages(&self) -> Result<Vec<SignedMessage>, Error> {
+        Ok(self.inner.lock().local_msgs.clone())
+    }
+
+    fn save_local_message(&self, msg: &SignedMessage) -> Result<(), Error> {
+        let mut inner = self.inner.lock();
+        if !inner.local_msgs.contains(msg) {
+            inner.local_msgs.push(msg.clone());
+        }
+        Ok(())
+    }
+
+    fn remove_local_message(&self, cid: &Cid) -> Result<(), Error> {
+        self.inner
+            .lock()
+            .local_msgs
+            .retain(|msg| msg.cid().ok().as_ref() != Some(cid));
+        Ok(())
+    }
+
     fn max_actor_pending_messages(&self) -> u64 {
         self.inner.lock().max_actor_pending_messages
     }
@@ -282,3 +311,23 @@ pub fn mock_block_with_parents(
         ..Default::default()
     })
 }
+
+/// Block header mined by `miner` at `epoch` on top of `parents`, with default
+/// fields otherwise.
+pub fn mock_header(miner: u64, epoch: ChainEpoch, parents: TipsetKey) -> CachingBlockHeader {
+    CachingBlockHeader::new(RawBlockHeader {
+        miner_address: Address::new_id(miner),
+        epoch,
+        parents,
+        ..Default::default()
+    })
+}
+
+/// Single-block tipset mined by `miner` at `epoch`, see [`mock_header`].
+pub fn mock_tipset(miner: u64, epoch: ChainEpoch) -> Arc<Tipset> {
+    Arc::new(Tipset::from(mock_header(
+        miner,
+        epoch,
+        TipsetKey::default(),
+    )))
+}
@@ -52,6 +52,6 @@ pub(in crate::message_pool) fn recover_sig(
     let val = bls_sig_cache
         .get(&msg.cid()?)
         .ok_or_else(|| Error::Other("Could not recover sig".to_owned()))?;
-    let smsg = SignedMessage::new_from_parts(msg, val.clone())?;
-    Ok(smsg)
+    // Only signatures of messages that passed verification are cached
+    Ok(SignedMessage::new_unchecked(msg, val.clone()))
 }
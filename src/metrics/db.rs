@@ -2,21 +2,28 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use std::path::PathBuf;
+use std::sync::Arc;
 
+use crate::db::DBStatistics;
 use prometheus::{
     core::{Collector, Desc},
-    proto, Gauge, Opts,
+    proto, Gauge, GaugeVec, Opts,
 };
 use tracing::error;
 
-pub struct DBCollector {
+pub struct DBCollector<DB> {
     db_directory: PathBuf,
+    db: Arc<DB>,
     descs: Vec<Desc>,
     db_size: Gauge,
+    column_size: GaugeVec,
+    column_files: GaugeVec,
+    column_compaction_pending: GaugeVec,
+    column_cache_usage: GaugeVec,
 }
 
-impl DBCollector {
-    pub fn new(db_directory: PathBuf) -> Self {
+impl<DB> DBCollector<DB> {
+    pub fn new(db_directory: PathBuf, db: Arc<DB>) -> Self {
         let mut descs: Vec<Desc> = vec![];
         let db_size = Gauge::with_opts(Opts::new(
             "forest_db_size",
@@ -24,15 +31,46 @@ impl DBCollector {
         ))
         .expect("Creating forest_db_size gauge must succeed");
         descs.extend(db_size.desc().into_iter().cloned());
+        let column_gauge = |descs: &mut Vec<Desc>, name: &str, help: &str| {
+            let gauge = GaugeVec::new(Opts::new(name, help), &[super::labels::COLUMN])
+                .unwrap_or_else(|_| panic!("Creating {name} gauge must succeed"));
+            descs.extend(gauge.desc().into_iter().cloned());
+            gauge
+        };
+        let column_size = column_gauge(
+            &mut descs,
+            "forest_db_column_size",
+            "Size of a column of Forest database in bytes",
+        );
+        let column_files = column_gauge(
+            &mut descs,
+            "forest_db_column_files",
+            "Number of files of a column of Forest database",
+        );
+        let column_compaction_pending = column_gauge(
+            &mut descs,
+            "forest_db_column_compaction_pending",
+            "Estimated bytes to be rewritten by the compaction of a column of Forest database",
+        );
+        let column_cache_usage = column_gauge(
+            &mut descs,
+            "forest_db_column_cache_usage",
+            "Memory used by the block cache of a column of Forest database in bytes",
+        );
         Self {
             db_directory,
+            db,
             descs,
             db_size,
+            column_size,
+            column_files,
+            column_compaction_pending,
+            column_cache_usage,
         }
     }
 }
 
-impl Collector for DBCollector {
+impl<DB: DBStatistics + Send + Sync> Collector for DBCollector<DB> {
     fn desc(&self) -> Vec<&Desc> {
         self.descs.iter().collect()
     }
@@ -48,8 +86,74 @@ impl Collector for DBCollector {
 
         self.db_size.set(db_size as f64);
 
+        let column_gauges = [
+            &self.column_size,
+            &self.column_files,
+            &self.column_compaction_pending,
+            &self.column_cache_usage,
+        ];
+        for gauge in column_gauges {
+            gauge.reset();
+        }
+        for column in self.db.get_column_statistics() {
+            let values = [
+                column.size_bytes,
+                column.file_count,
+                column.compaction_pending_bytes,
+                column.cache_usage_bytes,
+            ];
+            for (gauge, value) in column_gauges.iter().zip(values) {
+                if let Some(value) = value {
+                    gauge.with_label_values(&[&column.name]).set(value as f64);
+                }
+            }
+        }
+
         let mut metric_families = vec![];
         metric_families.extend(self.db_size.collect());
+        for gauge in column_gauges {
+            metric_families.extend(gauge.collect());
+        }
         metric_families
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::ColumnStatistics;
+
+    struct FakeDb;
+
+    impl DBStatistics for FakeDb {
+        fn get_column_statistics(&self) -> Vec<ColumnStatistics> {
+            vec![ColumnStatistics {
+                name: "graph".into(),
+                size_bytes: Some(42),
+                file_count: Some(3),
+                ..Default::default()
+            }]
+        }
+    }
+
+    #[test]
+    fn collects_column_statistics() {
+        let db_directory = tempfile::tempdir().unwrap();
+        let collector = DBCollector::new(db_directory.path().into(), Arc::new(FakeDb));
+        let families = collector.collect();
+        let value = |name: &str| {
+            families
+                .iter()
+                .find(|family| family.get_name() == name)
+                .and_then(|family| family.get_metric().first())
+                .map(|metric| {
+                    assert_eq!(metric.get_label()[0].get_value(), "graph");
+                    metric.get_gauge().get_value()
+                })
+        };
+        assert_eq!(value("forest_db_column_size"), Some(42.0));
+        assert_eq!(value("forest_db_column_files"), Some(3.0));
+        // Statistics the backend doesn't track aren't reported.
+        assert_eq!(value("forest_db_column_cache_usage"), None);
+    }
+}
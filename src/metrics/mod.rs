@@ -1,18 +1,44 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+#[cfg(feature = "metrics-server")]
 pub mod db;
+#[cfg(feature = "metrics-server")]
+mod server;
+
+#[cfg(feature = "metrics-server")]
+pub use server::init_prometheus;
+
+use std::path::PathBuf;
 
-use crate::db::DBStatistics;
-use axum::{http::StatusCode, response::IntoResponse, routing::get, Router};
 use once_cell::sync::Lazy;
 use parking_lot::RwLock;
 use prometheus::core::{AtomicU64, GenericCounterVec, Opts};
-use prometheus::{Encoder, TextEncoder};
-use std::path::PathBuf;
-use std::sync::Arc;
-use tokio::net::TcpListener;
-use tracing::warn;
+use serde::{Deserialize, Serialize};
+
+/// Protection of the metrics endpoint, which is often exposed on shared networks.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+pub struct MetricsConfig {
+    /// PEM certificate chain. The endpoint is served over TLS when both `tls_cert` and `tls_key`
+    /// are set.
+    pub tls_cert: Option<PathBuf>,
+    /// PEM private key of `tls_cert`.
+    pub tls_key: Option<PathBuf>,
+    /// Credentials required with HTTP basic authentication.
+    pub basic_auth: Option<BasicAuth>,
+    /// Token required with HTTP bearer authentication.
+    pub bearer_token: Option<String>,
+}
+
+/// Credentials of HTTP basic authentication.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+pub struct BasicAuth {
+    pub username: String,
+    pub password: String,
+}
 
 pub static DEFAULT_REGISTRY: Lazy<RwLock<prometheus_client::registry::Registry>> =
     Lazy::new(Default::default);
@@ -44,75 +70,12 @@ pub static LRU_CACHE_MISS: Lazy<Box<GenericCounterVec<AtomicU64>>> = Lazy::new(|
     lru_cache_miss
 });
 
-pub async fn init_prometheus<DB>(
-    prometheus_listener: TcpListener,
-    db_directory: PathBuf,
-    db: Arc<DB>,
-) -> anyhow::Result<()>
-where
-    DB: DBStatistics + Send + Sync + 'static,
-{
-    let registry = prometheus::default_registry();
-
-    // Add the DBCollector to the registry
-    let db_collector = crate::metrics::db::DBCollector::new(db_directory);
-    registry.register(Box::new(db_collector))?;
-
-    // Create an configure HTTP server
-    let app = Router::new()
-        .route("/metrics", get(collect_prometheus_metrics))
-        .route("/stats/db", get(collect_db_metrics::<DB>))
-        .with_state(db);
-
-    // Wait for server to exit
-    Ok(axum::serve(prometheus_listener, app.into_make_service()).await?)
-}
-
-async fn collect_prometheus_metrics() -> impl IntoResponse {
-    let registry = prometheus::default_registry();
-    let metric_families = registry.gather();
-    let mut metrics = vec![];
-
-    let encoder = TextEncoder::new();
-    encoder
-        .encode(&metric_families, &mut metrics)
-        .expect("Encoding Prometheus metrics must succeed.");
-
-    let mut text = String::new();
-    match prometheus_client::encoding::text::encode(&mut text, &DEFAULT_REGISTRY.read()) {
-        Ok(()) => metrics.extend_from_slice(text.as_bytes()),
-        Err(e) => warn!("{e}"),
-    };
-
-    (
-        StatusCode::OK,
-        [("content-type", "text/plain; charset=utf-8")],
-        metrics,
-    )
-}
-
-#[allow(clippy::unused_async)]
-async fn collect_db_metrics<DB>(
-    axum::extract::State(db): axum::extract::State<Arc<DB>>,
-) -> impl IntoResponse
-where
-    DB: DBStatistics,
-{
-    let mut metrics = "# DB statistics:\n".to_owned();
-    if let Some(db_stats) = db.get_statistics() {
-        metrics.push_str(&db_stats);
-    } else {
-        metrics.push_str("Not enabled. Set enable_statistics to true in config and restart daemon");
-    }
-    (
-        StatusCode::OK,
-        [("content-type", "text/plain; charset=utf-8")],
-        metrics,
-    )
-}
-
 pub mod labels {
     pub const KIND: &str = "kind";
+    pub const CALLER: &str = "caller";
+    pub const METHOD: &str = "method";
+    pub const COLUMN: &str = "column";
+    pub const SERVICE: &str = "service";
 }
 
 pub mod values {
@@ -120,4 +83,18 @@ pub mod values {
     pub const TIPSET: &str = "tipset";
     /// tipset cache in state manager
     pub const STATE_MANAGER_TIPSET: &str = "sm_tipset";
+    /// actor cache in state manager
+    pub const STATE_MANAGER_ACTOR: &str = "sm_actor";
+    /// state view cache in state manager
+    pub const STATE_MANAGER_VIEW: &str = "sm_view";
+    /// `StateCall` result cache in state manager
+    pub const STATE_MANAGER_CALL: &str = "sm_call";
+    /// hot tier of the block cache
+    pub const BLOCK_CACHE_HOT: &str = "block_cache_hot";
+    /// recent tier of the block cache
+    pub const BLOCK_CACHE_RECENT: &str = "block_cache_recent";
+    /// tipset weight cache in consensus
+    pub const TIPSET_WEIGHT: &str = "tipset_weight";
+    /// Verified BLS aggregate signatures cache
+    pub const BLS_AGGREGATE: &str = "bls_aggregate";
 }
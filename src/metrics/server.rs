@@ -0,0 +1,232 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{MetricsConfig, DEFAULT_REGISTRY};
+use crate::db::DBStatistics;
+use anyhow::Context as _;
+use axum::{
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use axum_server::tls_rustls::RustlsConfig;
+use base64::{prelude::BASE64_STANDARD, Engine as _};
+use prometheus::{Encoder, TextEncoder};
+use std::path::PathBuf;
+use std::sync::Arc;
+use subtle::{Choice, ConstantTimeEq as _};
+use tokio::net::TcpListener;
+use tracing::warn;
+
+pub async fn init_prometheus<DB>(
+    prometheus_listener: TcpListener,
+    db_directory: PathBuf,
+    db: Arc<DB>,
+    config: MetricsConfig,
+) -> anyhow::Result<()>
+where
+    DB: DBStatistics + Send + Sync + 'static,
+{
+    let registry = prometheus::default_registry();
+
+    // Add the DBCollector to the registry
+    let db_collector = crate::metrics::db::DBCollector::new(db_directory, db.clone());
+    registry.register(Box::new(db_collector))?;
+
+    // Create an configure HTTP server
+    let app = Router::new()
+        .route("/metrics", get(collect_prometheus_metrics))
+        .route("/stats/db", get(collect_db_metrics::<DB>))
+        .route("/stats/db/columns", get(collect_db_column_metrics::<DB>))
+        .with_state(db);
+    let app = match authorization(&config) {
+        Some(expected) => app.layer(middleware::from_fn_with_state(
+            Arc::new(expected),
+            require_authorization,
+        )),
+        None => app,
+    };
+
+    // Wait for server to exit
+    match (&config.tls_cert, &config.tls_key) {
+        (Some(cert), Some(key)) => {
+            let tls = RustlsConfig::from_pem_file(cert, key)
+                .await
+                .context("failed to load the TLS certificate of the metrics endpoint")?;
+            Ok(
+                axum_server::from_tcp_rustls(prometheus_listener.into_std()?, tls)
+                    .serve(app.into_make_service())
+                    .await?,
+            )
+        }
+        (None, None) => Ok(axum::serve(prometheus_listener, app.into_make_service()).await?),
+        _ => anyhow::bail!("both `tls_cert` and `tls_key` must be set to serve metrics over TLS"),
+    }
+}
+
+/// Returns the `(scheme, credentials)` pairs of the `Authorization` header accepted by the
+/// endpoint, if it is protected.
+fn authorization(config: &MetricsConfig) -> Option<Vec<(&'static str, String)>> {
+    let basic = config.basic_auth.iter().map(|auth| {
+        let credentials = BASE64_STANDARD.encode(format!("{}:{}", auth.username, auth.password));
+        ("Basic", credentials)
+    });
+    let bearer = config
+        .bearer_token
+        .iter()
+        .map(|token| ("Bearer", token.clone()));
+    let accepted = basic.chain(bearer).collect::<Vec<_>>();
+    (!accepted.is_empty()).then_some(accepted)
+}
+
+/// Checks an `Authorization` header value against the accepted credentials. The scheme is
+/// case-insensitive, and the credentials are compared in constant time.
+fn is_authorized(accepted: &[(&'static str, String)], authorization: &str) -> bool {
+    let Some((scheme, credentials)) = authorization.split_once(' ') else {
+        return false;
+    };
+    let credentials = credentials.trim_start().as_bytes();
+    accepted
+        .iter()
+        .fold(
+            Choice::from(0),
+            |authorized, (accepted_scheme, accepted_credentials)| {
+                let scheme_matches =
+                    Choice::from(u8::from(scheme.eq_ignore_ascii_case(accepted_scheme)));
+                authorized | (scheme_matches & credentials.ct_eq(accepted_credentials.as_bytes()))
+            },
+        )
+        .into()
+}
+
+async fn require_authorization(
+    axum::extract::State(accepted): axum::extract::State<Arc<Vec<(&'static str, String)>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| is_authorized(&accepted, value));
+    if authorized {
+        next.run(request).await
+    } else {
+        (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Basic realm=\"forest\"")],
+        )
+            .into_response()
+    }
+}
+
+async fn collect_prometheus_metrics() -> impl IntoResponse {
+    let registry = prometheus::default_registry();
+    let metric_families = registry.gather();
+    let mut metrics = vec![];
+
+    let encoder = TextEncoder::new();
+    encoder
+        .encode(&metric_families, &mut metrics)
+        .expect("Encoding Prometheus metrics must succeed.");
+
+    let mut text = String::new();
+    match prometheus_client::encoding::text::encode(&mut text, &DEFAULT_REGISTRY.read()) {
+        Ok(()) => metrics.extend_from_slice(text.as_bytes()),
+        Err(e) => warn!("{e}"),
+    };
+
+    (
+        StatusCode::OK,
+        [("content-type", "text/plain; charset=utf-8")],
+        metrics,
+    )
+}
+
+#[allow(clippy::unused_async)]
+async fn collect_db_metrics<DB>(
+    axum::extract::State(db): axum::extract::State<Arc<DB>>,
+) -> impl IntoResponse
+where
+    DB: DBStatistics,
+{
+    let mut metrics = "# DB statistics:\n".to_owned();
+    if let Some(db_stats) = db.get_statistics() {
+        metrics.push_str(&db_stats);
+    } else {
+        metrics.push_str("Not enabled. Set enable_statistics to true in config and restart daemon");
+    }
+    (
+        StatusCode::OK,
+        [("content-type", "text/plain; charset=utf-8")],
+        metrics,
+    )
+}
+
+/// Serves the statistics of each column of the database as JSON.
+#[allow(clippy::unused_async)]
+async fn collect_db_column_metrics<DB>(
+    axum::extract::State(db): axum::extract::State<Arc<DB>>,
+) -> impl IntoResponse
+where
+    DB: DBStatistics,
+{
+    Json(db.get_column_statistics())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::BasicAuth;
+
+    #[test]
+    fn unprotected_by_default() {
+        assert_eq!(authorization(&MetricsConfig::default()), None);
+    }
+
+    #[test]
+    fn accepts_basic_and_bearer_credentials() {
+        let config = MetricsConfig {
+            basic_auth: Some(BasicAuth {
+                username: "Aladdin".into(),
+                password: "open sesame".into(),
+            }),
+            bearer_token: Some("token".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            authorization(&config).unwrap(),
+            [
+                ("Basic", "QWxhZGRpbjpvcGVuIHNlc2FtZQ==".to_owned()),
+                ("Bearer", "token".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn matches_the_scheme_case_insensitively() {
+        let accepted = [
+            ("Basic", "QWxhZGRpbjpvcGVuIHNlc2FtZQ==".to_owned()),
+            ("Bearer", "token".to_owned()),
+        ];
+        assert!(is_authorized(
+            &accepted,
+            "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ=="
+        ));
+        assert!(is_authorized(
+            &accepted,
+            "basic QWxhZGRpbjpvcGVuIHNlc2FtZQ=="
+        ));
+        assert!(is_authorized(&accepted, "BEARER token"));
+        assert!(!is_authorized(
+            &accepted,
+            "Bearer QWxhZGRpbjpvcGVuIHNlc2FtZQ=="
+        ));
+        assert!(!is_authorized(&accepted, "Bearer Token"));
+        assert!(!is_authorized(&accepted, "Bearer"));
+        assert!(!is_authorized(&accepted, "token"));
+    }
+}
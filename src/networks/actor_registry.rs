@@ -0,0 +1,103 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Names of the builtin actors by code CID, for all the [actor bundles](ACTOR_BUNDLES) of all
+//! the networks. Code CIDs are opaque, while names like `fil/12/storageminer` tell the actor and
+//! the version of the actors it was released with.
+
+use std::fmt;
+
+use ahash::{HashMap, HashMapExt as _};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use tracing::warn;
+
+use super::ACTOR_BUNDLES;
+use crate::shim::machine::{BuiltinActor, BuiltinActorManifest};
+
+/// A builtin actor of a given actors version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ActorCodeInfo {
+    pub actor: BuiltinActor,
+    /// Major version of the actors, e.g., `12`.
+    pub version: u64,
+}
+
+/// Formats the actor like Lotus does, e.g., `fil/12/storageminer`.
+impl fmt::Display for ActorCodeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fil/{}/{}", self.version, self.actor.name())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ActorRegistry {
+    codes: HashMap<Cid, ActorCodeInfo>,
+}
+
+impl ActorRegistry {
+    /// Registers the actors of the bundles in `db`. Bundles that haven't been loaded, e.g., those
+    /// of other networks, are skipped.
+    pub fn load(db: &impl Blockstore) -> Self {
+        let mut codes = HashMap::new();
+        for bundle in ACTOR_BUNDLES.iter() {
+            if !db.has(&bundle.manifest).unwrap_or(false) {
+                continue;
+            }
+            let manifest = match BuiltinActorManifest::load_manifest(db, &bundle.manifest) {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    warn!("Failed to load the manifest of bundle {bundle:?}: {e}");
+                    continue;
+                }
+            };
+            let version = bundle.actors_version();
+            for (actor, code) in manifest.builtin_actors() {
+                codes.insert(code, ActorCodeInfo { actor, version });
+            }
+        }
+        Self { codes }
+    }
+
+    pub fn get(&self, code: &Cid) -> Option<ActorCodeInfo> {
+        self.codes.get(code).copied()
+    }
+
+    /// Returns the name of the actor with the code CID `code`, or the CID itself if it isn't
+    /// registered.
+    pub fn name(&self, code: &Cid) -> String {
+        match self.get(code) {
+            Some(info) => info.to_string(),
+            None => code.to_string(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.codes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_like_lotus() {
+        let info = ActorCodeInfo {
+            actor: BuiltinActor::Miner,
+            version: 12,
+        };
+        assert_eq!(info.to_string(), "fil/12/storageminer");
+    }
+
+    #[test]
+    fn unknown_codes_are_named_by_cid() {
+        let registry = ActorRegistry::load(&crate::db::MemoryDB::default());
+        assert!(registry.is_empty());
+        assert_eq!(registry.name(&Cid::default()), Cid::default().to_string());
+    }
+}
@@ -12,11 +12,14 @@ use futures::{stream, StreamExt, TryStreamExt};
 use itertools::Itertools;
 use once_cell::sync::Lazy;
 use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
 use tokio::fs::File;
 use tracing::warn;
 
 use crate::utils::db::car_stream::{CarStream, CarWriter};
 use crate::utils::net::http_get;
+use crate::utils::outbound::{retry, OutboundService};
 
 use std::str::FromStr;
 
@@ -31,6 +34,35 @@ pub struct ActorBundleInfo {
     /// ourselves when a new bundle is released.
     pub alt_url: Url,
     pub network: NetworkChain,
+    /// Release of the builtin actors, e.g., `v12.0.0`.
+    pub version: &'static str,
+}
+
+impl ActorBundleInfo {
+    /// Major version of the actors of the bundle, e.g., `12` for `v12.0.0`.
+    pub fn actors_version(&self) -> u64 {
+        self.version
+            .trim_start_matches('v')
+            .split('.')
+            .next()
+            .and_then(|major| major.parse().ok())
+            .expect("bundle versions are hard-coded")
+    }
+}
+
+/// Actor bundle fetched in addition to the compiled-in ones, e.g. for a network
+/// upgrade scheduled after the release.
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+pub struct ActorBundleSource {
+    /// Manifest CID, the single root of the bundle CAR.
+    #[serde_as(as = "DisplayFromStr")]
+    pub manifest: Cid,
+    #[cfg_attr(test, arbitrary(gen(
+        |_| "https://example.com/builtin-actors.car".parse().unwrap()
+    )))]
+    pub url: Url,
 }
 
 macro_rules! actor_bundle_info {
@@ -54,6 +86,7 @@ macro_rules! actor_bundle_info {
                             ".car"
                         ).parse().unwrap(),
                     network: NetworkChain::from_str($network).unwrap(),
+                    version: $version,
                 },
             )*
         ]
@@ -87,15 +120,18 @@ pub async fn generate_actor_bundle(output: &Path) -> anyhow::Result<()> {
              manifest: root,
              url,
              alt_url,
-             network: _,
+             ..
          }| async move {
-            let response = if let Ok(response) = http_get(url).await {
-                response
-            } else {
-                warn!("failed to download bundle from primary URL, trying alternative URL");
-                http_get(alt_url).await?
-            };
-            let bytes = response.bytes().await?;
+            let bytes = retry(OutboundService::ActorBundle, || async {
+                let response = if let Ok(response) = http_get(url).await {
+                    response
+                } else {
+                    warn!("failed to download bundle from primary URL, trying alternative URL");
+                    http_get(alt_url).await?
+                };
+                anyhow::Ok(response.bytes().await?)
+            })
+            .await?;
             let car = CarStream::new(Cursor::new(bytes)).await?;
             ensure!(car.header.version == 1);
             ensure!(car.header.roots.len() == 1);
@@ -148,6 +184,13 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn bundle_versions() {
+        for bundle in ACTOR_BUNDLES.iter() {
+            assert!(bundle.actors_version() >= 9, "{bundle:?}");
+        }
+    }
+
     #[tokio::test]
     async fn check_bundles_are_mirrored() {
         // Run the test only in CI so that regular test on dev machines don't download the bundles
@@ -161,7 +204,7 @@ mod tests {
                  manifest,
                  url,
                  alt_url,
-                 network: _,
+                 ..
              }| async move {
                 let (primary, alt) = match (http_get(url).await, http_get(alt_url).await) {
                     (Ok(primary), Ok(alt)) => (primary, alt),
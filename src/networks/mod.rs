@@ -1,13 +1,16 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::{fmt::Display, str::FromStr};
+use std::{cmp::Ordering, fmt::Display, str::FromStr};
 
+use ahash::HashMap;
+use anyhow::ensure;
 use cid::Cid;
 use fil_actors_shared::v10::runtime::Policy;
 use libp2p::Multiaddr;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
 use strum_macros::Display;
 
 use crate::beacon::{BeaconPoint, BeaconSchedule, DrandBeacon, DrandConfig};
@@ -17,8 +20,10 @@ use crate::shim::clock::{ChainEpoch, EPOCH_DURATION_SECONDS};
 use crate::shim::sector::{RegisteredPoStProofV3, RegisteredSealProofV3};
 use crate::shim::version::NetworkVersion;
 
+mod actor_registry;
 mod actors_bundle;
-pub use actors_bundle::{generate_actor_bundle, ActorBundleInfo, ACTOR_BUNDLES};
+pub use actor_registry::{ActorCodeInfo, ActorRegistry};
+pub use actors_bundle::{generate_actor_bundle, ActorBundleInfo, ActorBundleSource, ACTOR_BUNDLES};
 
 mod drand;
 
@@ -27,6 +32,19 @@ pub mod calibnet;
 pub mod devnet;
 pub mod mainnet;
 
+/// Blocks of calibnet and mainnet at regular epochs, as CIDs by epoch, see
+/// `build/known_blocks.yaml`.
+#[derive(Deserialize)]
+pub struct KnownBlocks {
+    pub calibnet: HashMap<ChainEpoch, String>,
+    pub mainnet: HashMap<ChainEpoch, String>,
+}
+
+pub static KNOWN_BLOCKS: Lazy<KnownBlocks> = Lazy::new(|| {
+    serde_yaml::from_str(include_str!("../../build/known_blocks.yaml"))
+        .expect("known blocks must be valid")
+});
+
 /// Newest network version for all networks
 pub const NEWEST_NETWORK_VERSION: NetworkVersion = NetworkVersion::V17;
 
@@ -95,8 +113,10 @@ impl NetworkChain {
     }
 }
 
-/// Defines the meaningful heights of the protocol.
-#[derive(Debug, Display, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+/// Defines the meaningful heights of the protocol, in upgrade order.
+#[derive(
+    Debug, Display, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+)]
 #[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
 pub enum Height {
     Breeze,
@@ -162,11 +182,13 @@ impl From<Height> for NetworkVersion {
     }
 }
 
+#[serde_as]
 #[derive(Default, Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
 pub struct HeightInfo {
     pub height: Height,
     pub epoch: ChainEpoch,
+    #[serde_as(as = "Option<DisplayFromStr>")]
     pub bundle: Option<Cid>,
 }
 
@@ -176,6 +198,19 @@ pub fn sort_by_epoch(height_info_slice: &[HeightInfo]) -> Vec<HeightInfo> {
     height_info_vec
 }
 
+/// Consensus the heaviest chain is selected and its blocks are validated with.
+#[derive(Default, Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(rename_all = "kebab-case")]
+pub enum ConsensusKind {
+    /// Expected Consensus, as on the Filecoin networks.
+    #[default]
+    Ec,
+    /// Testing consensus with instant finality, for devnets. Blocks aren't
+    /// checked for winning elections, and the head is never reverted.
+    Instant,
+}
+
 #[derive(Clone)]
 struct DrandPoint<'a> {
     pub height: ChainEpoch,
@@ -204,6 +239,7 @@ pub struct ChainConfig {
     #[serde(default = "default_policy")]
     pub policy: Policy,
     pub eth_chain_id: u32,
+    pub consensus: ConsensusKind,
 }
 
 impl ChainConfig {
@@ -218,6 +254,7 @@ impl ChainConfig {
             height_infos: HEIGHT_INFOS.to_vec(),
             policy: Policy::mainnet(),
             eth_chain_id: ETH_CHAIN_ID as u32,
+            consensus: ConsensusKind::Ec,
         }
     }
 
@@ -232,6 +269,7 @@ impl ChainConfig {
             height_infos: HEIGHT_INFOS.to_vec(),
             policy: Policy::calibnet(),
             eth_chain_id: ETH_CHAIN_ID as u32,
+            consensus: ConsensusKind::Ec,
         }
     }
 
@@ -264,6 +302,7 @@ impl ChainConfig {
             height_infos: HEIGHT_INFOS.to_vec(),
             policy,
             eth_chain_id: ETH_CHAIN_ID as u32,
+            consensus: ConsensusKind::Ec,
         }
     }
 
@@ -279,6 +318,7 @@ impl ChainConfig {
             height_infos: HEIGHT_INFOS.to_vec(),
             policy: make_butterfly_policy!(v10),
             eth_chain_id: ETH_CHAIN_ID as u32,
+            consensus: ConsensusKind::Ec,
         }
     }
 
@@ -294,6 +334,54 @@ impl ChainConfig {
         }
     }
 
+    /// Reschedules the upgrades of the network at the heights of `upgrades`.
+    /// Upgrades without a bundle keep the one compiled in for their height.
+    /// Upgrades after genesis must happen in upgrade order.
+    pub fn with_upgrades(mut self, upgrades: &[HeightInfo]) -> anyhow::Result<Self> {
+        for upgrade in upgrades {
+            ensure!(
+                upgrades
+                    .iter()
+                    .filter(|other| other.height == upgrade.height)
+                    .count()
+                    == 1,
+                "upgrade {} is scheduled more than once",
+                upgrade.height
+            );
+            match self
+                .height_infos
+                .iter_mut()
+                .find(|info| info.height == upgrade.height)
+            {
+                Some(info) => {
+                    info.epoch = upgrade.epoch;
+                    info.bundle = upgrade.bundle.or(info.bundle);
+                }
+                None => self.height_infos.push(upgrade.clone()),
+            }
+        }
+
+        // Upgrades at or before genesis are all in effect from the start.
+        for upgrade in upgrades {
+            for info in &self.height_infos {
+                let (prev, next) = match info.height.cmp(&upgrade.height) {
+                    Ordering::Less => (info, upgrade),
+                    Ordering::Greater => (upgrade, info),
+                    Ordering::Equal => continue,
+                };
+                ensure!(
+                    prev.epoch.max(0) <= next.epoch.max(0),
+                    "upgrade {} at epoch {} is scheduled before upgrade {} at epoch {}",
+                    next.height,
+                    next.epoch,
+                    prev.height,
+                    prev.epoch
+                );
+            }
+        }
+        Ok(self)
+    }
+
     pub fn network_version(&self, epoch: ChainEpoch) -> NetworkVersion {
         let height = sort_by_epoch(&self.height_infos)
             .iter()
@@ -305,6 +393,16 @@ impl ChainConfig {
         From::from(height)
     }
 
+    /// Returns the manifest of the actor bundle in use at network version
+    /// `version`, if actors are deployed from bundles at that version.
+    pub fn manifest_cid(&self, version: NetworkVersion) -> Option<Cid> {
+        sort_by_epoch(&self.height_infos)
+            .iter()
+            .rev()
+            .filter(|info| NetworkVersion::from(info.height) <= version)
+            .find_map(|info| info.bundle)
+    }
+
     pub fn get_beacon_schedule(&self, genesis_ts: u64) -> BeaconSchedule {
         let ds_iter = match self.network {
             NetworkChain::Mainnet => mainnet::DRAND_SCHEDULE.iter(),
@@ -374,3 +472,66 @@ pub(crate) fn parse_bootstrap_peers(bootstrap_peer_list: &str) -> Vec<Multiaddr>
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upgrades_are_rescheduled() {
+        let upgrade = |height, epoch| HeightInfo {
+            height,
+            epoch,
+            bundle: None,
+        };
+        let config = ChainConfig::devnet()
+            .with_upgrades(&[upgrade(Height::Watermelon, 100)])
+            .unwrap();
+        assert_eq!(config.epoch(Height::Watermelon), 100);
+        assert_eq!(config.network_version(100), NetworkVersion::V20);
+        assert_eq!(config.network_version(101), NetworkVersion::V21);
+        // The compiled-in bundle is kept.
+        assert!(config
+            .height_infos
+            .iter()
+            .any(|info| info.height == Height::Watermelon && info.bundle.is_some()));
+
+        // Out of order
+        assert!(ChainConfig::devnet()
+            .with_upgrades(&[
+                upgrade(Height::Thunder, 200),
+                upgrade(Height::Watermelon, 100)
+            ])
+            .is_err());
+        // Duplicated
+        assert!(ChainConfig::devnet()
+            .with_upgrades(&[
+                upgrade(Height::Watermelon, 100),
+                upgrade(Height::Watermelon, 200)
+            ])
+            .is_err());
+    }
+
+    #[test]
+    fn manifest_by_network_version() {
+        let config = ChainConfig::mainnet();
+        let bundle = |height| {
+            config
+                .height_infos
+                .iter()
+                .find(|info| info.height == height)
+                .and_then(|info| info.bundle)
+        };
+        assert_eq!(
+            config.manifest_cid(NetworkVersion::V21),
+            bundle(Height::Watermelon)
+        );
+        // Upgrades without a bundle keep the previous one.
+        assert_eq!(
+            config.manifest_cid(NetworkVersion::V20),
+            bundle(Height::Lightning)
+        );
+        assert!(config.manifest_cid(NetworkVersion::V20).is_some());
+        assert_eq!(config.manifest_cid(NetworkVersion::V15), None);
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+#![allow(clippy::unused_async)]
+
+use std::sync::Arc;
+
+use crate::blocks::Tipset;
+use crate::chain::{
+    event_index::{self, ActorEvent},
+    index::ResolveNullTipset,
+};
+use crate::lotus_json::LotusJson;
+use crate::rpc_api::data_types::{ActorEventFilter, RPCState};
+use crate::shim::{address::Address, clock::ChainEpoch};
+use ahash::HashSet;
+use anyhow::Context as _;
+use fvm_ipld_blockstore::Blockstore;
+use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
+
+/// Maximum number of epochs the range of a filter can span, as in Lotus.
+const MAX_FILTER_HEIGHT_RANGE: ChainEpoch = 2880;
+
+/// Returns the indexed actor events matching the filter.
+pub(in crate::rpc) async fn get_actor_events_raw<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((filter,))): Params<LotusJson<(Option<ActorEventFilter>,)>>,
+) -> Result<Vec<ActorEvent>, JsonRpcError> {
+    Ok(actor_events(&data, &filter.unwrap_or_default())?)
+}
+
+/// Streams the actor events matching the filter, over WebSocket. Served by
+/// the WebSocket handler.
+pub(in crate::rpc) async fn subscribe_actor_events_raw<DB: Blockstore>(
+    _data: Data<RPCState<DB>>,
+) -> Result<(), JsonRpcError> {
+    Err(JsonRpcError::METHOD_NOT_FOUND)
+}
+
+fn actor_events<DB: Blockstore>(
+    data: &RPCState<DB>,
+    filter: &ActorEventFilter,
+) -> anyhow::Result<Vec<ActorEvent>> {
+    let head = data.chain_store.heaviest_tipset();
+    let tipsets: Vec<Arc<Tipset>> = match &filter.tipset_key {
+        Some(tsk) => {
+            anyhow::ensure!(
+                filter.from_height.is_none() && filter.to_height.is_none(),
+                "tipsetKey can't be used along with fromHeight or toHeight"
+            );
+            vec![data.chain_store.load_required_tipset(tsk)?]
+        }
+        None => {
+            let from = filter.from_height.unwrap_or(head.epoch());
+            let to = filter.to_height.unwrap_or(head.epoch()).min(head.epoch());
+            anyhow::ensure!(
+                to - from <= MAX_FILTER_HEIGHT_RANGE,
+                "the range of epochs can't exceed {MAX_FILTER_HEIGHT_RANGE}"
+            );
+            let to = data.chain_store.chain_index.tipset_by_height(
+                to,
+                Arc::clone(&head),
+                ResolveNullTipset::TakeOlder,
+            )?;
+            data.chain_store
+                .chain_index
+                .chain(to)
+                .take_while(|tipset| tipset.epoch() >= from)
+                .collect()
+        }
+    };
+    let emitters = emitters(data, &head, &filter.addresses)?;
+
+    let mut events = Vec::new();
+    // From the oldest tipset.
+    for tipset in tipsets.iter().rev() {
+        let indexed = event_index::events_at(data.chain_store.settings().as_ref(), tipset.epoch())?;
+        events.extend(indexed.into_iter().filter(|event| {
+            // Events of reverted tipsets are indexed until replaced.
+            event.tipset_key == *tipset.key()
+                && (emitters.is_empty() || emitters.contains(&event.emitter))
+                && matches_fields(event, filter)
+        }));
+    }
+    Ok(events)
+}
+
+/// Returns the addresses of the emitters, by ID and by delegated address.
+fn emitters<DB: Blockstore>(
+    data: &RPCState<DB>,
+    head: &Tipset,
+    addresses: &[Address],
+) -> anyhow::Result<HashSet<Address>> {
+    let mut emitters = HashSet::default();
+    for address in addresses {
+        emitters.insert(*address);
+        let Some(id) = data.state_manager.lookup_id(address, head)? else {
+            continue;
+        };
+        emitters.insert(id);
+        let delegated = data
+            .state_manager
+            .get_actor(&id, *head.parent_state())?
+            .context("failed to load actor state")?
+            .delegated_address;
+        emitters.extend(delegated.map(Address::from));
+    }
+    Ok(emitters)
+}
+
+fn matches_fields(event: &ActorEvent, filter: &ActorEventFilter) -> bool {
+    filter.fields.iter().all(|(key, values)| {
+        event.entries.iter().any(|entry| {
+            entry.key == *key
+                && (values.is_empty()
+                    || values
+                        .iter()
+                        .any(|value| value.codec == entry.codec && value.value == entry.value))
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::TipsetKey;
+    use crate::chain::event_index::EventEntry;
+    use crate::rpc_api::data_types::ActorEventBlock;
+    use cid::Cid;
+
+    #[test]
+    fn events_match_fields() {
+        let event = ActorEvent {
+            entries: vec![EventEntry {
+                flags: 3,
+                key: "t1".into(),
+                codec: 0x55,
+                value: b"transfer".to_vec(),
+            }],
+            emitter: Address::new_id(1000),
+            reverted: false,
+            height: 42,
+            tipset_key: TipsetKey::from_iter([Cid::default()]),
+            msg_cid: Cid::default(),
+        };
+        let filter = |key: &str, values: &[&[u8]]| ActorEventFilter {
+            fields: [(
+                key.to_owned(),
+                values
+                    .iter()
+                    .map(|value| ActorEventBlock {
+                        codec: 0x55,
+                        value: value.to_vec(),
+                    })
+                    .collect(),
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+
+        assert!(matches_fields(&event, &ActorEventFilter::default()));
+        assert!(matches_fields(&event, &filter("t1", &[])));
+        assert!(matches_fields(
+            &event,
+            &filter("t1", &[b"mint", b"transfer"])
+        ));
+        assert!(!matches_fields(&event, &filter("t1", &[b"mint"])));
+        assert!(!matches_fields(&event, &filter("t2", &[])));
+    }
+}
@@ -15,7 +15,12 @@ pub(in crate::rpc) async fn auth_new<DB: Blockstore>(
     let auth_params: AuthNewParams = params;
     let ks = data.keystore.read().await;
     let ki = ks.get(JWT_IDENTIFIER)?;
-    let token = create_token(auth_params.perms, ki.private_key(), auth_params.token_exp)?;
+    let token = create_labeled_token(
+        auth_params.perms,
+        auth_params.label,
+        ki.private_key(),
+        auth_params.token_exp,
+    )?;
     Ok(LotusJson(token.as_bytes().to_vec()))
 }
 
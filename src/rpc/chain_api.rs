@@ -3,18 +3,24 @@
 #![allow(clippy::unused_async)]
 
 use crate::blocks::{CachingBlockHeader, Tipset, TipsetKey};
+use crate::chain::compute_base_fee;
 use crate::chain::index::ResolveNullTipset;
+use crate::chain::store::reorg::{reorg_ops, Reorg};
 use crate::cid_collections::CidHashSet;
+use crate::db::car::ForestCar;
 use crate::lotus_json::LotusJson;
 use crate::message::ChainMessage;
+use crate::networks::Height;
 use crate::rpc_api::data_types::{ApiMessage, ApiReceipt};
 use crate::rpc_api::{
     chain_api::*,
     data_types::{BlockMessages, RPCState},
 };
 use crate::shim::clock::ChainEpoch;
+use crate::shim::econ::TokenAmount;
 use crate::shim::message::Message;
-use crate::utils::io::VoidAsyncWriter;
+use crate::utils::io::{EitherMmapOrRandomAccessFile, VoidAsyncWriter};
+use ahash::HashSet;
 use cid::Cid;
 use fil_actors_shared::fvm_ipld_amt::Amtv0 as Amt;
 use fvm_ipld_blockstore::Blockstore;
@@ -24,8 +30,10 @@ use hex::ToHex;
 use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
 use once_cell::sync::Lazy;
 use sha2::Sha256;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use tracing::info;
 
 pub(in crate::rpc) async fn chain_get_message<DB: Blockstore>(
     data: Data<RPCState<DB>>,
@@ -97,14 +105,59 @@ pub(in crate::rpc) async fn chain_get_parent_receipts<DB: Blockstore + Send + Sy
 
 pub(crate) async fn chain_get_messages_in_tipset<DB: Blockstore>(
     data: Data<RPCState<DB>>,
-    Params(LotusJson((tsk,))): Params<LotusJson<(TipsetKey,)>>,
+    Params(params): Params<ChainGetMessagesInTipsetParams>,
 ) -> Result<LotusJson<Vec<ApiMessage>>, JsonRpcError> {
+    let (tsk, options) = params.into_parts();
     let store = data.chain_store.blockstore();
     let tipset = Tipset::load_required(store, &tsk)?;
     let messages = load_api_messages_from_tipset(store, &tipset)?;
-    Ok(LotusJson(messages))
+    Ok(LotusJson(select_messages(messages, &options)))
+}
+
+/// Applies the [`MessagesInTipsetOptions`] to the messages of a tipset.
+fn select_messages(
+    mut messages: Vec<ApiMessage>,
+    options: &MessagesInTipsetOptions,
+) -> Vec<ApiMessage> {
+    if options.dedupe {
+        // Messages are in execution order, only the first message of a sender nonce is executed.
+        let mut seen = HashSet::default();
+        messages.retain(|msg| seen.insert((msg.message().from, msg.message().sequence)));
+    }
+    messages
+        .into_iter()
+        .skip(options.offset)
+        .take(options.limit.unwrap_or(usize::MAX))
+        .collect()
+}
+
+/// Refuses exports to files outside of `export_dir`, if set.
+fn check_export_path(export_dir: Option<&Path>, output_path: &Path) -> Result<(), JsonRpcError> {
+    let Some(export_dir) = export_dir else {
+        return Ok(());
+    };
+    // The file may not exist yet, so its directory is resolved instead.
+    let dir = match output_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let inside = match (dir.canonicalize(), export_dir.canonicalize()) {
+        (Ok(dir), Ok(export_dir)) => dir.starts_with(export_dir),
+        _ => false,
+    };
+    if !inside || output_path.file_name().is_none() {
+        Err(&format!(
+            "{} is not a file of the export directory {}",
+            output_path.display(),
+            export_dir.display()
+        ))?;
+    }
+    Ok(())
 }
 
+/// Only one chain export job may run at a time.
+pub(in crate::rpc) static CHAIN_EXPORT_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
 pub(in crate::rpc) async fn chain_export<DB>(
     data: Data<RPCState<DB>>,
     Params(ChainExportParams {
@@ -114,14 +167,14 @@ pub(in crate::rpc) async fn chain_export<DB>(
         tipset_keys: tsk,
         skip_checksum,
         dry_run,
+        diff,
+        include_old_messages,
     }): Params<ChainExportParams>,
 ) -> Result<Option<String>, JsonRpcError>
 where
     DB: Blockstore + Send + Sync + 'static,
 {
-    static LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
-
-    let _locked = LOCK.try_lock();
+    let _locked = CHAIN_EXPORT_LOCK.try_lock();
     if _locked.is_err() {
         return Err(JsonRpcError::Provided {
             code: http::StatusCode::SERVICE_UNAVAILABLE.as_u16() as _,
@@ -142,6 +195,18 @@ where
             .chain_index
             .tipset_by_height(epoch, head, ResolveNullTipset::TakeOlder)?;
 
+    // Blocks of the base snapshot are looked up in its index, so that neither
+    // they nor the graphs below them are exported again.
+    let base = match diff {
+        Some(base) => {
+            let car = ForestCar::new(EitherMmapOrRandomAccessFile::open(&base)?).map_err(|e| {
+                format!("base snapshot {} must be a forest CAR: {e}", base.display())
+            })?;
+            Some(Arc::new(car) as Arc<dyn Blockstore + Send + Sync>)
+        }
+        None => None,
+    };
+
     match if dry_run {
         crate::chain::export::<Sha256>(
             Arc::clone(&data.chain_store.db),
@@ -149,10 +214,13 @@ where
             recent_roots,
             VoidAsyncWriter,
             CidHashSet::default(),
+            base,
             skip_checksum,
+            include_old_messages,
         )
         .await
     } else {
+        check_export_path(data.export_dir.as_deref(), &output_path)?;
         let file = tokio::fs::File::create(&output_path).await?;
         crate::chain::export::<Sha256>(
             Arc::clone(&data.chain_store.db),
@@ -160,6 +228,66 @@ where
             recent_roots,
             file,
             CidHashSet::default(),
+            base,
+            skip_checksum,
+            include_old_messages,
+        )
+        .await
+    } {
+        Ok(checksum_opt) => Ok(checksum_opt.map(|hash| hash.encode_hex())),
+        Err(e) => Err(JsonRpcError::from(e)),
+    }
+}
+
+pub(in crate::rpc) async fn chain_export_range<DB>(
+    data: Data<RPCState<DB>>,
+    Params(ChainExportRangeParams {
+        from,
+        to,
+        output_path,
+        tipset_keys: tsk,
+        skip_checksum,
+        dry_run,
+    }): Params<ChainExportRangeParams>,
+) -> Result<Option<String>, JsonRpcError>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    let _locked = CHAIN_EXPORT_LOCK.try_lock();
+    if _locked.is_err() {
+        return Err(JsonRpcError::Provided {
+            code: http::StatusCode::SERVICE_UNAVAILABLE.as_u16() as _,
+            message: "Another chain export job is still in progress",
+        });
+    }
+
+    if from > to {
+        Err(&format!("export range start {from} is after its end {to}"))?;
+    }
+
+    let head = data.chain_store.load_required_tipset(&tsk)?;
+    let end_ts =
+        data.chain_store
+            .chain_index
+            .tipset_by_height(to, head, ResolveNullTipset::TakeOlder)?;
+
+    match if dry_run {
+        crate::chain::export_messages::<Sha256>(
+            Arc::clone(&data.chain_store.db),
+            &end_ts,
+            from,
+            VoidAsyncWriter,
+            skip_checksum,
+        )
+        .await
+    } else {
+        check_export_path(data.export_dir.as_deref(), &output_path)?;
+        let file = tokio::fs::File::create(&output_path).await?;
+        crate::chain::export_messages::<Sha256>(
+            Arc::clone(&data.chain_store.db),
+            &end_ts,
+            from,
+            file,
             skip_checksum,
         )
         .await
@@ -169,6 +297,48 @@ where
     }
 }
 
+pub(in crate::rpc) async fn chain_add_car<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((path,))): Params<LotusJson<(PathBuf,)>>,
+) -> Result<LotusJson<Tipset>, JsonRpcError> {
+    let car_files = data.car_files.clone();
+    let heaviest_tipset = tokio::task::spawn_blocking({
+        let path = path.clone();
+        move || car_files.add_read_only_file(&path)
+    })
+    .await??;
+    info!(
+        "Loaded CAR file {} with heaviest epoch {}",
+        path.display(),
+        heaviest_tipset.epoch()
+    );
+    Ok(LotusJson(heaviest_tipset))
+}
+
+pub(in crate::rpc) async fn chain_remove_car<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((path,))): Params<LotusJson<(PathBuf,)>>,
+) -> Result<(), JsonRpcError> {
+    if !data.car_files.remove_read_only_file(&path) {
+        Err(&format!("{} is not loaded", path.display()))?;
+    }
+    info!("Unloaded CAR file {}", path.display());
+    Ok(())
+}
+
+pub(in crate::rpc) async fn chain_list_cars<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+) -> Result<LotusJson<Vec<PathBuf>>, JsonRpcError> {
+    Ok(LotusJson(data.car_files.read_only_file_paths()))
+}
+
+/// Returns the most recent reorgs of the heaviest tipset, oldest first.
+pub(in crate::rpc) async fn chain_get_reorgs<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+) -> Result<LotusJson<Vec<Reorg>>, JsonRpcError> {
+    Ok(LotusJson(data.chain_store.reorgs().reorgs()))
+}
+
 pub(in crate::rpc) async fn chain_read_obj<DB: Blockstore>(
     data: Data<RPCState<DB>>,
     Params(LotusJson((obj_cid,))): Params<LotusJson<(Cid,)>>,
@@ -300,6 +470,30 @@ pub(in crate::rpc) async fn chain_set_head<DB: Blockstore>(
         .map_err(Into::into)
 }
 
+/// Returns the tipsets to revert, newest first, then the tipsets to apply,
+/// oldest first, to move the head from `from` to `to`.
+pub(in crate::rpc) async fn chain_get_path<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((from, to))): Params<LotusJson<(TipsetKey, TipsetKey)>>,
+) -> Result<LotusJson<Vec<PathChange>>, JsonRpcError> {
+    let chain_store = data.state_manager.chain_store();
+    let ops = reorg_ops(
+        &chain_store.chain_index,
+        chain_store.load_required_tipset(&from)?,
+        chain_store.load_required_tipset(&to)?,
+        None,
+    )?;
+    let revert = ops
+        .revert
+        .into_iter()
+        .map(|tipset| PathChange::Revert((*tipset).clone()));
+    let apply = ops
+        .apply
+        .into_iter()
+        .map(|tipset| PathChange::Apply((*tipset).clone()));
+    Ok(LotusJson(revert.chain(apply).collect()))
+}
+
 pub(crate) async fn chain_get_min_base_fee<DB: Blockstore>(
     data: Data<RPCState<DB>>,
     Params((basefee_lookback,)): Params<(u32,)>,
@@ -320,6 +514,24 @@ pub(crate) async fn chain_get_min_base_fee<DB: Blockstore>(
     Ok(min_base_fee.atto().to_string())
 }
 
+/// Returns the base fee of the messages of the children of a tipset, as
+/// computed when validating them.
+pub(in crate::rpc) async fn chain_compute_base_fee<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((tsk,))): Params<LotusJson<(TipsetKey,)>>,
+) -> Result<LotusJson<TokenAmount>, JsonRpcError> {
+    let ts = data
+        .state_manager
+        .chain_store()
+        .load_required_tipset(&tsk)?;
+    let smoke_height = data.state_manager.chain_config().epoch(Height::Smoke);
+    Ok(LotusJson(compute_base_fee(
+        data.state_manager.blockstore(),
+        &ts,
+        smoke_height,
+    )?))
+}
+
 pub(crate) async fn chain_notify<DB: Blockstore>(
     _data: Data<RPCState<DB>>,
 ) -> Result<(), JsonRpcError> {
@@ -354,3 +566,95 @@ fn load_api_messages_from_tipset(
 
     Ok(messages)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shim::address::Address;
+
+    fn message(from: u64, sequence: u64, value: u64) -> ApiMessage {
+        let message = Message {
+            from: Address::new_id(from),
+            sequence,
+            value: crate::shim::econ::TokenAmount::from_atto(value),
+            ..Default::default()
+        };
+        ApiMessage::new(message.cid().unwrap(), message)
+    }
+
+    fn sequences(messages: &[ApiMessage]) -> Vec<(Address, u64)> {
+        messages
+            .iter()
+            .map(|msg| (msg.message().from, msg.message().sequence))
+            .collect()
+    }
+
+    #[test]
+    fn exports_stay_in_the_export_directory() {
+        let export_dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let dir = Some(export_dir.path());
+        assert!(check_export_path(None, &outside.path().join("export.car")).is_ok());
+        assert!(check_export_path(dir, &export_dir.path().join("export.car")).is_ok());
+        assert!(check_export_path(dir, &outside.path().join("export.car")).is_err());
+        assert!(check_export_path(dir, &export_dir.path().join("../export.car")).is_err());
+        assert!(check_export_path(dir, &export_dir.path().join("..")).is_err());
+    }
+
+    #[test]
+    fn select_messages_is_lotus_compatible_by_default() {
+        let messages = vec![message(1, 0, 0), message(1, 0, 0), message(2, 0, 0)];
+        let selected = select_messages(messages.clone(), &Default::default());
+        assert_eq!(sequences(&selected), sequences(&messages));
+    }
+
+    #[test]
+    fn select_messages_dedupes_and_paginates() {
+        let messages = vec![
+            message(1, 0, 1),
+            message(1, 0, 2),
+            message(2, 0, 1),
+            message(1, 1, 1),
+            message(2, 1, 1),
+        ];
+        let options = MessagesInTipsetOptions {
+            dedupe: true,
+            ..Default::default()
+        };
+        let selected = select_messages(messages.clone(), &options);
+        assert_eq!(selected.len(), 4);
+        // The first message of a sender nonce is kept.
+        assert_eq!(selected[0].message().value, messages[0].message().value);
+
+        let options = MessagesInTipsetOptions {
+            offset: 1,
+            limit: Some(2),
+            dedupe: true,
+        };
+        let selected = select_messages(messages, &options);
+        assert_eq!(
+            sequences(&selected),
+            vec![(Address::new_id(2), 0), (Address::new_id(1), 1)]
+        );
+    }
+
+    #[test]
+    fn messages_in_tipset_params() {
+        let tsk = serde_json::json!([{ "/": Cid::default().to_string() }]);
+        let params: ChainGetMessagesInTipsetParams =
+            serde_json::from_value(serde_json::json!([tsk])).unwrap();
+        assert_eq!(params.into_parts().1, MessagesInTipsetOptions::default());
+
+        let params: ChainGetMessagesInTipsetParams =
+            serde_json::from_value(serde_json::json!([tsk, { "Limit": 10, "Dedupe": true }]))
+                .unwrap();
+        assert_eq!(
+            params.into_parts().1,
+            MessagesInTipsetOptions {
+                offset: 0,
+                limit: Some(10),
+                dedupe: true,
+            }
+        );
+    }
+}
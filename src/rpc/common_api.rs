@@ -2,9 +2,11 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 #![allow(clippy::unused_async)]
 
+use crate::cli_shared::logger;
+use crate::daemon::{config_reload::ConfigReloadReport, ShutdownKind};
 use crate::rpc_api::data_types::{APIVersion, RPCState, Version};
 use fvm_ipld_blockstore::Blockstore;
-use jsonrpc_v2::{Data, Error as JsonRpcError};
+use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
 use once_cell::sync::Lazy;
 use semver::Version as SemVer;
 use tokio::sync::mpsc::Sender;
@@ -30,17 +32,57 @@ pub(in crate::rpc) async fn version(
     })
 }
 
-pub(in crate::rpc) async fn shutdown(shutdown_send: Sender<()>) -> Result<(), JsonRpcError> {
+pub(in crate::rpc) async fn shutdown(
+    shutdown_send: Sender<ShutdownKind>,
+) -> Result<(), JsonRpcError> {
     // Trigger graceful shutdown
-    if let Err(err) = shutdown_send.send(()).await {
+    if let Err(err) = shutdown_send.send(ShutdownKind::Stop).await {
         return Err(JsonRpcError::from(err));
     }
     Ok(())
 }
 
+/// Stops the node gracefully, and starts it again with the same command line, which applies the
+/// changes of the configuration that require a restart.
+pub(in crate::rpc) async fn restart(
+    shutdown_send: Sender<ShutdownKind>,
+) -> Result<(), JsonRpcError> {
+    if let Err(err) = shutdown_send.send(ShutdownKind::Restart).await {
+        return Err(JsonRpcError::from(err));
+    }
+    Ok(())
+}
+
+/// Lists the targets whose log level is set.
+pub(in crate::rpc) async fn log_list() -> Result<Vec<String>, JsonRpcError> {
+    Ok(logger::log_targets()?)
+}
+
+/// Sets the level of the logs of a target, until the node is restarted.
+pub(in crate::rpc) async fn log_set_level(
+    Params((target, level)): Params<(String, String)>,
+) -> Result<(), JsonRpcError> {
+    Ok(logger::set_log_level(&target, &level)?)
+}
+
 /// gets start time from network
 pub(in crate::rpc) async fn start_time<DB: Blockstore>(
     data: Data<RPCState<DB>>,
 ) -> Result<chrono::DateTime<chrono::Utc>, JsonRpcError> {
     Ok(data.start_time)
 }
+
+/// Reloads the configuration file of the node, and reports which changes were applied.
+pub(in crate::rpc) async fn config_reload<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+) -> Result<ConfigReloadReport, JsonRpcError> {
+    let (tx, rx) = flume::bounded(1);
+    data.config_reload_requests
+        .send_async(tx)
+        .await
+        .map_err(|_| "the configuration reloader isn't running")?;
+    Ok(rx
+        .recv_async()
+        .await
+        .map_err(|_| "the configuration reloader stopped")??)
+}
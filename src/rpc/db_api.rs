@@ -0,0 +1,24 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::db::GcReport;
+use crate::rpc_api::data_types::RPCState;
+use fvm_ipld_blockstore::Blockstore;
+use jsonrpc_v2::{Data, Error as JsonRpcError};
+
+/// Runs the garbage collector of the database immediately. Returns the amount
+/// of data it removed, or the epoch at which the marked data is swept if the
+/// chain hasn't advanced enough since the mark.
+pub(in crate::rpc) async fn database_garbage_collect<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+) -> Result<GcReport, JsonRpcError> {
+    let (tx, rx) = flume::bounded(1);
+    data.gc_requests
+        .send_async(tx)
+        .await
+        .map_err(|_| "the garbage collector isn't running")?;
+    Ok(rx
+        .recv_async()
+        .await
+        .map_err(|_| "the garbage collector stopped")??)
+}
@@ -8,9 +8,11 @@ use super::gas_api;
 use crate::blocks::{Tipset, TipsetKey};
 use crate::chain::{index::ResolveNullTipset, ChainStore};
 use crate::cid_collections::FrozenCidVec;
+use crate::eth::EthTx;
 use crate::lotus_json::LotusJson;
 use crate::rpc_api::{data_types::RPCState, eth_api::BigInt as EthBigInt, eth_api::*};
 use crate::shim::{clock::ChainEpoch, state_tree::StateTree};
+use crate::utils::encoding::keccak_256;
 
 use anyhow::bail;
 use fvm_ipld_blockstore::Blockstore;
@@ -60,13 +62,70 @@ pub(in crate::rpc) async fn eth_chain_id<DB: Blockstore>(
     ))
 }
 
+/// Maximum number of tipsets `eth_feeHistory` reports on, as in Lotus.
+const MAX_FEE_HISTORY_BLOCKS: u64 = 1024;
+
+pub(in crate::rpc) async fn eth_fee_history<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((Uint64(block_count), newest_block, reward_percentiles))): Params<
+        LotusJson<(Uint64, BlockNumberOrHash, Option<Vec<f64>>)>,
+    >,
+) -> Result<FeeHistory, JsonRpcError> {
+    if block_count > MAX_FEE_HISTORY_BLOCKS {
+        return Err(format!("block count should be smaller than {MAX_FEE_HISTORY_BLOCKS}").into());
+    }
+    let percentiles = reward_percentiles.as_deref().unwrap_or_default();
+    if percentiles.iter().any(|p| !(0.0..=100.0).contains(p)) {
+        return Err("invalid reward percentile, it should be between 0 and 100".into());
+    }
+    if percentiles.windows(2).any(|w| w[0] > w[1]) {
+        return Err("invalid reward percentiles, they should be in ascending order".into());
+    }
+
+    let chain = &data.chain_store;
+    let ts = tipset_by_block_number_or_hash(chain, newest_block)?;
+    // Filecoin executes messages in the next tipset, so the base fee after the
+    // newest tipset isn't known yet and the last value is repeated instead.
+    let mut base_fee_per_gas = vec![EthBigInt(
+        ts.block_headers().first().parent_base_fee.atto().clone(),
+    )];
+    let mut gas_used_ratio = vec![];
+    let mut reward = vec![];
+    let mut oldest_block = 1;
+    let tipsets = chain
+        .chain_index
+        .load_tipsets_range(ts.key(), block_count as usize)?;
+    for ts in tipsets.iter().take_while(|ts| ts.epoch() > 0) {
+        let fees = chain.fee_history().get_or_index(chain.blockstore(), ts)?;
+        base_fee_per_gas.push(EthBigInt(fees.base_fee.atto().clone()));
+        gas_used_ratio.push(fees.gas_used_ratio());
+        reward.push(
+            percentiles
+                .iter()
+                .map(|p| EthBigInt(fees.premium_at(*p).atto().clone()))
+                .collect::<Vec<_>>(),
+        );
+        oldest_block = ts.epoch() as u64;
+    }
+    base_fee_per_gas.reverse();
+    gas_used_ratio.reverse();
+    reward.reverse();
+
+    Ok(FeeHistory {
+        oldest_block: Uint64(oldest_block),
+        base_fee_per_gas,
+        gas_used_ratio,
+        reward: reward_percentiles.map(|_| reward),
+    })
+}
+
 pub(in crate::rpc) async fn eth_gas_price<DB: Blockstore>(
     data: Data<RPCState<DB>>,
 ) -> Result<GasPriceResult, JsonRpcError> {
     let ts = data.state_manager.chain_store().heaviest_tipset();
     let block0 = ts.block_headers().first();
     let base_fee = &block0.parent_base_fee;
-    if let Ok(premium) = gas_api::estimate_gas_premium(&data, 10000).await {
+    if let Ok(premium) = gas_api::estimate_gas_premium(&data, 10000, &TipsetKey::default()).await {
         let gas_price = base_fee.add(premium);
         Ok(GasPriceResult(gas_price.atto().clone()))
     } else {
@@ -95,6 +154,63 @@ pub(in crate::rpc) async fn eth_get_balance<DB: Blockstore>(
     Ok(EthBigInt(actor.balance.atto().clone()))
 }
 
+pub(in crate::rpc) async fn eth_get_block_transaction_count_by_hash<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((block_hash,))): Params<LotusJson<(Hash,)>>,
+) -> Result<Uint64, JsonRpcError> {
+    let ts = tipset_by_block_number_or_hash(
+        &data.chain_store,
+        BlockNumberOrHash::BlockHash(block_hash, false),
+    )?;
+    Ok(count_messages_in_tipset(&data.chain_store, &ts)?)
+}
+
+pub(in crate::rpc) async fn eth_get_block_transaction_count_by_number<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((block_param,))): Params<LotusJson<(BlockNumberOrHash,)>>,
+) -> Result<Uint64, JsonRpcError> {
+    let ts = tipset_by_block_number_or_hash(&data.chain_store, block_param)?;
+    Ok(count_messages_in_tipset(&data.chain_store, &ts)?)
+}
+
+pub(in crate::rpc) async fn eth_get_uncle_count_by_block_hash(
+    Params(LotusJson((_block_hash,))): Params<LotusJson<(Hash,)>>,
+) -> Result<Uint64, JsonRpcError> {
+    // Filecoin has no uncle blocks
+    Ok(Uint64(0))
+}
+
+fn count_messages_in_tipset<DB: Blockstore>(
+    chain: &Arc<ChainStore<DB>>,
+    ts: &Tipset,
+) -> anyhow::Result<Uint64> {
+    let msgs = chain.messages_for_tipset(ts)?;
+    Ok(Uint64(msgs.len() as u64))
+}
+
+pub(in crate::rpc) async fn eth_send_raw_transaction<DB>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((raw_tx,))): Params<LotusJson<(Bytes,)>>,
+) -> Result<Hash, JsonRpcError>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    let (tx, signature) = EthTx::decode_signed_eip1559(&raw_tx.0)?;
+    let eth_chain_id = data.state_manager.chain_config().eth_chain_id as u64;
+    if tx.chain_id() != eth_chain_id {
+        return Err(format!(
+            "invalid chain id {}, expected {eth_chain_id}",
+            tx.chain_id()
+        )
+        .into());
+    }
+    // The message pool verifies the signature against the sender recovered here
+    let smsg = tx.into_signed_message(signature)?;
+    data.mpool.push(smsg).await?;
+
+    Ok(Hash(keccak_256(&raw_tx.0).into()))
+}
+
 fn tipset_by_block_number_or_hash<DB: Blockstore>(
     chain: &Arc<ChainStore<DB>>,
     block_param: BlockNumberOrHash,
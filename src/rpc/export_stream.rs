@@ -0,0 +1,245 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Snapshots streamed to RPC clients.
+//!
+//! `Filecoin.ChainExport` calls with the Lotus parameters, see
+//! [`ChainExportStreamParams`], are answered over HTTP with the snapshot
+//! itself, a `.forest.car.zst` sent in chunks as it is exported, instead of a
+//! JSON-RPC response. Calls with [`ChainExportParams`] still write the snapshot
+//! to the filesystem of the node.
+//!
+//! [`ChainExportParams`]: crate::rpc_api::chain_api::ChainExportParams
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use crate::cid_collections::CidHashSet;
+use crate::lotus_json::LotusJson;
+use crate::rpc_api::chain_api::{ChainExportStreamParams, CHAIN_EXPORT};
+use crate::rpc_api::data_types::RPCState;
+use axum::body::Body;
+use axum::response::{IntoResponse, Response};
+use bytes::Bytes;
+use futures::stream::{BoxStream, StreamExt as _};
+use fvm_ipld_blockstore::Blockstore;
+use http::StatusCode;
+use sha2::Sha256;
+use tokio::io::AsyncWrite;
+use tokio::time::Sleep;
+use tokio_util::io::ReaderStream;
+
+use super::chain_api::CHAIN_EXPORT_LOCK;
+
+/// Size of the buffer between the export and the connection of the client.
+const EXPORT_STREAM_BUFFER_SIZE: usize = 1 << 20;
+
+/// Time after which an export blocked by a client not reading the snapshot
+/// fails, releasing the export lock.
+const EXPORT_STREAM_WRITE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Exports snapshots as streams of bytes.
+pub trait ChainExporter: Send + Sync {
+    /// Starts an export and returns the stream of its bytes, which fails if
+    /// the export does.
+    fn export_stream(
+        &self,
+        params: ChainExportStreamParams,
+    ) -> Result<BoxStream<'static, io::Result<Bytes>>, (StatusCode, String)>;
+}
+
+impl<DB> ChainExporter for RPCState<DB>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    fn export_stream(
+        &self,
+        (recent_roots, skip_old_messages, tsk): ChainExportStreamParams,
+    ) -> Result<BoxStream<'static, io::Result<Bytes>>, (StatusCode, String)> {
+        let locked = CHAIN_EXPORT_LOCK.try_lock().map_err(|_| {
+            (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Another chain export job is still in progress".to_string(),
+            )
+        })?;
+
+        let chain_finality = self.state_manager.chain_config().policy.chain_finality;
+        if recent_roots < chain_finality {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("recent-stateroots must be greater than {chain_finality}"),
+            ));
+        }
+        let head = self
+            .chain_store
+            .load_required_tipset(&tsk)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+        let db = Arc::clone(&self.chain_store.db);
+        let (writer, reader) = tokio::io::duplex(EXPORT_STREAM_BUFFER_SIZE);
+        let writer = StallTimeout::new(writer, EXPORT_STREAM_WRITE_TIMEOUT);
+        let export = tokio::spawn(async move {
+            let _locked = locked;
+            crate::chain::export::<Sha256>(
+                db,
+                &head,
+                recent_roots,
+                writer,
+                CidHashSet::default(),
+                None,
+                true,
+                !skip_old_messages,
+            )
+            .await
+        });
+        // A failed export fails the stream, so that the client doesn't take a
+        // truncated snapshot for a complete one.
+        let outcome = futures::stream::once(async move {
+            match export.await {
+                Ok(Ok(_)) => None,
+                Ok(Err(e)) => Some(Err(io::Error::other(e.to_string()))),
+                Err(e) => Some(Err(io::Error::other(e))),
+            }
+        })
+        .filter_map(futures::future::ready);
+        Ok(ReaderStream::new(reader).chain(outcome).boxed())
+    }
+}
+
+/// A writer failing with [`io::ErrorKind::TimedOut`] when a write or a flush
+/// can't make progress for longer than `timeout`.
+struct StallTimeout<W> {
+    inner: W,
+    timeout: Duration,
+    deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl<W> StallTimeout<W> {
+    fn new(inner: W, timeout: Duration) -> Self {
+        Self {
+            inner,
+            timeout,
+            deadline: None,
+        }
+    }
+
+    /// Resets the deadline on progress, or fails once it has passed.
+    fn poll_deadline<T>(
+        &mut self,
+        cx: &mut Context<'_>,
+        poll: Poll<io::Result<T>>,
+    ) -> Poll<io::Result<T>> {
+        if poll.is_ready() {
+            self.deadline = None;
+            return poll;
+        }
+        let timeout = self.timeout;
+        let deadline = self
+            .deadline
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+        ready!(deadline.as_mut().poll(cx));
+        self.deadline = None;
+        Poll::Ready(Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("no progress writing the snapshot for {timeout:?}"),
+        )))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for StallTimeout<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.inner).poll_write(cx, buf);
+        this.poll_deadline(cx, poll)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.inner).poll_flush(cx);
+        this.poll_deadline(cx, poll)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let poll = Pin::new(&mut this.inner).poll_shutdown(cx);
+        this.poll_deadline(cx, poll)
+    }
+}
+
+/// Returns the parameters of `rpc_call` if it is a streamed export.
+pub fn streamed_export_params(
+    rpc_call: &jsonrpc_v2::RequestObject,
+) -> Option<ChainExportStreamParams> {
+    if rpc_call.method_ref() != CHAIN_EXPORT {
+        return None;
+    }
+    let mut request = serde_json::to_value(rpc_call).ok()?;
+    let LotusJson(params) = serde_json::from_value(request.get_mut("params")?.take()).ok()?;
+    Some(params)
+}
+
+/// Responds with the bytes of the snapshot as they are exported.
+pub fn export_response(exporter: &dyn ChainExporter, params: ChainExportStreamParams) -> Response {
+    match exporter.export_stream(params) {
+        Ok(stream) => (
+            [("content-type", "application/zstd")],
+            Body::from_stream(stream),
+        )
+            .into_response(),
+        Err((code, msg)) => (code, msg).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::TipsetKey;
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+    fn request(params: serde_json::Value) -> jsonrpc_v2::RequestObject {
+        serde_json::from_value(serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": CHAIN_EXPORT,
+            "params": params,
+            "id": 0,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn only_lotus_exports_are_streamed() {
+        let (recent_roots, skip_old_messages, tsk) =
+            streamed_export_params(&request(serde_json::json!([2000, true, []]))).unwrap();
+        assert_eq!(recent_roots, 2000);
+        assert!(skip_old_messages);
+        assert_eq!(tsk, TipsetKey::default());
+
+        assert!(streamed_export_params(&request(serde_json::json!({
+            "epoch": 0,
+            "recent_roots": 2000,
+        })))
+        .is_none());
+    }
+
+    #[tokio::test]
+    async fn stalled_exports_time_out() {
+        let (writer, mut reader) = tokio::io::duplex(4);
+        let mut writer = StallTimeout::new(writer, Duration::from_millis(10));
+        writer.write_all(b"snap").await.unwrap();
+        let err = writer.write_all(b"shot").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        // Writes go on once the client reads.
+        let mut buf = [0; 4];
+        reader.read_exact(&mut buf).await.unwrap();
+        writer.write_all(b"shot").await.unwrap();
+    }
+}
@@ -3,9 +3,12 @@
 #![allow(clippy::unused_async)]
 
 use crate::blocks::TipsetKey;
-use crate::chain::{BASE_FEE_MAX_CHANGE_DENOM, BLOCK_GAS_TARGET, MINIMUM_BASE_FEE};
+use crate::chain::{
+    compute_base_fee, BASE_FEE_MAX_CHANGE_DENOM, BLOCK_GAS_TARGET, MINIMUM_BASE_FEE,
+};
 use crate::lotus_json::LotusJson;
 use crate::message::{ChainMessage, Message as MessageTrait};
+use crate::networks::Height;
 use crate::rpc_api::data_types::{MessageSendSpec, RPCState};
 use crate::shim::address::Address;
 use crate::shim::econ::BLOCK_GAS_LIMIT;
@@ -14,6 +17,8 @@ use fvm_ipld_blockstore::Blockstore;
 use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
 use num::BigInt;
 use num_traits::{FromPrimitive, Zero};
+use once_cell::sync::Lazy;
+use rand::Rng;
 use rand_distr::{Distribution, Normal};
 
 const MIN_GAS_PREMIUM: f64 = 100000.0;
@@ -32,15 +37,21 @@ fn estimate_fee_cap<DB: Blockstore>(
     data: &Data<RPCState<DB>>,
     msg: Message,
     max_queue_blks: i64,
-    _tsk: TipsetKey,
+    tsk: TipsetKey,
 ) -> Result<TokenAmount, JsonRpcError> {
-    let ts = data.state_manager.chain_store().heaviest_tipset();
+    let ts = data
+        .state_manager
+        .chain_store()
+        .load_required_tipset(&tsk)?;
+    let smoke_height = data.state_manager.chain_config().epoch(Height::Smoke);
 
-    let parent_base_fee = &ts.block_headers().first().parent_base_fee;
+    // The base fee of the next tipset is known, it may then increase by at most
+    // 1/BASE_FEE_MAX_CHANGE_DENOM every following tipset.
+    let next_base_fee = compute_base_fee(data.state_manager.blockstore(), &ts, smoke_height)?;
     let increase_factor =
-        (1.0 + (BASE_FEE_MAX_CHANGE_DENOM as f64).recip()).powf(max_queue_blks as f64);
+        (1.0 + (BASE_FEE_MAX_CHANGE_DENOM as f64).recip()).powf((max_queue_blks - 1).max(0) as f64);
 
-    let fee_in_future = parent_base_fee
+    let fee_in_future = next_base_fee
         * BigInt::from_f64(increase_factor * (1 << 8) as f64)
             .ok_or("failed to convert fee_in_future f64 to bigint")?;
     let mut out: crate::shim::econ::TokenAmount = fee_in_future.div_floor(1 << 8);
@@ -53,15 +64,46 @@ pub(in crate::rpc) async fn gas_estimate_gas_premium<DB: Blockstore>(
     data: Data<RPCState<DB>>,
     Params(params): Params<LotusJson<(u64, Address, i64, TipsetKey)>>,
 ) -> Result<String, JsonRpcError> {
-    let (nblocksincl, _sender, _gas_limit, _) = params.0;
-    estimate_gas_premium::<DB>(&data, nblocksincl)
+    let (nblocksincl, _sender, _gas_limit, tsk) = params.0;
+    estimate_gas_premium::<DB>(&data, nblocksincl, &tsk)
         .await
         .map(|n| TokenAmount::to_string(&n))
 }
 
+/// Whether the noise added to gas premium estimations is disabled, with the
+/// `FOREST_DETERMINISTIC_GAS_PREMIUM` environment variable set to `1` or
+/// `true`. Deterministic estimations are useful to tests and replays.
+fn is_gas_premium_deterministic() -> bool {
+    static DETERMINISTIC: Lazy<bool> =
+        Lazy::new(|| match std::env::var("FOREST_DETERMINISTIC_GAS_PREMIUM") {
+            Ok(var) => matches!(var.to_lowercase().as_str(), "1" | "true"),
+            _ => false,
+        });
+    *DETERMINISTIC
+}
+
+/// Estimates the gas premium of messages to be included within `nblocksincl`
+/// blocks after the tipset of `tsk`, the heaviest tipset if empty, with some
+/// noise unless [deterministic](is_gas_premium_deterministic).
 pub async fn estimate_gas_premium<DB: Blockstore>(
+    data: &Data<RPCState<DB>>,
+    nblocksincl: u64,
+    tsk: &TipsetKey,
+) -> Result<TokenAmount, JsonRpcError> {
+    let premium = estimate_gas_premium_without_noise(data, nblocksincl, tsk)?;
+    if is_gas_premium_deterministic() {
+        return Ok(premium);
+    }
+    add_noise(premium, &mut rand::thread_rng())
+}
+
+/// Estimates the gas premium of messages to be included within `nblocksincl`
+/// blocks after the tipset of `tsk`, the heaviest tipset if empty, from the
+/// premiums of the messages of the tipsets before it.
+pub fn estimate_gas_premium_without_noise<DB: Blockstore>(
     data: &Data<RPCState<DB>>,
     mut nblocksincl: u64,
+    tsk: &TipsetKey,
 ) -> Result<TokenAmount, JsonRpcError> {
     if nblocksincl == 0 {
         nblocksincl = 1;
@@ -75,38 +117,36 @@ pub async fn estimate_gas_premium<DB: Blockstore>(
     let mut prices: Vec<GasMeta> = Vec::new();
     let mut blocks = 0;
 
-    let mut ts = data.state_manager.chain_store().heaviest_tipset();
+    let chain_store = data.state_manager.chain_store();
+    let head = chain_store.load_required_tipset(tsk)?;
+    let parents = if head.epoch() == 0 {
+        vec![]
+    } else {
+        chain_store.load_tipsets_range(head.parents(), (nblocksincl * 2) as usize)?
+    };
 
-    for _ in 0..(nblocksincl * 2) {
-        if ts.epoch() == 0 {
-            break;
-        }
-        let pts = data
-            .state_manager
-            .chain_store()
-            .load_required_tipset(ts.parents())?;
+    // Messages included by several blocks are only executed once.
+    for (pts, msgs) in parents {
         blocks += pts.block_headers().len();
-        let msgs = crate::chain::messages_for_tipset(data.state_manager.blockstore_owned(), &pts)?;
 
         prices.append(
             &mut msgs
                 .iter()
                 .map(|msg| GasMeta {
-                    price: msg.message().gas_premium(),
-                    limit: msg.message().gas_limit(),
+                    price: msg.message.message().gas_premium(),
+                    limit: msg.message.message().gas_limit(),
                 })
                 .collect(),
         );
-        ts = pts;
     }
 
     prices.sort_by(|a, b| b.price.cmp(&a.price));
-    let mut at = BLOCK_GAS_TARGET * blocks as u64 / 2;
+    let mut at = (BLOCK_GAS_TARGET * blocks as u64 / 2) as i64;
     let mut prev = TokenAmount::zero();
     let mut premium = TokenAmount::zero();
 
     for price in prices {
-        at -= price.limit;
+        at -= price.limit as i64;
         if at > 0 {
             prev = price.price;
             continue;
@@ -126,12 +166,16 @@ pub async fn estimate_gas_premium<DB: Blockstore>(
         });
     }
 
+    Ok(premium)
+}
+
+/// Multiplies `premium` by a random factor, so that the premiums of messages
+/// sent at the same time differ.
+fn add_noise(mut premium: TokenAmount, rng: &mut impl Rng) -> Result<TokenAmount, JsonRpcError> {
     let precision = 32;
 
     // mean 1, stddev 0.005 => 95% within +-1%
-    let noise: f64 = Normal::new(1.0, 0.005)
-        .unwrap()
-        .sample(&mut rand::thread_rng());
+    let noise: f64 = Normal::new(1.0, 0.005).unwrap().sample(rng);
 
     premium *= BigInt::from_f64(noise * (1i64 << precision) as f64)
         .ok_or("failed to converrt gas premium f64 to bigint")?;
@@ -154,7 +198,7 @@ where
 async fn estimate_gas_limit<DB>(
     data: &Data<RPCState<DB>>,
     msg: Message,
-    _: TipsetKey,
+    tsk: TipsetKey,
 ) -> Result<i64, JsonRpcError>
 where
     DB: Blockstore + Send + Sync + 'static,
@@ -164,7 +208,10 @@ where
     msg.set_gas_fee_cap(TokenAmount::from_atto(MINIMUM_BASE_FEE + 1));
     msg.set_gas_premium(TokenAmount::from_atto(1));
 
-    let curr_ts = data.state_manager.chain_store().heaviest_tipset();
+    let curr_ts = data
+        .state_manager
+        .chain_store()
+        .load_required_tipset(&tsk)?;
     let from_a = data
         .state_manager
         .resolve_to_key_addr(&msg.from, &curr_ts)
@@ -175,10 +222,13 @@ where
         .map(|s| s.into_iter().map(ChainMessage::Signed).collect::<Vec<_>>())
         .unwrap_or_default();
 
-    let ts = data.mpool.cur_tipset.lock().clone();
     let res = data
         .state_manager
-        .call_with_gas(&mut ChainMessage::Unsigned(msg), &prior_messages, Some(ts))
+        .call_with_gas(
+            &mut ChainMessage::Unsigned(msg),
+            &prior_messages,
+            Some(curr_ts),
+        )
         .await?;
     match res.msg_rct {
         Some(rct) => {
@@ -212,7 +262,7 @@ where
 pub(in crate::rpc) async fn estimate_message_gas<DB>(
     data: &Data<RPCState<DB>>,
     msg: Message,
-    _spec: Option<MessageSendSpec>,
+    spec: Option<MessageSendSpec>,
     tsk: TipsetKey,
 ) -> Result<Message, JsonRpcError>
 where
@@ -224,15 +274,81 @@ where
         msg.set_gas_limit(gl as u64);
     }
     if msg.gas_premium.is_zero() {
-        let gp = estimate_gas_premium(data, 10).await?;
+        let gp = estimate_gas_premium(data, 10, &tsk).await?;
         msg.set_gas_premium(gp);
     }
     if msg.gas_fee_cap.is_zero() {
         let gfp = estimate_fee_cap(data, msg.clone(), 20, tsk)?;
         msg.set_gas_fee_cap(gfp);
     }
+    let default_max_fee = data.fee_config.read().max_fee.clone();
+    cap_gas_fee(&mut msg, spec.as_ref(), &default_max_fee);
     // TODO(forest): https://github.com/ChainSafe/forest/issues/901
     //               Figure out why we always under estimate the gas
     //               calculation so we dont need to add 200000
     Ok(msg)
 }
+
+/// Lowers the gas fee cap of the message so that it pays at most the maximum fee of `spec`, or
+/// `default_max_fee` if it has none, and the gas premium to the gas fee cap.
+fn cap_gas_fee(msg: &mut Message, spec: Option<&MessageSendSpec>, default_max_fee: &TokenAmount) {
+    let max_fee = match spec {
+        Some(spec) if !spec.max_fee.is_zero() => &spec.max_fee,
+        _ => default_max_fee,
+    };
+    if msg.gas_limit > 0 && &msg.gas_fee_cap * BigInt::from(msg.gas_limit) > *max_fee {
+        msg.set_gas_fee_cap(max_fee.div_floor(msg.gas_limit));
+    }
+    if msg.gas_premium > msg.gas_fee_cap {
+        msg.set_gas_premium(msg.gas_fee_cap.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn noise_is_seeded_by_the_rng() {
+        let premium = TokenAmount::from_atto(1_000_000);
+        let noisy = add_noise(premium.clone(), &mut StdRng::seed_from_u64(0)).unwrap();
+        assert_eq!(
+            add_noise(premium.clone(), &mut StdRng::seed_from_u64(0)).unwrap(),
+            noisy
+        );
+        // Well within 10 standard deviations.
+        assert!(noisy > TokenAmount::from_atto(950_000));
+        assert!(noisy < TokenAmount::from_atto(1_050_000));
+    }
+
+    #[test]
+    fn gas_fees_are_capped() {
+        let message = |gas_fee_cap, gas_premium| Message {
+            gas_limit: 1_000,
+            gas_fee_cap: TokenAmount::from_atto(gas_fee_cap),
+            gas_premium: TokenAmount::from_atto(gas_premium),
+            ..Default::default()
+        };
+        let default_max_fee = TokenAmount::from_atto(100_000);
+
+        let mut msg = message(50, 10);
+        cap_gas_fee(&mut msg, None, &default_max_fee);
+        assert_eq!(msg.gas_fee_cap, TokenAmount::from_atto(50));
+        assert_eq!(msg.gas_premium, TokenAmount::from_atto(10));
+
+        let mut msg = message(500, 200);
+        cap_gas_fee(&mut msg, None, &default_max_fee);
+        assert_eq!(msg.gas_fee_cap, TokenAmount::from_atto(100));
+        assert_eq!(msg.gas_premium, TokenAmount::from_atto(100));
+
+        // The maximum fee of the spec takes precedence.
+        let spec = MessageSendSpec {
+            max_fee: TokenAmount::from_atto(20_000),
+        };
+        let mut msg = message(50, 10);
+        cap_gas_fee(&mut msg, Some(&spec), &default_max_fee);
+        assert_eq!(msg.gas_fee_cap, TokenAmount::from_atto(20));
+        assert_eq!(msg.gas_premium, TokenAmount::from_atto(10));
+    }
+}
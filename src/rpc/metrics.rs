@@ -0,0 +1,23 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::metrics::labels;
+use once_cell::sync::Lazy;
+use prometheus::core::{AtomicU64, GenericCounterVec, Opts};
+
+pub static RPC_REQUESTS: Lazy<Box<GenericCounterVec<AtomicU64>>> = Lazy::new(|| {
+    let rpc_requests = Box::new(
+        GenericCounterVec::<AtomicU64>::new(
+            Opts::new(
+                "rpc_requests",
+                "Number of RPC requests, by caller and method",
+            ),
+            &[labels::CALLER, labels::METHOD],
+        )
+        .expect("Defining the rpc_requests metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(rpc_requests.clone())
+        .expect("Registering the rpc_requests metric with the metrics registry must succeed");
+    rpc_requests
+});
@@ -1,12 +1,18 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+#[cfg(feature = "indexer")]
+mod actor_events_api;
 mod auth_api;
 mod beacon_api;
 mod chain_api;
 mod common_api;
+mod db_api;
+#[cfg(feature = "eth-api")]
 mod eth_api;
+mod export_stream;
 mod gas_api;
+mod metrics;
 mod mpool_api;
 mod net_api;
 mod node_api;
@@ -14,36 +20,176 @@ mod rpc_http_handler;
 mod rpc_util;
 mod rpc_ws_handler;
 mod state_api;
+mod subscription;
 mod sync_api;
 mod wallet_api;
 
+pub use mpool_api::report_consensus_faults;
+
+use std::net::SocketAddr;
 use std::sync::Arc;
 
+use crate::chain_sync::SyncWorkers;
+use crate::daemon::ShutdownKind;
+use crate::key_management::KeyStore;
 use crate::rpc_api::{
-    auth_api::*, beacon_api::*, chain_api::*, common_api::*, data_types::RPCState, eth_api::*,
-    gas_api::*, mpool_api::*, net_api::*, node_api::NODE_STATUS, state_api::*, sync_api::*,
+    auth_api::*,
+    beacon_api::*,
+    chain_api::*,
+    common_api::*,
+    data_types::{JsonRpcServerState, RPCState},
+    db_api::*,
+    gas_api::*,
+    mpool_api::*,
+    net_api::*,
+    node_api::NODE_STATUS,
+    state_api::*,
+    sync_api::*,
     wallet_api::*,
 };
+use axum::extract::FromRef;
 use axum::routing::{get, post};
 use fvm_ipld_blockstore::Blockstore;
-use jsonrpc_v2::{Data, Error as JSONRPCError, Server};
+use jsonrpc_v2::{Data, Error as JSONRPCError, MapRouter, Server, ServerBuilder};
 use tokio::net::TcpListener;
-use tokio::sync::mpsc::Sender;
+use tokio::sync::{mpsc::Sender, RwLock};
 use tracing::info;
 
 use crate::rpc::{
     beacon_api::beacon_get_entry,
-    common_api::{session, shutdown, start_time, version},
+    common_api::{
+        config_reload, log_list, log_set_level, restart, session, shutdown, start_time, version,
+    },
+    export_stream::ChainExporter,
     rpc_http_handler::{rpc_http_handler, rpc_v0_http_handler},
     rpc_ws_handler::{rpc_v0_ws_handler, rpc_ws_handler},
     state_api::*,
+    subscription::{HeadChangeJournal, HEAD_CHANGE_JOURNAL_CAPACITY},
 };
 
+/// State shared by the RPC endpoints.
+#[derive(Clone)]
+struct RpcServerState {
+    rpc_server: JsonRpcServerState,
+    keystore: Arc<RwLock<KeyStore>>,
+    head_changes: Arc<HeadChangeJournal>,
+    sync_workers: SyncWorkers,
+    chain_exporter: Arc<dyn ChainExporter>,
+}
+
+impl FromRef<RpcServerState> for JsonRpcServerState {
+    fn from_ref(state: &RpcServerState) -> Self {
+        state.rpc_server.clone()
+    }
+}
+
+impl FromRef<RpcServerState> for Arc<RwLock<KeyStore>> {
+    fn from_ref(state: &RpcServerState) -> Self {
+        state.keystore.clone()
+    }
+}
+
+impl FromRef<RpcServerState> for Arc<HeadChangeJournal> {
+    fn from_ref(state: &RpcServerState) -> Self {
+        state.head_changes.clone()
+    }
+}
+
+impl FromRef<RpcServerState> for SyncWorkers {
+    fn from_ref(state: &RpcServerState) -> Self {
+        state.sync_workers.clone()
+    }
+}
+
+impl FromRef<RpcServerState> for Arc<dyn ChainExporter> {
+    fn from_ref(state: &RpcServerState) -> Self {
+        state.chain_exporter.clone()
+    }
+}
+
+/// Registration of the methods of the optional subsystems, which registers nothing when the
+/// subsystem is compiled out.
+trait WithOptionalMethods {
+    fn with_actor_events_methods<DB>(self) -> Self
+    where
+        DB: Blockstore + Send + Sync + 'static;
+
+    fn with_eth_methods<DB>(self) -> Self
+    where
+        DB: Blockstore + Send + Sync + 'static;
+}
+
+impl WithOptionalMethods for ServerBuilder<MapRouter> {
+    #[cfg(feature = "indexer")]
+    fn with_actor_events_methods<DB>(self) -> Self
+    where
+        DB: Blockstore + Send + Sync + 'static,
+    {
+        use crate::rpc_api::actor_events_api::*;
+
+        self.with_method(
+            GET_ACTOR_EVENTS_RAW,
+            actor_events_api::get_actor_events_raw::<DB>,
+        )
+        .with_method(
+            SUBSCRIBE_ACTOR_EVENTS_RAW,
+            actor_events_api::subscribe_actor_events_raw::<DB>,
+        )
+    }
+
+    #[cfg(not(feature = "indexer"))]
+    fn with_actor_events_methods<DB>(self) -> Self
+    where
+        DB: Blockstore + Send + Sync + 'static,
+    {
+        self
+    }
+
+    #[cfg(feature = "eth-api")]
+    fn with_eth_methods<DB>(self) -> Self
+    where
+        DB: Blockstore + Send + Sync + 'static,
+    {
+        use crate::rpc_api::eth_api::*;
+
+        self.with_method(ETH_ACCOUNTS, eth_api::eth_accounts)
+            .with_method(ETH_BLOCK_NUMBER, eth_api::eth_block_number::<DB>)
+            .with_method(ETH_CHAIN_ID, eth_api::eth_chain_id::<DB>)
+            .with_method(ETH_FEE_HISTORY, eth_api::eth_fee_history::<DB>)
+            .with_method(ETH_GAS_PRICE, eth_api::eth_gas_price::<DB>)
+            .with_method(ETH_GET_BALANCE, eth_api::eth_get_balance::<DB>)
+            .with_method(
+                ETH_GET_BLOCK_TRANSACTION_COUNT_BY_HASH,
+                eth_api::eth_get_block_transaction_count_by_hash::<DB>,
+            )
+            .with_method(
+                ETH_GET_BLOCK_TRANSACTION_COUNT_BY_NUMBER,
+                eth_api::eth_get_block_transaction_count_by_number::<DB>,
+            )
+            .with_method(
+                ETH_GET_UNCLE_COUNT_BY_BLOCK_HASH,
+                eth_api::eth_get_uncle_count_by_block_hash,
+            )
+            .with_method(
+                ETH_SEND_RAW_TRANSACTION,
+                eth_api::eth_send_raw_transaction::<DB>,
+            )
+    }
+
+    #[cfg(not(feature = "eth-api"))]
+    fn with_eth_methods<DB>(self) -> Self
+    where
+        DB: Blockstore + Send + Sync + 'static,
+    {
+        self
+    }
+}
+
 pub async fn start_rpc<DB>(
     state: Arc<RPCState<DB>>,
     rpc_endpoint: TcpListener,
     forest_version: &'static str,
-    shutdown_send: Sender<()>,
+    shutdown_send: Sender<ShutdownKind>,
 ) -> Result<(), JSONRPCError>
 where
     DB: Blockstore + Send + Sync + 'static,
@@ -56,6 +202,18 @@ where
     use wallet_api::*;
 
     let block_delay = state.state_manager.chain_config().block_delay_secs as u64;
+    let head_changes = Arc::new(HeadChangeJournal::new(
+        state.chain_store.heaviest_tipset(),
+        HEAD_CHANGE_JOURNAL_CAPACITY,
+    ));
+    tokio::spawn(
+        head_changes
+            .clone()
+            .record(state.chain_store.publisher().subscribe()),
+    );
+    let sync_workers = state.sync_workers.clone();
+    let keystore = state.keystore.clone();
+    let chain_exporter: Arc<dyn ChainExporter> = state.clone();
     let rpc_server = Arc::new(
         Server::new()
             .with_data(Data(state))
@@ -67,6 +225,7 @@ where
             // Chain API
             .with_method(CHAIN_GET_MESSAGE, chain_api::chain_get_message::<DB>)
             .with_method(CHAIN_EXPORT, chain_api::chain_export::<DB>)
+            .with_method(CHAIN_EXPORT_RANGE, chain_api::chain_export_range::<DB>)
             .with_method(CHAIN_READ_OBJ, chain_read_obj::<DB>)
             .with_method(CHAIN_HAS_OBJ, chain_has_obj::<DB>)
             .with_method(CHAIN_GET_BLOCK_MESSAGES, chain_get_block_messages::<DB>)
@@ -76,10 +235,15 @@ where
             .with_method(CHAIN_HEAD, chain_head::<DB>)
             .with_method(CHAIN_GET_BLOCK, chain_api::chain_get_block::<DB>)
             .with_method(CHAIN_SET_HEAD, chain_api::chain_set_head::<DB>)
+            .with_method(CHAIN_GET_PATH, chain_api::chain_get_path::<DB>)
             .with_method(
                 CHAIN_GET_MIN_BASE_FEE,
                 chain_api::chain_get_min_base_fee::<DB>,
             )
+            .with_method(
+                CHAIN_COMPUTE_BASE_FEE,
+                chain_api::chain_compute_base_fee::<DB>,
+            )
             .with_method(
                 CHAIN_GET_MESSAGES_IN_TIPSET,
                 chain_api::chain_get_messages_in_tipset::<DB>,
@@ -90,15 +254,27 @@ where
             )
             .with_method(CHAIN_NOTIFY, chain_api::chain_notify::<DB>)
             .with_method(CHAIN_GET_PARENT_RECEIPTS, chain_get_parent_receipts::<DB>)
+            .with_method(CHAIN_ADD_CAR, chain_add_car::<DB>)
+            .with_method(CHAIN_REMOVE_CAR, chain_remove_car::<DB>)
+            .with_method(CHAIN_LIST_CARS, chain_list_cars::<DB>)
+            .with_method(CHAIN_GET_REORGS, chain_get_reorgs::<DB>)
             // Message Pool API
             .with_method(MPOOL_GET_NONCE, mpool_get_nonce::<DB>)
             .with_method(MPOOL_PENDING, mpool_pending::<DB>)
             .with_method(MPOOL_PUSH, mpool_push::<DB>)
             .with_method(MPOOL_PUSH_MESSAGE, mpool_push_message::<DB>)
+            .with_method(MPOOL_BATCH_PUSH, mpool_batch_push::<DB>)
+            .with_method(MPOOL_BATCH_PUSH_MESSAGE, mpool_batch_push_message::<DB>)
+            .with_method(MPOOL_SELECT, mpool_select::<DB>)
             // Sync API
             .with_method(SYNC_CHECK_BAD, sync_check_bad::<DB>)
+            .with_method(SYNC_CONSENSUS_FAULTS, sync_consensus_faults::<DB>)
             .with_method(SYNC_MARK_BAD, sync_mark_bad::<DB>)
+            .with_method(SYNC_UNMARK_BAD, sync_unmark_bad::<DB>)
+            .with_method(SYNC_UNMARK_ALL_BAD, sync_unmark_all_bad::<DB>)
+            .with_method(SYNC_SET_CHECKPOINT, sync_set_checkpoint::<DB>)
             .with_method(SYNC_STATE, sync_state::<DB>)
+            .with_method(SYNC_STATE_NOTIFY, sync_state_notify::<DB>)
             // Wallet API
             .with_method(WALLET_BALANCE, wallet_balance::<DB>)
             .with_method(WALLET_DEFAULT_ADDRESS, wallet_default_address::<DB>)
@@ -109,6 +285,7 @@ where
             .with_method(WALLET_NEW, wallet_new::<DB>)
             .with_method(WALLET_SET_DEFAULT, wallet_set_default::<DB>)
             .with_method(WALLET_SIGN, wallet_sign::<DB>)
+            .with_method(WALLET_SIGN_MESSAGE, wallet_sign_message::<DB>)
             .with_method(WALLET_VERIFY, wallet_verify)
             .with_method(WALLET_DELETE, wallet_delete::<DB>)
             // State API
@@ -116,6 +293,8 @@ where
             .with_method(STATE_REPLAY, state_replay::<DB>)
             .with_method(STATE_NETWORK_NAME, state_network_name::<DB>)
             .with_method(STATE_NETWORK_VERSION, state_get_network_version::<DB>)
+            .with_method(STATE_ACTOR_CODE_CIDS, state_actor_code_cids::<DB>)
+            .with_method(STATE_ACTOR_MANIFEST_CID, state_actor_manifest_cid::<DB>)
             .with_method(STATE_ACCOUNT_KEY, state_account_key::<DB>)
             .with_method(STATE_LOOKUP_ID, state_lookup_id::<DB>)
             .with_method(STATE_GET_ACTOR, state_get_actor::<DB>)
@@ -123,6 +302,7 @@ where
             .with_method(STATE_MARKET_DEALS, state_market_deals::<DB>)
             .with_method(STATE_MINER_INFO, state_miner_info::<DB>)
             .with_method(MINER_GET_BASE_INFO, miner_get_base_info::<DB>)
+            .with_method(MINER_CREATE_BLOCK, miner_create_block::<DB>)
             .with_method(STATE_MINER_ACTIVE_SECTORS, state_miner_active_sectors::<DB>)
             .with_method(STATE_MINER_SECTOR_COUNT, state_miner_sector_count::<DB>)
             .with_method(STATE_MINER_FAULTS, state_miner_faults::<DB>)
@@ -134,8 +314,39 @@ where
                 STATE_MINER_PROVING_DEADLINE,
                 state_miner_proving_deadline::<DB>,
             )
+            .with_method(
+                STATE_MINER_INITIAL_PLEDGE_COLLATERAL,
+                state_miner_initial_pledge_collateral::<DB>,
+            )
+            .with_method(
+                STATE_MINER_PRE_COMMIT_DEPOSIT_FOR_POWER,
+                state_miner_pre_commit_deposit_for_power::<DB>,
+            )
+            .with_method(
+                STATE_MINER_AVAILABLE_BALANCE,
+                state_miner_available_balance::<DB>,
+            )
+            .with_method(
+                STATE_MINER_SECTOR_ALLOCATED,
+                state_miner_sector_allocated::<DB>,
+            )
+            .with_method(STATE_VERIFY_SEALS, state_verify_seals::<DB>)
+            .with_method(STATE_VERIFY_WINDOW_POST, state_verify_window_post::<DB>)
+            .with_method(
+                STATE_DEAL_PROVIDER_COLLATERAL_BOUNDS,
+                state_deal_provider_collateral_bounds::<DB>,
+            )
             .with_method(STATE_GET_RECEIPT, state_get_receipt::<DB>)
             .with_method(STATE_WAIT_MSG, state_wait_msg::<DB>)
+            .with_method(
+                STATE_SUBSCRIBE_ACTOR_CHANGES,
+                state_subscribe_actor_changes::<DB>,
+            )
+            .with_method(STATE_NEXT_ACTOR_CHANGES, state_next_actor_changes::<DB>)
+            .with_method(
+                STATE_UNSUBSCRIBE_ACTOR_CHANGES,
+                state_unsubscribe_actor_changes::<DB>,
+            )
             .with_method(STATE_SEARCH_MSG, state_search_msg::<DB>)
             .with_method(STATE_SEARCH_MSG_LIMITED, state_search_msg_limited::<DB>)
             .with_method(STATE_FETCH_ROOT, state_fetch_root::<DB>)
@@ -150,6 +361,7 @@ where
             .with_method(STATE_READ_STATE, state_read_state::<DB>)
             .with_method(STATE_CIRCULATING_SUPPLY, state_circulating_supply::<DB>)
             .with_method(STATE_SECTOR_GET_INFO, state_sector_get_info::<DB>)
+            .with_method(STATE_MINER_SECTORS, state_miner_sectors::<DB>)
             .with_method(
                 STATE_VERIFIED_CLIENT_STATUS,
                 state_verified_client_status::<DB>,
@@ -160,6 +372,7 @@ where
             )
             .with_method(MSIG_GET_AVAILABLE_BALANCE, msig_get_available_balance::<DB>)
             .with_method(MSIG_GET_PENDING, msig_get_pending::<DB>)
+            .with_method(STATE_MIGRATION_PROGRESS, state_migration_progress)
             // Gas API
             .with_method(GAS_ESTIMATE_FEE_CAP, gas_estimate_fee_cap::<DB>)
             .with_method(GAS_ESTIMATE_GAS_LIMIT, gas_estimate_gas_limit::<DB>)
@@ -168,22 +381,39 @@ where
             // Common API
             .with_method(VERSION, move || version(block_delay, forest_version))
             .with_method(SESSION, session)
-            .with_method(SHUTDOWN, move || shutdown(shutdown_send.clone()))
+            .with_method(SHUTDOWN, {
+                let shutdown_send = shutdown_send.clone();
+                move || shutdown(shutdown_send.clone())
+            })
+            .with_method(RESTART, move || restart(shutdown_send.clone()))
             .with_method(START_TIME, start_time::<DB>)
+            .with_method(CONFIG_RELOAD, config_reload::<DB>)
+            .with_method(LOG_LIST, log_list)
+            .with_method(LOG_SET_LEVEL, log_set_level)
             // Net API
             .with_method(NET_ADDRS_LISTEN, net_api::net_addrs_listen::<DB>)
             .with_method(NET_PEERS, net_api::net_peers::<DB>)
+            .with_method(NET_PEER_INFO, net_api::net_peer_info::<DB>)
             .with_method(NET_INFO, net_api::net_info::<DB>)
             .with_method(NET_CONNECT, net_api::net_connect::<DB>)
             .with_method(NET_DISCONNECT, net_api::net_disconnect::<DB>)
+            .with_method(NET_PROTECT_ADD, net_api::net_protect_add::<DB>)
+            .with_method(NET_PROTECT_REMOVE, net_api::net_protect_remove::<DB>)
+            .with_method(NET_PROTECT_LIST, net_api::net_protect_list::<DB>)
+            .with_method(NET_BLOCK_ADD, net_api::net_block_add::<DB>)
+            .with_method(NET_BLOCK_REMOVE, net_api::net_block_remove::<DB>)
+            .with_method(NET_BLOCK_LIST, net_api::net_block_list::<DB>)
+            .with_method(NET_PUBSUB_SCORES, net_api::net_pubsub_scores::<DB>)
+            .with_method(NET_DISCOVER, net_api::net_discover::<DB>)
             // Node API
             .with_method(NODE_STATUS, node_api::node_status::<DB>)
-            // Eth API
-            .with_method(ETH_ACCOUNTS, eth_api::eth_accounts)
-            .with_method(ETH_BLOCK_NUMBER, eth_api::eth_block_number::<DB>)
-            .with_method(ETH_CHAIN_ID, eth_api::eth_chain_id::<DB>)
-            .with_method(ETH_GAS_PRICE, eth_api::eth_gas_price::<DB>)
-            .with_method(ETH_GET_BALANCE, eth_api::eth_get_balance::<DB>)
+            // Database API
+            .with_method(
+                DATABASE_GARBAGE_COLLECT,
+                db_api::database_garbage_collect::<DB>,
+            )
+            .with_actor_events_methods::<DB>()
+            .with_eth_methods::<DB>()
             .finish_unwrapped(),
     );
 
@@ -192,10 +422,20 @@ where
         .route("/rpc/v1", get(rpc_ws_handler))
         .route("/rpc/v0", post(rpc_v0_http_handler))
         .route("/rpc/v1", post(rpc_http_handler))
-        .with_state(rpc_server);
+        .with_state(RpcServerState {
+            rpc_server,
+            keystore,
+            head_changes,
+            sync_workers,
+            chain_exporter,
+        });
 
     info!("Ready for RPC connections");
-    axum::serve(rpc_endpoint, app.into_make_service()).await?;
+    axum::serve(
+        rpc_endpoint,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     info!("Stopped accepting RPC connections");
 
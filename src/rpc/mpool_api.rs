@@ -3,23 +3,40 @@
 #![allow(clippy::unused_async)]
 
 use std::convert::TryFrom;
+use std::sync::Arc;
 
 use crate::blocks::TipsetKey;
+use crate::chain::store::consensus_fault::ConsensusFault;
+use crate::key_management::KeyStore;
 use crate::lotus_json::LotusJson;
-use crate::message::SignedMessage;
+use crate::message::{signing_bytes, SignedMessage};
 use crate::rpc_api::data_types::{MessageSendSpec, RPCState};
 use crate::shim::{
     address::{Address, Protocol},
-    message::Message,
+    message::{Message, MethodNum},
 };
 
 use ahash::{HashSet, HashSetExt};
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::RawBytes;
 use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
+use serde_tuple::{self, Serialize_tuple};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, info, warn};
 
 use super::gas_api::estimate_message_gas;
 
+/// Method number of `ReportConsensusFault` on the miner actor.
+const MINER_REPORT_CONSENSUS_FAULT: MethodNum = 15;
+
+#[derive(Serialize_tuple)]
+struct ReportConsensusFaultParams {
+    header1: RawBytes,
+    header2: RawBytes,
+    header_extra: RawBytes,
+}
+
 /// Gets next nonce for the specified sender.
 pub(in crate::rpc) async fn mpool_get_nonce<DB>(
     data: Data<RPCState<DB>>,
@@ -99,6 +116,24 @@ where
     Ok(pending.into_iter().collect::<Vec<_>>().into())
 }
 
+/// Return the messages the node would select from `mpool` for a block built on
+/// top of the given tipset. The ticket quality is accepted for compatibility
+/// with Lotus, the greedy selection of the node doesn't depend on it
+pub(in crate::rpc) async fn mpool_select<DB>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((tsk, _ticket_quality))): Params<LotusJson<(TipsetKey, f64)>>,
+) -> Result<LotusJson<Vec<SignedMessage>>, JsonRpcError>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    let ts = data
+        .state_manager
+        .chain_store()
+        .load_required_tipset(&tsk)?;
+
+    Ok(data.mpool.select_messages_for_block(&ts)?.into())
+}
+
 /// Add `SignedMessage` to `mpool`, return message CID
 pub(in crate::rpc) async fn mpool_push<DB>(
     data: Data<RPCState<DB>>,
@@ -112,6 +147,29 @@ where
     Ok(cid.into())
 }
 
+/// Add `SignedMessage`s to `mpool` in order, return their CIDs. Stops at the
+/// first message that can't be added.
+pub(in crate::rpc) async fn mpool_batch_push<DB>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((signed_messages,))): Params<LotusJson<(Vec<SignedMessage>,)>>,
+) -> Result<LotusJson<Vec<Cid>>, JsonRpcError>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    let mut cids = Vec::with_capacity(signed_messages.len());
+    for (i, signed_message) in signed_messages.into_iter().enumerate() {
+        let cid = data
+            .mpool
+            .as_ref()
+            .push(signed_message)
+            .await
+            .map_err(|e| format!("failed to push message {i}: {e}"))?;
+        cids.push(cid);
+    }
+
+    Ok(cids.into())
+}
+
 /// Sign given `UnsignedMessage` and add it to `mpool`, return `SignedMessage`
 pub(in crate::rpc) async fn mpool_push_message<DB>(
     data: Data<RPCState<DB>>,
@@ -120,9 +178,45 @@ pub(in crate::rpc) async fn mpool_push_message<DB>(
 where
     DB: Blockstore + Send + Sync + 'static,
 {
-    let from = umsg.from;
+    let mut keystore = data.keystore.as_ref().write().await;
+    let smsg = sign_and_push(&data, &mut keystore, umsg, spec).await?;
+
+    Ok(smsg.into())
+}
 
+/// Sign given `UnsignedMessage`s and add them to `mpool` in order, return the
+/// `SignedMessage`s. Stops at the first message that can't be added.
+pub(in crate::rpc) async fn mpool_batch_push_message<DB>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((umsgs, spec))): Params<LotusJson<(Vec<Message>, Option<MessageSendSpec>)>>,
+) -> Result<LotusJson<Vec<SignedMessage>>, JsonRpcError>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    // Hold the keystore for the whole batch, so that the messages of a sender
+    // get consecutive nonces.
     let mut keystore = data.keystore.as_ref().write().await;
+    let mut smsgs = Vec::with_capacity(umsgs.len());
+    for umsg in umsgs {
+        smsgs.push(sign_and_push(&data, &mut keystore, umsg, spec.clone()).await?);
+    }
+
+    Ok(smsgs.into())
+}
+
+/// Estimates the gas of `umsg`, assigns it the next nonce of its sender, signs
+/// it with the key of the sender and adds it to `mpool`.
+async fn sign_and_push<DB>(
+    data: &Data<RPCState<DB>>,
+    keystore: &mut KeyStore,
+    umsg: Message,
+    spec: Option<MessageSendSpec>,
+) -> Result<SignedMessage, JsonRpcError>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    let from = umsg.from;
+
     let heaviest_tipset = data.state_manager.chain_store().heaviest_tipset();
     let key_addr = data
         .state_manager
@@ -134,7 +228,7 @@ where
             "Expected nonce for MpoolPushMessage is 0, and will be calculated for you.".into(),
         );
     }
-    let mut umsg = estimate_message_gas::<DB>(&data, umsg, spec, Default::default()).await?;
+    let mut umsg = estimate_message_gas::<DB>(data, umsg, spec, Default::default()).await?;
     if umsg.gas_premium > umsg.gas_fee_cap {
         return Err("After estimation, gas premium is greater than gas fee cap".into());
     }
@@ -145,18 +239,87 @@ where
     let nonce = data.mpool.get_sequence(&from)?;
     umsg.sequence = nonce;
     let key = crate::key_management::Key::try_from(crate::key_management::try_find(
-        &key_addr,
-        &mut keystore,
+        &key_addr, keystore,
     )?)?;
+    let eth_chain_id = data.state_manager.chain_config().eth_chain_id as u64;
     let sig = crate::key_management::sign(
         *key.key_info.key_type(),
         key.key_info.private_key(),
-        umsg.cid().unwrap().to_bytes().as_slice(),
+        &signing_bytes(&umsg, *key.key_info.key_type(), eth_chain_id)?,
     )?;
 
-    let smsg = SignedMessage::new_from_parts(umsg, sig)?;
+    let smsg = SignedMessage::new_from_parts(umsg, sig, eth_chain_id)?;
 
     data.mpool.as_ref().push(smsg.clone()).await?;
 
-    Ok(smsg.into())
+    Ok(smsg)
+}
+
+/// Submits a `ReportConsensusFault` message from `reporter` for every
+/// consensus fault detected by the chain store, until the detector is dropped.
+pub async fn report_consensus_faults<DB>(
+    state: Arc<RPCState<DB>>,
+    reporter: Address,
+) -> anyhow::Result<()>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    let data = Data(state);
+    let mut faults = data.chain_store.consensus_faults().subscribe();
+    loop {
+        let fault = match faults.recv().await {
+            Ok(fault) => fault,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("Skipped reporting {skipped} consensus faults");
+                continue;
+            }
+            Err(RecvError::Closed) => return Ok(()),
+        };
+        // The miner actor rejects the reports of faults older than the finality, e.g. detected
+        // while catching up with the network.
+        let head_epoch = data.chain_store.heaviest_tipset().epoch();
+        if fault.epoch + data.state_manager.chain_config().policy.chain_finality < head_epoch {
+            debug!(
+                "Not reporting {:?} by miner {} at epoch {}, older than the finality",
+                fault.kind, fault.miner, fault.epoch
+            );
+            continue;
+        }
+        let message = report_consensus_fault_message(reporter, &fault)?;
+        let mut keystore = data.keystore.as_ref().write().await;
+        match sign_and_push(&data, &mut keystore, message, None).await {
+            Ok(smsg) => info!(
+                "Reported {:?} by miner {} at epoch {} in message {}",
+                fault.kind,
+                fault.miner,
+                fault.epoch,
+                smsg.cid()?
+            ),
+            Err(e) => warn!(
+                "Failed to report {:?} by miner {} at epoch {}: {e:?}",
+                fault.kind, fault.miner, fault.epoch
+            ),
+        }
+    }
+}
+
+fn report_consensus_fault_message(
+    reporter: Address,
+    fault: &ConsensusFault,
+) -> anyhow::Result<Message> {
+    let header_extra = match &fault.block_header_extra {
+        Some(header) => fvm_ipld_encoding::to_vec(&**header)?,
+        None => vec![],
+    };
+    Ok(Message {
+        from: reporter,
+        to: fault.miner,
+        method_num: MINER_REPORT_CONSENSUS_FAULT,
+        params: RawBytes::serialize(ReportConsensusFaultParams {
+            header1: fvm_ipld_encoding::to_vec(&*fault.block_header_1)?.into(),
+            header2: fvm_ipld_encoding::to_vec(&*fault.block_header_2)?.into(),
+            header_extra: header_extra.into(),
+        })?,
+        ..Default::default()
+    })
 }
@@ -3,9 +3,9 @@
 
 use std::str::FromStr;
 
-use crate::libp2p::{NetRPCMethods, NetworkMessage, PeerId};
+use crate::libp2p::{BlockList, NetRPCMethods, NetworkMessage, PeerId};
 use crate::rpc_api::{
-    data_types::{AddrInfo, RPCState},
+    data_types::{AddrInfo, ExtendedPeerInfo, PubsubPeerScore, PubsubScore, RPCState},
     net_api::*,
 };
 use cid::multibase;
@@ -28,6 +28,7 @@ pub(in crate::rpc) async fn net_addrs_listen<DB: Blockstore>(
     Ok(AddrInfo {
         id: id.to_string(),
         addrs,
+        quarantine: None,
     })
 }
 
@@ -44,15 +45,38 @@ pub(in crate::rpc) async fn net_peers<DB: Blockstore>(
 
     let connections = peer_addresses
         .into_iter()
-        .map(|(id, addrs)| AddrInfo {
+        .map(|(id, (addrs, quarantine))| AddrInfo {
             id: id.to_string(),
             addrs,
+            quarantine: quarantine.map(Into::into),
         })
         .collect();
 
     Ok(connections)
 }
 
+pub(in crate::rpc) async fn net_peer_info<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params((id,)): Params<(String,)>,
+) -> Result<ExtendedPeerInfo, JsonRpcError> {
+    let peer_id = PeerId::from_str(&id)?;
+
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::PeerInfo(tx, peer_id),
+    };
+
+    data.network_send.send_async(req).await?;
+    let (addrs, score, quarantine) = rx.await?;
+
+    Ok(ExtendedPeerInfo {
+        id: peer_id.to_string(),
+        addrs,
+        score: score.map(Into::into),
+        quarantine: quarantine.map(Into::into),
+    })
+}
+
 pub(in crate::rpc) async fn net_info<DB: Blockstore>(
     data: Data<RPCState<DB>>,
 ) -> Result<NetInfoResult, JsonRpcError> {
@@ -67,7 +91,7 @@ pub(in crate::rpc) async fn net_info<DB: Blockstore>(
 
 pub(in crate::rpc) async fn net_connect<DB: Blockstore>(
     data: Data<RPCState<DB>>,
-    Params((AddrInfo { id, addrs },)): Params<(AddrInfo,)>,
+    Params((AddrInfo { id, addrs, .. },)): Params<(AddrInfo,)>,
 ) -> Result<(), JsonRpcError> {
     let (_, id) = multibase::decode(format!("{}{}", "z", id))?;
     let peer_id = PeerId::from_bytes(&id)?;
@@ -104,3 +128,134 @@ pub(in crate::rpc) async fn net_disconnect<DB: Blockstore>(
 
     Ok(())
 }
+
+pub(in crate::rpc) async fn net_protect_add<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params((ids,)): Params<(Vec<String>,)>,
+) -> Result<(), JsonRpcError> {
+    let peer_ids = parse_peer_ids(&ids)?;
+
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::ProtectAdd(tx, peer_ids),
+    };
+
+    data.network_send.send_async(req).await?;
+    rx.await?;
+
+    Ok(())
+}
+
+pub(in crate::rpc) async fn net_protect_remove<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params((ids,)): Params<(Vec<String>,)>,
+) -> Result<(), JsonRpcError> {
+    let peer_ids = parse_peer_ids(&ids)?;
+
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::ProtectRemove(tx, peer_ids),
+    };
+
+    data.network_send.send_async(req).await?;
+    rx.await?;
+
+    Ok(())
+}
+
+pub(in crate::rpc) async fn net_protect_list<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+) -> Result<Vec<String>, JsonRpcError> {
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::ProtectList(tx),
+    };
+
+    data.network_send.send_async(req).await?;
+    let peer_ids = rx.await?;
+
+    Ok(peer_ids.iter().map(ToString::to_string).collect())
+}
+
+pub(in crate::rpc) async fn net_block_add<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params((block_list,)): Params<(BlockList,)>,
+) -> Result<(), JsonRpcError> {
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::BlockAdd(tx, block_list),
+    };
+
+    data.network_send.send_async(req).await?;
+    rx.await?;
+
+    Ok(())
+}
+
+pub(in crate::rpc) async fn net_block_remove<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params((block_list,)): Params<(BlockList,)>,
+) -> Result<(), JsonRpcError> {
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::BlockRemove(tx, block_list),
+    };
+
+    data.network_send.send_async(req).await?;
+    rx.await?;
+
+    Ok(())
+}
+
+pub(in crate::rpc) async fn net_block_list<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+) -> Result<BlockList, JsonRpcError> {
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::BlockList(tx),
+    };
+
+    data.network_send.send_async(req).await?;
+    Ok(rx.await?)
+}
+
+pub(in crate::rpc) async fn net_pubsub_scores<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+) -> Result<Vec<PubsubScore>, JsonRpcError> {
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::PubsubScores(tx),
+    };
+
+    data.network_send.send_async(req).await?;
+    let scores = rx.await?;
+
+    Ok(scores
+        .into_iter()
+        .map(|(peer_id, score)| PubsubScore {
+            id: peer_id.to_string(),
+            score: PubsubPeerScore { score },
+        })
+        .collect())
+}
+
+pub(in crate::rpc) async fn net_discover<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+) -> Result<(), JsonRpcError> {
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::Discover(tx),
+    };
+
+    data.network_send.send_async(req).await?;
+    rx.await?;
+
+    Ok(())
+}
+
+fn parse_peer_ids(ids: &[String]) -> Result<Vec<PeerId>, JsonRpcError> {
+    Ok(ids
+        .iter()
+        .map(|id| PeerId::from_str(id))
+        .collect::<Result<_, _>>()?)
+}
@@ -1,13 +1,21 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::key_management::KeyStore;
 use crate::rpc_api::data_types::JsonRpcServerState;
+use axum::extract::ConnectInfo;
 use axum::response::{IntoResponse, Response};
 use http::{HeaderMap, StatusCode};
 use jsonrpc_v2::RequestObject as JsonRpcRequestObject;
+use tokio::sync::RwLock;
+use tracing::Instrument as _;
 
+use crate::rpc::export_stream::{export_response, streamed_export_params, ChainExporter};
 use crate::rpc::rpc_util::{
-    call_rpc_str, check_permissions, get_auth_header, is_streaming_method, is_v1_method,
+    authorize_caller, call_rpc_str, get_auth_header, is_streaming_method, is_v1_method, CALLER,
 };
 
 // Lotus exposes two versions of its RPC API: v0 and v1. Version 0 is almost a
@@ -18,7 +26,10 @@ use crate::rpc::rpc_util::{
 // This HTTP handler rejects RPC calls if they're not v0 methods.
 pub async fn rpc_v0_http_handler(
     headers: HeaderMap,
+    remote_addr: ConnectInfo<SocketAddr>,
     rpc_server: axum::extract::State<JsonRpcServerState>,
+    keystore: axum::extract::State<Arc<RwLock<KeyStore>>>,
+    chain_exporter: axum::extract::State<Arc<dyn ChainExporter>>,
     rpc_call: axum::Json<JsonRpcRequestObject>,
 ) -> Response {
     if is_v1_method(rpc_call.0.method_ref()) {
@@ -28,43 +39,69 @@ pub async fn rpc_v0_http_handler(
         )
             .into_response()
     } else {
-        rpc_http_handler(headers, rpc_server, rpc_call)
-            .await
-            .into_response()
+        rpc_http_handler(
+            headers,
+            remote_addr,
+            rpc_server,
+            keystore,
+            chain_exporter,
+            rpc_call,
+        )
+        .await
     }
 }
 
-// This HTTP handler accepts both v0 and v1 RPC calls.
+// This HTTP handler accepts both v0 and v1 RPC calls. Streamed exports are
+// answered with the snapshot rather than with a JSON-RPC response.
 pub async fn rpc_http_handler(
     headers: HeaderMap,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
     axum::extract::State(rpc_server): axum::extract::State<JsonRpcServerState>,
+    axum::extract::State(keystore): axum::extract::State<Arc<RwLock<KeyStore>>>,
+    axum::extract::State(chain_exporter): axum::extract::State<Arc<dyn ChainExporter>>,
     axum::Json(rpc_call): axum::Json<JsonRpcRequestObject>,
-) -> impl IntoResponse {
+) -> Response {
     let response_headers = [("content-type", "application/json-rpc;charset=utf-8")];
-    if let Err((code, msg)) = check_permissions(
+    let caller = match authorize_caller(
         rpc_server.clone(),
+        &keystore,
         rpc_call.method_ref(),
         get_auth_header(headers),
+        remote_addr,
     )
     .await
     {
-        return (code, response_headers, msg);
+        Ok(caller) => caller,
+        Err((code, msg)) => return (code, response_headers, msg).into_response(),
+    };
+
+    if let Some(params) = streamed_export_params(&rpc_call) {
+        return export_response(chain_exporter.as_ref(), params);
     }
 
     if is_streaming_method(rpc_call.method_ref()) {
         return (
             StatusCode::INTERNAL_SERVER_ERROR,
             response_headers,
-            "This endpoint cannot handle streaming methods".into(),
-        );
+            "This endpoint cannot handle streaming methods",
+        )
+            .into_response();
     }
 
-    match call_rpc_str(rpc_server.clone(), rpc_call).await {
-        Ok(result) => (StatusCode::OK, response_headers, result),
+    let span = tracing::info_span!("rpc", caller = %caller, method = rpc_call.method_ref());
+    match CALLER
+        .scope(
+            caller,
+            call_rpc_str(rpc_server.clone(), rpc_call).instrument(span),
+        )
+        .await
+    {
+        Ok(result) => (StatusCode::OK, response_headers, result).into_response(),
         Err(err) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             response_headers,
             err.to_string(),
-        ),
+        )
+            .into_response(),
     }
 }
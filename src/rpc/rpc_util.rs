@@ -1,11 +1,19 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::auth::{verify_labeled_token, JWT_IDENTIFIER};
+use crate::key_management::KeyStore;
+use crate::rpc::metrics::RPC_REQUESTS;
+#[cfg(feature = "eth-api")]
+use crate::rpc_api::eth_api::*;
 use crate::rpc_api::{
-    auth_api::*, check_access, data_types::JsonRpcServerState, eth_api::*, ACCESS_MAP,
+    actor_events_api::SUBSCRIBE_ACTOR_EVENTS_RAW, auth_api::*, chain_api::CHAIN_NOTIFY,
+    check_access, data_types::JsonRpcServerState, sync_api::SYNC_STATE_NOTIFY, ACCESS_MAP,
 };
 use http::{HeaderMap, HeaderValue, StatusCode};
 use serde::de::DeserializeOwned;
+use std::net::SocketAddr;
+use tokio::sync::RwLock;
 use tracing::{debug, error};
 
 pub fn get_error_obj(code: i64, message: String) -> jsonrpc_v2::Error {
@@ -35,18 +43,27 @@ pub fn get_error_str(code: i64, message: String) -> String {
     }
 }
 
-const STREAMING_METHODS: [&str; 0] = [];
+const STREAMING_METHODS: [&str; 3] = [CHAIN_NOTIFY, SYNC_STATE_NOTIFY, SUBSCRIBE_ACTOR_EVENTS_RAW];
 
 pub fn is_streaming_method(method_name: &str) -> bool {
     STREAMING_METHODS.contains(&method_name)
 }
 
-const V1_METHODS: [&str; 5] = [
+#[cfg(not(feature = "eth-api"))]
+const V1_METHODS: [&str; 0] = [];
+
+#[cfg(feature = "eth-api")]
+const V1_METHODS: [&str; 10] = [
     ETH_ACCOUNTS,
     ETH_BLOCK_NUMBER,
     ETH_CHAIN_ID,
+    ETH_FEE_HISTORY,
     ETH_GAS_PRICE,
     ETH_GET_BALANCE,
+    ETH_GET_BLOCK_TRANSACTION_COUNT_BY_HASH,
+    ETH_GET_BLOCK_TRANSACTION_COUNT_BY_NUMBER,
+    ETH_GET_UNCLE_COUNT_BY_BLOCK_HASH,
+    ETH_SEND_RAW_TRANSACTION,
 ];
 
 pub fn is_v1_method(method_name: &str) -> bool {
@@ -94,6 +111,61 @@ pub async fn check_permissions(
     }
 }
 
+/// Callers without a labeled token are counted together in metrics.
+const ANONYMOUS_CALLER: &str = "anonymous";
+
+/// Checks the permissions of the caller of `method` and counts its request. Returns the identity of
+/// the caller.
+pub async fn authorize_caller(
+    rpc_server: JsonRpcServerState,
+    keystore: &RwLock<KeyStore>,
+    method: &str,
+    authorization_header: Option<HeaderValue>,
+    remote_addr: SocketAddr,
+) -> Result<String, (StatusCode, String)> {
+    let result = check_permissions(rpc_server, method, authorization_header.clone()).await;
+    let label = match result {
+        Ok(()) => verified_token_label(keystore, authorization_header.as_ref()).await,
+        Err(_) => None,
+    };
+    // The names of unknown methods are chosen by the callers, don't track them individually.
+    let method_label = if ACCESS_MAP.contains_key(&method) {
+        method
+    } else {
+        "unknown"
+    };
+    // Remote addresses would make the number of series unbounded, only the labels of verified
+    // tokens are tracked.
+    RPC_REQUESTS
+        .with_label_values(&[label.as_deref().unwrap_or(ANONYMOUS_CALLER), method_label])
+        .inc();
+    result.map(|()| caller_identity(label, remote_addr))
+}
+
+/// Returns the label of the token in `authorization_header`, if it is signed by the node.
+async fn verified_token_label(
+    keystore: &RwLock<KeyStore>,
+    authorization_header: Option<&HeaderValue>,
+) -> Option<String> {
+    let token = authorization_header?.to_str().ok()?;
+    let keystore = keystore.read().await;
+    let key_info = keystore.get(JWT_IDENTIFIER).ok()?;
+    verify_labeled_token(token.trim_start_matches("Bearer "), key_info.private_key())
+        .ok()?
+        .1
+}
+
+tokio::task_local! {
+    /// Identity of the caller of the RPC method being served, as returned by [`authorize_caller`].
+    /// Lets methods account for the resources held by each caller.
+    pub static CALLER: String;
+}
+
+/// Identifies the caller of an RPC request in logs: the label of its token, or its remote address.
+pub fn caller_identity(label: Option<String>, remote_addr: SocketAddr) -> String {
+    label.unwrap_or_else(|| remote_addr.ip().to_string())
+}
+
 pub fn get_auth_header(headers: HeaderMap) -> Option<HeaderValue> {
     headers.get("Authorization").cloned()
 }
@@ -158,3 +230,34 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_tokens_cannot_export_to_files() {
+        use crate::auth::{create_token, generate_priv_key, verify_token, ADMIN, READ};
+        use crate::rpc_api::chain_api::CHAIN_EXPORT_RANGE;
+
+        let key = generate_priv_key();
+        let claims = |perms: &[&str]| {
+            let perms = perms.iter().map(|perm| perm.to_string()).collect();
+            let token = create_token(perms, key.private_key(), chrono::Duration::hours(1)).unwrap();
+            verify_token(&token, key.private_key()).unwrap()
+        };
+        let access = ACCESS_MAP.get(&CHAIN_EXPORT_RANGE).unwrap();
+        assert!(!check_access(access, &claims(READ)));
+        assert!(check_access(access, &claims(ADMIN)));
+    }
+
+    #[test]
+    fn caller_identity_prefers_token_label() {
+        let remote_addr = "127.0.0.1:1234".parse().unwrap();
+        assert_eq!(
+            caller_identity(Some("Azathoth".into()), remote_addr),
+            "Azathoth"
+        );
+        assert_eq!(caller_identity(None, remote_addr), "127.0.0.1");
+    }
+}
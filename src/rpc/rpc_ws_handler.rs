@@ -1,13 +1,18 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
-use crate::rpc_api::data_types::JsonRpcServerState;
+use crate::chain_sync::SyncWorkers;
+use crate::key_management::KeyStore;
+use crate::rpc_api::{
+    actor_events_api::SUBSCRIBE_ACTOR_EVENTS_RAW, chain_api::CHAIN_NOTIFY,
+    data_types::JsonRpcServerState, sync_api::SYNC_STATE_NOTIFY,
+};
 use axum::{
     extract::{
         ws::{Message, WebSocket},
-        WebSocketUpgrade,
+        ConnectInfo, WebSocketUpgrade,
     },
     response::IntoResponse,
 };
@@ -15,28 +20,64 @@ use crossbeam::atomic::AtomicCell;
 use futures::{stream::SplitSink, SinkExt, StreamExt};
 use http::{HeaderMap, HeaderValue};
 use tokio::sync::RwLock;
-use tracing::{debug, error, warn};
+use tracing::{debug, error, warn, Instrument as _};
 
-use crate::rpc::rpc_util::{
-    call_rpc_str, check_permissions, get_auth_header, get_error_str, is_v1_method,
+use crate::rpc::{
+    rpc_util::{
+        authorize_caller, call_rpc_str, get_auth_header, get_error_str, is_v1_method, CALLER,
+    },
+    subscription::{self, HeadChangeJournal},
 };
 
+#[allow(clippy::too_many_arguments)]
 async fn rpc_ws_task(
     authorization_header: Option<HeaderValue>,
+    remote_addr: SocketAddr,
+    request: Vec<u8>,
     rpc_call: jsonrpc_v2::RequestObject,
     rpc_server: JsonRpcServerState,
+    keystore: Arc<RwLock<KeyStore>>,
+    head_changes: Arc<HeadChangeJournal>,
+    sync_workers: SyncWorkers,
     _is_socket_active: Arc<AtomicCell<bool>>,
     ws_sender: Arc<RwLock<SplitSink<WebSocket, Message>>>,
 ) -> anyhow::Result<()> {
     let call_method = rpc_call.method_ref();
     let _call_id = rpc_call.id_ref();
 
-    check_permissions(rpc_server.clone(), call_method, authorization_header)
-        .await
-        .map_err(|(_, e)| anyhow::Error::msg(e))?;
+    let caller = authorize_caller(
+        rpc_server.clone(),
+        &keystore,
+        call_method,
+        authorization_header,
+        remote_addr,
+    )
+    .await
+    .map_err(|(_, e)| anyhow::Error::msg(e))?;
+    let span = tracing::info_span!("rpc", caller = %caller, method = call_method);
 
     debug!("RPC WS called method: {}", call_method);
-    let response = call_rpc_str(rpc_server.clone(), rpc_call).await?;
+    if call_method == CHAIN_NOTIFY {
+        return subscription::serve_chain_notify(&request, head_changes, ws_sender)
+            .instrument(span)
+            .await;
+    }
+    if call_method == SYNC_STATE_NOTIFY {
+        return subscription::serve_sync_state_notify(&request, sync_workers, ws_sender)
+            .instrument(span)
+            .await;
+    }
+    if call_method == SUBSCRIBE_ACTOR_EVENTS_RAW {
+        return subscription::serve_actor_events(&request, rpc_server, head_changes, ws_sender)
+            .instrument(span)
+            .await;
+    }
+    let response = CALLER
+        .scope(
+            caller,
+            call_rpc_str(rpc_server.clone(), rpc_call).instrument(span),
+        )
+        .await?;
     ws_sender
         .write()
         .await
@@ -54,30 +95,63 @@ async fn rpc_ws_task(
 // This WS handler rejects RPC calls if they're not v0 methods.
 pub async fn rpc_v0_ws_handler(
     headers: HeaderMap,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
     axum::extract::State(rpc_server): axum::extract::State<JsonRpcServerState>,
+    axum::extract::State(keystore): axum::extract::State<Arc<RwLock<KeyStore>>>,
+    axum::extract::State(head_changes): axum::extract::State<Arc<HeadChangeJournal>>,
+    axum::extract::State(sync_workers): axum::extract::State<SyncWorkers>,
     ws: WebSocketUpgrade,
 ) -> impl IntoResponse {
     let authorization_header = get_auth_header(headers);
     ws.on_upgrade(move |socket| async {
-        rpc_ws_handler_inner(socket, authorization_header, rpc_server, true).await
+        rpc_ws_handler_inner(
+            socket,
+            authorization_header,
+            remote_addr,
+            rpc_server,
+            keystore,
+            head_changes,
+            sync_workers,
+            true,
+        )
+        .await
     })
 }
 
 pub async fn rpc_ws_handler(
     headers: HeaderMap,
+    ConnectInfo(remote_addr): ConnectInfo<SocketAddr>,
     axum::extract::State(rpc_server): axum::extract::State<JsonRpcServerState>,
+    axum::extract::State(keystore): axum::extract::State<Arc<RwLock<KeyStore>>>,
+    axum::extract::State(head_changes): axum::extract::State<Arc<HeadChangeJournal>>,
+    axum::extract::State(sync_workers): axum::extract::State<SyncWorkers>,
     ws: WebSocketUpgrade,
 ) -> impl IntoResponse {
     let authorization_header = get_auth_header(headers);
     ws.on_upgrade(move |socket| async {
-        rpc_ws_handler_inner(socket, authorization_header, rpc_server, false).await
+        rpc_ws_handler_inner(
+            socket,
+            authorization_header,
+            remote_addr,
+            rpc_server,
+            keystore,
+            head_changes,
+            sync_workers,
+            false,
+        )
+        .await
     })
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn rpc_ws_handler_inner(
     socket: WebSocket,
     authorization_header: Option<HeaderValue>,
+    remote_addr: SocketAddr,
     rpc_server: JsonRpcServerState,
+    keystore: Arc<RwLock<KeyStore>>,
+    head_changes: Arc<HeadChangeJournal>,
+    sync_workers: SyncWorkers,
     reject_v1_methods: bool,
 ) {
     debug!("Accepted WS connection!");
@@ -87,17 +161,17 @@ async fn rpc_ws_handler_inner(
     while let Some(Ok(message)) = receiver.next().await {
         debug!("Received new WS RPC message: {:?}", message);
 
-        let payload: Option<Result<jsonrpc_v2::RequestObject, serde_json::Error>> = match message {
+        let request: Option<Vec<u8>> = match message {
             Message::Text(request_text) => {
                 if !request_text.is_empty() {
-                    Some(serde_json::from_str(&request_text))
+                    Some(request_text.into_bytes())
                 } else {
                     None
                 }
             }
             Message::Binary(request_data) => {
                 if !request_data.is_empty() {
-                    Some(serde_json::from_slice(&request_data))
+                    Some(request_data)
                 } else {
                     None
                 }
@@ -106,10 +180,15 @@ async fn rpc_ws_handler_inner(
             _ => None,
         };
 
-        if let Some(request_obj) = payload {
+        if let Some(request) = request {
+            let request_obj: Result<jsonrpc_v2::RequestObject, serde_json::Error> =
+                serde_json::from_slice(&request);
             debug!("RPC Request Received: {:?}", &request_obj);
             let authorization_header = authorization_header.clone();
             let task_rpc_server = rpc_server.clone();
+            let task_keystore = keystore.clone();
+            let task_head_changes = head_changes.clone();
+            let task_sync_workers = sync_workers.clone();
             let task_socket_active = socket_active.clone();
             let task_ws_sender = ws_sender.clone();
             match request_obj {
@@ -127,8 +206,13 @@ async fn rpc_ws_handler_inner(
                     tokio::task::spawn(async move {
                         match rpc_ws_task(
                             authorization_header,
+                            remote_addr,
+                            request,
                             rpc_call,
                             task_rpc_server,
+                            task_keystore,
+                            task_head_changes,
+                            task_sync_workers,
                             task_socket_active,
                             task_ws_sender.clone(),
                         )
@@ -2,22 +2,29 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 #![allow(clippy::unused_async)]
 
-use crate::blocks::TipsetKey;
+use crate::blocks::{GossipBlock, TipsetKey};
 use crate::cid_collections::CidHashSet;
 use crate::libp2p::NetworkMessage;
 use crate::lotus_json::LotusJson;
+use crate::rpc::rpc_util::CALLER;
 use crate::rpc_api::data_types::{
-    ApiActorState, ApiDeadline, ApiInvocResult, CirculatingSupply, MarketDeal, MessageLookup,
-    MinerSectors, MiningBaseInfo, RPCState, SectorOnChainInfo, Transaction,
+    ApiActorState, ApiDeadline, ApiInvocResult, BlockTemplate, CirculatingSupply,
+    DealCollateralBounds, MarketDeal, MessageLookup, MinerSectors, MiningBaseInfo, RPCState,
+    SealVerifyInfo, SectorOnChainInfo, SectorPreCommitInfo, Transaction, WindowPoStVerifyInfo,
 };
+use crate::rpc_api::state_api::StateWaitMsgParams;
+use crate::shim::machine::BuiltinActorManifest;
 use crate::shim::{
     address::Address, clock::ChainEpoch, econ::TokenAmount, executor::Receipt, message::Message,
-    state_tree::ActorState, version::NetworkVersion,
+    sector::SectorNumber, state_tree::ActorState, version::NetworkVersion,
 };
 use crate::state_manager::chain_rand::ChainRand;
 use crate::state_manager::vm_circ_supply::GenesisInfo;
-use crate::state_manager::{InvocResult, MarketBalance};
+use crate::state_manager::{ActorChange, InvocResult, MarketBalance};
+use crate::state_migration::progress::MigrationProgress;
+use crate::utils::cid::CidCborExt as _;
 use crate::utils::db::car_stream::{CarBlock, CarWriter};
+use crate::utils::proofs_api::verification;
 use ahash::{HashMap, HashMapExt};
 use anyhow::Context as _;
 use cid::Cid;
@@ -25,7 +32,7 @@ use fil_actor_interface::miner::DeadlineInfo;
 use fil_actor_interface::{
     market, miner,
     miner::{MinerInfo, MinerPower},
-    multisig, power,
+    multisig,
 };
 use fil_actors_shared::fvm_ipld_bitfield::BitField;
 use futures::StreamExt;
@@ -55,6 +62,29 @@ pub(in crate::rpc) async fn miner_get_base_info<DB: Blockstore + Send + Sync + '
         .await
         .map(|info| info.into())
 }
+
+/// assembles and signs a block from the given template, for external mining software.
+pub(in crate::rpc) async fn miner_create_block<DB: Blockstore + Send + Sync + 'static>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((template,))): Params<LotusJson<(BlockTemplate,)>>,
+) -> Result<LotusJson<GossipBlock>, JsonRpcError> {
+    let block = crate::fil_cns::create_block(&data.state_manager, &data.keystore, template).await?;
+    // The messages must be available to whoever receives the block from this node.
+    block.persist(data.state_manager.blockstore())?;
+    Ok(LotusJson(GossipBlock {
+        header: block.header().clone(),
+        bls_messages: block
+            .bls_msgs()
+            .iter()
+            .map(Cid::from_cbor_blake2b256)
+            .collect::<Result<_, _>>()?,
+        secpk_messages: block
+            .secp_msgs()
+            .iter()
+            .map(Cid::from_cbor_blake2b256)
+            .collect::<Result<_, _>>()?,
+    }))
+}
 /// runs the given message and returns its result without any persisted changes.
 pub(in crate::rpc) async fn state_call<DB: Blockstore + Send + Sync + 'static>(
     data: Data<RPCState<DB>>,
@@ -110,6 +140,45 @@ pub(in crate::rpc) async fn state_get_network_version<DB: Blockstore>(
     Ok(data.state_manager.get_network_version(ts.epoch()))
 }
 
+/// returns the code CIDs of the builtin actors of the given network version, by actor name.
+pub(in crate::rpc) async fn state_actor_code_cids<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((version,))): Params<LotusJson<(NetworkVersion,)>>,
+) -> Result<LotusJson<HashMap<String, Cid>>, JsonRpcError> {
+    let manifest_cid = actor_manifest_cid(&data, version)?;
+    let manifest =
+        BuiltinActorManifest::load_manifest(data.state_manager.blockstore(), &manifest_cid)?;
+    Ok(LotusJson(
+        manifest
+            .builtin_actors()
+            .map(|(actor, code)| (actor.name().to_string(), code))
+            .collect(),
+    ))
+}
+
+/// returns the CID of the manifest of the builtin actors of the given network version.
+pub(in crate::rpc) async fn state_actor_manifest_cid<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((version,))): Params<LotusJson<(NetworkVersion,)>>,
+) -> Result<LotusJson<Cid>, JsonRpcError> {
+    Ok(LotusJson(actor_manifest_cid(&data, version)?))
+}
+
+fn actor_manifest_cid<DB: Blockstore>(
+    data: &RPCState<DB>,
+    version: NetworkVersion,
+) -> anyhow::Result<Cid> {
+    data.state_manager
+        .chain_config()
+        .manifest_cid(version)
+        .with_context(|| {
+            format!(
+                "no actor bundle for network version {}",
+                u32::from(*version)
+            )
+        })
+}
+
 /// gets the public key address of the given ID address
 /// See <https://github.com/filecoin-project/lotus/blob/master/documentation/en/api-v0-methods.md#StateAccountKey>
 pub(in crate::rpc) async fn state_account_key<DB: Blockstore>(
@@ -175,12 +244,8 @@ pub(in crate::rpc) async fn state_market_deals<DB: Blockstore>(
     Params(LotusJson((tsk,))): Params<LotusJson<(TipsetKey,)>>,
 ) -> Result<HashMap<String, MarketDeal>, JsonRpcError> {
     let ts = data.chain_store.load_required_tipset(&tsk)?;
-    let actor = data
-        .state_manager
-        .get_actor(&Address::MARKET_ACTOR, *ts.parent_state())?
-        .ok_or("Market actor address could not be resolved")?;
-    let market_state =
-        market::State::load(data.state_manager.blockstore(), actor.code, actor.state)?;
+    let view = data.state_manager.state_at(&ts);
+    let market_state = view.market_state()?;
 
     let da = market_state.proposals(data.state_manager.blockstore())?;
     let sa = market_state.states(data.state_manager.blockstore())?;
@@ -223,11 +288,7 @@ pub(in crate::rpc) async fn state_miner_active_sectors<DB: Blockstore>(
     let bs = data.state_manager.blockstore();
     let ts = data.chain_store.load_required_tipset(&tsk)?;
     let policy = &data.state_manager.chain_config().policy;
-    let actor = data
-        .state_manager
-        .get_actor(&miner, *ts.parent_state())?
-        .ok_or("Miner actor address could not be resolved")?;
-    let miner_state = miner::State::load(bs, actor.code, actor.state)?;
+    let miner_state = data.state_manager.state_at(&ts).miner_state(&miner)?;
 
     // Collect active sectors from each partition in each deadline.
     let mut active_sectors = vec![];
@@ -255,11 +316,7 @@ pub(in crate::rpc) async fn state_miner_sector_count<DB: Blockstore>(
     let bs = data.state_manager.blockstore();
     let ts = data.chain_store.load_required_tipset(&tsk)?;
     let policy = &data.state_manager.chain_config().policy;
-    let actor = data
-        .state_manager
-        .get_actor(&miner, *ts.parent_state())?
-        .ok_or("Miner actor address could not be resolved")?;
-    let miner_state = miner::State::load(bs, actor.code, actor.state)?;
+    let miner_state = data.state_manager.state_at(&ts).miner_state(&miner)?;
 
     // Collect live, active and faulty sectors count from each partition in each deadline.
     let mut live_count = 0;
@@ -302,12 +359,8 @@ pub(in crate::rpc) async fn state_miner_deadlines<DB: Blockstore + Send + Sync +
 ) -> Result<LotusJson<Vec<ApiDeadline>>, JsonRpcError> {
     let ts = data.chain_store.load_required_tipset(&tsk)?;
     let policy = &data.state_manager.chain_config().policy;
-    let actor = data
-        .state_manager
-        .get_actor(&addr, *ts.parent_state())?
-        .ok_or("Miner actor address could not be resolved")?;
     let store = data.state_manager.blockstore();
-    let state = miner::State::load(store, actor.code, actor.state)?;
+    let state = data.state_manager.state_at(&ts).miner_state(&addr)?;
     let mut res = Vec::new();
     state.for_each_deadline(policy, store, |_idx, deadline| {
         res.push(ApiDeadline {
@@ -325,13 +378,125 @@ pub(in crate::rpc) async fn state_miner_proving_deadline<DB: Blockstore + Send +
 ) -> Result<LotusJson<DeadlineInfo>, JsonRpcError> {
     let ts = data.chain_store.load_required_tipset(&tsk)?;
     let policy = &data.state_manager.chain_config().policy;
-    let actor = data
-        .state_manager
-        .get_actor(&addr, *ts.parent_state())?
+    let state = data.state_manager.state_at(&ts).miner_state(&addr)?;
+    Ok(LotusJson(state.deadline_info(policy, ts.epoch())))
+}
+
+/// returns the initial pledge collateral required to commit the given sector.
+pub(in crate::rpc) async fn state_miner_initial_pledge_collateral<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((address, sector, tsk))): Params<
+        LotusJson<(Address, SectorPreCommitInfo, TipsetKey)>,
+    >,
+) -> Result<LotusJson<TokenAmount>, JsonRpcError> {
+    let ts = data.chain_store.load_required_tipset(&tsk)?;
+    Ok(LotusJson(
+        data.state_manager
+            .miner_initial_pledge_collateral(&address, &sector, &ts)?,
+    ))
+}
+
+/// returns the deposit required to pre-commit the given sector.
+pub(in crate::rpc) async fn state_miner_pre_commit_deposit_for_power<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((address, sector, tsk))): Params<
+        LotusJson<(Address, SectorPreCommitInfo, TipsetKey)>,
+    >,
+) -> Result<LotusJson<TokenAmount>, JsonRpcError> {
+    let ts = data.chain_store.load_required_tipset(&tsk)?;
+    Ok(LotusJson(
+        data.state_manager
+            .miner_pre_commit_deposit_for_power(&address, &sector, &ts)?,
+    ))
+}
+
+/// returns the balance of the miner that is neither locked nor owed, including the vested funds
+/// that are not withdrawn yet.
+pub(in crate::rpc) async fn state_miner_available_balance<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((address, tsk))): Params<LotusJson<(Address, TipsetKey)>>,
+) -> Result<LotusJson<TokenAmount>, JsonRpcError> {
+    let ts = data.chain_store.load_required_tipset(&tsk)?;
+    let store = data.state_manager.blockstore();
+    let view = data.state_manager.state_at(&ts);
+    let actor = view
+        .get_actor(&address)?
         .ok_or("Miner actor address could not be resolved")?;
+    let balance = TokenAmount::from(&actor.balance);
+    let state = view.miner_state(&address)?;
+
+    macro_rules! available_balance {
+        ($($variant:ident),+) => {
+            match &*state {
+                $(
+                    miner::State::$variant(st) => {
+                        let available: TokenAmount =
+                            st.get_available_balance(&(&balance).into())?.into();
+                        st.load_vesting_funds(store)?
+                            .funds
+                            .iter()
+                            .filter(|fund| fund.epoch < ts.epoch())
+                            .fold(available, |total, fund| total + TokenAmount::from(&fund.amount))
+                    }
+                )+
+            }
+        };
+    }
+
+    Ok(LotusJson(available_balance!(V8, V9, V10, V11, V12)))
+}
+
+/// checks if a sector number is marked as allocated by the miner.
+pub(in crate::rpc) async fn state_miner_sector_allocated<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((address, sector_number, tsk))): Params<
+        LotusJson<(Address, SectorNumber, TipsetKey)>,
+    >,
+) -> Result<LotusJson<bool>, JsonRpcError> {
+    let ts = data.chain_store.load_required_tipset(&tsk)?;
     let store = data.state_manager.blockstore();
-    let state = miner::State::load(store, actor.code, actor.state)?;
-    Ok(LotusJson(state.deadline_info(policy, ts.epoch())))
+    let allocated_sectors = match &*data.state_manager.state_at(&ts).miner_state(&address)? {
+        miner::State::V8(st) => st.allocated_sectors,
+        miner::State::V9(st) => st.allocated_sectors,
+        miner::State::V10(st) => st.allocated_sectors,
+        miner::State::V11(st) => st.allocated_sectors,
+        miner::State::V12(st) => st.allocated_sectors,
+    };
+    let allocated_sectors: BitField = store
+        .get_cbor(&allocated_sectors)?
+        .context("allocated sectors bitfield not found")?;
+    Ok(LotusJson(allocated_sectors.get(sector_number)))
+}
+
+/// verifies the seal proofs of a batch of sectors.
+pub(in crate::rpc) async fn state_verify_seals<DB: Blockstore>(
+    _data: Data<RPCState<DB>>,
+    Params(LotusJson((infos,))): Params<LotusJson<(Vec<SealVerifyInfo>,)>>,
+) -> Result<LotusJson<Vec<bool>>, JsonRpcError> {
+    let valid = tokio::task::spawn_blocking(move || verification::verify_seals(&infos)).await?;
+    Ok(LotusJson(valid))
+}
+
+/// verifies the window `PoSt` proofs of a deadline partition.
+pub(in crate::rpc) async fn state_verify_window_post<DB: Blockstore>(
+    _data: Data<RPCState<DB>>,
+    Params(LotusJson((info,))): Params<LotusJson<(WindowPoStVerifyInfo,)>>,
+) -> Result<LotusJson<bool>, JsonRpcError> {
+    let valid =
+        tokio::task::spawn_blocking(move || verification::verify_window_post(&info)).await??;
+    Ok(LotusJson(valid))
+}
+
+/// returns the minimum and maximum collateral a storage provider can lock for a deal.
+pub(in crate::rpc) async fn state_deal_provider_collateral_bounds<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((piece_size, _verified, tsk))): Params<LotusJson<(u64, bool, TipsetKey)>>,
+) -> Result<LotusJson<DealCollateralBounds>, JsonRpcError> {
+    let ts = data.chain_store.load_required_tipset(&tsk)?;
+    Ok(LotusJson(
+        data.state_manager
+            .deal_provider_collateral_bounds(piece_size, &ts)?,
+    ))
 }
 
 /// looks up the miner power of the given address.
@@ -384,23 +549,65 @@ pub(in crate::rpc) async fn state_get_receipt<DB: Blockstore + Send + Sync + 'st
 /// message arrives on chain, and gets to the indicated confidence depth.
 pub(in crate::rpc) async fn state_wait_msg<DB: Blockstore + Send + Sync + 'static>(
     data: Data<RPCState<DB>>,
-    Params(LotusJson((cid, confidence))): Params<LotusJson<(Cid, i64)>>,
+    Params(params): Params<StateWaitMsgParams>,
 ) -> Result<MessageLookup, JsonRpcError> {
-    let state_manager = &data.state_manager;
-    let (tipset, receipt) = state_manager.wait_for_message(cid, confidence).await?;
-    let tipset = tipset.ok_or("wait for msg returned empty tuple")?;
-    let receipt = receipt.ok_or("wait for msg returned empty receipt")?;
+    let (cid, options) = params.into_parts();
+    let (tipset, receipt, executed) = data.state_manager.wait_for_message(cid, options).await?;
     let ipld = receipt.return_data().deserialize().unwrap_or(Ipld::Null);
 
     Ok(MessageLookup {
         receipt,
         tipset: tipset.key().clone(),
         height: tipset.epoch(),
-        message: cid,
+        message: executed,
         return_dec: ipld,
     })
 }
 
+/// Starts watching the heads of the given actors. Returns a subscription
+/// identifier to be polled with `Filecoin.StateNextActorChanges`.
+pub(in crate::rpc) async fn state_subscribe_actor_changes<
+    DB: Blockstore + Send + Sync + 'static,
+>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((addresses,))): Params<LotusJson<(Vec<Address>,)>>,
+) -> Result<LotusJson<u64>, JsonRpcError> {
+    let state_manager = &data.state_manager;
+    let caller = CALLER.try_with(Clone::clone).unwrap_or_default();
+    let receiver = state_manager.subscribe_actor_changes(addresses)?;
+    Ok(LotusJson(
+        state_manager
+            .actor_subscriptions()
+            .insert(caller, receiver)?,
+    ))
+}
+
+/// Blocks until at least one watched actor head changes, then returns all the
+/// changes queued on the subscription.
+pub(in crate::rpc) async fn state_next_actor_changes<DB: Blockstore + Send + Sync + 'static>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((id,))): Params<LotusJson<(u64,)>>,
+) -> Result<LotusJson<Vec<ActorChange>>, JsonRpcError> {
+    let changes = data
+        .state_manager
+        .actor_subscriptions()
+        .next_changes(id)
+        .await
+        .ok_or_else(|| format!("no active actor changes subscription with id {id}"))?;
+    Ok(LotusJson(changes))
+}
+
+pub(in crate::rpc) async fn state_unsubscribe_actor_changes<
+    DB: Blockstore + Send + Sync + 'static,
+>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((id,))): Params<LotusJson<(u64,)>>,
+) -> Result<LotusJson<bool>, JsonRpcError> {
+    Ok(LotusJson(
+        data.state_manager.actor_subscriptions().remove(id),
+    ))
+}
+
 /// Searches for a message in the chain, and returns its receipt and the tipset where it was executed.
 /// See <https://github.com/filecoin-project/lotus/blob/master/documentation/en/api-v0-methods.md#StateSearchMsg>
 pub(in crate::rpc) async fn state_search_msg<DB: Blockstore + Send + Sync + 'static>(
@@ -767,17 +974,39 @@ pub(in crate::rpc) async fn state_sector_get_info<DB: Blockstore + Send + Sync +
     Params(LotusJson((addr, sector_no, tsk))): Params<LotusJson<(Address, u64, TipsetKey)>>,
 ) -> Result<LotusJson<SectorOnChainInfo>, JsonRpcError> {
     let ts = data.chain_store.load_required_tipset(&tsk)?;
+    let mut sectors = BitField::new();
+    sectors.set(sector_no);
 
     Ok(LotusJson(
         data.state_manager
-            .get_all_sectors(&addr, &ts)?
+            .get_sectors(&addr, &ts, Some(&sectors))?
             .into_iter()
-            .find(|info| info.sector_number == sector_no)
+            .next()
             .map(SectorOnChainInfo::from)
             .ok_or(format!("Info for sector number {sector_no} not found"))?,
     ))
 }
 
+/// Returns the on-chain info of the given sectors of a miner, or of all its
+/// sectors if no bitfield is given, in ascending order of sector number.
+/// Sectors that don't exist are skipped.
+pub(in crate::rpc) async fn state_miner_sectors<DB: Blockstore + Send + Sync + 'static>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((addr, sectors, tsk))): Params<
+        LotusJson<(Address, Option<BitField>, TipsetKey)>,
+    >,
+) -> Result<LotusJson<Vec<SectorOnChainInfo>>, JsonRpcError> {
+    let ts = data.chain_store.load_required_tipset(&tsk)?;
+
+    Ok(LotusJson(
+        data.state_manager
+            .get_sectors(&addr, &ts, sectors.as_ref())?
+            .into_iter()
+            .map(SectorOnChainInfo::from)
+            .collect(),
+    ))
+}
+
 pub(in crate::rpc) async fn state_verified_client_status<DB: Blockstore + Send + Sync + 'static>(
     data: Data<RPCState<DB>>,
     Params(LotusJson((addr, tsk))): Params<LotusJson<(Address, TipsetKey)>>,
@@ -812,18 +1041,59 @@ pub(in crate::rpc) async fn state_list_miners<DB: Blockstore + Send + Sync + 'st
         .state_manager
         .chain_store()
         .load_required_tipset(&tsk)?;
-    let store = data.state_manager.blockstore();
-    let actor = data
-        .state_manager
-        .get_actor(&Address::POWER_ACTOR, *ts.parent_state())?
-        .ok_or("Power actor not found".to_string())?;
-
-    let state = power::State::load(store, actor.code, actor.state)?;
-    let miners = state
-        .list_all_miners(store)?
+    let view = data.state_manager.state_at(&ts);
+    let miners = view
+        .power_state()?
+        .list_all_miners(view.blockstore())?
         .iter()
         .map(|addr| addr.into())
         .collect();
 
     Ok(LotusJson(miners))
 }
+
+/// Returns the progress of the running state migration, or of the last one since the node
+/// started.
+pub(in crate::rpc) async fn state_migration_progress(
+) -> Result<Option<MigrationProgress>, JsonRpcError> {
+    Ok(crate::state_migration::progress::current())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state_manager::WaitOptions;
+
+    #[test]
+    fn state_wait_msg_params() {
+        let cid = serde_json::json!({ "/": Cid::default().to_string() });
+        let params: StateWaitMsgParams =
+            serde_json::from_value(serde_json::json!([cid, 5])).unwrap();
+        assert_eq!(
+            params.into_parts(),
+            (
+                Cid::default(),
+                WaitOptions {
+                    confidence: 5,
+                    look_back_limit: None,
+                    allow_replaced: true,
+                }
+            )
+        );
+
+        let params: StateWaitMsgParams =
+            serde_json::from_value(serde_json::json!([cid, 5, 100, false])).unwrap();
+        assert_eq!(
+            params.into_parts().1,
+            WaitOptions {
+                confidence: 5,
+                look_back_limit: Some(100),
+                allow_replaced: false,
+            }
+        );
+
+        let params: StateWaitMsgParams =
+            serde_json::from_value(serde_json::json!([cid, 5, -1, true])).unwrap();
+        assert_eq!(params.into_parts().1.look_back_limit, None);
+    }
+}
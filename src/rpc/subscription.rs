@@ -0,0 +1,572 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! `Filecoin.ChainNotify` subscriptions over WebSocket, with resumption
+//! tokens.
+//!
+//! Head changes are recorded in a bounded [`HeadChangeJournal`]. Notifications
+//! have the Lotus `[channel, changes]` parameters, unless the client opts in to
+//! resumption by passing a parameter to `Filecoin.ChainNotify`: an empty string
+//! for a new subscription, or the last token it was sent. Every notification
+//! then also carries a token that identifies the last change it contains. A
+//! client that lost its connection passes its last token and is sent the
+//! changes it missed, rather than starting over from the current head. Tokens
+//! that are too old, or that were handed out before a restart of the node, fall
+//! back to a `current` notification, as for a new subscription.
+//!
+//! `Filecoin.SyncStateNotify` subscriptions stream the states of the chain
+//! sync workers as they change stage, after the current state of every worker.
+//!
+//! `Filecoin.SubscribeActorEventsRaw` subscriptions stream the actor events
+//! matching a filter, as the tipsets including them are executed, after the
+//! past events the filter selects.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::blocks::Tipset;
+use crate::chain::event_index::ActorEvent;
+use crate::chain::HeadChange;
+use crate::chain_sync::{SyncState, SyncWorkers};
+use crate::lotus_json::LotusJson;
+use crate::rpc::rpc_util::call_rpc;
+use crate::rpc_api::{
+    actor_events_api::GET_ACTOR_EVENTS_RAW,
+    data_types::{ActorEventFilter, JsonRpcServerState},
+};
+use axum::extract::ws::{Message, WebSocket};
+use futures::{stream::SplitSink, SinkExt};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, RwLock as AsyncRwLock};
+use tracing::{debug, warn};
+
+/// Number of head changes kept to resume subscriptions, a bit over 8 hours of
+/// chain.
+pub const HEAD_CHANGE_JOURNAL_CAPACITY: usize = 1000;
+
+/// Identifiers of the channels notifications are sent on.
+static NEXT_CHANNEL_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Position in a [`HeadChangeJournal`]: the sequence number of the first
+/// change the holder hasn't been sent yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResumeToken {
+    instance: u64,
+    seq: u64,
+}
+
+impl fmt::Display for ResumeToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}{:016x}", self.instance, self.seq)
+    }
+}
+
+impl FromStr for ResumeToken {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        anyhow::ensure!(
+            s.len() == 32 && s.is_ascii(),
+            "invalid subscription resumption token: {s}"
+        );
+        Ok(ResumeToken {
+            instance: u64::from_str_radix(&s[..16], 16)?,
+            seq: u64::from_str_radix(&s[16..], 16)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct JournalEntry {
+    seq: u64,
+    change: HeadChange,
+}
+
+struct JournalState {
+    /// Head as of the last recorded change.
+    head: Arc<Tipset>,
+    next_seq: u64,
+    entries: VecDeque<JournalEntry>,
+}
+
+/// Bounded, in-memory record of the most recent head changes.
+pub struct HeadChangeJournal {
+    /// Random identifier of the journal, so that tokens handed out before a
+    /// restart aren't mistaken for positions in this journal.
+    instance: u64,
+    capacity: usize,
+    state: RwLock<JournalState>,
+    live: broadcast::Sender<JournalEntry>,
+}
+
+/// Head change, in the Lotus JSON format of `Filecoin.ChainNotify`.
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct HeadChangeEvent {
+    r#type: &'static str,
+    val: LotusJson<Tipset>,
+}
+
+impl HeadChangeEvent {
+    fn current(head: &Tipset) -> Self {
+        HeadChangeEvent {
+            r#type: "current",
+            val: LotusJson(head.clone()),
+        }
+    }
+
+    fn from_change(change: &HeadChange) -> Self {
+        match change {
+            HeadChange::Apply(ts) => HeadChangeEvent {
+                r#type: "apply",
+                val: LotusJson(ts.as_ref().clone()),
+            },
+        }
+    }
+}
+
+/// Subscription to a [`HeadChangeJournal`].
+struct Subscription {
+    /// Changes to send before the live ones.
+    initial: Vec<HeadChangeEvent>,
+    /// Sequence number of the next change to send.
+    next_seq: u64,
+    live: broadcast::Receiver<JournalEntry>,
+}
+
+impl HeadChangeJournal {
+    pub fn new(head: Arc<Tipset>, capacity: usize) -> Self {
+        HeadChangeJournal {
+            instance: rand::random(),
+            capacity,
+            state: RwLock::new(JournalState {
+                head,
+                next_seq: 0,
+                entries: VecDeque::with_capacity(capacity),
+            }),
+            live: broadcast::channel(capacity).0,
+        }
+    }
+
+    /// Records the head changes published on `head_changes`, until the
+    /// publisher is dropped.
+    pub async fn record(self: Arc<Self>, mut head_changes: broadcast::Receiver<HeadChange>) {
+        loop {
+            match head_changes.recv().await {
+                Ok(change) => self.push(change),
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!("Head change journal missed {n} head changes")
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    }
+
+    fn push(&self, change: HeadChange) {
+        let mut state = self.state.write();
+        let entry = JournalEntry {
+            seq: state.next_seq,
+            change,
+        };
+        state.next_seq += 1;
+        match &entry.change {
+            HeadChange::Apply(ts) => state.head = ts.clone(),
+        }
+        state.entries.push_back(entry.clone());
+        if state.entries.len() > self.capacity {
+            state.entries.pop_front();
+        }
+        // Sent under the lock, so that subscribers get every change either
+        // from the journal or live.
+        let _ = self.live.send(entry);
+    }
+
+    fn subscribe(&self, token: Option<ResumeToken>) -> Subscription {
+        let state = self.state.read();
+        let live = self.live.subscribe();
+        let oldest_seq = state
+            .entries
+            .front()
+            .map(|entry| entry.seq)
+            .unwrap_or(state.next_seq);
+        match token {
+            Some(token)
+                if token.instance == self.instance
+                    && (oldest_seq..=state.next_seq).contains(&token.seq) =>
+            {
+                Subscription {
+                    initial: state
+                        .entries
+                        .iter()
+                        .filter(|entry| entry.seq >= token.seq)
+                        .map(|entry| HeadChangeEvent::from_change(&entry.change))
+                        .collect(),
+                    next_seq: state.next_seq,
+                    live,
+                }
+            }
+            _ => Subscription {
+                initial: vec![HeadChangeEvent::current(&state.head)],
+                next_seq: state.next_seq,
+                live,
+            },
+        }
+    }
+
+    fn token(&self, seq: u64) -> ResumeToken {
+        ResumeToken {
+            instance: self.instance,
+            seq,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ChainNotifyRequest {
+    #[serde(default)]
+    id: serde_json::Value,
+    /// Optional resumption token, empty to start a resumable subscription.
+    #[serde(default)]
+    params: Vec<String>,
+}
+
+async fn send_json(
+    ws_sender: &AsyncRwLock<SplitSink<WebSocket, Message>>,
+    value: serde_json::Value,
+) -> anyhow::Result<()> {
+    ws_sender
+        .write()
+        .await
+        .send(Message::Text(serde_json::to_string(&value)?))
+        .await?;
+    Ok(())
+}
+
+/// Parameters of a head change notification, with the resumption token only
+/// for subscriptions that opted in.
+fn notification_params(
+    channel_id: u64,
+    changes: Vec<HeadChangeEvent>,
+    token: Option<ResumeToken>,
+) -> serde_json::Value {
+    match token {
+        Some(token) => serde_json::json!([channel_id, changes, token.to_string()]),
+        None => serde_json::json!([channel_id, changes]),
+    }
+}
+
+async fn send_notification(
+    ws_sender: &AsyncRwLock<SplitSink<WebSocket, Message>>,
+    channel_id: u64,
+    changes: Vec<HeadChangeEvent>,
+    token: Option<ResumeToken>,
+) -> anyhow::Result<()> {
+    send_json(
+        ws_sender,
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "xrpc.ch.val",
+            "params": notification_params(channel_id, changes, token),
+        }),
+    )
+    .await
+}
+
+/// Serves a `Filecoin.ChainNotify` request until the WebSocket is closed.
+pub(in crate::rpc) async fn serve_chain_notify(
+    request: &[u8],
+    journal: Arc<HeadChangeJournal>,
+    ws_sender: Arc<AsyncRwLock<SplitSink<WebSocket, Message>>>,
+) -> anyhow::Result<()> {
+    let request: ChainNotifyRequest = serde_json::from_slice(request)?;
+    let resumable = !request.params.is_empty();
+    let token = request
+        .params
+        .first()
+        .filter(|token| !token.is_empty())
+        .map(|token| token.parse::<ResumeToken>())
+        .transpose()?;
+    let Subscription {
+        initial,
+        mut next_seq,
+        mut live,
+    } = journal.subscribe(token);
+
+    let channel_id = NEXT_CHANNEL_ID.fetch_add(1, Ordering::Relaxed);
+    debug!("Serving head changes on channel {channel_id}");
+    send_json(
+        &ws_sender,
+        serde_json::json!({ "jsonrpc": "2.0", "result": channel_id, "id": request.id }),
+    )
+    .await?;
+    if !initial.is_empty() {
+        let token = resumable.then(|| journal.token(next_seq));
+        send_notification(&ws_sender, channel_id, initial, token).await?;
+    }
+
+    loop {
+        match live.recv().await {
+            // Already sent from the journal.
+            Ok(entry) if entry.seq < next_seq => {}
+            Ok(entry) => {
+                next_seq = entry.seq + 1;
+                let changes = vec![HeadChangeEvent::from_change(&entry.change)];
+                let token = resumable.then(|| journal.token(next_seq));
+                send_notification(&ws_sender, channel_id, changes, token).await?;
+            }
+            // Changes were lost, start over from the current head.
+            Err(broadcast::error::RecvError::Lagged(_)) => {
+                let Subscription {
+                    initial,
+                    next_seq: seq,
+                    live: receiver,
+                } = journal.subscribe(None);
+                (next_seq, live) = (seq, receiver);
+                let token = resumable.then(|| journal.token(next_seq));
+                send_notification(&ws_sender, channel_id, initial, token).await?;
+            }
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SyncStateNotifyRequest {
+    #[serde(default)]
+    id: serde_json::Value,
+}
+
+async fn send_sync_states(
+    ws_sender: &AsyncRwLock<SplitSink<WebSocket, Message>>,
+    channel_id: u64,
+    states: Vec<SyncState>,
+) -> anyhow::Result<()> {
+    send_json(
+        ws_sender,
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "xrpc.ch.val",
+            "params": [channel_id, LotusJson(states)],
+        }),
+    )
+    .await
+}
+
+/// Serves a `Filecoin.SyncStateNotify` request until the WebSocket is closed.
+pub(in crate::rpc) async fn serve_sync_state_notify(
+    request: &[u8],
+    sync_workers: SyncWorkers,
+    ws_sender: Arc<AsyncRwLock<SplitSink<WebSocket, Message>>>,
+) -> anyhow::Result<()> {
+    let request: SyncStateNotifyRequest = serde_json::from_slice(request)?;
+    // Subscribed before reading the current states, so that no transition is
+    // missed in between.
+    let mut transitions = sync_workers.subscribe();
+
+    let channel_id = NEXT_CHANNEL_ID.fetch_add(1, Ordering::Relaxed);
+    debug!("Serving sync states on channel {channel_id}");
+    send_json(
+        &ws_sender,
+        serde_json::json!({ "jsonrpc": "2.0", "result": channel_id, "id": request.id }),
+    )
+    .await?;
+    send_sync_states(&ws_sender, channel_id, sync_workers.states().into()).await?;
+
+    loop {
+        match transitions.recv().await {
+            Ok(state) => send_sync_states(&ws_sender, channel_id, vec![state]).await?,
+            // Transitions were lost, start over from the current states.
+            Err(broadcast::error::RecvError::Lagged(_)) => {
+                transitions = sync_workers.subscribe();
+                send_sync_states(&ws_sender, channel_id, sync_workers.states().into()).await?;
+            }
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct SubscribeActorEventsRequest {
+    #[serde(default)]
+    id: serde_json::Value,
+    /// Optional filter.
+    #[serde(default)]
+    params: Vec<Option<LotusJson<ActorEventFilter>>>,
+}
+
+async fn actor_events(
+    rpc_server: &JsonRpcServerState,
+    filter: ActorEventFilter,
+) -> anyhow::Result<Vec<ActorEvent>> {
+    let (_, LotusJson(events)) = call_rpc::<LotusJson<Vec<ActorEvent>>>(
+        rpc_server.clone(),
+        jsonrpc_v2::RequestObject::request()
+            .with_method(GET_ACTOR_EVENTS_RAW)
+            .with_params(serde_json::json!([LotusJson(filter)]))
+            .with_id(0)
+            .finish(),
+    )
+    .await?;
+    Ok(events)
+}
+
+async fn send_actor_events(
+    ws_sender: &AsyncRwLock<SplitSink<WebSocket, Message>>,
+    channel_id: u64,
+    events: Vec<ActorEvent>,
+) -> anyhow::Result<()> {
+    for event in events {
+        send_json(
+            ws_sender,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "xrpc.ch.val",
+                "params": [channel_id, LotusJson(event)],
+            }),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Serves a `Filecoin.SubscribeActorEventsRaw` request until the WebSocket is
+/// closed, or the tipsets up to the end of the range of the filter are
+/// executed.
+pub(in crate::rpc) async fn serve_actor_events(
+    request: &[u8],
+    rpc_server: JsonRpcServerState,
+    journal: Arc<HeadChangeJournal>,
+    ws_sender: Arc<AsyncRwLock<SplitSink<WebSocket, Message>>>,
+) -> anyhow::Result<()> {
+    let request: SubscribeActorEventsRequest = serde_json::from_slice(request)?;
+    let filter = request
+        .params
+        .into_iter()
+        .next()
+        .flatten()
+        .map(|LotusJson(filter)| filter)
+        .unwrap_or_default();
+    // Subscribed before reading the past events, so that no tipset is missed
+    // in between.
+    let mut live = journal.live.subscribe();
+
+    let channel_id = NEXT_CHANNEL_ID.fetch_add(1, Ordering::Relaxed);
+    debug!("Serving actor events on channel {channel_id}");
+    send_json(
+        &ws_sender,
+        serde_json::json!({ "jsonrpc": "2.0", "result": channel_id, "id": request.id }),
+    )
+    .await?;
+    if filter.from_height.is_some() || filter.tipset_key.is_some() {
+        let events = actor_events(&rpc_server, filter.clone()).await?;
+        send_actor_events(&ws_sender, channel_id, events).await?;
+    }
+    if filter.tipset_key.is_some() {
+        return Ok(());
+    }
+
+    loop {
+        match live.recv().await {
+            // The events of the parent of the new head are indexed as it is
+            // applied.
+            Ok(JournalEntry {
+                change: HeadChange::Apply(ts),
+                ..
+            }) => {
+                let parent_filter = ActorEventFilter {
+                    tipset_key: Some(ts.parents().clone()),
+                    from_height: None,
+                    to_height: None,
+                    ..filter.clone()
+                };
+                let events = actor_events(&rpc_server, parent_filter)
+                    .await?
+                    .into_iter()
+                    .filter(|event| filter.to_height.map_or(true, |to| event.height <= to))
+                    .collect();
+                send_actor_events(&ws_sender, channel_id, events).await?;
+                if filter.to_height.is_some_and(|to| ts.epoch() > to) {
+                    return Ok(());
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                warn!("Actor events of {n} tipsets were lost on channel {channel_id}")
+            }
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message_pool::test_provider::mock_tipset;
+
+    fn epochs(events: &[HeadChangeEvent]) -> Vec<(&'static str, i64)> {
+        events
+            .iter()
+            .map(|event| (event.r#type, event.val.0.epoch()))
+            .collect()
+    }
+
+    #[test]
+    fn resume_token_roundtrip() {
+        let token = ResumeToken {
+            instance: 0xdead_beef,
+            seq: 42,
+        };
+        assert_eq!(token.to_string().parse::<ResumeToken>().unwrap(), token);
+        assert!("42".parse::<ResumeToken>().is_err());
+    }
+
+    #[test]
+    fn notifications_only_carry_tokens_when_resumable() {
+        let journal = HeadChangeJournal::new(mock_tipset(0, 0), 3);
+        let current = || vec![HeadChangeEvent::current(&mock_tipset(0, 0))];
+
+        let lotus = notification_params(7, current(), None);
+        assert_eq!(lotus.as_array().unwrap().len(), 2);
+        assert_eq!(lotus[0], 7);
+
+        let resumable = notification_params(7, current(), Some(journal.token(1)));
+        assert_eq!(resumable.as_array().unwrap().len(), 3);
+        assert_eq!(resumable[2], journal.token(1).to_string());
+    }
+
+    #[test]
+    fn subscriptions_resume_from_the_journal() {
+        let journal = HeadChangeJournal::new(mock_tipset(0, 0), 3);
+        assert_eq!(epochs(&journal.subscribe(None).initial), [("current", 0)]);
+
+        for epoch in 1..=2 {
+            journal.push(HeadChange::Apply(mock_tipset(0, epoch)));
+        }
+        let resumed = journal.subscribe(Some(journal.token(1)));
+        assert_eq!(epochs(&resumed.initial), [("apply", 2)]);
+        assert_eq!(resumed.next_seq, 2);
+
+        // Up to date tokens resume without any change to replay.
+        assert!(journal.subscribe(Some(journal.token(2))).initial.is_empty());
+
+        // Evicted changes can't be replayed.
+        for epoch in 3..=5 {
+            journal.push(HeadChange::Apply(mock_tipset(0, epoch)));
+        }
+        let expired = journal.subscribe(Some(journal.token(1)));
+        assert_eq!(epochs(&expired.initial), [("current", 5)]);
+
+        // Nor can changes of another journal.
+        let foreign = ResumeToken {
+            instance: journal.instance.wrapping_add(1),
+            seq: 4,
+        };
+        assert_eq!(
+            epochs(&journal.subscribe(Some(foreign)).initial),
+            [("current", 5)]
+        );
+    }
+}
@@ -2,14 +2,13 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 #![allow(clippy::unused_async)]
 
-use crate::chain_sync::SyncState;
+use crate::chain::store::consensus_fault::ConsensusFault;
+use crate::chain_sync::{checkpoints, Checkpoint};
 use crate::lotus_json::LotusJson;
 use crate::rpc_api::data_types::{RPCState, RPCSyncState};
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
 use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
-use nonempty::nonempty;
-use parking_lot::RwLock;
 
 /// Checks if a given block is marked as bad.
 pub(in crate::rpc) async fn sync_check_bad<DB: Blockstore>(
@@ -19,6 +18,14 @@ pub(in crate::rpc) async fn sync_check_bad<DB: Blockstore>(
     Ok(data.bad_blocks.peek(&cid).unwrap_or_default())
 }
 
+/// Returns the consensus faults detected in the validated blocks, oldest
+/// first.
+pub(in crate::rpc) async fn sync_consensus_faults<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+) -> Result<LotusJson<Vec<ConsensusFault>>, JsonRpcError> {
+    Ok(data.chain_store.consensus_faults().faults().into())
+}
+
 /// Marks a block as bad, meaning it will never be synced.
 pub(in crate::rpc) async fn sync_mark_bad<DB: Blockstore>(
     data: Data<RPCState<DB>>,
@@ -29,16 +36,49 @@ pub(in crate::rpc) async fn sync_mark_bad<DB: Blockstore>(
     Ok(())
 }
 
-async fn clone_state(state: &RwLock<SyncState>) -> SyncState {
-    state.read().clone()
+/// Unmarks a bad block, so that it can be synced again.
+pub(in crate::rpc) async fn sync_unmark_bad<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((cid,))): Params<LotusJson<(Cid,)>>,
+) -> Result<(), JsonRpcError> {
+    data.bad_blocks.remove(&cid);
+    Ok(())
+}
+
+/// Unmarks all the bad blocks, so that they can be synced again.
+pub(in crate::rpc) async fn sync_unmark_all_bad<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+) -> Result<(), JsonRpcError> {
+    data.bad_blocks.clear();
+    Ok(())
 }
 
-/// Returns the current status of the `ChainSync` process.
+/// Sets the known-good tipset whose ancestors are synced without verifying
+/// their signatures and election proofs, in place of the configured ones.
+pub(in crate::rpc) async fn sync_set_checkpoint<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((checkpoint,))): Params<LotusJson<(Checkpoint,)>>,
+) -> Result<(), JsonRpcError> {
+    checkpoints::save_checkpoint(data.chain_store.settings().as_ref(), &checkpoint)?;
+    Ok(())
+}
+
+/// Returns the current status of the `ChainSync` workers.
 pub(in crate::rpc) async fn sync_state<DB: Blockstore>(
     data: Data<RPCState<DB>>,
 ) -> Result<RPCSyncState, JsonRpcError> {
-    let active_syncs = nonempty![clone_state(data.sync_state.as_ref()).await];
-    Ok(RPCSyncState { active_syncs })
+    Ok(RPCSyncState {
+        active_syncs: data.sync_workers.states(),
+        vm_applied: crate::interpreter::applied_messages(),
+    })
+}
+
+/// Streams the states of the `ChainSync` workers as they change stage, over
+/// WebSocket. Served by the WebSocket handler.
+pub(in crate::rpc) async fn sync_state_notify<DB: Blockstore>(
+    _data: Data<RPCState<DB>>,
+) -> Result<(), JsonRpcError> {
+    Err(JsonRpcError::METHOD_NOT_FOUND)
 }
 
 #[cfg(test)]
@@ -50,7 +90,7 @@ mod tests {
     use crate::blocks::{CachingBlockHeader, Tipset};
     use crate::chain::ChainStore;
     use crate::chain_sync::{SyncConfig, SyncStage};
-    use crate::db::MemoryDB;
+    use crate::db::{car::ManyCar, MemoryDB};
     use crate::key_management::{KeyStore, KeyStoreConfig};
     use crate::libp2p::NetworkMessage;
     use crate::message_pool::{MessagePool, MpoolRpcProvider};
@@ -66,7 +106,7 @@ mod tests {
 
     const TEST_NET_NAME: &str = "test";
 
-    fn state_setup() -> (Arc<RPCState<MemoryDB>>, flume::Receiver<NetworkMessage>) {
+    async fn state_setup() -> (Arc<RPCState<MemoryDB>>, flume::Receiver<NetworkMessage>) {
         let beacon = Arc::new(BeaconSchedule(vec![BeaconPoint {
             height: 0,
             beacon: Box::<MockBeacon>::default(),
@@ -119,6 +159,7 @@ mod tests {
                 state_manager_for_thread.chain_config().clone(),
                 &mut services,
             )
+            .await
             .unwrap()
         };
         let start_time = chrono::Utc::now();
@@ -128,11 +169,16 @@ mod tests {
             keystore: Arc::new(RwLock::new(KeyStore::new(KeyStoreConfig::Memory).unwrap())),
             mpool: Arc::new(pool),
             bad_blocks: Default::default(),
-            sync_state: Arc::new(parking_lot::RwLock::new(Default::default())),
+            sync_workers: Default::default(),
             network_send,
+            gc_requests: flume::bounded(1).0,
+            config_reload_requests: flume::bounded(1).0,
+            fee_config: Default::default(),
+            export_dir: None,
             network_name: TEST_NET_NAME.to_owned(),
             start_time,
             chain_store: cs_for_chain.clone(),
+            car_files: Arc::new(ManyCar::<MemoryDB>::default()),
             beacon,
         });
         (state, network_rx)
@@ -140,7 +186,7 @@ mod tests {
 
     #[tokio::test]
     async fn set_check_bad() {
-        let (state, _) = state_setup();
+        let (state, _) = state_setup().await;
 
         let cid = from_str::<LotusJson<Cid>>(
             r#"{"/":"bafy2bzacea3wsdh6y3a36tb3skempjoxqpuyompjbmfeyf34fi3uy6uue42v4"}"#,
@@ -158,36 +204,42 @@ mod tests {
                 .await
                 .is_ok()
         );
-        match sync_check_bad(Data(state), Params(LotusJson((cid,)))).await {
+        match sync_check_bad(Data(state.clone()), Params(LotusJson((cid,)))).await {
             Ok(reason) => assert_eq!(reason, "Marked bad manually through RPC API"),
             Err(e) => std::panic::panic_any(e),
         }
+
+        // Unmark it and check that it isn't bad anymore
+        assert!(
+            sync_unmark_bad(Data(state.clone()), Params(LotusJson((cid,))))
+                .await
+                .is_ok()
+        );
+        match sync_check_bad(Data(state), Params(LotusJson((cid,)))).await {
+            Ok(reason) => assert_eq!(reason, ""),
+            Err(e) => std::panic::panic_any(e),
+        }
     }
 
     #[tokio::test]
     async fn sync_state_test() {
-        let (state, _) = state_setup();
+        let (state, _) = state_setup().await;
 
-        let st_copy = state.sync_state.clone();
+        let workers = state.sync_workers.clone();
 
         match sync_state(Data(state.clone())).await {
-            Ok(ret) => assert_eq!(
-                ret.active_syncs,
-                nonempty![clone_state(st_copy.as_ref()).await]
-            ),
+            Ok(ret) => assert_eq!(ret.active_syncs, workers.states()),
             Err(e) => std::panic::panic_any(e),
         }
 
         // update cloned state
-        st_copy.write().set_stage(SyncStage::Messages);
-        st_copy.write().set_epoch(4);
+        workers.main().write().set_stage(SyncStage::Messages);
+        workers.main().write().set_epoch(4);
 
         match sync_state(Data(state.clone())).await {
             Ok(ret) => {
-                assert_eq!(
-                    ret.active_syncs,
-                    nonempty![clone_state(st_copy.as_ref()).await]
-                );
+                assert_eq!(ret.active_syncs, workers.states());
+                assert_eq!(ret.active_syncs.first().stage(), SyncStage::Messages);
             }
             Err(e) => std::panic::panic_any(e),
         }
@@ -5,11 +5,13 @@ use std::{convert::TryFrom, str::FromStr};
 
 use crate::key_management::{Error, Key, KeyInfo};
 use crate::lotus_json::LotusJson;
+use crate::message::{signing_bytes, SignedMessage};
 use crate::rpc_api::data_types::RPCState;
 use crate::shim::{
     address::Address,
     crypto::{Signature, SignatureType},
     econ::TokenAmount,
+    message::Message,
     state_tree::StateTree,
 };
 use base64::{prelude::BASE64_STANDARD, Engine};
@@ -155,10 +157,62 @@ pub(in crate::rpc) async fn wallet_sign<DB>(
 where
     DB: Blockstore + Send + Sync + 'static,
 {
-    let state_manager = &data.state_manager;
+    let key = find_signing_key(&data, &address).await?;
+    let sig = crate::key_management::sign(
+        *key.key_info.key_type(),
+        key.key_info.private_key(),
+        &BASE64_STANDARD.decode(msg_string)?,
+    )?;
+
+    Ok(sig.into())
+}
+
+/// Sign an unsigned message with the key of its sender
+pub(in crate::rpc) async fn wallet_sign_message<DB>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((address, message))): Params<LotusJson<(Address, Message)>>,
+) -> Result<LotusJson<SignedMessage>, JsonRpcError>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    let key = find_signing_key(&data, &address).await?;
+    // The signature has to be verifiable against the sender of the message.
+    if message.from != address {
+        let heaviest_tipset = data.state_manager.chain_store().heaviest_tipset();
+        let from = data
+            .state_manager
+            .resolve_to_key_addr(&message.from, &heaviest_tipset)
+            .await?;
+        if from != key.address {
+            return Err(format!(
+                "cannot sign a message from {} with the key of {address}",
+                message.from
+            )
+            .into());
+        }
+    }
+    let sig = crate::key_management::sign(
+        *key.key_info.key_type(),
+        key.key_info.private_key(),
+        &signing_bytes(
+            &message,
+            *key.key_info.key_type(),
+            data.state_manager.chain_config().eth_chain_id as u64,
+        )?,
+    )?;
+
+    Ok(SignedMessage::new_unchecked(message, sig).into())
+}
+
+/// Resolve `address` to its key address and look up the matching key
+async fn find_signing_key<DB>(data: &RPCState<DB>, address: &Address) -> Result<Key, JsonRpcError>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
     let heaviest_tipset = data.state_manager.chain_store().heaviest_tipset();
-    let key_addr = state_manager
-        .resolve_to_key_addr(&address, &heaviest_tipset)
+    let key_addr = data
+        .state_manager
+        .resolve_to_key_addr(address, &heaviest_tipset)
         .await?;
     let keystore = &mut *data.keystore.write().await;
     let key = match crate::key_management::find_key(&key_addr, keystore) {
@@ -168,14 +222,7 @@ where
             Key::try_from(key_info)?
         }
     };
-
-    let sig = crate::key_management::sign(
-        *key.key_info.key_type(),
-        key.key_info.private_key(),
-        &BASE64_STANDARD.decode(msg_string)?,
-    )?;
-
-    Ok(sig.into())
+    Ok(key)
 }
 
 /// Verify a Signature, true if verified, false otherwise
@@ -1,19 +1,23 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
 
 use crate::beacon::{BeaconEntry, BeaconSchedule};
-use crate::blocks::TipsetKey;
+use crate::blocks::{ElectionProof, Ticket, TipsetKey};
 use crate::chain::ChainStore;
-use crate::chain_sync::{BadBlockCache, SyncState};
+use crate::chain_sync::{BadBlockCache, SyncState, SyncWorkers};
+use crate::daemon::config_reload::ConfigReloadRequest;
+use crate::db::car::ReadOnlyCarFiles;
+use crate::db::GcRequest;
 use crate::key_management::KeyStore;
 pub use crate::libp2p::Multiaddr;
-use crate::libp2p::{Multihash, NetworkMessage};
+use crate::libp2p::{Multihash, NetworkMessage, PeerQuarantine, PeerScore};
 use crate::lotus_json::{lotus_json_with_self, HasLotusJson, LotusJson};
 use crate::message::signed_message::SignedMessage;
-use crate::message_pool::{MessagePool, MpoolRpcProvider};
+use crate::message_pool::{FeeConfig, MessagePool, MpoolRpcProvider};
 use crate::shim::sector::SectorInfo;
 use crate::shim::{
     address::Address,
@@ -24,11 +28,11 @@ use crate::shim::{
     executor::Receipt,
     fvm_shared_latest::MethodNum,
     message::Message,
-    sector::{RegisteredSealProof, SectorNumber},
+    sector::{PoStProof, RegisteredSealProof, SectorNumber},
     state_tree::ActorState,
 };
 use crate::state_manager::StateManager;
-use ahash::HashSet;
+use ahash::{HashMap, HashSet};
 use chrono::Utc;
 use cid::Cid;
 use fil_actor_interface::miner::MinerInfo;
@@ -46,7 +50,6 @@ use libipld_core::ipld::Ipld;
 use libp2p::PeerId;
 use nonempty::NonEmpty;
 use num_bigint::BigInt;
-use parking_lot::RwLock as SyncRwLock;
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::Value;
 use tokio::sync::RwLock;
@@ -59,11 +62,21 @@ where
 {
     pub keystore: Arc<RwLock<KeyStore>>,
     pub chain_store: Arc<ChainStore<DB>>,
+    /// Read-only CAR files of the blockstore of `chain_store`.
+    pub car_files: Arc<dyn ReadOnlyCarFiles>,
     pub state_manager: Arc<StateManager<DB>>,
     pub mpool: Arc<MessagePool<MpoolRpcProvider<DB>>>,
     pub bad_blocks: Arc<BadBlockCache>,
-    pub sync_state: Arc<SyncRwLock<SyncState>>,
+    pub sync_workers: SyncWorkers,
     pub network_send: flume::Sender<NetworkMessage>,
+    /// Requests for immediate garbage collection runs.
+    pub gc_requests: flume::Sender<GcRequest>,
+    /// Requests for configuration reloads.
+    pub config_reload_requests: flume::Sender<ConfigReloadRequest>,
+    /// Caps of the fees of the messages whose gas is estimated, updated by configuration reloads.
+    pub fee_config: Arc<parking_lot::RwLock<FeeConfig>>,
+    /// Directory the exports to files are restricted to, if any.
+    pub export_dir: Option<PathBuf>,
     pub network_name: String,
     pub start_time: chrono::DateTime<Utc>,
     pub beacon: Arc<BeaconSchedule>,
@@ -74,10 +87,50 @@ where
 pub struct RPCSyncState {
     #[serde(with = "crate::lotus_json")]
     pub active_syncs: NonEmpty<SyncState>,
+    /// Number of messages applied since the node started.
+    #[serde(rename = "VMApplied", default)]
+    pub vm_applied: u64,
 }
 
 lotus_json_with_self!(RPCSyncState);
 
+/// Value an event entry is matched against.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActorEventBlock {
+    pub codec: u64,
+    #[serde(with = "crate::lotus_json")]
+    pub value: Vec<u8>,
+}
+
+/// Criteria the actor events are selected with. The events of the current
+/// head are selected by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActorEventFilter {
+    /// Addresses of the emitters, any if empty.
+    #[serde(default, with = "crate::lotus_json")]
+    pub addresses: Vec<Address>,
+    /// Values of the entries of the events, by key. Events match if, for
+    /// every key, they have an entry with one of the values, or any value if
+    /// there are none.
+    #[serde(default)]
+    pub fields: HashMap<String, Vec<ActorEventBlock>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from_height: Option<ChainEpoch>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_height: Option<ChainEpoch>,
+    /// Tipset whose events are selected, in place of a range of epochs.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::lotus_json"
+    )]
+    pub tipset_key: Option<TipsetKey>,
+}
+
+lotus_json_with_self!(ActorEventBlock, ActorEventFilter);
+
 pub type JsonRpcServerState = Arc<JsonRpcServer<JsonRpcMapRouter>>;
 
 // Chain API
@@ -97,7 +150,7 @@ lotus_json_with_self!(BlockMessages);
 #[serde(rename_all = "PascalCase")]
 pub struct MessageSendSpec {
     #[serde(with = "crate::lotus_json")]
-    max_fee: TokenAmount,
+    pub max_fee: TokenAmount,
 }
 
 lotus_json_with_self!(MessageSendSpec);
@@ -132,10 +185,101 @@ pub struct AddrInfo {
     #[serde(rename = "ID")]
     pub id: String,
     pub addrs: HashSet<Multiaddr>,
+    /// Chain exchange quarantine details of the peer, only set by `NetPeers`
+    /// for peers that served invalid responses recently. Forest-specific.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quarantine: Option<PeerQuarantineInfo>,
 }
 
 lotus_json_with_self!(AddrInfo);
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PeerQuarantineInfo {
+    /// Invalid chain exchange responses served by the peer, decayed over time.
+    pub penalty: f64,
+    /// Seconds left before the peer is sent chain exchange requests again,
+    /// zero if it isn't quarantined.
+    pub remaining_secs: u64,
+}
+
+impl From<PeerQuarantine> for PeerQuarantineInfo {
+    fn from(quarantine: PeerQuarantine) -> Self {
+        Self {
+            penalty: quarantine.penalty,
+            remaining_secs: quarantine
+                .remaining
+                .map(|remaining| remaining.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Details of a peer, returned by `NetPeerInfo`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ExtendedPeerInfo {
+    #[serde(rename = "ID")]
+    pub id: String,
+    pub addrs: HashSet<Multiaddr>,
+    /// Chain exchange statistics and score of the peer, if it is tracked by
+    /// the peer manager. Forest-specific.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<PeerScoreInfo>,
+    /// Chain exchange quarantine details of the peer. Forest-specific.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quarantine: Option<PeerQuarantineInfo>,
+}
+
+lotus_json_with_self!(ExtendedPeerInfo);
+
+/// `Gossipsub` score of a peer, returned by `NetPubsubScores`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PubsubScore {
+    #[serde(rename = "ID")]
+    pub id: String,
+    pub score: PubsubPeerScore,
+}
+
+lotus_json_with_self!(PubsubScore);
+
+/// Only the overall score is tracked, the per-topic and penalty breakdown of
+/// Lotus isn't available.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PubsubPeerScore {
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PeerScoreInfo {
+    /// Number of successful chain exchange requests.
+    pub successes: u32,
+    /// Number of failed chain exchange requests.
+    pub failures: u32,
+    /// Average response time of the peer, in milliseconds.
+    pub average_latency_ms: u64,
+    /// Size of the chain exchange responses served by the peer.
+    pub served_bytes: u64,
+    /// Cost of sending requests to the peer, the peers with the lowest costs
+    /// are preferred.
+    pub cost: f64,
+}
+
+impl From<PeerScore> for PeerScoreInfo {
+    fn from(score: PeerScore) -> Self {
+        Self {
+            successes: score.successes,
+            failures: score.failures,
+            average_latency_ms: score.average_time.as_millis() as u64,
+            served_bytes: score.served_bytes,
+            cost: score.cost,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct PeerID {
     pub multihash: Multihash,
@@ -174,6 +318,10 @@ impl ApiMessage {
     pub fn new(cid: Cid, message: Message) -> Self {
         Self { cid, message }
     }
+
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
 }
 
 #[derive(Serialize, Deserialize)]
@@ -449,6 +597,32 @@ pub struct MiningBaseInfo {
 
 lotus_json_with_self!(MiningBaseInfo);
 
+/// Block assembled and signed by the node for external mining software.
+// Note: kept the name in line with Lotus implementation for cross-referencing simplicity.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BlockTemplate {
+    #[serde(with = "crate::lotus_json")]
+    pub miner: Address,
+    #[serde(with = "crate::lotus_json")]
+    pub parents: TipsetKey,
+    #[serde(with = "crate::lotus_json")]
+    pub ticket: Option<Ticket>,
+    #[serde(with = "crate::lotus_json")]
+    pub eproof: Option<ElectionProof>,
+    #[serde(with = "crate::lotus_json")]
+    pub beacon_values: Vec<BeaconEntry>,
+    #[serde(with = "crate::lotus_json")]
+    pub messages: Vec<SignedMessage>,
+    pub epoch: ChainEpoch,
+    pub timestamp: u64,
+    #[serde(rename = "WinningPoStProof")]
+    #[serde(with = "crate::lotus_json")]
+    pub winning_post_proof: Vec<PoStProof>,
+}
+
+lotus_json_with_self!(BlockTemplate);
+
 impl HasLotusJson for MinerPower {
     type LotusJson = MinerPowerLotusJson;
     fn snapshots() -> Vec<(serde_json::Value, Self)> {
@@ -669,6 +843,98 @@ impl From<fil_actor_interface::miner::SectorOnChainInfo> for SectorOnChainInfo {
 
 lotus_json_with_self!(SectorOnChainInfo);
 
+/// Information a storage provider submits to pre-commit a sector.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct SectorPreCommitInfo {
+    pub seal_proof: RegisteredSealProof,
+
+    pub sector_number: SectorNumber,
+
+    #[serde(with = "crate::lotus_json")]
+    #[serde(rename = "SealedCID")]
+    /// `CommR`
+    pub sealed_cid: Cid,
+
+    pub seal_rand_epoch: ChainEpoch,
+
+    #[serde(rename = "DealIDs")]
+    #[serde(with = "crate::lotus_json")]
+    pub deal_ids: Vec<DealID>,
+
+    /// Epoch during which the sector expires
+    pub expiration: ChainEpoch,
+
+    #[serde(with = "crate::lotus_json")]
+    /// `CommD`, absent for sectors without deals
+    pub unsealed_cid: Option<Cid>,
+}
+
+lotus_json_with_self!(SectorPreCommitInfo);
+
+/// Identifies a sector of a miner.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct SectorId {
+    /// Actor ID of the miner
+    pub miner: u64,
+    pub number: SectorNumber,
+}
+
+/// Information needed to verify the seal proof of a sector.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "PascalCase")]
+pub struct SealVerifyInfo {
+    pub seal_proof: RegisteredSealProof,
+
+    #[serde(rename = "SectorID")]
+    pub sector_id: SectorId,
+
+    #[serde(rename = "DealIDs")]
+    #[serde(with = "crate::lotus_json")]
+    pub deal_ids: Vec<DealID>,
+
+    #[serde(with = "crate::lotus_json")]
+    pub randomness: Vec<u8>,
+
+    #[serde(with = "crate::lotus_json")]
+    pub interactive_randomness: Vec<u8>,
+
+    #[serde(with = "crate::lotus_json")]
+    pub proof: Vec<u8>,
+
+    #[serde(with = "crate::lotus_json")]
+    #[serde(rename = "SealedCID")]
+    /// `CommR`
+    pub sealed_cid: Cid,
+
+    #[serde(with = "crate::lotus_json")]
+    #[serde(rename = "UnsealedCID")]
+    /// `CommD`
+    pub unsealed_cid: Cid,
+}
+
+lotus_json_with_self!(SealVerifyInfo);
+
+/// Information needed to verify the window `PoSt` proofs of a deadline partition.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct WindowPoStVerifyInfo {
+    #[serde(with = "crate::lotus_json")]
+    pub randomness: Vec<u8>,
+
+    #[serde(with = "crate::lotus_json")]
+    pub proofs: Vec<PoStProof>,
+
+    #[serde(with = "crate::lotus_json")]
+    pub challenged_sectors: Vec<SectorInfo>,
+
+    /// Actor ID of the miner
+    pub prover: u64,
+}
+
+lotus_json_with_self!(WindowPoStVerifyInfo);
+
 #[derive(Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "PascalCase")]
 pub struct ApiDeadline {
@@ -679,7 +945,7 @@ pub struct ApiDeadline {
 }
 
 lotus_json_with_self!(ApiDeadline);
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct ApiInvocResult {
     #[serde(with = "crate::lotus_json")]
@@ -825,6 +1091,18 @@ pub struct CirculatingSupply {
 
 lotus_json_with_self!(CirculatingSupply);
 
+/// Collateral a storage provider can lock for a deal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DealCollateralBounds {
+    #[serde(with = "crate::lotus_json")]
+    pub min: TokenAmount,
+    #[serde(with = "crate::lotus_json")]
+    pub max: TokenAmount,
+}
+
+lotus_json_with_self!(DealCollateralBounds);
+
 #[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "PascalCase")]
 pub struct MinerSectors {
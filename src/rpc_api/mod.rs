@@ -37,6 +37,7 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     // Chain API
     access.insert(chain_api::CHAIN_GET_MESSAGE, Access::Read);
     access.insert(chain_api::CHAIN_EXPORT, Access::Read);
+    access.insert(chain_api::CHAIN_EXPORT_RANGE, Access::Admin);
     access.insert(chain_api::CHAIN_READ_OBJ, Access::Read);
     access.insert(chain_api::CHAIN_HAS_OBJ, Access::Read);
     access.insert(chain_api::CHAIN_GET_BLOCK_MESSAGES, Access::Read);
@@ -46,22 +47,36 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     access.insert(chain_api::CHAIN_GET_BLOCK, Access::Read);
     access.insert(chain_api::CHAIN_GET_TIPSET, Access::Read);
     access.insert(chain_api::CHAIN_SET_HEAD, Access::Admin);
+    access.insert(chain_api::CHAIN_GET_PATH, Access::Read);
     access.insert(chain_api::CHAIN_GET_MIN_BASE_FEE, Access::Admin);
+    access.insert(chain_api::CHAIN_COMPUTE_BASE_FEE, Access::Read);
     access.insert(chain_api::CHAIN_GET_MESSAGES_IN_TIPSET, Access::Read);
     access.insert(chain_api::CHAIN_GET_PARENT_MESSAGES, Access::Read);
     access.insert(chain_api::CHAIN_NOTIFY, Access::Read);
     access.insert(chain_api::CHAIN_GET_PARENT_RECEIPTS, Access::Read);
+    access.insert(chain_api::CHAIN_ADD_CAR, Access::Admin);
+    access.insert(chain_api::CHAIN_REMOVE_CAR, Access::Admin);
+    access.insert(chain_api::CHAIN_LIST_CARS, Access::Read);
+    access.insert(chain_api::CHAIN_GET_REORGS, Access::Read);
 
     // Message Pool API
     access.insert(mpool_api::MPOOL_GET_NONCE, Access::Read);
     access.insert(mpool_api::MPOOL_PENDING, Access::Read);
     access.insert(mpool_api::MPOOL_PUSH, Access::Write);
     access.insert(mpool_api::MPOOL_PUSH_MESSAGE, Access::Sign);
+    access.insert(mpool_api::MPOOL_BATCH_PUSH, Access::Write);
+    access.insert(mpool_api::MPOOL_BATCH_PUSH_MESSAGE, Access::Sign);
+    access.insert(mpool_api::MPOOL_SELECT, Access::Read);
 
     // Sync API
     access.insert(sync_api::SYNC_CHECK_BAD, Access::Read);
+    access.insert(sync_api::SYNC_CONSENSUS_FAULTS, Access::Read);
     access.insert(sync_api::SYNC_MARK_BAD, Access::Admin);
+    access.insert(sync_api::SYNC_UNMARK_BAD, Access::Admin);
+    access.insert(sync_api::SYNC_UNMARK_ALL_BAD, Access::Admin);
+    access.insert(sync_api::SYNC_SET_CHECKPOINT, Access::Admin);
     access.insert(sync_api::SYNC_STATE, Access::Read);
+    access.insert(sync_api::SYNC_STATE_NOTIFY, Access::Read);
 
     // Wallet API
     access.insert(wallet_api::WALLET_BALANCE, Access::Write);
@@ -74,6 +89,7 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     access.insert(wallet_api::WALLET_NEW, Access::Write);
     access.insert(wallet_api::WALLET_SET_DEFAULT, Access::Write);
     access.insert(wallet_api::WALLET_SIGN, Access::Sign);
+    access.insert(wallet_api::WALLET_SIGN_MESSAGE, Access::Sign);
     access.insert(wallet_api::WALLET_VERIFY, Access::Read);
     access.insert(wallet_api::WALLET_DELETE, Access::Write);
 
@@ -85,18 +101,40 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     access.insert(state_api::STATE_MARKET_DEALS, Access::Read);
     access.insert(state_api::STATE_MINER_INFO, Access::Read);
     access.insert(state_api::MINER_GET_BASE_INFO, Access::Read);
+    access.insert(state_api::MINER_CREATE_BLOCK, Access::Write);
     access.insert(state_api::STATE_MINER_ACTIVE_SECTORS, Access::Read);
     access.insert(state_api::STATE_MINER_FAULTS, Access::Read);
     access.insert(state_api::STATE_MINER_RECOVERIES, Access::Read);
     access.insert(state_api::STATE_MINER_POWER, Access::Read);
     access.insert(state_api::STATE_MINER_DEADLINES, Access::Read);
     access.insert(state_api::STATE_MINER_PROVING_DEADLINE, Access::Read);
+    access.insert(
+        state_api::STATE_MINER_INITIAL_PLEDGE_COLLATERAL,
+        Access::Read,
+    );
+    access.insert(
+        state_api::STATE_MINER_PRE_COMMIT_DEPOSIT_FOR_POWER,
+        Access::Read,
+    );
+    access.insert(state_api::STATE_MINER_AVAILABLE_BALANCE, Access::Read);
+    access.insert(state_api::STATE_MINER_SECTOR_ALLOCATED, Access::Read);
+    access.insert(state_api::STATE_VERIFY_SEALS, Access::Read);
+    access.insert(state_api::STATE_VERIFY_WINDOW_POST, Access::Read);
+    access.insert(
+        state_api::STATE_DEAL_PROVIDER_COLLATERAL_BOUNDS,
+        Access::Read,
+    );
     access.insert(state_api::STATE_GET_RECEIPT, Access::Read);
     access.insert(state_api::STATE_WAIT_MSG, Access::Read);
+    access.insert(state_api::STATE_SUBSCRIBE_ACTOR_CHANGES, Access::Read);
+    access.insert(state_api::STATE_NEXT_ACTOR_CHANGES, Access::Read);
+    access.insert(state_api::STATE_UNSUBSCRIBE_ACTOR_CHANGES, Access::Read);
     access.insert(state_api::STATE_SEARCH_MSG, Access::Read);
     access.insert(state_api::STATE_SEARCH_MSG_LIMITED, Access::Read);
     access.insert(state_api::STATE_NETWORK_NAME, Access::Read);
     access.insert(state_api::STATE_NETWORK_VERSION, Access::Read);
+    access.insert(state_api::STATE_ACTOR_CODE_CIDS, Access::Read);
+    access.insert(state_api::STATE_ACTOR_MANIFEST_CID, Access::Read);
     access.insert(state_api::STATE_ACCOUNT_KEY, Access::Read);
     access.insert(state_api::STATE_LOOKUP_ID, Access::Read);
     access.insert(state_api::STATE_FETCH_ROOT, Access::Read);
@@ -105,6 +143,7 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     access.insert(state_api::STATE_READ_STATE, Access::Read);
     access.insert(state_api::STATE_CIRCULATING_SUPPLY, Access::Read);
     access.insert(state_api::STATE_SECTOR_GET_INFO, Access::Read);
+    access.insert(state_api::STATE_MINER_SECTORS, Access::Read);
     access.insert(state_api::STATE_LIST_MINERS, Access::Read);
     access.insert(state_api::STATE_MINER_SECTOR_COUNT, Access::Read);
     access.insert(state_api::STATE_VERIFIED_CLIENT_STATUS, Access::Read);
@@ -114,6 +153,7 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     );
     access.insert(state_api::MSIG_GET_AVAILABLE_BALANCE, Access::Read);
     access.insert(state_api::MSIG_GET_PENDING, Access::Read);
+    access.insert(state_api::STATE_MIGRATION_PROGRESS, Access::Read);
 
     // Gas API
     access.insert(gas_api::GAS_ESTIMATE_GAS_LIMIT, Access::Read);
@@ -125,24 +165,58 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     access.insert(common_api::VERSION, Access::Read);
     access.insert(common_api::SESSION, Access::Read);
     access.insert(common_api::SHUTDOWN, Access::Admin);
+    access.insert(common_api::RESTART, Access::Admin);
     access.insert(common_api::START_TIME, Access::Read);
+    access.insert(common_api::CONFIG_RELOAD, Access::Admin);
+    access.insert(common_api::LOG_LIST, Access::Admin);
+    access.insert(common_api::LOG_SET_LEVEL, Access::Admin);
 
     // Net API
     access.insert(net_api::NET_ADDRS_LISTEN, Access::Read);
     access.insert(net_api::NET_PEERS, Access::Read);
+    access.insert(net_api::NET_PEER_INFO, Access::Read);
     access.insert(net_api::NET_INFO, Access::Read);
     access.insert(net_api::NET_CONNECT, Access::Write);
     access.insert(net_api::NET_DISCONNECT, Access::Write);
+    access.insert(net_api::NET_PROTECT_ADD, Access::Admin);
+    access.insert(net_api::NET_PROTECT_REMOVE, Access::Admin);
+    access.insert(net_api::NET_PROTECT_LIST, Access::Read);
+    access.insert(net_api::NET_BLOCK_ADD, Access::Admin);
+    access.insert(net_api::NET_BLOCK_REMOVE, Access::Admin);
+    access.insert(net_api::NET_BLOCK_LIST, Access::Read);
+    access.insert(net_api::NET_PUBSUB_SCORES, Access::Read);
+    access.insert(net_api::NET_DISCOVER, Access::Write);
 
     // Node API
     access.insert(node_api::NODE_STATUS, Access::Read);
 
+    // Database API
+    access.insert(db_api::DATABASE_GARBAGE_COLLECT, Access::Admin);
+
+    // Actor events API
+    access.insert(actor_events_api::GET_ACTOR_EVENTS_RAW, Access::Read);
+    access.insert(actor_events_api::SUBSCRIBE_ACTOR_EVENTS_RAW, Access::Read);
+
     // Eth API
-    access.insert(eth_api::ETH_ACCOUNTS, Access::Read);
-    access.insert(eth_api::ETH_BLOCK_NUMBER, Access::Read);
-    access.insert(eth_api::ETH_CHAIN_ID, Access::Read);
-    access.insert(eth_api::ETH_GAS_PRICE, Access::Read);
-    access.insert(eth_api::ETH_GET_BALANCE, Access::Read);
+    #[cfg(feature = "eth-api")]
+    {
+        access.insert(eth_api::ETH_ACCOUNTS, Access::Read);
+        access.insert(eth_api::ETH_BLOCK_NUMBER, Access::Read);
+        access.insert(eth_api::ETH_CHAIN_ID, Access::Read);
+        access.insert(eth_api::ETH_FEE_HISTORY, Access::Read);
+        access.insert(eth_api::ETH_GAS_PRICE, Access::Read);
+        access.insert(eth_api::ETH_GET_BALANCE, Access::Read);
+        access.insert(
+            eth_api::ETH_GET_BLOCK_TRANSACTION_COUNT_BY_HASH,
+            Access::Read,
+        );
+        access.insert(
+            eth_api::ETH_GET_BLOCK_TRANSACTION_COUNT_BY_NUMBER,
+            Access::Read,
+        );
+        access.insert(eth_api::ETH_GET_UNCLE_COUNT_BY_BLOCK_HASH, Access::Read);
+        access.insert(eth_api::ETH_SEND_RAW_TRANSACTION, Access::Read);
+    }
     access
 });
 
@@ -173,6 +247,9 @@ pub mod auth_api {
         pub perms: Vec<String>,
         #[serde_as(as = "DurationSeconds<i64>")]
         pub token_exp: Duration,
+        /// Name of the holder of the token, identifying its requests in logs and metrics.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pub label: Option<String>,
     }
     lotus_json_with_self!(AuthNewParams);
 
@@ -188,8 +265,8 @@ pub mod beacon_api {
 pub mod chain_api {
     use std::path::PathBuf;
 
-    use crate::blocks::TipsetKey;
-    use crate::lotus_json::lotus_json_with_self;
+    use crate::blocks::{Tipset, TipsetKey};
+    use crate::lotus_json::{lotus_json_with_self, LotusJson};
     use crate::shim::clock::ChainEpoch;
     use serde::{Deserialize, Serialize};
 
@@ -206,12 +283,42 @@ pub mod chain_api {
         pub tipset_keys: TipsetKey,
         pub skip_checksum: bool,
         pub dry_run: bool,
+        /// Base snapshot. Blocks it contains are left out of the export.
+        #[serde(default)]
+        pub diff: Option<PathBuf>,
+        /// Also export the messages of the tipsets older than `recent_roots`,
+        /// and the message receipts of every tipset.
+        #[serde(default)]
+        pub include_old_messages: bool,
     }
 
     lotus_json_with_self!(ChainExportParams);
 
     pub type ChainExportResult = Option<String>;
 
+    /// Lotus parameters of [`CHAIN_EXPORT`]: the number of recent state roots,
+    /// whether to skip the messages and receipts of older tipsets, and the
+    /// tipset to export. Such calls are answered over HTTP with the snapshot
+    /// itself, streamed as it is exported.
+    pub type ChainExportStreamParams = (ChainEpoch, bool, TipsetKey);
+
+    pub const CHAIN_EXPORT_RANGE: &str = "Filecoin.ChainExportRange";
+
+    /// Exports headers and messages, but no state, of the tipsets in
+    /// `from..=to`.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ChainExportRangeParams {
+        pub from: ChainEpoch,
+        pub to: ChainEpoch,
+        pub output_path: PathBuf,
+        #[serde(with = "crate::lotus_json")]
+        pub tipset_keys: TipsetKey,
+        pub skip_checksum: bool,
+        pub dry_run: bool,
+    }
+
+    lotus_json_with_self!(ChainExportRangeParams);
+
     pub const CHAIN_READ_OBJ: &str = "Filecoin.ChainReadObj";
     pub const CHAIN_HAS_OBJ: &str = "Filecoin.ChainHasObj";
     pub const CHAIN_GET_BLOCK_MESSAGES: &str = "Filecoin.ChainGetBlockMessages";
@@ -221,11 +328,65 @@ pub mod chain_api {
     pub const CHAIN_GET_BLOCK: &str = "Filecoin.ChainGetBlock";
     pub const CHAIN_GET_TIPSET: &str = "Filecoin.ChainGetTipSet";
     pub const CHAIN_SET_HEAD: &str = "Filecoin.ChainSetHead";
+    pub const CHAIN_GET_PATH: &str = "Filecoin.ChainGetPath";
+
+    /// A step of the path returned by [`CHAIN_GET_PATH`].
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    #[serde(tag = "Type", content = "Val", rename_all = "lowercase")]
+    pub enum PathChange {
+        Revert(#[serde(with = "crate::lotus_json")] Tipset),
+        Apply(#[serde(with = "crate::lotus_json")] Tipset),
+    }
+
+    lotus_json_with_self!(PathChange);
     pub const CHAIN_GET_MIN_BASE_FEE: &str = "Filecoin.ChainGetMinBaseFee";
+    pub const CHAIN_COMPUTE_BASE_FEE: &str = "Filecoin.ChainComputeBaseFee";
     pub const CHAIN_GET_MESSAGES_IN_TIPSET: &str = "Filecoin.ChainGetMessagesInTipset";
+
+    /// Forest-specific options of [`CHAIN_GET_MESSAGES_IN_TIPSET`]. Without them, all the
+    /// messages of the tipset are returned, as in Lotus.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+    #[serde(rename_all = "PascalCase", default)]
+    pub struct MessagesInTipsetOptions {
+        /// Number of messages to skip.
+        pub offset: usize,
+        /// Maximum number of messages to return.
+        pub limit: Option<usize>,
+        /// Only return the first message of each sender and nonce, the one that is executed.
+        pub dedupe: bool,
+    }
+
+    lotus_json_with_self!(MessagesInTipsetOptions);
+
+    /// Parameters of [`CHAIN_GET_MESSAGES_IN_TIPSET`]: the tipset key, and optionally
+    /// [`MessagesInTipsetOptions`].
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    pub enum ChainGetMessagesInTipsetParams {
+        WithOptions(LotusJson<TipsetKey>, MessagesInTipsetOptions),
+        Lotus((LotusJson<TipsetKey>,)),
+    }
+
+    impl ChainGetMessagesInTipsetParams {
+        pub fn into_parts(self) -> (TipsetKey, MessagesInTipsetOptions) {
+            match self {
+                Self::WithOptions(LotusJson(tsk), options) => (tsk, options),
+                Self::Lotus((LotusJson(tsk),)) => (tsk, Default::default()),
+            }
+        }
+    }
+
     pub const CHAIN_GET_PARENT_MESSAGES: &str = "Filecoin.ChainGetParentMessages";
     pub const CHAIN_NOTIFY: &str = "Filecoin.ChainNotify";
     pub const CHAIN_GET_PARENT_RECEIPTS: &str = "Filecoin.ChainGetParentReceipts";
+
+    /// Adds a CAR file to the read-only stores of the node's blockstore.
+    pub const CHAIN_ADD_CAR: &str = "Filecoin.ChainAddCar";
+    /// Removes a CAR file from the read-only stores of the node's blockstore.
+    pub const CHAIN_REMOVE_CAR: &str = "Filecoin.ChainRemoveCar";
+    /// Lists the CAR files of the read-only stores of the node's blockstore.
+    pub const CHAIN_LIST_CARS: &str = "Filecoin.ChainListCars";
+    pub const CHAIN_GET_REORGS: &str = "Filecoin.ChainGetReorgs";
 }
 
 /// Message Pool API
@@ -234,13 +395,21 @@ pub mod mpool_api {
     pub const MPOOL_PENDING: &str = "Filecoin.MpoolPending";
     pub const MPOOL_PUSH: &str = "Filecoin.MpoolPush";
     pub const MPOOL_PUSH_MESSAGE: &str = "Filecoin.MpoolPushMessage";
+    pub const MPOOL_BATCH_PUSH: &str = "Filecoin.MpoolBatchPush";
+    pub const MPOOL_BATCH_PUSH_MESSAGE: &str = "Filecoin.MpoolBatchPushMessage";
+    pub const MPOOL_SELECT: &str = "Filecoin.MpoolSelect";
 }
 
 /// Sync API
 pub mod sync_api {
     pub const SYNC_CHECK_BAD: &str = "Filecoin.SyncCheckBad";
+    pub const SYNC_CONSENSUS_FAULTS: &str = "Filecoin.SyncConsensusFaults";
     pub const SYNC_MARK_BAD: &str = "Filecoin.SyncMarkBad";
+    pub const SYNC_SET_CHECKPOINT: &str = "Filecoin.SyncSetCheckpoint";
     pub const SYNC_STATE: &str = "Filecoin.SyncState";
+    pub const SYNC_STATE_NOTIFY: &str = "Filecoin.SyncStateNotify";
+    pub const SYNC_UNMARK_ALL_BAD: &str = "Filecoin.SyncUnmarkAllBad";
+    pub const SYNC_UNMARK_BAD: &str = "Filecoin.SyncUnmarkBad";
 }
 
 /// Wallet API
@@ -254,28 +423,80 @@ pub mod wallet_api {
     pub const WALLET_NEW: &str = "Filecoin.WalletNew";
     pub const WALLET_SET_DEFAULT: &str = "Filecoin.WalletSetDefault";
     pub const WALLET_SIGN: &str = "Filecoin.WalletSign";
+    pub const WALLET_SIGN_MESSAGE: &str = "Filecoin.WalletSignMessage";
     pub const WALLET_VERIFY: &str = "Filecoin.WalletVerify";
     pub const WALLET_DELETE: &str = "Filecoin.WalletDelete";
 }
 
 /// State API
 pub mod state_api {
+    use crate::lotus_json::LotusJson;
+    use crate::shim::clock::ChainEpoch;
+    use crate::state_manager::WaitOptions;
+    use cid::Cid;
+    use serde::Deserialize;
+
     pub const STATE_CALL: &str = "Filecoin.StateCall";
     pub const STATE_REPLAY: &str = "Filecoin.StateReplay";
     pub const STATE_NETWORK_NAME: &str = "Filecoin.StateNetworkName";
     pub const STATE_NETWORK_VERSION: &str = "Filecoin.StateNetworkVersion";
+    pub const STATE_ACTOR_CODE_CIDS: &str = "Filecoin.StateActorCodeCIDs";
+    pub const STATE_ACTOR_MANIFEST_CID: &str = "Filecoin.StateActorManifestCID";
     pub const STATE_GET_ACTOR: &str = "Filecoin.StateGetActor";
     pub const STATE_MARKET_BALANCE: &str = "Filecoin.StateMarketBalance";
     pub const STATE_MARKET_DEALS: &str = "Filecoin.StateMarketDeals";
     pub const STATE_MINER_INFO: &str = "Filecoin.StateMinerInfo";
     pub const MINER_GET_BASE_INFO: &str = "Filecoin.MinerGetBaseInfo";
+    pub const MINER_CREATE_BLOCK: &str = "Filecoin.MinerCreateBlock";
     pub const STATE_MINER_FAULTS: &str = "Filecoin.StateMinerFaults";
     pub const STATE_MINER_RECOVERIES: &str = "Filecoin.StateMinerRecoveries";
     pub const STATE_MINER_POWER: &str = "Filecoin.StateMinerPower";
     pub const STATE_MINER_DEADLINES: &str = "Filecoin.StateMinerDeadlines";
     pub const STATE_MINER_PROVING_DEADLINE: &str = "Filecoin.StateMinerProvingDeadline";
+    pub const STATE_MINER_INITIAL_PLEDGE_COLLATERAL: &str =
+        "Filecoin.StateMinerInitialPledgeCollateral";
+    pub const STATE_MINER_PRE_COMMIT_DEPOSIT_FOR_POWER: &str =
+        "Filecoin.StateMinerPreCommitDepositForPower";
+    pub const STATE_MINER_AVAILABLE_BALANCE: &str = "Filecoin.StateMinerAvailableBalance";
+    pub const STATE_MINER_SECTOR_ALLOCATED: &str = "Filecoin.StateMinerSectorAllocated";
+    pub const STATE_VERIFY_SEALS: &str = "Filecoin.StateVerifySeals";
+    pub const STATE_VERIFY_WINDOW_POST: &str = "Filecoin.StateVerifyWindowPoSt";
+    pub const STATE_DEAL_PROVIDER_COLLATERAL_BOUNDS: &str =
+        "Filecoin.StateDealProviderCollateralBounds";
     pub const STATE_GET_RECEIPT: &str = "Filecoin.StateGetReceipt";
     pub const STATE_WAIT_MSG: &str = "Filecoin.StateWaitMsg";
+
+    /// Parameters of [`STATE_WAIT_MSG`]: the message CID, the confidence, and with the Lotus v1
+    /// API, the look-back limit in epochs, `-1` for no limit, and whether replacing messages are
+    /// accepted.
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    pub enum StateWaitMsgParams {
+        V1((LotusJson<Cid>, i64, ChainEpoch, bool)),
+        V0((LotusJson<Cid>, i64)),
+    }
+
+    impl StateWaitMsgParams {
+        pub fn into_parts(self) -> (Cid, WaitOptions) {
+            match self {
+                Self::V1((LotusJson(cid), confidence, look_back_limit, allow_replaced)) => (
+                    cid,
+                    WaitOptions {
+                        confidence,
+                        look_back_limit: (look_back_limit >= 0).then_some(look_back_limit),
+                        allow_replaced,
+                    },
+                ),
+                Self::V0((LotusJson(cid), confidence)) => (
+                    cid,
+                    WaitOptions {
+                        confidence,
+                        ..Default::default()
+                    },
+                ),
+            }
+        }
+    }
     pub const STATE_FETCH_ROOT: &str = "Filecoin.StateFetchRoot";
     pub const STATE_GET_RANDOMNESS_FROM_TICKETS: &str = "Filecoin.StateGetRandomnessFromTickets";
     pub const STATE_GET_RANDOMNESS_FROM_BEACON: &str = "Filecoin.StateGetRandomnessFromBeacon";
@@ -290,11 +511,16 @@ pub mod state_api {
     pub const STATE_SEARCH_MSG_LIMITED: &str = "Filecoin.StateSearchMsgLimited";
     pub const STATE_LIST_MINERS: &str = "Filecoin.StateListMiners";
     pub const STATE_MINER_SECTOR_COUNT: &str = "Filecoin.StateMinerSectorCount";
+    pub const STATE_MINER_SECTORS: &str = "Filecoin.StateMinerSectors";
     pub const STATE_VERIFIED_CLIENT_STATUS: &str = "Filecoin.StateVerifiedClientStatus";
     pub const STATE_VM_CIRCULATING_SUPPLY_INTERNAL: &str =
         "Filecoin.StateVMCirculatingSupplyInternal";
+    pub const STATE_SUBSCRIBE_ACTOR_CHANGES: &str = "Filecoin.StateSubscribeActorChanges";
+    pub const STATE_NEXT_ACTOR_CHANGES: &str = "Filecoin.StateNextActorChanges";
+    pub const STATE_UNSUBSCRIBE_ACTOR_CHANGES: &str = "Filecoin.StateUnsubscribeActorChanges";
     pub const MSIG_GET_AVAILABLE_BALANCE: &str = "Filecoin.MsigGetAvailableBalance";
     pub const MSIG_GET_PENDING: &str = "Filecoin.MsigGetPending";
+    pub const STATE_MIGRATION_PROGRESS: &str = "Filecoin.StateMigrationProgress";
 }
 
 /// Gas API
@@ -309,13 +535,18 @@ pub mod gas_api {
 pub mod common_api {
     pub const VERSION: &str = "Filecoin.Version";
     pub const SHUTDOWN: &str = "Filecoin.Shutdown";
+    pub const RESTART: &str = "Filecoin.Restart";
     pub const START_TIME: &str = "Filecoin.StartTime";
     pub const DISCOVER: &str = "Filecoin.Discover";
     pub const SESSION: &str = "Filecoin.Session";
+    pub const CONFIG_RELOAD: &str = "Filecoin.ConfigReload";
+    pub const LOG_LIST: &str = "Filecoin.LogList";
+    pub const LOG_SET_LEVEL: &str = "Filecoin.LogSetLevel";
 }
 
 /// Net API
 pub mod net_api {
+    use libp2p::Multiaddr;
     use serde::{Deserialize, Serialize};
 
     use crate::lotus_json::lotus_json_with_self;
@@ -324,6 +555,8 @@ pub mod net_api {
 
     pub const NET_PEERS: &str = "Filecoin.NetPeers";
 
+    pub const NET_PEER_INFO: &str = "Filecoin.NetPeerInfo";
+
     pub const NET_INFO: &str = "Filecoin.NetInfo";
 
     #[derive(Debug, Default, Serialize, Deserialize)]
@@ -334,6 +567,10 @@ pub mod net_api {
         pub num_pending_incoming: u32,
         pub num_pending_outgoing: u32,
         pub num_established: u32,
+        /// Reachability of the node as probed by `AutoNAT`: `Public`, `Private` or `Unknown`.
+        pub reachability: String,
+        /// Public address of the node, when reachable.
+        pub public_addr: Option<Multiaddr>,
     }
     lotus_json_with_self!(NetInfoResult);
 
@@ -347,12 +584,27 @@ pub mod net_api {
                 num_pending_incoming: counters.num_pending_incoming(),
                 num_pending_outgoing: counters.num_pending_outgoing(),
                 num_established: counters.num_established(),
+                reachability: Default::default(),
+                public_addr: None,
             }
         }
     }
 
     pub const NET_CONNECT: &str = "Filecoin.NetConnect";
     pub const NET_DISCONNECT: &str = "Filecoin.NetDisconnect";
+
+    pub const NET_PROTECT_ADD: &str = "Filecoin.NetProtectAdd";
+    pub const NET_PROTECT_REMOVE: &str = "Filecoin.NetProtectRemove";
+    pub const NET_PROTECT_LIST: &str = "Filecoin.NetProtectList";
+
+    pub const NET_BLOCK_ADD: &str = "Filecoin.NetBlockAdd";
+    pub const NET_BLOCK_REMOVE: &str = "Filecoin.NetBlockRemove";
+    pub const NET_BLOCK_LIST: &str = "Filecoin.NetBlockList";
+
+    pub const NET_PUBSUB_SCORES: &str = "Filecoin.NetPubsubScores";
+
+    /// Starts a peer discovery round right away. Forest-specific.
+    pub const NET_DISCOVER: &str = "Filecoin.NetDiscover";
 }
 
 /// Node API
@@ -392,7 +644,19 @@ pub mod node_api {
     lotus_json_with_self!(NodeStatus);
 }
 
+/// Database API
+pub mod db_api {
+    pub const DATABASE_GARBAGE_COLLECT: &str = "Filecoin.DatabaseGarbageCollect";
+}
+
+/// Actor events API
+pub mod actor_events_api {
+    pub const GET_ACTOR_EVENTS_RAW: &str = "Filecoin.GetActorEventsRaw";
+    pub const SUBSCRIBE_ACTOR_EVENTS_RAW: &str = "Filecoin.SubscribeActorEventsRaw";
+}
+
 // Eth API
+#[cfg(feature = "eth-api")]
 pub mod eth_api {
     use std::{fmt, str::FromStr};
 
@@ -409,8 +673,15 @@ pub mod eth_api {
     pub const ETH_ACCOUNTS: &str = "Filecoin.EthAccounts";
     pub const ETH_BLOCK_NUMBER: &str = "Filecoin.EthBlockNumber";
     pub const ETH_CHAIN_ID: &str = "Filecoin.EthChainId";
+    pub const ETH_FEE_HISTORY: &str = "Filecoin.EthFeeHistory";
     pub const ETH_GAS_PRICE: &str = "Filecoin.EthGasPrice";
     pub const ETH_GET_BALANCE: &str = "Filecoin.EthGetBalance";
+    pub const ETH_GET_BLOCK_TRANSACTION_COUNT_BY_HASH: &str =
+        "Filecoin.EthGetBlockTransactionCountByHash";
+    pub const ETH_GET_BLOCK_TRANSACTION_COUNT_BY_NUMBER: &str =
+        "Filecoin.EthGetBlockTransactionCountByNumber";
+    pub const ETH_GET_UNCLE_COUNT_BY_BLOCK_HASH: &str = "Filecoin.EthGetUncleCountByBlockHash";
+    pub const ETH_SEND_RAW_TRANSACTION: &str = "Filecoin.EthSendRawTransaction";
 
     const MASKED_ID_PREFIX: [u8; 12] = [0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
 
@@ -424,6 +695,23 @@ pub mod eth_api {
 
     lotus_json_with_self!(BigInt);
 
+    #[derive(PartialEq, Debug, Deserialize, Serialize, Default, Clone)]
+    pub struct Uint64(#[serde(with = "crate::lotus_json::hexify")] pub u64);
+
+    lotus_json_with_self!(Uint64);
+
+    #[derive(PartialEq, Debug, Deserialize, Serialize, Default)]
+    #[serde(rename_all = "camelCase")]
+    pub struct FeeHistory {
+        pub oldest_block: Uint64,
+        pub base_fee_per_gas: Vec<BigInt>,
+        pub gas_used_ratio: Vec<f64>,
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        pub reward: Option<Vec<Vec<BigInt>>>,
+    }
+
+    lotus_json_with_self!(FeeHistory);
+
     #[derive(Debug, Deserialize, Serialize, Default, Clone)]
     pub struct Address(
         #[serde(with = "crate::lotus_json::hexify_bytes")] pub ethereum_types::Address,
@@ -463,8 +751,32 @@ pub mod eth_api {
         }
     }
 
-    #[derive(Default, Clone)]
-    pub struct Hash(pub ethereum_types::H256);
+    /// Arbitrary bytes, serialized as a `0x`-prefixed hex string.
+    #[derive(PartialEq, Debug, Default, Clone)]
+    pub struct Bytes(pub Vec<u8>);
+
+    lotus_json_with_self!(Bytes);
+
+    impl Serialize for Bytes {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(&format!("0x{}", hex::encode(&self.0)))
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Bytes {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let s = String::deserialize(deserializer)?;
+            let s = s
+                .strip_prefix("0x")
+                .ok_or_else(|| serde::de::Error::custom("Invalid hex"))?;
+            Ok(Bytes(hex::decode(s).map_err(serde::de::Error::custom)?))
+        }
+    }
+
+    #[derive(PartialEq, Debug, Deserialize, Serialize, Default, Clone)]
+    pub struct Hash(#[serde(with = "crate::lotus_json::hexify_bytes")] pub ethereum_types::H256);
+
+    lotus_json_with_self!(Hash);
 
     impl Hash {
         // Should ONLY be used for blocks and Filecoin messages. Eth transactions expect a different hashing scheme.
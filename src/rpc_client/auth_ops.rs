@@ -11,12 +11,24 @@ impl ApiInfo {
     pub async fn auth_new(
         &self,
         perms: Vec<String>,
+        label: Option<String>,
         token_exp: Duration,
     ) -> Result<Vec<u8>, JsonRpcError> {
-        self.call(Self::auth_new_req(perms, token_exp)).await
+        self.call(Self::auth_new_req(perms, label, token_exp)).await
     }
 
-    pub fn auth_new_req(perms: Vec<String>, token_exp: Duration) -> RpcRequest<Vec<u8>> {
-        RpcRequest::new(AUTH_NEW, AuthNewParams { perms, token_exp })
+    pub fn auth_new_req(
+        perms: Vec<String>,
+        label: Option<String>,
+        token_exp: Duration,
+    ) -> RpcRequest<Vec<u8>> {
+        RpcRequest::new(
+            AUTH_NEW,
+            AuthNewParams {
+                perms,
+                token_exp,
+                label,
+            },
+        )
     }
 }
@@ -1,7 +1,9 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::chain::store::reorg::Reorg;
 use crate::rpc_api::data_types::{ApiMessage, ApiReceipt};
+use crate::shim::econ::TokenAmount;
 use crate::shim::message::Message;
 use crate::{
     blocks::{CachingBlockHeader, Tipset, TipsetKey},
@@ -10,6 +12,7 @@ use crate::{
     shim::clock::ChainEpoch,
 };
 use cid::Cid;
+use std::path::PathBuf;
 
 use super::{ApiInfo, JsonRpcError, RpcRequest};
 
@@ -73,6 +76,71 @@ impl ApiInfo {
         RpcRequest::new(CHAIN_SET_HEAD, (new_head,))
     }
 
+    pub async fn chain_get_path(
+        &self,
+        from: TipsetKey,
+        to: TipsetKey,
+    ) -> Result<Vec<PathChange>, JsonRpcError> {
+        self.call(Self::chain_get_path_req(from, to)).await
+    }
+
+    pub fn chain_get_path_req(from: TipsetKey, to: TipsetKey) -> RpcRequest<Vec<PathChange>> {
+        RpcRequest::new(CHAIN_GET_PATH, (from, to))
+    }
+
+    pub async fn chain_add_car(&self, path: PathBuf) -> Result<Tipset, JsonRpcError> {
+        self.call(Self::chain_add_car_req(path)).await
+    }
+
+    pub fn chain_add_car_req(path: PathBuf) -> RpcRequest<Tipset> {
+        RpcRequest::new(CHAIN_ADD_CAR, (path,))
+    }
+
+    pub async fn chain_remove_car(&self, path: PathBuf) -> Result<(), JsonRpcError> {
+        self.call(Self::chain_remove_car_req(path)).await
+    }
+
+    pub fn chain_remove_car_req(path: PathBuf) -> RpcRequest<()> {
+        RpcRequest::new(CHAIN_REMOVE_CAR, (path,))
+    }
+
+    pub async fn chain_list_cars(&self) -> Result<Vec<PathBuf>, JsonRpcError> {
+        self.call(Self::chain_list_cars_req()).await
+    }
+
+    pub fn chain_list_cars_req() -> RpcRequest<Vec<PathBuf>> {
+        RpcRequest::new(CHAIN_LIST_CARS, ())
+    }
+
+    pub async fn chain_compute_base_fee(
+        &self,
+        tsk: TipsetKey,
+    ) -> Result<TokenAmount, JsonRpcError> {
+        self.call(Self::chain_compute_base_fee_req(tsk)).await
+    }
+
+    pub fn chain_compute_base_fee_req(tsk: TipsetKey) -> RpcRequest<TokenAmount> {
+        RpcRequest::new(CHAIN_COMPUTE_BASE_FEE, (tsk,))
+    }
+
+    pub async fn chain_get_reorgs(&self) -> Result<Vec<Reorg>, JsonRpcError> {
+        self.call(Self::chain_get_reorgs_req()).await
+    }
+
+    pub fn chain_get_reorgs_req() -> RpcRequest<Vec<Reorg>> {
+        RpcRequest::new(CHAIN_GET_REORGS, ())
+    }
+
+    /// Returns the request of a snapshot streamed by the node, see
+    /// [`ChainExportStreamParams`].
+    pub fn chain_export_stream_req(
+        recent_roots: ChainEpoch,
+        skip_old_messages: bool,
+        tsk: TipsetKey,
+    ) -> RpcRequest<Vec<u8>> {
+        RpcRequest::new(CHAIN_EXPORT, (recent_roots, skip_old_messages, tsk))
+    }
+
     pub async fn chain_export(
         &self,
         params: ChainExportParams,
@@ -84,6 +152,17 @@ impl ApiInfo {
         RpcRequest::new(CHAIN_EXPORT, params)
     }
 
+    pub async fn chain_export_range(
+        &self,
+        params: ChainExportRangeParams,
+    ) -> Result<ChainExportResult, JsonRpcError> {
+        self.call(Self::chain_export_range_req(params)).await
+    }
+
+    pub fn chain_export_range_req(params: ChainExportRangeParams) -> RpcRequest<ChainExportResult> {
+        RpcRequest::new(CHAIN_EXPORT_RANGE, params)
+    }
+
     #[allow(dead_code)]
     pub async fn chain_get_message(&self, cid: Cid) -> Result<Message, JsonRpcError> {
         self.call(Self::chain_get_message_req(cid)).await
@@ -121,6 +200,13 @@ impl ApiInfo {
         RpcRequest::new(CHAIN_GET_MESSAGES_IN_TIPSET, (tsk,))
     }
 
+    pub fn chain_get_messages_in_tipset_with_options_req(
+        tsk: TipsetKey,
+        options: MessagesInTipsetOptions,
+    ) -> RpcRequest<Vec<ApiMessage>> {
+        RpcRequest::new(CHAIN_GET_MESSAGES_IN_TIPSET, (tsk, options))
+    }
+
     pub fn chain_get_parent_messages_req(block_cid: Cid) -> RpcRequest<Vec<ApiMessage>> {
         RpcRequest::new(CHAIN_GET_PARENT_MESSAGES, (block_cid,))
     }
@@ -1,8 +1,12 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::daemon::config_reload::ConfigReloadReport;
 use crate::rpc_api::{
-    common_api::{DISCOVER, SESSION, SHUTDOWN, START_TIME, VERSION},
+    common_api::{
+        CONFIG_RELOAD, DISCOVER, LOG_LIST, LOG_SET_LEVEL, RESTART, SESSION, SHUTDOWN, START_TIME,
+        VERSION,
+    },
     data_types::{APIVersion, DiscoverResult},
 };
 use chrono::{DateTime, Utc};
@@ -30,6 +34,14 @@ impl ApiInfo {
         RpcRequest::new(SHUTDOWN, ())
     }
 
+    pub async fn restart(&self) -> Result<(), JsonRpcError> {
+        self.call(Self::restart_req()).await
+    }
+
+    pub fn restart_req() -> RpcRequest<()> {
+        RpcRequest::new(RESTART, ())
+    }
+
     pub fn discover_req() -> RpcRequest<DiscoverResult> {
         RpcRequest::new(DISCOVER, ())
     }
@@ -37,4 +49,28 @@ impl ApiInfo {
     pub fn session_req() -> RpcRequest<String> {
         RpcRequest::new(SESSION, ())
     }
+
+    pub async fn config_reload(&self) -> Result<ConfigReloadReport, JsonRpcError> {
+        self.call(Self::config_reload_req()).await
+    }
+
+    pub fn config_reload_req() -> RpcRequest<ConfigReloadReport> {
+        RpcRequest::new(CONFIG_RELOAD, ())
+    }
+
+    pub async fn log_list(&self) -> Result<Vec<String>, JsonRpcError> {
+        self.call(Self::log_list_req()).await
+    }
+
+    pub fn log_list_req() -> RpcRequest<Vec<String>> {
+        RpcRequest::new(LOG_LIST, ())
+    }
+
+    pub async fn log_set_level(&self, target: String, level: String) -> Result<(), JsonRpcError> {
+        self.call(Self::log_set_level_req(target, level)).await
+    }
+
+    pub fn log_set_level_req(target: String, level: String) -> RpcRequest<()> {
+        RpcRequest::new(LOG_SET_LEVEL, (target, level))
+    }
 }
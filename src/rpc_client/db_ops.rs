@@ -0,0 +1,24 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::time::Duration;
+
+use crate::db::GcReport;
+use crate::rpc_api::db_api::*;
+
+use super::{ApiInfo, JsonRpcError, RpcRequest};
+
+/// Garbage collection can take hours on mainnet.
+const DATABASE_GARBAGE_COLLECT_TIMEOUT: Duration = Duration::from_secs(60 * 60 * 24);
+
+impl ApiInfo {
+    pub async fn database_garbage_collect(&self) -> Result<GcReport, JsonRpcError> {
+        self.call(Self::database_garbage_collect_req()).await
+    }
+
+    pub fn database_garbage_collect_req() -> RpcRequest<GcReport> {
+        let mut req = RpcRequest::new(DATABASE_GARBAGE_COLLECT, ());
+        req.set_timeout(DATABASE_GARBAGE_COLLECT_TIMEOUT);
+        req
+    }
+}
@@ -18,6 +18,17 @@ impl ApiInfo {
         RpcRequest::new_v1(ETH_CHAIN_ID, ())
     }
 
+    pub fn eth_fee_history_req(
+        block_count: u64,
+        newest_block: BlockNumberOrHash,
+        reward_percentiles: Option<Vec<f64>>,
+    ) -> RpcRequest<FeeHistory> {
+        RpcRequest::new_v1(
+            ETH_FEE_HISTORY,
+            (Uint64(block_count), newest_block, reward_percentiles),
+        )
+    }
+
     pub fn eth_gas_price_req() -> RpcRequest<String> {
         RpcRequest::new_v1(ETH_GAS_PRICE, ())
     }
@@ -28,4 +39,22 @@ impl ApiInfo {
     ) -> RpcRequest<BigInt> {
         RpcRequest::new_v1(ETH_GET_BALANCE, (address, block_param))
     }
+
+    pub fn eth_get_block_transaction_count_by_hash_req(block_hash: Hash) -> RpcRequest<Uint64> {
+        RpcRequest::new_v1(ETH_GET_BLOCK_TRANSACTION_COUNT_BY_HASH, (block_hash,))
+    }
+
+    pub fn eth_get_block_transaction_count_by_number_req(
+        block_param: BlockNumberOrHash,
+    ) -> RpcRequest<Uint64> {
+        RpcRequest::new_v1(ETH_GET_BLOCK_TRANSACTION_COUNT_BY_NUMBER, (block_param,))
+    }
+
+    pub fn eth_get_uncle_count_by_block_hash_req(block_hash: Hash) -> RpcRequest<Uint64> {
+        RpcRequest::new_v1(ETH_GET_UNCLE_COUNT_BY_BLOCK_HASH, (block_hash,))
+    }
+
+    pub fn eth_send_raw_transaction_req(raw_tx: Bytes) -> RpcRequest<Hash> {
+        RpcRequest::new_v1(ETH_SEND_RAW_TRANSACTION, (raw_tx,))
+    }
 }
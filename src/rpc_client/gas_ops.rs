@@ -0,0 +1,58 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::{
+    blocks::TipsetKey,
+    rpc_api::{data_types::MessageSendSpec, gas_api::*},
+    shim::{address::Address, message::Message},
+};
+
+use super::{ApiInfo, JsonRpcError, RpcRequest};
+
+impl ApiInfo {
+    pub async fn gas_estimate_message_gas(
+        &self,
+        message: Message,
+        specs: Option<MessageSendSpec>,
+        tsk: TipsetKey,
+    ) -> Result<Message, JsonRpcError> {
+        self.call(Self::gas_estimate_message_gas_req(message, specs, tsk))
+            .await
+    }
+
+    pub fn gas_estimate_message_gas_req(
+        message: Message,
+        specs: Option<MessageSendSpec>,
+        tsk: TipsetKey,
+    ) -> RpcRequest<Message> {
+        RpcRequest::new(GAS_ESTIMATE_MESSAGE_GAS, (message, specs, tsk))
+    }
+
+    pub async fn gas_estimate_gas_premium(
+        &self,
+        nblocksincl: u64,
+        sender: Address,
+        gas_limit: i64,
+        tsk: TipsetKey,
+    ) -> Result<String, JsonRpcError> {
+        self.call(Self::gas_estimate_gas_premium_req(
+            nblocksincl,
+            sender,
+            gas_limit,
+            tsk,
+        ))
+        .await
+    }
+
+    pub fn gas_estimate_gas_premium_req(
+        nblocksincl: u64,
+        sender: Address,
+        gas_limit: i64,
+        tsk: TipsetKey,
+    ) -> RpcRequest<String> {
+        RpcRequest::new(
+            GAS_ESTIMATE_GAS_PREMIUM,
+            (nblocksincl, sender, gas_limit, tsk),
+        )
+    }
+}
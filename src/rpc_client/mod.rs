@@ -5,7 +5,10 @@ pub mod auth_ops;
 pub mod beacon_ops;
 pub mod chain_ops;
 pub mod common_ops;
+pub mod db_ops;
+#[cfg(feature = "eth-api")]
 pub mod eth_ops;
+pub mod gas_ops;
 pub mod mpool_ops;
 pub mod net_ops;
 pub mod node_ops;
@@ -27,7 +30,8 @@ use jsonrpc_v2::{Id, RequestObject, V2};
 use serde::Deserialize;
 use tracing::debug;
 
-use futures_util::{SinkExt, StreamExt};
+use bytes::Bytes;
+use futures_util::{SinkExt, Stream, StreamExt};
 use tokio_tungstenite::tungstenite;
 use tokio_tungstenite::{connect_async, tungstenite::protocol::Message as WsMessage};
 
@@ -122,6 +126,42 @@ impl ApiInfo {
         }
     }
 
+    /// Calls a method answered with a stream of bytes rather than with a
+    /// JSON-RPC response, e.g., a streamed `Filecoin.ChainExport`. The call has
+    /// no timeout.
+    pub async fn call_stream(
+        &self,
+        req: RpcRequest<Vec<u8>>,
+    ) -> Result<impl Stream<Item = Result<Bytes, JsonRpcError>>, JsonRpcError> {
+        let rpc_req = RequestObject::request()
+            .with_method(req.method_name)
+            .with_params(req.params)
+            .with_id(0)
+            .finish();
+
+        let api_url = multiaddress_to_url(&self.multiaddr, req.rpc_endpoint).to_string();
+
+        debug!("Using JSON-RPC v2 HTTP URL: {}", api_url);
+
+        let request = global_http_client().post(api_url).json(&rpc_req);
+        let request = match self.token.as_ref() {
+            Some(token) => request.header(http0::header::AUTHORIZATION, token),
+            _ => request,
+        };
+
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(JsonRpcError {
+                code: status.as_u16() as i64,
+                message: Cow::Owned(response.text().await?),
+            });
+        }
+        Ok(response
+            .bytes_stream()
+            .map(|chunk| chunk.map_err(JsonRpcError::from)))
+    }
+
     pub async fn ws_call<T: HasLotusJson>(&self, req: RpcRequest<T>) -> Result<T, JsonRpcError> {
         let rpc_req = RequestObject::request()
             .with_method(req.method_name)
@@ -374,6 +414,15 @@ impl<T> RpcRequest<T> {
         self.timeout = timeout;
     }
 
+    /// Returns the Lotus JSON encoding of the request parameters.
+    pub fn params(&self) -> &serde_json::Value {
+        &self.params
+    }
+
+    pub fn rpc_endpoint(&self) -> &'static str {
+        self.rpc_endpoint
+    }
+
     // Discard type information about the response.
     pub fn lower(self) -> RpcRequest {
         RpcRequest {
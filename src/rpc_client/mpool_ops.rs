@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use crate::{
+    blocks::TipsetKey,
     message::SignedMessage,
     rpc_api::{data_types::MessageSendSpec, mpool_api::*},
     shim::address::Address,
@@ -32,6 +33,33 @@ impl ApiInfo {
         RpcRequest::new(MPOOL_PUSH_MESSAGE, (message, specs))
     }
 
+    pub async fn mpool_batch_push(
+        &self,
+        messages: Vec<SignedMessage>,
+    ) -> Result<Vec<Cid>, JsonRpcError> {
+        self.call(Self::mpool_batch_push_req(messages)).await
+    }
+
+    pub fn mpool_batch_push_req(messages: Vec<SignedMessage>) -> RpcRequest<Vec<Cid>> {
+        RpcRequest::new(MPOOL_BATCH_PUSH, (messages,))
+    }
+
+    pub async fn mpool_batch_push_message(
+        &self,
+        messages: Vec<Message>,
+        specs: Option<MessageSendSpec>,
+    ) -> Result<Vec<SignedMessage>, JsonRpcError> {
+        self.call(Self::mpool_batch_push_message_req(messages, specs))
+            .await
+    }
+
+    pub fn mpool_batch_push_message_req(
+        messages: Vec<Message>,
+        specs: Option<MessageSendSpec>,
+    ) -> RpcRequest<Vec<SignedMessage>> {
+        RpcRequest::new(MPOOL_BATCH_PUSH_MESSAGE, (messages, specs))
+    }
+
     pub async fn mpool_pending(&self, cids: Vec<Cid>) -> Result<Vec<SignedMessage>, JsonRpcError> {
         self.call(Self::mpool_pending_req(cids)).await
     }
@@ -39,4 +67,8 @@ impl ApiInfo {
     pub fn mpool_pending_req(cids: Vec<Cid>) -> RpcRequest<Vec<SignedMessage>> {
         RpcRequest::new(MPOOL_PENDING, (cids,))
     }
+
+    pub fn mpool_select_req(tsk: TipsetKey, ticket_quality: f64) -> RpcRequest<Vec<SignedMessage>> {
+        RpcRequest::new(MPOOL_SELECT, (tsk, ticket_quality))
+    }
 }
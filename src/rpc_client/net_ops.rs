@@ -1,7 +1,11 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use crate::rpc_api::{data_types::AddrInfo, net_api::*};
+use crate::libp2p::BlockList;
+use crate::rpc_api::{
+    data_types::{AddrInfo, ExtendedPeerInfo, PubsubScore},
+    net_api::*,
+};
 
 use super::{ApiInfo, JsonRpcError, RpcRequest};
 
@@ -22,6 +26,14 @@ impl ApiInfo {
         RpcRequest::new(NET_PEERS, ())
     }
 
+    pub async fn net_peer_info(&self, peer: String) -> Result<ExtendedPeerInfo, JsonRpcError> {
+        self.call(Self::net_peer_info_req(peer)).await
+    }
+
+    pub fn net_peer_info_req(peer: String) -> RpcRequest<ExtendedPeerInfo> {
+        RpcRequest::new(NET_PEER_INFO, (peer,))
+    }
+
     pub async fn net_info(&self) -> Result<NetInfoResult, JsonRpcError> {
         self.call(Self::net_info_req()).await
     }
@@ -45,4 +57,68 @@ impl ApiInfo {
     pub fn net_disconnect_req(peer: String) -> RpcRequest<()> {
         RpcRequest::new(NET_DISCONNECT, (peer,))
     }
+
+    pub async fn net_protect_add(&self, peers: Vec<String>) -> Result<(), JsonRpcError> {
+        self.call(Self::net_protect_add_req(peers)).await
+    }
+
+    pub fn net_protect_add_req(peers: Vec<String>) -> RpcRequest<()> {
+        RpcRequest::new(NET_PROTECT_ADD, (peers,))
+    }
+
+    pub async fn net_protect_remove(&self, peers: Vec<String>) -> Result<(), JsonRpcError> {
+        self.call(Self::net_protect_remove_req(peers)).await
+    }
+
+    pub fn net_protect_remove_req(peers: Vec<String>) -> RpcRequest<()> {
+        RpcRequest::new(NET_PROTECT_REMOVE, (peers,))
+    }
+
+    pub async fn net_protect_list(&self) -> Result<Vec<String>, JsonRpcError> {
+        self.call(Self::net_protect_list_req()).await
+    }
+
+    pub fn net_protect_list_req() -> RpcRequest<Vec<String>> {
+        RpcRequest::new(NET_PROTECT_LIST, ())
+    }
+
+    pub async fn net_block_add(&self, block_list: BlockList) -> Result<(), JsonRpcError> {
+        self.call(Self::net_block_add_req(block_list)).await
+    }
+
+    pub fn net_block_add_req(block_list: BlockList) -> RpcRequest<()> {
+        RpcRequest::new(NET_BLOCK_ADD, (block_list,))
+    }
+
+    pub async fn net_block_remove(&self, block_list: BlockList) -> Result<(), JsonRpcError> {
+        self.call(Self::net_block_remove_req(block_list)).await
+    }
+
+    pub fn net_block_remove_req(block_list: BlockList) -> RpcRequest<()> {
+        RpcRequest::new(NET_BLOCK_REMOVE, (block_list,))
+    }
+
+    pub async fn net_block_list(&self) -> Result<BlockList, JsonRpcError> {
+        self.call(Self::net_block_list_req()).await
+    }
+
+    pub fn net_block_list_req() -> RpcRequest<BlockList> {
+        RpcRequest::new(NET_BLOCK_LIST, ())
+    }
+
+    pub async fn net_pubsub_scores(&self) -> Result<Vec<PubsubScore>, JsonRpcError> {
+        self.call(Self::net_pubsub_scores_req()).await
+    }
+
+    pub fn net_pubsub_scores_req() -> RpcRequest<Vec<PubsubScore>> {
+        RpcRequest::new(NET_PUBSUB_SCORES, ())
+    }
+
+    pub async fn net_discover(&self) -> Result<(), JsonRpcError> {
+        self.call(Self::net_discover_req()).await
+    }
+
+    pub fn net_discover_req() -> RpcRequest<()> {
+        RpcRequest::new(NET_DISCOVER, ())
+    }
 }
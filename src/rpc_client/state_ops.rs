@@ -3,20 +3,23 @@
 
 use std::path::PathBuf;
 
-use crate::rpc_api::data_types::{MiningBaseInfo, Transaction};
+use crate::rpc_api::data_types::{BlockTemplate, MiningBaseInfo, Transaction};
 use crate::{
-    blocks::TipsetKey,
+    blocks::{GossipBlock, TipsetKey},
     rpc_api::{
         data_types::{
-            ApiActorState, ApiDeadline, ApiInvocResult, CirculatingSupply, MessageLookup,
-            MinerSectors, SectorOnChainInfo,
+            ApiActorState, ApiDeadline, ApiInvocResult, CirculatingSupply, DealCollateralBounds,
+            MessageLookup, MinerSectors, SealVerifyInfo, SectorOnChainInfo, SectorPreCommitInfo,
+            WindowPoStVerifyInfo,
         },
         state_api::*,
     },
     shim::{
         address::Address, clock::ChainEpoch, econ::TokenAmount, message::Message,
-        message::MethodNum, state_tree::ActorState, version::NetworkVersion,
+        message::MethodNum, sector::SectorNumber, state_tree::ActorState, version::NetworkVersion,
     },
+    state_manager::ActorChange,
+    state_migration::progress::MigrationProgress,
 };
 use cid::Cid;
 use fil_actor_interface::miner::{DeadlineInfo, MinerInfo, MinerPower};
@@ -75,6 +78,10 @@ impl ApiInfo {
         RpcRequest::new(MINER_GET_BASE_INFO, (miner, epoch, tsk))
     }
 
+    pub fn miner_create_block_req(template: BlockTemplate) -> RpcRequest<GossipBlock> {
+        RpcRequest::new(MINER_CREATE_BLOCK, (template,))
+    }
+
     pub fn state_call_req(message: Message, tsk: TipsetKey) -> RpcRequest<ApiInvocResult> {
         RpcRequest::new(STATE_CALL, (message, tsk))
     }
@@ -105,6 +112,59 @@ impl ApiInfo {
         RpcRequest::new(STATE_MINER_PROVING_DEADLINE, (miner, tsk))
     }
 
+    pub fn state_miner_initial_pledge_collateral_req(
+        miner: Address,
+        sector: SectorPreCommitInfo,
+        tsk: TipsetKey,
+    ) -> RpcRequest<TokenAmount> {
+        RpcRequest::new(STATE_MINER_INITIAL_PLEDGE_COLLATERAL, (miner, sector, tsk))
+    }
+
+    pub fn state_miner_pre_commit_deposit_for_power_req(
+        miner: Address,
+        sector: SectorPreCommitInfo,
+        tsk: TipsetKey,
+    ) -> RpcRequest<TokenAmount> {
+        RpcRequest::new(
+            STATE_MINER_PRE_COMMIT_DEPOSIT_FOR_POWER,
+            (miner, sector, tsk),
+        )
+    }
+
+    pub fn state_miner_available_balance_req(
+        miner: Address,
+        tsk: TipsetKey,
+    ) -> RpcRequest<TokenAmount> {
+        RpcRequest::new(STATE_MINER_AVAILABLE_BALANCE, (miner, tsk))
+    }
+
+    pub fn state_miner_sector_allocated_req(
+        miner: Address,
+        sector_number: SectorNumber,
+        tsk: TipsetKey,
+    ) -> RpcRequest<bool> {
+        RpcRequest::new(STATE_MINER_SECTOR_ALLOCATED, (miner, sector_number, tsk))
+    }
+
+    pub fn state_verify_seals_req(infos: Vec<SealVerifyInfo>) -> RpcRequest<Vec<bool>> {
+        RpcRequest::new(STATE_VERIFY_SEALS, (infos,))
+    }
+
+    pub fn state_verify_window_post_req(info: WindowPoStVerifyInfo) -> RpcRequest<bool> {
+        RpcRequest::new(STATE_VERIFY_WINDOW_POST, (info,))
+    }
+
+    pub fn state_deal_provider_collateral_bounds_req(
+        piece_size: u64,
+        verified: bool,
+        tsk: TipsetKey,
+    ) -> RpcRequest<DealCollateralBounds> {
+        RpcRequest::new(
+            STATE_DEAL_PROVIDER_COLLATERAL_BOUNDS,
+            (piece_size, verified, tsk),
+        )
+    }
+
     pub fn state_get_randomness_from_tickets_req(
         tsk: TipsetKey,
         personalization: DomainSeparationTag,
@@ -155,6 +215,16 @@ impl ApiInfo {
         RpcRequest::new(STATE_NETWORK_VERSION, (tsk,))
     }
 
+    pub fn state_actor_code_cids_req(
+        version: NetworkVersion,
+    ) -> RpcRequest<ahash::HashMap<String, Cid>> {
+        RpcRequest::new(STATE_ACTOR_CODE_CIDS, (version,))
+    }
+
+    pub fn state_actor_manifest_cid_req(version: NetworkVersion) -> RpcRequest<Cid> {
+        RpcRequest::new(STATE_ACTOR_MANIFEST_CID, (version,))
+    }
+
     pub fn state_account_key_req(addr: Address, tsk: TipsetKey) -> RpcRequest<Address> {
         RpcRequest::new(STATE_ACCOUNT_KEY, (addr, tsk))
     }
@@ -193,6 +263,24 @@ impl ApiInfo {
         RpcRequest::new(STATE_SECTOR_GET_INFO, (addr, sector_no, tsk))
     }
 
+    pub async fn state_miner_sectors(
+        &self,
+        addr: Address,
+        sectors: Option<BitField>,
+        tsk: TipsetKey,
+    ) -> Result<Vec<SectorOnChainInfo>, JsonRpcError> {
+        self.call(Self::state_miner_sectors_req(addr, sectors, tsk))
+            .await
+    }
+
+    pub fn state_miner_sectors_req(
+        addr: Address,
+        sectors: Option<BitField>,
+        tsk: TipsetKey,
+    ) -> RpcRequest<Vec<SectorOnChainInfo>> {
+        RpcRequest::new(STATE_MINER_SECTORS, (addr, sectors, tsk))
+    }
+
     pub fn state_wait_msg_req(msg_cid: Cid, confidence: i64) -> RpcRequest<Option<MessageLookup>> {
         RpcRequest::new(STATE_WAIT_MSG, (msg_cid, confidence))
     }
@@ -222,4 +310,20 @@ impl ApiInfo {
     pub fn msig_get_pending_req(addr: Address, tsk: TipsetKey) -> RpcRequest<Vec<Transaction>> {
         RpcRequest::new(MSIG_GET_PENDING, (addr, tsk))
     }
+
+    pub fn state_subscribe_actor_changes_req(addresses: Vec<Address>) -> RpcRequest<u64> {
+        RpcRequest::new(STATE_SUBSCRIBE_ACTOR_CHANGES, (addresses,))
+    }
+
+    pub fn state_next_actor_changes_req(id: u64) -> RpcRequest<Vec<ActorChange>> {
+        RpcRequest::new(STATE_NEXT_ACTOR_CHANGES, (id,))
+    }
+
+    pub fn state_unsubscribe_actor_changes_req(id: u64) -> RpcRequest<bool> {
+        RpcRequest::new(STATE_UNSUBSCRIBE_ACTOR_CHANGES, (id,))
+    }
+
+    pub fn state_migration_progress_req() -> RpcRequest<Option<MigrationProgress>> {
+        RpcRequest::new(STATE_MIGRATION_PROGRESS, ())
+    }
 }
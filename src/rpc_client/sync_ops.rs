@@ -1,6 +1,8 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::chain::store::consensus_fault::ConsensusFault;
+use crate::chain_sync::Checkpoint;
 use crate::rpc_api::{data_types::RPCSyncState, sync_api::*};
 use cid::Cid;
 
@@ -15,6 +17,14 @@ impl ApiInfo {
         RpcRequest::new(SYNC_CHECK_BAD, (cid,))
     }
 
+    pub async fn sync_consensus_faults(&self) -> Result<Vec<ConsensusFault>, JsonRpcError> {
+        self.call(Self::sync_consensus_faults_req()).await
+    }
+
+    pub fn sync_consensus_faults_req() -> RpcRequest<Vec<ConsensusFault>> {
+        RpcRequest::new(SYNC_CONSENSUS_FAULTS, ())
+    }
+
     pub async fn sync_mark_bad(&self, cid: Cid) -> Result<(), JsonRpcError> {
         self.call(Self::sync_mark_bad_req(cid)).await
     }
@@ -23,6 +33,30 @@ impl ApiInfo {
         RpcRequest::new(SYNC_MARK_BAD, (cid,))
     }
 
+    pub async fn sync_unmark_bad(&self, cid: Cid) -> Result<(), JsonRpcError> {
+        self.call(Self::sync_unmark_bad_req(cid)).await
+    }
+
+    pub fn sync_unmark_bad_req(cid: Cid) -> RpcRequest<()> {
+        RpcRequest::new(SYNC_UNMARK_BAD, (cid,))
+    }
+
+    pub async fn sync_unmark_all_bad(&self) -> Result<(), JsonRpcError> {
+        self.call(Self::sync_unmark_all_bad_req()).await
+    }
+
+    pub fn sync_unmark_all_bad_req() -> RpcRequest<()> {
+        RpcRequest::new(SYNC_UNMARK_ALL_BAD, ())
+    }
+
+    pub async fn sync_set_checkpoint(&self, checkpoint: Checkpoint) -> Result<(), JsonRpcError> {
+        self.call(Self::sync_set_checkpoint_req(checkpoint)).await
+    }
+
+    pub fn sync_set_checkpoint_req(checkpoint: Checkpoint) -> RpcRequest<()> {
+        RpcRequest::new(SYNC_SET_CHECKPOINT, (checkpoint,))
+    }
+
     pub async fn sync_status(&self) -> Result<RPCSyncState, JsonRpcError> {
         self.call(Self::sync_status_req()).await
     }
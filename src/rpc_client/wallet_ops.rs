@@ -4,10 +4,12 @@
 use super::{ApiInfo, JsonRpcError, RpcRequest};
 use crate::{
     key_management::KeyInfo,
+    message::SignedMessage,
     rpc_api::wallet_api::*,
     shim::{
         address::Address,
         crypto::{Signature, SignatureType},
+        message::Message,
     },
 };
 
@@ -88,6 +90,22 @@ impl ApiInfo {
         RpcRequest::new(WALLET_SIGN, (address, data))
     }
 
+    pub async fn wallet_sign_message(
+        &self,
+        address: Address,
+        message: Message,
+    ) -> Result<SignedMessage, JsonRpcError> {
+        self.call(Self::wallet_sign_message_req(address, message))
+            .await
+    }
+
+    pub fn wallet_sign_message_req(
+        address: Address,
+        message: Message,
+    ) -> RpcRequest<SignedMessage> {
+        RpcRequest::new(WALLET_SIGN_MESSAGE, (address, message))
+    }
+
     pub async fn wallet_verify(
         &self,
         address: Address,
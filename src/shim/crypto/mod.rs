@@ -0,0 +1,343 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+mod scheme;
+pub mod verification_pool;
+
+use std::{borrow::Cow, num::NonZeroUsize};
+
+use super::fvm_shared_latest::{self, commcid::Commitment};
+pub use super::fvm_shared_latest::{IPLD_RAW, TICKET_RANDOMNESS_LOOKBACK};
+use crate::metrics;
+use bls_signatures::{PublicKey as BlsPublicKey, Serialize as _, Signature as BlsSignature};
+use cid::Cid;
+use fvm_ipld_encoding::{
+    de,
+    repr::{Deserialize_repr, Serialize_repr},
+    ser, strict_bytes,
+};
+use lru::LruCache;
+use nonzero_ext::nonzero;
+use num::FromPrimitive;
+use num_derive::FromPrimitive;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+pub use scheme::{signature_scheme, SignatureScheme};
+
+/// Number of valid BLS aggregate signatures remembered, so that blocks
+/// validated again (e.g. during a re-sync) skip the pairing checks.
+const VERIFIED_BLS_AGGREGATE_CACHE_SIZE: NonZeroUsize = nonzero!(4096usize);
+
+/// Hashes of `(signature, data, public keys)` triples known to verify.
+static VERIFIED_BLS_AGGREGATES: Lazy<Mutex<LruCache<[u8; 32], ()>>> =
+    Lazy::new(|| Mutex::new(LruCache::new(VERIFIED_BLS_AGGREGATE_CACHE_SIZE)));
+
+/// A cryptographic signature, represented in bytes, of any key protocol.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+pub struct Signature {
+    pub sig_type: SignatureType,
+    pub bytes: Vec<u8>,
+}
+
+impl ser::Serialize for Signature {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: ser::Serializer,
+    {
+        let mut bytes = Vec::with_capacity(self.bytes.len() + 1);
+        // Insert signature type byte
+        bytes.push(self.sig_type as u8);
+        bytes.extend_from_slice(&self.bytes);
+
+        strict_bytes::Serialize::serialize(&bytes, serializer)
+    }
+}
+
+impl<'de> de::Deserialize<'de> for Signature {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let bytes: Cow<'de, [u8]> = strict_bytes::Deserialize::deserialize(deserializer)?;
+        if bytes.is_empty() {
+            return Err(de::Error::custom("Cannot deserialize empty bytes"));
+        }
+
+        // Remove signature type byte
+        let sig_type = SignatureType::from_u8(bytes[0]).ok_or_else(|| {
+            de::Error::custom(format!(
+                "Invalid signature type byte (must be 1, 2 or 3), was {}",
+                bytes[0]
+            ))
+        })?;
+
+        Ok(Signature {
+            bytes: bytes[1..].to_vec(),
+            sig_type,
+        })
+    }
+}
+
+impl Signature {
+    pub fn new(sig_type: SignatureType, bytes: Vec<u8>) -> Self {
+        Signature { sig_type, bytes }
+    }
+
+    /// Creates a BLS Signature given the raw bytes.
+    pub fn new_bls(bytes: Vec<u8>) -> Self {
+        Self {
+            sig_type: SignatureType::Bls,
+            bytes,
+        }
+    }
+
+    /// Creates a SECP Signature given the raw bytes.
+    pub fn new_secp256k1(bytes: Vec<u8>) -> Self {
+        Self {
+            sig_type: SignatureType::Secp256k1,
+            bytes,
+        }
+    }
+
+    pub fn signature_type(&self) -> SignatureType {
+        self.sig_type
+    }
+
+    /// Checks if a signature is valid given data and address.
+    pub fn verify(&self, data: &[u8], addr: &crate::shim::address::Address) -> Result<(), String> {
+        signature_scheme(self.sig_type).verify(&self.bytes, data, addr)
+    }
+
+    /// Checks that signatures of this type are accepted at network version
+    /// `nv`.
+    pub fn check_network_version(
+        &self,
+        nv: crate::shim::version::NetworkVersion,
+    ) -> Result<(), String> {
+        if signature_scheme(self.sig_type).is_enabled(nv) {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} signatures are not accepted at network version {nv:?}",
+                self.sig_type
+            ))
+        }
+    }
+
+    /// Returns reference to signature bytes.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl TryFrom<&Signature> for BlsSignature {
+    type Error = anyhow::Error;
+    fn try_from(value: &Signature) -> Result<Self, Self::Error> {
+        match value.sig_type {
+            SignatureType::Secp256k1 => {
+                anyhow::bail!("cannot convert Secp256k1 signature to bls signature")
+            }
+            SignatureType::Bls => Ok(BlsSignature::from_bytes(&value.bytes)?),
+            SignatureType::Delegated => {
+                anyhow::bail!("cannot convert delegated signature to bls signature")
+            }
+        }
+    }
+}
+
+// Forest's version of the `verify_bls_aggregate` function is semantically different
+// from the version in FVM.
+/// Aggregates and verifies BLS signatures collectively.
+pub fn verify_bls_aggregate(data: &[&[u8]], pub_keys: &[BlsPublicKey], sig: &Signature) -> bool {
+    // If the number of public keys and data does not match, then return false
+    if data.len() != pub_keys.len() {
+        return false;
+    }
+    if data.is_empty() {
+        return true;
+    }
+
+    let key = bls_aggregate_key(data, pub_keys, sig);
+    if VERIFIED_BLS_AGGREGATES.lock().get(&key).is_some() {
+        metrics::LRU_CACHE_HIT
+            .with_label_values(&[metrics::values::BLS_AGGREGATE])
+            .inc();
+        return true;
+    }
+    metrics::LRU_CACHE_MISS
+        .with_label_values(&[metrics::values::BLS_AGGREGATE])
+        .inc();
+
+    let bls_sig = match sig.try_into() {
+        Ok(bls_sig) => bls_sig,
+        _ => return false,
+    };
+
+    // Does the aggregate verification
+    let valid = bls_signatures::verify_messages(&bls_sig, data, pub_keys);
+    if valid {
+        VERIFIED_BLS_AGGREGATES.lock().put(key, ());
+    }
+    valid
+}
+
+/// Hashes everything an aggregate verification depends on. Messages are
+/// length-prefixed so that different splits of the same bytes can't collide.
+fn bls_aggregate_key(data: &[&[u8]], pub_keys: &[BlsPublicKey], sig: &Signature) -> [u8; 32] {
+    let mut state = blake2b_simd::Params::new().hash_length(32).to_state();
+    state.update(sig.bytes());
+    for msg in data {
+        state.update(&(msg.len() as u64).to_be_bytes());
+        state.update(msg);
+    }
+    for pub_key in pub_keys {
+        state.update(&pub_key.as_bytes());
+    }
+    let mut key = [0; 32];
+    key.copy_from_slice(state.finalize().as_bytes());
+    key
+}
+
+/// Returns `String` error if a BLS signature is invalid.
+pub fn verify_bls_sig(
+    signature: &[u8],
+    data: &[u8],
+    addr: &crate::shim::address::Address,
+) -> Result<(), String> {
+    fvm_shared_latest::crypto::signature::ops::verify_bls_sig(signature, data, &addr.into())
+}
+
+/// Returns `String` error if a delegated signature is invalid. Delegated
+/// signatures are 65-byte secp256k1 signatures (with recovery identifier) over
+/// the Keccak-256 digest of `data`; the recovered public key must hash to the
+/// Ethereum address embedded in the `f410` address `addr`.
+pub fn verify_delegated_sig(
+    signature: &[u8],
+    data: &[u8],
+    addr: &crate::shim::address::Address,
+) -> Result<(), String> {
+    use crate::eth::EAM_NAMESPACE;
+    use crate::shim::address::Payload;
+
+    match addr.payload() {
+        Payload::Delegated(delegated) if delegated.namespace() == EAM_NAMESPACE => {}
+        _ => {
+            return Err(format!(
+                "expected an Ethereum delegated address, got {addr}"
+            ))
+        }
+    }
+    let recovered = recover_delegated_address(signature, data)?;
+    if recovered != *addr {
+        return Err(format!(
+            "delegated signature did not match address {addr}, recovered {recovered}"
+        ));
+    }
+    Ok(())
+}
+
+/// Returns the `f410` address of the key that produced the delegated
+/// `signature` of `data`.
+pub fn recover_delegated_address(
+    signature: &[u8],
+    data: &[u8],
+) -> Result<crate::shim::address::Address, String> {
+    use crate::eth::EAM_NAMESPACE;
+    use crate::shim::address::Address;
+    use crate::utils::encoding::keccak_256;
+
+    if signature.len() != 65 {
+        return Err(format!(
+            "invalid delegated signature length, expected 65 bytes, got {}",
+            signature.len()
+        ));
+    }
+
+    let message = libsecp256k1::Message::parse(&keccak_256(data));
+    let sig = libsecp256k1::Signature::parse_standard_slice(&signature[..64])
+        .map_err(|e| format!("invalid delegated signature: {e}"))?;
+    let recovery_id = libsecp256k1::RecoveryId::parse(signature[64])
+        .map_err(|e| format!("invalid delegated signature recovery id: {e}"))?;
+    let public_key = libsecp256k1::recover(&message, &sig, &recovery_id)
+        .map_err(|e| format!("failed to recover public key from delegated signature: {e}"))?;
+
+    // The Ethereum address is the last 20 bytes of the Keccak-256 hash of the
+    // uncompressed public key, without its `0x04` prefix.
+    Address::new_delegated(
+        EAM_NAMESPACE,
+        &keccak_256(&public_key.serialize()[1..])[12..],
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Extracts the raw replica commitment from a CID
+/// assuming that it has the correct hashing function and
+/// serialization types
+pub fn cid_to_replica_commitment_v1(c: &Cid) -> Result<Commitment, &'static str> {
+    fvm_shared_latest::commcid::cid_to_replica_commitment_v1(c)
+}
+
+/// Extracts the raw data commitment from a CID
+/// assuming that it has the correct hashing function and
+/// serialization types
+pub fn cid_to_data_commitment_v1(c: &Cid) -> Result<Commitment, &'static str> {
+    fvm_shared_latest::commcid::cid_to_data_commitment_v1(c)
+}
+
+/// Signature variants for Filecoin signatures.
+#[derive(
+    Clone,
+    Debug,
+    PartialEq,
+    FromPrimitive,
+    Copy,
+    Eq,
+    Serialize_repr,
+    Deserialize_repr,
+    Hash,
+    strum::Display,
+    strum::EnumString,
+)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[repr(u8)]
+#[strum(serialize_all = "lowercase")]
+pub enum SignatureType {
+    Secp256k1 = 1,
+    Bls = 2,
+    Delegated = 3,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bls_signatures::PrivateKey as BlsPrivate;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn verified_bls_aggregates_are_cached() {
+        let keys = [
+            BlsPrivate::generate(&mut OsRng),
+            BlsPrivate::generate(&mut OsRng),
+        ];
+        let data: [&[u8]; 2] = [b"first", b"second"];
+        let pub_keys = keys.iter().map(|k| k.public_key()).collect::<Vec<_>>();
+        let sigs = keys
+            .iter()
+            .zip(data)
+            .map(|(k, d)| k.sign(d))
+            .collect::<Vec<_>>();
+        let sig = Signature::new_bls(bls_signatures::aggregate(&sigs).unwrap().as_bytes());
+
+        let key = bls_aggregate_key(&data, &pub_keys, &sig);
+        assert!(verify_bls_aggregate(&data, &pub_keys, &sig));
+        assert!(VERIFIED_BLS_AGGREGATES.lock().contains(&key));
+        assert!(verify_bls_aggregate(&data, &pub_keys, &sig));
+
+        // A cached signature must not vouch for other messages
+        let other: [&[u8]; 2] = [b"first", b"other"];
+        assert!(!verify_bls_aggregate(&other, &pub_keys, &sig));
+        assert!(!VERIFIED_BLS_AGGREGATES
+            .lock()
+            .contains(&bls_aggregate_key(&other, &pub_keys, &sig)));
+    }
+}
@@ -0,0 +1,244 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Registry of supported signature schemes. Every [`SignatureType`] maps to a
+//! [`SignatureScheme`] implementing key handling, signing and verification
+//! for it, so supporting a new protocol only takes an implementation and an
+//! entry in [`SCHEMES`] rather than changes to every caller.
+
+use super::{verify_bls_sig, verify_delegated_sig, Signature, SignatureType};
+use crate::eth::{EthChainId, EthTx, EAM_NAMESPACE};
+use crate::shim::{address::Address, message::Message, version::NetworkVersion};
+use crate::utils::encoding::{blake2b_256, keccak_256};
+use anyhow::Context as _;
+use bls_signatures::{PrivateKey as BlsPrivate, Serialize as _};
+use libsecp256k1::{Message as SecpMessage, PublicKey as SecpPublic, SecretKey as SecpPrivate};
+use rand::rngs::OsRng;
+
+/// Key handling, signing and verification rules of a signature protocol.
+pub trait SignatureScheme: Send + Sync {
+    fn sig_type(&self) -> SignatureType;
+
+    /// Whether signatures of this scheme are accepted at network version `nv`.
+    fn is_enabled(&self, _nv: NetworkVersion) -> bool {
+        true
+    }
+
+    fn generate_private_key(&self) -> Vec<u8>;
+
+    fn public_key(&self, private_key: &[u8]) -> anyhow::Result<Vec<u8>>;
+
+    /// Returns the address controlled by `public_key`.
+    fn address(&self, public_key: &[u8]) -> anyhow::Result<Address>;
+
+    /// Signs arbitrary `data`, returning the raw signature bytes.
+    fn sign(&self, private_key: &[u8], data: &[u8]) -> anyhow::Result<Vec<u8>>;
+
+    /// Checks that `signature` of `data` was produced by the key behind `addr`.
+    fn verify(&self, signature: &[u8], data: &[u8], addr: &Address) -> Result<(), String>;
+
+    /// Returns the bytes that are signed for `message`, its CID by default.
+    fn message_signing_bytes(
+        &self,
+        message: &Message,
+        _eth_chain_id: EthChainId,
+    ) -> anyhow::Result<Vec<u8>> {
+        Ok(message.cid()?.to_bytes())
+    }
+
+    /// Checks that `signature` of `message` was produced by the key behind
+    /// `key_addr`, the key address of the sender.
+    fn verify_message(
+        &self,
+        signature: &Signature,
+        message: &Message,
+        key_addr: &Address,
+        eth_chain_id: EthChainId,
+    ) -> Result<(), String> {
+        let data = self
+            .message_signing_bytes(message, eth_chain_id)
+            .map_err(|e| e.to_string())?;
+        self.verify(signature.bytes(), &data, key_addr)
+    }
+}
+
+static SCHEMES: [&(dyn SignatureScheme); 3] = [&BlsScheme, &Secp256k1Scheme, &DelegatedScheme];
+
+/// Returns the scheme implementing `sig_type`.
+pub fn signature_scheme(sig_type: SignatureType) -> &'static dyn SignatureScheme {
+    *SCHEMES
+        .iter()
+        .find(|scheme| scheme.sig_type() == sig_type)
+        .expect("every signature type must have a registered scheme")
+}
+
+struct BlsScheme;
+
+impl SignatureScheme for BlsScheme {
+    fn sig_type(&self) -> SignatureType {
+        SignatureType::Bls
+    }
+
+    fn generate_private_key(&self) -> Vec<u8> {
+        BlsPrivate::generate(&mut OsRng).as_bytes()
+    }
+
+    fn public_key(&self, private_key: &[u8]) -> anyhow::Result<Vec<u8>> {
+        Ok(BlsPrivate::from_bytes(private_key)?.public_key().as_bytes())
+    }
+
+    fn address(&self, public_key: &[u8]) -> anyhow::Result<Address> {
+        Ok(Address::new_bls(public_key)?)
+    }
+
+    fn sign(&self, private_key: &[u8], data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        Ok(BlsPrivate::from_bytes(private_key)?.sign(data).as_bytes())
+    }
+
+    fn verify(&self, signature: &[u8], data: &[u8], addr: &Address) -> Result<(), String> {
+        verify_bls_sig(signature, data, addr)
+    }
+}
+
+struct Secp256k1Scheme;
+
+impl SignatureScheme for Secp256k1Scheme {
+    fn sig_type(&self) -> SignatureType {
+        SignatureType::Secp256k1
+    }
+
+    fn generate_private_key(&self) -> Vec<u8> {
+        SecpPrivate::random(&mut OsRng).serialize().to_vec()
+    }
+
+    fn public_key(&self, private_key: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let private_key =
+            SecpPrivate::parse_slice(private_key).map_err(|e| anyhow::anyhow!("{e}"))?;
+        Ok(SecpPublic::from_secret_key(&private_key)
+            .serialize()
+            .to_vec())
+    }
+
+    fn address(&self, public_key: &[u8]) -> anyhow::Result<Address> {
+        Ok(Address::new_secp256k1(public_key)?)
+    }
+
+    fn sign(&self, private_key: &[u8], data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        sign_secp256k1(private_key, &blake2b_256(data))
+    }
+
+    fn verify(&self, signature: &[u8], data: &[u8], addr: &Address) -> Result<(), String> {
+        super::fvm_shared_latest::crypto::signature::ops::verify_secp256k1_sig(
+            signature, data, addr,
+        )
+    }
+}
+
+/// Ethereum keys behind `f410` addresses. They are secp256k1 keys signing
+/// Keccak-256 digests, and messages are signed as Ethereum transactions.
+struct DelegatedScheme;
+
+impl SignatureScheme for DelegatedScheme {
+    fn sig_type(&self) -> SignatureType {
+        SignatureType::Delegated
+    }
+
+    // Ethereum accounts were introduced in the Hygge upgrade
+    fn is_enabled(&self, nv: NetworkVersion) -> bool {
+        nv >= NetworkVersion::V18
+    }
+
+    fn generate_private_key(&self) -> Vec<u8> {
+        Secp256k1Scheme.generate_private_key()
+    }
+
+    fn public_key(&self, private_key: &[u8]) -> anyhow::Result<Vec<u8>> {
+        Secp256k1Scheme.public_key(private_key)
+    }
+
+    fn address(&self, public_key: &[u8]) -> anyhow::Result<Address> {
+        // The Ethereum address is the last 20 bytes of the Keccak-256 hash of the
+        // uncompressed public key, without its `0x04` prefix.
+        let public_key = public_key
+            .strip_prefix(&[0x04])
+            .context("expected an uncompressed public key")?;
+        Ok(Address::new_delegated(
+            EAM_NAMESPACE,
+            &keccak_256(public_key)[12..],
+        )?)
+    }
+
+    fn sign(&self, private_key: &[u8], data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        sign_secp256k1(private_key, &keccak_256(data))
+    }
+
+    fn verify(&self, signature: &[u8], data: &[u8], addr: &Address) -> Result<(), String> {
+        verify_delegated_sig(signature, data, addr)
+    }
+
+    fn message_signing_bytes(
+        &self,
+        message: &Message,
+        eth_chain_id: EthChainId,
+    ) -> anyhow::Result<Vec<u8>> {
+        Ok(EthTx::new_eip1559(eth_chain_id, message)?.rlp_unsigned_message())
+    }
+
+    // The transaction type a message was signed as depends on its signature.
+    fn verify_message(
+        &self,
+        signature: &Signature,
+        message: &Message,
+        key_addr: &Address,
+        eth_chain_id: EthChainId,
+    ) -> Result<(), String> {
+        EthTx::from_signed_message(eth_chain_id, message, signature)
+            .and_then(|tx| tx.verify_signature(signature, key_addr))
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Signs a 32-byte digest, returning the 64-byte signature followed by the
+/// recovery identifier.
+fn sign_secp256k1(private_key: &[u8], digest: &[u8; 32]) -> anyhow::Result<Vec<u8>> {
+    let private_key = SecpPrivate::parse_slice(private_key).map_err(|e| anyhow::anyhow!("{e}"))?;
+    let (sig, recovery_id) = libsecp256k1::sign(&SecpMessage::parse(digest), &private_key);
+    let mut bytes = [0; 65];
+    bytes[..64].copy_from_slice(&sig.serialize());
+    bytes[64] = recovery_id.serialize();
+    Ok(bytes.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num::FromPrimitive;
+
+    #[test]
+    fn every_signature_type_is_registered() {
+        for sig_type in (0..=u8::MAX).filter_map(SignatureType::from_u8) {
+            assert_eq!(signature_scheme(sig_type).sig_type(), sig_type);
+        }
+    }
+
+    #[test]
+    fn sign_and_verify_with_every_scheme() {
+        let data = b"forest";
+        for scheme in SCHEMES {
+            let private_key = scheme.generate_private_key();
+            let public_key = scheme.public_key(&private_key).unwrap();
+            let addr = scheme.address(&public_key).unwrap();
+            let signature = scheme.sign(&private_key, data).unwrap();
+            scheme.verify(&signature, data, &addr).unwrap();
+            assert!(scheme.verify(&signature, b"other", &addr).is_err());
+        }
+    }
+
+    #[test]
+    fn delegated_signatures_are_gated_by_network_version() {
+        let scheme = signature_scheme(SignatureType::Delegated);
+        assert!(!scheme.is_enabled(NetworkVersion::V17));
+        assert!(scheme.is_enabled(NetworkVersion::V18));
+        assert!(signature_scheme(SignatureType::Secp256k1).is_enabled(NetworkVersion::V0));
+    }
+}
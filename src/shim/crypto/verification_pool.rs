@@ -0,0 +1,115 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Dedicated thread pool for signature verifications. Block and message pool
+//! validation submit their `secp256k1`, delegated and BLS checks here, so that
+//! the signatures of a block are verified in parallel and the async tasks
+//! driving the sync aren't blocked by pairing checks.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::Context as _;
+use once_cell::sync::{Lazy, OnceCell};
+use prometheus::core::{AtomicU64 as PromAtomicU64, GenericGauge};
+use rayon::prelude::*;
+
+/// A single signature check, run on the verification pool.
+pub type Verification<'a, E> = Box<dyn FnOnce() -> Result<(), E> + Send + 'a>;
+
+static POOL: OnceCell<rayon::ThreadPool> = OnceCell::new();
+
+static VERIFICATION_QUEUE_DEPTH: Lazy<Box<GenericGauge<PromAtomicU64>>> = Lazy::new(|| {
+    let verification_queue_depth = Box::new(
+        GenericGauge::<PromAtomicU64>::new(
+            "signature_verification_queue_depth",
+            "Number of signature verifications pending in the verification pool",
+        )
+        .expect("Defining the signature_verification_queue_depth metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(verification_queue_depth.clone())
+        .expect(
+            "Registering the signature_verification_queue_depth metric with the metrics registry must succeed",
+        );
+    verification_queue_depth
+});
+
+fn build_pool(num_threads: usize) -> Result<rayon::ThreadPool, rayon::ThreadPoolBuildError> {
+    rayon::ThreadPoolBuilder::new()
+        .thread_name(|id| format!("signature verification thread: {id}"))
+        // `0` lets rayon use one thread per available CPU.
+        .num_threads(num_threads)
+        .build()
+}
+
+/// Creates the verification pool with `num_threads` threads, or one thread
+/// per CPU if `num_threads` is `0`. Must be called before the first
+/// verification, otherwise the pool is created with the default size.
+pub fn init(num_threads: usize) -> anyhow::Result<()> {
+    let pool = build_pool(num_threads).context("failed to build the verification pool")?;
+    POOL.set(pool)
+        .map_err(|_| anyhow::anyhow!("the verification pool is already initialized"))
+}
+
+fn pool() -> &'static rayon::ThreadPool {
+    POOL.get_or_init(|| build_pool(0).expect("Building the verification pool must succeed"))
+}
+
+/// Runs `verifications` in parallel, stopping at the first failure.
+fn run<E: Send>(verifications: Vec<Verification<'_, E>>) -> Result<(), E> {
+    let submitted = verifications.len() as u64;
+    VERIFICATION_QUEUE_DEPTH.add(submitted);
+    let completed = AtomicU64::new(0);
+    let result = verifications.into_par_iter().try_for_each(|verify| {
+        let result = verify();
+        completed.fetch_add(1, Ordering::Relaxed);
+        VERIFICATION_QUEUE_DEPTH.dec();
+        result
+    });
+    // Verifications skipped after a failure are no longer pending either.
+    VERIFICATION_QUEUE_DEPTH.sub(submitted - completed.load(Ordering::Relaxed));
+    result
+}
+
+/// Submits `verifications` to the verification pool right away. The returned
+/// future resolves once they are all done, or one of them fails, so the caller
+/// can do other work in the meantime.
+pub fn submit_all<E: Send + 'static>(
+    verifications: Vec<Verification<'static, E>>,
+) -> impl Future<Output = Result<(), E>> {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    pool().spawn(move || {
+        let _ = tx.send(run(verifications));
+    });
+    async move {
+        rx.await
+            .expect("The verification pool must not drop submitted verifications")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verifications(outcomes: &[bool]) -> Vec<Verification<'static, usize>> {
+        outcomes
+            .iter()
+            .copied()
+            .enumerate()
+            .map(|(i, valid)| -> Verification<'static, usize> {
+                Box::new(move || if valid { Ok(()) } else { Err(i) })
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn submit_all_reports_failures() {
+        assert_eq!(submit_all(verifications(&[true; 64])).await, Ok(()));
+        assert_eq!(
+            submit_all(verifications(&[true, true, false])).await,
+            Err(2)
+        );
+        assert_eq!(submit_all::<usize>(vec![]).await, Ok(()));
+    }
+}
@@ -3,13 +3,18 @@
 
 use std::{
     fmt,
-    ops::{Add, AddAssign, Deref, DerefMut, Mul, MulAssign, Sub, SubAssign},
+    iter::Sum,
+    ops::{Add, AddAssign, Deref, DerefMut, Mul, MulAssign, Neg, Sub, SubAssign},
+    str::FromStr,
 };
 
 use super::fvm_shared_latest::econ::TokenAmount as TokenAmount_latest;
+use anyhow::{bail, Context as _};
+use bigdecimal::BigDecimal;
 use fvm_shared2::econ::TokenAmount as TokenAmount_v2;
 use fvm_shared3::econ::TokenAmount as TokenAmount_v3;
 pub use fvm_shared3::{BLOCK_GAS_LIMIT, TOTAL_FILECOIN_BASE};
+use fvm_shared4::bigint::MAX_BIGINT_SIZE;
 use fvm_shared4::econ::TokenAmount as TokenAmount_v4;
 use num_bigint::BigInt;
 use num_traits::Zero;
@@ -103,10 +108,60 @@ impl TokenAmount {
         TokenAmount_v3::from_nano(nano).into()
     }
 
+    pub fn from_pico(pico: impl Into<BigInt>) -> Self {
+        TokenAmount::from_atto(pico.into() * ATTO_PER_PICO)
+    }
+
     pub fn from_whole(fil: impl Into<BigInt>) -> Self {
         TokenAmount_v3::from_whole(fil).into()
     }
 
+    /// Formats the amount in `FIL`, e.g., `1.5 FIL`. See [`FromStr`] for the
+    /// inverse.
+    pub fn to_fil_string(&self) -> String {
+        format!("{self} FIL")
+    }
+
+    /// Formats the amount in `attoFIL`, e.g., `1500 attoFIL`.
+    pub fn to_atto_string(&self) -> String {
+        format!("{} attoFIL", self.atto())
+    }
+
+    /// Returns `None` if the sum is negative or doesn't fit in the on-chain
+    /// encoding of token amounts.
+    pub fn checked_add(&self, other: &TokenAmount) -> Option<TokenAmount> {
+        (self + other).checked()
+    }
+
+    /// Returns `None` if the product is negative or doesn't fit in the on-chain
+    /// encoding of token amounts.
+    pub fn checked_mul(&self, other: impl Into<BigInt>) -> Option<TokenAmount> {
+        (self * other.into()).checked()
+    }
+
+    /// Returns `None` if `other` is larger than `self`, as token amounts are
+    /// not negative.
+    pub fn checked_sub(&self, other: &TokenAmount) -> Option<TokenAmount> {
+        (self >= other).then(|| self - other)
+    }
+
+    /// Returns zero if `other` is larger than `self`.
+    pub fn saturating_sub(&self, other: &TokenAmount) -> TokenAmount {
+        self.checked_sub(other).unwrap_or_default()
+    }
+
+    /// Returns `None` if `other` is zero.
+    pub fn checked_div_floor(&self, other: impl Into<BigInt>) -> Option<TokenAmount> {
+        let other = other.into();
+        (!other.is_zero()).then(|| self.div_floor(other))
+    }
+
+    fn checked(self) -> Option<TokenAmount> {
+        // The encoding is a sign byte followed by the big-endian magnitude.
+        (!self.is_negative() && self.atto().bits() <= (MAX_BIGINT_SIZE as u64 - 1) * 8)
+            .then_some(self)
+    }
+
     #[inline]
     pub fn div_rem(&self, other: impl Into<BigInt>) -> (TokenAmount, TokenAmount) {
         let (q, r) = self.0.div_rem(other);
@@ -124,6 +179,188 @@ impl TokenAmount {
     }
 }
 
+/// Number of `attoFIL` in a `picoFIL`.
+const ATTO_PER_PICO: u64 = 1_000_000;
+
+/// Units accepted by [`FromStr`], with their exponent relative to `FIL`.
+const UNITS: &[(&str, i64)] = &[
+    ("fil", 0),
+    ("nanofil", -9),
+    ("picofil", -12),
+    ("attofil", -18),
+];
+
+/// Parses decimal amounts of `FIL`, optionally followed by a unit, e.g.,
+/// `1.5`, `1.5 FIL`, `3 nanoFIL` or `1500 attoFIL`. Units are case-insensitive.
+/// Amounts that aren't a whole number of `attoFIL` are rejected rather than
+/// rounded, and so are negative amounts.
+impl FromStr for TokenAmount {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (number, unit) = match s.find(|c: char| c.is_ascii_alphabetic()) {
+            Some(i) => (s[..i].trim_end(), &s[i..]),
+            None => (s, "fil"),
+        };
+        let Some((_, exponent)) = UNITS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(unit))
+        else {
+            bail!("unknown unit {unit:?} in {s:?}");
+        };
+        let number =
+            BigDecimal::from_str(number).with_context(|| format!("invalid amount {s:?}"))?;
+        let atto = number * BigDecimal::new(1.into(), -(TokenAmount::DECIMALS as i64 + exponent));
+        let whole = atto.with_scale(0);
+        if whole != atto {
+            bail!("{s:?} is not a whole number of attoFIL");
+        }
+        let (atto, _) = whole.into_bigint_and_exponent();
+        let amount = TokenAmount::from_atto(atto);
+        if amount.is_negative() {
+            bail!("{s:?} is a negative amount");
+        }
+        Ok(amount)
+    }
+}
+
+/// (De)serializes token amounts as `FIL` strings, e.g., `"1.5 FIL"`, for
+/// human-readable JSON. Deserialization accepts the formats of [`FromStr`].
+///
+/// ```ignore
+/// #[serde(with = "crate::shim::econ::fil_string")]
+/// pub balance: TokenAmount,
+/// ```
+pub mod fil_string {
+    use super::TokenAmount;
+    use serde::{de::Error as _, Deserialize as _, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        amount: &TokenAmount,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&amount.to_fil_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<TokenAmount, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// A signed change of a token amount, e.g., a refund or a penalty, for
+/// computations that would otherwise make a [`TokenAmount`] negative. It is
+/// encoded like a [`TokenAmount`].
+#[derive(Clone, PartialEq, Eq, Ord, PartialOrd, Hash, Serialize, Deserialize, Default)]
+#[serde(transparent)]
+pub struct TokenDelta(TokenAmount);
+
+impl fmt::Debug for TokenDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for TokenDelta {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl TokenDelta {
+    pub fn from_atto(atto: impl Into<BigInt>) -> Self {
+        TokenDelta(TokenAmount::from_atto(atto))
+    }
+
+    /// The change from `from` to `to`.
+    pub fn between(from: &TokenAmount, to: &TokenAmount) -> Self {
+        TokenDelta::from_atto(to.atto() - from.atto())
+    }
+
+    pub fn atto(&self) -> &BigInt {
+        self.0.atto()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0.is_negative()
+    }
+
+    /// The size of the change.
+    pub fn abs(&self) -> TokenAmount {
+        TokenAmount::from_atto(self.atto().magnitude().clone())
+    }
+
+    /// Applies the change to `amount`, or returns `None` if that makes it
+    /// negative.
+    pub fn apply(&self, amount: &TokenAmount) -> Option<TokenAmount> {
+        let applied = amount + &self.0;
+        (!applied.is_negative()).then_some(applied)
+    }
+}
+
+impl Zero for TokenDelta {
+    fn zero() -> Self {
+        TokenDelta(TokenAmount::zero())
+    }
+    fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+}
+
+impl From<TokenAmount> for TokenDelta {
+    fn from(amount: TokenAmount) -> Self {
+        TokenDelta(amount)
+    }
+}
+
+impl TryFrom<TokenDelta> for TokenAmount {
+    type Error = anyhow::Error;
+
+    fn try_from(delta: TokenDelta) -> Result<Self, Self::Error> {
+        if delta.is_negative() {
+            bail!("negative token amount {delta}");
+        }
+        Ok(delta.0)
+    }
+}
+
+impl Add for TokenDelta {
+    type Output = TokenDelta;
+    fn add(self, rhs: TokenDelta) -> Self::Output {
+        TokenDelta(self.0 + rhs.0)
+    }
+}
+
+impl AddAssign for TokenDelta {
+    fn add_assign(&mut self, other: Self) {
+        self.0 += other.0
+    }
+}
+
+impl Sub for TokenDelta {
+    type Output = TokenDelta;
+    fn sub(self, rhs: TokenDelta) -> Self::Output {
+        TokenDelta::from_atto(self.atto() - rhs.atto())
+    }
+}
+
+impl SubAssign for TokenDelta {
+    fn sub_assign(&mut self, other: Self) {
+        *self = TokenDelta::from_atto(self.atto() - other.atto())
+    }
+}
+
+impl Neg for TokenDelta {
+    type Output = TokenDelta;
+    fn neg(self) -> Self::Output {
+        TokenDelta::from_atto(-self.atto())
+    }
+}
+
 impl From<TokenAmount_v2> for TokenAmount {
     fn from(other: TokenAmount_v2) -> Self {
         (&other).into()
@@ -297,3 +534,148 @@ impl Sub<TokenAmount> for &TokenAmount {
         (&self.0).sub(&rhs.0).into()
     }
 }
+
+impl Sub<TokenAmount> for TokenAmount {
+    type Output = TokenAmount;
+    fn sub(self, rhs: TokenAmount) -> Self::Output {
+        (&self.0).sub(&rhs.0).into()
+    }
+}
+
+impl Sub<&TokenAmount> for &TokenAmount {
+    type Output = TokenAmount;
+    fn sub(self, rhs: &TokenAmount) -> Self::Output {
+        (&self.0).sub(&rhs.0).into()
+    }
+}
+
+impl Sum for TokenAmount {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(TokenAmount::zero(), Add::add)
+    }
+}
+
+impl<'a> Sum<&'a TokenAmount> for TokenAmount {
+    fn sum<I: Iterator<Item = &'a Self>>(iter: I) -> Self {
+        iter.fold(TokenAmount::zero(), |acc, amount| acc + amount)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_fil_strings() {
+        for (s, atto) in [
+            ("1.5", 1_500_000_000_000_000_000u64),
+            ("1.5 FIL", 1_500_000_000_000_000_000),
+            ("1.5fil", 1_500_000_000_000_000_000),
+            ("0.000000000000000001 FIL", 1),
+            ("3 nanoFIL", 3_000_000_000),
+            ("3 picoFIL", 3_000_000),
+            ("1500 attoFIL", 1500),
+            (" 0 FIL ", 0),
+        ] {
+            assert_eq!(
+                s.parse::<TokenAmount>().unwrap(),
+                TokenAmount::from_atto(atto),
+                "{s}"
+            );
+        }
+        for s in [
+            "",
+            "FIL",
+            "1.5 BTC",
+            "0.5 attoFIL",
+            "1.0000000000000000001",
+            "-2 FIL",
+            "-1 attoFIL",
+        ] {
+            assert!(s.parse::<TokenAmount>().is_err(), "{s}");
+        }
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn fil_string_roundtrip(amount: TokenAmount) {
+        assert_eq!(
+            amount.to_fil_string().parse::<TokenAmount>().unwrap(),
+            amount
+        );
+        assert_eq!(
+            amount.to_atto_string().parse::<TokenAmount>().unwrap(),
+            amount
+        );
+    }
+
+    #[test]
+    fn fil_string_serde() {
+        #[derive(Serialize, Deserialize)]
+        struct Balance(#[serde(with = "fil_string")] TokenAmount);
+
+        let json = serde_json::to_string(&Balance(TokenAmount::from_nano(1_500_000_000))).unwrap();
+        assert_eq!(json, r#""1.5 FIL""#);
+        let Balance(amount) = serde_json::from_str(r#""1500 picoFIL""#).unwrap();
+        assert_eq!(amount, TokenAmount::from_pico(1500));
+    }
+
+    #[test]
+    fn checked_sub() {
+        let (one, two) = (TokenAmount::from_whole(1), TokenAmount::from_whole(2));
+        assert_eq!(two.checked_sub(&one), Some(one.clone()));
+        assert_eq!(one.checked_sub(&one), Some(TokenAmount::zero()));
+        assert!(one.checked_sub(&two).is_none());
+        assert_eq!(two.saturating_sub(&one), one);
+        assert_eq!(one.saturating_sub(&two), TokenAmount::zero());
+    }
+
+    #[test]
+    fn token_delta() {
+        let (one, two) = (TokenAmount::from_whole(1), TokenAmount::from_whole(2));
+        let penalty = TokenDelta::between(&two, &one);
+        assert!(penalty.is_negative());
+        assert_eq!(penalty.abs(), one);
+        assert_eq!(penalty.apply(&two), Some(one.clone()));
+        assert!(penalty.apply(&TokenAmount::from_nano(1)).is_none());
+        assert_eq!(TokenAmount::try_from(-penalty.clone()).unwrap(), one);
+        assert!(TokenAmount::try_from(penalty).is_err());
+    }
+
+    #[quickcheck_macros::quickcheck]
+    fn token_delta_encoding(amount: TokenAmount) {
+        let delta = TokenDelta::from(amount.clone());
+        let bytes = fvm_ipld_encoding::to_vec(&delta).unwrap();
+        assert_eq!(bytes, fvm_ipld_encoding::to_vec(&amount).unwrap());
+        assert_eq!(
+            fvm_ipld_encoding::from_slice::<TokenAmount>(&bytes).unwrap(),
+            amount
+        );
+        let negated = -delta;
+        let bytes = fvm_ipld_encoding::to_vec(&negated).unwrap();
+        assert_eq!(
+            fvm_ipld_encoding::from_slice::<TokenDelta>(&bytes).unwrap(),
+            negated
+        );
+    }
+
+    #[test]
+    fn checked_arithmetic() {
+        let max = TokenAmount::from_atto((BigInt::from(1) << ((MAX_BIGINT_SIZE - 1) * 8)) - 1);
+        assert_eq!(max.checked_add(&TokenAmount::zero()), Some(max.clone()));
+        assert!(max.checked_add(&TokenAmount::from_atto(1)).is_none());
+        assert!(max.checked_mul(2).is_none());
+        assert_eq!(
+            TokenAmount::from_whole(3).checked_mul(2),
+            Some(TokenAmount::from_whole(6))
+        );
+        assert_eq!(
+            TokenAmount::from_atto(7).checked_div_floor(2),
+            Some(TokenAmount::from_atto(3))
+        );
+        assert!(TokenAmount::from_atto(7).checked_div_floor(0).is_none());
+        assert!(TokenAmount::from_whole(3).checked_mul(-2).is_none());
+        assert!(TokenAmount::from_whole(3)
+            .checked_add(&TokenAmount::from_whole(-4))
+            .is_none());
+    }
+}
@@ -4,7 +4,7 @@
 use super::trace::ExecutionEvent;
 use crate::shim::econ::TokenAmount;
 use cid::Cid;
-use fil_actors_shared::fvm_ipld_amt::Amtv0;
+use fil_actors_shared::fvm_ipld_amt::{Amt, Amtv0};
 use fvm2::executor::ApplyRet as ApplyRet_v2;
 use fvm3::executor::ApplyRet as ApplyRet_v3;
 use fvm4::executor::ApplyRet as ApplyRet_v4;
@@ -13,6 +13,7 @@ use fvm_ipld_encoding::RawBytes;
 use fvm_shared2::receipt::Receipt as Receipt_v2;
 use fvm_shared3::error::ExitCode;
 pub use fvm_shared3::receipt::Receipt as Receipt_v3;
+pub use fvm_shared4::event::{ActorEvent, Entry, Flags, StampedEvent};
 use fvm_shared4::receipt::Receipt as Receipt_v4;
 use serde::Serialize;
 
@@ -99,6 +100,35 @@ impl ApplyRet {
         }
     }
 
+    /// Returns the events emitted by the actors while executing the message.
+    /// The events of all the versions are encoded alike.
+    pub fn events(&self) -> Vec<StampedEvent> {
+        match self {
+            ApplyRet::V2(_) => vec![],
+            ApplyRet::V3(v3) => v3
+                .events
+                .iter()
+                .map(|stamped| StampedEvent {
+                    emitter: stamped.emitter,
+                    event: ActorEvent {
+                        entries: stamped
+                            .event
+                            .entries
+                            .iter()
+                            .map(|entry| Entry {
+                                flags: Flags::from_bits_truncate(entry.flags.bits()),
+                                key: entry.key.clone(),
+                                codec: entry.codec,
+                                value: entry.value.clone(),
+                            })
+                            .collect(),
+                    },
+                })
+                .collect(),
+            ApplyRet::V4(v4) => v4.events.clone(),
+        }
+    }
+
     pub fn exec_trace(&self) -> Vec<ExecutionEvent> {
         match self {
             ApplyRet::V2(v2) => v2.exec_trace.iter().cloned().map(Into::into).collect(),
@@ -167,6 +197,57 @@ impl Receipt {
         let receipts = amt.get(i)?;
         Ok(receipts.cloned().map(Receipt::V4))
     }
+
+    /// Returns all the receipts of the `receipts` AMT, in order.
+    pub fn get_receipts(db: &impl Blockstore, receipts: &Cid) -> anyhow::Result<Vec<Self>> {
+        // Try Receipt_v2 first
+        if let Ok(amt) = Amtv0::<Receipt_v2, _>::load(receipts, db) {
+            let mut v2 = Vec::new();
+            if amt
+                .for_each(|_, receipt| {
+                    v2.push(Receipt::V2(receipt.clone()));
+                    Ok(())
+                })
+                .is_ok()
+            {
+                return Ok(v2);
+            }
+        }
+
+        // Receipt_v4 and Receipt_v3 are identical, use v4 here
+        let mut v4 = Vec::new();
+        Amtv0::<Receipt_v4, _>::load(receipts, db)?.for_each(|_, receipt| {
+            v4.push(Receipt::V4(receipt.clone()));
+            Ok(())
+        })?;
+        Ok(v4)
+    }
+}
+
+/// Bit width of the AMTs of the events emitted while executing a message, as
+/// in the FVM.
+pub const EVENTS_AMT_BITWIDTH: u32 = 5;
+
+/// Writes the events emitted while executing a message to `db`, as the AMT
+/// the `events_root` of its receipt refers to, and returns its root. The FVM
+/// doesn't write them.
+pub fn store_events(
+    db: &impl Blockstore,
+    events: impl IntoIterator<Item = StampedEvent>,
+) -> anyhow::Result<Cid> {
+    let mut amt = Amt::new_with_bit_width(db, EVENTS_AMT_BITWIDTH);
+    amt.batch_set(events)?;
+    Ok(amt.flush()?)
+}
+
+/// Loads the events the `events_root` of a receipt refers to.
+pub fn load_events(db: &impl Blockstore, events_root: &Cid) -> anyhow::Result<Vec<StampedEvent>> {
+    let mut events = Vec::new();
+    Amt::<StampedEvent, _>::load(events_root, db)?.for_each(|_, event| {
+        events.push(event.clone());
+        Ok(())
+    })?;
+    Ok(events)
 }
 
 impl From<Receipt_v3> for Receipt {
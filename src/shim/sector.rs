@@ -62,6 +62,14 @@ impl RegisteredSealProof {
     }
 }
 
+impl TryFrom<RegisteredSealProof> for filecoin_proofs_api::RegisteredSealProof {
+    type Error = anyhow::Error;
+
+    fn try_from(value: RegisteredSealProof) -> Result<Self, Self::Error> {
+        value.0.try_into().map_err(|e: String| anyhow::anyhow!(e))
+    }
+}
+
 impl Deref for RegisteredSealProof {
     type Target = RegisteredSealProofV3;
     fn deref(&self) -> &Self::Target {
@@ -0,0 +1,147 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Cache of the actors looked up in recent state trees. State trees are immutable, so the
+//! cached actors of a state root never go stale: the cache only keeps the actors of the most
+//! recently used state roots, dropping those of older roots as new roots are looked up.
+
+use std::num::NonZeroUsize;
+
+use crate::metrics;
+use crate::shim::{address::Address, state_tree::ActorState};
+use cid::Cid;
+use lru::LruCache;
+use nonzero_ext::nonzero;
+use parking_lot::Mutex;
+
+/// Number of state roots whose actors are cached.
+const STATE_ROOTS: NonZeroUsize = nonzero!(8usize);
+/// Number of actors cached for each state root.
+const ACTORS_PER_STATE_ROOT: NonZeroUsize = nonzero!(1024usize);
+
+type RootActors = LruCache<Address, Option<ActorState>>;
+
+pub(in crate::state_manager) struct ActorCache {
+    roots: Mutex<LruCache<Cid, RootActors>>,
+}
+
+impl ActorCache {
+    pub fn new() -> Self {
+        Self {
+            roots: Mutex::new(LruCache::new(STATE_ROOTS)),
+        }
+    }
+
+    /// Returns the actor at `addr` in the state tree of `state_root`, calling `load` if it isn't
+    /// cached.
+    pub fn get_or_else(
+        &self,
+        state_root: Cid,
+        addr: &Address,
+        load: impl FnOnce() -> anyhow::Result<Option<ActorState>>,
+    ) -> anyhow::Result<Option<ActorState>> {
+        if let Some(actor) = self.get(state_root, addr) {
+            metrics::LRU_CACHE_HIT
+                .with_label_values(&[metrics::values::STATE_MANAGER_ACTOR])
+                .inc();
+            return Ok(actor);
+        }
+        metrics::LRU_CACHE_MISS
+            .with_label_values(&[metrics::values::STATE_MANAGER_ACTOR])
+            .inc();
+
+        // Don't hold the lock while loading, the lookup may be slow.
+        let actor = load()?;
+        self.roots
+            .lock()
+            .get_or_insert_mut(state_root, || LruCache::new(ACTORS_PER_STATE_ROOT))
+            .put(*addr, actor.clone());
+        Ok(actor)
+    }
+
+    fn get(&self, state_root: Cid, addr: &Address) -> Option<Option<ActorState>> {
+        self.roots.lock().get_mut(&state_root)?.get(addr).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cid::multihash::{Code, MultihashDigest as _};
+    use fvm_ipld_encoding::DAG_CBOR;
+
+    fn state_root(i: u64) -> Cid {
+        Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(&i.to_be_bytes()))
+    }
+
+    fn actor(balance: u64) -> ActorState {
+        ActorState::new(
+            Cid::default(),
+            Cid::default(),
+            crate::shim::econ::TokenAmount::from_atto(balance),
+            0,
+            None,
+        )
+    }
+
+    #[test]
+    fn loads_each_actor_once() {
+        let cache = ActorCache::new();
+        let mut loads = 0;
+        for _ in 0..3 {
+            let loaded = cache
+                .get_or_else(state_root(0), &Address::POWER_ACTOR, || {
+                    loads += 1;
+                    Ok(Some(actor(42)))
+                })
+                .unwrap();
+            assert_eq!(loaded, Some(actor(42)));
+        }
+        // Missing actors are cached too.
+        for _ in 0..3 {
+            let loaded = cache
+                .get_or_else(state_root(0), &Address::new_id(1000), || {
+                    loads += 1;
+                    Ok(None)
+                })
+                .unwrap();
+            assert_eq!(loaded, None);
+        }
+        assert_eq!(loads, 2);
+    }
+
+    #[test]
+    fn actors_are_cached_per_state_root() {
+        let cache = ActorCache::new();
+        cache
+            .get_or_else(state_root(0), &Address::POWER_ACTOR, || Ok(Some(actor(1))))
+            .unwrap();
+        let loaded = cache
+            .get_or_else(state_root(1), &Address::POWER_ACTOR, || Ok(Some(actor(2))))
+            .unwrap();
+        assert_eq!(loaded, Some(actor(2)));
+
+        // Looking up new state roots drops the actors of the oldest ones.
+        for i in 2..=STATE_ROOTS.get() as u64 {
+            cache
+                .get_or_else(state_root(i), &Address::POWER_ACTOR, || Ok(None))
+                .unwrap();
+        }
+        assert!(cache.get(state_root(0), &Address::POWER_ACTOR).is_none());
+        assert_eq!(
+            cache.get(state_root(1), &Address::POWER_ACTOR),
+            Some(Some(actor(2)))
+        );
+    }
+
+    #[test]
+    fn errors_are_not_cached() {
+        let cache = ActorCache::new();
+        assert!(cache
+            .get_or_else(state_root(0), &Address::POWER_ACTOR, || anyhow::bail!(
+                "Cthulhu fhtagn"
+            ))
+            .is_err());
+        assert!(cache.get(state_root(0), &Address::POWER_ACTOR).is_none());
+    }
+}
@@ -0,0 +1,362 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Notifications for watched actors whose head (state root) changes between
+//! tipsets. Heads are diffed as new heads are applied to the chain store, so
+//! consumers don't need to poll `StateGetActor` every epoch.
+//!
+//! Subscribers that don't keep up lose changes rather than queueing them
+//! without bound, subscriptions that aren't polled expire, and the number of
+//! subscriptions is capped per caller and in total.
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
+
+use crate::blocks::{Tipset, TipsetKey};
+use crate::chain::HeadChange;
+use crate::lotus_json::lotus_json_with_self;
+use crate::shim::{address::Address, clock::ChainEpoch};
+use ahash::{HashMap, HashMapExt};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use parking_lot::Mutex as SyncMutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{
+    broadcast::error::RecvError,
+    mpsc::{self, error::TrySendError},
+    Mutex as TokioMutex,
+};
+use tracing::warn;
+
+use super::StateManager;
+
+/// Number of changes queued for a subscriber, beyond which changes are dropped.
+const CHANGES_CAPACITY: usize = 256;
+
+/// Time after which a subscription that isn't polled is removed.
+const SUBSCRIPTION_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// Maximum number of subscriptions held by a single caller.
+const MAX_SUBSCRIPTIONS_PER_CALLER: usize = 16;
+
+/// Maximum number of subscriptions held by all the callers.
+const MAX_SUBSCRIPTIONS: usize = 1024;
+
+/// A change of a watched actor's head, observed when `tipset` became the
+/// heaviest tipset. A `None` head means the actor did not exist.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ActorChange {
+    #[serde(with = "crate::lotus_json")]
+    pub address: Address,
+    pub epoch: ChainEpoch,
+    #[serde(with = "crate::lotus_json")]
+    pub tipset: TipsetKey,
+    #[serde(with = "crate::lotus_json")]
+    pub old_head: Option<Cid>,
+    #[serde(with = "crate::lotus_json")]
+    pub new_head: Option<Cid>,
+}
+
+lotus_json_with_self!(ActorChange);
+
+/// Tracks the last observed head of each watched actor.
+struct ActorHeads {
+    heads: HashMap<Address, Option<Cid>>,
+}
+
+impl ActorHeads {
+    fn new(initial: impl IntoIterator<Item = (Address, Option<Cid>)>) -> Self {
+        Self {
+            heads: initial.into_iter().collect(),
+        }
+    }
+
+    /// Records the head of `address` at `tipset`, returning a change if it
+    /// differs from the previously observed one.
+    fn update(
+        &mut self,
+        address: Address,
+        new_head: Option<Cid>,
+        epoch: ChainEpoch,
+        tipset: &TipsetKey,
+    ) -> Option<ActorChange> {
+        let old_head = self.heads.insert(address, new_head).flatten();
+        (old_head != new_head).then(|| ActorChange {
+            address,
+            epoch,
+            tipset: tipset.clone(),
+            old_head,
+            new_head,
+        })
+    }
+}
+
+impl<DB> StateManager<DB>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    fn actor_head(&self, address: &Address, ts: &Tipset) -> anyhow::Result<Option<Cid>> {
+        Ok(self
+            .get_actor(address, *ts.parent_state())?
+            .map(|actor| actor.state))
+    }
+
+    /// Subscribes to head changes of the given actors. Heads are compared
+    /// against the parent state of every tipset applied to the chain store,
+    /// starting from the current heaviest tipset. The watcher stops once the
+    /// returned receiver is dropped, and drops the changes that don't fit in
+    /// the receiver.
+    pub fn subscribe_actor_changes(
+        self: &Arc<Self>,
+        addresses: Vec<Address>,
+    ) -> anyhow::Result<mpsc::Receiver<ActorChange>> {
+        let mut subscriber = self.cs.publisher().subscribe();
+        let head = self.cs.heaviest_tipset();
+        let mut heads = ActorHeads::new(
+            addresses
+                .iter()
+                .map(|addr| Ok((*addr, self.actor_head(addr, &head)?)))
+                .collect::<anyhow::Result<Vec<_>>>()?,
+        );
+
+        let (sender, receiver) = mpsc::channel(CHANGES_CAPACITY);
+        let sm = Arc::clone(self);
+        tokio::task::spawn(async move {
+            loop {
+                let ts = tokio::select! {
+                    _ = sender.closed() => break,
+                    head_change = subscriber.recv() => match head_change {
+                        Ok(HeadChange::Apply(ts)) => ts,
+                        Err(RecvError::Lagged(i)) => {
+                            warn!(
+                                "actor changes head change subscriber lagged, skipped {} events",
+                                i
+                            );
+                            continue;
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                };
+                for addr in addresses.iter() {
+                    let new_head = match sm.actor_head(addr, &ts) {
+                        Ok(head) => head,
+                        Err(e) => {
+                            warn!("failed to load actor {addr} at epoch {}: {e}", ts.epoch());
+                            continue;
+                        }
+                    };
+                    if let Some(change) = heads.update(*addr, new_head, ts.epoch(), ts.key()) {
+                        match sender.try_send(change) {
+                            Ok(()) => {}
+                            Err(TrySendError::Full(change)) => warn!(
+                                "actor changes subscriber is full, dropped the change of {} at epoch {}",
+                                change.address, change.epoch
+                            ),
+                            Err(TrySendError::Closed(_)) => return,
+                        }
+                    }
+                }
+            }
+        });
+        Ok(receiver)
+    }
+
+    /// Periodically removes the actor change subscriptions that haven't been
+    /// polled for [`SUBSCRIPTION_TTL`], stopping their watchers.
+    pub async fn expire_actor_subscriptions_loop(self: Arc<Self>) -> anyhow::Result<()> {
+        let mut interval = tokio::time::interval(SUBSCRIPTION_TTL / 10);
+        loop {
+            interval.tick().await;
+            self.actor_subscriptions().remove_idle(SUBSCRIPTION_TTL);
+        }
+    }
+}
+
+type ActorChangeReceiver = Arc<TokioMutex<mpsc::Receiver<ActorChange>>>;
+
+struct Subscription {
+    caller: String,
+    receiver: ActorChangeReceiver,
+    last_polled: Instant,
+}
+
+/// Registry of actor change subscriptions, used to expose them as a channel
+/// over RPC.
+#[derive(Default)]
+pub struct ActorChangeSubscriptions {
+    next_id: AtomicU64,
+    subscriptions: SyncMutex<HashMap<u64, Subscription>>,
+}
+
+impl ActorChangeSubscriptions {
+    /// Registers a subscription of `caller` and returns its identifier.
+    /// Fails, stopping the watcher of `receiver`, if `caller` or all the
+    /// callers already hold too many subscriptions.
+    pub fn insert(
+        &self,
+        caller: String,
+        receiver: mpsc::Receiver<ActorChange>,
+    ) -> anyhow::Result<u64> {
+        self.remove_idle(SUBSCRIPTION_TTL);
+        let mut subscriptions = self.subscriptions.lock();
+        anyhow::ensure!(
+            subscriptions.len() < MAX_SUBSCRIPTIONS,
+            "too many actor changes subscriptions"
+        );
+        anyhow::ensure!(
+            subscriptions
+                .values()
+                .filter(|subscription| subscription.caller == caller)
+                .count()
+                < MAX_SUBSCRIPTIONS_PER_CALLER,
+            "too many actor changes subscriptions for {caller}"
+        );
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        subscriptions.insert(
+            id,
+            Subscription {
+                caller,
+                receiver: Arc::new(TokioMutex::new(receiver)),
+                last_polled: Instant::now(),
+            },
+        );
+        Ok(id)
+    }
+
+    /// Removes the subscriptions that aren't being polled and haven't been
+    /// for `ttl`, stopping their watchers.
+    fn remove_idle(&self, ttl: Duration) {
+        self.subscriptions.lock().retain(|_, subscription| {
+            Arc::strong_count(&subscription.receiver) > 1
+                || subscription.last_polled.elapsed() < ttl
+        });
+    }
+
+    /// Waits for at least one change on the subscription and returns all the
+    /// changes queued so far. Returns `None` if the subscription does not
+    /// exist or its watcher has stopped.
+    pub async fn next_changes(&self, id: u64) -> Option<Vec<ActorChange>> {
+        let receiver = self.touch(id)?;
+        let mut receiver = receiver.lock().await;
+        let Some(first) = receiver.recv().await else {
+            self.remove(id);
+            return None;
+        };
+        let mut changes = vec![first];
+        while let Ok(change) = receiver.try_recv() {
+            changes.push(change);
+        }
+        self.touch(id);
+        Some(changes)
+    }
+
+    /// Marks the subscription as polled and returns its receiver.
+    fn touch(&self, id: u64) -> Option<ActorChangeReceiver> {
+        let mut subscriptions = self.subscriptions.lock();
+        let subscription = subscriptions.get_mut(&id)?;
+        subscription.last_polled = Instant::now();
+        Some(Arc::clone(&subscription.receiver))
+    }
+
+    /// Drops the subscription, stopping its watcher. Returns whether it
+    /// existed.
+    pub fn remove(&self, id: u64) -> bool {
+        self.subscriptions.lock().remove(&id).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cid::multihash::{Code::Identity, MultihashDigest};
+    use fvm_ipld_encoding::DAG_CBOR;
+
+    #[test]
+    fn actor_heads_report_only_changes() {
+        let addr = Address::new_id(1000);
+        let head_a = Cid::new_v1(DAG_CBOR, Identity.digest(&[1]));
+        let head_b = Cid::new_v1(DAG_CBOR, Identity.digest(&[2]));
+        let tsk = TipsetKey::default();
+        let mut heads = ActorHeads::new([(addr, Some(head_a))]);
+
+        assert_eq!(heads.update(addr, Some(head_a), 1, &tsk), None);
+        let change = heads.update(addr, Some(head_b), 2, &tsk).unwrap();
+        assert_eq!(change.old_head, Some(head_a));
+        assert_eq!(change.new_head, Some(head_b));
+        assert_eq!(change.epoch, 2);
+
+        let change = heads.update(addr, None, 3, &tsk).unwrap();
+        assert_eq!(change.old_head, Some(head_b));
+        assert_eq!(change.new_head, None);
+        assert_eq!(heads.update(addr, None, 4, &tsk), None);
+    }
+
+    #[tokio::test]
+    async fn subscriptions_drain_queued_changes() {
+        let subscriptions = ActorChangeSubscriptions::default();
+        let (sender, receiver) = mpsc::channel(CHANGES_CAPACITY);
+        let id = subscriptions.insert(String::new(), receiver).unwrap();
+        let change = ActorChange {
+            address: Address::new_id(1000),
+            epoch: 1,
+            tipset: TipsetKey::default(),
+            old_head: None,
+            new_head: None,
+        };
+        sender.try_send(change.clone()).unwrap();
+        sender.try_send(change.clone()).unwrap();
+        assert_eq!(
+            subscriptions.next_changes(id).await,
+            Some(vec![change.clone(), change])
+        );
+
+        drop(sender);
+        assert_eq!(subscriptions.next_changes(id).await, None);
+        assert!(!subscriptions.remove(id));
+    }
+
+    #[test]
+    fn idle_subscriptions_expire() {
+        let subscriptions = ActorChangeSubscriptions::default();
+        let (_sender, receiver) = mpsc::channel(CHANGES_CAPACITY);
+        let id = subscriptions.insert(String::new(), receiver).unwrap();
+        subscriptions.remove_idle(SUBSCRIPTION_TTL);
+        assert!(subscriptions.touch(id).is_some());
+
+        // A subscription being polled doesn't expire.
+        let polled = subscriptions.touch(id).unwrap();
+        subscriptions.remove_idle(Duration::ZERO);
+        drop(polled);
+        assert!(subscriptions.touch(id).is_some());
+
+        subscriptions.remove_idle(Duration::ZERO);
+        assert!(!subscriptions.remove(id));
+    }
+
+    #[test]
+    fn subscriptions_are_capped() {
+        let subscriptions = ActorChangeSubscriptions::default();
+        let mut senders = vec![];
+        let mut subscribe = |caller: &str| {
+            let (sender, receiver) = mpsc::channel(CHANGES_CAPACITY);
+            senders.push(sender);
+            subscriptions.insert(caller.into(), receiver)
+        };
+        for _ in 0..MAX_SUBSCRIPTIONS_PER_CALLER {
+            subscribe("alice").unwrap();
+        }
+        assert!(subscribe("alice").is_err());
+        for i in MAX_SUBSCRIPTIONS_PER_CALLER..MAX_SUBSCRIPTIONS {
+            subscribe(&format!("caller {i}")).unwrap();
+        }
+        assert!(subscribe("bob").is_err());
+
+        // Refused subscriptions stop their watchers.
+        assert!(senders.last().unwrap().is_closed());
+        assert!(!senders.first().unwrap().is_closed());
+    }
+}
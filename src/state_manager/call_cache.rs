@@ -0,0 +1,136 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Cache of the results of `StateCall`s. Calls don't persist their changes, so calling the same
+//! message on the same tipset gives the same result, and frontends tend to repeat their calls on
+//! the head. The cache is emptied when the head changes, as calls then move to the new head.
+
+use std::num::NonZeroUsize;
+
+use crate::blocks::TipsetKey;
+use crate::metrics;
+use cid::Cid;
+use lru::LruCache;
+use nonzero_ext::nonzero;
+use parking_lot::Mutex;
+
+/// Number of call results that are cached.
+const CALL_RESULTS: NonZeroUsize = nonzero!(256usize);
+
+pub(in crate::state_manager) struct CallCache<T> {
+    inner: Mutex<Inner<T>>,
+}
+
+struct Inner<T> {
+    /// Head of the chain when the cached calls were made.
+    head: Option<TipsetKey>,
+    results: LruCache<(TipsetKey, Cid), T>,
+}
+
+impl<T: Clone> CallCache<T> {
+    pub fn new() -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                head: None,
+                results: LruCache::new(CALL_RESULTS),
+            }),
+        }
+    }
+
+    /// Returns the result of calling the message `msg` on `tipset`, calling `call` if it isn't
+    /// cached. Failed calls aren't cached.
+    pub fn get_or_else<E>(
+        &self,
+        head: &TipsetKey,
+        tipset: &TipsetKey,
+        msg: Cid,
+        call: impl FnOnce() -> Result<T, E>,
+    ) -> Result<T, E> {
+        let key = (tipset.clone(), msg);
+        if let Some(result) = self.get(head, &key) {
+            metrics::LRU_CACHE_HIT
+                .with_label_values(&[metrics::values::STATE_MANAGER_CALL])
+                .inc();
+            return Ok(result);
+        }
+        metrics::LRU_CACHE_MISS
+            .with_label_values(&[metrics::values::STATE_MANAGER_CALL])
+            .inc();
+
+        // Don't hold the lock while calling, the execution may be slow.
+        let result = call()?;
+        let mut inner = self.inner.lock();
+        // Don't cache calls that raced with a head change.
+        if inner.head.as_ref() == Some(head) {
+            inner.results.put(key, result.clone());
+        }
+        Ok(result)
+    }
+
+    fn get(&self, head: &TipsetKey, key: &(TipsetKey, Cid)) -> Option<T> {
+        let mut inner = self.inner.lock();
+        if inner.head.as_ref() != Some(head) {
+            inner.head = Some(head.clone());
+            inner.results.clear();
+            return None;
+        }
+        inner.results.get(key).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{CachingBlockHeader, RawBlockHeader, Tipset};
+    use crate::shim::address::Address;
+    use cid::multihash::{Code, MultihashDigest as _};
+    use fvm_ipld_encoding::DAG_CBOR;
+
+    fn tipset_key(miner: u64) -> TipsetKey {
+        Tipset::from(CachingBlockHeader::new(RawBlockHeader {
+            miner_address: Address::new_id(miner),
+            ..Default::default()
+        }))
+        .key()
+        .clone()
+    }
+
+    fn msg(i: u64) -> Cid {
+        Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(&i.to_be_bytes()))
+    }
+
+    #[test]
+    fn calls_are_cached_until_the_head_changes() {
+        let cache = CallCache::new();
+        let (head, new_head) = (tipset_key(1000), tipset_key(1001));
+        let mut calls = 0;
+        let mut call = |head: &TipsetKey, msg| {
+            cache
+                .get_or_else(head, head, msg, || {
+                    calls += 1;
+                    Ok::<_, ()>(calls)
+                })
+                .unwrap()
+        };
+
+        assert_eq!(call(&head, msg(0)), 1);
+        assert_eq!(call(&head, msg(0)), 1);
+        assert_eq!(call(&head, msg(1)), 2);
+        assert_eq!(call(&new_head, msg(0)), 3);
+        // The results of the previous head are gone.
+        assert_eq!(call(&head, msg(0)), 4);
+    }
+
+    #[test]
+    fn errors_are_not_cached() {
+        let cache = CallCache::<u64>::new();
+        let head = tipset_key(1000);
+        assert!(cache
+            .get_or_else(&head, &head, msg(0), || Err("Cthulhu fhtagn"))
+            .is_err());
+        assert_eq!(
+            cache.get_or_else(&head, &head, msg(0), || Ok::<_, ()>(42)),
+            Ok(42)
+        );
+    }
+}
@@ -0,0 +1,334 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Pledge and collateral requirements of storage providers. They are computed from the power and
+//! reward actor states with the functions of the actors matching the actors version of the state.
+
+use super::{vm_circ_supply::GenesisInfo, StateManager};
+use crate::blocks::Tipset;
+use crate::rpc_api::data_types::{DealCollateralBounds, SectorPreCommitInfo};
+use crate::shim::{address::Address, clock::ChainEpoch, econ::TokenAmount, sector::StoragePower};
+use anyhow::Context as _;
+use fil_actor_interface::{market, power, reward};
+use fvm_ipld_blockstore::Blockstore;
+use num::BigInt;
+use num_traits::Zero;
+
+/// Lotus over-estimates the pledge requirements by 10%, for the requirements to still be met if
+/// the network grows between the estimation and the inclusion of the message.
+const INITIAL_PLEDGE_NUM: u64 = 110;
+const INITIAL_PLEDGE_DEN: u64 = 100;
+
+/// Lotus over-estimates the minimum provider collateral by 10% as well.
+const DEAL_PROVIDER_COLLATERAL_NUM: u64 = 110;
+const DEAL_PROVIDER_COLLATERAL_DEN: u64 = 100;
+
+/// Share of the circulating supply the providers should lock as collateral, for deals filling up
+/// the network.
+const PROV_COLLATERAL_PERCENT_SUPPLY_NUM: u64 = 1;
+const PROV_COLLATERAL_PERCENT_SUPPLY_DEN: u64 = 100;
+
+/// Total amount of FIL, the maximum provider collateral.
+const TOTAL_FILECOIN: u64 = 2_000_000_000;
+
+const SECTOR_QUALITY_PRECISION: i64 = 20;
+const QUALITY_BASE_MULTIPLIER: i64 = 10;
+const DEAL_WEIGHT_MULTIPLIER: i64 = 10;
+const VERIFIED_DEAL_WEIGHT_MULTIPLIER: i64 = 100;
+
+/// Evaluates `$body` with the reward and power states `$reward` and `$power` of the same actors
+/// version, and the miner actor of that version in scope as `miner_actor`.
+macro_rules! with_reward_and_power {
+    ($reward:expr, $power:expr, |$r:ident, $p:ident| $body:expr) => {
+        match ($reward, $power) {
+            (reward::State::V8($r), power::State::V8($p)) => {
+                use fil_actor_miner_state::v8 as miner_actor;
+                Ok($body)
+            }
+            (reward::State::V9($r), power::State::V9($p)) => {
+                use fil_actor_miner_state::v9 as miner_actor;
+                Ok($body)
+            }
+            (reward::State::V10($r), power::State::V10($p)) => {
+                use fil_actor_miner_state::v10 as miner_actor;
+                Ok($body)
+            }
+            (reward::State::V11($r), power::State::V11($p)) => {
+                use fil_actor_miner_state::v11 as miner_actor;
+                Ok($body)
+            }
+            (reward::State::V12($r), power::State::V12($p)) => {
+                use fil_actor_miner_state::v12 as miner_actor;
+                Ok($body)
+            }
+            _ => Err(anyhow::anyhow!(
+                "reward and power actor states are of different actors versions"
+            )),
+        }
+    };
+}
+
+impl<DB: Blockstore> StateManager<DB> {
+    /// Returns the deposit required to pre-commit `sector` on miner `address`, at the parent
+    /// state of `ts`.
+    pub fn miner_pre_commit_deposit_for_power(
+        &self,
+        address: &Address,
+        sector: &SectorPreCommitInfo,
+        ts: &Tipset,
+    ) -> anyhow::Result<TokenAmount> {
+        let sector_weight = self.pre_commit_sector_weight(address, sector, ts)?;
+        let (reward_state, power_state) = self.reward_and_power_states(ts)?;
+        let deposit: TokenAmount = with_reward_and_power!(&reward_state, &power_state, |r, p| {
+            miner_actor::pre_commit_deposit_for_power(
+                &r.this_epoch_reward_smoothed,
+                &p.this_epoch_qa_power_smoothed,
+                &sector_weight,
+            )
+            .into()
+        })?;
+        Ok(with_pledge_margin(&deposit))
+    }
+
+    /// Returns the collateral to pledge when committing `sector` on miner `address`, at the
+    /// parent state of `ts`.
+    pub fn miner_initial_pledge_collateral(
+        &self,
+        address: &Address,
+        sector: &SectorPreCommitInfo,
+        ts: &Tipset,
+    ) -> anyhow::Result<TokenAmount> {
+        let sector_weight = self.pre_commit_sector_weight(address, sector, ts)?;
+        let (reward_state, power_state) = self.reward_and_power_states(ts)?;
+        let circulating_supply = GenesisInfo::from_chain_config(self.chain_config())
+            .get_vm_circulating_supply_detailed(
+                ts.epoch(),
+                &self.blockstore_owned(),
+                ts.parent_state(),
+            )?
+            .fil_circulating;
+        let pledge: TokenAmount = with_reward_and_power!(&reward_state, &power_state, |r, p| {
+            miner_actor::initial_pledge_for_power(
+                &sector_weight,
+                &r.this_epoch_baseline_power,
+                &r.this_epoch_reward_smoothed,
+                &p.this_epoch_qa_power_smoothed,
+                &(&circulating_supply).into(),
+            )
+            .into()
+        })?;
+        Ok(with_pledge_margin(&pledge))
+    }
+
+    /// Returns the bounds of the collateral a provider can lock for a deal of `piece_size`
+    /// padded bytes, at the parent state of `ts`. The bounds are the same for verified deals.
+    pub fn deal_provider_collateral_bounds(
+        &self,
+        piece_size: u64,
+        ts: &Tipset,
+    ) -> anyhow::Result<DealCollateralBounds> {
+        let (reward_state, power_state) = self.reward_and_power_states(ts)?;
+        let baseline_power = match reward_state {
+            reward::State::V8(st) => st.this_epoch_baseline_power,
+            reward::State::V9(st) => st.this_epoch_baseline_power,
+            reward::State::V10(st) => st.this_epoch_baseline_power,
+            reward::State::V11(st) => st.this_epoch_baseline_power,
+            reward::State::V12(st) => st.this_epoch_baseline_power,
+        };
+        let circulating_supply = GenesisInfo::from_chain_config(self.chain_config())
+            .get_vm_circulating_supply_detailed(
+                ts.epoch(),
+                &self.blockstore_owned(),
+                ts.parent_state(),
+            )?
+            .fil_circulating;
+        let min = min_provider_collateral(
+            piece_size,
+            &power_state.total_power().raw_byte_power,
+            &baseline_power,
+            &circulating_supply,
+        );
+        Ok(DealCollateralBounds {
+            min: (&min * DEAL_PROVIDER_COLLATERAL_NUM).div_floor(DEAL_PROVIDER_COLLATERAL_DEN),
+            max: TokenAmount::from_whole(TOTAL_FILECOIN),
+        })
+    }
+
+    fn reward_and_power_states(
+        &self,
+        ts: &Tipset,
+    ) -> anyhow::Result<(reward::State, power::State)> {
+        let actor = self
+            .get_actor(&Address::REWARD_ACTOR, *ts.parent_state())?
+            .context("Reward actor address could not be resolved")?;
+        let reward_state = reward::State::load(self.blockstore(), actor.code, actor.state)?;
+        let actor = self
+            .get_actor(&Address::POWER_ACTOR, *ts.parent_state())?
+            .context("Power actor address could not be resolved")?;
+        let power_state = power::State::load(self.blockstore(), actor.code, actor.state)?;
+        Ok((reward_state, power_state))
+    }
+
+    /// Returns the quality-adjusted power of `sector` once its deals are activated. Like Lotus,
+    /// the duration of the sector starts at `ts`, which over-estimates the power.
+    fn pre_commit_sector_weight(
+        &self,
+        address: &Address,
+        sector: &SectorPreCommitInfo,
+        ts: &Tipset,
+    ) -> anyhow::Result<StoragePower> {
+        let sector_size = sector
+            .seal_proof
+            .sector_size()
+            .map_err(|e| anyhow::anyhow!("invalid seal proof of sector: {e}"))?;
+        let duration = sector.expiration - ts.epoch();
+        anyhow::ensure!(
+            duration > 0,
+            "sector expires at epoch {}, before epoch {}",
+            sector.expiration,
+            ts.epoch()
+        );
+
+        let actor = self
+            .get_actor(&Address::MARKET_ACTOR, *ts.parent_state())?
+            .context("Market actor address could not be resolved")?;
+        let market_state = market::State::load(self.blockstore(), actor.code, actor.state)?;
+        let proposals = market_state.proposals(self.blockstore())?;
+        let miner_id = self
+            .lookup_id(address, ts)?
+            .with_context(|| format!("Failed to resolve address {address}"))?;
+        let (mut deal_weight, mut verified_deal_weight) = (BigInt::zero(), BigInt::zero());
+        for deal_id in &sector.deal_ids {
+            let proposal = proposals
+                .get(*deal_id)?
+                .with_context(|| format!("deal {deal_id} not found"))?;
+            anyhow::ensure!(
+                Address::from(proposal.provider) == miner_id,
+                "deal {deal_id} is not proposed to miner {address}"
+            );
+            let space_time =
+                BigInt::from(proposal.piece_size.0) * (proposal.end_epoch - ts.epoch());
+            if proposal.verified_deal {
+                verified_deal_weight += space_time;
+            } else {
+                deal_weight += space_time;
+            }
+        }
+
+        Ok(qa_power_for_weight(
+            sector_size as u64,
+            duration,
+            &deal_weight,
+            &verified_deal_weight,
+        ))
+    }
+}
+
+fn with_pledge_margin(amount: &TokenAmount) -> TokenAmount {
+    (amount * INITIAL_PLEDGE_NUM).div_floor(INITIAL_PLEDGE_DEN)
+}
+
+/// Returns the share of the locking target of the network, for a deal of `piece_size` bytes. The
+/// share is relative to the larger of the network raw power and the baseline power.
+fn min_provider_collateral(
+    piece_size: u64,
+    network_raw_power: &StoragePower,
+    baseline_power: &StoragePower,
+    circulating_supply: &TokenAmount,
+) -> TokenAmount {
+    let lock_target = circulating_supply * PROV_COLLATERAL_PERCENT_SUPPLY_NUM;
+    let power_share = BigInt::from(piece_size);
+    let power_share_den = network_raw_power
+        .max(baseline_power)
+        .max(&power_share)
+        .clone();
+    (lock_target * power_share).div_floor(power_share_den * PROV_COLLATERAL_PERCENT_SUPPLY_DEN)
+}
+
+/// Returns the quality-adjusted power of a sector of `sector_size` bytes living `duration` epochs,
+/// with the given space-time of regular and verified deals.
+fn qa_power_for_weight(
+    sector_size: u64,
+    duration: ChainEpoch,
+    deal_weight: &BigInt,
+    verified_deal_weight: &BigInt,
+) -> StoragePower {
+    let sector_space_time = BigInt::from(sector_size) * duration;
+    let total_deal_space_time = deal_weight + verified_deal_weight;
+    let weighted_base_space_time =
+        (&sector_space_time - total_deal_space_time) * QUALITY_BASE_MULTIPLIER;
+    let weighted_deal_space_time = deal_weight * DEAL_WEIGHT_MULTIPLIER;
+    let weighted_verified_space_time = verified_deal_weight * VERIFIED_DEAL_WEIGHT_MULTIPLIER;
+    let weighted_sum_space_time =
+        weighted_base_space_time + weighted_deal_space_time + weighted_verified_space_time;
+    let quality = ((weighted_sum_space_time << SECTOR_QUALITY_PRECISION) / sector_space_time)
+        / QUALITY_BASE_MULTIPLIER;
+    (BigInt::from(sector_size) * quality) >> SECTOR_QUALITY_PRECISION
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECTOR_SIZE: u64 = 32 << 30;
+
+    #[test]
+    fn qa_power_of_sector_without_deals_is_its_size() {
+        let zero = BigInt::zero();
+        assert_eq!(
+            qa_power_for_weight(SECTOR_SIZE, 1000, &zero, &zero),
+            BigInt::from(SECTOR_SIZE)
+        );
+    }
+
+    #[test]
+    fn qa_power_of_fully_verified_sector_is_ten_times_its_size() {
+        let weight = BigInt::from(SECTOR_SIZE) * 1000;
+        assert_eq!(
+            qa_power_for_weight(SECTOR_SIZE, 1000, &BigInt::zero(), &weight),
+            BigInt::from(SECTOR_SIZE) * 10
+        );
+    }
+
+    #[test]
+    fn qa_power_of_half_verified_sector() {
+        let weight = BigInt::from(SECTOR_SIZE) * 500;
+        assert_eq!(
+            qa_power_for_weight(SECTOR_SIZE, 1000, &BigInt::zero(), &weight),
+            BigInt::from(SECTOR_SIZE) * 11 / 2
+        );
+    }
+
+    #[test]
+    fn min_provider_collateral_is_share_of_lock_target() {
+        let supply = TokenAmount::from_whole(1_000);
+        // The share is relative to the baseline power when the network is smaller.
+        assert_eq!(
+            min_provider_collateral(
+                1 << 10,
+                &BigInt::from(1 << 20),
+                &BigInt::from(1 << 30),
+                &supply
+            ),
+            TokenAmount::from_whole(10).div_floor(1 << 20)
+        );
+        // A piece larger than the network locks the whole target.
+        assert_eq!(
+            min_provider_collateral(
+                1 << 40,
+                &BigInt::from(1 << 20),
+                &BigInt::from(1 << 30),
+                &supply
+            ),
+            TokenAmount::from_whole(10)
+        );
+    }
+
+    #[test]
+    fn pledge_margin_is_ten_percent() {
+        assert_eq!(
+            with_pledge_margin(&TokenAmount::from_atto(1000)),
+            TokenAmount::from_atto(1100)
+        );
+    }
+}
@@ -0,0 +1,191 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Waiting for messages to be executed, see [`StateManager::wait_for_message`].
+//!
+//! The watcher follows the head of the chain: a message executed in a tipset that a reorg
+//! removes from the chain is searched for again from the new head, and the execution is only
+//! returned once `confidence` tipsets have been applied on top of it. The nonce of the sender
+//! may also be used by another message than the awaited one, either a replacing message, which
+//! only differs by its gas parameters and is accepted if allowed, or a different call, after
+//! which the awaited message can never be executed.
+
+use std::sync::Arc;
+
+use crate::blocks::Tipset;
+use crate::chain::{index::ResolveNullTipset, HeadChange};
+use crate::message::{ChainMessage, Message as _};
+use crate::shim::{clock::ChainEpoch, executor::Receipt};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, warn};
+
+use super::{Error, StateManager};
+
+/// Options of [`StateManager::wait_for_message`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WaitOptions {
+    /// Number of tipsets to be applied on top of the tipset that executed the message.
+    pub confidence: i64,
+    /// Number of epochs below the head to search for the message, without limit if `None`.
+    pub look_back_limit: Option<ChainEpoch>,
+    /// Accept the execution of a message replacing the awaited one, i.e., with the same call
+    /// but different gas parameters.
+    pub allow_replaced: bool,
+}
+
+impl Default for WaitOptions {
+    fn default() -> Self {
+        Self {
+            confidence: 0,
+            look_back_limit: None,
+            allow_replaced: true,
+        }
+    }
+}
+
+/// A message executed in a tipset.
+type Execution = (Arc<Tipset>, Receipt);
+
+impl<DB> StateManager<DB>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    /// Blocks until the message `msg_cid` is executed on chain with `options.confidence`, and
+    /// returns the tipset that executed it, its receipt, and the CID of the executed message,
+    /// which is the CID of the replacing message if the awaited one was replaced.
+    pub async fn wait_for_message(
+        self: &Arc<Self>,
+        msg_cid: Cid,
+        options: WaitOptions,
+    ) -> Result<(Arc<Tipset>, Receipt, Cid), Error> {
+        let message = crate::chain::get_chain_message(self.blockstore(), &msg_cid)
+            .map_err(|err| Error::Other(format!("failed to load message {err:}")))?;
+        // Subscribe before reading the head, so that no head change is missed.
+        let mut subscriber = self.cs.publisher().subscribe();
+        let mut head = self.cs.heaviest_tipset();
+        let min_epoch = options
+            .look_back_limit
+            .map(|limit| head.epoch().saturating_sub(limit));
+        let mut execution = self.search_execution(&head, &message, min_epoch).await?;
+
+        loop {
+            if let Some((tipset, receipt)) = &execution {
+                if head.epoch() >= tipset.epoch() + options.confidence {
+                    let executed = match self.message_with_nonce(tipset, &message)? {
+                        Some(executed) => {
+                            executed.cid().map_err(|e| Error::Other(e.to_string()))?
+                        }
+                        None => msg_cid,
+                    };
+                    if executed != msg_cid && !options.allow_replaced {
+                        return Err(Error::Other(format!(
+                            "message {msg_cid} was replaced by message {executed}"
+                        )));
+                    }
+                    return Ok((Arc::clone(tipset), receipt.clone(), executed));
+                }
+            }
+
+            let new_head = match subscriber.recv().await {
+                Ok(HeadChange::Apply(tipset)) => tipset,
+                Err(RecvError::Lagged(skipped)) => {
+                    warn!("message watcher lagged behind the head, skipped {skipped} changes");
+                    self.cs.heaviest_tipset()
+                }
+                Err(RecvError::Closed) => {
+                    return Err(Error::Other(
+                        "head change subscription closed while waiting for the message".into(),
+                    ))
+                }
+            };
+
+            if let Some((tipset, _)) = &execution {
+                if !self.is_ancestor(tipset, &new_head)? {
+                    debug!(
+                        "tipset {} executing message {msg_cid} was reverted",
+                        tipset.key()
+                    );
+                    execution = None;
+                }
+            }
+            if execution.is_none() {
+                execution = if new_head.parents() == head.key() {
+                    match self.tipset_executed_message(&new_head, &message, true)? {
+                        Some(receipt) => Some((Arc::clone(&new_head), receipt)),
+                        None if self.message_with_nonce(&new_head, &message)?.is_some() => {
+                            return Err(Error::Other(format!(
+                                "nonce {} of {} was used by another message than {msg_cid}",
+                                message.sequence(),
+                                message.from()
+                            )))
+                        }
+                        None => None,
+                    }
+                } else {
+                    // The head jumped or was reorganized, the message may have been executed in
+                    // any of the new tipsets.
+                    self.search_execution(&new_head, &message, min_epoch)
+                        .await?
+                };
+            }
+            head = new_head;
+        }
+    }
+
+    /// Searches for the execution of `message` in `head` and its ancestors above `min_epoch`.
+    async fn search_execution(
+        self: &Arc<Self>,
+        head: &Arc<Tipset>,
+        message: &ChainMessage,
+        min_epoch: Option<ChainEpoch>,
+    ) -> Result<Option<Execution>, Error> {
+        if let Some(receipt) = self.tipset_executed_message(head, message, true)? {
+            return Ok(Some((Arc::clone(head), receipt)));
+        }
+        let (sm, head, message) = (Arc::clone(self), Arc::clone(head), message.clone());
+        tokio::task::spawn_blocking(move || sm.search_back_for_message(head, &message, min_epoch))
+            .await
+            .map_err(|e| Error::Other(format!("Could not search backwards for message {e}")))?
+    }
+
+    /// Returns the message executed by `tipset` with the sender and the nonce of `message`, if
+    /// any. It is either `message`, a replacing message, or a different call.
+    fn message_with_nonce(
+        &self,
+        tipset: &Tipset,
+        message: &ChainMessage,
+    ) -> Result<Option<ChainMessage>, Error> {
+        if tipset.epoch() == 0 {
+            return Ok(None);
+        }
+        let parent = self
+            .cs
+            .load_required_tipset(tipset.parents())
+            .map_err(|e| Error::Other(e.to_string()))?;
+        Ok(self
+            .cs
+            .messages_for_tipset(&parent)
+            .map_err(|e| Error::Other(e.to_string()))?
+            .into_iter()
+            .find(|m| m.from() == message.from() && m.sequence() == message.sequence()))
+    }
+
+    /// Returns `true` if `tipset` is `head` or one of its ancestors.
+    fn is_ancestor(&self, tipset: &Arc<Tipset>, head: &Arc<Tipset>) -> Result<bool, Error> {
+        if tipset.epoch() > head.epoch() {
+            return Ok(false);
+        }
+        let at_epoch = self
+            .cs
+            .chain_index
+            .tipset_by_height(
+                tipset.epoch(),
+                Arc::clone(head),
+                ResolveNullTipset::TakeOlder,
+            )
+            .map_err(|e| Error::Other(e.to_string()))?;
+        Ok(at_epoch.key() == tipset.key())
+    }
+}
@@ -1,34 +1,48 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+mod actor_cache;
+mod actor_changes;
+mod call_cache;
 pub mod chain_rand;
+mod economics;
 mod errors;
+mod message_watcher;
 mod metrics;
+mod state_view;
 pub mod utils;
 pub mod vm_circ_supply;
+
+use self::actor_cache::ActorCache;
+pub use self::actor_changes::{ActorChange, ActorChangeSubscriptions};
+use self::call_cache::CallCache;
 pub use self::errors::*;
+pub use self::message_watcher::WaitOptions;
+pub use self::state_view::StateView;
+use self::state_view::StateViewCache;
 use self::utils::structured;
 
 use crate::beacon::{BeaconEntry, BeaconSchedule};
 use crate::blocks::{Tipset, TipsetKey};
 use crate::chain::{
     index::{ChainIndex, ResolveNullTipset},
-    ChainStore, HeadChange,
+    ChainStore,
 };
 use crate::chain_sync::SyncConfig;
+use crate::db::{state_write_batch_size, BufferedBlockstore};
 use crate::interpreter::{
     resolve_to_key_addr, ApplyResult, BlockMessages, CalledAt, ExecutionContext,
     IMPLICIT_MESSAGE_GAS_LIMIT, VM,
 };
 use crate::interpreter::{MessageCallbackCtx, VMTrace};
 use crate::message::{ChainMessage, Message as MessageTrait};
-use crate::networks::ChainConfig;
+use crate::networks::{ActorRegistry, ChainConfig};
 use crate::rpc_api::data_types::{ApiInvocResult, MessageGasCost, MiningBaseInfo};
 use crate::shim::{
     address::{Address, Payload, Protocol},
     clock::ChainEpoch,
     econ::TokenAmount,
-    executor::{ApplyRet, Receipt},
+    executor::{store_events, ApplyRet, Receipt},
     message::Message,
     randomness::Randomness,
     state_tree::{ActorState, StateTree},
@@ -36,7 +50,6 @@ use crate::shim::{
 };
 use crate::state_manager::chain_rand::draw_randomness;
 use crate::state_migration::run_state_migrations;
-use ahash::{HashMap, HashMapExt};
 use anyhow::{bail, Context as _};
 use bls_signatures::{PublicKey as BlsPublicKey, Serialize as _};
 use chain_rand::ChainRand;
@@ -50,7 +63,6 @@ use fil_actors_shared::fvm_ipld_amt::Amtv0 as Amt;
 use fil_actors_shared::fvm_ipld_bitfield::BitField;
 use fil_actors_shared::v10::runtime::Policy;
 use fil_actors_shared::v12::runtime::DomainSeparationTag;
-use futures::{channel::oneshot, select, FutureExt};
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::to_vec;
 use itertools::Itertools as _;
@@ -58,17 +70,21 @@ use lru::LruCache;
 use nonzero_ext::nonzero;
 use num::BigInt;
 use num_traits::identities::Zero;
+use once_cell::sync::OnceCell;
 use parking_lot::Mutex as SyncMutex;
 use rayon::prelude::ParallelBridge;
 use serde::{Deserialize, Serialize};
 use std::ops::RangeInclusive;
 use std::{num::NonZeroUsize, sync::Arc};
-use tokio::sync::{broadcast::error::RecvError, Mutex as TokioMutex, RwLock};
-use tracing::{debug, error, info, instrument, warn};
+use tokio::sync::Mutex as TokioMutex;
+use tracing::{debug, error, info, instrument};
 pub use utils::is_valid_for_sending;
 pub use vm_circ_supply::GenesisInfo;
 
-const DEFAULT_TIPSET_CACHE_SIZE: NonZeroUsize = nonzero!(1024usize);
+const DEFAULT_TIPSET_STATE_CACHE_SIZE: NonZeroUsize = nonzero!(1024usize);
+
+/// Number of tipsets loaded at once when searching back for a message.
+const SEARCH_BACK_BATCH_SIZE: usize = 32;
 
 /// Intermediary for retrieving state objects and updating actor states.
 type CidPair = (Cid, Cid);
@@ -83,7 +99,7 @@ struct TipsetStateCacheInner {
 impl Default for TipsetStateCacheInner {
     fn default() -> Self {
         Self {
-            values: LruCache::new(DEFAULT_TIPSET_CACHE_SIZE),
+            values: LruCache::new(DEFAULT_TIPSET_STATE_CACHE_SIZE),
             pending: Vec::with_capacity(8),
         }
     }
@@ -215,12 +231,22 @@ pub struct StateManager<DB> {
 
     /// This is a cache which indexes tipsets to their calculated state.
     cache: TipsetStateCache,
+    /// Cache of the actors looked up in recent state trees.
+    actor_cache: ActorCache,
+    /// Cache of the results of `StateCall`s on recent tipsets.
+    call_cache: CallCache<ApiInvocResult>,
+    /// Views of the states of recent tipsets.
+    state_views: StateViewCache<DB>,
     // Beacon can be cheaply crated from the `chain_config`. The only reason we
     // store it here is because it has a look-up cache.
     beacon: Arc<crate::beacon::BeaconSchedule>,
     chain_config: Arc<ChainConfig>,
     sync_config: Arc<SyncConfig>,
     engine: crate::shim::machine::MultiEngine,
+    actor_subscriptions: ActorChangeSubscriptions,
+    /// Names of the actor code CIDs, loaded on first use, once the actor
+    /// bundles are in the database.
+    actor_registry: OnceCell<ActorRegistry>,
 }
 
 #[allow(clippy::type_complexity)]
@@ -241,10 +267,15 @@ where
         Ok(Self {
             cs,
             cache: TipsetStateCache::new(),
+            actor_cache: ActorCache::new(),
+            call_cache: CallCache::new(),
+            state_views: StateViewCache::new(),
             beacon,
             chain_config,
             sync_config,
             engine: crate::shim::machine::MultiEngine::default(),
+            actor_subscriptions: ActorChangeSubscriptions::default(),
+            actor_registry: OnceCell::new(),
         })
     }
 
@@ -257,6 +288,17 @@ where
         self.chain_config.network_version(epoch)
     }
 
+    /// Actor change subscriptions exposed over RPC.
+    pub fn actor_subscriptions(&self) -> &ActorChangeSubscriptions {
+        &self.actor_subscriptions
+    }
+
+    /// Names of the builtin actors, e.g., for error messages.
+    pub fn actor_registry(&self) -> &ActorRegistry {
+        self.actor_registry
+            .get_or_init(|| ActorRegistry::load(self.blockstore()))
+    }
+
     pub fn chain_config(&self) -> &Arc<ChainConfig> {
         &self.chain_config
     }
@@ -267,8 +309,16 @@ where
 
     /// Gets actor from given [`Cid`], if it exists.
     pub fn get_actor(&self, addr: &Address, state_cid: Cid) -> anyhow::Result<Option<ActorState>> {
-        let state = StateTree::new_from_root(self.blockstore_owned(), &state_cid)?;
-        state.get_actor(addr)
+        self.actor_cache.get_or_else(state_cid, addr, || {
+            let state = StateTree::new_from_root(self.blockstore_owned(), &state_cid)?;
+            state.get_actor(addr)
+        })
+    }
+
+    /// Returns a read-only view of the parent state of `tipset`, shared with
+    /// the other readers of the same tipset.
+    pub fn state_at(&self, tipset: &Arc<Tipset>) -> Arc<StateView<DB>> {
+        self.state_views.get_or_insert(&self.cs.db, tipset)
     }
 
     /// Returns a reference to the state manager's [`Blockstore`].
@@ -366,13 +416,24 @@ where
         self: &Arc<Self>,
         addr: &Address,
         ts: &Arc<Tipset>,
+    ) -> anyhow::Result<Vec<SectorOnChainInfo>> {
+        self.get_sectors(addr, ts, None)
+    }
+
+    /// Returns the on-chain info of the given sectors of a miner, or of all its
+    /// sectors if `sectors` is `None`. Sectors that don't exist are skipped.
+    pub fn get_sectors(
+        self: &Arc<Self>,
+        addr: &Address,
+        ts: &Arc<Tipset>,
+        sectors: Option<&BitField>,
     ) -> anyhow::Result<Vec<SectorOnChainInfo>> {
         let actor = self
             .get_actor(addr, *ts.parent_state())?
             .ok_or_else(|| Error::State("Miner actor not found".to_string()))?;
         let state = miner::State::load(self.blockstore(), actor.code, actor.state)?;
 
-        state.load_sectors(self.blockstore(), None)
+        state.load_sectors(self.blockstore(), sectors)
     }
 }
 
@@ -478,15 +539,20 @@ where
     }
 
     /// runs the given message and returns its result without any persisted
-    /// changes.
+    /// changes. Results are cached until the head changes.
     pub fn call(
         self: &Arc<Self>,
         message: &Message,
         tipset: Option<Arc<Tipset>>,
     ) -> Result<ApiInvocResult, Error> {
-        let ts = tipset.unwrap_or_else(|| self.cs.heaviest_tipset());
-        let chain_rand = self.chain_rand(Arc::clone(&ts));
-        self.call_raw(message, chain_rand, &ts)
+        let head = self.cs.heaviest_tipset();
+        let ts = tipset.unwrap_or_else(|| Arc::clone(&head));
+        let msg_cid = message.cid().map_err(|e| Error::Other(e.to_string()))?;
+        self.call_cache
+            .get_or_else(head.key(), ts.key(), msg_cid, || {
+                let chain_rand = self.chain_rand(Arc::clone(&ts));
+                self.call_raw(message, chain_rand, &ts)
+            })
     }
 
     /// Computes message on the given [Tipset] state, after applying other
@@ -673,7 +739,7 @@ where
     ///
     /// For details, see the documentation for [`apply_block_messages`].
     ///
-    #[instrument(skip_all)]
+    #[instrument(skip_all, fields(epoch = tipset.epoch()))]
     pub async fn compute_tipset_state(
         self: &Arc<Self>,
         tipset: Arc<Tipset>,
@@ -688,7 +754,7 @@ where
     }
 
     /// Blocking version of `compute_tipset_state`
-    #[tracing::instrument(skip_all)]
+    #[tracing::instrument(skip_all, fields(epoch = tipset.epoch()))]
     pub fn compute_tipset_state_blocking(
         self: &Arc<Self>,
         tipset: Arc<Tipset>,
@@ -781,35 +847,53 @@ where
             .lookup_id(&message_from_address, current.as_ref())?
             .context("Failed to lookup id")
             .map_err(|e| Error::State(e.to_string()))?;
-        while current.epoch() > look_back_limit.unwrap_or_default() {
-            let parent_tipset = self
+        if current_actor_state.sequence <= message_sequence {
+            // The nonce of the message hasn't been used yet.
+            return Ok(None);
+        }
+        let look_back_limit = look_back_limit.unwrap_or_default();
+        'search: while current.epoch() > look_back_limit {
+            // No tipset below the parent at the limit is needed.
+            let batch_size =
+                SEARCH_BACK_BATCH_SIZE.min((current.epoch() - look_back_limit) as usize);
+            let parent_tipsets = self
                 .cs
-                .load_required_tipset(current.parents())
+                .chain_index
+                .load_tipsets_range(current.parents(), batch_size)
                 .map_err(|err| {
                     Error::Other(format!(
                         "failed to load tipset during msg wait searchback: {err:}"
                     ))
                 })?;
 
-            let parent_actor_state = self
-                .get_actor(&message_from_id, *parent_tipset.parent_state())
-                .map_err(|e| Error::State(e.to_string()))?;
-
-            if parent_actor_state.is_none()
-                || (current_actor_state.sequence > message_sequence
-                    && parent_actor_state.as_ref().unwrap().sequence <= message_sequence)
-            {
-                let receipt = self
-                    .tipset_executed_message(current.as_ref(), message, true)?
-                    .context("Failed to get receipt with tipset_executed_message")?;
-                return Ok(Some((current, receipt)));
-            }
+            for parent_tipset in parent_tipsets {
+                if current.epoch() <= look_back_limit {
+                    break 'search;
+                }
+                let parent_actor_state = self
+                    .get_actor(&message_from_id, *parent_tipset.parent_state())
+                    .map_err(|e| Error::State(e.to_string()))?;
+
+                if parent_actor_state.is_none()
+                    || (current_actor_state.sequence > message_sequence
+                        && parent_actor_state.as_ref().unwrap().sequence <= message_sequence)
+                {
+                    let receipt = self
+                        .tipset_executed_message(current.as_ref(), message, true)?
+                        .with_context(|| {
+                            format!(
+                                "nonce {message_sequence} of {message_from_address} was used by another message"
+                            )
+                        })?;
+                    return Ok(Some((current, receipt)));
+                }
 
-            if let Some(parent_actor_state) = parent_actor_state {
-                current = parent_tipset;
-                current_actor_state = parent_actor_state;
-            } else {
-                break;
+                if let Some(parent_actor_state) = parent_actor_state {
+                    current = parent_tipset;
+                    current_actor_state = parent_actor_state;
+                } else {
+                    break 'search;
+                }
             }
         }
 
@@ -822,9 +906,55 @@ where
         message: &ChainMessage,
         look_back_limit: Option<i64>,
     ) -> Result<Option<(Arc<Tipset>, Receipt)>, Error> {
+        if let Some(found) = self.search_message_index(&current, message, look_back_limit)? {
+            return Ok(Some(found));
+        }
         self.check_search(current, message, look_back_limit)
     }
 
+    /// Looks the message up in the message index. The indexed tipset has to be
+    /// `current` or one of its ancestors, above `look_back_limit`, otherwise
+    /// the message has to be searched for.
+    fn search_message_index(
+        &self,
+        current: &Arc<Tipset>,
+        message: &ChainMessage,
+        look_back_limit: Option<i64>,
+    ) -> Result<Option<(Arc<Tipset>, Receipt)>, Error> {
+        let msg_cid = message.cid().map_err(|e| Error::Other(e.to_string()))?;
+        let Some(location) = self
+            .cs
+            .message_location(&msg_cid)
+            .map_err(|e| Error::Other(e.to_string()))?
+        else {
+            return Ok(None);
+        };
+        if location.epoch > current.epoch() || location.epoch <= look_back_limit.unwrap_or_default()
+        {
+            return Ok(None);
+        }
+        let tipset = self
+            .cs
+            .chain_index
+            .tipset_by_height(
+                location.epoch,
+                Arc::clone(current),
+                ResolveNullTipset::TakeOlder,
+            )
+            .map_err(|e| Error::Other(e.to_string()))?;
+        if *tipset.key() != location.tipset {
+            // The message was executed on a fork.
+            return Ok(None);
+        }
+        let receipt = crate::chain::get_parent_receipt(
+            self.blockstore(),
+            tipset.block_headers().first(),
+            location.receipt_index as usize,
+        )
+        .map_err(|e| Error::Other(e.to_string()))?;
+        Ok(receipt.map(|receipt| (tipset, receipt)))
+    }
+
     /// Returns a message receipt from a given tipset and message CID.
     pub fn get_receipt(&self, tipset: Arc<Tipset>, msg: Cid) -> Result<Receipt, Error> {
         let m = crate::chain::get_chain_message(self.blockstore(), &msg)
@@ -843,127 +973,6 @@ where
         Ok(message_receipt)
     }
 
-    /// `WaitForMessage` blocks until a message appears on chain. It looks
-    /// backwards in the chain to see if this has already happened. It
-    /// guarantees that the message has been on chain for at least
-    /// confidence epochs without being reverted before returning.
-    pub async fn wait_for_message(
-        self: &Arc<Self>,
-        msg_cid: Cid,
-        confidence: i64,
-    ) -> Result<(Option<Arc<Tipset>>, Option<Receipt>), Error> {
-        let mut subscriber = self.cs.publisher().subscribe();
-        let (sender, mut receiver) = oneshot::channel::<()>();
-        let message = crate::chain::get_chain_message(self.blockstore(), &msg_cid)
-            .map_err(|err| Error::Other(format!("failed to load message {err:}")))?;
-        let current_tipset = self.cs.heaviest_tipset();
-        let maybe_message_reciept =
-            self.tipset_executed_message(&current_tipset, &message, true)?;
-        if let Some(r) = maybe_message_reciept {
-            return Ok((Some(current_tipset.clone()), Some(r)));
-        }
-
-        let mut candidate_tipset: Option<Arc<Tipset>> = None;
-        let mut candidate_receipt: Option<Receipt> = None;
-
-        let sm_cloned = Arc::clone(self);
-
-        let message_for_task = message.clone();
-        let height_of_head = current_tipset.epoch();
-        let task = tokio::task::spawn(async move {
-            let back_tuple =
-                sm_cloned.search_back_for_message(current_tipset, &message_for_task, None)?;
-            sender
-                .send(())
-                .map_err(|e| Error::Other(format!("Could not send to channel {e:?}")))?;
-            Ok::<_, Error>(back_tuple)
-        });
-
-        let reverts: Arc<RwLock<HashMap<TipsetKey, bool>>> = Arc::new(RwLock::new(HashMap::new()));
-        let block_revert = reverts.clone();
-        let sm_cloned = Arc::clone(self);
-
-        // Wait for message to be included in head change.
-        let mut subscriber_poll = tokio::task::spawn(async move {
-            loop {
-                match subscriber.recv().await {
-                    Ok(subscriber) => match subscriber {
-                        HeadChange::Apply(tipset) => {
-                            if candidate_tipset
-                                .as_ref()
-                                .map(|s| tipset.epoch() >= s.epoch() + confidence)
-                                .unwrap_or_default()
-                            {
-                                return Ok((candidate_tipset, candidate_receipt));
-                            }
-                            let poll_receiver = receiver.try_recv();
-                            if let Ok(Some(_)) = poll_receiver {
-                                block_revert
-                                    .write()
-                                    .await
-                                    .insert(tipset.key().to_owned(), true);
-                            }
-
-                            let maybe_receipt =
-                                sm_cloned.tipset_executed_message(&tipset, &message, true)?;
-                            if let Some(receipt) = maybe_receipt {
-                                if confidence == 0 {
-                                    return Ok((Some(tipset), Some(receipt)));
-                                }
-                                candidate_tipset = Some(tipset);
-                                candidate_receipt = Some(receipt)
-                            }
-                        }
-                    },
-                    Err(RecvError::Lagged(i)) => {
-                        warn!(
-                            "wait for message head change subscriber lagged, skipped {} events",
-                            i
-                        );
-                    }
-                    Err(RecvError::Closed) => break,
-                }
-            }
-            Ok((None, None))
-        })
-        .fuse();
-
-        // Search backwards for message.
-        let mut search_back_poll = tokio::task::spawn(async move {
-            let back_tuple = task.await.map_err(|e| {
-                Error::Other(format!("Could not search backwards for message {e}"))
-            })??;
-            if let Some((back_tipset, back_receipt)) = back_tuple {
-                let should_revert = *reverts
-                    .read()
-                    .await
-                    .get(back_tipset.key())
-                    .unwrap_or(&false);
-                let larger_height_of_head = height_of_head >= back_tipset.epoch() + confidence;
-                if !should_revert && larger_height_of_head {
-                    return Ok::<_, Error>((Some(back_tipset), Some(back_receipt)));
-                }
-                return Ok((None, None));
-            }
-            Ok((None, None))
-        })
-        .fuse();
-
-        // Await on first future to finish.
-        loop {
-            select! {
-                res = subscriber_poll => {
-                    return res?
-                }
-                res = search_back_poll => {
-                    if let Ok((Some(ts), Some(rct))) = res? {
-                        return Ok((Some(ts), Some(rct)));
-                    }
-                }
-            }
-        }
-    }
-
     pub async fn search_for_message(
         self: &Arc<Self>,
         from: Option<Arc<Tipset>>,
@@ -1543,6 +1552,14 @@ where
 
     let _timer = metrics::APPLY_BLOCKS_TIME.start_timer();
 
+    let db = Arc::clone(&chain_index.db);
+    // Buffer the blocks written by the state computation, to write them in large batches.
+    let store = Arc::new(BufferedBlockstore::new(
+        Arc::clone(&chain_index.db),
+        state_write_batch_size(),
+    ));
+    let chain_index = Arc::new(chain_index.with_db(Arc::clone(&store)));
+
     let rand = ChainRand::new(
         Arc::clone(&chain_config),
         Arc::clone(&tipset),
@@ -1573,7 +1590,7 @@ where
 
     let mut parent_state = *tipset.parent_state();
 
-    let parent_epoch = Tipset::load_required(&chain_index.db, tipset.parents())?.epoch();
+    let parent_epoch = chain_index.load_required_tipset(tipset.parents())?.epoch();
     let epoch = tipset.epoch();
 
     for epoch_i in parent_epoch..epoch {
@@ -1594,9 +1611,10 @@ where
         }
 
         // step 3: run migrations
-        if let Some(new_state) =
-            run_state_migrations(epoch_i, &chain_config, &chain_index.db, &parent_state)?
-        {
+        // Migrations write to the database directly, so that their checkpoints only refer to
+        // persisted blocks.
+        store.flush()?;
+        if let Some(new_state) = run_state_migrations(epoch_i, &chain_config, &db, &parent_state)? {
             parent_state = new_state;
         }
     }
@@ -1606,16 +1624,172 @@ where
 
     // FVM requires a stack size of 64MiB. The alternative is to use `ThreadedExecutor` from
     // FVM, but that introduces some constraints, and possible deadlocks.
-    stacker::grow(64 << 20, || -> anyhow::Result<(Cid, Cid)> {
+    let cid_pair = stacker::grow(64 << 20, || -> anyhow::Result<(Cid, Cid)> {
         let mut vm = create_vm(parent_state, epoch, tipset.min_timestamp())?;
 
         // step 4: apply tipset messages
-        let receipts = vm.apply_block_messages(&block_messages, epoch, callback)?;
+        let (receipts, events) = vm.apply_block_messages(&block_messages, epoch, callback)?;
 
-        // step 5: construct receipt root from receipts and flush the state-tree
+        // step 5: store the events the receipts refer to, construct receipt root from
+        // receipts, and flush the state-tree
+        for (receipt, events) in receipts.iter().zip(events) {
+            if events.is_empty() {
+                continue;
+            }
+            let events_root = store_events(&chain_index.db, events)?;
+            if receipt.events_root() != Some(events_root) {
+                // The events can't be looked up from the receipt.
+                error!(
+                    "stored events root {events_root} doesn't match the events root {:?} of the receipt",
+                    receipt.events_root()
+                );
+            }
+        }
         let receipt_root = Amt::new_from_iter(&chain_index.db, receipts)?;
         let state_root = vm.flush()?;
 
         Ok((state_root, receipt_root))
-    })
+    })?;
+
+    store.flush()?;
+    Ok(cid_pair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blocks::{CachingBlockHeader, RawBlockHeader, TxMeta};
+    use crate::chain::message_index;
+    use crate::db::MemoryDB;
+    use crate::shim::executor::Receipt_v3;
+    use crate::utils::db::CborStoreExt;
+    use fvm_ipld_encoding::RawBytes;
+
+    fn tipset(db: &MemoryDB, header: RawBlockHeader) -> Arc<Tipset> {
+        let header = CachingBlockHeader::new(header);
+        db.put_cbor_default(&*header).unwrap();
+        Arc::new(Tipset::from(header))
+    }
+
+    fn message(db: &MemoryDB, sequence: u64) -> ChainMessage {
+        let message = Message {
+            from: Address::new_id(1000),
+            sequence,
+            ..Default::default()
+        };
+        db.put_cbor_default(&message).unwrap();
+        ChainMessage::Unsigned(message)
+    }
+
+    #[test]
+    #[cfg(feature = "indexer")]
+    fn search_message_index() {
+        let db = Arc::new(MemoryDB::default());
+        let chain_config = Arc::new(ChainConfig::default());
+        let genesis = CachingBlockHeader::default();
+        db.put_cbor_default(&*genesis).unwrap();
+        let cs = Arc::new(
+            ChainStore::new(
+                db.clone(),
+                db.clone(),
+                chain_config.clone(),
+                genesis.clone(),
+            )
+            .unwrap(),
+        );
+        let sm = StateManager::new(cs, chain_config, Default::default()).unwrap();
+        let genesis = Tipset::from(genesis);
+
+        let (executed, pending) = (message(&db, 0), message(&db, 1));
+        let messages = db
+            .put_cbor_default(&TxMeta {
+                bls_message_root: Amt::new_from_iter(&*db, [executed.cid().unwrap()]).unwrap(),
+                secp_message_root: Amt::new_from_iter(&*db, std::iter::empty::<Cid>()).unwrap(),
+            })
+            .unwrap();
+        let message_receipts = Amt::new_from_iter(
+            &*db,
+            [Receipt_v3 {
+                exit_code: fvm_shared3::error::ExitCode::OK,
+                return_data: RawBytes::default(),
+                gas_used: 42,
+                events_root: None,
+            }],
+        )
+        .unwrap();
+        let a1 = tipset(
+            &db,
+            RawBlockHeader {
+                miner_address: Address::new_id(1000),
+                epoch: 1,
+                parents: genesis.key().clone(),
+                messages,
+                ..Default::default()
+            },
+        );
+        let a2 = tipset(
+            &db,
+            RawBlockHeader {
+                miner_address: Address::new_id(1000),
+                epoch: 2,
+                parents: a1.key().clone(),
+                message_receipts,
+                ..Default::default()
+            },
+        );
+        let b2 = tipset(
+            &db,
+            RawBlockHeader {
+                miner_address: Address::new_id(1001),
+                epoch: 2,
+                parents: a1.key().clone(),
+                ..Default::default()
+            },
+        );
+        message_index::index_tipset(sm.chain_store().settings().as_ref(), &*db, &a2).unwrap();
+
+        // Hit, from the executing tipset and from its descendants.
+        let (found, receipt) = sm
+            .search_message_index(&a2, &executed, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(found, a2);
+        assert_eq!(receipt.gas_used(), 42);
+        let a3 = tipset(
+            &db,
+            RawBlockHeader {
+                miner_address: Address::new_id(1000),
+                epoch: 3,
+                parents: a2.key().clone(),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            sm.search_message_index(&a3, &executed, None)
+                .unwrap()
+                .map(|(found, _)| found),
+            Some(Arc::clone(&a2))
+        );
+
+        // Miss, the message isn't indexed, or not executed yet or beyond the
+        // look back limit.
+        assert!(sm
+            .search_message_index(&a2, &pending, None)
+            .unwrap()
+            .is_none());
+        assert!(sm
+            .search_message_index(&a1, &executed, None)
+            .unwrap()
+            .is_none());
+        assert!(sm
+            .search_message_index(&a3, &executed, Some(2))
+            .unwrap()
+            .is_none());
+
+        // Reorg, the indexed tipset isn't on the chain of the fork.
+        assert!(sm
+            .search_message_index(&b2, &executed, None)
+            .unwrap()
+            .is_none());
+    }
 }
@@ -0,0 +1,172 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Read-only views of the state of tipsets, see [`StateManager::state_at`].
+//!
+//! A view loads actors and the states of the power, market, reward and miner
+//! actors the first time they are read, and keeps them for the later reads.
+//! The state manager keeps the views of the most recently viewed tipsets, so
+//! that RPC handlers reading the same tipset share the loaded states.
+//!
+//! [`StateManager::state_at`]: super::StateManager::state_at
+
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+
+use crate::blocks::{Tipset, TipsetKey};
+use crate::metrics;
+use crate::shim::{
+    address::Address,
+    state_tree::{ActorState, StateTree},
+};
+use ahash::HashMap;
+use anyhow::Context as _;
+use cid::Cid;
+use fil_actor_interface::{market, miner, power, reward};
+use fvm_ipld_blockstore::Blockstore;
+use lru::LruCache;
+use nonzero_ext::nonzero;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+
+/// Number of tipsets whose views are kept.
+const STATE_VIEWS: NonZeroUsize = nonzero!(8usize);
+
+/// The parent state of a tipset, i.e., the state its messages are applied to.
+pub struct StateView<DB> {
+    db: Arc<DB>,
+    tipset: Arc<Tipset>,
+    actors: Mutex<HashMap<Address, Option<ActorState>>>,
+    power: OnceCell<power::State>,
+    market: OnceCell<market::State>,
+    reward: OnceCell<reward::State>,
+    miners: Mutex<HashMap<Address, Arc<miner::State>>>,
+}
+
+impl<DB: Blockstore> StateView<DB> {
+    pub fn new(db: Arc<DB>, tipset: Arc<Tipset>) -> Self {
+        Self {
+            db,
+            tipset,
+            actors: Default::default(),
+            power: Default::default(),
+            market: Default::default(),
+            reward: Default::default(),
+            miners: Default::default(),
+        }
+    }
+
+    pub fn tipset(&self) -> &Arc<Tipset> {
+        &self.tipset
+    }
+
+    pub fn state_root(&self) -> &Cid {
+        self.tipset.parent_state()
+    }
+
+    pub fn blockstore(&self) -> &DB {
+        &self.db
+    }
+
+    /// Returns the actor at `addr`, if it exists.
+    pub fn get_actor(&self, addr: &Address) -> anyhow::Result<Option<ActorState>> {
+        if let Some(actor) = self.actors.lock().get(addr) {
+            return Ok(actor.clone());
+        }
+        // Don't hold the lock while loading, the lookup may be slow.
+        let actor =
+            StateTree::new_from_root(Arc::clone(&self.db), self.state_root())?.get_actor(addr)?;
+        self.actors.lock().insert(*addr, actor.clone());
+        Ok(actor)
+    }
+
+    /// Returns the actor at `addr`, failing if it doesn't exist.
+    pub fn get_required_actor(&self, addr: &Address) -> anyhow::Result<ActorState> {
+        self.get_actor(addr)?
+            .with_context(|| format!("Actor {addr} could not be resolved"))
+    }
+
+    pub fn power_state(&self) -> anyhow::Result<&power::State> {
+        self.power.get_or_try_init(|| {
+            let actor = self.get_required_actor(&Address::POWER_ACTOR)?;
+            power::State::load(self.blockstore(), actor.code, actor.state)
+        })
+    }
+
+    pub fn market_state(&self) -> anyhow::Result<&market::State> {
+        self.market.get_or_try_init(|| {
+            let actor = self.get_required_actor(&Address::MARKET_ACTOR)?;
+            market::State::load(self.blockstore(), actor.code, actor.state)
+        })
+    }
+
+    pub fn reward_state(&self) -> anyhow::Result<&reward::State> {
+        self.reward.get_or_try_init(|| {
+            let actor = self.get_required_actor(&Address::REWARD_ACTOR)?;
+            reward::State::load(self.blockstore(), actor.code, actor.state)
+        })
+    }
+
+    /// Returns the state of the miner actor at `addr`.
+    pub fn miner_state(&self, addr: &Address) -> anyhow::Result<Arc<miner::State>> {
+        if let Some(state) = self.miners.lock().get(addr) {
+            return Ok(Arc::clone(state));
+        }
+        let actor = self
+            .get_actor(addr)?
+            .with_context(|| format!("Miner actor {addr} could not be resolved"))?;
+        let state = Arc::new(miner::State::load(
+            self.blockstore(),
+            actor.code,
+            actor.state,
+        )?);
+        self.miners.lock().insert(*addr, Arc::clone(&state));
+        Ok(state)
+    }
+}
+
+/// The views of the most recently viewed tipsets.
+pub(in crate::state_manager) struct StateViewCache<DB> {
+    views: Mutex<LruCache<TipsetKey, Arc<StateView<DB>>>>,
+}
+
+impl<DB: Blockstore> StateViewCache<DB> {
+    pub fn new() -> Self {
+        Self {
+            views: Mutex::new(LruCache::new(STATE_VIEWS)),
+        }
+    }
+
+    pub fn get_or_insert(&self, db: &Arc<DB>, tipset: &Arc<Tipset>) -> Arc<StateView<DB>> {
+        let mut views = self.views.lock();
+        let counter = match views.contains(tipset.key()) {
+            true => &metrics::LRU_CACHE_HIT,
+            false => &metrics::LRU_CACHE_MISS,
+        };
+        counter
+            .with_label_values(&[metrics::values::STATE_MANAGER_VIEW])
+            .inc();
+        Arc::clone(views.get_or_insert(tipset.key().clone(), || {
+            Arc::new(StateView::new(Arc::clone(db), Arc::clone(tipset)))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+    use crate::message_pool::test_provider::mock_tipset;
+
+    #[test]
+    fn views_are_shared_per_tipset() {
+        let db = Arc::new(MemoryDB::default());
+        let cache = StateViewCache::new();
+        let (a, b) = (mock_tipset(1000, 0), mock_tipset(1001, 0));
+
+        let view = cache.get_or_insert(&db, &a);
+        assert_eq!(view.tipset(), &a);
+        assert!(Arc::ptr_eq(&view, &cache.get_or_insert(&db, &a)));
+        assert!(!Arc::ptr_eq(&view, &cache.get_or_insert(&db, &b)));
+    }
+}
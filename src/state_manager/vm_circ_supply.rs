@@ -172,8 +172,7 @@ impl GenesisInfo {
                         let ms = multisig::State::load(&db, actor.code, actor.state)?;
 
                         let locked_balance: TokenAmount = ms.locked_balance(height)?.into();
-                        let avail_balance = actor_balance.clone() - &locked_balance;
-                        circ += avail_balance.max(TokenAmount::zero());
+                        circ += actor_balance.saturating_sub(&locked_balance);
                         un_circ += actor_balance.min(locked_balance);
                     }
                     _ => bail!("unexpected actor: {:?}", actor),
@@ -21,6 +21,14 @@ use parking_lot::RwLock;
 pub(in crate::state_migration) use state_migration::StateMigration;
 pub(in crate::state_migration) type Migrator<BS> = Arc<dyn ActorMigration<BS> + Send + Sync>;
 
+/// Whether a migration computes the state-tree of a network upgrade, or is a pre-migration,
+/// computing the bulk of the migration ahead of the upgrade to fill its [`MigrationCache`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(in crate::state_migration) enum MigrationMode {
+    Upgrade,
+    PreMigration,
+}
+
 /// Cache of existing CID to CID migrations for an actor.
 #[derive(Clone)]
 pub(in crate::state_migration) struct MigrationCache {
@@ -34,6 +42,14 @@ impl MigrationCache {
         }
     }
 
+    /// Creates a cache that keeps all its entries, e.g. to keep the results of a pre-migration
+    /// until the migration.
+    pub fn unbounded() -> Self {
+        Self {
+            cache: Arc::new(RwLock::new(LruCache::unbounded())),
+        }
+    }
+
     pub fn get(&self, key: &str) -> Option<Cid> {
         self.cache.write().get(key).cloned()
     }
@@ -54,6 +70,10 @@ impl MigrationCache {
     pub fn insert(&self, key: String, value: Cid) {
         self.cache.write().put(key, value);
     }
+
+    pub fn values(&self) -> Vec<Cid> {
+        self.cache.read().iter().map(|(_, v)| *v).collect()
+    }
 }
 
 #[allow(dead_code)] // future migrations might need the fields.
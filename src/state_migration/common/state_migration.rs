@@ -1,12 +1,13 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::num::NonZeroUsize;
-use std::sync::atomic::AtomicU64;
+use std::collections::BTreeSet;
+use std::sync::Arc;
 
 use crate::cid_collections::CidHashMap;
 use crate::shim::{clock::ChainEpoch, state_tree::StateTree};
-use crate::state_migration::common::MigrationCache;
+use crate::state_migration::common::MigrationMode;
+use crate::state_migration::{premigration, progress};
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
 
@@ -65,19 +66,46 @@ impl<BS: Blockstore> StateMigration<BS> {
 }
 
 impl<BS: Blockstore + Send + Sync> StateMigration<BS> {
+    /// Migrates `actors_in` into `actors_out`. The progress of the migration is checkpointed after
+    /// each shard of actors, and a migration of the same state-tree resumes from the last
+    /// checkpoint. See [`progress`].
+    ///
+    /// Pre-migrations only fill the cache of the migration at `prior_epoch`, see [`premigration`]:
+    /// they don't build the new state-tree, and return the root of the prior one.
     pub(in crate::state_migration) fn migrate_state_tree(
         &self,
-        store: &BS,
+        store: &Arc<BS>,
         prior_epoch: ChainEpoch,
-        actors_in: StateTree<BS>,
+        mut actors_in: StateTree<BS>,
         mut actors_out: StateTree<BS>,
+        mode: MigrationMode,
     ) -> anyhow::Result<Cid> {
+        let is_upgrade = mode == MigrationMode::Upgrade;
         // Checks if the migration specification is correct
         if let Some(verifier) = &self.verifier {
             verifier.verify_migration(store, &self.migrations, &actors_in)?;
         }
 
-        let cache = MigrationCache::new(NonZeroUsize::new(10_000).expect("infallible"));
+        let prior_state = actors_in.flush()?;
+        let mut actors_resumed = 0;
+        if is_upgrade {
+            let mut actors_total = 0;
+            actors_in.for_each(|_, _| {
+                actors_total += 1;
+                Ok(())
+            })?;
+            if let Some(checkpoint) = progress::load_checkpoint(&prior_state, prior_epoch)? {
+                tracing::info!(
+                    "Resuming state migration after {} actors",
+                    checkpoint.actors_done
+                );
+                actors_out = StateTree::new_from_root(Arc::clone(store), &checkpoint.actors_out)?;
+                actors_resumed = checkpoint.actors_done;
+            }
+            progress::set_actors(actors_total, actors_resumed);
+        }
+
+        let cache = premigration::cache(prior_epoch);
         let pool = rayon::ThreadPoolBuilder::new()
             .thread_name(|id| format!("state migration thread: {id}"))
             .num_threads(3) // minimum needed, more doesn't increase performance in any way
@@ -86,21 +114,25 @@ impl<BS: Blockstore + Send + Sync> StateMigration<BS> {
         let (state_tx, state_rx) = crossbeam_channel::bounded(1);
         let (job_tx, job_rx) = crossbeam_channel::bounded(1);
 
-        let job_counter = AtomicU64::new(0);
         pool.scope(|s| {
             s.spawn(move |_| {
+                let mut index = 0;
                 actors_in
                     .for_each(|addr, state| {
-                        state_tx
-                            .send((addr, state.clone()))
-                            .expect("failed sending actor state through channel");
+                        // Actors before the checkpoint are already in `actors_out`.
+                        if index >= actors_resumed {
+                            state_tx
+                                .send((index, addr, state.clone()))
+                                .expect("failed sending actor state through channel");
+                        }
+                        index += 1;
                         Ok(())
                     })
                     .expect("Failed iterating over actor state");
             });
 
             s.spawn(move |scope| {
-                while let Ok((address, state)) = state_rx.recv() {
+                while let Ok((index, address, state)) = state_rx.recv() {
                     let job_tx = job_tx.clone();
                     let migrator = self.migrations.get(&state.code).cloned().unwrap_or_else(|| panic!("migration failed with state code: {}", state.code));
                     let cache_clone = cache.clone();
@@ -117,7 +149,7 @@ impl<BS: Blockstore + Send + Sync> StateMigration<BS> {
                             )
                         });
 
-                        job_tx.send(job_output).unwrap_or_else(|_| {
+                        job_tx.send((index, job_output)).unwrap_or_else(|_| {
                             panic!("failed sending job output for address: {address}")
                         });
                     });
@@ -125,11 +157,16 @@ impl<BS: Blockstore + Send + Sync> StateMigration<BS> {
                 drop(job_tx);
             });
 
-            while let Ok(job_output) = job_rx.recv() {
+            // Jobs complete out of order: only the actors before `actors_done` are all migrated.
+            let mut actors_done = actors_resumed;
+            let mut completed = BTreeSet::new();
+            let mut next_checkpoint = actors_resumed + progress::SHARD_SIZE;
+            let mut job_counter = actors_resumed;
+            while let Ok((index, job_output)) = job_rx.recv() {
                 if let Some(MigrationJobOutput {
                     address,
                     actor_state,
-                }) = job_output {
+                }) = job_output.filter(|_| is_upgrade) {
                     actors_out
                         .set_actor(&address, actor_state)
                         .unwrap_or_else(|e| {
@@ -137,15 +174,40 @@ impl<BS: Blockstore + Send + Sync> StateMigration<BS> {
                                 "Failed setting new actor state at given address: {address}, Reason: {e}"
                             )
                         });
-                    job_counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    let job_counter = job_counter.load(std::sync::atomic::Ordering::Relaxed);
-                    if job_counter % 100_000 == 0 {
-                        tracing::info!("Processed {job_counter} actors", job_counter = job_counter);
+                }
+                job_counter += 1;
+                if is_upgrade {
+                    progress::set_migrated(job_counter);
+                }
+                if job_counter % 100_000 == 0 {
+                    tracing::info!("Processed {job_counter} actors", job_counter = job_counter);
+                }
+
+                completed.insert(index);
+                while completed.remove(&actors_done) {
+                    actors_done += 1;
+                }
+                if is_upgrade && actors_done >= next_checkpoint {
+                    next_checkpoint = actors_done + progress::SHARD_SIZE;
+                    let checkpoint = actors_out.flush().and_then(|actors_out| {
+                        progress::save_checkpoint(&progress::Checkpoint {
+                            prior_state,
+                            prior_epoch,
+                            actors_done,
+                            actors_out,
+                        })
+                    });
+                    if let Err(e) = checkpoint {
+                        tracing::warn!("Failed to checkpoint state migration: {e:#}");
                     }
                 }
             }
         });
 
+        if !is_upgrade {
+            return Ok(prior_state);
+        }
+
         // execute post migration actions, e.g., create new actors
         for post_migrator in self.post_migrators.iter() {
             post_migrator.post_migrate_state(store, &mut actors_out)?;
@@ -156,6 +218,10 @@ impl<BS: Blockstore + Send + Sync> StateMigration<BS> {
             post_migration_check.post_migrate_check(store, &actors_out)?;
         }
 
-        actors_out.flush()
+        let new_state = actors_out.flush()?;
+        if let Err(e) = progress::clear_checkpoint() {
+            tracing::warn!("Failed to clear state migration checkpoint: {e:#}");
+        }
+        Ok(new_state)
     }
 }
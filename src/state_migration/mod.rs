@@ -14,6 +14,8 @@ use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::CborStore;
 
+use self::common::MigrationMode;
+
 pub(in crate::state_migration) mod common;
 mod nv17;
 mod nv18;
@@ -21,21 +23,19 @@ mod nv19;
 mod nv21;
 mod nv21fix;
 mod nv21fix2;
+pub mod premigration;
+pub mod progress;
 mod type_migrations;
 
-type RunMigration<DB> = fn(&ChainConfig, &Arc<DB>, &Cid, ChainEpoch) -> anyhow::Result<Cid>;
+type RunMigration<DB> =
+    fn(&ChainConfig, &Arc<DB>, &Cid, ChainEpoch, MigrationMode) -> anyhow::Result<Cid>;
 
-/// Run state migrations
-pub fn run_state_migrations<DB>(
-    epoch: ChainEpoch,
-    chain_config: &Arc<ChainConfig>,
-    db: &Arc<DB>,
-    parent_state: &Cid,
-) -> anyhow::Result<Option<Cid>>
+/// Returns the migrations of `network`, by upgrade height.
+fn migrations<DB>(network: &NetworkChain) -> Vec<(Height, RunMigration<DB>)>
 where
     DB: Blockstore + Send + Sync,
 {
-    let mappings: Vec<(_, RunMigration<DB>)> = match chain_config.network {
+    match network {
         NetworkChain::Mainnet => {
             vec![
                 (Height::Shark, nv17::run_migration::<DB>),
@@ -65,7 +65,20 @@ where
                 (Height::Watermelon, nv21::run_migration::<DB>),
             ]
         }
-    };
+    }
+}
+
+/// Run state migrations
+pub fn run_state_migrations<DB>(
+    epoch: ChainEpoch,
+    chain_config: &Arc<ChainConfig>,
+    db: &Arc<DB>,
+    parent_state: &Cid,
+) -> anyhow::Result<Option<Cid>>
+where
+    DB: Blockstore + Send + Sync,
+{
+    let mappings = migrations::<DB>(&chain_config.network);
 
     // Make sure bundle is defined.
     static BUNDLE_CHECKED: AtomicBool = AtomicBool::new(false);
@@ -88,7 +101,22 @@ where
         if epoch == chain_config.epoch(height) {
             tracing::info!("Running {height} migration at epoch {epoch}");
             let start_time = std::time::Instant::now();
-            let new_state = migrate(chain_config, db, parent_state, epoch)?;
+            progress::start(height, epoch);
+            let new_state = match migrate(
+                chain_config,
+                db,
+                parent_state,
+                epoch,
+                MigrationMode::Upgrade,
+            ) {
+                Ok(new_state) => new_state,
+                Err(e) => {
+                    progress::abort();
+                    return Err(e);
+                }
+            };
+            progress::finish();
+            premigration::remove_cache(epoch);
             let elapsed = start_time.elapsed().as_secs_f32();
             // `new_state_actors` is the Go state migration output, log for comparision
             let new_state_actors = db
@@ -17,7 +17,7 @@ use fvm_ipld_encoding::CborStore as _;
 
 use super::super::common::{
     migrators::{nil_migrator, DeferredMigrator},
-    StateMigration,
+    MigrationMode, StateMigration,
 };
 use super::{
     datacap, miner, system, util::get_pending_verified_deals_and_total_size, verifier::Verifier,
@@ -134,6 +134,7 @@ pub fn run_migration<DB>(
     blockstore: &Arc<DB>,
     state: &Cid,
     epoch: ChainEpoch,
+    mode: MigrationMode,
 ) -> anyhow::Result<Cid>
 where
     DB: Blockstore + Send + Sync,
@@ -171,7 +172,7 @@ where
 
     let actors_out = StateTree::new(blockstore.clone(), StateTreeVersion::V4)?;
 
-    let new_state = migration.migrate_state_tree(blockstore, epoch, actors_in, actors_out)?;
+    let new_state = migration.migrate_state_tree(blockstore, epoch, actors_in, actors_out, mode)?;
 
     Ok(new_state)
 }
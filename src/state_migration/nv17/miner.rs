@@ -372,6 +372,7 @@ mod tests {
         machine::{BuiltinActor, BuiltinActorManifest},
         state_tree::{ActorState, StateRoot, StateTree, StateTreeVersion},
     };
+    use crate::state_migration::common::MigrationMode;
     use cid::multihash::{Multihash, MultihashDigest};
     use fil_actor_interface::BURNT_FUNDS_ACTOR_ADDR;
     use fil_actors_shared::fvm_ipld_hamt::BytesKey;
@@ -619,15 +620,27 @@ mod tests {
         if let Some(bundle) = &mut chain_config.height_infos[Height::Shark as usize].bundle {
             *bundle = new_manifest_cid;
         }
-        let new_state_cid =
-            super::super::run_migration(&chain_config, &store, &tree_root, 200).unwrap();
+        let new_state_cid = super::super::run_migration(
+            &chain_config,
+            &store,
+            &tree_root,
+            200,
+            MigrationMode::Upgrade,
+        )
+        .unwrap();
         let actors_out_state_root: StateRoot = store.get_cbor(&new_state_cid).unwrap().unwrap();
         assert_eq!(
             actors_out_state_root.actors.to_string(),
             "bafy2bzacedgtk3lnnyfxnzc32etqaj3zvi7ar7nxq2jtxd2qr36ftbsjoycqu"
         );
-        let new_state_cid2 =
-            super::super::run_migration(&chain_config, &store, &tree_root, 200).unwrap();
+        let new_state_cid2 = super::super::run_migration(
+            &chain_config,
+            &store,
+            &tree_root,
+            200,
+            MigrationMode::Upgrade,
+        )
+        .unwrap();
         assert_eq!(new_state_cid, new_state_cid2);
     }
 
@@ -652,8 +665,14 @@ mod tests {
         if let Some(bundle) = &mut chain_config.height_infos[Height::Shark as usize].bundle {
             *bundle = new_manifest_cid;
         }
-        let new_state_cid =
-            super::super::run_migration(&chain_config, &store, &state_tree_old_root, 200).unwrap();
+        let new_state_cid = super::super::run_migration(
+            &chain_config,
+            &store,
+            &state_tree_old_root,
+            200,
+            MigrationMode::Upgrade,
+        )
+        .unwrap();
         let actors_out_state_root: StateRoot = store.get_cbor(&new_state_cid).unwrap().unwrap();
         assert_eq!(
             actors_out_state_root.actors.to_string(),
@@ -19,7 +19,7 @@ use super::{
     eam::EamPostMigrator, eth_account::EthAccountPostMigrator, init, system, verifier::Verifier,
     SystemStateOld,
 };
-use crate::state_migration::common::{migrators::nil_migrator, StateMigration};
+use crate::state_migration::common::{migrators::nil_migrator, MigrationMode, StateMigration};
 impl<BS: Blockstore> StateMigration<BS> {
     pub fn add_nv18_migrations(
         &mut self,
@@ -70,6 +70,7 @@ pub fn run_migration<DB>(
     blockstore: &Arc<DB>,
     state: &Cid,
     epoch: ChainEpoch,
+    mode: MigrationMode,
 ) -> anyhow::Result<Cid>
 where
     DB: Blockstore + Send + Sync,
@@ -97,7 +98,7 @@ where
 
     let actors_in = StateTree::new_from_root(blockstore.clone(), state)?;
     let actors_out = StateTree::new(blockstore.clone(), StateTreeVersion::V5)?;
-    let new_state = migration.migrate_state_tree(blockstore, epoch, actors_in, actors_out)?;
+    let new_state = migration.migrate_state_tree(blockstore, epoch, actors_in, actors_out, mode)?;
 
     Ok(new_state)
 }
@@ -16,7 +16,7 @@ use fvm_ipld_blockstore::Blockstore;
 use fvm_ipld_encoding::CborStore as _;
 
 use super::{miner, power, system, verifier::Verifier, SystemStateOld};
-use crate::state_migration::common::{migrators::nil_migrator, StateMigration};
+use crate::state_migration::common::{migrators::nil_migrator, MigrationMode, StateMigration};
 
 impl<BS: Blockstore> StateMigration<BS> {
     pub fn add_nv19_migrations(
@@ -69,6 +69,7 @@ pub fn run_migration<DB>(
     blockstore: &Arc<DB>,
     state: &Cid,
     epoch: ChainEpoch,
+    mode: MigrationMode,
 ) -> anyhow::Result<Cid>
 where
     DB: Blockstore + Send + Sync,
@@ -96,7 +97,7 @@ where
 
     let actors_in = StateTree::new_from_root(blockstore.clone(), state)?;
     let actors_out = StateTree::new(blockstore.clone(), StateTreeVersion::V5)?;
-    let new_state = migration.migrate_state_tree(blockstore, epoch, actors_in, actors_out)?;
+    let new_state = migration.migrate_state_tree(blockstore, epoch, actors_in, actors_out, mode)?;
 
     Ok(new_state)
 }
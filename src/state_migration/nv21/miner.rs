@@ -300,6 +300,7 @@ mod tests {
         machine::{BuiltinActor, BuiltinActorManifest},
         state_tree::{ActorState, StateTree, StateTreeVersion},
     };
+    use crate::state_migration::common::MigrationMode;
     use cid::multihash::MultihashDigest;
     use fvm_ipld_encoding::IPLD_RAW;
     use fvm_shared2::bigint::Zero;
@@ -367,11 +368,23 @@ mod tests {
         if let Some(bundle) = &mut chain_config.height_infos[Height::Watermelon as usize].bundle {
             *bundle = new_manifest_cid;
         }
-        let new_state_cid =
-            super::super::run_migration(&chain_config, &store, &tree_root, 200).unwrap();
+        let new_state_cid = super::super::run_migration(
+            &chain_config,
+            &store,
+            &tree_root,
+            200,
+            MigrationMode::Upgrade,
+        )
+        .unwrap();
 
-        let new_state_cid2 =
-            super::super::run_migration(&chain_config, &store, &tree_root, 200).unwrap();
+        let new_state_cid2 = super::super::run_migration(
+            &chain_config,
+            &store,
+            &tree_root,
+            200,
+            MigrationMode::Upgrade,
+        )
+        .unwrap();
 
         assert_eq!(new_state_cid, new_state_cid2);
 
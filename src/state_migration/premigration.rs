@@ -0,0 +1,180 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Pre-migrations compute the bulk of the state migration of a network upgrade ahead of the
+//! upgrade, so that the node doesn't stall at the upgrade epoch.
+//!
+//! From [`PRE_MIGRATION_START`] epochs before an upgrade, its migration is run in the background on
+//! the state of the head. The actor migrations are kept in a [`MigrationCache`], which the migration
+//! at the upgrade epoch then uses: only the actors that changed since are migrated again. The
+//! migrated states aren't reachable from the chain until the upgrade, and are pinned in the garbage
+//! collector, see [`pinned_roots`].
+
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::networks::ChainConfig;
+use crate::shim::clock::ChainEpoch;
+use crate::state_manager::StateManager;
+use ahash::HashMap;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use tracing::{info, warn};
+
+use super::common::{MigrationCache, MigrationMode};
+
+/// Number of epochs before an upgrade from which its pre-migration is started.
+pub const PRE_MIGRATION_START: ChainEpoch = 240;
+
+/// Number of epochs before an upgrade from which its pre-migration isn't started anymore, as it
+/// wouldn't be done before the upgrade.
+pub const PRE_MIGRATION_DEADLINE: ChainEpoch = 15;
+
+/// Caches of the pre-migrations, by upgrade epoch.
+static CACHES: Lazy<Mutex<HashMap<ChainEpoch, MigrationCache>>> = Lazy::new(Default::default);
+
+/// Returns the cache of the migration at `epoch`, filled by its pre-migration if any.
+pub(in crate::state_migration) fn cache(epoch: ChainEpoch) -> MigrationCache {
+    CACHES
+        .lock()
+        .get(&epoch)
+        .cloned()
+        .unwrap_or_else(|| MigrationCache::new(NonZeroUsize::new(10_000).expect("infallible")))
+}
+
+/// Drops the cache of the migration at `epoch`, once the migration is done.
+pub(in crate::state_migration) fn remove_cache(epoch: ChainEpoch) {
+    CACHES.lock().remove(&epoch);
+}
+
+/// Returns the roots of the states computed by the pre-migrations, that the garbage collector must
+/// keep until the migrations use them.
+pub fn pinned_roots() -> Vec<Cid> {
+    CACHES
+        .lock()
+        .values()
+        .flat_map(MigrationCache::values)
+        .collect()
+}
+
+fn is_pre_migration_epoch(epoch: ChainEpoch, upgrade_epoch: ChainEpoch) -> bool {
+    (upgrade_epoch - PRE_MIGRATION_START..upgrade_epoch - PRE_MIGRATION_DEADLINE).contains(&epoch)
+}
+
+/// Runs the pre-migrations of the upgrades coming after `epoch` on `state`, unless they already
+/// ran.
+pub fn run_pre_migrations<DB>(
+    epoch: ChainEpoch,
+    chain_config: &ChainConfig,
+    db: &Arc<DB>,
+    state: &Cid,
+) -> anyhow::Result<()>
+where
+    DB: Blockstore + Send + Sync,
+{
+    for (height, migrate) in super::migrations::<DB>(&chain_config.network) {
+        let upgrade_epoch = chain_config.epoch(height);
+        if !is_pre_migration_epoch(epoch, upgrade_epoch) {
+            continue;
+        }
+        {
+            let mut caches = CACHES.lock();
+            if caches.contains_key(&upgrade_epoch) {
+                continue;
+            }
+            // The cache is shared with the migration right away, should it start before the
+            // pre-migration is done.
+            caches.insert(upgrade_epoch, MigrationCache::unbounded());
+        }
+
+        info!("Running {height} pre-migration at epoch {epoch}");
+        let start_time = Instant::now();
+        migrate(
+            chain_config,
+            db,
+            state,
+            upgrade_epoch,
+            MigrationMode::PreMigration,
+        )?;
+        info!(
+            "Pre-migration for height {height} (epoch {upgrade_epoch}) was successful. Took: {}s.",
+            start_time.elapsed().as_secs_f32()
+        );
+    }
+    Ok(())
+}
+
+/// Runs the pre-migrations of the upcoming upgrades on the state of the head, checking for them at
+/// every epoch.
+pub async fn pre_migration_loop<DB>(state_manager: Arc<StateManager<DB>>) -> anyhow::Result<()>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    let mut interval = tokio::time::interval(Duration::from_secs(u64::from(
+        state_manager.chain_config().block_delay_secs,
+    )));
+    loop {
+        interval.tick().await;
+        let state_manager = Arc::clone(&state_manager);
+        let result = tokio::task::spawn_blocking(move || {
+            let head = state_manager.chain_store().heaviest_tipset();
+            run_pre_migrations(
+                head.epoch(),
+                state_manager.chain_config(),
+                &state_manager.blockstore_owned(),
+                head.parent_state(),
+            )
+        })
+        .await?;
+        if let Err(e) = result {
+            // The migration runs without the cache of the pre-migration then.
+            warn!("Pre-migration failed: {e:#}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pre_migration_epochs() {
+        let upgrade_epoch = 1_000;
+        assert!(!is_pre_migration_epoch(
+            upgrade_epoch - PRE_MIGRATION_START - 1,
+            upgrade_epoch
+        ));
+        assert!(is_pre_migration_epoch(
+            upgrade_epoch - PRE_MIGRATION_START,
+            upgrade_epoch
+        ));
+        assert!(is_pre_migration_epoch(
+            upgrade_epoch - PRE_MIGRATION_DEADLINE - 1,
+            upgrade_epoch
+        ));
+        assert!(!is_pre_migration_epoch(
+            upgrade_epoch - PRE_MIGRATION_DEADLINE,
+            upgrade_epoch
+        ));
+        assert!(!is_pre_migration_epoch(upgrade_epoch, upgrade_epoch));
+    }
+
+    #[test]
+    fn migration_uses_pre_migration_cache() {
+        // An epoch that no other test migrates at.
+        let upgrade_epoch = -42;
+        let cid = Cid::default();
+        CACHES
+            .lock()
+            .insert(upgrade_epoch, MigrationCache::unbounded());
+        cache(upgrade_epoch).insert("Cthulhu".to_owned(), cid);
+        assert_eq!(cache(upgrade_epoch).get("Cthulhu"), Some(cid));
+        assert!(pinned_roots().contains(&cid));
+
+        remove_cache(upgrade_epoch);
+        assert_eq!(cache(upgrade_epoch).get("Cthulhu"), None);
+    }
+}
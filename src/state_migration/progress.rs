@@ -0,0 +1,279 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Progress of the running state migration, and checkpoints to resume it.
+//!
+//! Actors are migrated in shards of [`SHARD_SIZE`] actors. Once all the actors of a shard are
+//! migrated, the partially migrated state-tree is flushed and a [`Checkpoint`] is written to the
+//! settings store, so that a migration interrupted by a restart resumes from the last shard.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::db::{setting_keys::STATE_MIGRATION_CHECKPOINT_KEY, SettingsStore, SettingsStoreExt};
+use crate::lotus_json::lotus_json_with_self;
+use crate::networks::Height;
+use crate::shim::clock::ChainEpoch;
+use cid::Cid;
+use once_cell::sync::{Lazy, OnceCell};
+use parking_lot::RwLock;
+use prometheus::core::{AtomicU64, GenericGauge};
+use serde::{Deserialize, Serialize};
+
+/// Number of actors migrated between two checkpoints.
+pub(in crate::state_migration) const SHARD_SIZE: u64 = 100_000;
+
+static CHECKPOINT_STORE: OnceCell<Arc<dyn SettingsStore + Sync + Send>> = OnceCell::new();
+
+static PROGRESS: Lazy<RwLock<Option<Tracker>>> = Lazy::new(Default::default);
+
+static MIGRATION_ACTORS_TOTAL: Lazy<Box<GenericGauge<AtomicU64>>> = Lazy::new(|| {
+    let migration_actors_total = Box::new(
+        GenericGauge::<AtomicU64>::new(
+            "state_migration_actors_total",
+            "Number of actors of the state-tree being migrated",
+        )
+        .expect("Defining the state_migration_actors_total metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(migration_actors_total.clone())
+        .expect(
+            "Registering the state_migration_actors_total metric with the metrics registry must succeed",
+        );
+    migration_actors_total
+});
+
+static MIGRATION_ACTORS_MIGRATED: Lazy<Box<GenericGauge<AtomicU64>>> = Lazy::new(|| {
+    let migration_actors_migrated = Box::new(
+        GenericGauge::<AtomicU64>::new(
+            "state_migration_actors_migrated",
+            "Number of actors of the state-tree being migrated that are already migrated",
+        )
+        .expect("Defining the state_migration_actors_migrated metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(migration_actors_migrated.clone())
+        .expect(
+            "Registering the state_migration_actors_migrated metric with the metrics registry must succeed",
+        );
+    migration_actors_migrated
+});
+
+static MIGRATION_ETA: Lazy<Box<GenericGauge<AtomicU64>>> = Lazy::new(|| {
+    let migration_eta = Box::new(
+        GenericGauge::<AtomicU64>::new(
+            "state_migration_eta",
+            "Estimated time left to the running state migration, in seconds",
+        )
+        .expect("Defining the state_migration_eta metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(migration_eta.clone())
+        .expect(
+            "Registering the state_migration_eta metric with the metrics registry must succeed",
+        );
+    migration_eta
+});
+
+/// Progress of a state migration, as reported by the `Filecoin.StateMigrationProgress` RPC method.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MigrationProgress {
+    /// Network upgrade of the migration.
+    pub height: String,
+    pub epoch: ChainEpoch,
+    pub actors_total: u64,
+    pub actors_migrated: u64,
+    /// Number of actors migrated before the node restarted, which weren't migrated again.
+    pub actors_resumed: u64,
+    pub percent: f64,
+    pub elapsed_secs: u64,
+    /// Estimated time left, in seconds. Unknown until some actors are migrated.
+    pub eta_secs: Option<u64>,
+    pub done: bool,
+}
+
+lotus_json_with_self!(MigrationProgress);
+
+struct Tracker {
+    height: Height,
+    epoch: ChainEpoch,
+    start: Instant,
+    actors_total: u64,
+    actors_migrated: u64,
+    actors_resumed: u64,
+    done: bool,
+}
+
+impl Tracker {
+    fn eta_secs(&self) -> Option<u64> {
+        let migrated_since_start = self.actors_migrated.saturating_sub(self.actors_resumed);
+        if self.done {
+            return Some(0);
+        }
+        if migrated_since_start == 0 {
+            return None;
+        }
+        let actors_left = self.actors_total.saturating_sub(self.actors_migrated);
+        let secs_per_actor = self.start.elapsed().as_secs_f64() / migrated_since_start as f64;
+        Some((actors_left as f64 * secs_per_actor).ceil() as u64)
+    }
+
+    fn progress(&self) -> MigrationProgress {
+        let percent = if self.done {
+            100.0
+        } else if self.actors_total == 0 {
+            0.0
+        } else {
+            (self.actors_migrated as f64 * 100.0 / self.actors_total as f64).min(100.0)
+        };
+        MigrationProgress {
+            height: self.height.to_string(),
+            epoch: self.epoch,
+            actors_total: self.actors_total,
+            actors_migrated: self.actors_migrated,
+            actors_resumed: self.actors_resumed,
+            percent,
+            elapsed_secs: self.start.elapsed().as_secs(),
+            eta_secs: self.eta_secs(),
+            done: self.done,
+        }
+    }
+
+    fn update_metrics(&self) {
+        MIGRATION_ACTORS_TOTAL.set(self.actors_total);
+        MIGRATION_ACTORS_MIGRATED.set(self.actors_migrated);
+        MIGRATION_ETA.set(self.eta_secs().unwrap_or_default());
+    }
+}
+
+/// Returns the progress of the running state migration, or of the last one if none is running.
+pub fn current() -> Option<MigrationProgress> {
+    PROGRESS.read().as_ref().map(Tracker::progress)
+}
+
+pub(in crate::state_migration) fn start(height: Height, epoch: ChainEpoch) {
+    let tracker = Tracker {
+        height,
+        epoch,
+        start: Instant::now(),
+        actors_total: 0,
+        actors_migrated: 0,
+        actors_resumed: 0,
+        done: false,
+    };
+    tracker.update_metrics();
+    *PROGRESS.write() = Some(tracker);
+}
+
+/// Sets the number of actors to migrate, and the number of actors migrated before a restart.
+pub(in crate::state_migration) fn set_actors(actors_total: u64, actors_resumed: u64) {
+    if let Some(tracker) = PROGRESS.write().as_mut() {
+        tracker.actors_total = actors_total;
+        tracker.actors_migrated = actors_resumed;
+        tracker.actors_resumed = actors_resumed;
+        tracker.update_metrics();
+    }
+}
+
+pub(in crate::state_migration) fn set_migrated(actors_migrated: u64) {
+    if let Some(tracker) = PROGRESS.write().as_mut() {
+        tracker.actors_migrated = actors_migrated;
+        tracker.update_metrics();
+    }
+}
+
+pub(in crate::state_migration) fn finish() {
+    if let Some(tracker) = PROGRESS.write().as_mut() {
+        tracker.actors_migrated = tracker.actors_total;
+        tracker.done = true;
+        tracker.update_metrics();
+    }
+}
+
+/// Forgets about a failed migration.
+pub(in crate::state_migration) fn abort() {
+    *PROGRESS.write() = None;
+    MIGRATION_ETA.set(0);
+}
+
+/// Sets the store of the migration checkpoints. Migrations can't be resumed if this isn't called.
+pub fn init_checkpoints(settings: Arc<dyn SettingsStore + Sync + Send>) -> anyhow::Result<()> {
+    CHECKPOINT_STORE
+        .set(settings)
+        .map_err(|_| anyhow::anyhow!("the state migration checkpoint store is already initialized"))
+}
+
+/// State of a migration after its last complete shard.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(in crate::state_migration) struct Checkpoint {
+    /// Root of the state-tree being migrated.
+    #[serde(with = "crate::lotus_json")]
+    pub prior_state: Cid,
+    pub prior_epoch: ChainEpoch,
+    /// Number of actors migrated, in iteration order of the prior state-tree.
+    pub actors_done: u64,
+    /// Root of the partially migrated state-tree.
+    #[serde(with = "crate::lotus_json")]
+    pub actors_out: Cid,
+}
+
+/// Returns the checkpoint of the migration of `prior_state`, if any.
+pub(in crate::state_migration) fn load_checkpoint(
+    prior_state: &Cid,
+    prior_epoch: ChainEpoch,
+) -> anyhow::Result<Option<Checkpoint>> {
+    let Some(settings) = CHECKPOINT_STORE.get() else {
+        return Ok(None);
+    };
+    let checkpoint: Option<Option<Checkpoint>> =
+        settings.read_obj(STATE_MIGRATION_CHECKPOINT_KEY)?;
+    Ok(checkpoint.flatten().filter(|checkpoint| {
+        checkpoint.prior_state == *prior_state && checkpoint.prior_epoch == prior_epoch
+    }))
+}
+
+pub(in crate::state_migration) fn save_checkpoint(checkpoint: &Checkpoint) -> anyhow::Result<()> {
+    match CHECKPOINT_STORE.get() {
+        Some(settings) => settings.write_obj(STATE_MIGRATION_CHECKPOINT_KEY, &Some(checkpoint)),
+        None => Ok(()),
+    }
+}
+
+pub(in crate::state_migration) fn clear_checkpoint() -> anyhow::Result<()> {
+    match CHECKPOINT_STORE.get() {
+        Some(settings) => settings.write_obj(STATE_MIGRATION_CHECKPOINT_KEY, &None::<Checkpoint>),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_and_eta() {
+        let mut tracker = Tracker {
+            height: Height::Watermelon,
+            epoch: 42,
+            start: Instant::now() - std::time::Duration::from_secs(10),
+            actors_total: 400,
+            actors_migrated: 100,
+            actors_resumed: 0,
+            done: false,
+        };
+        let progress = tracker.progress();
+        assert_eq!(progress.percent, 25.0);
+        // 100 actors in 10 seconds, 300 actors left.
+        assert!((29..=31).contains(&progress.eta_secs.unwrap()));
+
+        // Actors migrated before a restart don't count towards the migration rate.
+        tracker.actors_resumed = 100;
+        assert_eq!(tracker.progress().eta_secs, None);
+
+        tracker.done = true;
+        let progress = tracker.progress();
+        assert_eq!(progress.percent, 100.0);
+        assert_eq!(progress.eta_secs, Some(0));
+    }
+}
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use crate::utils::net::global_http_client;
-use crate::utils::{retry, RetryArgs};
+use crate::utils::outbound::{retry, OutboundService};
 use crate::{
     daemon::bundle::load_actor_bundles,
     networks::{ChainConfig, Height, NetworkChain},
@@ -83,11 +83,7 @@ async fn test_state_migration(
             .into_temp_path();
         let timeout = Duration::from_secs(5);
         retry(
-            RetryArgs {
-                timeout: Some(timeout),
-                max_retries: Some(5),
-                ..Default::default()
-            },
+            OutboundService::Snapshot,
             || async {
                 let response = global_http_client().get(format!(
                     "https://forest-continuous-integration.fra1.digitaloceanspaces.com/state_migration/state/{old_state}.car"
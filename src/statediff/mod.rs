@@ -10,6 +10,7 @@ use std::{
 };
 
 use crate::ipld::json::{IpldJson, IpldJsonRef};
+use crate::networks::ActorRegistry;
 use crate::shim::{
     address::Address,
     state_tree::{ActorState, StateTree},
@@ -223,6 +224,134 @@ where
     Ok(())
 }
 
+/// Change of an actor between two state trees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActorChange {
+    Added(ActorState),
+    Removed(ActorState),
+    Modified { old: ActorState, new: ActorState },
+}
+
+/// Returns the actors that were added, removed, or modified between the `old`
+/// and `new` state trees, sorted by address.
+pub fn diff_actors<BS: Blockstore>(
+    bs: &Arc<BS>,
+    old: &Cid,
+    new: &Cid,
+) -> anyhow::Result<Vec<(Address, ActorChange)>> {
+    let mut old_actors = root_to_state_map(bs, old)?;
+    let mut changes = vec![];
+    StateTree::new_from_root(bs.clone(), new)?.for_each(|addr, actor: &ActorState| {
+        match old_actors.remove(&addr) {
+            Some(old) if &old != actor => changes.push((
+                addr,
+                ActorChange::Modified {
+                    old,
+                    new: actor.clone(),
+                },
+            )),
+            Some(_) => {}
+            None => changes.push((addr, ActorChange::Added(actor.clone()))),
+        }
+        Ok(())
+    })?;
+    changes.extend(
+        old_actors
+            .into_iter()
+            .map(|(addr, actor)| (addr, ActorChange::Removed(actor))),
+    );
+    changes.sort_by_key(|(addr, _)| *addr);
+    Ok(changes)
+}
+
+/// Prints the actors that were added, removed, or modified between the `old`
+/// and `new` state trees, with their balance, sequence and head changes.
+pub fn print_actor_changes<BS: Blockstore>(
+    bs: &Arc<BS>,
+    old: &Cid,
+    new: &Cid,
+) -> anyhow::Result<()> {
+    let stdout = stdout();
+    let mut handle = stdout.lock();
+    let registry = ActorRegistry::load(bs.as_ref());
+    for (addr, change) in diff_actors(bs, old, new)? {
+        match change {
+            ActorChange::Added(actor) => writeln!(
+                handle,
+                "{}",
+                format!(
+                    "+ {addr}: balance {}, sequence {}, head {}",
+                    actor.balance, actor.sequence, actor.state
+                )
+                .green()
+            )?,
+            ActorChange::Removed(actor) => writeln!(
+                handle,
+                "{}",
+                format!(
+                    "- {addr}: balance {}, sequence {}, head {}",
+                    actor.balance, actor.sequence, actor.state
+                )
+                .red()
+            )?,
+            ActorChange::Modified { old, new } => {
+                let mut changed = vec![];
+                if old.code != new.code {
+                    changed.push(format!(
+                        "code {} -> {}",
+                        registry.name(&old.code),
+                        registry.name(&new.code)
+                    ));
+                }
+                if old.balance != new.balance {
+                    changed.push(format!("balance {} -> {}", old.balance, new.balance));
+                }
+                if old.sequence != new.sequence {
+                    changed.push(format!("sequence {} -> {}", old.sequence, new.sequence));
+                }
+                if old.state != new.state {
+                    changed.push(format!("head {} -> {}", old.state, new.state));
+                }
+                writeln!(
+                    handle,
+                    "{}",
+                    format!("~ {addr}: {}", changed.join(", ")).yellow()
+                )?
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Prints a diff of the resolved state of the actor at `addr`, including its
+/// HAMTs and AMTs, between the `old` and `new` state trees. Links deeper than
+/// `depth` aren't resolved.
+pub fn print_actor_state_diff<BS: Blockstore>(
+    bs: &Arc<BS>,
+    old: &Cid,
+    new: &Cid,
+    addr: &Address,
+    depth: Option<u64>,
+) -> anyhow::Result<()> {
+    let resolve = |root| -> anyhow::Result<String> {
+        match StateTree::new_from_root(bs.clone(), root)?.get_actor(addr)? {
+            Some(actor) => Ok(serde_json::to_string_pretty(&actor_to_resolved(
+                bs, &actor, depth,
+            ))?),
+            None => Ok(String::new()),
+        }
+    };
+    let (old_json, new_json) = (resolve(old)?, resolve(new)?);
+    let stdout = stdout();
+    let mut handle = stdout.lock();
+    if old_json == new_json {
+        writeln!(handle, "Actor {addr} is unchanged")?;
+    } else {
+        print_diffs(&mut handle, TextDiff::from_lines(&old_json, &new_json))?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use crate::db::MemoryDB;
@@ -232,7 +361,7 @@ mod tests {
     use fil_actor_account_state::v10::State as AccountState;
     use fvm_ipld_blockstore::Blockstore;
 
-    use super::pp_actor_state;
+    use super::{diff_actors, pp_actor_state, ActorChange};
 
     fn mk_account_v10(db: &impl Blockstore, account: &AccountState) -> ActorState {
         // mainnet v10 account actor cid
@@ -304,4 +433,47 @@ mod tests {
 }"
         );
     }
+
+    #[test]
+    fn diff_actors_reports_added_removed_and_modified() {
+        use crate::shim::state_tree::{StateTree, StateTreeVersion};
+        use std::sync::Arc;
+
+        let db = Arc::new(MemoryDB::default());
+        let account = mk_account_v10(
+            &db,
+            &AccountState {
+                address: Address::new_id(0xdeadbeef).into(),
+            },
+        );
+        let mut funded = account.clone();
+        funded.balance = TokenAmount::from_whole(1).into();
+
+        let flush = |actors: &[(u64, &ActorState)]| {
+            let mut tree = StateTree::new(db.clone(), StateTreeVersion::V5).unwrap();
+            for (id, actor) in actors {
+                tree.set_actor(&Address::new_id(*id), (*actor).clone())
+                    .unwrap();
+            }
+            tree.flush().unwrap()
+        };
+        let old = flush(&[(100, &account), (101, &account), (102, &account)]);
+        let new = flush(&[(100, &account), (101, &funded), (103, &account)]);
+
+        assert!(diff_actors(&db, &old, &old).unwrap().is_empty());
+        assert_eq!(
+            diff_actors(&db, &old, &new).unwrap(),
+            vec![
+                (
+                    Address::new_id(101),
+                    ActorChange::Modified {
+                        old: account.clone(),
+                        new: funded
+                    }
+                ),
+                (Address::new_id(102), ActorChange::Removed(account.clone())),
+                (Address::new_id(103), ActorChange::Added(account)),
+            ]
+        );
+    }
 }
@@ -24,11 +24,14 @@ where
             // Run command
             match cmd {
                 Subcommand::Benchmark(cmd) => cmd.run().await,
+                Subcommand::State(cmd) => cmd.run().await,
                 Subcommand::StateMigration(state_migration) => state_migration.run().await,
                 Subcommand::Snapshot(cmd) => cmd.run().await,
                 Subcommand::Fetch(cmd) => cmd.run().await,
                 Subcommand::Archive(cmd) => cmd.run().await,
                 Subcommand::DB(cmd) => cmd.run().await,
+                Subcommand::Devnet(cmd) => cmd.run().await,
+                Subcommand::Index(cmd) => cmd.run().await,
                 Subcommand::Car(cmd) => cmd.run().await,
                 Subcommand::Api(cmd) => cmd.run().await,
             }
@@ -1,20 +1,26 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+mod docs;
+
 use crate::blocks::Tipset;
 use crate::blocks::TipsetKey;
 use crate::cid_collections::CidHashSet;
 use crate::db::car::ManyCar;
 use crate::lotus_json::HasLotusJson;
 use crate::message::Message as _;
-use crate::rpc_api::data_types::MessageLookup;
+use crate::rpc_api::data_types::{MessageLookup, SectorPreCommitInfo};
 use crate::rpc_api::eth_api::Address as EthAddress;
 use crate::rpc_api::eth_api::*;
 use crate::rpc_client::{ApiInfo, JsonRpcError, RpcRequest};
 use crate::shim::address::{Address, Protocol};
+use crate::shim::clock::EPOCHS_IN_DAY;
 use crate::shim::crypto::Signature;
+use crate::shim::sector::RegisteredSealProofV3;
+use crate::shim::version::NetworkVersion;
 use ahash::HashMap;
 use clap::{Subcommand, ValueEnum};
+use fil_actors_shared::fvm_ipld_bitfield::BitField;
 use fil_actors_shared::v10::runtime::DomainSeparationTag;
 use futures::stream::FuturesUnordered;
 use futures::StreamExt;
@@ -58,6 +64,16 @@ pub enum ApiCommands {
         #[arg(long = "ws")]
         use_websocket: bool,
     },
+    /// Generate a reference of all RPC methods, with parameter and result
+    /// examples
+    GenerateDocs {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = docs::DocsFormat::Markdown)]
+        format: docs::DocsFormat,
+        /// Output file. Defaults to stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
 }
 
 /// For more information about each flag, refer to the Forest documentation at:
@@ -96,6 +112,13 @@ impl ApiCommands {
 
                 compare_apis(forest, lotus, snapshot_files, config).await?
             }
+            Self::GenerateDocs { format, output } => {
+                let docs = docs::generate_docs(format);
+                match output {
+                    Some(path) => std::fs::write(path, docs)?,
+                    None => print!("{docs}"),
+                }
+            }
         }
         Ok(())
     }
@@ -305,6 +328,10 @@ fn chain_tests_with_tipset(shared_tipset: &Tipset) -> Vec<RpcTest> {
             TipsetKey::default(),
         )),
         RpcTest::identity(ApiInfo::chain_get_tipset_req(shared_tipset.key().clone())),
+        RpcTest::identity(ApiInfo::chain_get_path_req(
+            shared_tipset.parents().clone(),
+            shared_tipset.key().clone(),
+        )),
         RpcTest::identity(ApiInfo::chain_read_obj_req(*shared_block.cid())),
         RpcTest::identity(ApiInfo::chain_has_obj_req(*shared_block.cid())),
     ]
@@ -377,12 +404,19 @@ fn state_tests(shared_tipset: &Tipset) -> Vec<RpcTest> {
         RpcTest::identity(ApiInfo::state_network_version_req(
             shared_tipset.key().clone(),
         )),
+        RpcTest::identity(ApiInfo::state_actor_code_cids_req(NetworkVersion::V21)),
+        RpcTest::identity(ApiInfo::state_actor_manifest_cid_req(NetworkVersion::V21)),
         RpcTest::identity(ApiInfo::state_list_miners_req(shared_tipset.key().clone())),
         RpcTest::identity(ApiInfo::state_sector_get_info_req(
             shared_block.miner_address,
             101,
             shared_tipset.key().clone(),
         )),
+        RpcTest::identity(ApiInfo::state_miner_sectors_req(
+            shared_block.miner_address,
+            Some(BitField::try_from_bits([101, 102, 0xdeadbeef]).unwrap()),
+            shared_tipset.key().clone(),
+        )),
         RpcTest::identity(ApiInfo::msig_get_available_balance_req(
             Address::new_id(18101), // msig address id
             shared_tipset.key().clone(),
@@ -418,6 +452,24 @@ fn wallet_tests() -> Vec<RpcTest> {
     ]
 }
 
+fn gas_tests() -> Vec<RpcTest> {
+    vec![
+        // Both nodes add noise to the estimation, within about 1%.
+        RpcTest::validate(
+            ApiInfo::gas_estimate_gas_premium_req(
+                10,
+                Address::SYSTEM_ACTOR,
+                0,
+                TipsetKey::default(),
+            ),
+            |forest, lotus| match (forest.parse::<u128>(), lotus.parse::<u128>()) {
+                (Ok(forest), Ok(lotus)) => forest.abs_diff(lotus) <= lotus / 20,
+                _ => false,
+            },
+        ),
+    ]
+}
+
 fn eth_tests() -> Vec<RpcTest> {
     vec![
         RpcTest::identity(ApiInfo::eth_accounts_req()),
@@ -452,6 +504,9 @@ fn eth_tests_with_tipset(shared_tipset: &Tipset) -> Vec<RpcTest> {
             EthAddress::from_str("0xff000000000000000000000000000000000003ec").unwrap(),
             BlockNumberOrHash::from_block_number(shared_tipset.epoch()),
         )),
+        RpcTest::identity(ApiInfo::eth_get_block_transaction_count_by_number_req(
+            BlockNumberOrHash::from_block_number(shared_tipset.epoch()),
+        )),
     ]
 }
 
@@ -593,6 +648,42 @@ fn snapshot_tests(store: &ManyCar, n_tipsets: usize) -> anyhow::Result<Vec<RpcTe
                 block.epoch,
                 tipset.key().clone(),
             )));
+            let sector = SectorPreCommitInfo {
+                seal_proof: RegisteredSealProofV3::StackedDRG32GiBV1P1.into(),
+                sector_number: 0,
+                sealed_cid: *block.cid(),
+                seal_rand_epoch: tipset.epoch(),
+                deal_ids: vec![],
+                expiration: tipset.epoch() + 540 * EPOCHS_IN_DAY,
+                unsealed_cid: None,
+            };
+            tests.push(RpcTest::identity(
+                ApiInfo::state_miner_initial_pledge_collateral_req(
+                    block.miner_address,
+                    sector.clone(),
+                    tipset.key().clone(),
+                ),
+            ));
+            tests.push(RpcTest::identity(
+                ApiInfo::state_miner_pre_commit_deposit_for_power_req(
+                    block.miner_address,
+                    sector,
+                    tipset.key().clone(),
+                ),
+            ));
+            tests.push(RpcTest::identity(
+                ApiInfo::state_miner_available_balance_req(
+                    block.miner_address,
+                    tipset.key().clone(),
+                ),
+            ));
+            tests.push(RpcTest::identity(
+                ApiInfo::state_miner_sector_allocated_req(
+                    block.miner_address,
+                    0,
+                    tipset.key().clone(),
+                ),
+            ));
             tests.push(RpcTest::identity(ApiInfo::state_miner_recoveries_req(
                 block.miner_address,
                 tipset.key().clone(),
@@ -605,6 +696,13 @@ fn snapshot_tests(store: &ManyCar, n_tipsets: usize) -> anyhow::Result<Vec<RpcTe
         tests.push(RpcTest::identity(ApiInfo::state_circulating_supply_req(
             tipset.key().clone(),
         )));
+        tests.push(RpcTest::identity(
+            ApiInfo::state_deal_provider_collateral_bounds_req(
+                32 << 30,
+                false,
+                tipset.key().clone(),
+            ),
+        ));
         tests.push(RpcTest::identity(
             ApiInfo::state_vm_circulating_supply_internal_req(tipset.key().clone()),
         ));
@@ -667,6 +765,7 @@ async fn compare_apis(
     tests.extend(net_tests());
     tests.extend(node_tests());
     tests.extend(wallet_tests());
+    tests.extend(gas_tests());
     tests.extend(eth_tests());
 
     if !snapshot_files.is_empty() {
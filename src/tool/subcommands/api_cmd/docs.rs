@@ -0,0 +1,589 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Generates the RPC API reference. Every method in [`ACCESS_MAP`] is listed
+//! with its permission, and methods in [`method_examples`] also get parameter
+//! and result examples. The examples are built with the typed `ApiInfo::*_req`
+//! constructors and serialized with the Lotus JSON serializers, so the
+//! reference can't drift from what the node actually accepts and returns.
+
+use crate::beacon::BeaconEntry;
+use crate::blocks::{CachingBlockHeader, Tipset, TipsetKey};
+use crate::chain::store::{
+    consensus_fault::{ConsensusFault, ConsensusFaultKind},
+    reorg::Reorg,
+};
+use crate::chain_sync::checkpoints::Checkpoint;
+use crate::db::GcReport;
+use crate::key_management::KeyInfo;
+use crate::libp2p::BlockList;
+use crate::lotus_json::HasLotusJson;
+use crate::message::SignedMessage;
+use crate::rpc_api::chain_api::{MessagesInTipsetOptions, PathChange};
+use crate::rpc_api::data_types::{APIVersion, ApiMessage, ApiReceipt, MessageLookup, Version};
+use crate::rpc_api::eth_api::{
+    Address as EthAddress, BigInt, BlockNumberOrHash, Bytes, FeeHistory, Hash, Predefined, Uint64,
+};
+use crate::rpc_api::net_api::NetInfoResult;
+use crate::rpc_api::node_api::NodeStatus;
+use crate::rpc_api::{Access, ACCESS_MAP};
+use crate::rpc_client::{ApiInfo, RpcRequest};
+use crate::shim::{
+    address::Address,
+    crypto::{Signature, SignatureType},
+    econ::TokenAmount,
+    executor::Receipt,
+    message::Message,
+    state_tree::ActorState,
+    version::NetworkVersion,
+};
+use crate::state_manager::ActorChange;
+use crate::state_migration::progress::MigrationProgress;
+use ahash::HashMap;
+use cid::Cid;
+use clap::ValueEnum;
+use itertools::Itertools as _;
+use libipld_core::ipld::Ipld;
+use std::fmt::Write as _;
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+#[clap(rename_all = "kebab_case")]
+pub enum DocsFormat {
+    Markdown,
+    Html,
+}
+
+/// Example request and response of an RPC method, in Lotus JSON.
+struct MethodExample {
+    method_name: &'static str,
+    rpc_endpoint: &'static str,
+    params: serde_json::Value,
+    result: serde_json::Value,
+}
+
+impl MethodExample {
+    fn new<T: HasLotusJson>(request: RpcRequest<T>, result: T) -> Self {
+        MethodExample {
+            method_name: request.method_name,
+            rpc_endpoint: request.rpc_endpoint(),
+            params: request.params().clone(),
+            result: serde_json::to_value(result.into_lotus_json())
+                .expect("Lotus JSON serialization is infallible"),
+        }
+    }
+}
+
+/// Returns the first Lotus JSON snapshot of `T` as an example value.
+fn example<T: HasLotusJson>() -> T {
+    T::snapshots()
+        .into_iter()
+        .next()
+        .expect("Lotus JSON types must have at least one snapshot")
+        .1
+}
+
+fn method_examples() -> Vec<MethodExample> {
+    let address = Address::new_id(1234);
+    let peer_id = "12D3KooWBqGnd4Ey5fKsBCCPTZSqBRGomcTFDP5bZCkWCAtMLNAi".to_string();
+    let message_lookup = MessageLookup {
+        receipt: example::<Receipt>(),
+        tipset: example::<TipsetKey>(),
+        height: 10101,
+        message: Cid::default(),
+        return_dec: Ipld::Null,
+    };
+    vec![
+        // Auth API
+        MethodExample::new(
+            ApiInfo::auth_new_req(
+                vec!["read".to_string(), "write".to_string()],
+                None,
+                chrono::Duration::days(1),
+            ),
+            b"eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9".to_vec(),
+        ),
+        // Common API
+        MethodExample::new(
+            ApiInfo::version_req(),
+            APIVersion {
+                version: "0.16.0+git.1234abcd".to_string(),
+                api_version: Version::new(2, 3, 0),
+                block_delay: 30,
+            },
+        ),
+        MethodExample::new(
+            ApiInfo::session_req(),
+            "2bc1bd54-e2c2-4e40-8ea7-ee23b4d3cd4c".to_string(),
+        ),
+        MethodExample::new(ApiInfo::shutdown_req(), ()),
+        MethodExample::new(ApiInfo::config_reload_req(), Default::default()),
+        MethodExample::new(
+            ApiInfo::log_list_req(),
+            vec!["forest_filecoin::chain_sync".to_string()],
+        ),
+        MethodExample::new(
+            ApiInfo::log_set_level_req(
+                "forest_filecoin::chain_sync".to_string(),
+                "debug".to_string(),
+            ),
+            (),
+        ),
+        // Node API
+        MethodExample::new(ApiInfo::node_status_req(), NodeStatus::default()),
+        // Net API
+        MethodExample::new(ApiInfo::net_info_req(), NetInfoResult::default()),
+        MethodExample::new(ApiInfo::net_disconnect_req(peer_id.clone()), ()),
+        MethodExample::new(ApiInfo::net_protect_add_req(vec![peer_id.clone()]), ()),
+        MethodExample::new(ApiInfo::net_protect_remove_req(vec![peer_id.clone()]), ()),
+        MethodExample::new(ApiInfo::net_protect_list_req(), vec![peer_id]),
+        MethodExample::new(ApiInfo::net_block_add_req(BlockList::default()), ()),
+        MethodExample::new(ApiInfo::net_block_remove_req(BlockList::default()), ()),
+        MethodExample::new(ApiInfo::net_block_list_req(), BlockList::default()),
+        MethodExample::new(ApiInfo::net_discover_req(), ()),
+        // Database API
+        MethodExample::new(ApiInfo::database_garbage_collect_req(), GcReport::default()),
+        // Beacon API
+        MethodExample::new(
+            ApiInfo::beacon_get_entry_req(10101),
+            example::<BeaconEntry>(),
+        ),
+        // Chain API
+        MethodExample::new(ApiInfo::chain_head_req(), example::<Tipset>()),
+        MethodExample::new(ApiInfo::chain_get_genesis_req(), Some(example::<Tipset>())),
+        MethodExample::new(
+            ApiInfo::chain_get_tipset_req(example::<TipsetKey>()),
+            example::<Tipset>(),
+        ),
+        MethodExample::new(
+            ApiInfo::chain_get_tipset_by_height_req(0, TipsetKey::default()),
+            example::<Tipset>(),
+        ),
+        MethodExample::new(
+            ApiInfo::chain_get_path_req(TipsetKey::default(), example::<TipsetKey>()),
+            vec![PathChange::Apply(example::<Tipset>())],
+        ),
+        MethodExample::new(
+            ApiInfo::chain_compute_base_fee_req(TipsetKey::default()),
+            TokenAmount::from_atto(100),
+        ),
+        MethodExample::new(
+            ApiInfo::chain_get_block_req(Cid::default()),
+            example::<CachingBlockHeader>(),
+        ),
+        MethodExample::new(
+            ApiInfo::chain_get_message_req(Cid::default()),
+            example::<Message>(),
+        ),
+        MethodExample::new(ApiInfo::chain_has_obj_req(Cid::default()), true),
+        MethodExample::new(
+            ApiInfo::chain_read_obj_req(Cid::default()),
+            vec![0x82, 0x01, 0x02],
+        ),
+        MethodExample::new(ApiInfo::chain_set_head_req(example::<TipsetKey>()), ()),
+        MethodExample::new(ApiInfo::chain_get_min_base_fee_req(20), "100".to_string()),
+        MethodExample::new(
+            ApiInfo::chain_get_messages_in_tipset_req(example::<TipsetKey>()),
+            vec![ApiMessage::new(Cid::default(), example::<Message>())],
+        ),
+        MethodExample::new(
+            ApiInfo::chain_get_messages_in_tipset_with_options_req(
+                example::<TipsetKey>(),
+                MessagesInTipsetOptions::default(),
+            ),
+            vec![ApiMessage::new(Cid::default(), example::<Message>())],
+        ),
+        MethodExample::new(
+            ApiInfo::chain_get_parent_messages_req(Cid::default()),
+            vec![ApiMessage::new(Cid::default(), example::<Message>())],
+        ),
+        MethodExample::new(
+            ApiInfo::chain_get_parent_receipts_req(Cid::default()),
+            vec![ApiReceipt {
+                exit_code: 0.into(),
+                return_data: Default::default(),
+                gas_used: 1_234_567,
+                events_root: None,
+            }],
+        ),
+        MethodExample::new(
+            ApiInfo::chain_add_car_req("/var/lib/forest/snapshot.car.zst".into()),
+            example::<Tipset>(),
+        ),
+        MethodExample::new(
+            ApiInfo::chain_remove_car_req("/var/lib/forest/snapshot.car.zst".into()),
+            (),
+        ),
+        MethodExample::new(
+            ApiInfo::chain_list_cars_req(),
+            vec!["/var/lib/forest/snapshot.car.zst".into()],
+        ),
+        MethodExample::new(
+            ApiInfo::chain_get_reorgs_req(),
+            vec![Reorg {
+                timestamp: 1_700_000_000,
+                old_head: example::<TipsetKey>(),
+                old_epoch: 10101,
+                new_head: example::<TipsetKey>(),
+                new_epoch: 10101,
+                fork_epoch: 10100,
+                depth: 1,
+                orphaned_blocks: vec![Cid::default()],
+            }],
+        ),
+        // Message Pool API
+        MethodExample::new(ApiInfo::mpool_get_nonce_req(address), 7),
+        MethodExample::new(
+            ApiInfo::mpool_pending_req(vec![]),
+            vec![example::<SignedMessage>()],
+        ),
+        MethodExample::new(
+            ApiInfo::mpool_push_message_req(example::<Message>(), None),
+            example::<SignedMessage>(),
+        ),
+        MethodExample::new(
+            ApiInfo::mpool_batch_push_req(vec![example::<SignedMessage>()]),
+            vec![Cid::default()],
+        ),
+        MethodExample::new(
+            ApiInfo::mpool_batch_push_message_req(vec![example::<Message>()], None),
+            vec![example::<SignedMessage>()],
+        ),
+        MethodExample::new(
+            ApiInfo::mpool_select_req(TipsetKey::default(), 0.8),
+            vec![example::<SignedMessage>()],
+        ),
+        // Gas API
+        MethodExample::new(
+            ApiInfo::gas_estimate_message_gas_req(example::<Message>(), None, TipsetKey::default()),
+            example::<Message>(),
+        ),
+        MethodExample::new(
+            ApiInfo::gas_estimate_gas_premium_req(10, address, 1_000_000, TipsetKey::default()),
+            "100000".to_string(),
+        ),
+        // Sync API
+        MethodExample::new(ApiInfo::sync_check_bad_req(Cid::default()), String::new()),
+        MethodExample::new(
+            ApiInfo::sync_consensus_faults_req(),
+            vec![ConsensusFault {
+                kind: ConsensusFaultKind::DoubleForkMining,
+                miner: address,
+                epoch: 10101,
+                block_header_1: example::<CachingBlockHeader>(),
+                block_header_2: example::<CachingBlockHeader>(),
+                block_header_extra: None,
+            }],
+        ),
+        MethodExample::new(ApiInfo::sync_mark_bad_req(Cid::default()), ()),
+        MethodExample::new(ApiInfo::sync_unmark_bad_req(Cid::default()), ()),
+        MethodExample::new(ApiInfo::sync_unmark_all_bad_req(), ()),
+        MethodExample::new(
+            ApiInfo::sync_set_checkpoint_req(Checkpoint {
+                epoch: 10101,
+                cids: vec![Cid::default()],
+            }),
+            (),
+        ),
+        // State API
+        MethodExample::new(
+            ApiInfo::state_network_name_req(),
+            "calibrationnet".to_string(),
+        ),
+        MethodExample::new(
+            ApiInfo::state_network_version_req(TipsetKey::default()),
+            NetworkVersion::V21,
+        ),
+        MethodExample::new(
+            ApiInfo::state_actor_code_cids_req(NetworkVersion::V21),
+            HashMap::from_iter([("account".to_string(), Cid::default())]),
+        ),
+        MethodExample::new(
+            ApiInfo::state_actor_manifest_cid_req(NetworkVersion::V21),
+            Cid::default(),
+        ),
+        MethodExample::new(
+            ApiInfo::state_wait_msg_req(Cid::default(), 5),
+            Some(message_lookup.clone()),
+        ),
+        MethodExample::new(
+            ApiInfo::state_search_msg_req(Cid::default()),
+            Some(message_lookup.clone()),
+        ),
+        MethodExample::new(
+            ApiInfo::state_search_msg_limited_req(Cid::default(), 2880),
+            Some(message_lookup),
+        ),
+        MethodExample::new(ApiInfo::state_subscribe_actor_changes_req(vec![address]), 1),
+        MethodExample::new(
+            ApiInfo::state_next_actor_changes_req(1),
+            vec![ActorChange {
+                address,
+                epoch: 10101,
+                tipset: example::<TipsetKey>(),
+                old_head: Some(Cid::default()),
+                new_head: Some(Cid::default()),
+            }],
+        ),
+        MethodExample::new(ApiInfo::state_unsubscribe_actor_changes_req(1), true),
+        MethodExample::new(
+            ApiInfo::state_migration_progress_req(),
+            Some(MigrationProgress {
+                height: "Watermelon".to_string(),
+                epoch: 10101,
+                actors_total: 1000,
+                actors_migrated: 250,
+                actors_resumed: 0,
+                percent: 25.0,
+                elapsed_secs: 60,
+                eta_secs: Some(180),
+                done: false,
+            }),
+        ),
+        MethodExample::new(
+            ApiInfo::msig_get_available_balance_req(address, TipsetKey::default()),
+            TokenAmount::from_whole(10),
+        ),
+        MethodExample::new(
+            ApiInfo::state_miner_available_balance_req(address, TipsetKey::default()),
+            TokenAmount::from_whole(10),
+        ),
+        MethodExample::new(
+            ApiInfo::state_miner_faults_req(address, TipsetKey::default()),
+            Default::default(),
+        ),
+        MethodExample::new(
+            ApiInfo::state_miner_recoveries_req(address, TipsetKey::default()),
+            Default::default(),
+        ),
+        MethodExample::new(
+            ApiInfo::state_get_actor_req(address, TipsetKey::default()),
+            Some(example::<ActorState>()),
+        ),
+        MethodExample::new(
+            ApiInfo::state_lookup_id_req(example::<Address>(), TipsetKey::default()),
+            Some(address),
+        ),
+        MethodExample::new(
+            ApiInfo::state_account_key_req(address, TipsetKey::default()),
+            example::<Address>(),
+        ),
+        MethodExample::new(
+            ApiInfo::state_list_miners_req(TipsetKey::default()),
+            vec![Address::new_id(1000)],
+        ),
+        MethodExample::new(
+            ApiInfo::state_circulating_supply_req(TipsetKey::default()),
+            TokenAmount::from_whole(1_000_000),
+        ),
+        // Wallet API
+        MethodExample::new(
+            ApiInfo::wallet_balance_req(address.to_string()),
+            TokenAmount::from_whole(1).atto().to_string(),
+        ),
+        MethodExample::new(ApiInfo::wallet_has_req(address.to_string()), true),
+        MethodExample::new(ApiInfo::wallet_list_req(), vec![example::<Address>()]),
+        MethodExample::new(
+            ApiInfo::wallet_default_address_req(),
+            Some(address.to_string()),
+        ),
+        MethodExample::new(ApiInfo::wallet_set_default_req(address), ()),
+        MethodExample::new(
+            ApiInfo::wallet_new_req(SignatureType::Secp256k1),
+            example::<Address>().to_string(),
+        ),
+        MethodExample::new(
+            ApiInfo::wallet_import_req(vec![example::<KeyInfo>()]),
+            example::<Address>().to_string(),
+        ),
+        MethodExample::new(ApiInfo::wallet_delete_req(address.to_string()), ()),
+        MethodExample::new(
+            ApiInfo::wallet_sign_message_req(address, example::<Message>()),
+            example::<SignedMessage>(),
+        ),
+        MethodExample::new(
+            ApiInfo::wallet_export_req(address.to_string()),
+            example::<KeyInfo>(),
+        ),
+        MethodExample::new(
+            ApiInfo::wallet_sign_req(address, b"Hello world!".to_vec()),
+            example::<Signature>(),
+        ),
+        MethodExample::new(
+            ApiInfo::wallet_verify_req(address, b"Hello world!".to_vec(), example::<Signature>()),
+            true,
+        ),
+        // Eth API
+        MethodExample::new(ApiInfo::eth_accounts_req(), vec![]),
+        MethodExample::new(ApiInfo::eth_block_number_req(), "0x1a2b".to_string()),
+        MethodExample::new(ApiInfo::eth_chain_id_req(), "0x4cb2f".to_string()),
+        MethodExample::new(ApiInfo::eth_gas_price_req(), "0x186a0".to_string()),
+        MethodExample::new(
+            ApiInfo::eth_fee_history_req(
+                2,
+                BlockNumberOrHash::from_predefined(Predefined::Latest),
+                Some(vec![25.0, 75.0]),
+            ),
+            FeeHistory {
+                oldest_block: Uint64(10100),
+                base_fee_per_gas: vec![BigInt(100.into()), BigInt(100.into()), BigInt(101.into())],
+                gas_used_ratio: vec![0.5, 0.6],
+                reward: Some(vec![vec![BigInt(1.into()), BigInt(2.into())]; 2]),
+            },
+        ),
+        MethodExample::new(
+            ApiInfo::eth_get_balance_req(
+                EthAddress::default(),
+                BlockNumberOrHash::from_block_number(10101),
+            ),
+            BigInt(1_000_000.into()),
+        ),
+        MethodExample::new(
+            ApiInfo::eth_get_block_transaction_count_by_number_req(
+                BlockNumberOrHash::from_block_number(10101),
+            ),
+            Uint64(3),
+        ),
+        MethodExample::new(
+            ApiInfo::eth_get_block_transaction_count_by_hash_req(Hash::default()),
+            Uint64(3),
+        ),
+        MethodExample::new(
+            ApiInfo::eth_get_uncle_count_by_block_hash_req(Hash::default()),
+            Uint64(0),
+        ),
+        MethodExample::new(
+            ApiInfo::eth_send_raw_transaction_req(Bytes(vec![0x02, 0xf8, 0x6c])),
+            Hash::default(),
+        ),
+    ]
+}
+
+fn access_name(access: &Access) -> &'static str {
+    match access {
+        Access::Admin => "admin",
+        Access::Sign => "sign",
+        Access::Write => "write",
+        Access::Read => "read",
+    }
+}
+
+fn pretty(value: &serde_json::Value) -> String {
+    serde_json::to_string_pretty(value).expect("JSON values always serialize")
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Renders the reference of all RPC methods, sorted by name.
+pub fn generate_docs(format: DocsFormat) -> String {
+    let mut examples: HashMap<_, _> = method_examples()
+        .into_iter()
+        .map(|example| (example.method_name, example))
+        .collect();
+    let methods = ACCESS_MAP
+        .keys()
+        .chain(examples.keys())
+        .copied()
+        .sorted()
+        .dedup()
+        .collect_vec();
+
+    let mut out = String::new();
+    match format {
+        DocsFormat::Markdown => {
+            out.push_str("# RPC API reference\n");
+            for method in methods {
+                let _ = write!(out, "\n## `{method}`\n\n");
+                if let Some(access) = ACCESS_MAP.get(method) {
+                    let _ = writeln!(out, "Permission: `{}`\n", access_name(access));
+                }
+                match examples.remove(method) {
+                    Some(example) => {
+                        let _ = writeln!(out, "Endpoint: `/{}`\n", example.rpc_endpoint);
+                        let _ = writeln!(
+                            out,
+                            "Params:\n\n```json\n{}\n```\n",
+                            pretty(&example.params)
+                        );
+                        let _ =
+                            writeln!(out, "Result:\n\n```json\n{}\n```", pretty(&example.result));
+                    }
+                    None => out.push_str("No example available.\n"),
+                }
+            }
+        }
+        DocsFormat::Html => {
+            out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+            out.push_str("<title>RPC API reference</title>\n</head>\n<body>\n");
+            out.push_str("<h1>RPC API reference</h1>\n");
+            for method in methods {
+                let _ = writeln!(out, "<h2 id=\"{method}\"><code>{method}</code></h2>");
+                if let Some(access) = ACCESS_MAP.get(method) {
+                    let _ = writeln!(
+                        out,
+                        "<p>Permission: <code>{}</code></p>",
+                        access_name(access)
+                    );
+                }
+                match examples.remove(method) {
+                    Some(example) => {
+                        let _ = writeln!(
+                            out,
+                            "<p>Endpoint: <code>/{}</code></p>",
+                            example.rpc_endpoint
+                        );
+                        let _ = writeln!(
+                            out,
+                            "<p>Params:</p>\n<pre><code>{}</code></pre>",
+                            escape_html(&pretty(&example.params))
+                        );
+                        let _ = writeln!(
+                            out,
+                            "<p>Result:</p>\n<pre><code>{}</code></pre>",
+                            escape_html(&pretty(&example.result))
+                        );
+                    }
+                    None => out.push_str("<p>No example available.</p>\n"),
+                }
+            }
+            out.push_str("</body>\n</html>\n");
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn examples_are_access_controlled() {
+        for example in method_examples() {
+            assert!(
+                ACCESS_MAP.contains_key(example.method_name),
+                "{} is missing from the access map",
+                example.method_name
+            );
+        }
+    }
+
+    #[test]
+    fn examples_are_unique() {
+        let examples = method_examples();
+        let methods: ahash::HashSet<_> = examples.iter().map(|e| e.method_name).collect();
+        assert_eq!(methods.len(), examples.len());
+    }
+
+    #[test]
+    fn every_method_is_documented() {
+        for format in [DocsFormat::Markdown, DocsFormat::Html] {
+            let docs = generate_docs(format);
+            for method in ACCESS_MAP.keys() {
+                assert!(docs.contains(method), "{method} is not documented");
+            }
+        }
+    }
+}
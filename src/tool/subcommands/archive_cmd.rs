@@ -38,24 +38,28 @@ use crate::db::car::{AnyCar, RandomAccessFileReader};
 use crate::interpreter::VMTrace;
 use crate::ipld::{stream_graph, unordered_stream_graph};
 use crate::networks::{butterflynet, calibnet, mainnet, ChainConfig, NetworkChain};
-use crate::shim::address::CurrentNetwork;
+use crate::shim::address::{Address, CurrentNetwork};
 use crate::shim::clock::{ChainEpoch, EPOCHS_IN_DAY, EPOCH_DURATION_SECONDS};
 use crate::shim::fvm_shared_latest::address::Network;
 use crate::shim::machine::MultiEngine;
+use crate::shim::state_tree::StateTree;
 use crate::state_manager::{apply_block_messages, NO_CALLBACK};
+use crate::utils::db::car_stream::{CarBlock, CarStream};
+use crate::utils::encoding::extract_cids;
 use anyhow::{bail, Context as _};
 use chrono::NaiveDateTime;
 use cid::Cid;
 use clap::Subcommand;
 use dialoguer::{theme::ColorfulTheme, Confirm};
-use futures::TryStreamExt;
+use futures::{StreamExt, TryStreamExt};
 use fvm_ipld_blockstore::Blockstore;
-use indicatif::ProgressIterator;
+use human_bytes::human_bytes;
+use indicatif::{ProgressBar, ProgressIterator, ProgressStyle};
 use itertools::Itertools;
 use sha2::Sha256;
 use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::io::{AsyncBufRead, AsyncWriteExt, BufWriter};
 use tracing::info;
 
 #[derive(Debug, Subcommand)]
@@ -64,6 +68,10 @@ pub enum ArchiveCommands {
     Info {
         /// Path to an uncompressed archive (CAR)
         snapshot: PathBuf,
+        /// Scan every block of the archive and report the number of blocks by
+        /// type, the compression ratio and the health of the index.
+        #[arg(long)]
+        deep: bool,
     },
     /// Trim a snapshot of the chain and write it to `<output_path>`
     Export {
@@ -126,16 +134,52 @@ pub enum ArchiveCommands {
         #[arg(long)]
         depth: Option<u64>,
     },
+    /// Export the state of a single actor over a range of epochs. The output
+    /// archive contains the block headers of the range, the state-tree nodes
+    /// needed to look the actor up, and the whole state of the actor.
+    ExportActor {
+        /// Snapshot input paths. Supports `.car`, `.car.zst`, and `.forest.car.zst`.
+        #[arg(required = true)]
+        snapshot_files: Vec<PathBuf>,
+        /// Address of the actor, e.g. `f01000`.
+        #[arg(long)]
+        actor: Address,
+        /// Lowest epoch of the range.
+        #[arg(long)]
+        from: ChainEpoch,
+        /// Highest epoch of the range. Defaults to the heaviest tipset.
+        #[arg(long)]
+        to: Option<ChainEpoch>,
+        /// Output file, in the `.forest.car.zst` format.
+        #[arg(short, long)]
+        output_path: PathBuf,
+        /// Overwrite output file without prompting.
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
 }
 
 impl ArchiveCommands {
     pub async fn run(self) -> anyhow::Result<()> {
         match self {
-            Self::Info { snapshot } => {
-                println!(
-                    "{}",
-                    ArchiveInfo::from_store(AnyCar::try_from(snapshot.as_path())?)?
-                );
+            Self::Info { snapshot, deep } => {
+                let store = AnyCar::try_from(snapshot.as_path())?;
+                let stats = if deep {
+                    let file = tokio::fs::File::open(&snapshot).await?;
+                    let file_size = file.metadata().await?.len();
+                    let pb = ProgressBar::new(file_size).with_style(
+                        ProgressStyle::with_template("{bar} {percent}%, eta: {eta}")
+                            .expect("infallible"),
+                    );
+                    let reader = tokio::io::BufReader::new(pb.wrap_async_read(file));
+                    Some(ArchiveStats::from_car(&store, reader, file_size).await?)
+                } else {
+                    None
+                };
+                println!("{}", ArchiveInfo::from_store(store)?);
+                if let Some(stats) = stats {
+                    println!("{stats}");
+                }
                 Ok(())
             }
             Self::Export {
@@ -174,6 +218,14 @@ impl ArchiveCommands {
                 epoch,
                 depth,
             } => show_tipset_diff(snapshot_files, epoch, depth).await,
+            Self::ExportActor {
+                snapshot_files,
+                actor,
+                from,
+                to,
+                output_path,
+                force,
+            } => export_actor(snapshot_files, actor, from, to, output_path, force).await,
         }
     }
 }
@@ -297,6 +349,123 @@ impl ArchiveInfo {
     }
 }
 
+/// Statistics gathered by scanning every block of an archive.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ArchiveStats {
+    head_epoch: ChainEpoch,
+    lowest_epoch: ChainEpoch,
+    /// Number of tipsets whose parent state-root is in the archive.
+    state_roots: u64,
+    blocks: u64,
+    headers: u64,
+    /// Messages and message receipts.
+    messages: u64,
+    state: u64,
+    file_size: u64,
+    uncompressed_size: u64,
+    /// Number of blocks that cannot be found with the index of the archive.
+    unindexed: u64,
+}
+
+impl std::fmt::Display for ArchiveStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let other = self.blocks - self.headers - self.messages - self.state;
+        writeln!(
+            f,
+            "Epoch range:   {}..={}",
+            self.lowest_epoch, self.head_epoch
+        )?;
+        writeln!(f, "Parent states: {}", self.state_roots)?;
+        writeln!(f, "Blocks:        {}", self.blocks)?;
+        writeln!(f, "  Headers:     {}", self.headers)?;
+        writeln!(f, "  Messages:    {}", self.messages)?;
+        writeln!(f, "  State:       {}", self.state)?;
+        writeln!(f, "  Other:       {other}")?;
+        writeln!(
+            f,
+            "Size:          {} ({} uncompressed, ratio {:.2})",
+            human_bytes(self.file_size as f64),
+            human_bytes(self.uncompressed_size as f64),
+            self.uncompressed_size as f64 / self.file_size.max(1) as f64
+        )?;
+        match self.unindexed {
+            0 => write!(f, "Index:         healthy"),
+            n => write!(f, "Index:         {n} blocks missing from the index"),
+        }
+    }
+}
+
+impl ArchiveStats {
+    // Stream every block of the archive in `reader` to count and size them,
+    // and check that `store` finds them all. Blocks are then classified by
+    // walking the chain of `store` from its heaviest tipset. Every visited CID
+    // is kept in memory.
+    async fn from_car(
+        store: &AnyCar<impl RandomAccessFileReader>,
+        reader: impl AsyncBufRead + Unpin,
+        file_size: u64,
+    ) -> anyhow::Result<Self> {
+        let mut stats = ArchiveStats {
+            file_size,
+            ..Default::default()
+        };
+
+        let mut stream = CarStream::new(reader).await?;
+        while let Some(block) = stream.try_next().await? {
+            stats.blocks += 1;
+            stats.uncompressed_size += (block.cid.encoded_len() + block.data.len()) as u64;
+            if !store.has(&block.cid)? {
+                stats.unindexed += 1;
+            }
+        }
+
+        let head = store.heaviest_tipset()?;
+        stats.head_epoch = head.epoch();
+        stats.lowest_epoch = head.epoch();
+        let mut seen = CidHashSet::default();
+        for tipset in head.chain(store) {
+            stats.lowest_epoch = tipset.epoch();
+            if store.has(tipset.parent_state())? {
+                stats.state_roots += 1;
+            }
+            for block in tipset.block_headers() {
+                if seen.insert(*block.cid()) {
+                    stats.headers += 1;
+                }
+            }
+            for block in tipset.block_headers() {
+                stats.messages +=
+                    count_reachable(store, [block.messages, block.message_receipts], &mut seen)?;
+            }
+            stats.state += count_reachable(store, [*tipset.parent_state()], &mut seen)?;
+        }
+        Ok(stats)
+    }
+}
+
+// Count the blocks of `store` that are reachable from `roots` and haven't been
+// `seen` yet.
+fn count_reachable(
+    store: &impl Blockstore,
+    roots: impl IntoIterator<Item = Cid>,
+    seen: &mut CidHashSet,
+) -> anyhow::Result<u64> {
+    let mut stack = roots.into_iter().collect_vec();
+    let mut count = 0;
+    while let Some(cid) = stack.pop() {
+        if !seen.insert(cid) {
+            continue;
+        }
+        if let Some(data) = store.get(&cid)? {
+            count += 1;
+            if cid.codec() == fvm_ipld_encoding::DAG_CBOR {
+                stack.extend(extract_cids(&data)?);
+            }
+        }
+    }
+    Ok(count)
+}
+
 // Print a mapping of epochs to block headers in yaml format. This mapping can
 // be used by Forest to quickly identify tipsets.
 fn print_checkpoints(snapshot_files: Vec<PathBuf>) -> anyhow::Result<()> {
@@ -445,7 +614,8 @@ async fn do_export(
     pb.enable_steady_tick(std::time::Duration::from_secs_f32(0.1));
     let writer = pb.wrap_async_write(writer);
 
-    crate::chain::export::<Sha256>(store.clone(), &ts, depth, writer, seen, true).await?;
+    crate::chain::export::<Sha256>(store.clone(), &ts, depth, writer, seen, None, true, false)
+        .await?;
 
     Ok(())
 }
@@ -502,6 +672,142 @@ async fn merge_snapshots(
     Ok(())
 }
 
+async fn export_actor(
+    snapshot_files: Vec<PathBuf>,
+    actor: Address,
+    from: ChainEpoch,
+    to: Option<ChainEpoch>,
+    output_path: PathBuf,
+    force: bool,
+) -> anyhow::Result<()> {
+    use crate::db::car::forest;
+
+    let store = Arc::new(ManyCar::try_from(snapshot_files)?);
+    let heaviest_tipset = Arc::new(store.heaviest_tipset()?);
+    let to = to.unwrap_or(heaviest_tipset.epoch());
+    if from > to {
+        bail!("Lowest epoch {from} is above highest epoch {to}");
+    }
+    let head = ChainIndex::new(Arc::clone(&store))
+        .tipset_by_height(to, heaviest_tipset, ResolveNullTipset::TakeOlder)
+        .context("unable to get a tipset at given height")?;
+    let roots = head.key().cids.clone().into_iter().collect();
+
+    if !force && output_path.exists() {
+        let have_permission = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!(
+                "{} will be overwritten. Continue?",
+                output_path.to_string_lossy()
+            ))
+            .default(false)
+            .interact()
+            // e.g not a tty (or some other error), so haven't got permission.
+            .unwrap_or(false);
+        if !have_permission {
+            return Ok(());
+        }
+    }
+
+    let tipsets = (*head)
+        .clone()
+        .chain(&store)
+        .take_while(|tipset| tipset.epoch() >= from);
+    let cids = actor_cids(&store, tipsets, &actor)?;
+    info!("exporting {} blocks of actor {actor}", cids.len());
+
+    let blocks = futures::stream::iter(cids).map(|cid| {
+        let data = store
+            .get(&cid)?
+            .with_context(|| format!("missing key: {cid}"))?;
+        anyhow::Ok(CarBlock { cid, data })
+    });
+
+    let mut writer = BufWriter::new(tokio::fs::File::create(&output_path).await.context(
+        format!(
+            "unable to create a snapshot - is the output path '{}' correct?",
+            output_path.to_str().unwrap_or_default()
+        ),
+    )?);
+    let frames = forest::Encoder::compress_stream_default(blocks);
+    forest::Encoder::write(&mut writer, roots, frames).await?;
+    writer.flush().await.context("failed to flush")?;
+
+    Ok(())
+}
+
+// Collect the CIDs of the block headers of `tipsets`, of the state-tree nodes
+// visited when looking `actor` up in their parent state, and of the state of
+// `actor`. Code CIDs aren't included. Tipsets where the actor doesn't exist
+// only contribute their headers and the nodes proving the actor is missing.
+fn actor_cids(
+    store: &impl Blockstore,
+    tipsets: impl Iterator<Item = Tipset>,
+    actor: &Address,
+) -> anyhow::Result<Vec<Cid>> {
+    let mut seen = CidHashSet::default();
+    let mut cids = vec![];
+    for tipset in tipsets {
+        for block in tipset.block_headers() {
+            if seen.insert(*block.cid()) {
+                cids.push(*block.cid());
+            }
+        }
+
+        let recorder = Arc::new(RecordingBlockstore::new(store));
+        let state = StateTree::new_from_root(Arc::clone(&recorder), tipset.parent_state())?
+            .get_actor(actor)?;
+        let mut stack = recorder.take();
+        stack.extend(state.map(|state| state.state));
+        stack.reverse();
+        while let Some(cid) = stack.pop() {
+            if !seen.insert(cid) {
+                continue;
+            }
+            if let Some(data) = store.get(&cid)? {
+                cids.push(cid);
+                if cid.codec() == fvm_ipld_encoding::DAG_CBOR {
+                    stack.extend(extract_cids(&data)?);
+                }
+            }
+        }
+    }
+    Ok(cids)
+}
+
+// Blockstore remembering the CIDs of the blocks read from it.
+struct RecordingBlockstore<DB> {
+    inner: DB,
+    read: parking_lot::Mutex<Vec<Cid>>,
+}
+
+impl<DB> RecordingBlockstore<DB> {
+    fn new(inner: DB) -> Self {
+        Self {
+            inner,
+            read: Default::default(),
+        }
+    }
+
+    // Returns the read CIDs, in the order they were read.
+    fn take(&self) -> Vec<Cid> {
+        std::mem::take(&mut self.read.lock())
+    }
+}
+
+impl<DB: Blockstore> Blockstore for RecordingBlockstore<DB> {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        let block = self.inner.get(k)?;
+        if block.is_some() {
+            self.read.lock().push(*k);
+        }
+        Ok(block)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.inner.put_keyed(k, block)
+    }
+}
+
 /// Compute the tree of actor states for a given epoch and compare it to the
 /// expected result (as encoded in the blockchain). Differences are printed
 /// using the diff format (red for the blockchain state, green for the computed
@@ -585,7 +891,6 @@ async fn show_tipset_diff(
 mod tests {
     use super::*;
     use crate::db::car::AnyCar;
-    use crate::utils::db::car_stream::CarStream;
     use tempfile::TempDir;
     use tokio::io::BufReader;
 
@@ -644,4 +949,53 @@ mod tests {
         assert_eq!(info.network, "mainnet");
         assert_eq!(info.epoch, 0);
     }
+
+    #[tokio::test]
+    async fn archive_stats_calibnet() {
+        let store = AnyCar::try_from(calibnet::DEFAULT_GENESIS).unwrap();
+        let file_size = calibnet::DEFAULT_GENESIS.len() as u64;
+        let stats = ArchiveStats::from_car(&store, calibnet::DEFAULT_GENESIS, file_size)
+            .await
+            .unwrap();
+        assert_eq!(stats.head_epoch, 0);
+        assert_eq!(stats.lowest_epoch, 0);
+        assert_eq!(stats.headers, 1);
+        assert_eq!(stats.state_roots, 1);
+        assert_eq!(stats.unindexed, 0);
+        assert!(stats.state > 0);
+        assert!(stats.headers + stats.messages + stats.state <= stats.blocks);
+        assert!(stats.uncompressed_size >= file_size);
+    }
+
+    #[tokio::test]
+    async fn actor_cids_calibnet() {
+        let store = AnyCar::try_from(calibnet::DEFAULT_GENESIS).unwrap();
+        let genesis = store.heaviest_tipset().unwrap();
+        let cids = actor_cids(
+            &store,
+            std::iter::once(genesis.clone()),
+            &Address::INIT_ACTOR,
+        )
+        .unwrap();
+        assert_eq!(cids[0], *genesis.min_ticket_block().cid());
+        assert_eq!(cids.iter().unique().count(), cids.len());
+
+        // The exported blocks are enough to look the actor up.
+        let exported = crate::db::MemoryDB::default();
+        for cid in &cids {
+            exported
+                .put_keyed(cid, &store.get(cid).unwrap().unwrap())
+                .unwrap();
+        }
+        let exported = Arc::new(exported);
+        let actor = StateTree::new_from_root(exported.clone(), genesis.parent_state())
+            .unwrap()
+            .get_actor(&Address::INIT_ACTOR)
+            .unwrap()
+            .unwrap();
+        assert!(exported.has(&actor.state).unwrap());
+        // Other actors are left out.
+        let stream = CarStream::new(calibnet::DEFAULT_GENESIS).await.unwrap();
+        assert!(cids.len() < stream.count().await);
+    }
 }
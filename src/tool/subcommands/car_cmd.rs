@@ -3,6 +3,8 @@
 
 use std::path::{Path, PathBuf};
 
+use anyhow::Context as _;
+use cid::Cid;
 use clap::Subcommand;
 use futures::{StreamExt, TryStreamExt};
 use fvm_ipld_blockstore::Blockstore;
@@ -10,18 +12,21 @@ use indicatif::{ProgressBar, ProgressStyle};
 use itertools::Itertools;
 use tokio::{
     fs::File,
-    io::{AsyncWriteExt, BufReader},
+    io::{AsyncBufRead, AsyncWriteExt, BufReader},
 };
 
+use crate::blocks::{CachingBlockHeader, Tipset};
+use crate::db::car::forest::{DEFAULT_FOREST_CAR_COMPRESSION_LEVEL, DEFAULT_FOREST_CAR_FRAME_SIZE};
 use crate::db::car::ForestCar;
 use crate::utils::db::{
-    car_stream::CarStream,
+    car_stream::{CarStream, CarWriter},
     car_util::{dedup_block_stream, merge_car_streams},
 };
 
 #[derive(Debug, Subcommand)]
 pub enum CarCommands {
-    /// Concatenate two or more CAR files into a single archive
+    /// Concatenate two or more CAR files into a single archive, without duplicate blocks, e.g. to
+    /// stitch diff snapshots together
     Concat {
         /// A list of CAR file paths. A CAR file can be a plain CAR, a zstd compressed CAR
         /// or a `.forest.car.zst` file
@@ -29,6 +34,31 @@ pub enum CarCommands {
         /// The output `.forest.car.zst` file path
         #[arg(short, long)]
         output: PathBuf,
+        /// Root the archive at the tipset of these block header CIDs, e.g. the head of the last
+        /// diff snapshot. Defaults to the roots of all the CAR files
+        #[arg(long, num_args = 1..)]
+        root: Vec<Cid>,
+    },
+    /// Generate the indexed `.forest.car.zst` format from a CAR file, e.g. exported by Lotus
+    Index {
+        /// Input CAR file, in `.car`, `.car.zst`, or `.forest.car.zst` format
+        car_file: PathBuf,
+        /// Output `.forest.car.zst` file path. Defaults to the input file name with the
+        /// `.forest.car.zst` extension, in the current directory
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Number of threads compressing blocks. `0` uses one thread per CPU
+        #[arg(long, default_value_t = 0)]
+        threads: usize,
+    },
+    /// Write the blocks of a CAR file, e.g. a `.forest.car.zst` file, to a plain uncompressed CAR
+    /// file
+    Flatten {
+        /// Input CAR file, in `.car`, `.car.zst`, or `.forest.car.zst` format
+        car_file: PathBuf,
+        /// Output `.car` file path
+        #[arg(short, long)]
+        output: PathBuf,
     },
     /// Check the validity of a CAR archive. For Filecoin-specific checks, see
     /// `forest-tool snapshot validate`.
@@ -47,28 +77,21 @@ pub enum CarCommands {
 impl CarCommands {
     pub async fn run(self) -> anyhow::Result<()> {
         match self {
-            Self::Concat { car_files, output } => {
-                let car_streams: Vec<_> = futures::stream::iter(car_files)
-                    .then(tokio::fs::File::open)
-                    .map_ok(tokio::io::BufReader::new)
-                    .and_then(CarStream::new)
-                    .try_collect()
-                    .await?;
-
-                let all_roots = car_streams
-                    .iter()
-                    .flat_map(|it| it.header.roots.iter())
-                    .unique()
-                    .cloned()
-                    .collect::<Vec<_>>();
-
-                let frames = crate::db::car::forest::Encoder::compress_stream_default(
-                    dedup_block_stream(merge_car_streams(car_streams)).map_err(anyhow::Error::from),
-                );
-                let mut writer = tokio::io::BufWriter::new(tokio::fs::File::create(&output).await?);
-                crate::db::car::forest::Encoder::write(&mut writer, all_roots, frames).await?;
-                writer.flush().await?;
+            Self::Concat {
+                car_files,
+                output,
+                root,
+            } => concat(&car_files, &output, &root).await?,
+            Self::Index {
+                car_file,
+                output,
+                threads,
+            } => {
+                let output = output.unwrap_or_else(|| forest_car_file_name(&car_file));
+                index(&car_file, &output, threads).await?;
+                println!("Indexed CAR file written to {}", output.display());
             }
+            Self::Flatten { car_file, output } => flatten(&car_file, &output).await?,
             Self::Validate {
                 car_file,
                 ignore_block_validity,
@@ -79,6 +102,117 @@ impl CarCommands {
     }
 }
 
+/// Writes the blocks of the `car_files` to `output`, skipping the blocks already written. The
+/// archive is rooted at the `root` tipset, if given, whose headers must be in the CAR files.
+async fn concat(car_files: &[PathBuf], output: &Path, root: &[Cid]) -> anyhow::Result<()> {
+    let car_streams: Vec<_> = futures::stream::iter(car_files)
+        .then(tokio::fs::File::open)
+        .map_ok(tokio::io::BufReader::new)
+        .and_then(CarStream::new)
+        .try_collect()
+        .await?;
+
+    let roots = match root {
+        [] => car_streams
+            .iter()
+            .flat_map(|it| it.header.roots.iter())
+            .unique()
+            .cloned()
+            .collect::<Vec<_>>(),
+        root => root.to_vec(),
+    };
+
+    let mut root_headers = vec![];
+    let blocks = dedup_block_stream(merge_car_streams(car_streams)).inspect_ok(|block| {
+        if root.contains(&block.cid) {
+            root_headers.push(block.data.clone());
+        }
+    });
+    let frames = crate::db::car::forest::Encoder::compress_stream_default(
+        blocks.map_err(anyhow::Error::from),
+    );
+    let mut writer = tokio::io::BufWriter::new(tokio::fs::File::create(output).await?);
+    crate::db::car::forest::Encoder::write(&mut writer, roots, frames).await?;
+    writer.flush().await?;
+
+    if !root.is_empty() {
+        if let Err(e) = ensure_tipset(root, &root_headers) {
+            tokio::fs::remove_file(output).await?;
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the `headers`, found for the `root` CIDs, make a tipset.
+fn ensure_tipset(root: &[Cid], headers: &[Vec<u8>]) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        headers.len() == root.len(),
+        "the root blocks aren't all in the CAR files"
+    );
+    let headers = headers
+        .iter()
+        .map(|data| fvm_ipld_encoding::from_slice::<CachingBlockHeader>(data))
+        .collect::<Result<Vec<_>, _>>()
+        .context("the root blocks aren't block headers")?;
+    Tipset::new(headers).context("the root blocks aren't a tipset")?;
+    Ok(())
+}
+
+/// Returns the name of the `.forest.car.zst` file of `car_file`, e.g. `snapshot.forest.car.zst` for
+/// `/path/to/snapshot.car.zst`.
+fn forest_car_file_name(car_file: &Path) -> PathBuf {
+    let mut name = PathBuf::from(car_file.file_name().unwrap_or_default());
+    while let Some(ext) = name.extension() {
+        if !(ext == "zst" || ext == "car" || ext == "forest") {
+            break;
+        }
+        name.set_extension("");
+    }
+    name.with_extension("forest.car.zst")
+}
+
+/// Opens a CAR file, showing the progress of its reading.
+async fn open_car_stream(car_file: &Path) -> anyhow::Result<CarStream<impl AsyncBufRead + Unpin>> {
+    let file = File::open(car_file).await?;
+    let pb = ProgressBar::new(file.metadata().await?.len()).with_style(
+        ProgressStyle::with_template("{bar} {percent}%, eta: {eta}").expect("infallible"),
+    );
+    Ok(CarStream::new(BufReader::new(pb.wrap_async_read(file))).await?)
+}
+
+/// Writes the blocks of `car_file` to `output` in the `.forest.car.zst` format. Frames are
+/// compressed on `threads` threads, and only a bounded number of them is kept in memory.
+async fn index(car_file: &Path, output: &Path, threads: usize) -> anyhow::Result<()> {
+    let threads = match threads {
+        0 => std::thread::available_parallelism().map_or(1, usize::from),
+        n => n,
+    };
+    let mut car_stream = open_car_stream(car_file).await?;
+    let roots = std::mem::take(&mut car_stream.header.roots);
+    let frames = crate::db::car::forest::Encoder::compress_stream_parallel(
+        DEFAULT_FOREST_CAR_FRAME_SIZE,
+        DEFAULT_FOREST_CAR_COMPRESSION_LEVEL,
+        threads,
+        car_stream.map_err(anyhow::Error::from),
+    );
+    let mut writer = tokio::io::BufWriter::new(File::create(output).await?);
+    crate::db::car::forest::Encoder::write(&mut writer, roots, frames).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Writes the blocks of `car_file` to `output`, as a plain CAR file.
+async fn flatten(car_file: &Path, output: &Path) -> anyhow::Result<()> {
+    let mut car_stream = open_car_stream(car_file).await?;
+    let roots = std::mem::take(&mut car_stream.header.roots);
+    let writer = tokio::io::BufWriter::new(File::create(output).await?);
+    car_stream
+        .forward(CarWriter::new_carv1(roots, writer)?)
+        .await?;
+    Ok(())
+}
+
 /// At present, three properties are checked:
 /// - The CAR file is syntactically valid and all blocks can be streamed.
 /// - Each block CID is checked against the hash of the block.
@@ -100,13 +234,7 @@ async fn validate(
         None
     };
 
-    let file = File::open(car_file).await?;
-    let pb = ProgressBar::new(file.metadata().await?.len()).with_style(
-        ProgressStyle::with_template("{bar} {percent}%, eta: {eta}").expect("infallible"),
-    );
-    let file = BufReader::new(pb.wrap_async_read(file));
-
-    let mut stream = CarStream::new(file).await?;
+    let mut stream = open_car_stream(car_file).await?;
     while let Some(block) = stream.try_next().await? {
         if !ignore_block_validity && !block.valid() {
             anyhow::ensure!(block.valid(), "CID/Block mismatch for block: {}", block.cid);
@@ -120,14 +248,15 @@ async fn validate(
 
 #[cfg(test)]
 mod tests {
-    use super::validate;
+    use super::{concat, flatten, forest_car_file_name, index, validate};
     use crate::db::car::forest;
     use crate::networks::{calibnet, mainnet};
-    use crate::utils::db::car_stream::CarBlock;
+    use crate::utils::db::car_stream::{CarBlock, CarStream};
     use cid::multihash::{Code, MultihashDigest};
     use cid::Cid;
     use futures::{stream::iter, StreamExt, TryStreamExt};
     use std::io::Write;
+    use std::path::Path;
     use tempfile::{Builder, TempPath};
     use tokio::io::AsyncWriteExt;
 
@@ -231,6 +360,98 @@ mod tests {
         assert!(validate(&temp_path, true, false).await.is_ok());
     }
 
+    #[test]
+    fn forest_car_file_names() {
+        for (car_file, forest_car_file) in [
+            ("/path/to/snapshot.car", "snapshot.forest.car.zst"),
+            ("snapshot.car.zst", "snapshot.forest.car.zst"),
+            ("snapshot.forest.car.zst", "snapshot.forest.car.zst"),
+            ("snapshot.v1.car", "snapshot.v1.forest.car.zst"),
+        ] {
+            assert_eq!(
+                forest_car_file_name(Path::new(car_file)),
+                Path::new(forest_car_file)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn flatten_and_index() {
+        let blocks = vec![valid_block("cthulhu"), valid_block("azathoth")];
+        let forest_car = create_raw_car_file(blocks.clone(), vec![]).await;
+        let dir = tempfile::tempdir().unwrap();
+
+        let plain_car = dir.path().join("plain.car");
+        flatten(&forest_car, &plain_car).await.unwrap();
+        let file = tokio::io::BufReader::new(tokio::fs::File::open(&plain_car).await.unwrap());
+        let flattened: Vec<_> = CarStream::new(file)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(flattened, blocks);
+
+        let indexed_car = dir.path().join("indexed.forest.car.zst");
+        index(&plain_car, &indexed_car, 2).await.unwrap();
+        validate(&indexed_car, false, false).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn concat_without_duplicates() {
+        let first =
+            create_raw_car_file(vec![valid_block("cthulhu"), valid_block("dagon")], vec![]).await;
+        let second =
+            create_raw_car_file(vec![valid_block("dagon"), valid_block("azathoth")], vec![]).await;
+        let output = Builder::new().tempfile().unwrap().into_temp_path();
+
+        concat(&[first.to_path_buf(), second.to_path_buf()], &output, &[])
+            .await
+            .unwrap();
+        let file = tokio::io::BufReader::new(tokio::fs::File::open(&output).await.unwrap());
+        let blocks: Vec<_> = CarStream::new(file)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(
+            blocks,
+            [
+                valid_block("cthulhu"),
+                valid_block("dagon"),
+                valid_block("azathoth")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn concat_rerooted() {
+        let mut genesis = Builder::new().tempfile().unwrap();
+        genesis.write_all(calibnet::DEFAULT_GENESIS).unwrap();
+        let genesis = genesis.into_temp_path();
+        let file = tokio::io::BufReader::new(tokio::fs::File::open(&genesis).await.unwrap());
+        let genesis_cid = CarStream::new(file).await.unwrap().header.roots[0];
+        let other = create_raw_car_file(vec![valid_block("cthulhu")], vec![]).await;
+        let output = Builder::new().tempfile().unwrap().into_temp_path();
+        let car_files = [genesis.to_path_buf(), other.to_path_buf()];
+
+        concat(&car_files, &output, &[genesis_cid]).await.unwrap();
+        let file = tokio::io::BufReader::new(tokio::fs::File::open(&output).await.unwrap());
+        assert_eq!(
+            CarStream::new(file).await.unwrap().header.roots,
+            [genesis_cid]
+        );
+
+        // Blocks that aren't block headers can't be roots.
+        concat(&car_files, &output, &[valid_block("cthulhu").cid])
+            .await
+            .unwrap_err();
+        concat(&car_files, &output, &[valid_block("dagon").cid])
+            .await
+            .unwrap_err();
+    }
+
     // If a CarBlock exist that isn't referenced in the index, this is an error.
     #[tokio::test]
     async fn validate_invalid_index() {
@@ -3,11 +3,16 @@
 
 use crate::cli::subcommands::prompt_confirm;
 use crate::cli_shared::{chain_path, read_config};
-use crate::db::db_engine::db_root;
+use crate::db::db_engine::{copy_db, db_root, open_db, BackendKind};
 use crate::networks::NetworkChain;
+use anyhow::Context as _;
 use clap::Subcommand;
+use strum::IntoEnumIterator as _;
 use tracing::error;
 
+/// Directory the database is converted into by `migrate-backend`, in the chain data directory.
+const MIGRATE_BACKEND_DIR: &str = "migrate-backend.tmp";
+
 #[derive(Debug, Subcommand)]
 pub enum DBCommands {
     /// Show DB stats
@@ -31,6 +36,19 @@ pub enum DBCommands {
         #[arg(long)]
         chain: Option<NetworkChain>,
     },
+    /// Convert the database to another storage backend. The node must be stopped. The previous
+    /// database is kept aside, and can be removed once the node runs fine with the new one.
+    MigrateBackend {
+        /// Backend to convert the database to
+        #[arg(long)]
+        to: BackendKind,
+        /// Optional TOML file containing forest daemon configuration
+        #[arg(short, long)]
+        config: Option<String>,
+        /// Optional chain, will override the chain section of configuration file if used
+        #[arg(long)]
+        chain: Option<NetworkChain>,
+    },
 }
 
 impl DBCommands {
@@ -78,6 +96,54 @@ impl DBCommands {
                     }
                 }
             }
+            Self::MigrateBackend { to, config, chain } => {
+                let (_, config) = read_config(config, chain)?;
+
+                let chain_data_path = chain_path(&config);
+                let dir = db_root(&chain_data_path)?;
+                let from = BackendKind::iter()
+                    .find(|backend| backend.exists_in(&dir))
+                    .with_context(|| format!("no database found in {}", dir.display()))?;
+                anyhow::ensure!(from != *to, "the database already uses the {to} backend");
+
+                let temp_dir = chain_data_path.join(MIGRATE_BACKEND_DIR);
+                if temp_dir.exists() {
+                    std::fs::remove_dir_all(&temp_dir)?;
+                }
+                println!(
+                    "Converting the {from} database in {} to {to}",
+                    dir.display()
+                );
+                let copied = {
+                    let source = open_db(dir.clone(), config.db_config_for(from))?;
+                    let target = open_db(temp_dir.clone(), config.db_config_for(*to))?;
+                    copy_db(&source, &target)?
+                };
+
+                // CAR files are the same for all backends.
+                let car_db_dir = dir.join("car_db");
+                if car_db_dir.exists() {
+                    std::fs::rename(&car_db_dir, temp_dir.join("car_db"))?;
+                }
+                let backup_dir = chain_data_path.join(format!(
+                    "{}.{from}.bak",
+                    dir.file_name()
+                        .context("invalid database directory")?
+                        .to_string_lossy()
+                ));
+                std::fs::rename(&dir, &backup_dir)?;
+                std::fs::rename(&temp_dir, &dir)?;
+
+                println!("Copied {copied} blocks");
+                println!(
+                    "Set `backend = \"{to}\"` in the `[db]` section of the configuration to use the new database"
+                );
+                println!(
+                    "The {from} database was moved to {}, and can be removed",
+                    backup_dir.display()
+                );
+                Ok(())
+            }
         }
     }
 }
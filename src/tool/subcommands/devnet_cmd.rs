@@ -0,0 +1,131 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::chain_sync::SyncConfig;
+use crate::cli_shared::cli::{Client, Config};
+use crate::daemon::bundle::load_actor_bundles;
+use crate::db::MemoryDB;
+use crate::genesis::builder::{write_car, GenesisBuilder};
+use crate::key_management::generate_key;
+use crate::lotus_json::LotusJson;
+use crate::networks::{ChainConfig, ConsensusKind, NetworkChain};
+use crate::shim::{
+    crypto::SignatureType, econ::TokenAmount, machine::BuiltinActorManifest, sector::StoragePower,
+};
+use anyhow::Context as _;
+use clap::Subcommand;
+use itertools::Itertools as _;
+
+#[derive(Debug, Subcommand)]
+pub enum DevnetCommands {
+    /// Generate the genesis, the owner key and the configuration of a local
+    /// single-node network
+    Init {
+        /// Directory to write the files of the network to
+        #[arg(short, long, default_value = "devnet")]
+        output: PathBuf,
+        /// Name of the network
+        #[arg(long, default_value = "localnet")]
+        network_name: String,
+        /// Number of genesis miners, with power but no pre-sealed sectors
+        #[arg(long, default_value_t = 1)]
+        miners: u64,
+        /// Power of each genesis miner, in bytes
+        #[arg(long, default_value_t = 1 << 40)]
+        miner_power: u64,
+        /// Initial balance of the owner account, in FIL
+        #[arg(long, default_value_t = 1_000_000)]
+        balance: u64,
+    },
+}
+
+impl DevnetCommands {
+    pub async fn run(self) -> anyhow::Result<()> {
+        match self {
+            Self::Init {
+                output,
+                network_name,
+                miners,
+                miner_power,
+                balance,
+            } => {
+                std::fs::create_dir_all(&output)?;
+                let output = output.canonicalize()?;
+                let chain = NetworkChain::Devnet(network_name.clone());
+
+                // The genesis actors are those of the network version at epoch 0.
+                let db = Arc::new(MemoryDB::default());
+                load_actor_bundles(&db, &chain).await?;
+                let manifest_cid = ChainConfig::from_chain(&chain)
+                    .height_infos
+                    .iter()
+                    .filter(|info| info.epoch <= 0)
+                    .filter_map(|info| info.bundle)
+                    .last()
+                    .context("no actor bundle at genesis")?;
+                let manifest = BuiltinActorManifest::load_manifest(&db, &manifest_cid)?;
+
+                let owner = generate_key(SignatureType::Bls)?;
+                let builder = GenesisBuilder::new(&network_name)
+                    .with_timestamp(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+                    .with_account(owner.address, TokenAmount::from_whole(balance));
+                let (genesis, miners) = (0..miners)
+                    .fold(builder, |builder, _| {
+                        builder.with_miner(owner.address, StoragePower::from(miner_power))
+                    })
+                    .build(&db, &manifest)?;
+
+                let genesis_path = output.join("genesis.car");
+                write_car(&db, &genesis, tokio::fs::File::create(&genesis_path).await?).await?;
+
+                let key_path = output.join("owner.key");
+                let key = serde_json::to_string(&LotusJson(owner.key_info))?;
+                std::fs::write(&key_path, hex::encode(key))?;
+
+                let config = Config {
+                    chain,
+                    client: Client {
+                        data_dir: output.join("data"),
+                        genesis_file: Some(genesis_path.display().to_string()),
+                        ..Default::default()
+                    },
+                    sync: SyncConfig {
+                        consensus: ConsensusKind::Instant,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                };
+                let config_path = output.join("config.toml");
+                std::fs::write(&config_path, toml::to_string(&config)?)?;
+
+                println!(
+                    "Genesis {} written to {}",
+                    genesis.cid(),
+                    genesis_path.display()
+                );
+                println!(
+                    "Owner {} key written to {}",
+                    owner.address,
+                    key_path.display()
+                );
+                println!("Configuration written to {}", config_path.display());
+                if let Some(miner) = miners.first() {
+                    println!(
+                        "Genesis miners: {}",
+                        miners.iter().map(ToString::to_string).join(", ")
+                    );
+                    println!(
+                        "Start mining with `forest --config {} --mine {miner}`, then import the owner key with `forest-wallet import {}`",
+                        config_path.display(),
+                        key_path.display()
+                    );
+                }
+                Ok(())
+            }
+        }
+    }
+}
@@ -6,6 +6,7 @@ use crate::utils::proofs_api::paramfetch::{get_params_default, SectorSizeOpt};
 
 use crate::cli::subcommands::cli_error_and_die;
 use crate::cli_shared::read_config;
+use url::Url;
 
 #[allow(missing_docs)]
 #[derive(Debug, clap::Args)]
@@ -21,6 +22,11 @@ pub struct FetchCommands {
     dry_run: bool,
     /// Size in bytes
     params_size: Option<String>,
+    /// Gateways to download the parameter files from, tried in order. Interrupted downloads
+    /// are resumed. Defaults to the `IPFS_GATEWAY` environment variable, a comma-separated
+    /// list, or the Filecoin proofs gateway.
+    #[arg(long)]
+    mirror: Vec<Url>,
     /// Optional TOML file containing forest daemon configuration
     #[arg(short, long)]
     pub config: Option<String>,
@@ -44,7 +50,7 @@ impl FetchCommands {
             );
         };
 
-        get_params_default(&config.client.data_dir, sizes, self.dry_run).await
+        get_params_default(&config.client.data_dir, sizes, self.dry_run, &self.mirror).await
     }
 }
 
@@ -0,0 +1,75 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::blocks::{Tipset, TipsetKey};
+use crate::chain::message_index;
+use crate::cli_shared::{chain_path, read_config};
+use crate::db::db_engine::{db_root, open_db};
+use crate::db::setting_keys::HEAD_KEY;
+use crate::db::SettingsStoreExt;
+use crate::networks::NetworkChain;
+use crate::shim::clock::ChainEpoch;
+use clap::Subcommand;
+
+#[derive(Debug, Subcommand)]
+pub enum IndexCommands {
+    /// Index the messages executed on chain, walking back from the head, so
+    /// that they can be looked up without searching the chain. Only the
+    /// messages of the epochs retained by the index are indexed. The node must
+    /// be stopped.
+    Backfill {
+        /// Epoch to stop indexing at, if more recent than the retained epochs
+        #[arg(long, default_value_t = 0)]
+        to: ChainEpoch,
+        /// Optional TOML file containing forest daemon configuration
+        #[arg(short, long)]
+        config: Option<String>,
+        /// Optional chain, will override the chain section of configuration file if used
+        #[arg(long)]
+        chain: Option<NetworkChain>,
+    },
+}
+
+impl IndexCommands {
+    pub async fn run(&self) -> anyhow::Result<()> {
+        match self {
+            Self::Backfill { to, config, chain } => {
+                let (_, config) = read_config(config, chain)?;
+
+                let db = open_db(db_root(&chain_path(&config))?, config.db_config())?;
+                let head = Tipset::load_required(&db, &db.require_obj::<TipsetKey>(HEAD_KEY)?)?;
+                // Older messages would be pruned as soon as the head advances.
+                let to = (*to).max(head.epoch() - message_index::MESSAGE_INDEX_RETENTION);
+                println!("Indexing messages from epoch {} to {to}", head.epoch());
+
+                let pb = indicatif::ProgressBar::new((head.epoch() - to).max(0) as u64).with_style(
+                    indicatif::ProgressStyle::with_template(
+                        "{bar} {pos}/{len} epochs, {msg} messages indexed in {elapsed}",
+                    )
+                    .expect("indicatif template must be valid"),
+                );
+                let head_epoch = head.epoch();
+                let mut indexed = 0;
+                for tipset in head.chain(&db).take_while(|tipset| tipset.epoch() > to) {
+                    match message_index::index_tipset(&db, &db, &tipset) {
+                        Ok(count) => indexed += count,
+                        // Snapshots only hold the messages of their most
+                        // recent epochs.
+                        Err(e) => {
+                            println!(
+                                "Stopped at epoch {}, whose messages can't be indexed: {e}",
+                                tipset.epoch()
+                            );
+                            break;
+                        }
+                    }
+                    pb.set_position((head_epoch - tipset.epoch()) as u64);
+                    pb.set_message(indexed.to_string());
+                }
+                pb.finish();
+                println!("Indexed {indexed} messages");
+                Ok(())
+            }
+        }
+    }
+}
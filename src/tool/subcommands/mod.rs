@@ -6,8 +6,11 @@ pub mod archive_cmd;
 pub mod benchmark_cmd;
 pub mod car_cmd;
 pub mod db_cmd;
+pub mod devnet_cmd;
 pub mod fetch_params_cmd;
+pub mod index_cmd;
 pub mod snapshot_cmd;
+pub mod state_cmd;
 pub mod state_migration_cmd;
 
 use crate::cli_shared::cli::HELP_MESSAGE;
@@ -31,6 +34,10 @@ pub enum Subcommand {
     #[command(subcommand)]
     Benchmark(benchmark_cmd::BenchmarkCommands),
 
+    /// Inspect state trees
+    #[command(subcommand)]
+    State(state_cmd::StateCommands),
+
     /// State migration tools
     #[command(subcommand)]
     StateMigration(state_migration_cmd::StateMigrationCommands),
@@ -51,6 +58,14 @@ pub enum Subcommand {
     #[command(subcommand)]
     DB(db_cmd::DBCommands),
 
+    /// Local devnet tooling
+    #[command(subcommand)]
+    Devnet(devnet_cmd::DevnetCommands),
+
+    /// Message index management
+    #[command(subcommand)]
+    Index(index_cmd::IndexCommands),
+
     /// Utilities for manipulating CAR files
     #[command(subcommand)]
     Car(car_cmd::CarCommands),
@@ -1,6 +1,8 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+mod serve;
+
 use super::*;
 use crate::blocks::Tipset;
 use crate::chain::index::{ChainIndex, ResolveNullTipset};
@@ -16,6 +18,7 @@ use crate::shim::address::CurrentNetwork;
 use crate::shim::clock::ChainEpoch;
 use crate::shim::fvm_shared_latest::address::Network;
 use crate::shim::machine::MultiEngine;
+use crate::shim::state_tree::StateTree;
 use crate::state_manager::apply_block_messages;
 use crate::utils::db::car_stream::CarStream;
 use crate::utils::proofs_api::paramfetch::ensure_params_downloaded;
@@ -26,7 +29,8 @@ use dialoguer::{theme::ColorfulTheme, Confirm};
 use futures::TryStreamExt;
 use fvm_ipld_blockstore::Blockstore;
 use indicatif::{ProgressBar, ProgressStyle};
-use std::path::PathBuf;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
@@ -47,6 +51,9 @@ pub enum SnapshotCommands {
 
     /// Validates the snapshot.
     Validate {
+        /// Skip checking that every block of the CAR files matches its CID
+        #[arg(long)]
+        skip_car_integrity: bool,
         /// Number of recent epochs to scan for broken links
         #[arg(long, default_value_t = 2000)]
         check_links: u32,
@@ -57,11 +64,24 @@ pub enum SnapshotCommands {
         /// Number of recent epochs to scan for bad messages/transactions
         #[arg(long, default_value_t = 60)]
         check_stateroots: u32,
+        /// Print the outcome of each check as JSON
+        #[arg(long)]
+        json: bool,
         /// Path to a snapshot CAR, which may be zstd compressed
         #[arg(required = true)]
         snapshot_files: Vec<PathBuf>,
     },
 
+    /// Serves the snapshots of a directory over HTTP, with support for range
+    /// requests, checksums and a JSON listing at `/`.
+    Serve {
+        /// Directory containing the snapshots
+        directory: PathBuf,
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: std::net::SocketAddr,
+    },
+
     /// Make this snapshot suitable for use as a compressed car-backed blockstore.
     Compress {
         /// Input CAR file, in `.car`, `.car.zst`, or `.forest.car.zst` format.
@@ -126,20 +146,36 @@ impl SnapshotCommands {
                 Err(e) => cli_error_and_die(format!("Failed fetching the snapshot: {e}"), 1),
             },
             Self::Validate {
+                skip_car_integrity,
                 check_links,
                 check_network,
                 check_stateroots,
+                json,
                 snapshot_files,
             } => {
-                let store = ManyCar::try_from(snapshot_files)?;
-                validate_with_blockstore(
-                    store.heaviest_tipset()?,
-                    Arc::new(store),
+                let mut report = ValidationReport::default();
+                let result = validate_snapshot(
+                    &mut report,
+                    snapshot_files,
+                    skip_car_integrity,
                     check_links,
                     check_network,
                     check_stateroots,
                 )
-                .await
+                .await;
+                report.valid = result.is_ok();
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                } else if report.valid {
+                    println!("Snapshot is valid");
+                }
+                result
+            }
+            Self::Serve { directory, listen } => {
+                let listener = tokio::net::TcpListener::bind(listen)
+                    .await
+                    .with_context(|| format!("failed to listen on {listen}"))?;
+                serve::serve(directory, listener).await
             }
             Self::Compress {
                 source,
@@ -212,10 +248,72 @@ impl SnapshotCommands {
     }
 }
 
-// Check the validity of a snapshot by looking at IPLD links, the genesis block,
-// and message output. More checks may be added in the future.
+/// Outcome of `forest-tool snapshot validate`, printed with `--json`.
+#[derive(Debug, Default, Serialize)]
+struct ValidationReport {
+    valid: bool,
+    /// Checks that were run, in order. Validation stops at the first failed
+    /// check.
+    checks: Vec<CheckOutcome>,
+}
+
+#[derive(Debug, Serialize)]
+struct CheckOutcome {
+    check: &'static str,
+    passed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl ValidationReport {
+    /// Records the outcome of `check`, and passes it on.
+    fn record<T>(&mut self, check: &'static str, result: anyhow::Result<T>) -> anyhow::Result<T> {
+        self.checks.push(CheckOutcome {
+            check,
+            passed: result.is_ok(),
+            error: result.as_ref().err().map(|e| format!("{e:#}")),
+        });
+        result
+    }
+}
+
+async fn validate_snapshot(
+    report: &mut ValidationReport,
+    snapshot_files: Vec<PathBuf>,
+    skip_car_integrity: bool,
+    check_links: u32,
+    check_network: Option<NetworkChain>,
+    check_stateroots: u32,
+) -> anyhow::Result<()> {
+    if !skip_car_integrity {
+        for snapshot_file in &snapshot_files {
+            report.record("car_integrity", validate_car_integrity(snapshot_file).await)?;
+        }
+    }
+    let store = report.record(
+        "car_index",
+        ManyCar::try_from(snapshot_files).context("failed to open the snapshot"),
+    )?;
+    let root = report.record("head_tipset", store.heaviest_tipset())?;
+    validate_with_blockstore(
+        report,
+        root,
+        Arc::new(store),
+        check_links,
+        check_network,
+        check_stateroots,
+    )
+    .await
+}
+
+// Check the validity of a snapshot by looking at the chain of block headers,
+// IPLD links, the genesis block, and message output. More checks may be added
+// in the future.
 //
 // If the snapshot is valid, the output should look like this:
+//     Checking CAR integrity:        ✅ verified!
+//     Checking block headers:        ✅ verified!
+//     Loading head state tree:       ✅ verified!
 //     Checking IPLD integrity:       ✅ verified!
 //     Identifying genesis block:     ✅ found!
 //     Verifying network identity:    ✅ verified!
@@ -224,11 +322,15 @@ impl SnapshotCommands {
 //
 // If we receive a mainnet snapshot but expect a calibnet snapshot, the output
 // should look like this:
+//     Checking CAR integrity:        ✅ verified!
+//     Checking block headers:        ✅ verified!
+//     Loading head state tree:       ✅ verified!
 //     Checking IPLD integrity:       ✅ verified!
 //     Identifying genesis block:     ✅ found!
 //     Verifying network identity:    ❌ wrong!
 //   Error: Expected mainnet but found calibnet
 async fn validate_with_blockstore<BlockstoreT>(
+    report: &mut ValidationReport,
     root: Tipset,
     store: Arc<BlockstoreT>,
     check_links: u32,
@@ -238,30 +340,137 @@ async fn validate_with_blockstore<BlockstoreT>(
 where
     BlockstoreT: Blockstore + Send + Sync + 'static,
 {
+    report.record(
+        "block_headers",
+        validate_block_headers(root.clone(), &store),
+    )?;
+    report.record("head_state_root", validate_head_state_root(&root, &store))?;
+
     if check_links != 0 {
-        validate_ipld_links(root.clone(), &store, check_links).await?;
+        report.record(
+            "ipld_links",
+            validate_ipld_links(root.clone(), &store, check_links).await,
+        )?;
     }
 
     if let Some(expected_network) = &check_network {
-        let actual_network = query_network(&root, &store)?;
+        let actual_network = report.record("genesis_block", query_network(&root, &store))?;
         // Somewhat silly use of a spinner but this makes the checks line up nicely.
         let pb = validation_spinner("Verifying network identity:");
         if expected_network != &actual_network {
             pb.finish_with_message("❌ wrong!");
-            bail!("Expected {} but found {}", expected_network, actual_network);
+            return report.record(
+                "network",
+                Err(anyhow::anyhow!(
+                    "Expected {} but found {}",
+                    expected_network,
+                    actual_network
+                )),
+            );
         } else {
             pb.finish_with_message("✅ verified!");
+            report.record("network", Ok(()))?;
         }
     }
 
     if check_stateroots != 0 {
-        let network = check_network
-            .map(anyhow::Ok)
-            .unwrap_or_else(|| query_network(&root, &store))?;
-        validate_stateroots(root, &store, network, check_stateroots).await?;
+        let network = match check_network {
+            Some(network) => network,
+            None => report.record("genesis_block", query_network(&root, &store))?,
+        };
+        report.record(
+            "state_roots",
+            validate_stateroots(root, &store, network, check_stateroots).await,
+        )?;
     }
 
-    println!("Snapshot is valid");
+    Ok(())
+}
+
+// Every block of a CAR file is addressed by the hash of its data. Hashing all
+// of them catches truncated or corrupted downloads before they are imported.
+async fn validate_car_integrity(snapshot_file: &Path) -> anyhow::Result<()> {
+    let file = File::open(snapshot_file)
+        .await
+        .with_context(|| format!("failed to open {}", snapshot_file.display()))?;
+    let pb = ProgressBar::new(file.metadata().await?.len())
+        .with_style(
+            ProgressStyle::with_template("{spinner} {prefix:<30} {bar} {percent}%, eta: {eta}")
+                .expect("indicatif template must be valid"),
+        )
+        .with_prefix("Checking CAR integrity:")
+        .with_finish(indicatif::ProgressFinish::AbandonWithMessage(
+            "❌ Invalid block!".into(),
+        ));
+    let reader = tokio::io::BufReader::new(pb.wrap_async_read(file));
+
+    let mut blocks = CarStream::new(reader).await?;
+    while let Some(block) = blocks.try_next().await? {
+        if !block.valid() {
+            bail!(
+                "Block {} of {} doesn't match its hash",
+                block.cid,
+                snapshot_file.display()
+            );
+        }
+    }
+
+    pb.set_style(
+        ProgressStyle::with_template("{spinner} {prefix:<30} {msg}")
+            .expect("indicatif template must be valid"),
+    );
+    pb.finish_with_message("✅ verified!");
+    Ok(())
+}
+
+// Snapshots contain every block header from their head down to genesis. The
+// headers must be chained together by their parent keys, with strictly
+// decreasing epochs.
+fn validate_block_headers(ts: Tipset, db: &impl Blockstore) -> anyhow::Result<()> {
+    let pb = validation_spinner("Checking block headers:").with_finish(
+        indicatif::ProgressFinish::AbandonWithMessage("❌ Broken header chain!".into()),
+    );
+
+    let mut tipset = ts;
+    while tipset.epoch() > 0 {
+        pb.set_message(format!("{} remaining epochs", tipset.epoch()));
+        let parent = Tipset::load(db, tipset.parents())?.with_context(|| {
+            format!(
+                "Missing parent tipset {} of the tipset at epoch {}",
+                tipset.parents(),
+                tipset.epoch()
+            )
+        })?;
+        if parent.epoch() >= tipset.epoch() {
+            bail!(
+                "Tipset at epoch {} has a parent at epoch {}",
+                tipset.epoch(),
+                parent.epoch()
+            );
+        }
+        tipset = parent;
+    }
+
+    pb.finish_with_message("✅ verified!");
+    Ok(())
+}
+
+// The state tree of the head tipset is needed to import the snapshot. Loading
+// it catches snapshots that were cut short of their state.
+fn validate_head_state_root<DB>(ts: &Tipset, db: &Arc<DB>) -> anyhow::Result<()>
+where
+    DB: Blockstore,
+{
+    let pb = validation_spinner("Loading head state tree:").with_finish(
+        indicatif::ProgressFinish::AbandonWithMessage("❌ Unreachable state root!".into()),
+    );
+    StateTree::new_from_root(db.clone(), ts.parent_state()).with_context(|| {
+        format!(
+            "State root {} of the head tipset is unreachable",
+            ts.parent_state()
+        )
+    })?;
+    pb.finish_with_message("✅ verified!");
     Ok(())
 }
 
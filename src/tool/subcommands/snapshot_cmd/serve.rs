@@ -0,0 +1,355 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Static HTTP server for a directory of snapshots.
+//!
+//! - `GET /` lists the snapshots of the directory as JSON.
+//! - `GET /<snapshot>` downloads a snapshot. Single byte ranges are supported,
+//!   so that interrupted downloads can be resumed.
+//! - `GET /<checksum>` returns the SHA-256 checksum of a snapshot, in the
+//!   `sha256sum` format of `forest-cli snapshot export`. Checksum files written
+//!   next to the snapshots are served as is, otherwise checksums are computed on
+//!   the first request and cached.
+
+use std::io::{self, Read as _, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use ahash::HashMap;
+use anyhow::Context as _;
+use axum::{
+    body::Body,
+    extract::{self, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use http_range_header::parse_range_header;
+use parking_lot::Mutex;
+use serde::Serialize;
+use sha2::{Digest as _, Sha256};
+use tokio::io::{AsyncReadExt as _, AsyncSeekExt as _};
+use tokio::net::TcpListener;
+use tokio_util::io::ReaderStream;
+use tracing::info;
+
+const SNAPSHOT_EXTENSIONS: [&str; 2] = [".car", ".car.zst"];
+
+struct ServerState {
+    directory: PathBuf,
+    /// Computed checksums, along with the modification time of the snapshot
+    /// when they were computed.
+    checksums: Mutex<HashMap<PathBuf, (SystemTime, String)>>,
+}
+
+/// Entry of the snapshot listing.
+#[derive(Debug, Serialize)]
+struct SnapshotEntry {
+    name: String,
+    size: u64,
+    /// Modification time, in RFC 3339 format.
+    modified: String,
+    url: String,
+    checksum_url: String,
+    /// SHA-256 checksum, if already known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+}
+
+/// Serves the snapshots of `directory` on `listener`, until the process is
+/// stopped.
+pub async fn serve(directory: PathBuf, listener: TcpListener) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        directory.is_dir(),
+        "{} is not a directory",
+        directory.display()
+    );
+    info!(
+        "Serving snapshots of {} on http://{}",
+        directory.display(),
+        listener.local_addr()?
+    );
+    let state = Arc::new(ServerState {
+        directory,
+        checksums: Default::default(),
+    });
+    let app = Router::new()
+        .route("/", get(list_snapshots))
+        .route("/:name", get(get_file))
+        .with_state(state);
+    axum::serve(listener, app.into_make_service()).await?;
+    Ok(())
+}
+
+/// Name of the checksum file of `snapshot`.
+fn checksum_name(snapshot: &str) -> String {
+    Path::new(snapshot)
+        .with_extension("sha256sum")
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Lists the snapshots in `directory`, sorted by name.
+fn snapshots(directory: &Path) -> io::Result<Vec<(String, std::fs::Metadata)>> {
+    let mut snapshots = vec![];
+    for entry in std::fs::read_dir(directory)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if metadata.is_file() && SNAPSHOT_EXTENSIONS.iter().any(|ext| name.ends_with(ext)) {
+            snapshots.push((name, metadata));
+        }
+    }
+    snapshots.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(snapshots)
+}
+
+impl ServerState {
+    fn cached_checksum(&self, path: &Path, modified: SystemTime) -> Option<String> {
+        self.checksums
+            .lock()
+            .get(path)
+            .filter(|(cached_modified, _)| *cached_modified == modified)
+            .map(|(_, checksum)| checksum.clone())
+    }
+
+    /// Returns the contents of the checksum file of `snapshot`.
+    async fn checksum_file(
+        &self,
+        snapshot: &str,
+        metadata: &std::fs::Metadata,
+    ) -> io::Result<String> {
+        let checksum_path = self.directory.join(checksum_name(snapshot));
+        if checksum_path.is_file() {
+            return tokio::fs::read_to_string(checksum_path).await;
+        }
+
+        let path = self.directory.join(snapshot);
+        let modified = metadata.modified()?;
+        let checksum = match self.cached_checksum(&path, modified) {
+            Some(checksum) => checksum,
+            None => {
+                info!("Computing the checksum of {snapshot}");
+                let checksum = tokio::task::spawn_blocking({
+                    let path = path.clone();
+                    move || sha256_file(&path)
+                })
+                .await??;
+                self.checksums
+                    .lock()
+                    .insert(path, (modified, checksum.clone()));
+                checksum
+            }
+        };
+        Ok(format!("{checksum} {snapshot}\n"))
+    }
+}
+
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 20];
+    loop {
+        match file.read(&mut buffer)? {
+            0 => break,
+            n => hasher.update(&buffer[..n]),
+        }
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn internal_error(error: impl std::fmt::Display) -> Response {
+    (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()).into_response()
+}
+
+async fn list_snapshots(State(state): State<Arc<ServerState>>) -> Response {
+    let snapshots = match snapshots(&state.directory) {
+        Ok(snapshots) => snapshots,
+        Err(e) => return internal_error(e),
+    };
+    let entries: Vec<SnapshotEntry> = snapshots
+        .into_iter()
+        .map(|(name, metadata)| {
+            let path = state.directory.join(&name);
+            let modified = metadata.modified().ok();
+            SnapshotEntry {
+                size: metadata.len(),
+                modified: modified
+                    .map(|modified| chrono::DateTime::<chrono::Utc>::from(modified).to_rfc3339())
+                    .unwrap_or_default(),
+                url: format!("/{name}"),
+                checksum_url: format!("/{}", checksum_name(&name)),
+                sha256: modified.and_then(|modified| state.cached_checksum(&path, modified)),
+                name,
+            }
+        })
+        .collect();
+    Json(entries).into_response()
+}
+
+async fn get_file(
+    State(state): State<Arc<ServerState>>,
+    extract::Path(name): extract::Path<String>,
+    headers: HeaderMap,
+) -> Response {
+    let snapshots = match snapshots(&state.directory) {
+        Ok(snapshots) => snapshots,
+        Err(e) => return internal_error(e),
+    };
+    // Only files of the listing are served, so requests can't escape the
+    // directory.
+    if let Some((_, metadata)) = snapshots.iter().find(|(snapshot, _)| *snapshot == name) {
+        return match serve_snapshot(&state.directory.join(&name), metadata.len(), &headers).await {
+            Ok(response) => response,
+            Err(e) => internal_error(e),
+        };
+    }
+    if let Some((snapshot, metadata)) = snapshots
+        .iter()
+        .find(|(snapshot, _)| checksum_name(snapshot) == name)
+    {
+        return match state.checksum_file(snapshot, metadata).await {
+            Ok(checksum) => checksum.into_response(),
+            Err(e) => internal_error(e),
+        };
+    }
+    StatusCode::NOT_FOUND.into_response()
+}
+
+/// Byte range requested by `headers`, or `None` for the whole file. Only single
+/// ranges are honored, other requests get the whole file.
+fn requested_range(
+    headers: &HeaderMap,
+    len: u64,
+) -> Result<Option<std::ops::RangeInclusive<u64>>, ()> {
+    let Some(range) = headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return Ok(None);
+    };
+    let ranges = parse_range_header(range)
+        .map_err(|_| ())?
+        .validate(len)
+        .map_err(|_| ())?;
+    match ranges.as_slice() {
+        [range] => Ok(Some(range.clone())),
+        _ => Ok(None),
+    }
+}
+
+async fn serve_snapshot(path: &Path, len: u64, headers: &HeaderMap) -> anyhow::Result<Response> {
+    let range = match requested_range(headers, len) {
+        Ok(range) => range,
+        Err(()) => {
+            return Ok((
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(header::CONTENT_RANGE, format!("bytes */{len}"))],
+            )
+                .into_response())
+        }
+    };
+
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    let (status, start, body_len) = match &range {
+        Some(range) => (
+            StatusCode::PARTIAL_CONTENT,
+            *range.start(),
+            range.end() - range.start() + 1,
+        ),
+        None => (StatusCode::OK, 0, len),
+    };
+    file.seek(SeekFrom::Start(start)).await?;
+    let body = Body::from_stream(ReaderStream::new(file.take(body_len)));
+
+    let mut response = (status, body).into_response();
+    let response_headers = response.headers_mut();
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(body_len));
+    response_headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/octet-stream"),
+    );
+    if let Some(range) = range {
+        response_headers.insert(
+            header::CONTENT_RANGE,
+            HeaderValue::from_str(&format!("bytes {}-{}/{len}", range.start(), range.end()))?,
+        );
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn start_server(directory: PathBuf) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(directory, listener));
+        format!("http://{addr}")
+    }
+
+    #[tokio::test]
+    async fn serves_ranges_checksums_and_listing() {
+        let directory = tempfile::tempdir().unwrap();
+        let data: Vec<u8> = (0..=255).collect();
+        std::fs::write(directory.path().join("snapshot.car.zst"), &data).unwrap();
+        std::fs::write(directory.path().join("notes.txt"), b"not a snapshot").unwrap();
+        let url = start_server(directory.path().to_path_buf()).await;
+        let client = reqwest::Client::new();
+
+        let listing: serde_json::Value =
+            client.get(&url).send().await.unwrap().json().await.unwrap();
+        assert_eq!(listing.as_array().unwrap().len(), 1);
+        assert_eq!(listing[0]["name"], "snapshot.car.zst");
+        assert_eq!(listing[0]["size"], 256);
+
+        let response = client
+            .get(format!("{url}/snapshot.car.zst"))
+            .header("Range", "bytes=16-31")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 206);
+        assert_eq!(response.bytes().await.unwrap(), data[16..32]);
+
+        let response = client
+            .get(format!("{url}/snapshot.car.zst"))
+            .header("Range", "bytes=300-")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 416);
+
+        let full = client
+            .get(format!("{url}/snapshot.car.zst"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(full.status().as_u16(), 200);
+        assert_eq!(full.bytes().await.unwrap(), data);
+
+        let checksum = client
+            .get(format!("{url}/snapshot.car.sha256sum"))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(
+            checksum,
+            format!("{} snapshot.car.zst\n", hex::encode(Sha256::digest(&data)))
+        );
+
+        let not_found = client.get(format!("{url}/notes.txt")).send().await.unwrap();
+        assert_eq!(not_found.status().as_u16(), 404);
+    }
+}
@@ -0,0 +1,119 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::chain::index::{ChainIndex, ResolveNullTipset};
+use crate::db::car::ManyCar;
+use crate::shim::address::Address;
+use crate::shim::clock::ChainEpoch;
+use crate::statediff::{print_actor_changes, print_actor_state_diff};
+use anyhow::Context as _;
+use cid::Cid;
+use clap::Subcommand;
+use fvm_ipld_blockstore::Blockstore;
+use std::path::PathBuf;
+
+#[derive(Debug, Subcommand)]
+pub enum StateCommands {
+    /// Show the actors that were added, removed, or modified between two
+    /// states, with their balance, sequence and head changes.
+    Diff {
+        /// State to compare against, either an epoch or a state-root CID. The
+        /// state of an epoch is the parent state of its tipset.
+        from: StateRef,
+        /// Compared state, either an epoch or a state-root CID.
+        to: StateRef,
+        /// Snapshot input paths. Supports `.car`, `.car.zst`, and `.forest.car.zst`.
+        #[arg(long, required = true, num_args = 1..)]
+        snapshot_files: Vec<PathBuf>,
+        /// Only show the diff of the resolved state of this actor, including
+        /// its HAMTs and AMTs.
+        #[arg(long)]
+        actor: Option<Address>,
+        /// Depth of the resolution of the actor state. Differences below this
+        /// depth are shown as different CIDs.
+        #[arg(long, requires = "actor")]
+        depth: Option<u64>,
+    },
+}
+
+/// A state tree, identified by an epoch or by its root.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateRef {
+    Epoch(ChainEpoch),
+    Root(Cid),
+}
+
+impl FromStr for StateRef {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse() {
+            Ok(epoch) => Ok(StateRef::Epoch(epoch)),
+            Err(_) => {
+                Ok(StateRef::Root(s.parse().with_context(|| {
+                    format!("{s} is neither an epoch nor a CID")
+                })?))
+            }
+        }
+    }
+}
+
+impl StateRef {
+    fn resolve(self, store: &Arc<ManyCar>) -> anyhow::Result<Cid> {
+        let root = match self {
+            StateRef::Root(root) => root,
+            StateRef::Epoch(epoch) => {
+                let tipset = ChainIndex::new(Arc::clone(store))
+                    .tipset_by_height(
+                        epoch,
+                        Arc::new(store.heaviest_tipset()?),
+                        ResolveNullTipset::TakeOlder,
+                    )
+                    .with_context(|| format!("unable to get a tipset at epoch {epoch}"))?;
+                *tipset.parent_state()
+            }
+        };
+        anyhow::ensure!(store.has(&root)?, "state-root {root} is missing");
+        Ok(root)
+    }
+}
+
+impl StateCommands {
+    pub async fn run(self) -> anyhow::Result<()> {
+        match self {
+            Self::Diff {
+                from,
+                to,
+                snapshot_files,
+                actor,
+                depth,
+            } => {
+                let store = Arc::new(ManyCar::try_from(snapshot_files)?);
+                let (from, to) = (from.resolve(&store)?, to.resolve(&store)?);
+                match actor {
+                    Some(actor) => print_actor_state_diff(&store, &from, &to, &actor, depth),
+                    None => print_actor_changes(&store, &from, &to),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_state_ref() {
+        assert_eq!("42".parse::<StateRef>().unwrap(), StateRef::Epoch(42));
+        let cid = "bafy2bzacecyaggy24wol5ruvs6qm73gjibs2l2iyhcqmvi7r7a4ph7zx3yqd4";
+        assert_eq!(
+            cid.parse::<StateRef>().unwrap(),
+            StateRef::Root(cid.parse().unwrap())
+        );
+        assert!("head".parse::<StateRef>().is_err());
+    }
+}
@@ -1,22 +1,50 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use crate::networks::generate_actor_bundle;
+use crate::cli_shared::{chain_path, read_config};
+use crate::daemon::bundle::{load_actor_bundle_sources, load_actor_bundles};
+use crate::db::db_engine::{db_root, open_db};
+use crate::networks::{generate_actor_bundle, NetworkChain};
 use std::path::PathBuf;
 
 #[derive(Debug, clap::Subcommand)]
 pub enum StateMigrationCommands {
-    /// Generate a merged actor bundle from the hard-coded sources in forest
+    /// Generate a merged actor bundle from the hard-coded sources in forest,
+    /// or prefetch the bundles of a network into the node database
     ActorBundle {
         #[arg(default_value = "actor_bundles.car.zst")]
         output: PathBuf,
+        /// Download and verify the bundles of the network, including those
+        /// of the configuration, into the node database instead. The node
+        /// must be stopped.
+        #[arg(long)]
+        prefetch: bool,
+        /// Optional TOML file containing forest daemon configuration
+        #[arg(short, long, requires = "prefetch")]
+        config: Option<String>,
+        /// Optional chain, will override the chain section of configuration file if used
+        #[arg(long, requires = "prefetch")]
+        chain: Option<NetworkChain>,
     },
 }
 
 impl StateMigrationCommands {
     pub async fn run(self) -> anyhow::Result<()> {
         match self {
-            Self::ActorBundle { output } => {
+            Self::ActorBundle {
+                output: _,
+                prefetch: true,
+                config,
+                chain,
+            } => {
+                let (_, config) = read_config(&config, &chain)?;
+                let db = open_db(db_root(&chain_path(&config))?, config.db_config())?;
+                load_actor_bundles(&db, &config.chain).await?;
+                load_actor_bundle_sources(&db, &config.client.actor_bundles).await?;
+                println!("Prefetched the actor bundles of {}", config.chain);
+                Ok(())
+            }
+            Self::ActorBundle { output, .. } => {
                 generate_actor_bundle(&output).await?;
                 println!("Wrote the actors bundle to {}", output.display());
                 Ok(())
@@ -3,6 +3,7 @@
 
 mod mmap;
 pub mod progress_log;
+mod reader_checksum;
 mod tempfile;
 mod writer_checksum;
 
@@ -14,6 +15,7 @@ use std::{
 
 pub use mmap::EitherMmapOrRandomAccessFile;
 pub use progress_log::{WithProgress, WithProgressRaw};
+pub use reader_checksum::AsyncReaderWithChecksum;
 pub use writer_checksum::*;
 
 /// Restricts permissions on a file to user-only: 0600
@@ -0,0 +1,68 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+use std::{pin::Pin, task::Poll};
+
+use digest::{Digest, Output};
+use pin_project_lite::pin_project;
+use tokio::io::{AsyncRead, ReadBuf};
+
+pin_project! {
+    /// Wrapper `AsyncRead` implementation that calculates the checksum of the bytes read on the
+    /// fly, e.g. to verify a download while it is being processed.
+    pub struct AsyncReaderWithChecksum<D, R> {
+        #[pin]
+        inner: R,
+        hasher: D,
+    }
+}
+
+impl<D: Digest, R: AsyncRead> AsyncRead for AsyncReaderWithChecksum<D, R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.project();
+        let filled = buf.filled().len();
+        let r = this.inner.poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = r {
+            this.hasher.update(&buf.filled()[filled..]);
+        }
+        r
+    }
+}
+
+impl<D: Digest, R> AsyncReaderWithChecksum<D, R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            inner: reader,
+            hasher: D::new(),
+        }
+    }
+
+    /// Returns the checksum of the bytes read so far.
+    pub fn finalize(self) -> Output<D> {
+        self.hasher.finalize()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use sha2::Sha256;
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn checksum_of_read_bytes() {
+        let data = "cthulhuazathothdagon";
+        let mut reader = AsyncReaderWithChecksum::<Sha256, _>::new(data.as_bytes());
+        let mut read = String::new();
+        reader.read_to_string(&mut read).await.unwrap();
+        assert_eq!(read, data);
+        assert_eq!(
+            "3386191dc5c285074c3827452f4e3b685e3253f5b9ca7c4c2bb3f44d1263aef1",
+            format!("{:x}", reader.finalize())
+        );
+    }
+}
@@ -0,0 +1,304 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Retry policy of the outbound HTTP requests of the node, e.g. to the `drand` servers or to
+//! download snapshots.
+//!
+//! Failed requests are retried with an exponential backoff: the delay between two attempts doubles
+//! after each attempt, up to a maximum, and is shortened by a random fraction (jitter) so that
+//! nodes don't retry in lockstep. Each [`OutboundService`] has its own [`RetryPolicy`], which
+//! also bounds the total time spent on a request. The policies are set in the
+//! `[network.outbound]` section of the configuration.
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use crate::metrics::labels;
+use once_cell::sync::{Lazy, OnceCell};
+use prometheus::core::{AtomicU64, GenericCounterVec, Opts};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+static POLICIES: OnceCell<OutboundConfig> = OnceCell::new();
+
+static OUTBOUND_ATTEMPTS: Lazy<Box<GenericCounterVec<AtomicU64>>> = Lazy::new(|| {
+    let outbound_attempts = Box::new(
+        GenericCounterVec::<AtomicU64>::new(
+            Opts::new(
+                "outbound_attempts",
+                "Number of outbound HTTP requests attempted, by service",
+            ),
+            &[labels::SERVICE],
+        )
+        .expect("Defining the outbound_attempts metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(outbound_attempts.clone())
+        .expect("Registering the outbound_attempts metric with the metrics registry must succeed");
+    outbound_attempts
+});
+
+static OUTBOUND_FAILURES: Lazy<Box<GenericCounterVec<AtomicU64>>> = Lazy::new(|| {
+    let outbound_failures = Box::new(
+        GenericCounterVec::<AtomicU64>::new(
+            Opts::new(
+                "outbound_failures",
+                "Number of outbound HTTP requests that failed after exhausting their retries, by service",
+            ),
+            &[labels::SERVICE],
+        )
+        .expect("Defining the outbound_failures metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(outbound_failures.clone())
+        .expect("Registering the outbound_failures metric with the metrics registry must succeed");
+    outbound_failures
+});
+
+/// Services of the node issuing outbound HTTP requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum OutboundService {
+    /// Randomness beacon entries.
+    Drand,
+    /// Snapshot downloads.
+    Snapshot,
+    /// Actor bundle downloads.
+    ActorBundle,
+    /// Proof parameter downloads.
+    ProofParams,
+}
+
+/// Retry policy of an [`OutboundService`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts of a request, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry, in milliseconds.
+    pub initial_delay_ms: u64,
+    /// Maximum delay between two attempts, in milliseconds.
+    pub max_delay_ms: u64,
+    /// Percentage of each delay that is randomly cut off.
+    pub jitter_percent: u8,
+    /// Maximum time spent on a request, retries included, in seconds. No retry is attempted past
+    /// this budget.
+    pub budget_secs: Option<u64>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_delay_ms: 500,
+            max_delay_ms: 30_000,
+            jitter_percent: 50,
+            budget_secs: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the retry following attempt `attempt`, counting from 1, before jitter.
+    fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .initial_delay_ms
+            .saturating_mul(1u64 << attempt.saturating_sub(1).min(32));
+        Duration::from_millis(delay.min(self.max_delay_ms))
+    }
+
+    fn jitter(&self, delay: Duration, rng: &mut impl Rng) -> Duration {
+        let jitter_percent = u32::from(self.jitter_percent.min(100));
+        delay - delay * rng.gen_range(0..=jitter_percent) / 100
+    }
+
+    fn budget(&self) -> Option<Duration> {
+        self.budget_secs.map(Duration::from_secs)
+    }
+}
+
+/// Retry policies of the outbound HTTP requests, by [`OutboundService`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(default)]
+pub struct OutboundConfig {
+    pub drand: RetryPolicy,
+    pub snapshot: RetryPolicy,
+    pub actor_bundle: RetryPolicy,
+    pub proof_params: RetryPolicy,
+}
+
+impl Default for OutboundConfig {
+    fn default() -> Self {
+        Self {
+            // Beacon entries are needed to validate blocks, keep trying for a while.
+            drand: RetryPolicy {
+                max_attempts: 20,
+                initial_delay_ms: 500,
+                max_delay_ms: 60_000,
+                budget_secs: Some(15 * 60),
+                ..Default::default()
+            },
+            snapshot: RetryPolicy {
+                initial_delay_ms: 1_000,
+                ..Default::default()
+            },
+            actor_bundle: RetryPolicy::default(),
+            proof_params: RetryPolicy {
+                max_attempts: 10,
+                budget_secs: Some(15 * 60),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+impl OutboundConfig {
+    fn policy(&self, service: OutboundService) -> &RetryPolicy {
+        match service {
+            OutboundService::Drand => &self.drand,
+            OutboundService::Snapshot => &self.snapshot,
+            OutboundService::ActorBundle => &self.actor_bundle,
+            OutboundService::ProofParams => &self.proof_params,
+        }
+    }
+}
+
+/// Sets the retry policies of the outbound HTTP requests. The default policies are used if this
+/// isn't called.
+pub fn init(config: OutboundConfig) -> anyhow::Result<()> {
+    POLICIES
+        .set(config)
+        .map_err(|_| anyhow::anyhow!("the outbound retry policies are already initialized"))
+}
+
+fn policies() -> &'static OutboundConfig {
+    POLICIES.get_or_init(OutboundConfig::default)
+}
+
+/// Keeps running the future created by `make_fut` until it succeeds, or the retry policy of
+/// `service` gives up. The error of the last attempt is returned then.
+pub async fn retry<F, T>(service: OutboundService, make_fut: impl FnMut() -> F) -> anyhow::Result<T>
+where
+    F: Future<Output = anyhow::Result<T>>,
+{
+    retry_with_policy(service, policies().policy(service), make_fut).await
+}
+
+async fn retry_with_policy<F, T>(
+    service: OutboundService,
+    policy: &RetryPolicy,
+    mut make_fut: impl FnMut() -> F,
+) -> anyhow::Result<T>
+where
+    F: Future<Output = anyhow::Result<T>>,
+{
+    let service_label = service.to_string();
+    let start = Instant::now();
+    let max_attempts = policy.max_attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        OUTBOUND_ATTEMPTS.with_label_values(&[&service_label]).inc();
+        let err = match make_fut().await {
+            Ok(ok) => return Ok(ok),
+            Err(err) => err,
+        };
+        let delay = policy.jitter(policy.delay(attempt), &mut rand::thread_rng());
+        let over_budget = policy
+            .budget()
+            .is_some_and(|budget| start.elapsed() + delay > budget);
+        if attempt >= max_attempts || over_budget {
+            OUTBOUND_FAILURES.with_label_values(&[&service_label]).inc();
+            return Err(err.context(format!("{service} request failed after {attempt} attempts")));
+        }
+        warn!("{service} request failed, retrying in {delay:?} (attempt {attempt}/{max_attempts}): {err:#}");
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering::SeqCst};
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_delay_ms: 1,
+            max_delay_ms: 4,
+            jitter_percent: 0,
+            budget_secs: None,
+        }
+    }
+
+    #[test]
+    fn delays_grow_exponentially_up_to_max_delay() {
+        let policy = policy(10);
+        let delays = (1..=5)
+            .map(|attempt| policy.delay(attempt).as_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, vec![1, 2, 4, 4, 4]);
+        // Large attempt numbers don't overflow.
+        assert_eq!(policy.delay(u32::MAX), Duration::from_millis(4));
+    }
+
+    #[test]
+    fn jitter_shortens_delays() {
+        let policy = RetryPolicy {
+            jitter_percent: 50,
+            ..Default::default()
+        };
+        let delay = Duration::from_secs(10);
+        for _ in 0..100 {
+            let jittered = policy.jitter(delay, &mut rand::thread_rng());
+            assert!(jittered <= delay && jittered >= delay / 2);
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_until_success() {
+        let attempts = AtomicU32::new(0);
+        let res = retry_with_policy(OutboundService::Drand, &policy(5), || async {
+            match attempts.fetch_add(1, SeqCst) {
+                0 | 1 => anyhow::bail!("Cthulhu fhtagn"),
+                _ => Ok(42),
+            }
+        })
+        .await;
+        assert_eq!(res.unwrap(), 42);
+        assert_eq!(attempts.load(SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let attempts = AtomicU32::new(0);
+        let res = retry_with_policy(OutboundService::Snapshot, &policy(3), || async {
+            attempts.fetch_add(1, SeqCst);
+            anyhow::bail!("Cthulhu fhtagn") as anyhow::Result<()>
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(attempts.load(SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_past_budget() {
+        let policy = RetryPolicy {
+            initial_delay_ms: 2_000,
+            max_delay_ms: 2_000,
+            budget_secs: Some(1),
+            ..policy(10)
+        };
+        let attempts = AtomicU32::new(0);
+        let res = retry_with_policy(OutboundService::ActorBundle, &policy, || async {
+            attempts.fetch_add(1, SeqCst);
+            anyhow::bail!("Cthulhu fhtagn") as anyhow::Result<()>
+        })
+        .await;
+        assert!(res.is_err());
+        assert_eq!(attempts.load(SeqCst), 1);
+    }
+}
@@ -0,0 +1,53 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use once_cell::sync::Lazy;
+use prometheus::core::{AtomicU64, GenericCounter};
+
+pub static PROOF_PARAMS_DOWNLOADED_BYTES: Lazy<Box<GenericCounter<AtomicU64>>> = Lazy::new(|| {
+    let proof_params_downloaded_bytes = Box::new(
+        GenericCounter::<AtomicU64>::new(
+            "proof_params_downloaded_bytes",
+            "Number of bytes of proof parameter files downloaded",
+        )
+        .expect("Defining the proof_params_downloaded_bytes metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(proof_params_downloaded_bytes.clone())
+        .expect(
+            "Registering the proof_params_downloaded_bytes metric with the metrics registry must succeed",
+        );
+    proof_params_downloaded_bytes
+});
+
+pub static PROOF_PARAMS_DOWNLOADED_FILES: Lazy<Box<GenericCounter<AtomicU64>>> = Lazy::new(|| {
+    let proof_params_downloaded_files = Box::new(
+        GenericCounter::<AtomicU64>::new(
+            "proof_params_downloaded_files",
+            "Number of proof parameter files downloaded and verified",
+        )
+        .expect("Defining the proof_params_downloaded_files metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(proof_params_downloaded_files.clone())
+        .expect(
+            "Registering the proof_params_downloaded_files metric with the metrics registry must succeed",
+        );
+    proof_params_downloaded_files
+});
+
+pub static PROOF_PARAMS_CHECKSUM_FAILURES: Lazy<Box<GenericCounter<AtomicU64>>> = Lazy::new(|| {
+    let proof_params_checksum_failures = Box::new(
+        GenericCounter::<AtomicU64>::new(
+            "proof_params_checksum_failures",
+            "Number of proof parameter files whose checksum did not match the manifest",
+        )
+        .expect("Defining the proof_params_checksum_failures metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(proof_params_checksum_failures.clone())
+        .expect(
+            "Registering the proof_params_checksum_failures metric with the metrics registry must succeed",
+        );
+    proof_params_checksum_failures
+});
@@ -1,4 +1,6 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+mod metrics;
 pub mod paramfetch;
+pub mod verification;
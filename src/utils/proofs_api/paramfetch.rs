@@ -2,21 +2,28 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use std::{
+    ffi::OsString,
     fs::File as SyncFile,
     io::{self, copy as sync_copy, BufReader as SyncBufReader, ErrorKind},
     path::{Path, PathBuf},
-    str::FromStr,
     sync::Arc,
 };
 
-use crate::{shim::sector::SectorSize, utils::net::download_ipfs_file_trustlessly};
+use super::metrics;
+use crate::shim::sector::SectorSize;
+use crate::utils::io::WithProgress;
+use crate::utils::net::global_http_client;
+use crate::utils::outbound::{retry, OutboundService};
 use ahash::HashMap;
-use backoff::{future::retry, ExponentialBackoff};
 use blake2b_simd::{Hash, State as Blake2b};
-use cid::Cid;
+use futures::TryStreamExt;
+use reqwest::{header::RANGE, StatusCode};
 use serde::{Deserialize, Serialize};
-use tokio::fs::{self};
+use tap::Pipe;
+use tokio::fs::{self, OpenOptions};
+use tokio_util::io::StreamReader;
 use tracing::{debug, error, info, warn};
+use url::Url;
 
 const GATEWAY: &str = "https://proofs.filecoin.io/ipfs/";
 const PARAM_DIR: &str = "filecoin-proof-parameters";
@@ -74,18 +81,36 @@ pub async fn ensure_params_downloaded() -> anyhow::Result<()> {
     if data_dir.is_empty() {
         anyhow::bail!("Proof parameter data dir is not set");
     }
-    get_params_default(Path::new(&data_dir), SectorSizeOpt::Keys, false).await?;
+    get_params_default(Path::new(&data_dir), SectorSizeOpt::Keys, false, &[]).await?;
 
     Ok(())
 }
 
+/// Gateways the parameter files are downloaded from when no mirror is given: the comma-separated
+/// list of the `IPFS_GATEWAY` environment variable, or the Filecoin proofs gateway.
+fn default_mirrors() -> anyhow::Result<Vec<Url>> {
+    let gateways = std::env::var(GATEWAY_ENV).unwrap_or_else(|_| GATEWAY.to_owned());
+    parse_mirrors(&gateways)
+}
+
+fn parse_mirrors(gateways: &str) -> anyhow::Result<Vec<Url>> {
+    gateways
+        .split(',')
+        .map(str::trim)
+        .filter(|gateway| !gateway.is_empty())
+        .map(|gateway| Ok(Url::parse(gateway)?))
+        .collect()
+}
+
 /// Get proofs parameters and all verification keys for a given sector size
-/// given a parameter JSON manifest.
+/// given a parameter JSON manifest. The files are downloaded from the first of the `mirrors`
+/// that serves them, or from the default mirrors if none is given.
 pub async fn get_params(
     data_dir: &Path,
     param_json: &str,
     storage_size: SectorSizeOpt,
     dry_run: bool,
+    mirrors: &[Url],
 ) -> Result<(), anyhow::Error> {
     // Just print out the parameters download directory path and exit.
     if dry_run {
@@ -96,6 +121,10 @@ pub async fn get_params(
     fs::create_dir_all(param_dir(data_dir)).await?;
 
     let params: ParameterMap = serde_json::from_str(param_json)?;
+    let mirrors: Arc<[Url]> = match mirrors {
+        [] => default_mirrors()?.into(),
+        mirrors => mirrors.into(),
+    };
     let mut tasks = Vec::with_capacity(params.len());
 
     params
@@ -109,8 +138,9 @@ pub async fn get_params(
         })
         .for_each(|(name, info)| {
             let data_dir_clone = data_dir.to_owned();
+            let mirrors = mirrors.clone();
             tasks.push(tokio::task::spawn(async move {
-                fetch_verify_params(&data_dir_clone, &name, Arc::new(info))
+                fetch_verify_params(&data_dir_clone, &name, Arc::new(info), &mirrors)
                     .await
                     .map_err(|err| {
                         error!("Error fetching param file {name}: {err}");
@@ -147,14 +177,16 @@ pub async fn get_params_default(
     data_dir: &Path,
     storage_size: SectorSizeOpt,
     dry_run: bool,
+    mirrors: &[Url],
 ) -> Result<(), anyhow::Error> {
-    get_params(data_dir, DEFAULT_PARAMETERS, storage_size, dry_run).await
+    get_params(data_dir, DEFAULT_PARAMETERS, storage_size, dry_run, mirrors).await
 }
 
 async fn fetch_verify_params(
     data_dir: &Path,
     name: &str,
     info: Arc<ParameterData>,
+    mirrors: &[Url],
 ) -> Result<(), anyhow::Error> {
     let path: PathBuf = param_dir(data_dir).join(name);
 
@@ -167,22 +199,92 @@ async fn fetch_verify_params(
         }
     }
 
-    fetch_params(&path, &info).await?;
+    fetch_params(&path, &info, mirrors).await?;
 
-    check_file(&path, &info).await?;
+    if let Err(e) = check_file(&path, &info).await {
+        // Don't keep a corrupted file around, so that it is downloaded again by the next run.
+        let _ = fs::remove_file(&path).await;
+        return Err(e.into());
+    }
+    metrics::PROOF_PARAMS_DOWNLOADED_FILES.inc();
     Ok(())
 }
 
-async fn fetch_params(path: &Path, info: &ParameterData) -> anyhow::Result<()> {
-    let cid = Cid::from_str(&info.cid)?;
-    let gw = std::env::var(GATEWAY_ENV).unwrap_or_else(|_| GATEWAY.to_owned());
-    info!("Fetching param file {:?} from {}", path, gw);
-    let result = retry(ExponentialBackoff::default(), || async {
-        Ok(download_ipfs_file_trustlessly(&cid, Some(GATEWAY), path).await?)
-    })
-    .await;
-    debug!("Done fetching param file {:?} from {}", path, gw);
-    result
+/// Fetches the param file from the first mirror that serves it. A download interrupted on a mirror
+/// is resumed on the next one.
+async fn fetch_params(path: &Path, info: &ParameterData, mirrors: &[Url]) -> anyhow::Result<()> {
+    let mut last_error = None;
+    for mirror in mirrors {
+        let url = mirror.join(&info.cid)?;
+        info!("Fetching param file {:?} from {}", path, mirror);
+        match retry(OutboundService::ProofParams, || {
+            download_resumable(&url, path)
+        })
+        .await
+        {
+            Ok(()) => {
+                debug!("Done fetching param file {:?} from {}", path, mirror);
+                return Ok(());
+            }
+            Err(e) => {
+                warn!("Failed to fetch param file {:?} from {}: {e}", path, mirror);
+                last_error = Some(e);
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow::anyhow!("no mirror to fetch param files from")))
+}
+
+/// Downloads `url` to `path`. The bytes are written to a partial file next to `path` first, whose
+/// download is resumed with a range request when it was interrupted, e.g. by a previous run.
+async fn download_resumable(url: &Url, path: &Path) -> anyhow::Result<()> {
+    let partial = partial_path(path);
+    let offset = match fs::metadata(&partial).await {
+        Ok(metadata) => metadata.len(),
+        Err(_) => 0,
+    };
+    let mut request = global_http_client().get(url.clone());
+    if offset > 0 {
+        request = request.header(RANGE, format!("bytes={offset}-"));
+    }
+    let response = request.send().await?;
+    let mut file = match response.status() {
+        StatusCode::PARTIAL_CONTENT => {
+            debug!("Resuming download of {:?} at byte {offset}", path);
+            OpenOptions::new().append(true).open(&partial).await?
+        }
+        // The partial file is complete.
+        StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => {
+            fs::rename(&partial, path).await?;
+            return Ok(());
+        }
+        _ => {
+            response.error_for_status_ref()?;
+            fs::File::create(&partial).await?
+        }
+    };
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let remaining = response.content_length().unwrap_or_default();
+    let mut body = std::pin::pin!(WithProgress::wrap_async_read(
+        &format!("Downloading {name}"),
+        response
+            .bytes_stream()
+            .map_err(std::io::Error::other)
+            .pipe(StreamReader::new),
+        remaining,
+    )
+    .bytes());
+    let downloaded = tokio::io::copy(&mut body, &mut file).await?;
+    metrics::PROOF_PARAMS_DOWNLOADED_BYTES.inc_by(downloaded);
+    file.sync_all().await?;
+    fs::rename(&partial, path).await?;
+    Ok(())
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().map(OsString::from).unwrap_or_default();
+    file_name.push(".part");
+    path.with_file_name(file_name)
 }
 
 async fn check_file(path: &Path, info: &ParameterData) -> Result<(), io::Error> {
@@ -208,9 +310,35 @@ async fn check_file(path: &Path, info: &ParameterData) -> Result<(), io::Error>
         debug!("Parameter file {:?} is ok", path);
         Ok(())
     } else {
+        metrics::PROOF_PARAMS_CHECKSUM_FAILURES.inc();
         Err(io::Error::other(format!(
             "Checksum mismatch in param file {:?}. ({} != {})",
             path, str_sum, info.digest
         )))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mirrors_are_comma_separated() {
+        assert_eq!(
+            parse_mirrors("https://a.example/ipfs/, https://b.example/ipfs/,").unwrap(),
+            [
+                Url::parse("https://a.example/ipfs/").unwrap(),
+                Url::parse("https://b.example/ipfs/").unwrap(),
+            ]
+        );
+        assert!(parse_mirrors("not a url").is_err());
+    }
+
+    #[test]
+    fn partial_file_is_next_to_param_file() {
+        assert_eq!(
+            partial_path(Path::new("/params/v28-stacked-proof.vk")),
+            Path::new("/params/v28-stacked-proof.vk.part")
+        );
+    }
+}
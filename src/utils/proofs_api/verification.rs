@@ -0,0 +1,88 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Verification of seal and window `PoSt` proofs. The verification keys are read from the proof
+//! parameter cache, see [`super::paramfetch`].
+
+use std::collections::BTreeMap;
+
+use crate::rpc_api::data_types::{SealVerifyInfo, WindowPoStVerifyInfo};
+use crate::shim::{
+    crypto::{cid_to_data_commitment_v1, cid_to_replica_commitment_v1},
+    sector::RegisteredPoStProof,
+};
+use crate::utils::encoding::prover_id_from_u64;
+use anyhow::anyhow;
+use filecoin_proofs_api::{post, seal, PublicReplicaInfo, SectorId};
+use fvm_ipld_encoding::bytes_32;
+use rayon::prelude::*;
+use tracing::debug;
+
+/// Returns whether the seal proof of the sector is valid.
+pub fn verify_seal(info: &SealVerifyInfo) -> anyhow::Result<bool> {
+    let comm_r = cid_to_replica_commitment_v1(&info.sealed_cid).map_err(|e| anyhow!(e))?;
+    let comm_d = cid_to_data_commitment_v1(&info.unsealed_cid).map_err(|e| anyhow!(e))?;
+    seal::verify_seal(
+        info.seal_proof.try_into()?,
+        comm_r,
+        comm_d,
+        prover_id_from_u64(info.sector_id.miner),
+        SectorId::from(info.sector_id.number),
+        bytes_32(&info.randomness),
+        bytes_32(&info.interactive_randomness),
+        &info.proof,
+    )
+}
+
+/// Verifies the seal proofs in parallel. Proofs that cannot be verified, e.g. because of an
+/// unsupported proof type, are invalid.
+pub fn verify_seals(infos: &[SealVerifyInfo]) -> Vec<bool> {
+    infos
+        .par_iter()
+        .map(|info| {
+            verify_seal(info).unwrap_or_else(|e| {
+                debug!(
+                    "Failed to verify the seal of sector {} of miner {}: {e}",
+                    info.sector_id.number, info.sector_id.miner
+                );
+                false
+            })
+        })
+        .collect()
+}
+
+/// Verifies the window `PoSt` proofs of the challenged sectors of a deadline partition.
+pub fn verify_window_post(info: &WindowPoStVerifyInfo) -> anyhow::Result<bool> {
+    let mut rand = bytes_32(&info.randomness);
+    // Necessary to be valid bls12 381 element.
+    rand[31] &= 0x3f;
+
+    let replicas = info
+        .challenged_sectors
+        .iter()
+        .map(|sector| {
+            let comm_r =
+                cid_to_replica_commitment_v1(&sector.sealed_cid).map_err(|e| anyhow!(e))?;
+            let proof = sector
+                .proof
+                .registered_window_post_proof()
+                .map_err(|e| anyhow!(e))?;
+            Ok((
+                SectorId::from(sector.sector_number),
+                PublicReplicaInfo::new(RegisteredPoStProof::from(proof).try_into()?, comm_r),
+            ))
+        })
+        .collect::<anyhow::Result<BTreeMap<_, _>>>()?;
+    let proofs = info
+        .proofs
+        .iter()
+        .map(|proof| {
+            Ok((
+                RegisteredPoStProof::from(proof.post_proof).try_into()?,
+                proof.proof_bytes.as_slice(),
+            ))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    post::verify_window_post(&rand, &proofs, &replicas, prover_id_from_u64(info.prover))
+}
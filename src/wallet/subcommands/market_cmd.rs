@@ -0,0 +1,175 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::str::FromStr as _;
+
+use crate::blocks::TipsetKey;
+use crate::cli::humantoken::{self, TokenAmountPretty as _};
+use crate::rpc_client::ApiInfo;
+use crate::shim::{
+    address::{Address, StrictAddress},
+    econ::TokenAmount,
+    message::{Message, MethodNum},
+};
+use anyhow::Context as _;
+use clap::Subcommand;
+use dialoguer::{theme::ColorfulTheme, Confirm};
+use fvm_ipld_encoding::RawBytes;
+use serde_tuple::{self, Serialize_tuple};
+
+/// Method number of `AddBalance` on the storage market actor.
+const MARKET_ADD_BALANCE: MethodNum = 2;
+/// Method number of `WithdrawBalance` on the storage market actor.
+const MARKET_WITHDRAW_BALANCE: MethodNum = 3;
+
+#[derive(Serialize_tuple)]
+struct WithdrawBalanceParams {
+    provider_or_client: Address,
+    amount: TokenAmount,
+}
+
+/// Manage storage market escrow balances
+#[derive(Debug, Subcommand)]
+pub enum MarketCommands {
+    /// Add funds to the market escrow of an address
+    Add {
+        /// Wallet address to send the funds from (otherwise the default one
+        /// will be used)
+        #[arg(long)]
+        from: Option<String>,
+        /// Client or provider address to credit (defaults to the `from`
+        /// address)
+        #[arg(long)]
+        address: Option<String>,
+        /// Amount to add
+        #[arg(value_parser = humantoken::parse)]
+        amount: TokenAmount,
+        /// Send the message without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Withdraw funds from the market escrow of an address
+    Withdraw {
+        /// Wallet address to send the message from, which receives the funds
+        /// (otherwise the default one will be used)
+        #[arg(long)]
+        wallet: Option<String>,
+        /// Client or provider address to withdraw from (defaults to the
+        /// `wallet` address)
+        #[arg(long)]
+        address: Option<String>,
+        /// Amount to withdraw
+        #[arg(value_parser = humantoken::parse)]
+        amount: TokenAmount,
+        /// Send the message without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+impl MarketCommands {
+    pub async fn run(&self, api: ApiInfo) -> anyhow::Result<()> {
+        let message = match self {
+            Self::Add {
+                from,
+                address,
+                amount,
+                ..
+            } => {
+                let from = wallet_address(&api, from.as_deref()).await?;
+                let address = parse_address(address.as_deref())?.unwrap_or(from);
+                println!(
+                    "Adding {} to the market escrow of {address}",
+                    amount.pretty()
+                );
+                Message {
+                    from,
+                    to: Address::MARKET_ACTOR,
+                    value: amount.clone(),
+                    method_num: MARKET_ADD_BALANCE,
+                    params: RawBytes::serialize(address)?,
+                    ..Default::default()
+                }
+            }
+            Self::Withdraw {
+                wallet,
+                address,
+                amount,
+                ..
+            } => {
+                let from = wallet_address(&api, wallet.as_deref()).await?;
+                let address = parse_address(address.as_deref())?.unwrap_or(from);
+                println!(
+                    "Withdrawing {} from the market escrow of {address}",
+                    amount.pretty()
+                );
+                Message {
+                    from,
+                    to: Address::MARKET_ACTOR,
+                    method_num: MARKET_WITHDRAW_BALANCE,
+                    params: RawBytes::serialize(WithdrawBalanceParams {
+                        provider_or_client: address,
+                        amount: amount.clone(),
+                    })?,
+                    ..Default::default()
+                }
+            }
+        };
+
+        let message = api
+            .gas_estimate_message_gas(message, None, TipsetKey::default())
+            .await?;
+        let max_fee = &message.gas_fee_cap * message.gas_limit;
+        println!(
+            "Gas limit: {}, maximum fee: {}",
+            message.gas_limit,
+            max_fee.pretty()
+        );
+
+        let yes = match self {
+            Self::Add { yes, .. } | Self::Withdraw { yes, .. } => *yes,
+        };
+        if !yes && !confirm().await? {
+            println!("Aborted.");
+            return Ok(());
+        }
+
+        let signed_msg = api.mpool_push_message(message, None).await?;
+        println!("{}", signed_msg.cid()?);
+        Ok(())
+    }
+}
+
+/// Parses `address`, or falls back to the default wallet address.
+async fn wallet_address(api: &ApiInfo, address: Option<&str>) -> anyhow::Result<Address> {
+    match parse_address(address)? {
+        Some(address) => Ok(address),
+        None => Ok(Address::from_str(
+            &api.wallet_default_address()
+                .await?
+                .context("No default wallet address selected. Please set a default address.")?,
+        )?),
+    }
+}
+
+fn parse_address(address: Option<&str>) -> anyhow::Result<Option<Address>> {
+    address
+        .map(|address| {
+            let StrictAddress(address) = StrictAddress::from_str(address)
+                .with_context(|| format!("Invalid address: {address}"))?;
+            Ok(address)
+        })
+        .transpose()
+}
+
+async fn confirm() -> anyhow::Result<bool> {
+    Ok(tokio::task::spawn_blocking(|| {
+        Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt("Send the message?")
+            .default(false)
+            .interact()
+            // e.g not a tty (or some other error), so haven't got permission.
+            .unwrap_or(false)
+    })
+    .await?)
+}
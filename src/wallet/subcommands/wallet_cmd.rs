@@ -22,11 +22,13 @@ use num::BigInt;
 
 use crate::cli::humantoken::TokenAmountPretty as _;
 
+use super::market_cmd::MarketCommands;
+
 #[derive(Debug, Subcommand)]
 pub enum WalletCommands {
     /// Create a new wallet
     New {
-        /// The signature type to use. One of SECP256k1, or BLS
+        /// The signature type to use. One of SECP256k1, BLS or delegated
         #[arg(default_value = "secp256k1")]
         signature_type: String,
     },
@@ -96,6 +98,9 @@ pub enum WalletCommands {
         /// The address of the wallet to delete
         address: String,
     },
+    /// Manage storage market escrow balances
+    #[command(subcommand)]
+    Market(MarketCommands),
 }
 
 impl WalletCommands {
@@ -104,7 +109,11 @@ impl WalletCommands {
             Self::New { signature_type } => {
                 let signature_type = match signature_type.to_lowercase().as_str() {
                     "secp256k1" => SignatureType::Secp256k1,
-                    _ => SignatureType::Bls,
+                    "bls" => SignatureType::Bls,
+                    "delegated" => SignatureType::Delegated,
+                    _ => anyhow::bail!(
+                        "invalid signature type {signature_type}, expected one of SECP256k1, BLS or delegated"
+                    ),
                 };
 
                 let response = api.wallet_new(signature_type).await?;
@@ -248,6 +257,7 @@ impl WalletCommands {
                 println!("{response}");
                 Ok(())
             }
+            Self::Market(cmd) => cmd.run(api).await,
         }
     }
 }